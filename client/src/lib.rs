@@ -0,0 +1,183 @@
+//! A typed client for the proof job API of the raiko host.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), raiko_client::ClientError> {
+//! use raiko_client::{ProofRequest, RaikoClient};
+//!
+//! let client = RaikoClient::new("http://localhost:8080");
+//! let job = client
+//!     .submit(&ProofRequest::new("taiko_a7", 1000, "sgx"))
+//!     .await?;
+//! let job = client
+//!     .wait(job.id, std::time::Duration::from_secs(10))
+//!     .await?;
+//! println!("{:?}", job.proof);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use reqwest::{Method, Response};
+use serde::{de::DeserializeOwned, Serialize};
+
+pub use crate::{problem::*, types::*};
+
+mod problem;
+mod types;
+
+#[derive(thiserror::Error, Debug)]
+/// The errors of the requests to the host.
+pub enum ClientError {
+    /// The host could not be reached or sent an unexpected response.
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// The host rejected the request, e.g. with [ErrorCode::JobNotFound] for an unknown job.
+    #[error("The host returned {}: {0}", .0.status)]
+    Problem(Problem),
+
+    /// The host rejected the request without problem details, e.g. a proxy in front of it.
+    #[error("The host returned {status}: {message}")]
+    Api { status: u16, message: String },
+}
+
+/// A result of a request to the host.
+pub type ClientResult<T> = Result<T, ClientError>;
+
+/// A client for the `/v2/proof` endpoints of a raiko host.
+#[derive(Clone, Debug)]
+pub struct RaikoClient {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+}
+
+impl RaikoClient {
+    /// Create a client for the host at `url`, e.g. `http://localhost:8080`.
+    pub fn new(url: &str) -> Self {
+        Self::with_client(reqwest::Client::new(), url)
+    }
+
+    /// Create a client sending its requests with the given HTTP client, e.g. to set timeouts
+    /// or default headers.
+    pub fn with_client(client: reqwest::Client, url: &str) -> Self {
+        Self {
+            client,
+            url: url.trim_end_matches('/').to_string(),
+            api_key: None,
+        }
+    }
+
+    /// Authenticate the requests with the API key, for hosts that have keys configured.
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    /// Queue a proof job for the request.
+    pub async fn submit(&self, request: &ProofRequest) -> ClientResult<JobSummary> {
+        self.send(Method::POST, "/v2/proof", Some(request)).await
+    }
+
+    /// Get the status of the job.
+    pub async fn status(&self, id: JobId) -> ClientResult<JobSummary> {
+        self.send::<(), _>(Method::GET, &format!("/v2/proof/{id}/status"), None)
+            .await
+    }
+
+    /// Get the job, including the proof once it succeeded.
+    pub async fn job(&self, id: JobId) -> ClientResult<Job> {
+        self.send::<(), _>(Method::GET, &format!("/v2/proof/{id}"), None)
+            .await
+    }
+
+    /// Cancel a queued or running job.
+    pub async fn cancel(&self, id: JobId) -> ClientResult<JobSummary> {
+        self.send::<(), _>(Method::DELETE, &format!("/v2/proof/{id}"), None)
+            .await
+    }
+
+    /// Poll the status of the job every `interval` until it finished, then get the job.
+    pub async fn wait(&self, id: JobId, interval: Duration) -> ClientResult<Job> {
+        while !self.status(id).await?.status.is_terminal() {
+            tokio::time::sleep(interval).await;
+        }
+        self.job(id).await
+    }
+
+    async fn send<B: Serialize, T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> ClientResult<T> {
+        let mut request = self.client.request(method, format!("{}{path}", self.url));
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        let response = check_status(request.send().await?).await?;
+        Ok(response.json().await?)
+    }
+}
+
+/// Turn error responses into [ClientError::Problem], or [ClientError::Api] with the message
+/// of the response if it has no problem details.
+async fn check_status(response: Response) -> ClientResult<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let message = response.text().await.unwrap_or_default();
+    match serde_json::from_str::<Problem>(&message) {
+        Ok(problem) => Err(ClientError::Problem(problem)),
+        Err(_) => Err(ClientError::Api {
+            status: status.as_u16(),
+            message,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_proof_request_only_sends_set_fields() {
+        let mut request = ProofRequest::new("taiko_a7", 10, "sgx");
+        request
+            .prover_args
+            .insert("sgx".to_string(), json!({"instance_id": 1}));
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "network": "taiko_a7",
+                "block_number": 10,
+                "proof_type": "sgx",
+                "prover_args": {"sgx": {"instance_id": 1}},
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_job_summary() {
+        let summary: JobSummary = serde_json::from_value(json!({
+            "id": 3,
+            "status": "failed",
+            "error": "No BlockProposed event found for block 10",
+            "retries": 2,
+            "submission": null,
+            "created_at": 1700000000,
+            "updated_at": 1700000060,
+        }))
+        .unwrap();
+        assert_eq!(summary.status, JobStatus::Failed);
+        assert!(summary.status.is_terminal());
+        assert_eq!(summary.retries, 2);
+    }
+}
@@ -0,0 +1,215 @@
+//! The error responses of the host, RFC 7807 problem details with a machine-readable code.
+
+use serde::{Deserialize, Serialize};
+
+/// The content type of the error responses.
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+/// What went wrong with a request.
+pub enum ErrorCode {
+    /// The request is invalid in a way no other code describes.
+    InvalidRequest,
+    /// The body is not valid JSON or has a field of the wrong type.
+    MalformedBody,
+    /// The request has a field the endpoint does not know.
+    UnknownField,
+    /// A required field is neither in the request nor in the config of the host.
+    MissingField,
+    /// A field is not valid hex or has the wrong length.
+    InvalidHex,
+    /// The proof type is unknown or the host was built without it.
+    UnsupportedProofType,
+    /// The network is not in the chain registry of the host.
+    UnknownNetwork,
+    /// The block is older than the host proves.
+    BlockTooOld,
+    /// The block is not on the chain yet.
+    BlockTooNew,
+    /// The block was proposed in an L1 block that is not final yet, retry later.
+    BlockNotFinalized,
+    InvalidAddress,
+    InvalidQuote,
+    /// The block was not proposed on L1 like its input says.
+    ProposalMismatch,
+    /// The block of the request body is inconsistent with itself, its parent or its hash.
+    InvalidBlock,
+    JobNotFound,
+    JobAlreadyFinished,
+    /// The worker is not registered at the coordinator, e.g. after it missed its heartbeats.
+    WorkerNotFound,
+    Unauthorized,
+    Forbidden,
+    TooManyRequests,
+    /// The host does not support the operation for the proof type.
+    FeatureNotSupported,
+    /// The prover failed to generate the proof.
+    ProverError,
+    /// The proof job exceeded the memory or the other resources it is limited to.
+    ResourceExhausted,
+    /// An unexpected error of the host.
+    Internal,
+    /// The host is shutting down and takes no new proof requests.
+    ShuttingDown,
+    /// The queue of the host is full, retry after the seconds of the `Retry-After` header.
+    QueueFull,
+    /// A backend of the request is unavailable, e.g. the node of the chain can't be reached or
+    /// the circuit breaker of the backend is open after it failed repeatedly. Retry later, after
+    /// the seconds of the `Retry-After` header if there is one.
+    BackendUnavailable,
+    /// A code this version of the client does not know yet.
+    #[serde(other)]
+    Other,
+}
+
+impl ErrorCode {
+    /// The HTTP status code of the error.
+    pub fn status(&self) -> u16 {
+        match self {
+            ErrorCode::InvalidRequest
+            | ErrorCode::MalformedBody
+            | ErrorCode::UnknownField
+            | ErrorCode::MissingField
+            | ErrorCode::InvalidHex
+            | ErrorCode::UnsupportedProofType
+            | ErrorCode::UnknownNetwork
+            | ErrorCode::BlockTooOld
+            | ErrorCode::BlockTooNew
+            | ErrorCode::InvalidAddress
+            | ErrorCode::InvalidQuote
+            | ErrorCode::ProposalMismatch
+            | ErrorCode::InvalidBlock => 400,
+            ErrorCode::Unauthorized => 401,
+            ErrorCode::Forbidden => 403,
+            ErrorCode::JobNotFound | ErrorCode::WorkerNotFound => 404,
+            ErrorCode::FeatureNotSupported => 405,
+            ErrorCode::JobAlreadyFinished => 409,
+            ErrorCode::ProverError => 424,
+            ErrorCode::BlockNotFinalized => 425,
+            ErrorCode::ResourceExhausted => 507,
+            ErrorCode::TooManyRequests => 429,
+            ErrorCode::Internal | ErrorCode::Other => 500,
+            ErrorCode::ShuttingDown | ErrorCode::QueueFull | ErrorCode::BackendUnavailable => 503,
+        }
+    }
+
+    /// A short summary of the error, the same for every occurrence.
+    pub fn title(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidRequest => "Invalid request",
+            ErrorCode::MalformedBody => "Malformed request body",
+            ErrorCode::UnknownField => "Unknown field",
+            ErrorCode::MissingField => "Missing field",
+            ErrorCode::InvalidHex => "Invalid hex value",
+            ErrorCode::UnsupportedProofType => "Unsupported proof type",
+            ErrorCode::UnknownNetwork => "Unknown network",
+            ErrorCode::BlockTooOld => "Block too old",
+            ErrorCode::BlockTooNew => "Block too new",
+            ErrorCode::InvalidAddress => "Invalid address",
+            ErrorCode::InvalidQuote => "Invalid quote",
+            ErrorCode::ProposalMismatch => "Proposal mismatch",
+            ErrorCode::InvalidBlock => "Invalid block",
+            ErrorCode::BlockNotFinalized => "Block not finalized",
+            ErrorCode::JobNotFound => "Job not found",
+            ErrorCode::JobAlreadyFinished => "Job already finished",
+            ErrorCode::WorkerNotFound => "Worker not found",
+            ErrorCode::Unauthorized => "Unauthorized",
+            ErrorCode::Forbidden => "Forbidden",
+            ErrorCode::TooManyRequests => "Too many requests",
+            ErrorCode::FeatureNotSupported => "Feature not supported",
+            ErrorCode::ProverError => "Prover error",
+            ErrorCode::ResourceExhausted => "Resource exhausted",
+            ErrorCode::Internal | ErrorCode::Other => "Internal error",
+            ErrorCode::ShuttingDown => "Shutting down",
+            ErrorCode::QueueFull => "Queue full",
+            ErrorCode::BackendUnavailable => "Backend unavailable",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The serialized name, e.g. `block_too_new`
+        let name = serde_json::to_value(self).map_err(|_| std::fmt::Error)?;
+        f.write_str(name.as_str().unwrap_or_default())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+/// An error response, e.g. `{"type": "urn:raiko:error:block_too_new", "title": "Block too
+/// new", "status": 400, "detail": "...", "code": "block_too_new", "field": "block_number"}`.
+pub struct Problem {
+    /// A URI identifying the kind of error, derived from the code.
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: String,
+    pub status: u16,
+    /// What went wrong with this request.
+    pub detail: String,
+    pub code: ErrorCode,
+    /// The request field at fault, if there is one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+impl Problem {
+    pub fn new(code: ErrorCode, detail: impl Into<String>) -> Self {
+        Self {
+            kind: format!("urn:raiko:error:{code}"),
+            title: code.title().to_string(),
+            status: code.status(),
+            detail: detail.into(),
+            code,
+            field: None,
+        }
+    }
+
+    /// Name the request field at fault.
+    pub fn with_field(mut self, field: impl Into<String>) -> Self {
+        self.field = Some(field.into());
+        self
+    }
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}): {}", self.title, self.code, self.detail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_problem_json() {
+        let problem = Problem::new(ErrorCode::BlockTooNew, "Block 10 is after the head 9")
+            .with_field("block_number");
+        assert_eq!(
+            serde_json::to_value(&problem).unwrap(),
+            json!({
+                "type": "urn:raiko:error:block_too_new",
+                "title": "Block too new",
+                "status": 400,
+                "detail": "Block 10 is after the head 9",
+                "code": "block_too_new",
+                "field": "block_number",
+            })
+        );
+        // Codes of newer hosts are still parsed
+        let problem: Problem = serde_json::from_value(json!({
+            "type": "urn:raiko:error:new_code",
+            "title": "New code",
+            "status": 400,
+            "detail": "",
+            "code": "new_code",
+        }))
+        .unwrap();
+        assert_eq!(problem.code, ErrorCode::Other);
+    }
+}
@@ -0,0 +1,199 @@
+//! The request and response types of the proof job endpoints, as described by the OpenAPI
+//! specification the host serves at `/openapi.json`. The host tests them against its
+//! specification, so they can't drift apart.
+
+use std::collections::BTreeMap;
+
+use alloy_primitives::{Address, B256, U256};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The identifier of a proof job.
+pub type JobId = u64;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+/// A proof request. Unset fields are taken from the config of the host.
+pub struct ProofRequest {
+    /// The block number for the block to generate a proof for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_number: Option<u64>,
+    /// The name of the chain to generate the proof for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    /// The L1 network of the chain.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l1_network: Option<String>,
+    /// RPC URL for retrieving block by block number.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc: Option<String>,
+    /// The L1 node URL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l1_rpc: Option<String>,
+    /// The beacon node URL for retrieving data blobs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub beacon_rpc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub graffiti: Option<B256>,
+    /// The address of the prover.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prover: Option<Address>,
+    /// The proof type, e.g. `sgx`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proof_type: Option<String>,
+    /// Whether to wrap the proof into a SNARK that can be verified on-chain.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compress: Option<bool>,
+    /// The scheduling priority, requests with a higher priority are proven first.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u32>,
+    /// The unix timestamp the proving window of the block ends at.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<u64>,
+    /// Generate a new proof even if a proof for the block is in the proof cache.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub force: Option<bool>,
+    /// Prove the on-chain block while executing it natively, failing as soon as they differ.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cross_check: Option<bool>,
+    /// Have the guest report its profile, added to the stats of the proof.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile_guest: Option<bool>,
+    /// The URL the result of the job is posted to once it finished.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+    /// The name of the guest version to prove with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guest: Option<String>,
+    /// Additional prover params by proof type, e.g. `{"sgx": {"instance_id": 1}}`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub prover_args: BTreeMap<String, Value>,
+}
+
+impl ProofRequest {
+    /// A request for a proof of the block, with the other fields from the config of the host.
+    pub fn new(network: &str, block_number: u64, proof_type: &str) -> Self {
+        Self {
+            network: Some(network.to_string()),
+            block_number: Some(block_number),
+            proof_type: Some(proof_type.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// The state of a proof job.
+pub enum JobStatus {
+    /// The job is waiting for a free prover slot.
+    Queued,
+    /// The proof is being generated.
+    Running,
+    /// The proof was generated successfully.
+    Success,
+    /// The proof generation failed.
+    Failed,
+    /// The job was cancelled before it finished.
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Returns whether the job has reached a final state.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Success | JobStatus::Failed | JobStatus::Cancelled
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// The status report of a proof job, without the proof itself.
+pub struct JobSummary {
+    pub id: JobId,
+    pub status: JobStatus,
+    /// The error message, set once the job failed.
+    pub error: Option<String>,
+    /// The number of times the job was retried after a transient failure.
+    pub retries: u32,
+    /// The submission of the proof to L1, set once the relayer picked it up.
+    #[serde(default)]
+    pub submission: Option<Submission>,
+    /// The objects the proof was uploaded to, set once it was uploaded.
+    #[serde(default)]
+    pub upload: Option<Upload>,
+    /// The trace id of the request that submitted the job, to find its logs by.
+    #[serde(default)]
+    pub trace_id: String,
+    /// The position of the job in the queue when it was submitted, 1 for the next job to
+    /// start. Only set in the response to the submission.
+    #[serde(default)]
+    pub queue_position: Option<usize>,
+    /// Unix timestamp of the job creation.
+    pub created_at: u64,
+    /// Unix timestamp of the last status change.
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// A proof job together with its result.
+pub struct Job {
+    pub id: JobId,
+    /// The proof request the job was created for, completed with the config of the host.
+    pub request: Value,
+    pub status: JobStatus,
+    /// The proof, set once the job succeeded. Its fields depend on the proof type.
+    pub proof: Option<Value>,
+    /// The error message, set once the job failed.
+    pub error: Option<String>,
+    #[serde(default)]
+    pub retries: u32,
+    #[serde(default)]
+    pub submission: Option<Submission>,
+    #[serde(default)]
+    pub upload: Option<Upload>,
+    #[serde(default)]
+    pub trace_id: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// The state of the submission of a proof.
+pub enum SubmissionStatus {
+    /// The transaction is being sent or waits to be mined.
+    Pending,
+    /// The transaction was mined and has enough confirmations.
+    Confirmed,
+    /// The transaction reverted or could not be mined.
+    Failed,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// The submission of a proof to L1 by the relayer of the host.
+pub struct Submission {
+    pub status: SubmissionStatus,
+    /// The hash of the last transaction sent.
+    pub tx_hash: Option<B256>,
+    /// The account the transaction is sent from.
+    pub from: Address,
+    pub nonce: Option<u64>,
+    /// The gas price of the last transaction sent, in wei.
+    pub gas_price: Option<U256>,
+    /// The number of transactions sent, including the replacements.
+    pub attempts: u32,
+    /// The L1 block the transaction was mined in.
+    pub block_number: Option<u64>,
+    pub gas_used: Option<u128>,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// The objects the proof of a job was uploaded to.
+pub struct Upload {
+    /// The URL of the proof.
+    pub proof_url: String,
+    /// The URL of the input the block was proven with, unless inputs aren't uploaded.
+    pub input_url: Option<String>,
+}
@@ -0,0 +1,21 @@
+use std::process::Command;
+
+/// Embed the commit the host is built from, see `images::BUILD_COMMIT`. Builds without the git
+/// repository, e.g. in docker, set it with `RAIKO_BUILD_COMMIT`.
+fn main() {
+    println!("cargo:rerun-if-env-changed=RAIKO_BUILD_COMMIT");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs");
+    if std::env::var("RAIKO_BUILD_COMMIT").is_ok() {
+        return;
+    }
+    let commit = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=RAIKO_BUILD_COMMIT={}", commit.trim());
+    }
+}
@@ -0,0 +1,317 @@
+//! API keys of the clients of the host.
+//!
+//! Once a key is configured, the API is only served to requests with a key in the
+//! `Authorization: Bearer <key>` or `X-Api-Key` header, within the rate limit and the daily
+//! proof quota of the key. The health, metrics and specification routes stay open. Keys added
+//! through the admin endpoints are kept in memory, so they are gone after a restart.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use raiko_primitives::hex;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    clock::unix_timestamp,
+    error::{HostError, HostResult},
+    metrics::{inc_api_key_proof_count, inc_api_key_request_count},
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// A client of the host, e.g. `{"name": "alice", "key": "...", "daily_quota": 100}`.
+pub struct ApiKeyConfig {
+    /// The name of the client, used as the label of its usage metrics.
+    pub name: String,
+    /// The secret the client authenticates with. Generated when adding a key without one.
+    #[serde(default)]
+    pub key: String,
+    /// Max requests per minute, unlimited if not set.
+    #[serde(default)]
+    pub rate_limit: Option<u32>,
+    /// Max proof requests per UTC day, unlimited if not set.
+    #[serde(default)]
+    pub daily_quota: Option<u32>,
+    /// Whether the key may use the `/admin` endpoints.
+    #[serde(default)]
+    pub admin: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// The usage of an API key.
+pub struct ApiKeyUsage {
+    pub name: String,
+    pub rate_limit: Option<u32>,
+    pub daily_quota: Option<u32>,
+    pub admin: bool,
+    /// The requests in the current minute.
+    pub requests_this_minute: u32,
+    /// The proof requests of the current UTC day.
+    pub proofs_today: u32,
+    /// The requests since the host started.
+    pub total_requests: u64,
+    /// The proof requests since the host started.
+    pub total_proofs: u64,
+}
+
+#[derive(Debug)]
+struct ApiKeyState {
+    config: ApiKeyConfig,
+    /// The minute since the unix epoch `requests` counts for.
+    minute: u64,
+    requests: u32,
+    /// The day since the unix epoch `proofs` counts for.
+    day: u64,
+    proofs: u32,
+    total_requests: u64,
+    total_proofs: u64,
+}
+
+impl ApiKeyState {
+    fn new(config: ApiKeyConfig) -> Self {
+        Self {
+            config,
+            minute: 0,
+            requests: 0,
+            day: 0,
+            proofs: 0,
+            total_requests: 0,
+            total_proofs: 0,
+        }
+    }
+
+    /// Count a request at unix time `now`, unless it exceeds the limits of the key.
+    fn use_key(&mut self, proof: bool, now: u64) -> HostResult<()> {
+        let (minute, day) = (now / 60, now / (24 * 60 * 60));
+        if self.minute != minute {
+            self.minute = minute;
+            self.requests = 0;
+        }
+        if self.day != day {
+            self.day = day;
+            self.proofs = 0;
+        }
+        if let Some(rate_limit) = self.config.rate_limit {
+            if self.requests >= rate_limit {
+                return Err(HostError::TooManyRequests(format!(
+                    "Rate limit of {rate_limit} requests per minute reached"
+                )));
+            }
+        }
+        if let (true, Some(daily_quota)) = (proof, self.config.daily_quota) {
+            if self.proofs >= daily_quota {
+                return Err(HostError::TooManyRequests(format!(
+                    "Daily quota of {daily_quota} proofs used up"
+                )));
+            }
+        }
+        self.requests += 1;
+        self.total_requests += 1;
+        inc_api_key_request_count(&self.config.name);
+        if proof {
+            self.proofs += 1;
+            self.total_proofs += 1;
+            inc_api_key_proof_count(&self.config.name);
+        }
+        Ok(())
+    }
+
+    fn usage(&self, now: u64) -> ApiKeyUsage {
+        ApiKeyUsage {
+            name: self.config.name.clone(),
+            rate_limit: self.config.rate_limit,
+            daily_quota: self.config.daily_quota,
+            admin: self.config.admin,
+            requests_this_minute: if self.minute == now / 60 {
+                self.requests
+            } else {
+                0
+            },
+            proofs_today: if self.day == now / (24 * 60 * 60) {
+                self.proofs
+            } else {
+                0
+            },
+            total_requests: self.total_requests,
+            total_proofs: self.total_proofs,
+        }
+    }
+}
+
+/// The API keys of the host, by key.
+#[derive(Clone, Debug, Default)]
+pub struct ApiKeys {
+    keys: Arc<Mutex<HashMap<String, ApiKeyState>>>,
+}
+
+impl ApiKeys {
+    pub fn new(configs: &[ApiKeyConfig]) -> HostResult<Self> {
+        let keys = Self::default();
+        for config in configs {
+            keys.add(config.clone())?;
+        }
+        Ok(keys)
+    }
+
+    /// Whether requests need an API key, i.e. any key is configured.
+    pub fn is_enabled(&self) -> bool {
+        !self.lock().is_empty()
+    }
+
+    /// Whether any admin key is configured. The `/admin` endpoints are only served then.
+    pub fn has_admin_key(&self) -> bool {
+        self.lock().values().any(|state| state.config.admin)
+    }
+
+    /// Add a key, generating its secret if it is empty. Returns the added key.
+    pub fn add(&self, mut config: ApiKeyConfig) -> HostResult<ApiKeyConfig> {
+        if config.name.is_empty() {
+            return Err(HostError::InvalidRequestConfig(
+                "The API key needs a name".to_string(),
+            ));
+        }
+        if config.key.is_empty() {
+            config.key = hex::encode(rand::random::<[u8; 32]>());
+        }
+        let mut keys = self.lock();
+        if keys.contains_key(&config.key)
+            || keys.values().any(|state| state.config.name == config.name)
+        {
+            return Err(HostError::InvalidRequestConfig(format!(
+                "An API key named {} already exists",
+                config.name
+            )));
+        }
+        keys.insert(config.key.clone(), ApiKeyState::new(config.clone()));
+        Ok(config)
+    }
+
+    /// Remove the key with the given name.
+    pub fn remove(&self, name: &str) -> HostResult<()> {
+        let mut keys = self.lock();
+        let len = keys.len();
+        keys.retain(|_, state| state.config.name != name);
+        if keys.len() == len {
+            return Err(HostError::InvalidRequestConfig(format!(
+                "Unknown API key: {name}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check the key of a request and count it. `admin` requests need an admin key, `proof`
+    /// requests count against the daily quota. Returns the name of the key, or `None` if no
+    /// keys are configured.
+    pub fn authorize(
+        &self,
+        key: Option<&str>,
+        admin: bool,
+        proof: bool,
+    ) -> HostResult<Option<String>> {
+        let mut keys = self.lock();
+        if keys.is_empty() {
+            return Ok(None);
+        }
+        let key = key.ok_or_else(|| HostError::Unauthorized("Missing API key".to_string()))?;
+        let state = keys
+            .get_mut(key)
+            .ok_or_else(|| HostError::Unauthorized("Invalid API key".to_string()))?;
+        if admin && !state.config.admin {
+            return Err(HostError::Forbidden(format!(
+                "The API key {} is not an admin key",
+                state.config.name
+            )));
+        }
+        state.use_key(proof, unix_timestamp())?;
+        Ok(Some(state.config.name.clone()))
+    }
+
+    /// The usage of all keys, by name.
+    pub fn usage(&self) -> Vec<ApiKeyUsage> {
+        let now = unix_timestamp();
+        let mut usage: Vec<_> = self.lock().values().map(|state| state.usage(now)).collect();
+        usage.sort_by(|a, b| a.name.cmp(&b.name));
+        usage
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, ApiKeyState>> {
+        self.keys.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(name: &str, key: &str) -> ApiKeyConfig {
+        ApiKeyConfig {
+            name: name.to_string(),
+            key: key.to_string(),
+            rate_limit: None,
+            daily_quota: None,
+            admin: false,
+        }
+    }
+
+    #[test]
+    fn test_authorize() {
+        let keys = ApiKeys::default();
+        assert_eq!(keys.authorize(None, true, true).unwrap(), None);
+
+        keys.add(config("alice", "secret")).unwrap();
+        assert!(keys.add(config("alice", "other")).is_err());
+        assert!(!keys.has_admin_key());
+        assert!(matches!(
+            keys.authorize(None, false, false),
+            Err(HostError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            keys.authorize(Some("wrong"), false, false),
+            Err(HostError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            keys.authorize(Some("secret"), true, false),
+            Err(HostError::Forbidden(_))
+        ));
+        assert_eq!(
+            keys.authorize(Some("secret"), false, true).unwrap(),
+            Some("alice".to_string())
+        );
+        let generated = keys
+            .add(ApiKeyConfig {
+                admin: true,
+                ..config("bob", "")
+            })
+            .unwrap();
+        assert_eq!(generated.key.len(), 64);
+        assert!(keys.has_admin_key());
+        keys.remove("alice").unwrap();
+        assert!(keys.authorize(Some("secret"), false, false).is_err());
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut state = ApiKeyState::new(ApiKeyConfig {
+            rate_limit: Some(3),
+            daily_quota: Some(2),
+            ..config("alice", "secret")
+        });
+        let now = 1_700_000_000;
+        state.use_key(true, now).unwrap();
+        state.use_key(true, now).unwrap();
+        // The quota is used up, other requests are still fine
+        assert!(state.use_key(true, now).is_err());
+        state.use_key(false, now).unwrap();
+        // The rate limit is reached
+        assert!(state.use_key(false, now + 1).is_err());
+        // A new minute resets the rate limit, a new day the quota
+        state.use_key(false, now + 60).unwrap();
+        state.use_key(true, now + 24 * 60 * 60).unwrap();
+        let usage = state.usage(now + 24 * 60 * 60);
+        assert_eq!(usage.proofs_today, 1);
+        assert_eq!(usage.total_requests, 5);
+        assert_eq!(usage.total_proofs, 3);
+    }
+}
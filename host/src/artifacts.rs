@@ -0,0 +1,407 @@
+//! The artifacts of the jobs.
+//!
+//! With `--artifacts-dir`, every job gets a directory `job-<id>` in it, keeping what is needed
+//! to debug its proof without logging into the host: the input of the block
+//! (`input.bin.zst`, which the `prove` command and `POST /proof/replay` take), the checkpoints
+//! the prover kept in its [workspace](crate::workspace), e.g. the receipts of the Risc0
+//! segments (`workspace/`), the output of the prover subprocesses (`prover.log`), the
+//! [log](crate::jobs::logs) of the job (`log.jsonl`) and the proof (`proof.json`) or the error
+//! (`error.txt`). The artifacts are listed and downloaded at `GET /v2/jobs/{id}/artifacts`.
+//!
+//! The artifacts are pruned whenever a job finishes, removing the directories of the jobs
+//! older than the max age and then the oldest directories until all of them fit the max size.
+//! The [garbage collection](crate::gc) removes more of the oldest directories when the disk
+//! runs out of space.
+
+use std::{
+    fs::File,
+    future::Future,
+    io::{BufWriter, Write},
+    path::{Component, Path, PathBuf},
+    sync::RwLock,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use lazy_static::lazy_static;
+use raiko_client::{ErrorCode, Problem};
+use raiko_lib::{input::GuestInput, prover::Proof};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    clock::unix_timestamp,
+    error::{HostError, HostResult},
+    input_codec,
+    jobs::{logs, JobId},
+    request::ProofRequest,
+    workspace,
+};
+
+/// The artifact with the input of the job.
+pub const INPUT_FILE: &str = "input.bin.zst";
+
+/// The directory with the checkpoints of the prover.
+pub const WORKSPACE_DIR: &str = "workspace";
+
+/// The targets of the output of the prover subprocesses in the log of a job.
+const PROVER_OUTPUT_TARGETS: &[&str] = &["sgx_guest"];
+
+#[derive(Clone, Debug)]
+struct Config {
+    dir: PathBuf,
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+}
+
+lazy_static! {
+    static ref CONFIG: RwLock<Option<Config>> = RwLock::new(None);
+}
+
+tokio::task_local! {
+    static CURRENT_JOB: JobId;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+/// A file kept for a job.
+pub struct Artifact {
+    /// The path of the file in the artifacts of the job.
+    pub path: String,
+    /// The size of the file in bytes.
+    pub size: u64,
+    /// Unix timestamp of the last change of the file.
+    pub modified: u64,
+}
+
+/// Set the directory the artifacts are kept in, holding at most `max_size` bytes of artifacts
+/// of jobs which are at most `max_age` old. Unset to keep no artifacts.
+pub fn configure(
+    dir: Option<PathBuf>,
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+) -> HostResult<()> {
+    if let Some(dir) = &dir {
+        std::fs::create_dir_all(dir)?;
+    }
+    *CONFIG.write().unwrap_or_else(|e| e.into_inner()) = dir.map(|dir| Config {
+        dir,
+        max_size,
+        max_age,
+    });
+    Ok(())
+}
+
+/// The directory the artifacts are kept in, unset without artifacts.
+pub fn dir() -> Option<PathBuf> {
+    config().map(|config| config.dir)
+}
+
+fn config() -> Option<Config> {
+    CONFIG.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Run the future of the job with the given id, so the proof keeps its artifacts in the
+/// directory of the job.
+pub async fn with_job<F: Future>(id: JobId, future: F) -> F::Output {
+    CURRENT_JOB.scope(id, future).await
+}
+
+fn job_path(dir: &Path, id: JobId) -> PathBuf {
+    dir.join(format!("job-{id}"))
+}
+
+/// The directory of the artifacts of the job, created if needed. Unset without an artifacts
+/// directory.
+fn job_dir(id: JobId) -> Option<PathBuf> {
+    let dir = job_path(&config()?.dir, id);
+    match std::fs::create_dir_all(&dir) {
+        Ok(()) => Some(dir),
+        Err(e) => {
+            warn!("Could not create the artifacts of job {id}: {e}");
+            None
+        }
+    }
+}
+
+/// Write an artifact of the job, if artifacts are kept.
+fn save(id: JobId, name: &str, write: impl FnOnce(&mut BufWriter<File>) -> HostResult<()>) {
+    let Some(dir) = job_dir(id) else {
+        return;
+    };
+    let result = File::create(dir.join(name))
+        .map_err(HostError::from)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            Ok(writer.flush()?)
+        });
+    if let Err(e) = result {
+        warn!("Could not save the artifact {name} of job {id}: {e}");
+    }
+}
+
+/// Keep the input of the job.
+pub fn save_input(id: JobId, input: &GuestInput) {
+    save(id, INPUT_FILE, |writer| {
+        input_codec::encode(input, writer, "artifacts")
+    });
+}
+
+/// Keep the proof or the error of the finished job and its log, then prune the artifacts.
+pub fn save_result(id: JobId, result: &Result<Proof, String>) {
+    if config().is_none() {
+        return;
+    }
+    match result {
+        Ok(proof) => save(id, "proof.json", |writer| {
+            Ok(serde_json::to_writer_pretty(writer, proof)?)
+        }),
+        Err(e) => save(
+            id,
+            "error.txt",
+            |writer| Ok(writer.write_all(e.as_bytes())?),
+        ),
+    }
+    let lines = logs::lines(id);
+    save(id, "log.jsonl", |writer| {
+        for line in &lines {
+            serde_json::to_writer(&mut *writer, line)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    });
+    let prover_output: Vec<_> = lines
+        .iter()
+        .filter(|line| PROVER_OUTPUT_TARGETS.contains(&line.target.as_str()))
+        .collect();
+    if !prover_output.is_empty() {
+        save(id, "prover.log", |writer| {
+            for line in prover_output {
+                writeln!(writer, "{}", line.message)?;
+            }
+            Ok(())
+        });
+    }
+    if let Err(e) = prune() {
+        warn!("Could not prune the artifacts: {e}");
+    }
+}
+
+/// Move the workspace of a generated proof into the artifacts of the current job, or remove
+/// it without a job or artifacts.
+pub fn keep_workspace(path: &Path) {
+    let Some(dir) = CURRENT_JOB.try_with(|id| *id).ok().and_then(job_dir) else {
+        workspace::remove(path);
+        return;
+    };
+    if let Err(e) = std::fs::rename(path, dir.join(WORKSPACE_DIR)) {
+        warn!(
+            "Could not move the workspace {} into the artifacts: {e}",
+            path.display()
+        );
+        workspace::remove(path);
+    }
+}
+
+fn not_kept() -> HostError {
+    HostError::Validation(Problem::new(
+        ErrorCode::FeatureNotSupported,
+        "The host keeps no artifacts, see --artifacts-dir",
+    ))
+}
+
+/// List the artifacts of the job. The checkpoints of an unfinished or failed proof are
+/// listed from its workspace.
+pub fn list(id: JobId, request: &ProofRequest) -> HostResult<Vec<Artifact>> {
+    let config = config().ok_or_else(not_kept)?;
+    let dir = job_path(&config.dir, id);
+    let mut artifacts = list_dir(&dir)?;
+    if !dir.join(WORKSPACE_DIR).exists() {
+        if let Some(workspace) = workspace::path(request).filter(|path| path.exists()) {
+            artifacts.extend(list_dir(&workspace)?.into_iter().map(|artifact| Artifact {
+                path: format!("{WORKSPACE_DIR}/{}", artifact.path),
+                ..artifact
+            }));
+        }
+    }
+    Ok(artifacts)
+}
+
+/// The path of an artifact of the job, listed by [list].
+pub fn path(id: JobId, request: &ProofRequest, artifact: &str) -> HostResult<PathBuf> {
+    let config = config().ok_or_else(not_kept)?;
+    let artifact = Path::new(artifact);
+    let invalid = || {
+        HostError::invalid(
+            ErrorCode::InvalidRequest,
+            "path",
+            format!("No artifact {} of job {id}", artifact.display()),
+        )
+    };
+    if !artifact
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(invalid());
+    }
+    let dir = job_path(&config.dir, id);
+    let mut path = dir.join(artifact);
+    if !path.is_file() {
+        if let (Ok(rest), Some(workspace)) = (
+            artifact.strip_prefix(WORKSPACE_DIR),
+            workspace::path(request),
+        ) {
+            path = workspace.join(rest);
+        }
+    }
+    if !path.is_file() {
+        return Err(invalid());
+    }
+    Ok(path)
+}
+
+/// List the files in the directory and its subdirectories, by path.
+fn list_dir(dir: &Path) -> HostResult<Vec<Artifact>> {
+    let mut artifacts = Vec::new();
+    if dir.exists() {
+        collect(dir, dir, &mut artifacts)?;
+    }
+    artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(artifacts)
+}
+
+fn collect(root: &Path, dir: &Path, artifacts: &mut Vec<Artifact>) -> HostResult<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            collect(root, &entry.path(), artifacts)?;
+            continue;
+        }
+        let path = entry.path();
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        artifacts.push(Artifact {
+            path: relative.to_string_lossy().replace('\\', "/"),
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .unwrap_or(UNIX_EPOCH)
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        });
+    }
+    Ok(())
+}
+
+/// Remove the artifacts of the jobs older than the max age, then the oldest artifacts until
+/// all of them fit the max size. Returns the number of bytes removed.
+pub fn prune() -> HostResult<u64> {
+    let Some(config) = config() else {
+        return Ok(0);
+    };
+    prune_dir(&config.dir, config.max_size, config.max_age, 0)
+}
+
+/// Prune the artifacts and remove the oldest of the others until at least `bytes` bytes are
+/// removed, to free disk space. Returns the number of bytes removed.
+pub fn evict(bytes: u64) -> HostResult<u64> {
+    let Some(config) = config() else {
+        return Ok(0);
+    };
+    prune_dir(&config.dir, config.max_size, config.max_age, bytes)
+}
+
+fn prune_dir(
+    dir: &Path,
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+    reclaim: u64,
+) -> HostResult<u64> {
+    if max_size.is_none() && max_age.is_none() && reclaim == 0 {
+        return Ok(0);
+    }
+    let mut jobs = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_name().to_string_lossy().starts_with("job-") {
+            continue;
+        }
+        let artifacts = list_dir(&entry.path())?;
+        let size: u64 = artifacts.iter().map(|artifact| artifact.size).sum();
+        let modified = artifacts
+            .iter()
+            .map(|artifact| artifact.modified)
+            .max()
+            .unwrap_or_default();
+        jobs.push((modified, size, entry.path()));
+    }
+    // Oldest first
+    jobs.sort();
+
+    let now = unix_timestamp();
+    let mut total_size: u64 = jobs.iter().map(|(_, size, _)| size).sum();
+    let mut removed = 0;
+    for (modified, size, path) in jobs {
+        let expired =
+            max_age.is_some_and(|max_age| now.saturating_sub(modified) > max_age.as_secs());
+        let too_large = max_size.is_some_and(|max_size| total_size > max_size);
+        if !expired && !too_large && removed >= reclaim {
+            // The jobs are sorted by age, so none of the newer jobs need to be removed.
+            break;
+        }
+        debug!("Pruning the artifacts {path:?}");
+        match std::fs::remove_dir_all(&path) {
+            Ok(()) => removed += size,
+            Err(e) => warn!("Could not remove the artifacts {path:?}: {e}"),
+        }
+        total_size -= size;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_and_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = SystemTime::now();
+        for (id, size, age) in [(1, 100, 7200), (2, 200, 1800), (3, 300, 0)] {
+            let job = job_path(dir.path(), id);
+            std::fs::create_dir_all(job.join(WORKSPACE_DIR)).unwrap();
+            for (name, content) in [
+                ("error.txt".to_string(), vec![b'e'; size]),
+                (format!("{WORKSPACE_DIR}/segment-0"), b"receipt".to_vec()),
+            ] {
+                std::fs::write(job.join(&name), content).unwrap();
+                File::options()
+                    .write(true)
+                    .open(job.join(&name))
+                    .unwrap()
+                    .set_modified(now - Duration::from_secs(age))
+                    .unwrap();
+            }
+        }
+        let artifacts = list_dir(&job_path(dir.path(), 2)).unwrap();
+        let paths: Vec<_> = artifacts
+            .iter()
+            .map(|a| (a.path.as_str(), a.size))
+            .collect();
+        assert_eq!(paths, vec![("error.txt", 200), ("workspace/segment-0", 7)]);
+
+        // The expired job is removed, the others fit.
+        let max_age = Some(Duration::from_secs(3600));
+        assert_eq!(prune_dir(dir.path(), Some(600), max_age, 0).unwrap(), 107);
+        assert!(!job_path(dir.path(), 1).exists());
+        assert!(job_path(dir.path(), 2).exists());
+        // The oldest job is removed until the others fit.
+        assert_eq!(prune_dir(dir.path(), Some(400), max_age, 0).unwrap(), 207);
+        assert!(!job_path(dir.path(), 2).exists());
+        assert!(job_path(dir.path(), 3).exists());
+        // Disk space is freed even when the artifacts fit.
+        assert_eq!(prune_dir(dir.path(), None, None, 1).unwrap(), 307);
+        assert!(!job_path(dir.path(), 3).exists());
+    }
+}
@@ -0,0 +1,255 @@
+//! Benchmarks of the provers.
+//!
+//! The calibration benchmark proves a set of reference blocks with every prover enabled on the
+//! host and fits the cycles and the proving time of each prover to the gas of the blocks. The
+//! resulting calibration profile is specific to the machine, and is what the
+//! [estimates](crate::estimate) and the scheduler of the jobs expect the proofs to take.
+//!
+//! The guests of the zkVM provers replace the crypto crates of their dependencies with patched
+//! versions calling the precompiles of the zkVM. The precompiles benchmark runs every
+//! accelerated precompile next to a software implementation of the same operation in the guest,
+//! to quantify the cycles the acceleration saves.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    time::Instant,
+};
+
+use tracing::{info, warn};
+
+use crate::{
+    error::{HostError, HostResult},
+    estimate::{load_calibration, Calibration, CalibrationProfile},
+    execution::{generate_proof, prepare_input},
+    fixtures,
+    jobs::progress::ProgressSender,
+    registry::list_provers,
+    request::{ProofRequest, ProofType},
+    BenchArgs, CalibrateArgs, Cli,
+};
+
+/// A reference block proven by a prover.
+#[derive(Clone, Debug, PartialEq)]
+struct Sample {
+    gas_used: u64,
+    seconds: f64,
+    cycles: Option<u64>,
+    peak_memory: u64,
+}
+
+/// Prove the reference blocks with every prover of the arguments, or every prover enabled on
+/// the host, and write the calibration profile of the provers. The calibrations of other
+/// provers already in the profile are kept.
+pub async fn bench_calibration(opts: &Cli, args: &CalibrateArgs) -> HostResult<()> {
+    opts.load_chains()?;
+    fixtures::configure(opts.fixture_mode, opts.fixture_dir.clone());
+    let proof_types = if args.proof_types.is_empty() {
+        list_provers()
+            .into_iter()
+            .map(|prover| prover.name)
+            .collect()
+    } else {
+        args.proof_types.clone()
+    }
+    .iter()
+    .map(|name| name.parse())
+    .collect::<HostResult<Vec<ProofType>>>()?;
+
+    let mut samples = vec![Vec::new(); proof_types.len()];
+    for block_number in &args.blocks {
+        let mut request_opt = opts.proof_request_opt.clone();
+        request_opt.block_number = Some(*block_number);
+        let input = prepare_input(ProofRequest::try_from(request_opt.clone())?).await?;
+        info!(
+            "Calibrating with block {block_number} using {} gas",
+            input.gas_used
+        );
+        for (proof_type, prover_samples) in proof_types.iter().zip(samples.iter_mut()) {
+            request_opt.proof_type = Some(proof_type.to_string());
+            let proof_request = ProofRequest::try_from(request_opt.clone())?;
+            let start = Instant::now();
+            let proof =
+                match generate_proof(&proof_request, &input, &ProgressSender::default()).await {
+                    Ok(proof) => proof,
+                    Err(e) => {
+                        warn!("Skipping {proof_type}, block {block_number} failed to prove: {e}");
+                        continue;
+                    }
+                };
+            let sample = Sample {
+                gas_used: input.gas_used,
+                seconds: start.elapsed().as_secs_f64(),
+                cycles: proof["stats"]["cycles"].as_u64(),
+                peak_memory: proof["stats"]["peak_memory"].as_u64().unwrap_or_default(),
+            };
+            let cycles = sample.cycles.map_or("no".to_string(), |c| c.to_string());
+            println!(
+                "{proof_type} block {block_number}: {} gas, {:.1}s, {cycles} cycles, {} bytes at peak",
+                sample.gas_used, sample.seconds, sample.peak_memory
+            );
+            prover_samples.push(sample);
+        }
+    }
+
+    let mut profile = if args.output.exists() {
+        load_calibration(&args.output)?
+    } else {
+        CalibrationProfile::new()
+    };
+    for (proof_type, samples) in proof_types.iter().zip(&samples) {
+        match calibrate(samples) {
+            Some(calibration) => {
+                profile.insert(proof_type.to_string(), calibration);
+            }
+            None => warn!("No block was proven with {proof_type}, it is not calibrated"),
+        }
+    }
+    let mut writer = BufWriter::new(File::create(&args.output)?);
+    serde_json::to_writer_pretty(&mut writer, &profile)?;
+    writer.flush()?;
+    println!("Wrote the calibration profile to {}", args.output.display());
+    Ok(())
+}
+
+/// The calibration fitting the samples of a prover, unset without samples.
+fn calibrate(samples: &[Sample]) -> Option<Calibration> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mgas = |sample: &Sample| sample.gas_used as f64 / 1e6;
+    let (base_seconds, seconds_per_mgas) = fit(&samples
+        .iter()
+        .map(|sample| (mgas(sample), sample.seconds))
+        .collect::<Vec<_>>());
+    let cycles = samples
+        .iter()
+        .map(|sample| Some((mgas(sample), sample.cycles? as f64)))
+        .collect::<Option<Vec<_>>>();
+    let (base_cycles, cycles_per_mgas) = match cycles {
+        Some(cycles) => {
+            let (base_cycles, cycles_per_mgas) = fit(&cycles);
+            (base_cycles as u64, Some(cycles_per_mgas))
+        }
+        None => (0, None),
+    };
+    Some(Calibration {
+        cycles_per_mgas,
+        base_cycles,
+        seconds_per_mgas,
+        base_seconds,
+        peak_memory: samples.iter().map(|sample| sample.peak_memory).max(),
+    })
+}
+
+/// Fit `y = base + slope * x` to the points with least squares, returning `(base, slope)`.
+/// Falls back to a line through the origin when the points don't determine a line with a
+/// positive base and slope, e.g. a single block.
+fn fit(points: &[(f64, f64)]) -> (f64, f64) {
+    let n = points.len() as f64;
+    let (sum_x, sum_y) = points
+        .iter()
+        .fold((0.0, 0.0), |(sum_x, sum_y), (x, y)| (sum_x + x, sum_y + y));
+    let (mean_x, mean_y) = (sum_x / n, sum_y / n);
+    let (covariance, variance) = points.iter().fold((0.0, 0.0), |(cov, var), (x, y)| {
+        (
+            cov + (x - mean_x) * (y - mean_y),
+            var + (x - mean_x) * (x - mean_x),
+        )
+    });
+    if variance > 0.0 {
+        let slope = covariance / variance;
+        let base = mean_y - slope * mean_x;
+        if slope >= 0.0 && base >= 0.0 {
+            return (base, slope);
+        }
+    }
+    if sum_x > 0.0 {
+        (0.0, sum_y / sum_x)
+    } else {
+        // Only empty blocks, the time doesn't depend on the gas.
+        (mean_y, 0.0)
+    }
+}
+
+/// Run the precompiles benchmark of the prover of the proof request options and print the
+/// cycles of every precompile.
+pub async fn bench_precompiles(opts: &Cli, args: &BenchArgs) -> HostResult<()> {
+    let proof_type: ProofType = opts
+        .proof_request_opt
+        .proof_type
+        .as_deref()
+        .unwrap_or_default()
+        .parse()?;
+    let iterations = args.iterations;
+    info!("Benchmarking the precompiles of {proof_type} with {iterations} iterations");
+    match proof_type {
+        #[cfg(feature = "risc0")]
+        ProofType::Risc0 => {
+            let cycles =
+                tokio::task::spawn_blocking(move || risc0_prover::bench_precompiles(iterations))
+                    .await??;
+            for cycles in &cycles {
+                println!(
+                    "{}: {} cycles accelerated, {} cycles in software, {:.1}% saved",
+                    cycles.precompile,
+                    cycles.accelerated,
+                    cycles.software,
+                    cycles.savings()
+                );
+            }
+            Ok(())
+        }
+        #[cfg(feature = "sp1")]
+        ProofType::Sp1 => {
+            tokio::task::spawn_blocking(move || sp1_prover::bench_precompiles(iterations))
+                .await??;
+            println!("The cycles of every precompile are in the cycle tracker logs of SP1");
+            Ok(())
+        }
+        _ => Err(HostError::FeatureNotSupportedError(proof_type)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit() {
+        assert_eq!(fit(&[(1.0, 30.0), (3.0, 70.0), (5.0, 110.0)]), (10.0, 20.0));
+        // A single block goes through the origin.
+        assert_eq!(fit(&[(2.0, 50.0)]), (0.0, 25.0));
+        // So do points that would fit a negative base.
+        assert_eq!(fit(&[(1.0, 5.0), (3.0, 35.0)]), (0.0, 10.0));
+        assert_eq!(fit(&[(0.0, 4.0), (0.0, 6.0)]), (5.0, 0.0));
+    }
+
+    #[test]
+    fn test_calibrate() {
+        let sample = |gas_used, seconds, cycles, peak_memory| Sample {
+            gas_used,
+            seconds,
+            cycles,
+            peak_memory,
+        };
+        assert_eq!(calibrate(&[]), None);
+        let calibration = calibrate(&[
+            sample(1_000_000, 30.0, Some(150_000_000), 1 << 30),
+            sample(3_000_000, 70.0, Some(350_000_000), 1 << 31),
+        ])
+        .unwrap();
+        assert_eq!(calibration.seconds_per_mgas, 20.0);
+        assert_eq!(calibration.base_seconds, 10.0);
+        assert_eq!(calibration.cycles_per_mgas, Some(1e8));
+        assert_eq!(calibration.base_cycles, 50_000_000);
+        assert_eq!(calibration.peak_memory, Some(1 << 31));
+        // Provers without cycles for every block aren't calibrated for cycles.
+        let calibration = calibrate(&[
+            sample(1_000_000, 30.0, Some(150_000_000), 0),
+            sample(3_000_000, 70.0, None, 0),
+        ])
+        .unwrap();
+        assert_eq!(calibration.cycles_per_mgas, None);
+    }
+}
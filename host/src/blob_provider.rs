@@ -0,0 +1,415 @@
+//! Sources of the blobs holding the tx lists of Taiko blocks.
+//!
+//! Beacon nodes prune blob sidecars after about 18 days, so proving an older block needs the
+//! blob from somewhere else. A [BlobProvider] fetches blobs from one source: a beacon node, a
+//! blobscan API or a local blob archive. The preflight asks the configured sources in their
+//! priority order, retrying each one a few times before falling back to the next, and keeps
+//! the blobs fetched from the network in the archive when there is one. A blob is only taken,
+//! and archived, once it matches its versioned hash and its KZG proof.
+
+use std::{path::PathBuf, sync::RwLock, time::Duration};
+
+use alloy_primitives::{Bytes, FixedBytes, B256};
+use anyhow::{anyhow, bail, ensure, Result};
+use clap::ValueEnum;
+use lazy_static::lazy_static;
+use raiko_lib::prover::BoxFuture;
+use raiko_primitives::{
+    eip4844::{commitment_to_versioned_hash, MAINNET_KZG_TRUSTED_SETUP},
+    kzg::{Blob, Bytes48, KzgProof},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+/// The number of attempts per source before falling back to the next source.
+const BLOB_FETCH_ATTEMPTS: u32 = 3;
+
+/// The delay before the first retry, doubling with every retry.
+const BLOB_FETCH_BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// A blob with its KZG commitment and proof.
+pub struct BlobSidecar {
+    pub blob: Bytes,
+    pub kzg_commitment: FixedBytes<48>,
+    pub kzg_proof: FixedBytes<48>,
+}
+
+impl BlobSidecar {
+    /// The versioned hash of the blob, as included in the blob transaction.
+    pub fn versioned_hash(&self) -> B256 {
+        commitment_to_versioned_hash(self.kzg_commitment.as_slice())
+    }
+
+    /// Check the blob is the blob with the given versioned hash: its commitment has to match
+    /// the hash and the blob its commitment, with the KZG proof.
+    pub fn verify(&self, blob_hash: B256) -> Result<()> {
+        ensure!(
+            self.versioned_hash() == blob_hash,
+            "The KZG commitment doesn't match blob {blob_hash}"
+        );
+        let blob =
+            Blob::from_bytes(&self.blob).map_err(|e| anyhow!("Invalid blob {blob_hash}: {e:?}"))?;
+        let valid = KzgProof::verify_blob_kzg_proof(
+            &blob,
+            &Bytes48::from(self.kzg_commitment.0),
+            &Bytes48::from(self.kzg_proof.0),
+            &MAINNET_KZG_TRUSTED_SETUP,
+        )
+        .map_err(|e| anyhow!("Could not verify blob {blob_hash}: {e:?}"))?;
+        ensure!(valid, "Invalid KZG proof for blob {blob_hash}");
+        Ok(())
+    }
+}
+
+/// A source of blobs.
+pub trait BlobProvider: Send + Sync {
+    /// The name of the source, used in logs.
+    fn name(&self) -> &str;
+
+    /// Get the blob with the given versioned hash, included in the beacon block of `slot`.
+    fn get_blob(&self, slot: u64, blob_hash: B256) -> BoxFuture<'_, Result<BlobSidecar>>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+/// The kinds of blob sources.
+pub enum BlobSource {
+    /// The blob sidecars API of the beacon node of the chain.
+    Beacon,
+    /// The blobscan API of the chain, see `blobscan_url` of the chain.
+    Blobscan,
+    /// The local blob archive, see `--blob-archive-dir`.
+    Archive,
+}
+
+/// Fetches blob sidecars from the beacon API of a consensus node.
+pub struct BeaconProvider {
+    url: String,
+}
+
+impl BeaconProvider {
+    pub fn new(url: String) -> Self {
+        Self { url }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BeaconBlobSidecar {
+    blob: Bytes,
+    kzg_commitment: FixedBytes<48>,
+    kzg_proof: FixedBytes<48>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BeaconBlobSidecars {
+    data: Vec<BeaconBlobSidecar>,
+}
+
+impl BlobProvider for BeaconProvider {
+    fn name(&self) -> &str {
+        "beacon"
+    }
+
+    fn get_blob(&self, slot: u64, blob_hash: B256) -> BoxFuture<'_, Result<BlobSidecar>> {
+        Box::pin(async move {
+            let url = format!(
+                "{}/eth/v1/beacon/blob_sidecars/{slot}",
+                self.url.trim_end_matches('/'),
+            );
+            let response = reqwest::get(url).await?;
+            if !response.status().is_success() {
+                bail!("Request failed with status code: {}", response.status());
+            }
+            let sidecars: BeaconBlobSidecars = response.json().await?;
+            ensure!(!sidecars.data.is_empty(), "Blob data not available anymore");
+            sidecars
+                .data
+                .into_iter()
+                .map(|sidecar| BlobSidecar {
+                    blob: sidecar.blob,
+                    kzg_commitment: sidecar.kzg_commitment,
+                    kzg_proof: sidecar.kzg_proof,
+                })
+                .find(|sidecar| sidecar.versioned_hash() == blob_hash)
+                .ok_or_else(|| anyhow!("No blob sidecar in slot {slot} for blob {blob_hash}"))
+        })
+    }
+}
+
+/// Fetches blobs from a blobscan API, which keeps blobs after the beacon nodes pruned them.
+pub struct BlobscanProvider {
+    url: String,
+}
+
+impl BlobscanProvider {
+    pub fn new(url: String) -> Self {
+        Self { url }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BlobscanBlob {
+    commitment: FixedBytes<48>,
+    proof: FixedBytes<48>,
+    data: Bytes,
+}
+
+impl BlobProvider for BlobscanProvider {
+    fn name(&self) -> &str {
+        "blobscan"
+    }
+
+    fn get_blob(&self, _slot: u64, blob_hash: B256) -> BoxFuture<'_, Result<BlobSidecar>> {
+        Box::pin(async move {
+            let url = format!("{}/blobs/{blob_hash}", self.url.trim_end_matches('/'));
+            let response = reqwest::get(url).await?;
+            if !response.status().is_success() {
+                bail!("Request failed with status code: {}", response.status());
+            }
+            let blob: BlobscanBlob = response.json().await?;
+            Ok(BlobSidecar {
+                blob: blob.data,
+                kzg_commitment: blob.commitment,
+                kzg_proof: blob.proof,
+            })
+        })
+    }
+}
+
+/// A directory of blob sidecars, one JSON file per blob named after its versioned hash.
+pub struct ArchiveProvider {
+    dir: PathBuf,
+}
+
+impl ArchiveProvider {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, blob_hash: &B256) -> PathBuf {
+        self.dir.join(format!("{blob_hash:x}.json"))
+    }
+
+    /// Add the blob to the archive.
+    pub fn store(&self, sidecar: &BlobSidecar) -> Result<()> {
+        let path = self.path(&sidecar.versioned_hash());
+        if path.exists() {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.dir)?;
+        // Write to a temporary file first, so a crash never leaves a partial blob behind.
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec(sidecar)?)?;
+        std::fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+}
+
+impl BlobProvider for ArchiveProvider {
+    fn name(&self) -> &str {
+        "archive"
+    }
+
+    fn get_blob(&self, _slot: u64, blob_hash: B256) -> BoxFuture<'_, Result<BlobSidecar>> {
+        Box::pin(async move {
+            let path = self.path(&blob_hash);
+            if !path.exists() {
+                bail!("Blob {blob_hash} is not archived");
+            }
+            Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+        })
+    }
+}
+
+/// The blob sources of a chain in their priority order.
+pub struct BlobProviders {
+    providers: Vec<Box<dyn BlobProvider>>,
+    archive: Option<ArchiveProvider>,
+}
+
+impl BlobProviders {
+    /// Use the given sources in the given order, storing the fetched blobs in `archive`.
+    pub fn new(providers: Vec<Box<dyn BlobProvider>>, archive: Option<ArchiveProvider>) -> Self {
+        Self { providers, archive }
+    }
+
+    /// Get the blob with the given versioned hash from the first source which has it. Every
+    /// source is retried a few times before falling back to the next one.
+    pub async fn get_blob(&self, slot: u64, blob_hash: B256) -> Result<BlobSidecar> {
+        let mut errors = Vec::new();
+        for provider in &self.providers {
+            for attempt in 1..=BLOB_FETCH_ATTEMPTS {
+                if attempt > 1 {
+                    tokio::time::sleep(BLOB_FETCH_BACKOFF * 2u32.pow(attempt - 2)).await;
+                }
+                let sidecar = match provider.get_blob(slot, blob_hash).await {
+                    Ok(sidecar) => sidecar,
+                    Err(e) => {
+                        debug!(
+                            "Could not get blob {blob_hash} from {} (attempt {attempt}): {e}",
+                            provider.name()
+                        );
+                        errors.push(format!("{}: {e}", provider.name()));
+                        continue;
+                    }
+                };
+                // A source returning another blob won't do better on the next attempt.
+                if let Err(e) = sidecar.verify(blob_hash) {
+                    warn!("Got the wrong blob from {}: {e}", provider.name());
+                    errors.push(format!("{}: {e}", provider.name()));
+                    break;
+                }
+                if let Some(archive) = &self.archive {
+                    if let Err(e) = archive.store(&sidecar) {
+                        warn!("Could not archive blob {blob_hash}: {e}");
+                    }
+                }
+                return Ok(sidecar);
+            }
+        }
+        Err(anyhow!(
+            "Could not get blob {blob_hash} from any source: {}",
+            errors.join(", ")
+        ))
+    }
+}
+
+#[derive(Clone, Debug)]
+struct BlobConfig {
+    sources: Vec<BlobSource>,
+    archive_dir: Option<PathBuf>,
+}
+
+lazy_static! {
+    static ref CONFIG: RwLock<BlobConfig> = RwLock::new(BlobConfig {
+        sources: vec![BlobSource::Beacon],
+        archive_dir: None,
+    });
+}
+
+/// Set the priority order of the blob sources and the directory of the blob archive.
+pub fn configure(sources: Vec<BlobSource>, archive_dir: Option<PathBuf>) {
+    *CONFIG.write().unwrap() = BlobConfig {
+        sources,
+        archive_dir,
+    };
+}
+
+/// Get the configured blob sources for a chain with the given beacon node and blobscan API.
+/// Sources the chain doesn't have are skipped.
+pub fn get_providers(beacon_url: Option<String>, blobscan_url: Option<String>) -> BlobProviders {
+    let config = CONFIG.read().unwrap().clone();
+    let mut providers: Vec<Box<dyn BlobProvider>> = Vec::new();
+    for source in config.sources {
+        match source {
+            BlobSource::Beacon => {
+                if let Some(url) = &beacon_url {
+                    providers.push(Box::new(BeaconProvider::new(url.clone())));
+                }
+            }
+            BlobSource::Blobscan => {
+                if let Some(url) = &blobscan_url {
+                    providers.push(Box::new(BlobscanProvider::new(url.clone())));
+                }
+            }
+            BlobSource::Archive => {
+                if let Some(dir) = &config.archive_dir {
+                    providers.push(Box::new(ArchiveProvider::new(dir.clone())));
+                }
+            }
+        }
+    }
+    BlobProviders::new(providers, config.archive_dir.map(ArchiveProvider::new))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    struct FlakyProvider {
+        sidecar: BlobSidecar,
+        failures: u32,
+        calls: AtomicU32,
+    }
+
+    impl BlobProvider for FlakyProvider {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        fn get_blob(&self, _slot: u64, _blob_hash: B256) -> BoxFuture<'_, Result<BlobSidecar>> {
+            Box::pin(async move {
+                if self.calls.fetch_add(1, Ordering::Relaxed) < self.failures {
+                    bail!("unavailable");
+                }
+                Ok(self.sidecar.clone())
+            })
+        }
+    }
+
+    fn sidecar(byte: u8) -> BlobSidecar {
+        use raiko_primitives::{blob_kzg::BYTES_PER_BLOB, kzg::KzgCommitment};
+
+        let mut blob = vec![0u8; BYTES_PER_BLOB];
+        for element in blob.chunks_exact_mut(32) {
+            // Keep the top byte zero so every element is below the modulus.
+            element[1] = byte;
+        }
+        let settings = &*MAINNET_KZG_TRUSTED_SETUP;
+        let kzg_blob = Blob::from_bytes(&blob).unwrap();
+        let commitment = KzgCommitment::blob_to_kzg_commitment(&kzg_blob, settings)
+            .unwrap()
+            .to_bytes();
+        let proof = KzgProof::compute_blob_kzg_proof(&kzg_blob, &commitment, settings)
+            .unwrap()
+            .to_bytes();
+        BlobSidecar {
+            blob: Bytes::from(blob),
+            kzg_commitment: FixedBytes::from_slice(commitment.as_slice()),
+            kzg_proof: FixedBytes::from_slice(proof.as_slice()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_and_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let expected = sidecar(1);
+        let blob_hash = expected.versioned_hash();
+        let mut tampered = expected.clone();
+        tampered.blob = sidecar(2).blob;
+        assert!(tampered.verify(blob_hash).is_err());
+        let providers = BlobProviders::new(
+            vec![
+                Box::new(ArchiveProvider::new(dir.path().to_path_buf())),
+                // Always returns another blob, so it is skipped after the first attempt.
+                Box::new(FlakyProvider {
+                    sidecar: sidecar(2),
+                    failures: 0,
+                    calls: AtomicU32::new(0),
+                }),
+                // Returns another blob with the commitment of the blob.
+                Box::new(FlakyProvider {
+                    sidecar: tampered,
+                    failures: 0,
+                    calls: AtomicU32::new(0),
+                }),
+                // Succeeds on the last attempt.
+                Box::new(FlakyProvider {
+                    sidecar: expected.clone(),
+                    failures: BLOB_FETCH_ATTEMPTS - 1,
+                    calls: AtomicU32::new(0),
+                }),
+            ],
+            Some(ArchiveProvider::new(dir.path().to_path_buf())),
+        );
+        assert_eq!(providers.get_blob(0, blob_hash).await.unwrap(), expected);
+
+        // The blob was archived, so the archive has it from now on.
+        let archive = ArchiveProvider::new(dir.path().to_path_buf());
+        assert_eq!(archive.get_blob(0, blob_hash).await.unwrap(), expected);
+        assert!(providers.get_blob(0, B256::ZERO).await.is_err());
+    }
+}
@@ -0,0 +1,368 @@
+//! Circuit breakers of the backends of the host.
+//!
+//! A remote prover like Bonsai or the SP1 network, or all the nodes of a network, can be down
+//! for a while. Instead of every job waiting for its own timeout and failing, with
+//! `circuit_breaker` in the config file, e.g. `{"failure_threshold": 5, "open_duration": 60}`,
+//! a breaker stops dispatching to a backend after `failure_threshold` consecutive failures.
+//! While it is open, the requests to the backend fail right away with
+//! [HostError::CircuitOpen]. After `open_duration` seconds a single request is let through to
+//! probe the backend, which closes the breaker when it succeeds and opens it again otherwise.
+//!
+//! Every proof type has a breaker around its prover, `prover:<proof type>`, and every network
+//! one around its preflight, `network:<network>`. Only the errors of the backend count as
+//! failures, not the ones of the request itself, e.g. a failed validation.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    error::{HostError, HostResult},
+    metrics::{inc_circuit_breaker_trips, set_circuit_breaker_state},
+    request::ProofType,
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+/// When the circuit breakers open and for how long.
+pub struct BreakerConfig {
+    /// The consecutive failures of a backend after which its breaker opens.
+    pub failure_threshold: u32,
+    /// Seconds a breaker stays open before it lets a request through to probe the backend.
+    pub open_duration: u64,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_duration: 60,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// The requests are dispatched to the backend.
+    Closed,
+    /// A single request probes whether the backend recovered.
+    HalfOpen,
+    /// The requests to the backend are rejected.
+    Open,
+}
+
+impl BreakerState {
+    /// The value of the state in the `circuit_breaker_state` metric.
+    fn metric(&self) -> i64 {
+        match self {
+            BreakerState::Closed => 0,
+            BreakerState::HalfOpen => 1,
+            BreakerState::Open => 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// The state of the circuit breaker of a backend.
+pub struct BreakerStatus {
+    /// The backend, e.g. `prover:risc0` or `network:taiko_a7`.
+    pub name: String,
+    pub state: BreakerState,
+    /// The consecutive failures of the backend.
+    pub failures: u32,
+    /// Seconds until the open breaker lets a request through to probe the backend.
+    pub retry_after: Option<u64>,
+}
+
+/// How a request through a breaker went.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Outcome {
+    Success,
+    Failure,
+    /// The request failed by itself or was aborted, which says nothing about the backend.
+    Ignored,
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: BreakerState,
+    failures: u32,
+    opened_at: Instant,
+}
+
+impl Default for Breaker {
+    fn default() -> Self {
+        Self {
+            state: BreakerState::Closed,
+            failures: 0,
+            opened_at: Instant::now(),
+        }
+    }
+}
+
+impl Breaker {
+    /// Seconds until the open breaker probes the backend, at least 1.
+    fn retry_after(&self, config: &BreakerConfig, now: Instant) -> u64 {
+        let open_duration = Duration::from_secs(config.open_duration);
+        let remaining = open_duration.saturating_sub(now.duration_since(self.opened_at));
+        remaining.as_secs_f64().ceil().max(1.0) as u64
+    }
+
+    fn open(&mut self, name: &str, now: Instant) {
+        self.state = BreakerState::Open;
+        self.opened_at = now;
+        inc_circuit_breaker_trips(name);
+    }
+}
+
+#[derive(Debug, Default)]
+/// The breakers of the backends by name.
+struct Breakers(BTreeMap<String, Breaker>);
+
+impl Breakers {
+    /// Let a request through the breaker. Returns whether it probes the open breaker, or the
+    /// seconds to retry after when it is rejected.
+    fn acquire(&mut self, name: &str, config: &BreakerConfig, now: Instant) -> Result<bool, u64> {
+        let breaker = self.0.entry(name.to_string()).or_default();
+        match breaker.state {
+            BreakerState::Closed => Ok(false),
+            BreakerState::Open
+                if now.duration_since(breaker.opened_at).as_secs() >= config.open_duration =>
+            {
+                info!("Probing the backend of the open circuit breaker {name}");
+                breaker.state = BreakerState::HalfOpen;
+                set_circuit_breaker_state(name, breaker.state.metric());
+                Ok(true)
+            }
+            BreakerState::Open => Err(breaker.retry_after(config, now)),
+            // The probe may take as long as a proof, so check back after another period.
+            BreakerState::HalfOpen => Err(config.open_duration.max(1)),
+        }
+    }
+
+    /// Record the outcome of a request let through the breaker.
+    fn record(
+        &mut self,
+        name: &str,
+        config: &BreakerConfig,
+        probe: bool,
+        outcome: Outcome,
+        now: Instant,
+    ) {
+        let Some(breaker) = self.0.get_mut(name) else {
+            return;
+        };
+        match outcome {
+            Outcome::Success => {
+                if breaker.state != BreakerState::Closed {
+                    info!("The backend of the circuit breaker {name} recovered, closing it");
+                }
+                breaker.state = BreakerState::Closed;
+                breaker.failures = 0;
+            }
+            Outcome::Failure if probe => {
+                warn!("The probe of the circuit breaker {name} failed, opening it again");
+                breaker.failures += 1;
+                breaker.open(name, now);
+            }
+            Outcome::Failure => {
+                breaker.failures += 1;
+                if breaker.state == BreakerState::Closed
+                    && breaker.failures >= config.failure_threshold
+                {
+                    warn!(
+                        "{} consecutive failures of the backend of the circuit breaker {name}, \
+                         opening it for {}s",
+                        breaker.failures, config.open_duration
+                    );
+                    breaker.open(name, now);
+                }
+            }
+            // Let the next request probe the backend instead.
+            Outcome::Ignored if probe && breaker.state == BreakerState::HalfOpen => {
+                breaker.state = BreakerState::Open;
+            }
+            Outcome::Ignored => {}
+        }
+        set_circuit_breaker_state(name, breaker.state.metric());
+    }
+
+    fn statuses(&self, config: &BreakerConfig, now: Instant) -> Vec<BreakerStatus> {
+        self.0
+            .iter()
+            .map(|(name, breaker)| BreakerStatus {
+                name: name.clone(),
+                state: breaker.state,
+                failures: breaker.failures,
+                retry_after: (breaker.state == BreakerState::Open)
+                    .then(|| breaker.retry_after(config, now)),
+            })
+            .collect()
+    }
+}
+
+lazy_static! {
+    static ref CONFIG: RwLock<Option<BreakerConfig>> = RwLock::new(None);
+    static ref BREAKERS: Mutex<Breakers> = Mutex::new(Breakers::default());
+}
+
+/// Set when the circuit breakers open, `None` disables them.
+pub fn configure(config: Option<BreakerConfig>) {
+    *CONFIG.write().unwrap() = config;
+}
+
+/// The name of the breaker of the prover of the proof type.
+pub fn prover(proof_type: &ProofType) -> String {
+    format!("prover:{proof_type}")
+}
+
+/// The name of the breaker of the nodes of the network.
+pub fn network(network: &str) -> String {
+    format!("network:{network}")
+}
+
+/// A request let through a breaker, which records its outcome with [Permit::record]. A
+/// permit dropped without it, e.g. by an aborted job, doesn't count.
+pub struct Permit {
+    /// The name of the breaker, unset when the breakers are disabled.
+    name: Option<String>,
+    probe: bool,
+    outcome: Outcome,
+}
+
+impl Permit {
+    /// Record the result of the request, its error counts as a failure of the backend unless
+    /// the request itself is at fault.
+    pub fn record<T>(mut self, result: &HostResult<T>) {
+        self.outcome = match result {
+            Ok(_) => Outcome::Success,
+            Err(e) if is_failure(e) => Outcome::Failure,
+            Err(_) => Outcome::Ignored,
+        };
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let (Some(name), Some(config)) = (&self.name, CONFIG.read().unwrap().clone()) else {
+            return;
+        };
+        BREAKERS
+            .lock()
+            .unwrap()
+            .record(name, &config, self.probe, self.outcome, Instant::now());
+    }
+}
+
+/// Let a request through the breaker of the given name, failing with
+/// [HostError::CircuitOpen] while it is open.
+pub fn acquire(name: String) -> HostResult<Permit> {
+    let Some(config) = CONFIG.read().unwrap().clone() else {
+        return Ok(Permit {
+            name: None,
+            probe: false,
+            outcome: Outcome::Ignored,
+        });
+    };
+    let probe = BREAKERS
+        .lock()
+        .unwrap()
+        .acquire(&name, &config, Instant::now())
+        .map_err(|retry_after| HostError::CircuitOpen(name.clone(), retry_after))?;
+    Ok(Permit {
+        name: Some(name),
+        probe,
+        outcome: Outcome::Ignored,
+    })
+}
+
+/// The state of the breakers of the backends used since the start of the host.
+pub fn statuses() -> Vec<BreakerStatus> {
+    let Some(config) = CONFIG.read().unwrap().clone() else {
+        return Vec::new();
+    };
+    BREAKERS.lock().unwrap().statuses(&config, Instant::now())
+}
+
+/// Whether the error is a failure of the backend, rather than of the request.
+fn is_failure(error: &HostError) -> bool {
+    match error {
+        // Mostly failing RPC requests and remote provers.
+        HostError::Io(_) | HostError::Anyhow(_) | HostError::GuestError(_) => true,
+        HostError::JoinHandle(e) => !e.is_cancelled(),
+        HostError::ProverCrashed(crash) => !crash.out_of_memory,
+        HostError::InvalidProofType(_)
+        | HostError::InvalidRequestConfig(_)
+        | HostError::InvalidAddress(_)
+        | HostError::InvalidQuote(_)
+        | HostError::Validation(_)
+        | HostError::JobNotFound(_)
+        | HostError::JobAlreadyFinished(_)
+        | HostError::BatchNotFound(_)
+        | HostError::BatchAlreadyFinished(_)
+        | HostError::Unauthorized(_)
+        | HostError::Forbidden(_)
+        | HostError::TooManyRequests(_)
+        | HostError::ShuttingDown
+        | HostError::QueueFull(_)
+        | HostError::CircuitOpen(..)
+        | HostError::ResourceExhausted(_)
+        | HostError::Serde(_)
+        | HostError::FeatureNotSupportedError(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaker() {
+        let config = BreakerConfig {
+            failure_threshold: 2,
+            open_duration: 60,
+        };
+        let name = "prover:risc0";
+        let mut breakers = Breakers::default();
+        let now = Instant::now();
+
+        // Closes again on a success before the threshold.
+        assert_eq!(breakers.acquire(name, &config, now), Ok(false));
+        breakers.record(name, &config, false, Outcome::Failure, now);
+        breakers.record(name, &config, false, Outcome::Success, now);
+        assert_eq!(breakers.statuses(&config, now)[0].failures, 0);
+
+        // Opens after the consecutive failures.
+        breakers.record(name, &config, false, Outcome::Failure, now);
+        breakers.record(name, &config, false, Outcome::Ignored, now);
+        breakers.record(name, &config, false, Outcome::Failure, now);
+        assert_eq!(breakers.statuses(&config, now)[0].state, BreakerState::Open);
+        let later = now + Duration::from_secs(20);
+        assert_eq!(breakers.acquire(name, &config, later), Err(40));
+
+        // Lets a single probe through after the open duration.
+        let later = now + Duration::from_secs(60);
+        assert_eq!(breakers.acquire(name, &config, later), Ok(true));
+        assert_eq!(breakers.acquire(name, &config, later), Err(60));
+        breakers.record(name, &config, true, Outcome::Failure, later);
+        assert_eq!(breakers.acquire(name, &config, later), Err(60));
+
+        // An aborted probe lets the next request probe.
+        let later = later + Duration::from_secs(60);
+        assert_eq!(breakers.acquire(name, &config, later), Ok(true));
+        breakers.record(name, &config, true, Outcome::Ignored, later);
+        assert_eq!(breakers.acquire(name, &config, later), Ok(true));
+        breakers.record(name, &config, true, Outcome::Success, later);
+        let status = &breakers.statuses(&config, later)[0];
+        assert_eq!(status.state, BreakerState::Closed);
+        assert_eq!(status.retry_after, None);
+        assert_eq!(breakers.acquire(name, &config, later), Ok(false));
+    }
+}
@@ -0,0 +1,223 @@
+//! The library API to prove blocks.
+//!
+//! [ProofBuilder] runs the same flow as a proof request to the host, the preflight, the
+//! prover and the verification of the proof, without the server, so other Rust services can
+//! embed raiko instead of sending requests to a running host:
+//!
+//! ```no_run
+//! # async fn run() -> raiko_host::error::HostResult<()> {
+//! use raiko_host::{builder::ProofBuilder, request::ProofType};
+//!
+//! let proof = ProofBuilder::new("taiko_a7")
+//!     .block(1000)
+//!     .proof_type(ProofType::Sgx)
+//!     .rpc("https://rpc.hekla.taiko.xyz")
+//!     .l1_rpc("https://ethereum-holesky-rpc.publicnode.com")
+//!     .beacon_rpc("https://ethereum-holesky-beacon-api.publicnode.com")
+//!     .prove()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The nodes of the chain are those of the [chain registry](crate::chain_registry) unless set
+//! on the builder, the built-in chains have none. Without an input or proof cache, every proof
+//! runs the preflight and the prover.
+
+use alloy_primitives::{Address, B256};
+use raiko_lib::{input::GuestInput, prover::Proof};
+use serde_json::Value;
+
+use crate::{
+    error::HostResult,
+    execution::{prepare_block_input, prove_block, prove_with_input},
+    input_cache::InputCache,
+    jobs::progress::ProgressSender,
+    proof_cache::ProofCache,
+    request::{ProofRequest, ProofRequestOpt, ProofType},
+};
+
+/// Builds and proves a proof request.
+#[derive(Clone, Debug)]
+pub struct ProofBuilder {
+    request: ProofRequestOpt,
+    prover_args: Vec<(String, Value)>,
+    input_cache: InputCache,
+    proof_cache: ProofCache,
+    progress: ProgressSender,
+}
+
+impl ProofBuilder {
+    /// Prove a block of the chain with the given name, see [crate::chain_registry]. The
+    /// graffiti and the prover address default to zero, the proof type to native.
+    pub fn new(chain: impl Into<String>) -> Self {
+        Self {
+            request: ProofRequestOpt {
+                network: Some(chain.into()),
+                graffiti: Some(B256::ZERO.to_string()),
+                prover: Some(Address::ZERO.to_string()),
+                proof_type: Some(ProofType::Native.to_string()),
+                ..Default::default()
+            },
+            prover_args: Vec::new(),
+            input_cache: InputCache::default(),
+            proof_cache: ProofCache::default(),
+            progress: ProgressSender::default(),
+        }
+    }
+
+    /// The number of the block to prove.
+    pub fn block(mut self, block_number: u64) -> Self {
+        self.request.block_number = Some(block_number);
+        self
+    }
+
+    /// The prover to prove the block with.
+    pub fn proof_type(mut self, proof_type: ProofType) -> Self {
+        self.request.proof_type = Some(proof_type.to_string());
+        self
+    }
+
+    /// The L2 node, instead of the node of the chain.
+    pub fn rpc(mut self, rpc: impl Into<String>) -> Self {
+        self.request.rpc = Some(rpc.into());
+        self
+    }
+
+    /// The L1 node, instead of the node of the chain.
+    pub fn l1_rpc(mut self, l1_rpc: impl Into<String>) -> Self {
+        self.request.l1_rpc = Some(l1_rpc.into());
+        self
+    }
+
+    /// The beacon node, instead of the node of the chain.
+    pub fn beacon_rpc(mut self, beacon_rpc: impl Into<String>) -> Self {
+        self.request.beacon_rpc = Some(beacon_rpc.into());
+        self
+    }
+
+    /// The graffiti of the protocol instance.
+    pub fn graffiti(mut self, graffiti: B256) -> Self {
+        self.request.graffiti = Some(graffiti.to_string());
+        self
+    }
+
+    /// The prover address of the protocol instance.
+    pub fn prover(mut self, prover: Address) -> Self {
+        self.request.prover = Some(prover.to_string());
+        self
+    }
+
+    /// The params of the prover with the given name, e.g. `{"instance_id": 1}` for `sgx`.
+    pub fn prover_args(mut self, prover: impl Into<String>, args: Value) -> Self {
+        self.prover_args.push((prover.into(), args));
+        self
+    }
+
+    /// Whether to wrap the proof into a SNARK that can be verified on-chain.
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.request.compress = Some(compress);
+        self
+    }
+
+    /// Prove the on-chain block while executing it natively, see [ProofRequest::cross_check].
+    pub fn cross_check(mut self, cross_check: bool) -> Self {
+        self.request.cross_check = Some(cross_check);
+        self
+    }
+
+    /// Generate a new proof even if a proof of the block is in the proof cache.
+    pub fn force(mut self, force: bool) -> Self {
+        self.request.force = Some(force);
+        self
+    }
+
+    /// Take the inputs from and keep them in the input cache.
+    pub fn input_cache(mut self, input_cache: InputCache) -> Self {
+        self.input_cache = input_cache;
+        self
+    }
+
+    /// Take the proofs from and keep them in the proof cache.
+    pub fn proof_cache(mut self, proof_cache: ProofCache) -> Self {
+        self.proof_cache = proof_cache;
+        self
+    }
+
+    /// Report the progress of the proof to the sender.
+    pub fn progress(mut self, progress: ProgressSender) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// The proof request of the builder.
+    pub fn request(&self) -> HostResult<ProofRequest> {
+        let mut request = ProofRequest::try_from(self.request.clone())?;
+        request.prover_args.extend(self.prover_args.iter().cloned());
+        Ok(request)
+    }
+
+    /// Prepare the input of the block with the preflight, without proving it.
+    pub async fn prepare(&self) -> HostResult<GuestInput> {
+        prepare_block_input(&self.request()?, &self.input_cache, &self.progress).await
+    }
+
+    /// Prove the block.
+    pub async fn prove(&self) -> HostResult<Proof> {
+        self.prove_block().await.map(|(_, proof)| proof)
+    }
+
+    /// Prove the block, returning its input with the proof unless the proof was taken from the
+    /// proof cache before the input was prepared.
+    pub async fn prove_block(&self) -> HostResult<(Option<GuestInput>, Proof)> {
+        prove_block(
+            &self.request()?,
+            &self.input_cache,
+            &self.proof_cache,
+            &self.progress,
+        )
+        .await
+    }
+
+    /// Prove the block with an input prepared before, e.g. with [ProofBuilder::prepare], which
+    /// waits for the block to be final.
+    pub async fn prove_input(&self, input: GuestInput) -> HostResult<Proof> {
+        prove_with_input(
+            &self.request()?,
+            &self.input_cache,
+            &self.proof_cache,
+            Some(input),
+            &self.progress,
+        )
+        .await
+        .map(|(_, proof)| proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request() {
+        let request = ProofBuilder::new("taiko_a7")
+            .block(1000)
+            .proof_type(ProofType::Sgx)
+            .rpc("http://localhost:8545")
+            .l1_rpc("http://localhost:8546")
+            .beacon_rpc("http://localhost:5052")
+            .prover_args("sgx", serde_json::json!({"instance_id": 1}))
+            .request()
+            .unwrap();
+        assert_eq!(request.network, "taiko_a7");
+        assert_eq!(request.block_number, 1000);
+        assert_eq!(request.proof_type, ProofType::Sgx);
+        assert_eq!(request.rpc, "http://localhost:8545");
+        assert_eq!(request.graffiti, B256::ZERO);
+        assert_eq!(request.prover_args["sgx"]["instance_id"], 1);
+
+        // The block and the nodes are required.
+        assert!(ProofBuilder::new("taiko_a7").block(1).request().is_err());
+        assert!(ProofBuilder::new("unknown").block(1).request().is_err());
+    }
+}
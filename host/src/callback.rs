@@ -0,0 +1,351 @@
+//! Callbacks on the completion of jobs.
+//!
+//! A proof request with a `callback_url` gets a `POST` to it once its job finished, succeeded,
+//! failed or was cancelled, with a [CallbackPayload] as the body. Callbacks that fail with a
+//! network error, a `429` or a `5xx` response are retried with backoff. With a callback secret,
+//! the body is signed so the receivers can authenticate the callbacks: the
+//! [SIGNATURE_HEADER] is `sha256=<hex HMAC-SHA256 of "<timestamp>.<body>">` with the secret as
+//! the key, and the [TIMESTAMP_HEADER] the unix timestamp it was signed at, so old callbacks
+//! can't be replayed.
+//!
+//! Callback URLs come from the clients, so they are only sent to public addresses, resolved
+//! once per callback and pinned for it, and redirects are not followed.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::RwLock,
+    time::Duration,
+};
+
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use raiko_lib::prover::Proof;
+use raiko_primitives::hex;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tracing::{info, warn};
+use url::{Host, Url};
+
+use crate::{
+    clock::unix_timestamp,
+    jobs::{Job, JobId, JobStatus},
+    retry::RetryPolicy,
+    upload::Upload,
+};
+
+/// The header with the signature of the body.
+pub const SIGNATURE_HEADER: &str = "X-Raiko-Signature";
+
+/// The header with the unix timestamp the body was signed at.
+pub const TIMESTAMP_HEADER: &str = "X-Raiko-Timestamp";
+
+/// How long a callback waits to connect to the receiver.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a callback waits for the receiver to answer, connecting included.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    static ref SECRET: RwLock<Option<String>> = RwLock::new(None);
+    static ref POLICY: RetryPolicy = RetryPolicy {
+        max_attempts: 5,
+        ..Default::default()
+    };
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// The body of the callback of a finished job.
+pub struct CallbackPayload {
+    /// The job identifier.
+    pub id: JobId,
+    /// The final state of the job.
+    pub status: JobStatus,
+    /// The name of the chain of the block.
+    pub network: String,
+    /// The number of the block.
+    pub block_number: u64,
+    /// The proof type.
+    pub proof_type: String,
+    /// The generated proof, set if the job succeeded.
+    pub proof: Option<Proof>,
+    /// The error message, set if the job failed.
+    pub error: Option<String>,
+    /// The objects the proof was uploaded to, if it was uploaded.
+    pub upload: Option<Upload>,
+    /// The trace id of the request that submitted the job.
+    pub trace_id: String,
+}
+
+impl From<&Job> for CallbackPayload {
+    fn from(job: &Job) -> Self {
+        Self {
+            id: job.id,
+            status: job.status,
+            network: job.request.network.clone(),
+            block_number: job.request.block_number,
+            proof_type: job.request.proof_type.to_string(),
+            proof: job.proof.clone(),
+            error: job.error.clone(),
+            upload: job.upload.clone(),
+            trace_id: job.trace_id.clone(),
+        }
+    }
+}
+
+/// Set the secret the callbacks are signed with, unset to send them unsigned.
+pub fn configure(secret: Option<String>) {
+    *SECRET.write().unwrap() = secret.filter(|secret| !secret.is_empty());
+}
+
+/// Check that the callback URL is an HTTP URL, and not one of the host or its network. Domains
+/// are checked again by their addresses when the callback is sent.
+pub fn validate_url(url: &str) -> Result<(), String> {
+    let url = Url::parse(url).map_err(|e| format!("Invalid callback URL: {e}"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "Invalid callback URL, expected http or https instead of {}",
+            url.scheme()
+        ));
+    }
+    let ip = match url.host() {
+        Some(Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            if domain == "localhost" || domain.ends_with(".localhost") {
+                return Err(format!("Invalid callback URL, {domain} is not public"));
+            }
+            return Ok(());
+        }
+        Some(Host::Ipv4(ip)) => IpAddr::V4(ip),
+        Some(Host::Ipv6(ip)) => IpAddr::V6(ip),
+        None => return Err("Invalid callback URL, it has no host".to_string()),
+    };
+    if !is_public(ip) {
+        return Err(format!("Invalid callback URL, {ip} is not public"));
+    }
+    Ok(())
+}
+
+/// Whether the address is reachable on the public internet, so callbacks can't reach the host
+/// itself or the services of its network.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // This network, shared address space and reserved
+                || a == 0
+                || (a == 100 && (b & 0xc0) == 64)
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_unspecified()
+                    || ip.is_loopback()
+                    || ip.is_multicast()
+                    // Unique local and link local
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Resolve the host of the URL, failing if any of its addresses is not public.
+async fn public_addrs(url: &Url) -> Result<Vec<SocketAddr>, String> {
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs: Vec<SocketAddr> = match url.host() {
+        Some(Host::Domain(domain)) => tokio::net::lookup_host((domain, port))
+            .await
+            .map_err(|e| format!("Could not resolve {domain}: {e}"))?
+            .collect(),
+        Some(Host::Ipv4(ip)) => vec![SocketAddr::new(ip.into(), port)],
+        Some(Host::Ipv6(ip)) => vec![SocketAddr::new(ip.into(), port)],
+        None => return Err("the URL has no host".to_string()),
+    };
+    if addrs.is_empty() {
+        return Err(format!("{url} resolves to no address"));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+        return Err(format!(
+            "{url} resolves to {}, which is not public",
+            addr.ip()
+        ));
+    }
+    Ok(addrs)
+}
+
+/// The client of the callbacks, which doesn't follow redirects so a receiver can't send the
+/// callback elsewhere.
+fn client_builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+}
+
+/// Send the callback of the finished job in the background, if it has a callback URL.
+pub fn notify(job: &Job) {
+    let Some(url) = job.request.callback_url.clone() else {
+        return;
+    };
+    let payload = CallbackPayload::from(job);
+    tokio::spawn(async move {
+        if let Err(e) = send(&url, &payload).await {
+            warn!(
+                "Could not send the callback of job {} to {url}: {e}",
+                payload.id
+            );
+        }
+    });
+}
+
+/// The hex encoded HMAC-SHA256 of the message with the key.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(message);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// The signature of the body at the timestamp.
+fn signature(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let message = [timestamp.to_string().as_bytes(), b".", body].concat();
+    format!("sha256={}", hmac_sha256(secret.as_bytes(), &message))
+}
+
+/// Whether a callback answered with the status may succeed when sent again.
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+async fn send(url: &str, payload: &CallbackPayload) -> anyhow::Result<()> {
+    let parsed = Url::parse(url)?;
+    // Connect to the checked addresses, not to the ones the domain resolves to later on
+    let addrs = public_addrs(&parsed).await.map_err(anyhow::Error::msg)?;
+    let mut client = client_builder();
+    if let Some(Host::Domain(domain)) = parsed.host() {
+        client = client.resolve_to_addrs(domain, &addrs);
+    }
+    let what = format!("callback of job {}", payload.id);
+    post_with(&client.build()?, url, payload, &what).await
+}
+
+/// Post the body to the URL, signed like the callbacks, and retry it like them. `what` names
+/// the body in the logs. Only for URLs of the config, the URLs of the clients aren't checked.
+pub async fn post(url: &str, body: &impl Serialize, what: &str) -> anyhow::Result<()> {
+    post_with(&client_builder().build()?, url, body, what).await
+}
+
+async fn post_with(
+    client: &reqwest::Client,
+    url: &str,
+    body: &impl Serialize,
+    what: &str,
+) -> anyhow::Result<()> {
+    let body = serde_json::to_vec(body)?;
+    let secret = SECRET.read().unwrap().clone();
+    let mut attempt = 1;
+    loop {
+        let mut request = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.clone());
+        if let Some(secret) = &secret {
+            let timestamp = unix_timestamp();
+            request = request
+                .header(TIMESTAMP_HEADER, timestamp)
+                .header(SIGNATURE_HEADER, signature(secret, timestamp, &body));
+        }
+        let error = match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                info!("Sent the {what} to {url}");
+                return Ok(());
+            }
+            Ok(response) if !is_retryable(response.status()) => {
+                anyhow::bail!("the receiver answered {}", response.status())
+            }
+            Ok(response) => format!("the receiver answered {}", response.status()),
+            Err(e) => e.to_string(),
+        };
+        if attempt >= POLICY.max_attempts {
+            anyhow::bail!("{error}, after {attempt} attempts");
+        }
+        let backoff = POLICY.backoff(attempt);
+        warn!(
+            "Sending the {what} failed, retry {attempt}/{} in {backoff:?}: {error}",
+            POLICY.max_attempts - 1
+        );
+        tokio::time::sleep(backoff).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            hmac_sha256(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            signature("Jefe", 1700000000, b"{}"),
+            format!("sha256={}", hmac_sha256(b"Jefe", b"1700000000.{}"))
+        );
+
+        assert!(validate_url("https://example.com/callback").is_ok());
+        assert!(validate_url("ftp://example.com").is_err());
+        assert!(validate_url("not a url").is_err());
+        assert!(validate_url("http://localhost:8080/callback").is_err());
+        assert!(validate_url("http://127.0.0.1/callback").is_err());
+        assert!(validate_url("http://[::ffff:10.0.0.1]/callback").is_err());
+        assert!(validate_url("http://1.1.1.1/callback").is_ok());
+        assert!(is_retryable(StatusCode::BAD_GATEWAY));
+        assert!(!is_retryable(StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn test_is_public() {
+        for ip in ["8.8.8.8", "2606:4700:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "0.0.0.0",
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "255.255.255.255",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_public_addrs() {
+        let url = Url::parse("http://1.1.1.1:8080/callback").unwrap();
+        assert_eq!(
+            public_addrs(&url).await.unwrap(),
+            vec!["1.1.1.1:8080".parse().unwrap()]
+        );
+        let url = Url::parse("http://169.254.169.254/latest").unwrap();
+        assert!(public_addrs(&url).await.is_err());
+    }
+}
@@ -0,0 +1,254 @@
+//! The calldata of `TaikoL1.proveBlock`.
+//!
+//! A proof is submitted on-chain as `proveBlock(blockId, input)`, with the input the ABI
+//! encoded block metadata, the transition and the tier proof. The data of the tier proof
+//! depends on the tier: the SGX tier takes the instance id, the new instance address and the
+//! signature of the enclave, see [SgxProof], the zk tiers take the seal of the prover as is.
+
+use alloy_primitives::{Address, Bytes, Signature};
+use alloy_sol_types::{SolCall, SolValue};
+use raiko_client::ErrorCode;
+use raiko_lib::{
+    input::{proveBlockCall, BlockMetadata, TierProof, Transition},
+    protocol_instance::SGX_PROOF_LEN,
+    prover::Proof,
+};
+use raiko_primitives::hex;
+
+use crate::error::{HostError, HostResult};
+
+#[derive(Clone, Debug)]
+/// A call of `proveBlock`.
+pub struct ProveBlockCall {
+    /// The id of the block, the block number on L2.
+    pub block_id: u64,
+    /// The metadata of the block, as proposed.
+    pub meta: BlockMetadata,
+    /// The transition the proof is for.
+    pub transition: Transition,
+    /// The tier of the proof.
+    pub tier: u16,
+    /// The data of the tier proof, e.g. an [SgxProof] or the seal of a zk prover.
+    pub data: Bytes,
+}
+
+impl ProveBlockCall {
+    /// The call submitting the on-chain proof of a proof generated by the host.
+    pub fn from_proof(
+        block_id: u64,
+        meta: BlockMetadata,
+        transition: Transition,
+        tier: u16,
+        proof: &Proof,
+    ) -> HostResult<Self> {
+        let data = proof
+            .get("proof")
+            .and_then(|data| data.as_str())
+            .ok_or_else(|| {
+                HostError::invalid(
+                    ErrorCode::MissingField,
+                    "proof",
+                    "The proof has no on-chain proof",
+                )
+            })?;
+        let data = hex::decode(data.trim_start_matches("0x")).map_err(|e| {
+            HostError::invalid(
+                ErrorCode::InvalidHex,
+                "proof",
+                format!("The on-chain proof is not hex encoded: {e}"),
+            )
+        })?;
+        Ok(Self {
+            block_id,
+            meta,
+            transition,
+            tier,
+            data: data.into(),
+        })
+    }
+
+    /// The `input` param of the call.
+    pub fn input(&self) -> Bytes {
+        let tier_proof = TierProof {
+            tier: self.tier,
+            data: self.data.clone(),
+        };
+        (self.meta.clone(), self.transition.clone(), tier_proof)
+            .abi_encode_params()
+            .into()
+    }
+
+    /// The calldata of the call, with the selector.
+    pub fn encode(&self) -> Bytes {
+        proveBlockCall {
+            blockId: self.block_id,
+            input: self.input(),
+        }
+        .abi_encode()
+        .into()
+    }
+
+    /// Decode the calldata of a call, e.g. of a transaction on L1.
+    pub fn decode(calldata: &[u8]) -> HostResult<Self> {
+        let invalid = |e: alloy_sol_types::Error| {
+            HostError::invalid(
+                ErrorCode::MalformedBody,
+                "calldata",
+                format!("Not a call of proveBlock: {e}"),
+            )
+        };
+        let call = proveBlockCall::abi_decode(calldata, true).map_err(invalid)?;
+        let (meta, transition, tier_proof) =
+            <(BlockMetadata, Transition, TierProof)>::abi_decode_params(&call.input, true)
+                .map_err(invalid)?;
+        Ok(Self {
+            block_id: call.blockId,
+            meta,
+            transition,
+            tier: tier_proof.tier,
+            data: tier_proof.data,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The data of an SGX tier proof.
+pub struct SgxProof {
+    /// The id the instance is registered as on L1.
+    pub instance_id: u32,
+    /// The address of the instance that replaces the one that signed the proof.
+    pub new_instance: Address,
+    /// The signature of the public input hash by the instance.
+    pub signature: Signature,
+}
+
+impl SgxProof {
+    /// The data of the tier proof.
+    pub fn encode(&self) -> Bytes {
+        let mut data = Vec::with_capacity(SGX_PROOF_LEN);
+        data.extend_from_slice(&self.instance_id.to_be_bytes());
+        data.extend_from_slice(self.new_instance.as_slice());
+        data.extend_from_slice(&self.signature.as_bytes());
+        data.into()
+    }
+
+    /// Decode the data of a tier proof.
+    pub fn decode(data: &[u8]) -> HostResult<Self> {
+        if data.len() != SGX_PROOF_LEN {
+            return Err(HostError::invalid(
+                ErrorCode::MalformedBody,
+                "proof",
+                format!("{} bytes instead of {SGX_PROOF_LEN}", data.len()),
+            ));
+        }
+        let signature = Signature::try_from(&data[24..]).map_err(|e| {
+            HostError::invalid(
+                ErrorCode::MalformedBody,
+                "proof",
+                format!("Invalid signature: {e}"),
+            )
+        })?;
+        Ok(Self {
+            instance_id: u32::from_be_bytes(data[..4].try_into().unwrap()),
+            new_instance: Address::from_slice(&data[4..24]),
+            signature,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{address, b256, B256};
+
+    use super::*;
+
+    /// The calldata of a `proveBlock` transaction of block 73 on a Taiko devnet, the
+    /// transaction `test_eip712_pi_hash` of `raiko_lib::protocol_instance` checks the public
+    /// input hash of.
+    const PROVE_BLOCK_TX: &str = "10d008bd000000000000000000000000000000000000000000000000000000000000004900000000000000000000000000000000000000000000000000000000000000400000000000000000000000000000000000000000000000000000000000000340689c98d83627e8749504eb6effbc2b08408183f11211bbf8bd281727b16255e6b3f8ee61d80cd7d30cdde9aa49acac0b82264a6b0f992139398e95636e501fd80189249f72753bd6c715511cc61facdec4781d4ecb1d028dafdff4a0827d7d53302e31382e302d64657600000000000000000000000000000000000000000000569e75fc77c1a856f6daaf9e69d8a9566ca34aa47f9133711ce065a571af0cfd00000000000000000000000016700100000000000000000000000000000100010000000000000000000000000000000000000000000000000000000000000049000000000000000000000000000000000000000000000000000000000e4e1c000000000000000000000000000000000000000000000000000000000065f94010000000000000000000000000000000000000000000000000000000000000036000000000000000000000000000000000000000000000000000000000000000640000000000000000000000000000000000000000000000000000000000000001fdbdc45da60168ddf29b246eb9e0a2e612a670f671c6d3aafdfdac21f86b4bca0000000000000000000000003c44cdddb6a900fa2b585dd299e03d12fa4293bcaf73b06ee94a454236314610c55e053df3af4402081df52c9ff2692349a6b497bc17a6706bc1cf4c363e800d2133d0d143363871d9c17b8fc5cf6d3cfd585bc80730a40cf8d8186241d45e19785c117956de919999d50e473aaa794b8fd4097000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000260000000000000000000000000000000000000000000000000000000000000006400000000000000000000000000000000000000000000000000000000000000400000000000000000000000000000000000000000000000000000000000000064ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff00000000000000000000000000000000000000000000000000000000";
+
+    #[test]
+    fn test_prove_block_call() {
+        let calldata = hex::decode(PROVE_BLOCK_TX).unwrap();
+        let call = ProveBlockCall::decode(&calldata).unwrap();
+        assert_eq!(call.block_id, 73);
+        assert_eq!(call.meta.id, 73);
+        assert_eq!(
+            call.meta.sender,
+            address!("3c44cdddb6a900fa2b585dd299e03d12fa4293bc")
+        );
+        assert_eq!(
+            call.transition.blockHash,
+            b256!("bc17a6706bc1cf4c363e800d2133d0d143363871d9c17b8fc5cf6d3cfd585bc8")
+        );
+        assert_eq!(call.tier, 100);
+        assert_eq!(call.data, Bytes::from(vec![0xff; 100]));
+        // Encoding the decoded call gives the transaction back.
+        assert_eq!(call.encode(), Bytes::from(calldata));
+
+        let proof = serde_json::json!({"proof": "0x0102"});
+        let call = ProveBlockCall::from_proof(73, call.meta, call.transition, 200, &proof).unwrap();
+        assert_eq!(call.data, Bytes::from(vec![1, 2]));
+        let decoded = ProveBlockCall::decode(&call.encode()).unwrap();
+        assert_eq!(decoded.tier, 200);
+        assert_eq!(decoded.data, call.data);
+        assert!(ProveBlockCall::from_proof(
+            73,
+            decoded.meta,
+            decoded.transition,
+            200,
+            &serde_json::json!({})
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sgx_tier_call() {
+        use alloy_signer::Signer;
+        use alloy_signer_wallet::LocalWallet;
+        use raiko_primitives::keccak::keccak;
+
+        // The devnet call with an SGX proof signed by an instance instead of its tier proof.
+        let call = ProveBlockCall::decode(&hex::decode(PROVE_BLOCK_TX).unwrap()).unwrap();
+        let wallet = LocalWallet::random();
+        let pi_hash: B256 = keccak(call.meta.abi_encode()).into();
+        let proof = SgxProof {
+            instance_id: 3,
+            new_instance: wallet.address(),
+            signature: wallet.sign_hash(&pi_hash).await.unwrap(),
+        };
+        let call = ProveBlockCall {
+            tier: 200,
+            data: proof.encode(),
+            ..call
+        };
+        assert_eq!(call.data.len(), SGX_PROOF_LEN);
+
+        let decoded = ProveBlockCall::decode(&call.encode()).unwrap();
+        assert_eq!(decoded.block_id, 73);
+        assert_eq!(decoded.tier, 200);
+        let decoded = SgxProof::decode(&decoded.data).unwrap();
+        assert_eq!(decoded, proof);
+        assert_eq!(
+            decoded
+                .signature
+                .recover_address_from_prehash(&pi_hash)
+                .unwrap(),
+            wallet.address()
+        );
+    }
+
+    #[test]
+    fn test_sgx_proof() {
+        let mut data = vec![0, 0, 0, 7];
+        data.extend_from_slice(&[0x11; 20]);
+        data.extend_from_slice(&[0x22; 32]);
+        data.extend_from_slice(&[0x33; 32]);
+        data.push(27);
+        let proof = SgxProof::decode(&data).unwrap();
+        assert_eq!(proof.instance_id, 7);
+        assert_eq!(proof.new_instance, Address::repeat_byte(0x11));
+        assert_eq!(proof.encode(), Bytes::from(data.clone()));
+        assert!(SgxProof::decode(&data[1..]).is_err());
+    }
+}
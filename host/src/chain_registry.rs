@@ -0,0 +1,345 @@
+//! The registry of chains the host can generate proofs for.
+//!
+//! The networks supported by raiko itself are always available. Other deployments, e.g. a
+//! devnet running the protocol of one of these networks with its own contracts, are loaded
+//! from a chain spec file with [load_chains], or from a directory with one JSON or TOML file
+//! per chain with [load_chain_dir]. Proof requests select the chain by its name as `network`,
+//! and the RPC endpoints of the chain are used unless the request sets its own. When the
+//! config is reloaded, all chains are replaced at once with [resolve_chains] and
+//! [replace_chains].
+
+use std::{collections::BTreeMap, path::Path, sync::RwLock};
+
+use lazy_static::lazy_static;
+use raiko_client::ErrorCode;
+use raiko_lib::consts::{
+    get_network_spec, ChainSpec, Network, ETH_HOLESKY_CHAIN_SPEC, ETH_MAINNET_CHAIN_SPEC,
+    TAIKO_A6_CHAIN_SPEC, TAIKO_A7_CHAIN_SPEC,
+};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use tracing::info;
+
+use crate::{
+    error::{HostError, HostResult},
+    preflight::PreflightStrategy,
+};
+
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// A chain the host can generate proofs for.
+pub struct ChainConfig {
+    /// The name of the chain, used as the network in proof requests. Defaults to the file
+    /// name for chains loaded from a chain spec directory.
+    #[serde(default)]
+    pub name: String,
+    /// The protocol the chain is running, e.g. `taiko_a7` for a devnet of the A7 protocol.
+    #[serde_as(as = "DisplayFromStr")]
+    pub network: Network,
+    /// The L1 network of the chain.
+    #[serde(default)]
+    pub l1_network: Option<String>,
+    /// The default RPC URL of the chain.
+    #[serde(default)]
+    pub rpc: Option<String>,
+    /// The default L1 node URL.
+    #[serde(default)]
+    pub l1_rpc: Option<String>,
+    /// The default beacon node URL.
+    #[serde(default)]
+    pub beacon_rpc: Option<String>,
+    /// More RPC URLs of the chain, the preflight fails over to these.
+    #[serde(default)]
+    pub rpc_fallbacks: Vec<String>,
+    /// More L1 node URLs, the preflight fails over to these.
+    #[serde(default)]
+    pub l1_rpc_fallbacks: Vec<String>,
+    /// More beacon node URLs, the preflight fails over to these.
+    #[serde(default)]
+    pub beacon_rpc_fallbacks: Vec<String>,
+    /// The blobscan API of the L1, used for blobs the beacon nodes pruned already.
+    #[serde(default)]
+    pub blobscan_url: Option<String>,
+    /// How the preflight finds the state accessed by a block. Tracing needs the `debug`
+    /// namespace on the RPC node.
+    #[serde(default)]
+    pub preflight: PreflightStrategy,
+    /// The proving window of each proof type in seconds, counted from the proposal of the
+    /// block, e.g. `{"sgx": 3600}`. Sets the deadline of the blocks proven by watchers and
+    /// bounds the deadlines of requests.
+    #[serde(default)]
+    pub proving_windows: BTreeMap<String, u64>,
+    /// The chain id, contract addresses and fork schedule of the chain.
+    pub chain_spec: ChainSpec,
+}
+
+impl ChainConfig {
+    fn builtin(network: Network, chain_spec: &ChainSpec, l1_network: Option<&str>) -> Self {
+        Self {
+            name: network.to_string(),
+            network,
+            l1_network: l1_network.map(str::to_string),
+            rpc: None,
+            l1_rpc: None,
+            beacon_rpc: None,
+            rpc_fallbacks: Vec::new(),
+            l1_rpc_fallbacks: Vec::new(),
+            beacon_rpc_fallbacks: Vec::new(),
+            blobscan_url: None,
+            preflight: PreflightStrategy::default(),
+            proving_windows: BTreeMap::new(),
+            chain_spec: chain_spec.clone(),
+        }
+    }
+
+    /// The end of the proving window of the proof type for a block proposed at the given unix
+    /// timestamp, if the chain has a window for it.
+    pub fn proving_deadline(&self, proof_type: &str, proposed_at: u64) -> Option<u64> {
+        self.proving_windows
+            .get(proof_type)
+            .map(|window| proposed_at.saturating_add(*window))
+    }
+
+    /// Clamp the deadline of a request to the end of the proving window of a block proposed
+    /// at the given unix timestamp, a proof after it is of no use.
+    pub fn clamp_deadline(
+        &self,
+        proof_type: &str,
+        deadline: Option<u64>,
+        proposed_at: u64,
+    ) -> Option<u64> {
+        match (deadline, self.proving_deadline(proof_type, proposed_at)) {
+            (Some(deadline), Some(window_end)) => Some(deadline.min(window_end)),
+            (deadline, _) => deadline,
+        }
+    }
+
+    /// Chains which don't set the Taiko forks of their protocol run it from genesis.
+    fn with_default_forks(mut self) -> Self {
+        if self.network.is_taiko() && self.chain_spec.taiko_forks.is_empty() {
+            self.chain_spec.taiko_forks = get_network_spec(self.network).taiko_forks;
+        }
+        self
+    }
+}
+
+lazy_static! {
+    static ref CHAINS: RwLock<BTreeMap<String, ChainConfig>> = RwLock::new(builtin_chains());
+}
+
+/// Load the chains from the JSON list at the given path. Chains with the name of an already
+/// registered chain replace it.
+pub fn load_chains(path: &Path) -> HostResult<()> {
+    read_chains(path)?.into_iter().for_each(register_chain);
+    Ok(())
+}
+
+/// Load the chains from the `.json` and `.toml` files in the given directory, one chain per
+/// file. Other files are ignored.
+pub fn load_chain_dir(dir: &Path) -> HostResult<()> {
+    read_chain_dir(dir)?.into_iter().for_each(register_chain);
+    Ok(())
+}
+
+/// The built-in chains and the chains loaded from the given path and directory, by name, to
+/// be registered with [replace_chains]. Fails if any of the chains is invalid.
+pub fn resolve_chains(
+    path: Option<&Path>,
+    dir: Option<&Path>,
+) -> HostResult<BTreeMap<String, ChainConfig>> {
+    let mut chains = builtin_chains();
+    let mut loaded = Vec::new();
+    if let Some(path) = path {
+        loaded.extend(read_chains(path)?);
+    }
+    if let Some(dir) = dir {
+        loaded.extend(read_chain_dir(dir)?);
+    }
+    for mut chain in loaded {
+        chain.name = chain.name.trim().to_lowercase();
+        chains.insert(chain.name.clone(), chain);
+    }
+    Ok(chains)
+}
+
+fn builtin_chains() -> BTreeMap<String, ChainConfig> {
+    [
+        ChainConfig::builtin(Network::Ethereum, &ETH_MAINNET_CHAIN_SPEC, None),
+        ChainConfig::builtin(Network::Holesky, &ETH_HOLESKY_CHAIN_SPEC, None),
+        ChainConfig::builtin(Network::TaikoA6, &TAIKO_A6_CHAIN_SPEC, Some("holesky")),
+        ChainConfig::builtin(Network::TaikoA7, &TAIKO_A7_CHAIN_SPEC, Some("holesky")),
+    ]
+    .into_iter()
+    .map(|chain| (chain.name.clone(), chain))
+    .collect()
+}
+
+fn read_chains(path: &Path) -> HostResult<Vec<ChainConfig>> {
+    let file = std::fs::File::open(path)?;
+    let chains: Vec<ChainConfig> = serde_json::from_reader(std::io::BufReader::new(file))?;
+    if chains.iter().any(|chain| chain.name.trim().is_empty()) {
+        return Err(HostError::InvalidRequestConfig(format!(
+            "Missing chain name in {}",
+            path.display()
+        )));
+    }
+    Ok(chains
+        .into_iter()
+        .map(ChainConfig::with_default_forks)
+        .collect())
+}
+
+fn read_chain_dir(dir: &Path) -> HostResult<Vec<ChainConfig>> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.sort();
+    let mut chains = Vec::new();
+    for path in paths {
+        let content = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") | Some("toml") => std::fs::read_to_string(&path)?,
+            _ => continue,
+        };
+        let mut chain: ChainConfig = if path.extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str(&content).map_err(|e| {
+                HostError::InvalidRequestConfig(format!(
+                    "Invalid chain spec {}: {e}",
+                    path.display()
+                ))
+            })?
+        } else {
+            serde_json::from_str(&content)?
+        };
+        if chain.name.trim().is_empty() {
+            let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            chain.name = stem.to_string();
+        }
+        chains.push(chain.with_default_forks());
+    }
+    Ok(chains)
+}
+
+/// Replace all registered chains at once with the chains from [resolve_chains].
+pub fn replace_chains(chains: BTreeMap<String, ChainConfig>) {
+    info!("Registered {} chains", chains.len());
+    *CHAINS.write().unwrap() = chains;
+}
+
+/// Register the chain under its name.
+pub fn register_chain(mut chain: ChainConfig) {
+    chain.name = chain.name.trim().to_lowercase();
+    info!(
+        "Registered chain {} (chain id {}) running {}",
+        chain.name, chain.chain_spec.chain_id, chain.network
+    );
+    CHAINS.write().unwrap().insert(chain.name.clone(), chain);
+}
+
+/// Get the chain registered under the given name.
+pub fn get_chain(name: &str) -> HostResult<ChainConfig> {
+    CHAINS
+        .read()
+        .unwrap()
+        .get(&name.trim().to_lowercase())
+        .cloned()
+        .ok_or_else(|| {
+            HostError::invalid(
+                ErrorCode::UnknownNetwork,
+                "network",
+                format!("Unknown network: {name}"),
+            )
+        })
+}
+
+/// Get all registered chains.
+pub fn list_chains() -> Vec<ChainConfig> {
+    CHAINS.read().unwrap().values().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use raiko_lib::consts::TaikoFork;
+
+    use super::*;
+
+    #[test]
+    fn test_register_chain() {
+        assert_eq!(get_chain("taiko_a7").unwrap().network, Network::TaikoA7);
+        assert!(get_chain("taiko_devnet").is_err());
+
+        let chain: ChainConfig = serde_json::from_value(serde_json::json!({
+            "name": "Taiko_Devnet",
+            "network": "taiko_a7",
+            "rpc": "http://localhost:8545",
+            "chain_spec": serde_json::to_value(&*TAIKO_A7_CHAIN_SPEC).unwrap(),
+        }))
+        .unwrap();
+        register_chain(chain);
+
+        let chain = get_chain("taiko_devnet").unwrap();
+        assert_eq!(chain.network, Network::TaikoA7);
+        assert_eq!(chain.rpc.as_deref(), Some("http://localhost:8545"));
+        assert_eq!(chain.l1_network, None);
+    }
+
+    #[test]
+    fn test_load_chain_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut chain_spec = toml::Table::try_from(&*TAIKO_A7_CHAIN_SPEC).unwrap();
+        chain_spec.insert("chain_id".to_string(), 167100.into());
+        chain_spec.insert("block_gas_limit".to_string(), 15_000_000.into());
+        chain_spec.remove("taiko_forks");
+        let chain = toml::Table::from_iter([
+            ("network".to_string(), "taiko_a7".into()),
+            ("chain_spec".to_string(), chain_spec.into()),
+        ]);
+        std::fs::write(dir.path().join("my_l3.toml"), chain.to_string()).unwrap();
+        std::fs::write(dir.path().join("README.md"), "not a chain").unwrap();
+
+        load_chain_dir(dir.path()).unwrap();
+        let chain = get_chain("my_l3").unwrap();
+        assert_eq!(chain.chain_spec.chain_id, 167100);
+        assert_eq!(chain.chain_spec.block_gas_limit, Some(15_000_000));
+        assert_eq!(
+            chain.chain_spec.anchor_gas_limit,
+            TAIKO_A7_CHAIN_SPEC.anchor_gas_limit
+        );
+        // The forks of the protocol of the chain apply without a fork schedule
+        assert_eq!(
+            chain.chain_spec.fork_rules(0, 0).unwrap().taiko_fork,
+            Some(TaikoFork::Hekla)
+        );
+    }
+
+    #[test]
+    fn test_resolve_chains() {
+        let dir = tempfile::tempdir().unwrap();
+        let chain = serde_json::json!({
+            "name": "My_L3",
+            "network": "taiko_a7",
+            "chain_spec": serde_json::to_value(&*TAIKO_A7_CHAIN_SPEC).unwrap(),
+        });
+        std::fs::write(dir.path().join("my_l3.json"), chain.to_string()).unwrap();
+
+        let chains = resolve_chains(None, Some(dir.path())).unwrap();
+        assert!(chains.contains_key("my_l3"));
+        assert!(chains.contains_key("taiko_a7"));
+
+        // A single invalid chain rejects all of them
+        std::fs::write(dir.path().join("broken.toml"), "network = ").unwrap();
+        assert!(resolve_chains(None, Some(dir.path())).is_err());
+    }
+
+    #[test]
+    fn test_clamp_deadline() {
+        let mut chain = get_chain("taiko_a7").unwrap();
+        chain.proving_windows.insert("sgx".to_string(), 3600);
+
+        assert_eq!(chain.clamp_deadline("sgx", Some(5000), 1000), Some(4600));
+        assert_eq!(chain.clamp_deadline("sgx", Some(2000), 1000), Some(2000));
+        assert_eq!(chain.clamp_deadline("sgx", None, 1000), None);
+        assert_eq!(chain.clamp_deadline("risc0", Some(5000), 1000), Some(5000));
+    }
+}
@@ -0,0 +1,19 @@
+//! The wall clock of the host, as unix timestamps.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The current unix timestamp in seconds, zero if the clock is before the epoch.
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// The current unix timestamp in milliseconds, zero if the clock is before the epoch.
+pub fn unix_timestamp_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
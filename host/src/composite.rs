@@ -0,0 +1,301 @@
+//! The `sgx_and_zk` proof type, an SGX and a ZK proof of the same block in one proof.
+//!
+//! The dual tier verifies a block with two verifiers at once. Both provers run in parallel on
+//! the same input and prove the same protocol instance, each committing to it with its own
+//! instance hash. The on-chain proof is the ABI encoded `SubProof[]` of the composed verifier,
+//! the SGX proof first, with the proofs of both provers next to it:
+//!
+//! ```json
+//! {"proof": "0x...", "sgx": {"proof": "0x...", ...}, "zk": {"proof": "0x...", ...}, "zk_type": "risc0"}
+//! ```
+//!
+//! The addresses of the verifiers of the sub proofs are the `sgx_verifier` and `zk_verifier`
+//! params of the `sgx_and_zk` prover args. Both are required, a request without them fails
+//! before the provers run.
+
+use std::sync::Arc;
+
+use alloy_primitives::{Address, B256};
+use alloy_sol_types::{sol, SolValue};
+use raiko_lib::{
+    input::{GuestInput, GuestOutput},
+    protocol_instance::{assemble_protocol_instance, ProtocolInstance},
+    prover::{BoxFuture, ProgressReporter, Proof, ProverBackend, ProverConfig, ProverResult},
+};
+use raiko_primitives::hex;
+use serde::{Deserialize, Serialize};
+
+use crate::registry::get_prover;
+
+/// The name of the proof type.
+pub const SGX_AND_ZK: &str = "sgx_and_zk";
+
+sol! {
+    #[derive(Debug)]
+    /// The proof of one of the verifiers of a composed tier.
+    struct SubProof {
+        address verifier;
+        bytes proof;
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// The params of the `sgx_and_zk` prover.
+pub struct SgxAndZkParam {
+    /// The address of the verifier of the SGX proof.
+    pub sgx_verifier: Address,
+    /// The address of the verifier of the ZK proof.
+    pub zk_verifier: Address,
+}
+
+impl SgxAndZkParam {
+    /// The params of the request, which has to have both verifiers.
+    fn from_config(config: &ProverConfig) -> ProverResult<Self> {
+        let param = config
+            .get(SGX_AND_ZK)
+            .ok_or_else(|| format!("Missing the {SGX_AND_ZK} params with the verifiers"))?;
+        let param =
+            Self::deserialize(param).map_err(|e| format!("Invalid {SGX_AND_ZK} params: {e}"))?;
+        for (name, verifier) in [
+            ("sgx_verifier", param.sgx_verifier),
+            ("zk_verifier", param.zk_verifier),
+        ] {
+            if verifier.is_zero() {
+                return Err(format!("The {name} of the {SGX_AND_ZK} params is zero").into());
+            }
+        }
+        Ok(param)
+    }
+}
+
+/// Proves blocks with the SGX prover and a ZK prover at once.
+pub struct SgxAndZkBackend {
+    sgx: String,
+    zk: String,
+}
+
+impl SgxAndZkBackend {
+    /// Prove with the SGX prover and the ZK prover with the given name.
+    pub fn new(zk: impl Into<String>) -> Self {
+        Self {
+            sgx: "sgx".to_string(),
+            zk: zk.into(),
+        }
+    }
+
+    fn backends(&self) -> ProverResult<[Arc<dyn ProverBackend>; 2]> {
+        let backend = |name: &str| {
+            get_prover(name).ok_or_else(|| format!("The {name} prover is not available"))
+        };
+        Ok([backend(&self.sgx)?, backend(&self.zk)?])
+    }
+}
+
+/// The output of the guest for the given prover, with the instance hash of the prover.
+fn sub_output(
+    backend: &dyn ProverBackend,
+    input: &GuestInput,
+    output: &GuestOutput,
+) -> ProverResult<GuestOutput> {
+    match output {
+        GuestOutput::Success((header, _)) => {
+            let pi = assemble_protocol_instance(input, &header.header)
+                .map_err(|e| format!("Could not assemble the protocol instance: {e}"))?;
+            Ok(GuestOutput::Success((
+                header.clone(),
+                backend.instance_hash(pi),
+            )))
+        }
+        GuestOutput::Failure => Ok(GuestOutput::Failure),
+    }
+}
+
+/// The on-chain proof of a sub proof.
+fn on_chain_proof(proof: &Proof, name: &str) -> ProverResult<Vec<u8>> {
+    let data = proof
+        .get("proof")
+        .and_then(|data| data.as_str())
+        .ok_or_else(|| format!("The {name} proof has no on-chain proof"))?;
+    hex::decode(data.trim_start_matches("0x"))
+        .map_err(|e| format!("The {name} proof is not hex encoded: {e}").into())
+}
+
+/// Combine the proofs of the two provers into the proof of the composed tier.
+fn combine(param: &SgxAndZkParam, sgx: Proof, zk: Proof, zk_type: &str) -> ProverResult<Proof> {
+    let sub_proofs = vec![
+        SubProof {
+            verifier: param.sgx_verifier,
+            proof: on_chain_proof(&sgx, "sgx")?.into(),
+        },
+        SubProof {
+            verifier: param.zk_verifier,
+            proof: on_chain_proof(&zk, zk_type)?.into(),
+        },
+    ];
+    Ok(serde_json::json!({
+        "proof": format!("0x{}", hex::encode(sub_proofs.abi_encode())),
+        "sgx": sgx,
+        "zk": zk,
+        "zk_type": zk_type,
+    }))
+}
+
+impl ProverBackend for SgxAndZkBackend {
+    fn name(&self) -> &str {
+        SGX_AND_ZK
+    }
+
+    fn run<'a>(
+        &'a self,
+        input: GuestInput,
+        output: GuestOutput,
+        config: &'a ProverConfig,
+        progress: &'a ProgressReporter,
+    ) -> BoxFuture<'a, ProverResult<Proof>> {
+        Box::pin(async move {
+            let param = SgxAndZkParam::from_config(config)?;
+            let [sgx, zk] = self.backends()?;
+            let (sgx_output, zk_output) = (
+                sub_output(sgx.as_ref(), &input, &output)?,
+                sub_output(zk.as_ref(), &input, &output)?,
+            );
+            let (sgx_proof, zk_proof) = tokio::try_join!(
+                sgx.run(input.clone(), sgx_output, config, progress),
+                zk.run(input.clone(), zk_output, config, progress),
+            )?;
+            combine(&param, sgx_proof, zk_proof, &self.zk)
+        })
+    }
+
+    /// The instance hash the ZK proof commits to, the SGX proof signs its own instance hash
+    /// of the same protocol instance.
+    fn instance_hash(&self, pi: ProtocolInstance) -> B256 {
+        get_prover(&self.zk)
+            .map(|zk| zk.instance_hash(pi))
+            .unwrap_or_default()
+    }
+
+    fn program_id(&self) -> Option<B256> {
+        get_prover(&self.zk)?.program_id()
+    }
+
+    fn warm_up(&self) -> ProverResult<()> {
+        self.backends()?
+            .iter()
+            .try_for_each(|backend| backend.warm_up())
+    }
+
+    fn verify(
+        &self,
+        proof: &Proof,
+        input: &GuestInput,
+        output: &GuestOutput,
+    ) -> Option<ProverResult<()>> {
+        let backends = match self.backends() {
+            Ok(backends) => backends,
+            Err(e) => return Some(Err(e)),
+        };
+        let mut verified = None;
+        for (backend, field) in backends.iter().zip(["sgx", "zk"]) {
+            let Some(sub_proof) = proof.get(field) else {
+                return Some(Err(
+                    format!("The {SGX_AND_ZK} proof has no {field} proof").into()
+                ));
+            };
+            let sub_output = match sub_output(backend.as_ref(), input, output) {
+                Ok(sub_output) => sub_output,
+                Err(e) => return Some(Err(e)),
+            };
+            match backend.verify(sub_proof, input, &sub_output) {
+                Some(Err(e)) => return Some(Err(format!("The {field} proof: {e}").into())),
+                Some(Ok(())) => verified = Some(Ok(())),
+                None => {}
+            }
+        }
+        verified
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::register_prover;
+
+    /// Returns the given on-chain proof.
+    struct FixedBackend(&'static str, &'static str);
+
+    impl ProverBackend for FixedBackend {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn run<'a>(
+            &'a self,
+            _input: GuestInput,
+            _output: GuestOutput,
+            _config: &'a ProverConfig,
+            _progress: &'a ProgressReporter,
+        ) -> BoxFuture<'a, ProverResult<Proof>> {
+            Box::pin(async { Ok(serde_json::json!({ "proof": self.1 })) })
+        }
+
+        fn instance_hash(&self, _pi: ProtocolInstance) -> B256 {
+            B256::default()
+        }
+    }
+
+    async fn run(backend: &SgxAndZkBackend, config: &ProverConfig) -> ProverResult<Proof> {
+        backend
+            .run(
+                GuestInput::default(),
+                GuestOutput::Failure,
+                config,
+                &ProgressReporter::default(),
+            )
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_sgx_and_zk() {
+        register_prover(Arc::new(FixedBackend("composite_sgx", "0x01"))).unwrap();
+        register_prover(Arc::new(FixedBackend("composite_zk", "0203"))).unwrap();
+        let backend = SgxAndZkBackend {
+            sgx: "composite_sgx".to_string(),
+            zk: "composite_zk".to_string(),
+        };
+        // Both verifiers are required.
+        for config in [
+            serde_json::json!({}),
+            serde_json::json!({"sgx_and_zk": {"zk_verifier": Address::repeat_byte(2)}}),
+            serde_json::json!({"sgx_and_zk": {
+                "sgx_verifier": Address::ZERO,
+                "zk_verifier": Address::repeat_byte(2),
+            }}),
+        ] {
+            assert!(run(&backend, &config).await.is_err(), "{config}");
+        }
+
+        let config = serde_json::json!({
+            "sgx_and_zk": {
+                "sgx_verifier": Address::repeat_byte(1),
+                "zk_verifier": Address::repeat_byte(2),
+            },
+        });
+        let proof = run(&backend, &config).await.unwrap();
+        assert_eq!(proof["sgx"]["proof"], "0x01");
+        assert_eq!(proof["zk_type"], "composite_zk");
+
+        let data = hex::decode(proof["proof"].as_str().unwrap().trim_start_matches("0x")).unwrap();
+        let sub_proofs = Vec::<SubProof>::abi_decode(&data, true).unwrap();
+        assert_eq!(sub_proofs.len(), 2);
+        assert_eq!(sub_proofs[0].verifier, Address::repeat_byte(1));
+        assert_eq!(sub_proofs[0].proof.to_vec(), vec![1]);
+        assert_eq!(sub_proofs[1].verifier, Address::repeat_byte(2));
+        assert_eq!(sub_proofs[1].proof.to_vec(), vec![2, 3]);
+
+        // Neither prover verifies its proofs.
+        assert!(backend
+            .verify(&proof, &GuestInput::default(), &GuestOutput::Failure)
+            .is_none());
+    }
+}
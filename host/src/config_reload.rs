@@ -0,0 +1,183 @@
+//! Reloading the config file while the host runs.
+//!
+//! On SIGHUP, or when the config file changes with `--watch-config`, the config file is read
+//! again and merged with the command line options like on startup. The chain specs, the pools
+//! of the node endpoints, the [runtime settings](crate::runtime_config) and the
+//! [guest versions](crate::guests) are only replaced once all of them are valid, an invalid
+//! config is rejected and the host keeps the current one. Running jobs keep the settings they
+//! started with, the [job limits](crate::isolation) apply to the proofs started after the reload.
+//! The other settings need a restart, their changes are logged.
+
+use std::{
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use crate::{
+    chain_registry::{replace_chains, resolve_chains},
+    error::HostResult,
+    guests, isolation,
+    metrics::inc_config_reload_count,
+    provider_pool,
+    runtime_config::RuntimeConfig,
+    Cli, ProverState,
+};
+
+/// How often the config file is checked for changes with `--watch-config`.
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The options applied by [reload], the others need a restart.
+const RELOADED_OPTIONS: &[&str] = &[
+    "concurrency_limit",
+    "prover_concurrency",
+    "max_queue_depth",
+    "rpc",
+    "l1_rpc",
+    "beacon_rpc",
+    "cache_max_size",
+    "cache_max_age",
+    "log_level",
+    "chain_spec_path",
+    "chain_spec_dir",
+    "rpc_selection",
+    "rpc_health_check_interval",
+    "guests",
+    "job_limits",
+];
+
+/// Apply the reloaded options to the host. Nothing is changed if any of them is invalid.
+pub fn reload(state: &ProverState, opts: &Cli) -> HostResult<RuntimeConfig> {
+    // Resolve everything that can fail before changing anything.
+    let chains = resolve_chains(
+        opts.chain_spec_path.as_deref(),
+        opts.chain_spec_dir.as_deref(),
+    )?;
+    guests::validate(&opts.guests)?;
+    let config = state.runtime.update(
+        &serde_json::to_value(RuntimeConfig::new(opts))?,
+        &state.jobs,
+        &state.input_cache,
+    )?;
+    replace_chains(chains);
+    guests::configure(opts.guests.clone())?;
+    isolation::configure(
+        opts.isolate_provers.clone(),
+        opts.prover_daemons.clone(),
+        opts.job_limits.clone(),
+    );
+    provider_pool::configure(
+        opts.rpc_selection,
+        Duration::from_secs(opts.rpc_health_check_interval),
+    );
+
+    for option in restart_needed(&state.opts, opts)? {
+        warn!("The option {option} changed, it is applied on the next restart");
+    }
+    Ok(config)
+}
+
+/// The options changed from the current ones that are not applied by [reload].
+fn restart_needed(current: &Cli, reloaded: &Cli) -> HostResult<Vec<String>> {
+    let (Value::Object(current), Value::Object(reloaded)) = (
+        serde_json::to_value(current)?,
+        serde_json::to_value(reloaded)?,
+    ) else {
+        return Ok(Vec::new());
+    };
+    Ok(reloaded
+        .into_iter()
+        .filter(|(option, value)| {
+            !RELOADED_OPTIONS.contains(&option.as_str()) && current.get(option) != Some(value)
+        })
+        .map(|(option, _)| option)
+        .collect())
+}
+
+/// Reload the config file on SIGHUP and, with `--watch-config`, when it changes.
+pub fn spawn(state: ProverState) {
+    let (trigger, mut reloads) = mpsc::channel(1);
+
+    #[cfg(unix)]
+    {
+        let trigger = trigger.clone();
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+            Ok(mut hangup) => {
+                tokio::spawn(async move {
+                    while hangup.recv().await.is_some() {
+                        info!("Reloading the config on SIGHUP");
+                        // A reload is already pending otherwise.
+                        let _ = trigger.try_send(());
+                    }
+                });
+            }
+            Err(e) => warn!("Could not install the SIGHUP handler: {e}"),
+        }
+    }
+
+    if state.opts.watch_config {
+        let path = state.opts.config_path.clone();
+        tokio::spawn(async move {
+            let mut last_modified = modified(&path);
+            let mut interval = tokio::time::interval(WATCH_INTERVAL);
+            loop {
+                interval.tick().await;
+                let current = modified(&path);
+                if current != last_modified {
+                    last_modified = current;
+                    info!("The config file {} changed, reloading it", path.display());
+                    let _ = trigger.try_send(());
+                }
+            }
+        });
+    }
+
+    tokio::spawn(async move {
+        while reloads.recv().await.is_some() {
+            match Cli::load().and_then(|opts| reload(&state, &opts)) {
+                Ok(_) => {
+                    info!("Reloaded the config");
+                    inc_config_reload_count("applied");
+                }
+                Err(e) => {
+                    error!("Rejected the reloaded config, keeping the current one: {e}");
+                    inc_config_reload_count("rejected");
+                }
+            }
+        }
+    });
+}
+
+/// The time the file was last modified at, unset if it can't be read.
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_needed() {
+        let current = Cli {
+            concurrency_limit: 4,
+            ..Default::default()
+        };
+        let mut reloaded = current.clone();
+        reloaded.concurrency_limit = 8;
+        reloaded.cache_max_size = Some(100);
+        assert!(restart_needed(&current, &reloaded).unwrap().is_empty());
+
+        reloaded.require_gpu = true;
+        reloaded.max_block_age = Some(3600);
+        assert_eq!(
+            restart_needed(&current, &reloaded).unwrap(),
+            ["max_block_age", "require_gpu"]
+        );
+    }
+}
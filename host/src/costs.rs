@@ -0,0 +1,279 @@
+//! Cost accounting of the generated proofs.
+//!
+//! The cost of every proof is estimated from the configured rates: proofs generated by this
+//! host cost the hourly rate of their proof type, e.g. of the GPU instance, for the time
+//! proving took, proofs generated by Bonsai cost the rate per million cycles it bills, and
+//! proofs generated by the SP1 prover network cost its fee per proof. The costs are in the
+//! currency of the rates, kept per day and proof type, and reported at `/admin/costs`.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{Mutex, RwLock},
+    time::Duration,
+};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    clock::unix_timestamp,
+    metrics::{observe_proof_cost, set_cost_today},
+    request::ProofType,
+};
+
+/// The number of proofs kept for [report].
+const MAX_PROOF_COSTS: usize = 1_000;
+
+/// The number of days kept for [report].
+const MAX_DAYS: usize = 90;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// The source of the proofs generated by this host.
+pub const LOCAL_SOURCE: &str = "local";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+/// The rates the cost of the proofs is estimated with, e.g. `{"hourly": {"risc0": 3.2},
+/// "bonsai_per_mcycle": 0.0005, "sp1_network_per_proof": 0.1}`.
+pub struct CostConfig {
+    /// The cost of an hour of proving on this host per proof type.
+    #[serde(default)]
+    pub hourly: HashMap<String, f64>,
+    /// The cost of a million cycles billed by Bonsai.
+    #[serde(default)]
+    pub bonsai_per_mcycle: Option<f64>,
+    /// The fee of a proof of the SP1 prover network.
+    #[serde(default)]
+    pub sp1_network_per_proof: Option<f64>,
+}
+
+impl CostConfig {
+    /// The cost of a proof generated by `source` in `time`, unset without a rate for it.
+    pub fn estimate(
+        &self,
+        proof_type: &ProofType,
+        source: &str,
+        time: Duration,
+        cycles: Option<u64>,
+    ) -> Option<f64> {
+        match source {
+            LOCAL_SOURCE => self
+                .hourly
+                .get(&proof_type.to_string())
+                .map(|rate| rate * time.as_secs_f64() / 3600.0),
+            "bonsai" => Some(self.bonsai_per_mcycle? * cycles? as f64 / 1e6),
+            "sp1_network" => self.sp1_network_per_proof,
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// The cost of a generated proof.
+pub struct ProofCost {
+    pub network: String,
+    pub block_number: u64,
+    pub proof_type: String,
+    /// Where the proof was generated, `local` or the remote proving service, e.g. `bonsai`.
+    pub source: String,
+    /// The estimated cost, unset without a rate for the source.
+    pub cost: Option<f64>,
+    /// Unix timestamp the proof was generated at.
+    pub finished_at: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+/// The cost of the proofs of a proof type generated on a day.
+pub struct DailyCost {
+    /// Unix timestamp of the start of the day in UTC.
+    pub day: u64,
+    pub proof_type: String,
+    pub proofs: u64,
+    /// The sum of the estimated costs, the proofs without a rate are not included.
+    pub cost: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// The cost of the proofs generated since the host started.
+pub struct CostReport {
+    /// The sum of the costs of all days.
+    pub total: f64,
+    /// The cost per day and proof type, the oldest day first.
+    pub days: Vec<DailyCost>,
+    /// The cost of the last generated proofs, the most recent first.
+    pub proofs: Vec<ProofCost>,
+}
+
+#[derive(Debug, Default)]
+struct Ledger {
+    days: BTreeMap<(u64, String), DailyCost>,
+    proofs: VecDeque<ProofCost>,
+}
+
+lazy_static! {
+    static ref CONFIG: RwLock<CostConfig> = RwLock::new(CostConfig::default());
+    static ref LEDGER: Mutex<Ledger> = Mutex::new(Ledger::default());
+}
+
+/// Set the rates the cost of the proofs generated afterwards is estimated with.
+pub fn configure(config: CostConfig) {
+    *CONFIG.write().unwrap_or_else(|e| e.into_inner()) = config;
+}
+
+/// Estimate the cost of a proof generated by `source` in `time` with the configured rates.
+pub fn estimate(
+    proof_type: &ProofType,
+    source: &str,
+    time: Duration,
+    cycles: Option<u64>,
+) -> Option<f64> {
+    CONFIG
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .estimate(proof_type, source, time, cycles)
+}
+
+/// Estimate the cost of a proof generated by `source` in `time` and record it.
+pub fn record(
+    proof_type: &ProofType,
+    network: &str,
+    block_number: u64,
+    source: &str,
+    time: Duration,
+    cycles: Option<u64>,
+) -> Option<f64> {
+    let cost = estimate(proof_type, source, time, cycles);
+    let mut ledger = LEDGER.lock().unwrap_or_else(|e| e.into_inner());
+    ledger.add(ProofCost {
+        network: network.to_string(),
+        block_number,
+        proof_type: proof_type.to_string(),
+        source: source.to_string(),
+        cost,
+        finished_at: unix_timestamp(),
+    });
+    if let Some(cost) = cost {
+        observe_proof_cost(proof_type, source, cost);
+    }
+    set_cost_today(&ledger.today(unix_timestamp()));
+    cost
+}
+
+/// The cost of the proofs generated since the host started.
+pub fn report() -> CostReport {
+    let ledger = LEDGER.lock().unwrap_or_else(|e| e.into_inner());
+    let days: Vec<_> = ledger.days.values().cloned().collect();
+    CostReport {
+        total: days.iter().map(|day| day.cost).sum(),
+        days,
+        proofs: ledger.proofs.iter().cloned().collect(),
+    }
+}
+
+impl Ledger {
+    fn add(&mut self, proof: ProofCost) {
+        let day = proof.finished_at / SECONDS_PER_DAY * SECONDS_PER_DAY;
+        let daily = self
+            .days
+            .entry((day, proof.proof_type.clone()))
+            .or_insert_with(|| DailyCost {
+                day,
+                proof_type: proof.proof_type.clone(),
+                ..Default::default()
+            });
+        daily.proofs += 1;
+        daily.cost += proof.cost.unwrap_or_default();
+        // Drop the oldest days, with all of their proof types.
+        while let Some(&(oldest, _)) = self.days.keys().next() {
+            if day.saturating_sub(oldest) < MAX_DAYS as u64 * SECONDS_PER_DAY {
+                break;
+            }
+            self.days.retain(|(day, _), _| *day != oldest);
+        }
+
+        if self.proofs.len() >= MAX_PROOF_COSTS {
+            self.proofs.pop_back();
+        }
+        self.proofs.push_front(proof);
+    }
+
+    /// The cost per proof type on the day of `now`.
+    fn today(&self, now: u64) -> Vec<(String, f64)> {
+        let today = now / SECONDS_PER_DAY * SECONDS_PER_DAY;
+        self.days
+            .range((today, String::new())..(today + SECONDS_PER_DAY, String::new()))
+            .map(|((_, proof_type), daily)| (proof_type.clone(), daily.cost))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        let config: CostConfig = serde_json::from_value(serde_json::json!({
+            "hourly": {"risc0": 3.0},
+            "bonsai_per_mcycle": 0.5,
+        }))
+        .unwrap();
+        let risc0 = ProofType::Risc0;
+        let half_hour = Duration::from_secs(1800);
+        assert_eq!(
+            config.estimate(&risc0, LOCAL_SOURCE, half_hour, None),
+            Some(1.5)
+        );
+        assert_eq!(
+            config.estimate(&risc0, "bonsai", half_hour, Some(4_000_000)),
+            Some(2.0)
+        );
+        // Bonsai bills the cycles, which are not always known
+        assert_eq!(config.estimate(&risc0, "bonsai", half_hour, None), None);
+        assert_eq!(
+            config.estimate(&ProofType::Sgx, LOCAL_SOURCE, half_hour, None),
+            None
+        );
+        assert_eq!(
+            config.estimate(&ProofType::Sp1, "sp1_network", half_hour, None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_ledger_days() {
+        let mut ledger = Ledger::default();
+        let proof = |proof_type: &str, cost, finished_at| ProofCost {
+            network: "taiko_a7".to_string(),
+            block_number: 1,
+            proof_type: proof_type.to_string(),
+            source: LOCAL_SOURCE.to_string(),
+            cost,
+            finished_at,
+        };
+        let day = 100 * SECONDS_PER_DAY;
+        ledger.add(proof("risc0", Some(1.0), day + 10));
+        ledger.add(proof("risc0", Some(2.0), day + 20));
+        ledger.add(proof("sgx", None, day + 30));
+        ledger.add(proof("risc0", Some(4.0), day + SECONDS_PER_DAY));
+        assert_eq!(
+            ledger.today(day + 40),
+            vec![("risc0".to_string(), 7.0), ("sgx".to_string(), 0.0)]
+        );
+        assert_eq!(
+            ledger.today(day + SECONDS_PER_DAY),
+            vec![("risc0".to_string(), 4.0)]
+        );
+        assert_eq!(ledger.proofs.front().unwrap().cost, Some(4.0));
+
+        // Days older than MAX_DAYS are dropped
+        ledger.add(proof(
+            "risc0",
+            None,
+            day + MAX_DAYS as u64 * SECONDS_PER_DAY,
+        ));
+        assert_eq!(ledger.days.len(), 2);
+        assert!(ledger.days.keys().all(|(key, _)| *key > day));
+    }
+}
@@ -0,0 +1,331 @@
+//! Long lived prover processes.
+//!
+//! The provers of `--prover-daemons` prove in prover daemons, child processes of the host
+//! started with `raiko-host prover-daemon` which set up the prover once, e.g. the proving keys
+//! and the CUDA context, and then prove one block after the other. The host sends the request
+//! and the input of a proof over a Unix socket and reads back the proof or the error, framed
+//! by their length. Idle daemons are kept per prover and CUDA device, one of each is started
+//! with the host, so the first proof doesn't wait for the setup either.
+//!
+//! A daemon is isolated like the child of a single proof, see [crate::isolation], including
+//! the limits of its prover. A crashed daemon fails its proof with the crash and the retry
+//! starts a new daemon. A daemon exits when the host is gone.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use raiko_client::{ErrorCode, Problem};
+use raiko_lib::{input::GuestInput, prover::Proof};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    process::Child,
+};
+use tracing::{info, warn};
+
+use crate::{
+    error::{HostError, HostResult},
+    execution::generate_proof as generate_proof_in_process,
+    gpu, input_codec,
+    isolation::{self, ProverCrash, STDERR_TAIL},
+    jobs::progress::ProgressSender,
+    request::{ProofRequest, ProofType},
+    Cli, DaemonArgs,
+};
+
+/// How long the setup of a daemon may take before it listens on its socket.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How often a starting daemon is checked for its socket.
+const STARTUP_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How often a daemon checks whether the host is still there.
+const PARENT_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a daemon that lost its connection is given to exit with its crash.
+const EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The daemons of a prover on a CUDA device are interchangeable.
+type DaemonKey = (String, Option<u32>);
+
+/// A running prover daemon.
+struct Daemon {
+    child: Child,
+    stream: UnixStream,
+    /// The end of the error output of the daemon.
+    stderr: Arc<Mutex<String>>,
+    /// The directory of the socket.
+    dir: PathBuf,
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        // The child is killed on drop, so the directory of its socket can go.
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+lazy_static! {
+    static ref IDLE: Mutex<HashMap<DaemonKey, Vec<Daemon>>> = Mutex::new(HashMap::new());
+}
+
+impl Daemon {
+    /// Start a daemon of the prover on the CUDA device and wait until it listens.
+    async fn spawn(proof_type: &ProofType, device: Option<u32>) -> HostResult<Self> {
+        let dir = isolation::job_dir();
+        std::fs::create_dir_all(&dir)?;
+        let socket = dir.join("daemon.sock");
+        let limits = isolation::limits(proof_type).unwrap_or_default();
+        let mut command = isolation::child_command(&limits, device)?;
+        command
+            .arg("prover-daemon")
+            .arg(format!("--socket={}", socket.display()))
+            .arg(format!("--prover={proof_type}"));
+        let mut child = command.spawn()?;
+        let stderr = Arc::new(Mutex::new(String::new()));
+        if let Some(output) = child.stderr.take() {
+            tokio::spawn(keep_tail(output, stderr.clone()));
+        }
+
+        let started = Instant::now();
+        let stream = loop {
+            if let Some(status) = child.try_wait()? {
+                let _ = std::fs::remove_dir_all(&dir);
+                return Err(HostError::Anyhow(anyhow::anyhow!(
+                    "The {proof_type} prover daemon exited on startup with {status}: {}",
+                    stderr.lock().unwrap()
+                )));
+            }
+            match UnixStream::connect(&socket).await {
+                Ok(stream) => break stream,
+                Err(_) if started.elapsed() < STARTUP_TIMEOUT => {
+                    tokio::time::sleep(STARTUP_POLL_INTERVAL).await;
+                }
+                Err(e) => {
+                    let _ = std::fs::remove_dir_all(&dir);
+                    return Err(HostError::Anyhow(anyhow::anyhow!(
+                        "The {proof_type} prover daemon didn't start within {}s: {e}",
+                        STARTUP_TIMEOUT.as_secs()
+                    )));
+                }
+            }
+        };
+        info!(
+            "Started a {proof_type} prover daemon in {:.1}s",
+            started.elapsed().as_secs_f64()
+        );
+        Ok(Daemon {
+            child,
+            stream,
+            stderr,
+            dir,
+        })
+    }
+
+    /// Send the proof to the daemon and wait for its result.
+    async fn prove(
+        &mut self,
+        proof_request: &ProofRequest,
+        input: &GuestInput,
+    ) -> HostResult<Proof> {
+        let exchange = async {
+            write_frame(&mut self.stream, &serde_json::to_vec(proof_request)?).await?;
+            write_frame(
+                &mut self.stream,
+                &input_codec::encode_to_vec(input, "daemon")?,
+            )
+            .await?;
+            let response = read_frame(&mut self.stream).await?;
+            HostResult::Ok(serde_json::from_slice::<Result<Proof, Problem>>(&response)?)
+        };
+        match exchange.await {
+            Ok(result) => result.map_err(from_problem),
+            Err(e) => {
+                // The daemon is gone in the middle of the proof, most likely it crashed.
+                let status = tokio::time::timeout(EXIT_TIMEOUT, self.child.wait()).await;
+                let stderr = self.stderr.lock().unwrap().clone();
+                match status {
+                    Ok(Ok(status)) => match ProverCrash::from_output(&status, &stderr) {
+                        Some(crash) => Err(isolation::crashed(proof_request, crash)),
+                        None => Err(HostError::Anyhow(anyhow::anyhow!(
+                            "The {} prover daemon exited with {status}: {stderr}",
+                            proof_request.proof_type
+                        ))),
+                    },
+                    _ => Err(e),
+                }
+            }
+        }
+    }
+}
+
+/// Generate the proof of the input in a prover daemon, started if none is idle.
+pub async fn generate_proof(proof_request: &ProofRequest, input: &GuestInput) -> HostResult<Proof> {
+    let key = (proof_request.proof_type.to_string(), gpu::cuda_device());
+    let idle = IDLE
+        .lock()
+        .unwrap()
+        .get_mut(&key)
+        .and_then(|daemons| daemons.pop());
+    let mut daemon = match idle {
+        Some(daemon) => daemon,
+        None => Daemon::spawn(&proof_request.proof_type, key.1).await?,
+    };
+    let result = daemon.prove(proof_request, input).await;
+    // A daemon that failed a proof for another reason than the prover may be broken.
+    if matches!(result, Ok(_) | Err(HostError::GuestError(_))) {
+        IDLE.lock().unwrap().entry(key).or_default().push(daemon);
+    }
+    result
+}
+
+/// Start a daemon of each prover on each of its CUDA devices, in the background.
+pub fn warm_up(provers: &[String], cuda_devices: &BTreeMap<String, Vec<u32>>) {
+    for prover in provers {
+        let Ok(proof_type) = prover.parse::<ProofType>() else {
+            warn!("Unknown prover of the prover daemons: {prover}");
+            continue;
+        };
+        let devices = match cuda_devices.get(prover) {
+            Some(devices) if !devices.is_empty() => devices.iter().copied().map(Some).collect(),
+            _ => vec![None],
+        };
+        for device in devices {
+            let proof_type = proof_type.clone();
+            tokio::spawn(async move {
+                match Daemon::spawn(&proof_type, device).await {
+                    Ok(daemon) => IDLE
+                        .lock()
+                        .unwrap()
+                        .entry((proof_type.to_string(), device))
+                        .or_default()
+                        .push(daemon),
+                    Err(e) => warn!("Could not start a {proof_type} prover daemon: {e}"),
+                }
+            });
+        }
+    }
+}
+
+/// The error of a proof in the daemon.
+fn from_problem(problem: Problem) -> HostError {
+    match problem.code {
+        ErrorCode::ProverError => HostError::GuestError(problem.detail.into()),
+        ErrorCode::ResourceExhausted => HostError::ResourceExhausted(problem.detail),
+        _ if problem.status < 500 => HostError::Validation(problem),
+        _ => HostError::Anyhow(anyhow::anyhow!("{problem}")),
+    }
+}
+
+/// Keep the end of the output in the buffer, until the output is closed.
+async fn keep_tail(output: impl AsyncRead + Unpin, tail: Arc<Mutex<String>>) {
+    let mut lines = BufReader::new(output).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let mut tail = tail.lock().unwrap();
+        tail.push_str(&line);
+        tail.push('\n');
+        if tail.len() > 2 * STDERR_TAIL {
+            *tail = isolation::tail(&tail).to_string();
+            tail.push('\n');
+        }
+    }
+}
+
+async fn write_frame(stream: &mut (impl AsyncWrite + Unpin), frame: &[u8]) -> std::io::Result<()> {
+    stream.write_u64(frame.len() as u64).await?;
+    stream.write_all(frame).await?;
+    stream.flush().await
+}
+
+async fn read_frame(stream: &mut (impl AsyncRead + Unpin)) -> std::io::Result<Vec<u8>> {
+    let len = stream.read_u64().await?;
+    let mut frame = vec![0; len as usize];
+    stream.read_exact(&mut frame).await?;
+    Ok(frame)
+}
+
+/// Run a prover daemon: set up the prover and prove the blocks sent to the socket until the
+/// host is gone.
+pub async fn serve_daemon(opts: &Cli, args: &DaemonArgs) -> HostResult<()> {
+    opts.load_chains()?;
+    let proof_type: ProofType = args.prover.parse()?;
+    let started = Instant::now();
+    proof_type.warm_up()?;
+    info!(
+        "Set up the {proof_type} prover in {:.1}s",
+        started.elapsed().as_secs_f64()
+    );
+    let listener = UnixListener::bind(&args.socket)?;
+    let parent = std::os::unix::process::parent_id();
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => accepted?.0,
+            _ = tokio::time::sleep(PARENT_CHECK_INTERVAL) => {
+                if std::os::unix::process::parent_id() != parent {
+                    info!("The host is gone, stopping the {proof_type} prover daemon");
+                    return Ok(());
+                }
+                continue;
+            }
+        };
+        serve_connection(stream).await?;
+    }
+}
+
+/// Prove the blocks sent over the connection, one after the other.
+async fn serve_connection(mut stream: UnixStream) -> HostResult<()> {
+    loop {
+        let request = match read_frame(&mut stream).await {
+            Ok(request) => request,
+            // The host closed the connection.
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let proof_request: ProofRequest = serde_json::from_slice(&request)?;
+        let input: GuestInput = input_codec::decode(&read_frame(&mut stream).await?[..])?;
+        info!(
+            "Proving block {} on {} in the prover daemon",
+            proof_request.block_number, proof_request.network
+        );
+        let result = generate_proof_in_process(&proof_request, &input, &ProgressSender::default())
+            .await
+            .map_err(|e| e.problem());
+        write_frame(&mut stream, &serde_json::to_vec(&result)?).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_frames() {
+        let (mut host, mut daemon) = UnixStream::pair().unwrap();
+        write_frame(&mut host, b"request").await.unwrap();
+        write_frame(&mut host, b"").await.unwrap();
+        assert_eq!(read_frame(&mut daemon).await.unwrap(), b"request");
+        assert_eq!(read_frame(&mut daemon).await.unwrap(), b"");
+        drop(host);
+        assert_eq!(
+            read_frame(&mut daemon).await.unwrap_err().kind(),
+            std::io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_from_problem() {
+        assert!(matches!(
+            from_problem(Problem::new(ErrorCode::ProverError, "failed")),
+            HostError::GuestError(_)
+        ));
+        assert!(matches!(
+            from_problem(Problem::new(ErrorCode::Internal, "failed")),
+            HostError::Anyhow(_)
+        ));
+    }
+}
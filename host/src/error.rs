@@ -1,8 +1,14 @@
-use axum::{http::StatusCode, response::IntoResponse};
+use axum::{
+    extract::rejection::JsonRejection,
+    http::{header, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use raiko_client::{ErrorCode, Problem, PROBLEM_JSON};
 use raiko_lib::prover::ProverError;
 use utoipa::ToSchema;
 
-use crate::request::ProofType;
+use crate::{isolation::ProverCrash, request::ProofType};
 
 /// The standardized error returned by the Raiko host.
 #[derive(thiserror::Error, Debug, ToSchema)]
@@ -19,6 +25,66 @@ pub enum HostError {
     #[error("Invalid address: {0}")]
     InvalidAddress(String),
 
+    /// For SGX quotes that don't verify.
+    #[error("Invalid quote: {0}")]
+    InvalidQuote(String),
+
+    /// For requesting a proof job that does not exist.
+    #[error("Job not found: {0}")]
+    JobNotFound(u64),
+
+    /// For cancelling a proof job that already finished.
+    #[error("Job already finished: {0}")]
+    JobAlreadyFinished(u64),
+
+    /// For requesting a batch that does not exist.
+    #[error("Batch not found: {0}")]
+    BatchNotFound(u64),
+
+    /// For cancelling a batch that already finished.
+    #[error("Batch already finished: {0}")]
+    BatchAlreadyFinished(u64),
+
+    /// For requests without a valid API key.
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    /// For requests with an API key that is not allowed to use the endpoint.
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    /// For requests over the rate limit or the daily quota of their API key.
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
+
+    /// For proof jobs that exceeded their resource limits.
+    #[error("Resource exhausted: {0}")]
+    ResourceExhausted(String),
+
+    /// For provers that crashed in their child process, see [crate::isolation].
+    #[error("The prover process crashed: {0}")]
+    #[schema(value_type = Value)]
+    ProverCrashed(ProverCrash),
+
+    /// For proof requests made while the host is shutting down.
+    #[error("The host is shutting down")]
+    ShuttingDown,
+
+    /// For proof requests made while the queue of the host is full, with the seconds to retry
+    /// after.
+    #[error("The queue of the host is full, retry in {0}s")]
+    QueueFull(u64),
+
+    /// For requests to a backend whose circuit breaker is open, with the breaker and the
+    /// seconds to retry after, see [crate::breakers].
+    #[error("The circuit breaker of {0} is open, retry in {1}s")]
+    CircuitOpen(String, u64),
+
+    /// For proof requests that fail validation, with the code and the field at fault.
+    #[error("Invalid proof request: {0}")]
+    #[schema(value_type = Problem)]
+    Validation(Problem),
+
     /// For I/O errors.
     #[error("There was a I/O error: {0}")]
     #[schema(value_type = Value)]
@@ -50,31 +116,83 @@ pub enum HostError {
     Anyhow(#[from] anyhow::Error),
 }
 
-impl IntoResponse for HostError {
-    fn into_response(self) -> axum::response::Response {
-        match self {
-            HostError::InvalidProofType(e)
-            | HostError::InvalidRequestConfig(e)
-            | HostError::InvalidAddress(e) => {
-                (StatusCode::BAD_REQUEST, e.to_string()).into_response()
-            }
-            HostError::Io(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
-            HostError::Serde(e) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
-            }
-            HostError::Anyhow(e) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
-            }
-            HostError::JoinHandle(e) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+impl HostError {
+    /// A failed validation of a request field.
+    pub fn invalid(code: ErrorCode, field: &str, detail: impl Into<String>) -> Self {
+        HostError::Validation(Problem::new(code, detail).with_field(field))
+    }
+
+    /// The problem details the error is returned to the client as.
+    pub fn problem(&self) -> Problem {
+        let (code, detail) = match self {
+            HostError::Validation(problem) => return problem.clone(),
+            HostError::InvalidProofType(e) => {
+                return Problem::new(
+                    ErrorCode::UnsupportedProofType,
+                    format!("Unknown proof type: {e}"),
+                )
+                .with_field("proof_type")
             }
-            HostError::GuestError(e) => {
-                (StatusCode::FAILED_DEPENDENCY, e.to_string()).into_response()
+            HostError::InvalidRequestConfig(e) => (ErrorCode::InvalidRequest, e.clone()),
+            HostError::InvalidAddress(e) => (ErrorCode::InvalidAddress, e.clone()),
+            HostError::InvalidQuote(e) => (ErrorCode::InvalidQuote, e.clone()),
+            HostError::JobNotFound(id) => (ErrorCode::JobNotFound, format!("Job not found: {id}")),
+            HostError::JobAlreadyFinished(id) => (
+                ErrorCode::JobAlreadyFinished,
+                format!("Job already finished: {id}"),
+            ),
+            HostError::BatchNotFound(_) => (ErrorCode::JobNotFound, self.to_string()),
+            HostError::BatchAlreadyFinished(_) => (ErrorCode::JobAlreadyFinished, self.to_string()),
+            HostError::Unauthorized(e) => (ErrorCode::Unauthorized, e.clone()),
+            HostError::Forbidden(e) => (ErrorCode::Forbidden, e.clone()),
+            HostError::TooManyRequests(e) => (ErrorCode::TooManyRequests, e.clone()),
+            HostError::ResourceExhausted(e) => (ErrorCode::ResourceExhausted, e.clone()),
+            HostError::ProverCrashed(crash) if crash.out_of_memory => {
+                (ErrorCode::ResourceExhausted, self.to_string())
             }
+            HostError::ProverCrashed(_) => (ErrorCode::ProverError, self.to_string()),
+            HostError::ShuttingDown => (ErrorCode::ShuttingDown, self.to_string()),
+            HostError::QueueFull(_) => (ErrorCode::QueueFull, self.to_string()),
+            HostError::CircuitOpen(..) => (ErrorCode::BackendUnavailable, self.to_string()),
+            HostError::Io(e) => (ErrorCode::Internal, e.to_string()),
+            HostError::Serde(e) => (ErrorCode::Internal, e.to_string()),
+            HostError::JoinHandle(e) => (ErrorCode::Internal, e.to_string()),
+            HostError::Anyhow(e) => (ErrorCode::Internal, e.to_string()),
+            HostError::GuestError(e) => (ErrorCode::ProverError, e.to_string()),
             HostError::FeatureNotSupportedError(e) => {
-                (StatusCode::METHOD_NOT_ALLOWED, e.to_string()).into_response()
+                (ErrorCode::FeatureNotSupported, e.to_string())
             }
+        };
+        Problem::new(code, detail)
+    }
+}
+
+impl IntoResponse for HostError {
+    fn into_response(self) -> axum::response::Response {
+        let problem = self.problem();
+        let status =
+            StatusCode::from_u16(problem.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = (
+            status,
+            [(header::CONTENT_TYPE, PROBLEM_JSON)],
+            Json(problem),
+        )
+            .into_response();
+        if let HostError::QueueFull(retry_after) | HostError::CircuitOpen(_, retry_after) = self {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
         }
+        response
+    }
+}
+
+impl From<JsonRejection> for HostError {
+    fn from(rejection: JsonRejection) -> Self {
+        HostError::Validation(Problem::new(
+            ErrorCode::MalformedBody,
+            rejection.body_text(),
+        ))
     }
 }
 
@@ -0,0 +1,254 @@
+//! Estimation of the cost of proving a block, without proving it.
+//!
+//! The input of the block is prepared with the preflight and the block is executed natively,
+//! which takes seconds instead of the hours proving takes. The gas the block uses is scaled
+//! with the calibration profile of every prover, measured on this machine, to the cycles and
+//! the time proving the block takes, which are priced with the rates of the
+//! [costs](crate::costs). The calibration profile is written by the [bench](crate::bench)
+//! command.
+
+use std::{
+    collections::BTreeMap, fs::File, io::BufReader, path::Path, sync::RwLock, time::Duration,
+};
+
+use anyhow::Context;
+use lazy_static::lazy_static;
+use raiko_lib::builder::{BlockBuilderStrategy, TaikoStrategy};
+use serde::{Deserialize, Serialize};
+use tracing::{info_span, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    costs::{self, LOCAL_SOURCE},
+    error::HostResult,
+    execution::prepare_input,
+    input_cache::InputCache,
+    request::{ProofRequest, ProofType},
+};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+/// The performance of a prover measured on this machine.
+pub struct Calibration {
+    /// The guest cycles per million gas, unset for provers without cycles.
+    #[serde(default)]
+    pub cycles_per_mgas: Option<f64>,
+    /// The guest cycles of a block without any gas used.
+    #[serde(default)]
+    pub base_cycles: u64,
+    /// The seconds proving takes per million gas.
+    pub seconds_per_mgas: f64,
+    /// The seconds proving a block without any gas used takes.
+    #[serde(default)]
+    pub base_seconds: f64,
+    /// The peak memory the host allocated while proving the calibration blocks, in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_memory: Option<u64>,
+}
+
+impl Calibration {
+    /// The estimated cycles of a block using the given gas.
+    pub fn cycles(&self, gas_used: u64) -> Option<u64> {
+        self.cycles_per_mgas
+            .map(|cycles_per_mgas| self.base_cycles + (cycles_per_mgas * mgas(gas_used)) as u64)
+    }
+
+    /// The estimated time proving a block using the given gas takes.
+    pub fn proving_time(&self, gas_used: u64) -> Duration {
+        Duration::from_secs_f64(
+            (self.base_seconds + self.seconds_per_mgas * mgas(gas_used)).max(0.0),
+        )
+    }
+}
+
+fn mgas(gas: u64) -> f64 {
+    gas as f64 / 1e6
+}
+
+/// The calibration of every prover measured on this machine, by proof type, e.g.
+/// `{"risc0": {"cycles_per_mgas": 9.5e8, "base_cycles": 2e8, "seconds_per_mgas": 950}}`.
+pub type CalibrationProfile = BTreeMap<String, Calibration>;
+
+lazy_static! {
+    static ref CALIBRATION: RwLock<CalibrationProfile> = RwLock::new(CalibrationProfile::new());
+}
+
+/// Read a calibration profile from a file.
+pub fn load_calibration(path: &Path) -> HostResult<CalibrationProfile> {
+    let file = File::open(path)
+        .with_context(|| format!("Could not open the calibration profile {}", path.display()))?;
+    Ok(serde_json::from_reader(BufReader::new(file))?)
+}
+
+/// Set the calibration profile the proofs are estimated with.
+pub fn configure(profile: CalibrationProfile) {
+    *CALIBRATION.write().unwrap_or_else(|e| e.into_inner()) = profile;
+}
+
+/// The estimated time proving a block using the given gas takes with the given prover, unset
+/// without a calibration for it.
+pub fn proving_time(proof_type: &ProofType, gas_used: u64) -> Option<Duration> {
+    CALIBRATION
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&proof_type.to_string())
+        .map(|calibration| calibration.proving_time(gas_used))
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+/// The estimated cost of proving a block with a prover.
+pub struct BackendEstimate {
+    pub proof_type: String,
+    /// Where the proof would be generated, `local` or the remote proving service, e.g.
+    /// `bonsai`.
+    pub source: String,
+    /// The estimated cycles of the guest, unset for provers without cycles.
+    pub cycles: Option<u64>,
+    /// The estimated seconds proving takes, unset for remote proving services.
+    pub proving_time: Option<f64>,
+    /// The estimated cost, unset without a rate for the source.
+    pub cost: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// The estimated cost of proving a block with every calibrated prover.
+pub struct ProofEstimate {
+    pub network: String,
+    pub block_number: u64,
+    /// The gas the block used, executed natively.
+    pub gas_used: u64,
+    /// The estimate of every calibrated prover and of the remote proving services of the
+    /// provers with a rate for them.
+    pub estimates: Vec<BackendEstimate>,
+}
+
+/// The remote proving service of a proof type, priced per proof or per cycle.
+fn remote_source(proof_type: &ProofType) -> Option<&'static str> {
+    match proof_type {
+        ProofType::Risc0 => Some("bonsai"),
+        ProofType::Sp1 => Some("sp1_network"),
+        _ => None,
+    }
+}
+
+/// Estimate proving a block using the given gas with every prover of the calibration profile.
+pub fn estimate_backends(profile: &CalibrationProfile, gas_used: u64) -> Vec<BackendEstimate> {
+    let mut estimates = Vec::new();
+    for (name, calibration) in profile {
+        let proof_type: ProofType = match name.parse() {
+            Ok(proof_type) => proof_type,
+            Err(e) => {
+                warn!("Skipping the calibration of {name}: {e}");
+                continue;
+            }
+        };
+        let cycles = calibration.cycles(gas_used);
+        let time = calibration.proving_time(gas_used);
+        estimates.push(BackendEstimate {
+            proof_type: proof_type.to_string(),
+            source: LOCAL_SOURCE.to_string(),
+            cycles,
+            proving_time: Some(time.as_secs_f64()),
+            cost: costs::estimate(&proof_type, LOCAL_SOURCE, time, cycles),
+        });
+        if let Some(source) = remote_source(&proof_type) {
+            if let Some(cost) = costs::estimate(&proof_type, source, time, cycles) {
+                estimates.push(BackendEstimate {
+                    proof_type: proof_type.to_string(),
+                    source: source.to_string(),
+                    cycles,
+                    proving_time: None,
+                    cost: Some(cost),
+                });
+            }
+        }
+    }
+    estimates
+}
+
+/// Prepare the input of the block of the request and execute it natively, to estimate proving
+/// it with every calibrated prover. The input is cached, so proving the block afterwards
+/// doesn't run the preflight again.
+pub async fn estimate(
+    proof_request: &ProofRequest,
+    input_cache: &InputCache,
+) -> HostResult<ProofEstimate> {
+    let input = prepare_input(proof_request.clone()).await?;
+    let (result, input) = tokio::task::spawn_blocking(move || {
+        let result = info_span!("guest_execution").in_scope(|| TaikoStrategy::build_from(&input));
+        (result, input)
+    })
+    .await?;
+    let (header, _mpt_node) = result.context("The block failed to execute")?;
+    input_cache.put(&proof_request.network, &input)?;
+
+    let profile = CALIBRATION
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    Ok(ProofEstimate {
+        network: proof_request.network.clone(),
+        block_number: proof_request.block_number,
+        gas_used: header.gas_used,
+        estimates: estimate_backends(&profile, header.gas_used),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calibration() {
+        let calibration = Calibration {
+            cycles_per_mgas: Some(1e8),
+            base_cycles: 50_000_000,
+            seconds_per_mgas: 60.0,
+            base_seconds: 30.0,
+            peak_memory: None,
+        };
+        assert_eq!(calibration.cycles(15_000_000), Some(1_550_000_000));
+        assert_eq!(
+            calibration.proving_time(15_000_000),
+            Duration::from_secs(930)
+        );
+        assert_eq!(calibration.proving_time(0), Duration::from_secs(30));
+        let calibration = Calibration {
+            cycles_per_mgas: None,
+            ..calibration
+        };
+        assert_eq!(calibration.cycles(15_000_000), None);
+    }
+
+    #[test]
+    fn test_estimate_backends() {
+        let profile: CalibrationProfile = serde_json::from_value(serde_json::json!({
+            "sgx": {"seconds_per_mgas": 0.5, "base_seconds": 2},
+            "risc0": {"cycles_per_mgas": 1e8, "seconds_per_mgas": 60},
+            "unknown": {"seconds_per_mgas": 1},
+        }))
+        .unwrap();
+        let estimates = estimate_backends(&profile, 2_000_000);
+        // The costs depend on the rates configured by other tests.
+        let local: Vec<_> = estimates
+            .iter()
+            .filter(|estimate| estimate.source == LOCAL_SOURCE)
+            .map(|estimate| {
+                (
+                    estimate.proof_type.as_str(),
+                    estimate.cycles,
+                    estimate.proving_time,
+                )
+            })
+            .collect();
+        assert_eq!(
+            local,
+            vec![
+                ("risc0", Some(200_000_000), Some(120.0)),
+                ("sgx", None, Some(3.0))
+            ]
+        );
+        assert!(estimates
+            .iter()
+            .all(|estimate| estimate.source != "bonsai" || estimate.proving_time.is_none()));
+    }
+}
@@ -1,92 +1,766 @@
-use alloy_consensus::Sealable;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use alloy_consensus::{Header as AlloyConsensusHeader, Sealable};
 use alloy_primitives::B256;
+use anyhow::anyhow;
+use raiko_client::ErrorCode;
 use raiko_lib::{
-    builder::{BlockBuilderStrategy, TaikoStrategy},
+    builder::{
+        profiler::{profile_block, EvmProfile},
+        BlockBuilderStrategy, TaikoStrategy,
+    },
     input::{GuestInput, GuestOutput, TaikoProverData, WrappedHeader},
     protocol_instance::{assemble_protocol_instance, ProtocolInstance},
-    prover::{to_proof, Proof, Prover, ProverResult},
+    prover::{
+        to_proof, CancellationToken, GuestProfile, ProgressReporter, Proof, Prover, ProverError,
+        ProverProgress, ProverResult,
+    },
     Measurement,
 };
 use serde::{Deserialize, Serialize};
-use tracing::{info, warn};
+use serde_json::Value;
+use tokio::task::JoinHandle;
+use tracing::{info, info_span, warn, Instrument, Span};
 
 use crate::{
-    error::HostResult,
-    memory,
-    metrics::{inc_guest_req_count, observe_guest_time, observe_prepare_input_time},
-    preflight::preflight,
-    request::ProofRequest,
+    artifacts,
+    blob_provider::{get_providers, BeaconProvider, BlobProviders},
+    breakers,
+    chain_registry::{get_chain, ChainConfig},
+    costs::{self, LOCAL_SOURCE},
+    error::{HostError, HostResult},
+    faults, finality,
+    fixtures::{self, FixtureSession},
+    gpu, guests,
+    header_store::{get_store, HeaderStore, HEADER_STORE_CAPACITY},
+    input_cache::InputCache,
+    input_codec, isolation,
+    jobs::progress::{ProgressEvent, ProgressSender},
+    l1_events, memory,
+    metrics::{
+        inc_guest_error, inc_guest_req_count, inc_guest_success, inc_host_error,
+        inc_host_req_count, inc_proof_cache_hit, inc_proof_cache_miss, inc_proof_verification,
+        observe_guest_time, observe_prepare_input_time, observe_proof_size, observe_total_time,
+    },
+    preflight::{get_block_hash, get_block_header, preflight},
+    proof_cache::{ProofCache, ProofKey},
+    proof_output,
+    provider_pool::{get_pool, EndpointKind},
+    request::{ProofRequest, ProofRequestOpt},
+    runtimes, workers, workspace, Cli, ProveArgs,
 };
 
-/// Execute the proof generation.
+/// Get the cached input of the requested block. Only the block hash is fetched from the node,
+/// so a reorged block is never proven with the input of the old block.
+async fn get_cached_input(
+    input_cache: &InputCache,
+    proof_request: &ProofRequest,
+) -> Option<GuestInput> {
+    if !input_cache.is_enabled() {
+        return None;
+    }
+    let block_hash = get_block_hash(&proof_request.rpc, proof_request.block_number)
+        .await
+        .map_err(|e| {
+            warn!(
+                "Could not get the hash of block {}: {e}",
+                proof_request.block_number
+            )
+        })
+        .ok()?;
+    input_cache.get(
+        &proof_request.network,
+        proof_request.block_number,
+        &block_hash,
+    )
+}
+
+/// Generate a proof for the given request.
+///
+/// Uses the cached input for the block when available and caches the prepared input
+/// afterwards. Returns the proof from the proof cache instead of proving, unless `force` is
+/// set on the request. Records the host and guest metrics for the request.
+pub async fn prove(
+    proof_request: &ProofRequest,
+    input_cache: &InputCache,
+    proof_cache: &ProofCache,
+    progress: &ProgressSender,
+) -> HostResult<Proof> {
+    prove_block(proof_request, input_cache, proof_cache, progress)
+        .await
+        .map(|(_, proof)| proof)
+}
+
+/// Generate a proof for the given request like [prove], returning the input of the block
+/// with the proof unless the proof was taken from the proof cache, see [execute].
+pub async fn prove_block(
+    proof_request: &ProofRequest,
+    input_cache: &InputCache,
+    proof_cache: &ProofCache,
+    progress: &ProgressSender,
+) -> HostResult<(Option<GuestInput>, Proof)> {
+    inc_host_req_count(&proof_request.network, proof_request.block_number);
+
+    println!(
+        "# Generating proof for block {} on {}",
+        proof_request.block_number, proof_request.network
+    );
+
+    // Check for a cached input for the given request config.
+    let cached_input = get_cached_input(input_cache, proof_request).await;
+    if let Some(input) = &cached_input {
+        finality::check(proof_request, input).await?;
+    }
+
+    prove_with_input(
+        proof_request,
+        input_cache,
+        proof_cache,
+        cached_input,
+        progress,
+    )
+    .await
+}
+
+/// Prepare the input of the block for the request, from the input cache if available, to be
+/// proven with [prove_with_input] once a prover is free. Waits for the block to be final before
+/// returning it, see [finality].
+pub async fn prepare_block_input(
+    proof_request: &ProofRequest,
+    input_cache: &InputCache,
+    progress: &ProgressSender,
+) -> HostResult<GuestInput> {
+    let input = fetch_block_input(proof_request, input_cache, progress).await?;
+    finality::check(proof_request, &input).await?;
+    Ok(input)
+}
+
+/// Prepare the input of the block for the request like [prepare_block_input], without waiting
+/// for the block to be final, e.g. to wait for it after releasing the preflight permit.
+pub async fn fetch_block_input(
+    proof_request: &ProofRequest,
+    input_cache: &InputCache,
+    progress: &ProgressSender,
+) -> HostResult<GuestInput> {
+    inc_host_req_count(&proof_request.network, proof_request.block_number);
+
+    println!(
+        "# Preparing input for block {} on {}",
+        proof_request.block_number, proof_request.network
+    );
+
+    let input = match get_cached_input(input_cache, proof_request).await {
+        Some(input) => Ok(input),
+        None => run_preflight(proof_request, progress).await,
+    };
+    if input.is_err() {
+        inc_host_error(&proof_request.network, proof_request.block_number);
+    }
+    input
+}
+
+/// Generate a proof for the given request, starting from the given input if available. The
+/// input is returned with the proof when it was given or prepared, see [execute]. A given input
+/// must be final already, e.g. from [prepare_block_input].
+pub async fn prove_with_input(
+    proof_request: &ProofRequest,
+    input_cache: &InputCache,
+    proof_cache: &ProofCache,
+    cached_input: Option<GuestInput>,
+    progress: &ProgressSender,
+) -> HostResult<(Option<GuestInput>, Proof)> {
+    // Execute the proof generation.
+    let total_time = Measurement::start("", false);
+    let (input, proof) = execute(proof_request, cached_input, proof_cache, progress)
+        .await
+        .map_err(|e| {
+            let total_time = total_time.stop_with("====> Proof generation failed");
+            observe_total_time(
+                &proof_request.proof_type,
+                &proof_request.network,
+                proof_request.block_number,
+                total_time.as_millis(),
+                false,
+            );
+            match e {
+                HostError::GuestError(e) => {
+                    inc_guest_error(
+                        &proof_request.proof_type,
+                        &proof_request.network,
+                        proof_request.block_number,
+                    );
+                    HostError::GuestError(e)
+                }
+                e => {
+                    inc_host_error(&proof_request.network, proof_request.block_number);
+                    e
+                }
+            }
+        })?;
+    inc_guest_success(
+        &proof_request.proof_type,
+        &proof_request.network,
+        proof_request.block_number,
+    );
+    let total_time = total_time.stop_with("====> Complete proof generated");
+    observe_total_time(
+        &proof_request.proof_type,
+        &proof_request.network,
+        proof_request.block_number,
+        total_time.as_millis(),
+        true,
+    );
+
+    // Cache the input for future use.
+    if let Some(input) = &input {
+        input_cache.put(&proof_request.network, input)?;
+    }
+
+    Ok((input, proof))
+}
+
+/// Execute the proof generation. The input is only returned when it was given or had to be
+/// prepared, a proof found in the proof cache is returned without preparing the input. Only the
+/// prepared input is checked to be final, a given input was checked by whoever prepared it.
 pub async fn execute(
     proof_request: &ProofRequest,
     cached_input: Option<GuestInput>,
-) -> HostResult<(GuestInput, Proof)> {
-    // 1. Prepare input - use cached input if available, otherwise prepare new input
-    let input = if let Some(cached_input) = cached_input {
-        println!("Using cached input");
-        cached_input
-    } else {
-        memory::reset_stats();
-        let measurement = Measurement::start("Generating input...", false);
-        let input = prepare_input(proof_request.clone()).await;
-        let input_time = measurement.stop_with("=> Input generated");
-        observe_prepare_input_time(
-            proof_request.block_number,
-            input_time.as_millis(),
-            input.is_ok(),
-        );
-        memory::print_stats("Input generation peak memory used: ");
-        input?
+    proof_cache: &ProofCache,
+    progress: &ProgressSender,
+) -> HostResult<(Option<GuestInput>, Proof)> {
+    // 1. Look up the proof of the block before preparing its input. Without an input, only the
+    // block hash is fetched from the node, so a reorged block is never served the old proof.
+    if !proof_request.force && proof_cache.is_enabled() {
+        let block_hash = match &cached_input {
+            Some(input) => Some(input.block_hash),
+            None => get_block_hash(&proof_request.rpc, proof_request.block_number)
+                .await
+                .map_err(|e| {
+                    warn!(
+                        "Could not get the hash of block {}: {e}",
+                        proof_request.block_number
+                    )
+                })
+                .ok(),
+        };
+        if let Some(block_hash) = block_hash {
+            let proof_key = ProofKey::new(proof_request, block_hash)?;
+            if let Some(proof) = proof_cache.get(&proof_key).await {
+                info!("Using cached proof {proof_key}");
+                inc_proof_cache_hit(&proof_request.proof_type);
+                progress.send(ProgressEvent::ProofGenerated);
+                return Ok((cached_input, proof));
+            }
+        }
+        inc_proof_cache_miss(&proof_request.proof_type);
+    }
+
+    // 2. Prepare input - use cached input if available, otherwise prepare new input
+    let prepared = cached_input.is_none();
+    let input = load_input(proof_request, cached_input, progress).await?;
+    let proof_key = ProofKey::new(proof_request, input.block_hash)?;
+
+    // 3. Check the block was proposed in a final L1 block like its input says, before proving
+    // it.
+    if prepared {
+        finality::check(proof_request, &input).await?;
+    }
+    l1_events::check_inclusion(proof_request, &input).await?;
+
+    // 4. Prove, on a worker if one is free for the proof type, see [workers].
+    let proof = match workers::acquire(&proof_request.proof_type) {
+        Some(worker) => worker.prove(proof_request, input.clone(), progress).await?,
+        None => generate_proof(proof_request, &input, progress).await?,
     };
+    proof_cache.put(&proof_key, &proof).await;
+    progress.send(ProgressEvent::ProofGenerated);
 
-    // 2. Test run the block
-    memory::reset_stats();
-    let build_result = TaikoStrategy::build_from(&input);
-    let output = match &build_result {
-        Ok((header, _mpt_node)) => {
-            info!("Verifying final state using provider data ...");
-            info!("Final block hash derived successfully. {}", header.hash());
-            info!("Final block header derived successfully. {header:?}");
-            let pi = proof_request
-                .proof_type
-                .instance_hash(assemble_protocol_instance(&input, header)?)?;
-            // Make sure the blockhash from the node matches the one from the builder
-            assert_eq!(header.hash(), input.block_hash, "block hash unexpected");
-            GuestOutput::Success((
-                WrappedHeader {
-                    header: header.clone(),
-                },
-                pi,
-            ))
+    Ok((Some(input), proof))
+}
+
+/// The proof request to prove an input saved before with, e.g. a file of the input cache, for
+/// [generate_proof] to prove the block without the preflight. The block is the block of the
+/// input, the other options are taken from the request options. The block is not cross checked,
+/// only its proposal is read from L1 to check the input against.
+pub fn replay_request(
+    mut request_opt: ProofRequestOpt,
+    input: &GuestInput,
+) -> HostResult<ProofRequest> {
+    request_opt.block_number = Some(input.block_number);
+    let mut proof_request = ProofRequest::try_from(request_opt)?;
+    proof_request.cross_check = false;
+    check_replay_spec(&proof_request, input)?;
+    Ok(proof_request)
+}
+
+/// Check that the chain spec of an input to replay is the spec of the chain of the request, the
+/// rules the block is proven with are taken from the input.
+fn check_replay_spec(proof_request: &ProofRequest, input: &GuestInput) -> HostResult<()> {
+    let chain_spec = get_chain(&proof_request.network)?.chain_spec;
+    if chain_spec != input.chain_spec {
+        return Err(HostError::invalid(
+            ErrorCode::InvalidRequest,
+            "network",
+            format!(
+                "The chain spec of the input of chain {} is not the spec of {} with chain {}",
+                input.chain_spec.chain_id, proof_request.network, chain_spec.chain_id
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Prove the block of the arguments, or the block of the input in the file of the arguments,
+/// and print the proof or write it to the output file. The inputs prepared by the preflight
+/// are taken from and kept in the input cache of the options.
+pub async fn prove_input_file(opts: &Cli, args: &ProveArgs) -> HostResult<()> {
+    opts.load_chains()?;
+    let mut request_opt = opts.proof_request_opt.clone();
+    if let Some(network) = &args.network {
+        request_opt.network = Some(network.clone());
+    }
+    if let Some(proof_type) = &args.proof_type {
+        request_opt.proof_type = Some(proof_type.clone());
+    }
+    let proof = match &args.input {
+        Some(path) => {
+            let (input, _) = input_codec::decode_input(BufReader::new(File::open(path)?))?;
+            let proof_request = match &args.request {
+                Some(request) => {
+                    let proof_request =
+                        serde_json::from_reader(BufReader::new(File::open(request)?))?;
+                    check_replay_spec(&proof_request, &input)?;
+                    proof_request
+                }
+                None => replay_request(request_opt, &input)?,
+            };
+            println!(
+                "# Generating proof for block {} on {} from {}",
+                proof_request.block_number,
+                proof_request.network,
+                path.display()
+            );
+            l1_events::check_inclusion(&proof_request, &input).await?;
+            generate_proof(&proof_request, &input, &ProgressSender::default()).await?
+        }
+        None => {
+            request_opt.block_number = args.block.or(request_opt.block_number);
+            let proof_request = ProofRequest::try_from(request_opt)?;
+            if let Some(cache_path) = &opts.cache_path {
+                std::fs::create_dir_all(cache_path)?;
+            }
+            let input_cache = InputCache::new(
+                opts.cache_path.clone(),
+                opts.cache_max_size.map(|size| size * 1024 * 1024),
+                opts.cache_max_age
+                    .map(|hours| Duration::from_secs(hours * 60 * 60)),
+            );
+            prove(
+                &proof_request,
+                &input_cache,
+                &ProofCache::default(),
+                &ProgressSender::default(),
+            )
+            .await?
+        }
+    };
+    match &args.output {
+        Some(output) => {
+            let mut writer = BufWriter::new(File::create(output)?);
+            serde_json::to_writer_pretty(&mut writer, &proof)?;
+            writer.flush()?;
         }
-        Err(_) => {
-            warn!("Proving bad block construction!");
-            GuestOutput::Failure
+        None => println!("{}", serde_json::to_string_pretty(&proof)?),
+    }
+    Ok(())
+}
+
+/// Run the block in the host and generate the proof for the given input with the prover of
+/// the request, with the stats of the proof added to it. Fails right away while the circuit
+/// breaker of the prover is open, see [breakers].
+pub async fn generate_proof(
+    proof_request: &ProofRequest,
+    input: &GuestInput,
+    progress: &ProgressSender,
+) -> HostResult<Proof> {
+    let permit = breakers::acquire(breakers::prover(&proof_request.proof_type))?;
+    let proof = run_proof(proof_request, input, progress).await;
+    permit.record(&proof);
+    proof
+}
+
+async fn run_proof(
+    proof_request: &ProofRequest,
+    input: &GuestInput,
+    progress: &ProgressSender,
+) -> HostResult<Proof> {
+    // Isolated provers prove in a child process, see [isolation]
+    if let Some(limits) = isolation::limits(&proof_request.proof_type) {
+        return isolation::generate_proof(proof_request, input, &limits).await;
+    }
+
+    // 1. Test run the block, unless it runs next to the prover when cross checking
+    let chain_header = if proof_request.cross_check {
+        Some(get_chain_header(proof_request, input).await?)
+    } else {
+        None
+    };
+    memory::reset_stats();
+    let output = match &chain_header {
+        Some(header) => guest_output(proof_request, input, header)?,
+        None => {
+            let build_result =
+                info_span!("guest_execution").in_scope(|| TaikoStrategy::build_from(input));
+            match &build_result {
+                Ok((header, _mpt_node)) => {
+                    let _span = info_span!("verification").entered();
+                    info!("Verifying final state using provider data ...");
+                    info!("Final block hash derived successfully. {}", header.hash());
+                    info!("Final block header derived successfully. {header:?}");
+                    // Make sure the blockhash from the node matches the one from the builder
+                    assert_eq!(header.hash(), input.block_hash, "block hash unexpected");
+                    guest_output(proof_request, input, header)?
+                }
+                Err(_) => {
+                    warn!("Proving bad block construction!");
+                    GuestOutput::Failure
+                }
+            }
         }
     };
     memory::print_stats("Guest program peak memory used: ");
 
-    // 3. Prove
+    // 2. Prove
     memory::reset_stats();
     let measurement = Measurement::start("Generating proof...", false);
-    inc_guest_req_count(&proof_request.proof_type, proof_request.block_number);
-    let res = proof_request
-        .proof_type
-        .run_prover(input.clone(), output, &serde_json::to_value(proof_request)?)
-        .await
-        .map(|proof| (input, proof));
+    inc_guest_req_count(
+        &proof_request.proof_type,
+        &proof_request.network,
+        proof_request.block_number,
+    );
+    let stats = Arc::new(Mutex::new(ProofStats::default()));
+    // The provers keep their checkpoints in the workspace, see [workspace].
+    let workspace = workspace::create(proof_request)?;
+    let guest = guests::select(proof_request)?;
+    let mut config = serde_json::to_value(proof_request)?;
+    if let Some(config) = config.as_object_mut() {
+        if let Some(workspace) = &workspace {
+            config.insert(
+                "workspace".to_string(),
+                workspace.display().to_string().into(),
+            );
+        }
+        if let Some(device) = gpu::cuda_device() {
+            config.insert("cuda_device".to_string(), device.into());
+        }
+        // The provers take the selected guest instead of its name.
+        match &guest {
+            Some(guest) => config.insert(
+                "guest".to_string(),
+                serde_json::to_value(guest.selection())?,
+            ),
+            None => config.remove("guest"),
+        };
+    }
+    // Dropping the proof, e.g. when its job is cancelled, tells the prover to stop.
+    let cancellation = CancellationToken::default();
+    let _cancel_on_drop = CancelOnDrop(cancellation.clone());
+    let reporter = stats_reporter(progress.reporter(&proof_request.proof_type), stats.clone())
+        .with_cancellation(cancellation);
+    let prover_output = output.clone();
+    let proof = faults::kill_prover(reporter, |reporter| async move {
+        match chain_header {
+            Some(header) => {
+                cross_check(
+                    proof_request,
+                    input,
+                    header,
+                    prover_output,
+                    config,
+                    reporter,
+                )
+                .await
+            }
+            None => {
+                let proof_type = proof_request.proof_type.clone();
+                let prover_input = input.clone();
+                let mut prover = AbortOnDrop(runtimes::spawn_prover(
+                    async move {
+                        proof_type
+                            .run_prover(prover_input, prover_output, &config, &reporter)
+                            .await
+                    }
+                    .instrument(info_span!("proof_generation")),
+                ));
+                (&mut prover.0).await?
+            }
+        }
+    })
+    .await;
     let guest_time = measurement.stop_with("=> Proof generated");
     observe_guest_time(
         &proof_request.proof_type,
+        &proof_request.network,
         proof_request.block_number,
         guest_time.as_millis(),
-        res.is_ok(),
+        proof.is_ok(),
     );
     memory::print_stats("Prover peak memory used: ");
+    let mut proof = proof?;
+    if let Some(workspace) = &workspace {
+        artifacts::keep_workspace(workspace);
+    }
+
+    let mut stats = stats.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    stats.proof_size = serde_json::to_vec(&proof).map_or(0, |proof| proof.len());
+    stats.peak_memory = memory::get_max_allocated();
+    observe_proof_size(
+        &proof_request.proof_type,
+        stats.proof_size,
+        stats.peak_memory,
+    );
+    stats.cost = costs::record(
+        &proof_request.proof_type,
+        &proof_request.network,
+        proof_request.block_number,
+        stats.source.as_deref().unwrap_or(LOCAL_SOURCE),
+        guest_time,
+        stats.cycles,
+    );
+
+    // 3. Verify the proof locally, so a proof of the wrong program doesn't reach L1. Verifying
+    // a SP1 or RISC Zero proof takes seconds of CPU, so it runs off the async workers.
+    let verification = {
+        let span = info_span!("proof_verification");
+        let proof_type = proof_request.proof_type.clone();
+        let (proof, input, output) = (proof.clone(), input.clone(), output.clone());
+        tokio::task::spawn_blocking(move || {
+            span.in_scope(|| proof_type.verify(&proof, &input, &output))
+        })
+        .await??
+    };
+    if let Some(proof) = proof.as_object_mut() {
+        if let Ok(stats) = serde_json::to_value(&stats) {
+            proof.insert("stats".to_string(), stats);
+        }
+        if let Some(verification) = verification {
+            inc_proof_verification(&proof_request.proof_type, verification.is_ok());
+            proof.insert("verified".to_string(), verification.is_ok().into());
+            if let Err(e) = verification {
+                warn!(
+                    "The {} proof of block {} does not verify: {e}",
+                    proof_request.proof_type, proof_request.block_number
+                );
+                proof.insert("verification_error".to_string(), e.into());
+            }
+        }
+    }
+    proof_output::annotate(&mut proof, proof_request, input, &output)?;
+    Ok(proof)
+}
+
+/// The output of the guest for the block with the given header.
+fn guest_output(
+    proof_request: &ProofRequest,
+    input: &GuestInput,
+    header: &AlloyConsensusHeader,
+) -> HostResult<GuestOutput> {
+    let pi = proof_request
+        .proof_type
+        .instance_hash(assemble_protocol_instance(input, header)?)?;
+    Ok(GuestOutput::Success((
+        WrappedHeader {
+            header: header.clone(),
+        },
+        pi,
+    )))
+}
+
+/// Get the header of the block of the input from the node, for the prover to prove while the
+/// block is executed natively.
+async fn get_chain_header(
+    proof_request: &ProofRequest,
+    input: &GuestInput,
+) -> HostResult<AlloyConsensusHeader> {
+    let header = get_block_header(&proof_request.rpc, proof_request.block_number).await?;
+    if header.hash() != input.block_hash {
+        return Err(HostError::Anyhow(anyhow!(
+            "Block {} was reorged since its input was prepared",
+            proof_request.block_number
+        )));
+    }
+    Ok(header)
+}
+
+/// Prove the on-chain block while it's executed natively on the same input, and abort the
+/// prover as soon as the native execution doesn't reproduce the block, e.g. because the input
+/// misses some state. Provers blocking their thread only stop at their next await point.
+async fn cross_check(
+    proof_request: &ProofRequest,
+    input: &GuestInput,
+    chain_header: AlloyConsensusHeader,
+    output: GuestOutput,
+    config: Value,
+    reporter: ProgressReporter,
+) -> HostResult<Proof> {
+    let proof_type = proof_request.proof_type.clone();
+    let prover_input = input.clone();
+    let mut prover = AbortOnDrop(runtimes::spawn_prover(
+        async move {
+            proof_type
+                .run_prover(prover_input, output, &config, &reporter)
+                .await
+        }
+        .instrument(info_span!("proof_generation")),
+    ));
+
+    let build_result = tokio::task::block_in_place(|| {
+        info_span!("guest_execution").in_scope(|| TaikoStrategy::build_from(input))
+    });
+    {
+        let _span = info_span!("verification").entered();
+        let header = match build_result {
+            Ok((header, _mpt_node)) => header,
+            Err(e) => {
+                return Err(HostError::GuestError(ProverError::GuestError(format!(
+                    "Cross check failed, the native execution of block {} failed: {e}",
+                    proof_request.block_number
+                ))))
+            }
+        };
+        if header.hash() != chain_header.hash() {
+            return Err(HostError::GuestError(ProverError::GuestError(format!(
+                "Cross check failed, the native execution of block {} results in block hash {} \
+                 and state root {} instead of {} and {}",
+                proof_request.block_number,
+                header.hash(),
+                header.state_root,
+                chain_header.hash(),
+                chain_header.state_root
+            ))));
+        }
+        info!(
+            "Cross check passed, the native execution reproduced block {}",
+            proof_request.block_number
+        );
+    }
+    (&mut prover.0).await?
+}
+
+/// Aborts the task when dropped, so a failed cross check or an aborted job stops its prover.
+struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Cancels the token when dropped, so the provers blocking their thread, which an aborted task
+/// doesn't stop, stop at their next step.
+struct CancelOnDrop(CancellationToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// The cost of generating a proof, added to the proof as `stats`.
+#[derive(Clone, Debug, Default, Serialize)]
+struct ProofStats {
+    /// The cycles of the guest execution, if the prover reports them.
+    cycles: Option<u64>,
+    /// The segments (Risc0) or shards (SP1) of the guest execution, if the prover reports
+    /// them.
+    segments: Option<u64>,
+    /// The size of the proof in bytes, without the stats.
+    proof_size: usize,
+    /// The peak memory allocated by the host while generating the proof, in bytes.
+    peak_memory: usize,
+    /// The estimated cost of the proof, unset without a rate for its source.
+    cost: Option<f64>,
+    /// The profile the guest reported with `profile_guest`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guest_profile: Option<GuestProfile>,
+    /// The remote proving service that generated the proof, e.g. `bonsai`.
+    #[serde(skip)]
+    source: Option<String>,
+}
 
-    res
+/// Forward the progress to the reporter, keeping the stats of the guest execution and the
+/// remote proving service.
+fn stats_reporter(reporter: ProgressReporter, stats: Arc<Mutex<ProofStats>>) -> ProgressReporter {
+    let cancellation = reporter.cancellation();
+    ProgressReporter::new(move |progress| {
+        match &progress {
+            ProverProgress::Executed { cycles, segments } => {
+                let mut stats = stats.lock().unwrap_or_else(|e| e.into_inner());
+                stats.cycles = *cycles;
+                stats.segments = *segments;
+            }
+            ProverProgress::RemoteSession { service, .. } => {
+                stats.lock().unwrap_or_else(|e| e.into_inner()).source = Some(service.clone());
+            }
+            ProverProgress::Profiled(profile) => {
+                stats
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .guest_profile = Some(profile.clone());
+            }
+            _ => {}
+        }
+        reporter.report(progress);
+    })
+    .with_cancellation(cancellation)
+}
+
+/// Use the cached input if available, otherwise prepare a new input for the request.
+async fn load_input(
+    proof_request: &ProofRequest,
+    cached_input: Option<GuestInput>,
+    progress: &ProgressSender,
+) -> HostResult<GuestInput> {
+    let input = if let Some(mut cached_input) = cached_input {
+        println!("Using cached input");
+        // The input cache is keyed by the block, the prover data is the one of this request.
+        cached_input.taiko.prover_data = TaikoProverData {
+            graffiti: proof_request.graffiti,
+            prover: proof_request.prover,
+        };
+        cached_input
+    } else {
+        run_preflight(proof_request, progress).await?
+    };
+    progress.send(ProgressEvent::InputPrepared);
+    Ok(input)
+}
+
+/// Prepare a new input for the request with the preflight.
+async fn run_preflight(
+    proof_request: &ProofRequest,
+    progress: &ProgressSender,
+) -> HostResult<GuestInput> {
+    memory::reset_stats();
+    progress.send(ProgressEvent::PreflightStarted);
+    let measurement = Measurement::start("Generating input...", false);
+    let input = prepare_input(proof_request.clone())
+        .instrument(info_span!("preflight"))
+        .await;
+    let input_time = measurement.stop_with("=> Input generated");
+    observe_prepare_input_time(
+        &proof_request.network,
+        proof_request.block_number,
+        input_time.as_millis(),
+        input.is_ok(),
+    );
+    memory::print_stats("Input generation peak memory used: ");
+    input
 }
 
 /// prepare input data for provers
@@ -102,35 +776,164 @@ pub async fn prepare_input(
         ..
     }: ProofRequest,
 ) -> HostResult<GuestInput> {
-    tokio::task::spawn_blocking(move || {
+    let chain = get_chain(&network)?;
+    let prover_data = TaikoProverData { graffiti, prover };
+    if let Some((mode, path)) = fixtures::fixture_path(&chain.name, block_number) {
+        // Only the responses of the nodes are in the fixture, so the headers and the blobs the
+        // host keeps itself are not used.
+        let session = FixtureSession::start(mode, path, [rpc, l1_rpc, beacon_rpc]).await?;
+        let [rpc, l1_rpc, beacon_rpc] = session.urls().clone();
+        let blob_providers =
+            BlobProviders::new(vec![Box::new(BeaconProvider::new(beacon_rpc))], None);
+        let header_store = Arc::new(HeaderStore::new(HEADER_STORE_CAPACITY));
+        let input = spawn_preflight(
+            &chain,
+            block_number,
+            prover_data,
+            [rpc, l1_rpc],
+            header_store,
+            blob_providers,
+        )
+        .await?;
+        session.finish()?;
+        return Ok(input);
+    }
+
+    let pools = [
+        (EndpointKind::Rpc, rpc, &chain.rpc_fallbacks),
+        (EndpointKind::Rpc, l1_rpc, &chain.l1_rpc_fallbacks),
+        (
+            EndpointKind::Beacon,
+            beacon_rpc,
+            &chain.beacon_rpc_fallbacks,
+        ),
+    ]
+    .map(|(kind, url, fallbacks)| {
+        let mut urls = vec![url];
+        urls.extend(fallbacks.iter().cloned());
+        get_pool(kind, urls)
+    });
+    let attempts = pools.iter().map(|pool| pool.len()).max().unwrap_or(1);
+
+    // The breaker opens once no endpoint of the network gets through the preflight.
+    let permit = breakers::acquire(breakers::network(&chain.name))?;
+    let input = async {
+        let mut tried: [Vec<String>; 3] = Default::default();
+        loop {
+            let [rpc, l1_rpc, beacon_rpc] = [0, 1, 2].map(|i| {
+                let url = pools[i]
+                    .select(&tried[i])
+                    .expect("pools have at least one endpoint");
+                tried[i].push(url.clone());
+                url
+            });
+            let blob_providers =
+                get_providers(Some(beacon_rpc.clone()), chain.blobscan_url.clone());
+            let result = spawn_preflight(
+                &chain,
+                block_number,
+                prover_data.clone(),
+                [rpc.clone(), l1_rpc.clone()],
+                get_store(&chain.name),
+                blob_providers,
+            )
+            .await;
+            let error = match result {
+                Ok(input) => return Ok(input),
+                Err(e) => e,
+            };
+            if tried[0].len() >= attempts {
+                return Err(error);
+            }
+
+            // Find out which endpoints caused the failure, so they are skipped from now on.
+            let healthy = [
+                pools[0].check(&rpc).await,
+                pools[1].check(&l1_rpc).await,
+                pools[2].check(&beacon_rpc).await,
+            ];
+            warn!(
+                "Preflight of block {block_number} failed (healthy endpoints: rpc {}, l1 rpc {}, \
+                 beacon rpc {}), trying other endpoints: {error}",
+                healthy[0], healthy[1], healthy[2]
+            );
+        }
+    }
+    .await;
+    permit.record(&input);
+    input
+}
+
+/// Run the preflight of the block with the given L2 and L1 nodes on a blocking thread.
+async fn spawn_preflight(
+    chain: &ChainConfig,
+    block_number: u64,
+    prover_data: TaikoProverData,
+    [rpc, l1_rpc]: [String; 2],
+    header_store: Arc<HeaderStore>,
+    blob_providers: BlobProviders,
+) -> HostResult<GuestInput> {
+    let (network, chain_spec, strategy) =
+        (chain.network, chain.chain_spec.clone(), chain.preflight);
+    // Keep the span of the request, so the preflight logs carry its trace id.
+    let span = Span::current();
+    let input = tokio::task::spawn_blocking(move || {
+        let _span = span.enter();
         preflight(
             Some(rpc),
             block_number,
             network,
-            TaikoProverData { graffiti, prover },
+            chain_spec,
+            strategy,
+            &header_store,
+            prover_data,
             Some(l1_rpc),
-            Some(beacon_rpc),
+            blob_providers,
         )
-        .expect("Failed to fetch required data for block")
     })
-    .await
-    .map_err(|e| e.into())
+    .await??;
+    Ok(input)
 }
 
 pub struct NativeProver;
 
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct NativeParam {
+    /// Execute the block with the EVM profiler and add its report to the response.
+    #[serde(default)]
+    pub profile: bool,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct NativeResponse {
     output: GuestOutput,
+    /// The gas and the time spent per opcode, contract and precompile, when profiled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    profile: Option<EvmProfile>,
 }
 
 impl Prover for NativeProver {
     async fn run(
-        _input: GuestInput,
+        input: GuestInput,
         output: GuestOutput,
-        _request: &serde_json::Value,
+        request: &serde_json::Value,
     ) -> ProverResult<Proof> {
-        to_proof(Ok(NativeResponse { output }))
+        let param = request
+            .get("native")
+            .map(NativeParam::deserialize)
+            .transpose()
+            .map_err(|e| ProverError::GuestError(format!("Native: invalid params: {e}")))?
+            .unwrap_or_default();
+        let profile = if param.profile {
+            let _span = info_span!("evm_profile").entered();
+            let profile = profile_block::<TaikoStrategy>(&input).map_err(|e| {
+                ProverError::GuestError(format!("Native: failed to profile the block: {e}"))
+            })?;
+            Some(profile)
+        } else {
+            None
+        };
+        to_proof(Ok(NativeResponse { output, profile }))
     }
 
     fn instance_hash(_pi: ProtocolInstance) -> B256 {
@@ -140,10 +943,79 @@ impl Prover for NativeProver {
 
 #[cfg(test)]
 mod tests {
+    use alloy_primitives::Address;
+    use raiko_lib::consts::TAIKO_A7_CHAIN_SPEC;
+
+    use super::*;
+
     #[tokio::test]
     async fn test_async_block() {
         let result = async { Result::<(), &'static str>::Err("error") };
         println!("must here");
         assert!(result.await.is_err());
     }
+
+    #[tokio::test]
+    async fn test_cached_input_prover_data() {
+        let proof_request: ProofRequest = serde_json::from_value(serde_json::json!({
+            "network": "taiko_a7",
+            "l1_network": "holesky",
+            "block_number": 1,
+            "rpc": "http://localhost:8545",
+            "l1_rpc": "http://localhost:8546",
+            "beacon_rpc": "http://localhost:5052",
+            "graffiti": B256::repeat_byte(2),
+            "prover": "0x0000000000000000000000000000000000000002",
+            "proof_type": "native",
+        }))
+        .unwrap();
+        let mut cached_input = GuestInput::default();
+        cached_input.taiko.prover_data.prover = Address::repeat_byte(1);
+
+        let input = load_input(
+            &proof_request,
+            Some(cached_input),
+            &ProgressSender::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(input.taiko.prover_data.prover, proof_request.prover);
+        assert_eq!(input.taiko.prover_data.graffiti, proof_request.graffiti);
+    }
+
+    #[test]
+    fn test_replay_request() {
+        let request_opt: ProofRequestOpt = serde_json::from_value(serde_json::json!({
+            "network": "taiko_a7",
+            "block_number": 1,
+            "rpc": "http://localhost:8545",
+            "l1_rpc": "http://localhost:8546",
+            "beacon_rpc": "http://localhost:5052",
+            "graffiti": "0x0000000000000000000000000000000000000000000000000000000000000000",
+            "prover": "0x0000000000000000000000000000000000000000",
+            "proof_type": "native",
+            "cross_check": true,
+        }))
+        .unwrap();
+        let mut input = GuestInput {
+            block_number: 1000,
+            chain_spec: TAIKO_A7_CHAIN_SPEC.clone(),
+            ..Default::default()
+        };
+        // The block is the block of the input and never cross checked.
+        let proof_request = replay_request(request_opt.clone(), &input).unwrap();
+        assert_eq!(proof_request.block_number, 1000);
+        assert!(!proof_request.cross_check);
+
+        input.chain_spec.chain_id += 1;
+        let problem = replay_request(request_opt.clone(), &input)
+            .unwrap_err()
+            .problem();
+        assert_eq!(problem.code, ErrorCode::InvalidRequest);
+
+        // Any other difference to the spec of the chain is refused too
+        input.chain_spec = TAIKO_A7_CHAIN_SPEC.clone();
+        input.chain_spec.anchor_gas_limit += 1;
+        assert!(replay_request(request_opt, &input).is_err());
+    }
 }
@@ -0,0 +1,253 @@
+//! Fault injection for resilience testing.
+//!
+//! With the `faults` feature, faults can be injected into a running host with
+//! `PUT /admin/faults`, to see the retries, the input cache and the checkpoints recover from
+//! them before relying on it in production: the responses of the JSON-RPC batches of the
+//! preflight are dropped, the cached inputs are corrupted on disk before they are read, and
+//! the prover is killed once its execution reaches a given progress. Without the feature, the
+//! hooks do nothing and the faults can't be set.
+
+#[cfg(feature = "faults")]
+use std::{
+    fs::OpenOptions,
+    io::{Seek, SeekFrom, Write},
+    sync::{Arc, RwLock},
+};
+use std::{future::Future, path::Path};
+
+#[cfg(feature = "faults")]
+use lazy_static::lazy_static;
+use raiko_client::ErrorCode;
+#[cfg(not(feature = "faults"))]
+use raiko_client::Problem;
+use raiko_lib::prover::ProgressReporter;
+#[cfg(feature = "faults")]
+use raiko_lib::prover::{ProverError, ProverProgress};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "faults")]
+use tokio::sync::Notify;
+#[cfg(feature = "faults")]
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::error::{HostError, HostResult};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+/// The faults injected into the host.
+pub struct FaultConfig {
+    /// The share of the JSON-RPC batches of the preflight whose response is dropped, between 0
+    /// and 1.
+    pub drop_rpc_rate: f64,
+    /// The share of the cached inputs that are corrupted before they are read, between 0 and 1.
+    pub corrupt_input_rate: f64,
+    /// Kill the prover once it reports this percentage of its execution. Only provers that
+    /// report their execution progress are killed.
+    pub kill_prover_at: Option<u8>,
+}
+
+impl FaultConfig {
+    fn validate(&self) -> HostResult<()> {
+        for (field, rate) in [
+            ("drop_rpc_rate", self.drop_rpc_rate),
+            ("corrupt_input_rate", self.corrupt_input_rate),
+        ] {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(HostError::invalid(
+                    ErrorCode::InvalidRequest,
+                    field,
+                    "The rate must be between 0 and 1",
+                ));
+            }
+        }
+        if self.kill_prover_at.is_some_and(|percent| percent > 100) {
+            return Err(HostError::invalid(
+                ErrorCode::InvalidRequest,
+                "kill_prover_at",
+                "The progress must be a percentage",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "faults")]
+lazy_static! {
+    static ref CONFIG: RwLock<FaultConfig> = RwLock::new(FaultConfig::default());
+}
+
+#[cfg(feature = "faults")]
+fn config() -> FaultConfig {
+    CONFIG.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[cfg(feature = "faults")]
+fn injected(rate: f64) -> bool {
+    rate > 0.0 && rand::random::<f64>() < rate
+}
+
+/// Get the faults injected into the host.
+#[cfg(feature = "faults")]
+pub fn get() -> HostResult<FaultConfig> {
+    Ok(config())
+}
+
+/// Set the faults injected into the host, replacing the faults set before.
+#[cfg(feature = "faults")]
+pub fn set(faults: FaultConfig) -> HostResult<FaultConfig> {
+    faults.validate()?;
+    if faults != FaultConfig::default() {
+        warn!("Injecting faults: {faults:?}");
+    }
+    *CONFIG.write().unwrap_or_else(|e| e.into_inner()) = faults.clone();
+    Ok(faults)
+}
+
+/// Fail with a dropped response of the JSON-RPC batch of the given method, when injected.
+#[cfg(feature = "faults")]
+pub fn drop_rpc_response(method: &str) -> anyhow::Result<()> {
+    if injected(config().drop_rpc_rate) {
+        warn!("Fault injection: dropping the response of the {method} batch");
+        anyhow::bail!("Fault injection: the response of the {method} batch was dropped");
+    }
+    Ok(())
+}
+
+/// Overwrite the middle of the cached input at the given path, when injected.
+#[cfg(feature = "faults")]
+pub fn corrupt_input(path: &Path) {
+    if !injected(config().corrupt_input_rate) {
+        return;
+    }
+    warn!("Fault injection: corrupting the cached input {path:?}");
+    let result = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .and_then(|mut file| {
+            let len = file.metadata()?.len();
+            file.seek(SeekFrom::Start(len / 2))?;
+            file.write_all(&rand::random::<[u8; 32]>())
+        });
+    if let Err(e) = result {
+        warn!("Could not corrupt the cached input {path:?}: {e}");
+    }
+}
+
+/// Run the prover with the reporter, and kill it once it reports the execution progress the
+/// prover is killed at, when injected. Dropping the prover kills its subprocesses.
+#[cfg(feature = "faults")]
+pub async fn kill_prover<T, Fut>(
+    reporter: ProgressReporter,
+    prover: impl FnOnce(ProgressReporter) -> Fut,
+) -> HostResult<T>
+where
+    Fut: Future<Output = HostResult<T>>,
+{
+    let Some(kill_at) = config().kill_prover_at else {
+        return prover(reporter).await;
+    };
+    let kill = Arc::new(Notify::new());
+    let reporter = {
+        let kill = kill.clone();
+        let cancellation = reporter.cancellation();
+        ProgressReporter::new(move |progress| {
+            if matches!(progress, ProverProgress::Execution(percent) if percent >= kill_at) {
+                kill.notify_one();
+            }
+            reporter.report(progress);
+        })
+        .with_cancellation(cancellation)
+    };
+    tokio::select! {
+        result = prover(reporter) => result,
+        _ = kill.notified() => {
+            warn!("Fault injection: killing the prover at {kill_at}% of its execution");
+            Err(HostError::GuestError(ProverError::GuestError(format!(
+                "Fault injection: the prover was killed at {kill_at}% of its execution"
+            ))))
+        }
+    }
+}
+
+#[cfg(not(feature = "faults"))]
+fn not_supported() -> HostError {
+    HostError::Validation(Problem::new(
+        ErrorCode::FeatureNotSupported,
+        "The host is built without the faults feature",
+    ))
+}
+
+#[cfg(not(feature = "faults"))]
+pub fn get() -> HostResult<FaultConfig> {
+    Err(not_supported())
+}
+
+#[cfg(not(feature = "faults"))]
+pub fn set(faults: FaultConfig) -> HostResult<FaultConfig> {
+    faults.validate()?;
+    Err(not_supported())
+}
+
+#[cfg(not(feature = "faults"))]
+pub fn drop_rpc_response(_method: &str) -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(feature = "faults"))]
+pub fn corrupt_input(_path: &Path) {}
+
+#[cfg(not(feature = "faults"))]
+pub async fn kill_prover<T, Fut>(
+    reporter: ProgressReporter,
+    prover: impl FnOnce(ProgressReporter) -> Fut,
+) -> HostResult<T>
+where
+    Fut: Future<Output = HostResult<T>>,
+{
+    prover(reporter).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(FaultConfig::default().validate().is_ok());
+        let faults: FaultConfig =
+            serde_json::from_value(serde_json::json!({"drop_rpc_rate": 0.5, "kill_prover_at": 50}))
+                .unwrap();
+        assert!(faults.validate().is_ok());
+        for faults in [
+            serde_json::json!({"drop_rpc_rate": 1.5}),
+            serde_json::json!({"corrupt_input_rate": -0.1}),
+            serde_json::json!({"kill_prover_at": 101}),
+        ] {
+            let faults: FaultConfig = serde_json::from_value(faults).unwrap();
+            assert!(faults.validate().is_err());
+        }
+    }
+
+    #[cfg(feature = "faults")]
+    #[tokio::test]
+    async fn test_kill_prover() {
+        set(FaultConfig {
+            kill_prover_at: Some(50),
+            ..Default::default()
+        })
+        .unwrap();
+        let result = kill_prover(ProgressReporter::default(), |reporter| async move {
+            reporter.report(ProverProgress::Execution(60));
+            std::future::pending::<()>().await;
+            Ok(())
+        })
+        .await;
+        assert!(matches!(result, Err(HostError::GuestError(_))));
+        set(FaultConfig::default()).unwrap();
+        assert!(
+            kill_prover(ProgressReporter::default(), |_| async { Ok(()) })
+                .await
+                .is_ok()
+        );
+    }
+}
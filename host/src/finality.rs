@@ -0,0 +1,231 @@
+//! Only proving the blocks proposed in final L1 blocks.
+//!
+//! A block proposed in an L1 block that is reorged out is proposed again or not at all, and
+//! its proof is wasted, see [crate::reorgs]. With `finality` in the config file, e.g.
+//! `{"confirmations": 12, "action": "wait"}`, a block is only proven once the L1 block it was
+//! proposed in, the block after the anchor block of its input, is `confirmations` blocks deep,
+//! or finalized when `confirmations` is unset. The blocks proposed in L1 blocks that are not
+//! final yet are:
+//!
+//! - `wait`: kept waiting until they are, checking L1 every `poll_interval` seconds, for at
+//!   most `max_wait` seconds when set. A job waits after its preflight, without its preflight
+//!   permit, and before it waits for a prover slot. The block is checked once, when its input
+//!   is prepared.
+//! - `reject`: failed with `block_not_finalized`, to be requested again later.
+//!
+//! Both are counted in the `unfinalized_blocks` metric.
+
+use std::{
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use alloy_provider::{Provider, ProviderBuilder, ReqwestProvider, RootProvider};
+use alloy_rpc_types::BlockNumberOrTag;
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use raiko_client::ErrorCode;
+use raiko_lib::input::GuestInput;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::{
+    chain_registry::get_chain,
+    error::{HostError, HostResult},
+    fixtures,
+    metrics::inc_unfinalized_blocks,
+    request::ProofRequest,
+};
+
+fn default_poll_interval() -> u64 {
+    12
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// What is done with the requests of blocks proposed in L1 blocks that are not final yet.
+pub enum FinalityAction {
+    /// Wait until the L1 block is final.
+    #[default]
+    Wait,
+    /// Fail the request with `block_not_finalized`.
+    Reject,
+}
+
+impl std::fmt::Display for FinalityAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FinalityAction::Wait => "wait",
+            FinalityAction::Reject => "reject",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// When the L1 block a block was proposed in is final enough to prove the block.
+pub struct FinalityConfig {
+    /// The blocks on top of the L1 block, unset to wait for the L1 block to be finalized.
+    #[serde(default)]
+    pub confirmations: Option<u64>,
+    #[serde(default)]
+    pub action: FinalityAction,
+    /// Seconds between the checks of L1 while waiting.
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+    /// Seconds to wait at most before failing the request like `reject`.
+    #[serde(default)]
+    pub max_wait: Option<u64>,
+}
+
+lazy_static! {
+    static ref CONFIG: RwLock<Option<FinalityConfig>> = RwLock::new(None);
+}
+
+/// Set when the blocks are final enough to prove, `None` proves all blocks right away.
+pub fn configure(config: Option<FinalityConfig>) {
+    *CONFIG.write().unwrap() = config;
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Decision {
+    Prove,
+    Wait,
+    Reject(String),
+}
+
+impl FinalityConfig {
+    /// What to do with a block proposed in the L1 block, by the last final L1 block and the
+    /// time waited for it so far.
+    fn decide(&self, l1_block: u64, final_block: u64, waited: Duration) -> Decision {
+        if l1_block <= final_block {
+            return Decision::Prove;
+        }
+        let finality = match self.confirmations {
+            Some(confirmations) => format!("{confirmations} blocks deep"),
+            None => "finalized".to_string(),
+        };
+        let timed_out = self
+            .max_wait
+            .is_some_and(|max_wait| waited >= Duration::from_secs(max_wait));
+        match self.action {
+            FinalityAction::Wait if !timed_out => Decision::Wait,
+            FinalityAction::Wait => Decision::Reject(format!(
+                "L1 block {l1_block} is still not {finality} after {}s, the last final block is \
+                 {final_block}",
+                waited.as_secs()
+            )),
+            FinalityAction::Reject => Decision::Reject(format!(
+                "L1 block {l1_block} is not {finality} yet, the last final block is {final_block}"
+            )),
+        }
+    }
+}
+
+/// Check the block of the request was proposed in a final L1 block before it is proven,
+/// waiting for it or failing with `block_not_finalized` depending on the config.
+pub async fn check(proof_request: &ProofRequest, input: &GuestInput) -> HostResult<()> {
+    let Some(config) = CONFIG.read().unwrap().clone() else {
+        return Ok(());
+    };
+    if !input.network.is_taiko() {
+        return Ok(());
+    }
+    // Only the responses of the nodes to the preflight are in the fixtures.
+    let chain = get_chain(&proof_request.network)?;
+    if fixtures::fixture_path(&chain.name, input.block_number).is_some() {
+        return Ok(());
+    }
+
+    // The block is proposed in the L1 block after the anchor block of its input.
+    let l1_block = input.taiko.l1_header.number + 1;
+    let provider = ProviderBuilder::new().provider(RootProvider::new_http(
+        reqwest::Url::parse(&proof_request.l1_rpc).map_err(|e| anyhow!(e))?,
+    ));
+    let started = Instant::now();
+    let mut counted = false;
+    loop {
+        let final_block = final_block(&provider, config.confirmations).await?;
+        match config.decide(l1_block, final_block, started.elapsed()) {
+            Decision::Prove => {
+                debug!(
+                    "Block {} was proposed in L1 block {l1_block}, which is final",
+                    input.block_number
+                );
+                return Ok(());
+            }
+            Decision::Wait => {
+                if !counted {
+                    inc_unfinalized_blocks(&proof_request.network, &config.action.to_string());
+                    counted = true;
+                }
+                info!(
+                    "Waiting for L1 block {l1_block} to be final to prove block {}, the last \
+                     final block is {final_block}",
+                    input.block_number
+                );
+                tokio::time::sleep(Duration::from_secs(config.poll_interval.max(1))).await;
+            }
+            Decision::Reject(detail) => {
+                if !counted {
+                    inc_unfinalized_blocks(&proof_request.network, &config.action.to_string());
+                }
+                return Err(HostError::invalid(
+                    ErrorCode::BlockNotFinalized,
+                    "block_number",
+                    detail,
+                ));
+            }
+        }
+    }
+}
+
+/// The last final L1 block, `confirmations` below the head or the finalized block.
+async fn final_block(provider: &ReqwestProvider, confirmations: Option<u64>) -> Result<u64> {
+    match confirmations {
+        Some(confirmations) => Ok(provider
+            .get_block_number()
+            .await?
+            .saturating_sub(confirmations)),
+        None => {
+            let number = provider
+                .get_block_by_number(BlockNumberOrTag::Finalized, false)
+                .await?
+                .and_then(|block| block.header.number)
+                .ok_or_else(|| anyhow!("No finalized L1 block"))?;
+            Ok(number.try_into()?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decide() {
+        let config: FinalityConfig = serde_json::from_str(r#"{"confirmations": 12}"#).unwrap();
+        assert_eq!(config.action, FinalityAction::Wait);
+        assert_eq!(config.poll_interval, 12);
+        assert_eq!(config.decide(100, 100, Duration::ZERO), Decision::Prove);
+        assert_eq!(config.decide(101, 100, Duration::ZERO), Decision::Wait);
+        assert_eq!(
+            config.decide(101, 100, Duration::from_secs(3600)),
+            Decision::Wait
+        );
+
+        let config = FinalityConfig {
+            max_wait: Some(600),
+            ..config
+        };
+        let Decision::Reject(detail) = config.decide(101, 100, Duration::from_secs(600)) else {
+            panic!("waited longer than max_wait");
+        };
+        assert!(detail.contains("still not 12 blocks deep"), "{detail}");
+
+        let config: FinalityConfig = serde_json::from_str(r#"{"action": "reject"}"#).unwrap();
+        let Decision::Reject(detail) = config.decide(101, 100, Duration::ZERO) else {
+            panic!("not finalized");
+        };
+        assert!(detail.contains("not finalized yet"), "{detail}");
+    }
+}
@@ -0,0 +1,428 @@
+//! Fixtures of the responses of the nodes to the preflight of a block.
+//!
+//! In record mode the preflight talks to the nodes through a local proxy for each of the L2,
+//! L1 and beacon nodes, which keeps their responses and writes them to a fixture file of the
+//! block once the preflight is done. In playback mode the proxies serve the responses of the
+//! fixture instead, so the preflight of the block runs offline, e.g. in integration tests or
+//! for local development without an archive node. The JSON-RPC responses are keyed by the
+//! method and the params of the request, the beacon responses by the path of the request.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    net::{Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+};
+
+use anyhow::{anyhow, Context};
+use axum::{
+    extract::State,
+    http::{StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use clap::ValueEnum;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{net::TcpListener, task::JoinHandle};
+use tracing::{info, warn};
+
+use crate::error::HostResult;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+/// What the preflight does with the fixtures of the blocks.
+pub enum FixtureMode {
+    /// Fetch from the nodes and write the responses to the fixture of the block.
+    Record,
+    /// Serve the responses of the fixture of the block instead of the nodes.
+    Playback,
+}
+
+/// The nodes the preflight talks to, in the order of the endpoints of a [FixtureSession].
+const ENDPOINTS: [&str; 3] = ["rpc", "l1_rpc", "beacon_rpc"];
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+/// The responses of the nodes to the preflight of a block.
+pub struct Fixture {
+    /// The responses by endpoint, e.g. `rpc`, and request.
+    pub responses: BTreeMap<String, BTreeMap<String, Value>>,
+}
+
+impl Fixture {
+    /// Read a fixture from a file.
+    pub fn load(path: &Path) -> HostResult<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Could not open the fixture {}", path.display()))?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    /// Write the fixture to a file.
+    pub fn save(&self, path: &Path) -> HostResult<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+struct FixtureConfig {
+    mode: FixtureMode,
+    dir: PathBuf,
+}
+
+lazy_static! {
+    static ref CONFIG: RwLock<Option<FixtureConfig>> = RwLock::new(None);
+}
+
+/// Record or play back the fixtures of the blocks in the given directory, or talk to the nodes
+/// without a mode.
+pub fn configure(mode: Option<FixtureMode>, dir: PathBuf) {
+    *CONFIG.write().unwrap() = mode.map(|mode| FixtureConfig { mode, dir });
+}
+
+/// The path of the fixture of a block, if fixtures are recorded or played back.
+pub fn fixture_path(network: &str, block_number: u64) -> Option<(FixtureMode, PathBuf)> {
+    let config = CONFIG.read().unwrap().clone()?;
+    let path = config.dir.join(format!("{network}-{block_number}.json"));
+    Some((config.mode, path))
+}
+
+/// Whether the preflight is served from the fixtures, so the nodes may not be reachable.
+pub fn is_playback() -> bool {
+    CONFIG
+        .read()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|config| config.mode == FixtureMode::Playback)
+}
+
+/// The proxies of the nodes the preflight of a block talks to while recording or playing back
+/// its fixture. The proxies stop when the session is dropped.
+pub struct FixtureSession {
+    mode: FixtureMode,
+    path: PathBuf,
+    fixture: Arc<Mutex<Fixture>>,
+    urls: [String; 3],
+    servers: Vec<JoinHandle<()>>,
+}
+
+struct Proxy {
+    endpoint: &'static str,
+    upstream: String,
+    mode: FixtureMode,
+    fixture: Arc<Mutex<Fixture>>,
+    client: reqwest::Client,
+}
+
+impl FixtureSession {
+    /// Start the proxies of the L2, L1 and beacon nodes at the given URLs. The fixture has to
+    /// exist for playback.
+    pub async fn start(
+        mode: FixtureMode,
+        path: PathBuf,
+        upstreams: [String; 3],
+    ) -> HostResult<Self> {
+        let fixture = match mode {
+            FixtureMode::Record => Fixture::default(),
+            FixtureMode::Playback => Fixture::load(&path)?,
+        };
+        info!("{mode:?} of the fixture {}", path.display());
+        let fixture = Arc::new(Mutex::new(fixture));
+        let mut urls: [String; 3] = Default::default();
+        let mut servers = Vec::new();
+        for (i, upstream) in upstreams.into_iter().enumerate() {
+            let proxy = Arc::new(Proxy {
+                endpoint: ENDPOINTS[i],
+                upstream: upstream.trim_end_matches('/').to_string(),
+                mode,
+                fixture: fixture.clone(),
+                client: reqwest::Client::new(),
+            });
+            let router = Router::new()
+                .route("/", post(rpc_handler))
+                .fallback(beacon_handler)
+                .with_state(proxy);
+            let listener = TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).await?;
+            urls[i] = format!("http://{}", listener.local_addr()?);
+            servers.push(tokio::spawn(async move {
+                if let Err(e) = axum::serve(listener, router).await {
+                    warn!("The fixture proxy stopped: {e}");
+                }
+            }));
+        }
+        Ok(Self {
+            mode,
+            path,
+            fixture,
+            urls,
+            servers,
+        })
+    }
+
+    /// The URLs of the proxies of the L2, L1 and beacon nodes.
+    pub fn urls(&self) -> &[String; 3] {
+        &self.urls
+    }
+
+    /// Stop the proxies, writing the fixture when recording.
+    pub fn finish(self) -> HostResult<()> {
+        if self.mode == FixtureMode::Record {
+            let fixture = self.fixture.lock().unwrap().clone();
+            fixture.save(&self.path)?;
+            info!("Recorded the fixture {}", self.path.display());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FixtureSession {
+    fn drop(&mut self) {
+        for server in &self.servers {
+            server.abort();
+        }
+    }
+}
+
+/// The key of a JSON-RPC request in the fixture.
+fn request_key(request: &Value) -> String {
+    format!("{}:{}", request["method"], request["params"])
+}
+
+impl Proxy {
+    fn get(&self, key: &str) -> Option<Value> {
+        let fixture = self.fixture.lock().unwrap();
+        fixture.responses.get(self.endpoint)?.get(key).cloned()
+    }
+
+    fn insert(&self, key: String, response: Value) {
+        let mut fixture = self.fixture.lock().unwrap();
+        fixture
+            .responses
+            .entry(self.endpoint.to_string())
+            .or_default()
+            .insert(key, response);
+    }
+
+    /// Forward the JSON-RPC request or batch to the node and keep the responses by request.
+    async fn record_rpc(&self, body: &Value) -> anyhow::Result<Value> {
+        let response: Value = self
+            .client
+            .post(&self.upstream)
+            .json(body)
+            .send()
+            .await?
+            .json()
+            .await?;
+        let requests = body
+            .as_array()
+            .map_or(std::slice::from_ref(body), Vec::as_slice);
+        let responses = response
+            .as_array()
+            .map_or(std::slice::from_ref(&response), Vec::as_slice);
+        for request in requests {
+            if let Some(response) = responses.iter().find(|r| r["id"] == request["id"]) {
+                let mut response = response.clone();
+                if let Some(response) = response.as_object_mut() {
+                    response.remove("id");
+                }
+                self.insert(request_key(request), response);
+            }
+        }
+        Ok(response)
+    }
+
+    /// Answer the JSON-RPC request with the response of the fixture, under the id of the
+    /// request.
+    fn play_rpc(&self, request: &Value) -> Value {
+        let mut response = self.get(&request_key(request)).unwrap_or_else(|| {
+            json!({
+                "jsonrpc": "2.0",
+                "error": {
+                    "code": -32000,
+                    "message": format!("No response to {} in the fixture", request["method"]),
+                },
+            })
+        });
+        response["id"] = request["id"].clone();
+        response
+    }
+}
+
+async fn rpc_handler(State(proxy): State<Arc<Proxy>>, Json(body): Json<Value>) -> Response {
+    match proxy.mode {
+        FixtureMode::Record => match proxy.record_rpc(&body).await {
+            Ok(response) => Json(response).into_response(),
+            Err(e) => (StatusCode::BAD_GATEWAY, e.to_string()).into_response(),
+        },
+        FixtureMode::Playback => match &body {
+            Value::Array(requests) => Json(Value::Array(
+                requests
+                    .iter()
+                    .map(|request| proxy.play_rpc(request))
+                    .collect(),
+            ))
+            .into_response(),
+            request => Json(proxy.play_rpc(request)).into_response(),
+        },
+    }
+}
+
+async fn beacon_handler(State(proxy): State<Arc<Proxy>>, uri: Uri) -> Response {
+    let key = uri
+        .path_and_query()
+        .map_or(uri.path(), |path| path.as_str())
+        .to_string();
+    match proxy.mode {
+        FixtureMode::Record => {
+            let response = async {
+                let response = proxy
+                    .client
+                    .get(format!("{}{key}", proxy.upstream))
+                    .send()
+                    .await?;
+                let status = response.status();
+                if !status.is_success() {
+                    return Err(anyhow!("Request failed with status code: {status}"));
+                }
+                Ok::<Value, anyhow::Error>(response.json().await?)
+            }
+            .await;
+            match response {
+                Ok(response) => {
+                    proxy.insert(key, response.clone());
+                    Json(response).into_response()
+                }
+                Err(e) => (StatusCode::BAD_GATEWAY, e.to_string()).into_response(),
+            }
+        }
+        FixtureMode::Playback => match proxy.get(&key) {
+            Some(response) => Json(response).into_response(),
+            None => (
+                StatusCode::NOT_FOUND,
+                format!("No response to {key} in the fixture"),
+            )
+                .into_response(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A node answering every JSON-RPC request with its method and every GET with its path.
+    async fn spawn_node() -> String {
+        let router = Router::new()
+            .route(
+                "/",
+                post(|Json(request): Json<Value>| async move {
+                    Json(
+                        json!({"jsonrpc": "2.0", "id": request["id"], "result": request["method"]}),
+                    )
+                }),
+            )
+            .fallback(|uri: Uri| async move { Json(json!({"data": uri.path()})) });
+        let listener = TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_record_playback() {
+        let node = spawn_node().await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("taiko_a7-10.json");
+        let client = reqwest::Client::new();
+        let request = json!({"jsonrpc": "2.0", "id": 1, "method": "eth_chainId", "params": []});
+
+        let session = FixtureSession::start(
+            FixtureMode::Record,
+            path.clone(),
+            [node.clone(), node.clone(), node],
+        )
+        .await
+        .unwrap();
+        let [rpc, _, beacon] = session.urls().clone();
+        let response: Value = client
+            .post(&rpc)
+            .json(&request)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response["result"], "eth_chainId");
+        let blobs = "/eth/v1/beacon/blob_sidecars/5";
+        let response: Value = client
+            .get(format!("{beacon}{blobs}"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response["data"], blobs);
+        session.finish().unwrap();
+
+        // The nodes are not needed for playback.
+        let unreachable = "http://127.0.0.1:1".to_string();
+        let session = FixtureSession::start(
+            FixtureMode::Playback,
+            path,
+            [unreachable.clone(), unreachable.clone(), unreachable],
+        )
+        .await
+        .unwrap();
+        let [rpc, l1_rpc, beacon] = session.urls().clone();
+        let batch = json!([
+            {"jsonrpc": "2.0", "id": 7, "method": "eth_chainId", "params": []},
+            {"jsonrpc": "2.0", "id": 8, "method": "eth_blockNumber", "params": []},
+        ]);
+        let response: Value = client
+            .post(&rpc)
+            .json(&batch)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response[0]["id"], 7);
+        assert_eq!(response[0]["result"], "eth_chainId");
+        assert_eq!(response[1]["id"], 8);
+        assert!(response[1]["error"].is_object());
+        // The responses are kept per node.
+        let response: Value = client
+            .post(&l1_rpc)
+            .json(&request)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(response["error"].is_object());
+        let response: Value = client
+            .get(format!("{beacon}{blobs}"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response["data"], blobs);
+    }
+}
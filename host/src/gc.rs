@@ -0,0 +1,272 @@
+//! Garbage collection of the files of the host.
+//!
+//! Long running hosts fill their disks with the artifacts of the jobs, the cached inputs, the
+//! workspaces of abandoned proofs and the files of the prover child processes. With `gc` in the
+//! config file, e.g. `{"interval": 600, "min_free_space": 20480, "max_workspace_age": 48}`, the
+//! garbage collection runs every `interval` seconds, and on `POST /admin/gc`. It
+//!
+//! - prunes the [artifacts](crate::artifacts) and the [input cache](crate::input_cache) by
+//!   their max size and age,
+//! - removes the [workspaces](crate::workspace) not changed for `max_workspace_age` hours, left
+//!   by proofs that were never retried,
+//! - removes the files of the jobs of hosts that are gone, see [crate::isolation],
+//! - and when the disk of the artifacts or of the input cache has less than `min_free_space` MB
+//!   free, removes their oldest entries until it has enough, the artifacts first.
+//!
+//! The free space is read with `df`, without it the floor isn't kept.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    artifacts,
+    error::{HostError, HostResult},
+    input_cache::InputCache,
+    isolation,
+    metrics::{inc_gc_reclaimed_bytes, set_disk_free_bytes},
+    workspace,
+};
+
+fn default_interval() -> u64 {
+    600
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// The thresholds of the garbage collection.
+pub struct GcConfig {
+    /// Seconds between the garbage collections, 0 only collects on `POST /admin/gc`.
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    /// The free space in MB kept on the disks of the artifacts and of the input cache.
+    #[serde(default)]
+    pub min_free_space: Option<u64>,
+    /// Hours after the last change of a workspace it is removed.
+    #[serde(default)]
+    pub max_workspace_age: Option<u64>,
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        Self {
+            interval: default_interval(),
+            min_free_space: None,
+            max_workspace_age: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+/// The result of a garbage collection.
+pub struct GcReport {
+    /// The bytes removed by what they were: `artifacts`, `inputs`, `workspaces` and `jobs`.
+    pub reclaimed: BTreeMap<String, u64>,
+    /// The free bytes of the disk with the least free space of the directories of the host,
+    /// after the collection. Unset when it can't be read.
+    pub free_space: Option<u64>,
+}
+
+impl GcReport {
+    fn add(&mut self, target: &str, bytes: u64) {
+        *self.reclaimed.entry(target.to_string()).or_default() += bytes;
+    }
+}
+
+lazy_static! {
+    static ref CONFIG: RwLock<GcConfig> = RwLock::new(GcConfig::default());
+    /// Held by the running collection, so collections don't run concurrently.
+    static ref RUNNING: Mutex<()> = Mutex::new(());
+}
+
+/// Set the thresholds of the garbage collection.
+pub fn configure(config: GcConfig) {
+    *CONFIG.write().unwrap() = config;
+}
+
+/// Collect the garbage every `interval` seconds, in the background.
+pub fn spawn(input_cache: InputCache) {
+    let interval = CONFIG.read().unwrap().interval;
+    if interval == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            if let Err(e) = collect(&input_cache).await {
+                warn!("The garbage collection failed: {e}");
+            }
+        }
+    });
+}
+
+/// Collect the garbage now.
+pub async fn collect(input_cache: &InputCache) -> HostResult<GcReport> {
+    let input_cache = input_cache.clone();
+    tokio::task::spawn_blocking(move || run(&input_cache))
+        .await
+        .map_err(|e| HostError::Anyhow(e.into()))?
+}
+
+fn run(input_cache: &InputCache) -> HostResult<GcReport> {
+    let _running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+    let config = CONFIG.read().unwrap().clone();
+    let mut report = GcReport::default();
+
+    report.add("artifacts", artifacts::prune()?);
+    report.add("inputs", input_cache.prune()?);
+    if let (Some(dir), Some(hours)) = (workspace::dir(), config.max_workspace_age) {
+        report.add(
+            "workspaces",
+            remove_stale_workspaces(&dir, Duration::from_secs(hours * 60 * 60))?,
+        );
+    }
+    report.add("jobs", isolation::remove_stale_jobs());
+
+    if let Some(min_free_space) = config.min_free_space.map(|size| size * 1024 * 1024) {
+        if let Some(free) = artifacts::dir().and_then(|dir| free_space(&dir)) {
+            if free < min_free_space {
+                warn!("Only {free} bytes free on the disk of the artifacts, removing the oldest");
+                report.add("artifacts", artifacts::evict(min_free_space - free)?);
+            }
+        }
+        if let Some(free) = input_cache.dir().and_then(free_space) {
+            if free < min_free_space {
+                warn!("Only {free} bytes free on the disk of the input cache, removing the oldest");
+                report.add("inputs", input_cache.evict(min_free_space - free)?);
+            }
+        }
+    }
+
+    let dirs: Vec<PathBuf> = [
+        artifacts::dir(),
+        input_cache.dir().map(Path::to_path_buf),
+        workspace::dir(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    report.free_space = dirs.iter().filter_map(|dir| free_space(dir)).min();
+    if let Some(free) = report.free_space {
+        set_disk_free_bytes(free);
+    }
+    for (target, bytes) in &report.reclaimed {
+        inc_gc_reclaimed_bytes(target, *bytes);
+    }
+    let total: u64 = report.reclaimed.values().sum();
+    if total > 0 {
+        info!("The garbage collection removed {total} bytes");
+    }
+    Ok(report)
+}
+
+/// Remove the workspaces in the directory not changed for the max age. Returns the number of
+/// bytes removed.
+fn remove_stale_workspaces(dir: &Path, max_age: Duration) -> HostResult<u64> {
+    let now = SystemTime::now();
+    let mut removed = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let (size, modified) = usage(&path);
+        if now.duration_since(modified).unwrap_or_default() <= max_age {
+            continue;
+        }
+        debug!("Removing the stale workspace {path:?}");
+        match std::fs::remove_dir_all(&path) {
+            Ok(()) => removed += size,
+            Err(e) => warn!("Could not remove the workspace {path:?}: {e}"),
+        }
+    }
+    Ok(removed)
+}
+
+/// The size in bytes of the files in the directory and its subdirectories, and the last time
+/// one of them changed, or the directory itself when it has no files.
+pub(crate) fn usage(path: &Path) -> (u64, SystemTime) {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return (0, SystemTime::UNIX_EPOCH);
+    };
+    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    if !metadata.is_dir() {
+        return (metadata.len(), modified);
+    }
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return (0, modified);
+    };
+    entries
+        .flatten()
+        .map(|entry| usage(&entry.path()))
+        .reduce(|(size, modified), (entry_size, entry_modified)| {
+            (size + entry_size, modified.max(entry_modified))
+        })
+        .unwrap_or((0, modified))
+}
+
+/// The free bytes of the disk of the directory, read with `df`.
+fn free_space(dir: &Path) -> Option<u64> {
+    let output = std::process::Command::new("df")
+        .arg("-Pk")
+        .arg(dir)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    parse_df(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The available bytes in the POSIX output of `df -Pk`.
+fn parse_df(output: &str) -> Option<u64> {
+    let available: u64 = output
+        .lines()
+        .nth(1)?
+        .split_whitespace()
+        .nth(3)?
+        .parse()
+        .ok()?;
+    Some(available * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_df() {
+        let output = "Filesystem     1024-blocks      Used Available Capacity Mounted on\n\
+                      /dev/nvme0n1p1   102400000  81920000  20480000      80% /\n";
+        assert_eq!(parse_df(output), Some(20480000 * 1024));
+        assert_eq!(parse_df("df: /missing: No such file or directory\n"), None);
+    }
+
+    #[test]
+    fn test_remove_stale_workspaces() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, age) in [("taiko_a7-1-risc0", 7200), ("taiko_a7-2-risc0", 0)] {
+            let workspace = dir.path().join(name);
+            std::fs::create_dir_all(&workspace).unwrap();
+            let segment = workspace.join("segment-0");
+            std::fs::write(&segment, b"receipt").unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&segment)
+                .unwrap()
+                .set_modified(SystemTime::now() - Duration::from_secs(age))
+                .unwrap();
+        }
+        assert_eq!(usage(&dir.path().join("taiko_a7-1-risc0")).0, 7);
+
+        let removed = remove_stale_workspaces(dir.path(), Duration::from_secs(3600)).unwrap();
+        assert_eq!(removed, 7);
+        assert!(!dir.path().join("taiko_a7-1-risc0").exists());
+        assert!(dir.path().join("taiko_a7-2-risc0").exists());
+    }
+}
@@ -0,0 +1,146 @@
+//! The CUDA devices of the GPU backed provers.
+//!
+//! `cuda_devices` of the config file lists the devices each prover proves on, e.g.
+//! `{"risc0": [0, 1], "sp1": [2]}`. The scheduler of the jobs gives every proof a free device of
+//! its prover, which the prover is passed as `cuda_device` in its config. The provers of
+//! `isolate_provers` and `prover_daemons` prove in child processes which only see their device
+//! through `CUDA_VISIBLE_DEVICES`. A prover proving in the host process can't pick a device, so
+//! it can only have one, which the host has to see as its first device. The utilization and
+//! the memory of the devices are exported as metrics, read with `nvidia-smi`.
+
+use std::{collections::BTreeMap, future::Future, process::Command, time::Duration};
+
+use tracing::warn;
+
+use crate::{
+    error::{HostError, HostResult},
+    metrics::set_gpu_stats,
+};
+
+/// How often the utilization and the memory of the devices are read.
+const METRICS_INTERVAL: Duration = Duration::from_secs(15);
+
+tokio::task_local! {
+    static CUDA_DEVICE: Option<u32>;
+}
+
+/// Run the future, generating a proof, on the CUDA device of its prover slot.
+pub async fn on_device<F: Future>(device: Option<u32>, future: F) -> F::Output {
+    CUDA_DEVICE.scope(device, future).await
+}
+
+/// The CUDA device of the proof generated by the current task, see [on_device].
+pub fn cuda_device() -> Option<u32> {
+    CUDA_DEVICE.try_with(|device| *device).ok().flatten()
+}
+
+/// Check that only the provers proving in child processes have several devices.
+pub fn check_devices(
+    cuda_devices: &BTreeMap<String, Vec<u32>>,
+    isolate_provers: &[String],
+    prover_daemons: &[String],
+) -> HostResult<()> {
+    for (prover, devices) in cuda_devices {
+        if devices.len() > 1
+            && !isolate_provers.contains(prover)
+            && !prover_daemons.contains(prover)
+        {
+            return Err(HostError::InvalidRequestConfig(format!(
+                "Several CUDA devices of {prover} need it in --isolate-provers or --prover-daemons"
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// The utilization and the memory of a device.
+pub struct GpuStats {
+    pub device: u32,
+    /// The percentage of time a kernel ran over the last sample period.
+    pub utilization: f64,
+    pub memory_used: u64,
+    pub memory_total: u64,
+}
+
+/// Parse the output of `nvidia-smi --query-gpu=index,utilization.gpu,memory.used,memory.total
+/// --format=csv,noheader,nounits`, with the memory in MiB.
+fn parse_stats(output: &str) -> Vec<GpuStats> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [device, utilization, memory_used, memory_total] = fields[..] else {
+                return None;
+            };
+            Some(GpuStats {
+                device: device.parse().ok()?,
+                utilization: utilization.parse().ok()?,
+                memory_used: memory_used.parse::<u64>().ok()? * 1024 * 1024,
+                memory_total: memory_total.parse::<u64>().ok()? * 1024 * 1024,
+            })
+        })
+        .collect()
+}
+
+/// Read the stats of all devices with `nvidia-smi`.
+pub fn read_stats() -> anyhow::Result<Vec<GpuStats>> {
+    let output = Command::new("nvidia-smi")
+        .args([
+            "--query-gpu=index,utilization.gpu,memory.used,memory.total",
+            "--format=csv,noheader,nounits",
+        ])
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!("nvidia-smi failed: {}", output.status);
+    }
+    Ok(parse_stats(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Export the stats of the devices as metrics periodically.
+pub fn spawn_metrics() {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(METRICS_INTERVAL);
+        loop {
+            interval.tick().await;
+            match tokio::task::spawn_blocking(read_stats).await {
+                Ok(Ok(stats)) => stats.iter().for_each(set_gpu_stats),
+                Ok(Err(e)) => warn!("Could not read the GPU stats: {e}"),
+                Err(e) => warn!("Could not read the GPU stats: {e}"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stats() {
+        let stats = parse_stats("0, 87, 20480, 81920\n1, 0, 3, 81920\ninvalid\n");
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].device, 0);
+        assert_eq!(stats[0].utilization, 87.0);
+        assert_eq!(stats[0].memory_used, 20480 * 1024 * 1024);
+        assert_eq!(stats[1].memory_total, 81920 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_check_devices() {
+        let cuda_devices = BTreeMap::from([
+            ("risc0".to_string(), vec![0, 1]),
+            ("sp1".to_string(), vec![2]),
+        ]);
+        assert!(check_devices(&cuda_devices, &[], &[]).is_err());
+        assert!(check_devices(&cuda_devices, &["risc0".to_string()], &[]).is_ok());
+        assert!(check_devices(&cuda_devices, &[], &["risc0".to_string()]).is_ok());
+        assert!(check_devices(&cuda_devices, &["sp1".to_string()], &[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_on_device() {
+        assert_eq!(cuda_device(), None);
+        assert_eq!(on_device(Some(1), async { cuda_device() }).await, Some(1));
+    }
+}
@@ -0,0 +1,230 @@
+//! The versions of the guests the provers prove with.
+//!
+//! The Risc0 and SP1 provers embed the guest they were built with. Other versions of a guest,
+//! e.g. the one of the next fork, are configured in `guests` of the config file with their ELF,
+//! the program id the on-chain verifier accepts and the blocks they prove:
+//!
+//! ```json
+//! [{"name": "ontake", "proof_type": "risc0", "elf": "guests/ontake.elf", "program_id": "0x...",
+//!   "network": "taiko_a7", "from_block": 1000000}]
+//! ```
+//!
+//! A proof request selects a version by name with `guest`, otherwise the version whose blocks
+//! include the block of the request is used, and the embedded guest if there is none. The
+//! provers check that the ELF has the configured program id before proving with it. The
+//! versions are reloaded with the config file, so a fork doesn't need a restart of the host.
+
+use std::{path::PathBuf, sync::RwLock};
+
+use alloy_primitives::B256;
+use lazy_static::lazy_static;
+use raiko_client::ErrorCode;
+use raiko_lib::prover::{self, GuestSelection};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    error::{HostError, HostResult},
+    request::{ProofRequest, ProofType},
+};
+
+/// The provers that can prove with another guest than the embedded one.
+const GUEST_PROVERS: [&str; 2] = ["risc0", "sp1"];
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// A version of the guest of a prover.
+pub struct GuestVersion {
+    /// The name requests select the version with.
+    pub name: String,
+    /// The proof type of the prover of the guest.
+    pub proof_type: ProofType,
+    /// The path of the ELF of the guest.
+    pub elf: PathBuf,
+    /// The program id of the guest: the image id of Risc0, the keccak hash of the ELF of SP1.
+    pub program_id: B256,
+    /// The chain the blocks of the version are on, any chain if not set.
+    #[serde(default)]
+    pub network: Option<String>,
+    /// The first block the version proves.
+    #[serde(default)]
+    pub from_block: Option<u64>,
+    /// The last block the version proves.
+    #[serde(default)]
+    pub to_block: Option<u64>,
+}
+
+impl GuestVersion {
+    /// Whether the version proves the block of the request by default.
+    fn covers(&self, request: &ProofRequest) -> bool {
+        self.proof_type == request.proof_type
+            && self.network.as_ref().map_or(true, |network| {
+                network.eq_ignore_ascii_case(&request.network)
+            })
+            && self
+                .from_block
+                .map_or(true, |from| request.block_number >= from)
+            && self.to_block.map_or(true, |to| request.block_number <= to)
+    }
+
+    /// The guest passed to the prover in its config.
+    pub fn selection(&self) -> GuestSelection {
+        GuestSelection {
+            name: self.name.clone(),
+            elf: self.elf.display().to_string(),
+            program_id: self.program_id,
+        }
+    }
+}
+
+lazy_static! {
+    static ref GUESTS: RwLock<Vec<GuestVersion>> = RwLock::new(Vec::new());
+}
+
+/// Check the versions: their names are unique, their provers can prove with them and their
+/// ELF files exist.
+pub fn validate(versions: &[GuestVersion]) -> HostResult<()> {
+    for (i, version) in versions.iter().enumerate() {
+        let invalid = |reason: String| {
+            HostError::InvalidRequestConfig(format!("Invalid guest {}: {reason}", version.name))
+        };
+        if versions[..i].iter().any(|other| other.name == version.name) {
+            return Err(invalid("the name is used twice".to_string()));
+        }
+        if !GUEST_PROVERS.contains(&version.proof_type.to_string().as_str()) {
+            return Err(invalid(format!(
+                "the {} prover only proves with its embedded guest",
+                version.proof_type
+            )));
+        }
+        if let (Some(from_block), Some(to_block)) = (version.from_block, version.to_block) {
+            if from_block > to_block {
+                return Err(invalid("from_block is after to_block".to_string()));
+            }
+        }
+        if !version.elf.is_file() {
+            return Err(invalid(format!(
+                "the ELF {} doesn't exist",
+                version.elf.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Replace the configured versions, after checking them.
+pub fn configure(versions: Vec<GuestVersion>) -> HostResult<()> {
+    validate(&versions)?;
+    if !versions.is_empty() {
+        info!("Registered {} guest versions", versions.len());
+    }
+    prover::configure_guests(versions.iter().map(GuestVersion::selection).collect());
+    *GUESTS.write().unwrap() = versions;
+    Ok(())
+}
+
+/// The configured versions.
+pub fn list_guests() -> Vec<GuestVersion> {
+    GUESTS.read().unwrap().clone()
+}
+
+/// The version the request is proven with, `None` for the embedded guest.
+pub fn select(request: &ProofRequest) -> HostResult<Option<GuestVersion>> {
+    let guests = GUESTS.read().unwrap();
+    let Some(name) = &request.guest else {
+        return Ok(guests
+            .iter()
+            .find(|version| version.covers(request))
+            .cloned());
+    };
+    let version = guests
+        .iter()
+        .find(|version| &version.name == name)
+        .ok_or_else(|| {
+            HostError::invalid(
+                ErrorCode::InvalidRequest,
+                "guest",
+                format!("Unknown guest: {name}"),
+            )
+        })?;
+    if version.proof_type != request.proof_type {
+        return Err(HostError::invalid(
+            ErrorCode::InvalidRequest,
+            "guest",
+            format!(
+                "The guest {name} is a guest of the {} prover",
+                version.proof_type
+            ),
+        ));
+    }
+    Ok(Some(version.clone()))
+}
+
+/// The id of the program the request is proven with.
+pub fn program_id(request: &ProofRequest) -> HostResult<Option<B256>> {
+    match select(request)? {
+        Some(version) => Ok(Some(version.program_id)),
+        None => request.proof_type.program_id(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(block_number: u64, guest: Option<&str>) -> ProofRequest {
+        serde_json::from_value(serde_json::json!({
+            "block_number": block_number,
+            "rpc": "http://localhost:8545",
+            "l1_rpc": "http://localhost:8546",
+            "beacon_rpc": "http://localhost:5052",
+            "network": "guests_test",
+            "l1_network": "holesky",
+            "graffiti": B256::ZERO,
+            "prover": "0x0000000000000000000000000000000000000000",
+            "proof_type": "risc0",
+            "guest": guest,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_select() {
+        let elf = std::env::temp_dir().join("raiko-guests-test.elf");
+        std::fs::write(&elf, b"elf").unwrap();
+        let version = |name: &str, from_block, to_block| GuestVersion {
+            name: name.to_string(),
+            proof_type: ProofType::Risc0,
+            elf: elf.clone(),
+            program_id: B256::repeat_byte(1),
+            network: Some("guests_test".to_string()),
+            from_block,
+            to_block,
+        };
+        let versions = vec![
+            version("pre", None, Some(99)),
+            version("post", Some(100), None),
+        ];
+        validate(&versions).unwrap();
+        assert!(validate(&[version("pre", None, None), version("pre", None, None)]).is_err());
+        assert!(validate(&[version("reversed", Some(2), Some(1))]).is_err());
+        let mut missing = version("missing", None, None);
+        missing.elf = elf.with_extension("missing");
+        assert!(validate(&[missing]).is_err());
+
+        configure(versions).unwrap();
+        assert_eq!(select(&request(99, None)).unwrap().unwrap().name, "pre");
+        assert_eq!(select(&request(100, None)).unwrap().unwrap().name, "post");
+        // The request selects the version regardless of the block.
+        assert_eq!(
+            select(&request(100, Some("pre"))).unwrap().unwrap().name,
+            "pre"
+        );
+        assert!(select(&request(100, Some("unknown"))).is_err());
+        assert_eq!(
+            program_id(&request(100, None)).unwrap(),
+            Some(B256::repeat_byte(1))
+        );
+        configure(Vec::new()).unwrap();
+        assert_eq!(select(&request(100, None)).unwrap(), None);
+    }
+}
@@ -0,0 +1,243 @@
+//! Store of the recent block headers of every chain.
+//!
+//! The preflight of a Taiko block needs the 256 headers before it for the anchor transaction.
+//! Consecutive blocks share almost all of them, so the headers are kept per chain and only the
+//! headers the store doesn't have are fetched. A background task per chain keeps the store
+//! close to the head of the chain.
+//!
+//! Stored headers are only used as far as they link up with the freshly fetched parent block,
+//! so a reorg never ends up in an input. Headers are only stored with their own hash, so a
+//! node can't plant a header under the hash of another one.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use alloy_consensus::{Header as AlloyConsensusHeader, Sealable};
+use alloy_primitives::B256;
+use alloy_provider::{Provider, ProviderBuilder, RootProvider};
+use anyhow::{anyhow, ensure, Result};
+use futures::future::try_join_all;
+use lazy_static::lazy_static;
+use raiko_lib::taiko_utils::to_header;
+use tracing::{debug, warn};
+
+/// The number of headers kept per chain, the 256 needed by a block plus some margin so the
+/// store still covers requests for blocks a bit behind the head.
+pub const HEADER_STORE_CAPACITY: usize = 1024;
+
+/// The number of headers fetched at the same time while syncing.
+const SYNC_BATCH_SIZE: u64 = 32;
+
+/// The recent headers of a chain, by block number.
+#[derive(Debug)]
+pub struct HeaderStore {
+    capacity: usize,
+    headers: RwLock<BTreeMap<u64, (B256, AlloyConsensusHeader)>>,
+}
+
+impl HeaderStore {
+    /// Create a store keeping the latest `capacity` headers.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            headers: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Get the hash and header of the given block.
+    pub fn get(&self, block_number: u64) -> Option<(B256, AlloyConsensusHeader)> {
+        self.headers.read().unwrap().get(&block_number).cloned()
+    }
+
+    /// Store the header of the given block, replacing the header stored for the block before.
+    /// The oldest headers are dropped once the store is full. Fails for a header that isn't the
+    /// header of the block with the given hash.
+    pub fn insert(
+        &self,
+        block_number: u64,
+        hash: B256,
+        header: AlloyConsensusHeader,
+    ) -> Result<()> {
+        ensure!(
+            header.number == block_number,
+            "The header of block {block_number} is the header of block {}",
+            header.number
+        );
+        let header_hash = header.hash();
+        ensure!(
+            header_hash == hash,
+            "The header of block {block_number} hashes to {header_hash}, not to {hash}"
+        );
+        let mut headers = self.headers.write().unwrap();
+        headers.insert(block_number, (hash, header));
+        while headers.len() > self.capacity {
+            headers.pop_first();
+        }
+        Ok(())
+    }
+
+    /// The number and hash of the latest stored block.
+    pub fn latest(&self) -> Option<(u64, B256)> {
+        self.headers
+            .read()
+            .unwrap()
+            .last_key_value()
+            .map(|(number, (hash, _))| (*number, *hash))
+    }
+
+    /// Get the stored ancestors of a block, newest first, down to block `start`. Stops at the
+    /// first ancestor that is missing or doesn't link up with the block after it.
+    pub fn ancestors(
+        &self,
+        block_number: u64,
+        parent_hash: B256,
+        start: u64,
+    ) -> Vec<(u64, B256, AlloyConsensusHeader)> {
+        let headers = self.headers.read().unwrap();
+        let mut ancestors = Vec::new();
+        let mut expected_hash = parent_hash;
+        for number in (start..block_number).rev() {
+            match headers.get(&number) {
+                Some((hash, header)) if *hash == expected_hash => {
+                    expected_hash = header.parent_hash;
+                    ancestors.push((number, *hash, header.clone()));
+                }
+                _ => break,
+            }
+        }
+        ancestors
+    }
+
+    fn clear(&self) {
+        self.headers.write().unwrap().clear();
+    }
+}
+
+lazy_static! {
+    static ref STORES: RwLock<HashMap<String, Arc<HeaderStore>>> = RwLock::new(HashMap::new());
+}
+
+/// Get the header store of the given chain.
+pub fn get_store(chain: &str) -> Arc<HeaderStore> {
+    if let Some(store) = STORES.read().unwrap().get(chain) {
+        return store.clone();
+    }
+    STORES
+        .write()
+        .unwrap()
+        .entry(chain.to_string())
+        .or_insert_with(|| Arc::new(HeaderStore::new(HEADER_STORE_CAPACITY)))
+        .clone()
+}
+
+/// Keep the header store of the chain in sync with the node at `rpc_url`.
+pub fn spawn_sync(chain: String, rpc_url: String, interval: Duration) {
+    tokio::spawn(async move {
+        let store = get_store(&chain);
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = sync(&store, &rpc_url).await {
+                warn!("Could not sync the headers of {chain}: {e}");
+            }
+        }
+    });
+}
+
+/// Fetch the headers from the latest stored header up to the head of the chain.
+async fn sync(store: &HeaderStore, rpc_url: &str) -> Result<()> {
+    let provider =
+        ProviderBuilder::new().provider(RootProvider::new_http(reqwest::Url::parse(rpc_url)?));
+    let head = provider.get_block_number().await?;
+    let from = match store.latest() {
+        Some((latest, _)) if latest < head => latest + 1,
+        Some(_) => return Ok(()),
+        None => 0,
+    }
+    .max(head.saturating_sub(store.capacity as u64 - 1));
+
+    let mut start = from;
+    while start <= head {
+        let end = (start + SYNC_BATCH_SIZE - 1).min(head);
+        let blocks = try_join_all(
+            (start..=end).map(|number| provider.get_block_by_number(number.into(), false)),
+        )
+        .await?;
+        for (number, block) in (start..=end).zip(blocks) {
+            let block = block.ok_or_else(|| anyhow!("No data for {number}"))?;
+            let hash = block
+                .header
+                .hash
+                .ok_or_else(|| anyhow!("No hash for block {number}"))?;
+            // A reorg, drop everything and start over from the head on the next sync.
+            if let Some((parent_hash, _)) = store.get(number.wrapping_sub(1)) {
+                if parent_hash != block.header.parent_hash {
+                    warn!("Reorg detected at block {number}, clearing the header store");
+                    store.clear();
+                    return Ok(());
+                }
+            }
+            store.insert(number, hash, to_header(&block.header))?;
+        }
+        start = end + 1;
+    }
+    debug!("Synced headers {from}..={head}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(parent_hash: B256, number: u64) -> AlloyConsensusHeader {
+        AlloyConsensusHeader {
+            parent_hash,
+            number,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_ancestors_stop_at_unlinked_header() {
+        let store = HeaderStore::new(3);
+        let mut hashes = vec![B256::ZERO];
+        for n in 1..=4 {
+            let header = header(hashes[n as usize - 1], n);
+            hashes.push(header.hash());
+            store.insert(n, header.hash(), header).unwrap();
+        }
+        // Only the latest 3 headers are kept.
+        assert!(store.get(1).is_none());
+        assert_eq!(store.latest(), Some((4, hashes[4])));
+
+        let ancestors = store.ancestors(5, hashes[4], 0);
+        assert_eq!(
+            ancestors.iter().map(|(n, ..)| *n).collect::<Vec<_>>(),
+            [4, 3, 2]
+        );
+        // The block was built on another block 4, e.g. after a reorg.
+        assert!(store.ancestors(5, B256::repeat_byte(9), 0).is_empty());
+        // Block 3 was replaced, so block 2 doesn't link up anymore.
+        let reorged = header(B256::repeat_byte(6), 3);
+        store.insert(3, reorged.hash(), reorged).unwrap();
+        assert_eq!(store.ancestors(5, hashes[4], 0).len(), 1);
+        assert_eq!(store.ancestors(5, hashes[4], 4).len(), 1);
+    }
+
+    #[test]
+    fn test_reject_mismatched_header() {
+        let store = HeaderStore::new(3);
+        let header = header(B256::ZERO, 1);
+        // Another header under the hash of the block, or the header of another block.
+        assert!(store
+            .insert(1, B256::repeat_byte(1), header.clone())
+            .is_err());
+        assert!(store.insert(2, header.hash(), header.clone()).is_err());
+        assert!(store.get(1).is_none());
+        store.insert(1, header.hash(), header).unwrap();
+        assert!(store.get(1).is_some());
+    }
+}
@@ -0,0 +1,331 @@
+//! The programs the provers of the host prove with.
+//!
+//! The on-chain verifiers only accept proofs of the programs registered with them: the image
+//! id of the Risc0 guest, the program of the SP1 guest and the MRENCLAVE and MRSIGNER of the
+//! SGX enclave. Relayers compare them with [list_images] before requesting proofs.
+//!
+//! `image verify` audits a guest: it rebuilds the guest from source in the docker image of its
+//! toolchain, which gives the same ELF on every machine, and compares the program id of the ELF
+//! with the program of a running host and with the programs the verifier on L1 trusts, see
+//! [verify_image].
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use alloy_primitives::{Address, B256};
+use alloy_provider::{ProviderBuilder, RootProvider};
+use alloy_sol_types::sol;
+use anyhow::anyhow;
+#[cfg(feature = "sgx")]
+use raiko_primitives::hex;
+use raiko_primitives::keccak::keccak;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::{
+    error::{HostError, HostResult},
+    registry::{get_prover, list_provers},
+    request::ProofType,
+    Cli, ImageVerifyArgs,
+};
+
+/// The commit the host was built from, set by the build script from git or from
+/// `RAIKO_BUILD_COMMIT` at build time.
+pub const BUILD_COMMIT: Option<&str> = option_env!("RAIKO_BUILD_COMMIT");
+
+/// The provers whose guests are embedded into the host when it's built.
+const EMBEDDED_GUESTS: [&str; 4] = ["risc0", "sp1", "jolt", "plonky3"];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+/// The program a prover proves blocks with.
+pub struct ProverImage {
+    /// The name of the prover.
+    pub name: String,
+    /// The id of the guest program: the image id of Risc0, the keccak hash of the ELF of SP1,
+    /// which the SP1 SDK of the host identifies the program with.
+    #[schema(value_type = Option<String>)]
+    pub program_id: Option<B256>,
+    /// The MRENCLAVE of the SGX enclave, from the quote of its bootstrapped key.
+    #[schema(value_type = Option<String>)]
+    pub mr_enclave: Option<B256>,
+    /// The MRSIGNER of the SGX enclave, from the quote of its bootstrapped key.
+    #[schema(value_type = Option<String>)]
+    pub mr_signer: Option<B256>,
+    /// The commit the guest was built from, for the guests built into the host.
+    pub guest_commit: Option<String>,
+    /// Why the program of the prover is unknown, e.g. an SGX enclave that isn't bootstrapped.
+    pub error: Option<String>,
+}
+
+/// The programs of all registered provers, ordered by name.
+pub fn list_images() -> Vec<ProverImage> {
+    list_provers()
+        .into_iter()
+        .map(|prover| {
+            let mut image = ProverImage {
+                program_id: get_prover(&prover.name).and_then(|backend| backend.program_id()),
+                name: prover.name,
+                ..Default::default()
+            };
+            if prover.builtin && EMBEDDED_GUESTS.contains(&image.name.as_str()) {
+                image.guest_commit = BUILD_COMMIT.map(str::to_string);
+            }
+            if image.name == "sgx" {
+                sgx_measurement(&mut image);
+            }
+            image
+        })
+        .collect()
+}
+
+#[cfg(feature = "sgx")]
+fn sgx_measurement(image: &mut ProverImage) {
+    let measurement = sgx_prover::load_key()
+        .map_err(|e| e.to_string())
+        .and_then(|key| {
+            hex::decode(key.quote.trim_start_matches("0x"))
+                .map_err(|e| format!("Invalid quote: {e}"))
+        })
+        .and_then(|quote| sgx_prover::quote_measurement(&quote).map_err(|e| e.to_string()));
+    match measurement {
+        Ok((mr_enclave, mr_signer)) => {
+            image.mr_enclave = Some(mr_enclave);
+            image.mr_signer = Some(mr_signer);
+        }
+        Err(e) => image.error = Some(e),
+    }
+}
+
+#[cfg(not(feature = "sgx"))]
+fn sgx_measurement(_image: &mut ProverImage) {}
+
+sol! {
+    #[sol(rpc)]
+    contract RiscZeroVerifier {
+        function isImageTrusted(bytes32 imageId) external view returns (bool);
+    }
+
+    #[sol(rpc)]
+    contract SP1Verifier {
+        function isProgramTrusted(bytes32 programVKey) external view returns (bool);
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// The result of `image verify`.
+pub struct ImageVerification {
+    pub proof_type: String,
+    /// The ELF of the guest that was checked.
+    pub elf: PathBuf,
+    /// The program id of the ELF.
+    pub program_id: B256,
+    /// The program id of the host, the running one or this binary.
+    pub host_program_id: Option<B256>,
+    /// Whether the verifier on L1 trusts the program id of the ELF, if it was checked.
+    pub trusted_on_chain: Option<bool>,
+    /// What doesn't match the rebuilt guest.
+    pub mismatches: Vec<String>,
+}
+
+/// The command building the guest of the prover reproducibly from the raiko checkout, and the
+/// ELF it builds.
+fn guest_build(proof_type: &ProofType, source: &Path) -> HostResult<(Command, PathBuf)> {
+    match proof_type {
+        ProofType::Risc0 => {
+            let guest = source.join("provers/risc0/guest");
+            // `cargo risczero build` always builds in the docker image of risc0.
+            let mut command = Command::new("cargo");
+            command
+                .args(["risczero", "build", "--manifest-path"])
+                .arg(guest.join("Cargo.toml"));
+            let elf = guest
+                .join("target/riscv-guest/riscv32im-risc0-zkvm-elf/docker/risc0_methods")
+                .join("risc0-methods");
+            Ok((command, elf))
+        }
+        ProofType::Sp1 => {
+            let guest = source.join("provers/sp1/guest");
+            let mut command = Command::new("cargo");
+            command
+                .args(["prove", "build", "--docker"])
+                .current_dir(&guest);
+            Ok((command, guest.join("elf/riscv32im-succinct-zkvm-elf")))
+        }
+        _ => Err(HostError::InvalidRequestConfig(format!(
+            "The {proof_type} prover has no guest to rebuild, only risc0 and sp1 have"
+        ))),
+    }
+}
+
+/// The program id of a guest ELF of the prover, like the prover computes it.
+pub fn elf_program_id(proof_type: &ProofType, elf: &[u8]) -> HostResult<B256> {
+    match proof_type {
+        #[cfg(feature = "risc0")]
+        ProofType::Risc0 => Ok(risc0_prover::elf_image_id(elf)?),
+        // The SP1 SDK of the host identifies the program by the hash of its ELF.
+        ProofType::Sp1 => Ok(keccak(elf).into()),
+        _ => Err(HostError::FeatureNotSupportedError(proof_type.clone())),
+    }
+}
+
+/// The program id the host proves with: the one served by the running host at the URL, or
+/// the one of the guest built into this binary.
+async fn host_program_id(proof_type: &ProofType, host: Option<&str>) -> HostResult<Option<B256>> {
+    let Some(host) = host else {
+        return proof_type.program_id();
+    };
+    let url = format!("{}/provers/images", host.trim_end_matches('/'));
+    let images: Vec<ProverImage> = reqwest::get(&url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| anyhow!("Could not get the programs of the host from {url}: {e}"))?
+        .json()
+        .await
+        .map_err(|e| anyhow!("Invalid programs of the host from {url}: {e}"))?;
+    Ok(images
+        .into_iter()
+        .find(|image| image.name == proof_type.to_string())
+        .and_then(|image| image.program_id))
+}
+
+/// Whether the verifier of the proof type on L1 trusts the program id.
+async fn trusted_on_chain(
+    proof_type: &ProofType,
+    l1_rpc: &str,
+    verifier: Address,
+    program_id: B256,
+) -> HostResult<bool> {
+    let url = reqwest::Url::parse(l1_rpc)
+        .map_err(|e| HostError::InvalidRequestConfig(format!("Invalid L1 node URL: {e}")))?;
+    let provider = ProviderBuilder::new().provider(RootProvider::new_http(url));
+    let trusted = match proof_type {
+        ProofType::Risc0 => {
+            RiscZeroVerifier::new(verifier, &provider)
+                .isImageTrusted(program_id)
+                .call()
+                .await
+        }
+        _ => {
+            SP1Verifier::new(verifier, &provider)
+                .isProgramTrusted(program_id)
+                .call()
+                .await
+        }
+    }
+    .map_err(|e| anyhow!("Could not call the verifier {verifier}: {e}"))?;
+    Ok(trusted._0)
+}
+
+/// Check the guest of the proof type against the host and the verifier on L1.
+pub async fn verify_image(opts: &Cli, args: &ImageVerifyArgs) -> HostResult<ImageVerification> {
+    let proof_type: ProofType = args
+        .proof_type
+        .as_deref()
+        .or(opts.proof_request_opt.proof_type.as_deref())
+        .unwrap_or_default()
+        .parse()?;
+    let elf = match &args.elf {
+        Some(elf) => elf.clone(),
+        None => {
+            let (mut command, elf) = guest_build(&proof_type, &args.source)?;
+            info!("Rebuilding the {proof_type} guest: {command:?}");
+            let status = command
+                .status()
+                .map_err(|e| anyhow!("Could not run {command:?}: {e}"))?;
+            if !status.success() {
+                return Err(anyhow!("Building the {proof_type} guest failed: {status}").into());
+            }
+            elf
+        }
+    };
+    let program_id = elf_program_id(&proof_type, &std::fs::read(&elf)?)?;
+
+    let mut verification = ImageVerification {
+        proof_type: proof_type.to_string(),
+        elf,
+        program_id,
+        host_program_id: host_program_id(&proof_type, args.host.as_deref()).await?,
+        ..Default::default()
+    };
+    if verification.host_program_id != Some(program_id) {
+        verification.mismatches.push(format!(
+            "The host proves with {} instead of {program_id}",
+            verification
+                .host_program_id
+                .map_or("no program".to_string(), |id| id.to_string())
+        ));
+    }
+    if let Some(verifier) = &args.verifier {
+        let verifier: Address = verifier.parse().map_err(|e| {
+            HostError::InvalidRequestConfig(format!("Invalid verifier address: {e}"))
+        })?;
+        let l1_rpc = args
+            .l1_rpc
+            .as_deref()
+            .or(opts.proof_request_opt.l1_rpc.as_deref())
+            .ok_or_else(|| {
+                HostError::InvalidRequestConfig(
+                    "The L1 node to call the verifier on is missing".to_string(),
+                )
+            })?;
+        let trusted = trusted_on_chain(&proof_type, l1_rpc, verifier, program_id).await?;
+        if !trusted {
+            verification.mismatches.push(format!(
+                "The verifier {verifier} doesn't trust {program_id}"
+            ));
+        }
+        verification.trusted_on_chain = Some(trusted);
+    }
+    Ok(verification)
+}
+
+/// Run `image verify`, failing when the guest doesn't match.
+pub async fn verify_image_command(opts: &Cli, args: &ImageVerifyArgs) -> HostResult<()> {
+    let verification = verify_image(opts, args).await?;
+    println!("{}", serde_json::to_string_pretty(&verification)?);
+    if verification.mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(HostError::Anyhow(anyhow!(
+            "The {} guest doesn't match: {}",
+            verification.proof_type,
+            verification.mismatches.join(", ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_images() {
+        let images = list_images();
+        let native = images.iter().find(|image| image.name == "native").unwrap();
+        // The native prover has no guest program.
+        assert_eq!(native.program_id, None);
+        assert_eq!(native.guest_commit, None);
+        assert_eq!(native.mr_enclave, None);
+    }
+
+    #[test]
+    fn test_guest_build() {
+        let (command, elf) = guest_build(&ProofType::Sp1, Path::new("raiko")).unwrap();
+        assert_eq!(command.get_program(), "cargo");
+        assert!(command.get_args().any(|arg| arg == "--docker"));
+        assert_eq!(
+            elf,
+            Path::new("raiko/provers/sp1/guest/elf/riscv32im-succinct-zkvm-elf")
+        );
+        assert!(guest_build(&ProofType::Native, Path::new("raiko")).is_err());
+
+        assert_eq!(
+            elf_program_id(&ProofType::Sp1, b"elf").unwrap(),
+            B256::from(keccak(b"elf"))
+        );
+        assert!(elf_program_id(&ProofType::Native, b"elf").is_err());
+    }
+}
@@ -0,0 +1,315 @@
+//! Cache for prepared guest inputs.
+//!
+//! The preflight is the slowest part of proving a block after the prover itself, and it is the
+//! same for every proof type. The inputs are stored [encoded](crate::input_codec) in the cache
+//! directory, keyed by the network and the hash of the block, so proving a block again with
+//! another prover skips the preflight, while a reorged block is never proven with the input of
+//! the old block. The inputs cached by older versions, including the gzipped ones, are still
+//! used and converted to the current format when they are read.
+//!
+//! The cache is pruned after every insert, removing the inputs older than the max age and then
+//! the oldest inputs until the cache fits the max size. The [garbage collection](crate::gc)
+//! removes more of the oldest inputs when the disk runs out of space.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use alloy_primitives::B256;
+use raiko_lib::input::{get_input_path, GuestInput};
+use tracing::{debug, info, warn};
+
+use crate::{
+    error::HostResult,
+    faults, input_codec,
+    metrics::{inc_input_cache_hit, inc_input_cache_miss},
+};
+
+/// The extension of the inputs cached by older versions, gzipped without a header.
+const LEGACY_EXTENSION: &str = "gz";
+
+/// Handle to the input cache directory. The cache is disabled without a directory.
+#[derive(Clone, Debug, Default)]
+pub struct InputCache {
+    dir: Option<PathBuf>,
+    limits: Arc<RwLock<Limits>>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Limits {
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+}
+
+impl InputCache {
+    /// Create a cache in the given directory, holding at most `max_size` bytes of inputs which
+    /// are at most `max_age` old.
+    pub fn new(dir: Option<PathBuf>, max_size: Option<u64>, max_age: Option<Duration>) -> Self {
+        Self {
+            dir,
+            limits: Arc::new(RwLock::new(Limits { max_size, max_age })),
+        }
+    }
+
+    /// Change the max size and age of the inputs, applied from the next prune on.
+    pub fn set_limits(&self, max_size: Option<u64>, max_age: Option<Duration>) {
+        *self.limits.write().unwrap() = Limits { max_size, max_age };
+    }
+
+    /// The directory of the cache, unset when the cache is disabled.
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Whether inputs are cached.
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// Get the cached input of the given block.
+    pub fn get(&self, network: &str, block_number: u64, block_hash: &B256) -> Option<GuestInput> {
+        let dir = self.dir.as_ref()?;
+        let path = get_input_path(dir, block_number, block_hash, network);
+        let legacy_path = path.with_extension(LEGACY_EXTENSION);
+        let read_path = if path.exists() {
+            path.clone()
+        } else if legacy_path.exists() {
+            legacy_path
+        } else {
+            inc_input_cache_miss();
+            return None;
+        };
+        faults::corrupt_input(&read_path);
+        match read_input(&read_path) {
+            Ok((input, outdated)) => {
+                inc_input_cache_hit();
+                if outdated {
+                    // Convert it, so it is decoded straight away next time.
+                    debug!("Converting cached input {read_path:?} to the current format");
+                    match write_input(&path, &input) {
+                        Ok(()) if read_path != path => {
+                            remove_input(&read_path);
+                        }
+                        Ok(()) => {}
+                        Err(e) => warn!("Could not convert cached input {read_path:?}: {e}"),
+                    }
+                }
+                Some(input)
+            }
+            Err(e) => {
+                // Remove it, so it is cached again once the input is prepared.
+                warn!("Could not load cached input {read_path:?}: {e}");
+                remove_input(&read_path);
+                inc_input_cache_miss();
+                None
+            }
+        }
+    }
+
+    /// Remove the cached input of the given block, e.g. after it was reorged. Returns whether
+    /// it was cached.
+    pub fn remove(&self, network: &str, block_number: u64, block_hash: &B256) -> bool {
+        let Some(dir) = self.dir.as_ref() else {
+            return false;
+        };
+        let path = get_input_path(dir, block_number, block_hash, network);
+        let legacy_path = path.with_extension(LEGACY_EXTENSION);
+        let removed = path.exists() && remove_input(&path);
+        (legacy_path.exists() && remove_input(&legacy_path)) || removed
+    }
+
+    /// Cache the input, unless the input of the block is cached already.
+    pub fn put(&self, network: &str, input: &GuestInput) -> HostResult<()> {
+        let Some(dir) = self.dir.as_ref() else {
+            return Ok(());
+        };
+        let path = get_input_path(dir, input.block_number, &input.block_hash, network);
+        if path.exists() || path.with_extension(LEGACY_EXTENSION).exists() {
+            return Ok(());
+        }
+
+        info!("Caching input for {path:?}");
+        write_input(&path, input)?;
+
+        self.prune()?;
+        Ok(())
+    }
+
+    /// Remove the inputs older than the max age, then the oldest inputs until the cache fits
+    /// the max size. Returns the number of bytes removed.
+    pub fn prune(&self) -> HostResult<u64> {
+        self.evict(0)
+    }
+
+    /// Prune the cache and remove the oldest of the other inputs until at least `bytes` bytes
+    /// are removed, to free disk space. Returns the number of bytes removed.
+    pub fn evict(&self, bytes: u64) -> HostResult<u64> {
+        let Some(dir) = self.dir.as_ref() else {
+            return Ok(0);
+        };
+        let Limits { max_size, max_age } = *self.limits.read().unwrap();
+
+        let mut inputs = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !(name.starts_with("input-")
+                && (name.ends_with(".bin.zst") || name.ends_with(".bin.gz")))
+            {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            inputs.push((modified, metadata.len(), entry.path()));
+        }
+        if max_size.is_none() && max_age.is_none() && bytes == 0 {
+            return Ok(0);
+        }
+        // Oldest first
+        inputs.sort();
+
+        let now = SystemTime::now();
+        let mut total_size: u64 = inputs.iter().map(|(_, size, _)| size).sum();
+        let mut removed = 0;
+        for (modified, size, path) in inputs {
+            let expired = max_age
+                .is_some_and(|max_age| now.duration_since(modified).unwrap_or_default() > max_age);
+            let too_large = max_size.is_some_and(|max_size| total_size > max_size);
+            if !expired && !too_large && removed >= bytes {
+                // The inputs are sorted by age, so none of the newer inputs need to be removed.
+                break;
+            }
+            debug!("Pruning cached input {path:?}");
+            if remove_input(&path) {
+                removed += size;
+            }
+            total_size -= size;
+        }
+        Ok(removed)
+    }
+}
+
+/// Read the cached input, returns whether it was written in an older format.
+fn read_input(path: &Path) -> HostResult<(GuestInput, bool)> {
+    input_codec::decode_input(BufReader::new(File::open(path)?))
+}
+
+fn write_input(path: &Path, input: &GuestInput) -> HostResult<()> {
+    // Write to a temporary file first, so a crash never leaves a partial input behind.
+    let tmp_path = path.with_extension("tmp");
+    input_codec::encode(input, BufWriter::new(File::create(&tmp_path)?), "cache")?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Remove the cached input, returns whether it was removed.
+fn remove_input(path: &Path) -> bool {
+    match std::fs::remove_file(path) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Could not remove cached input {path:?}: {e}");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use raiko_primitives::mpt::MptNode;
+
+    use super::*;
+
+    fn input(block_number: u64) -> GuestInput {
+        GuestInput {
+            block_number,
+            block_hash: B256::repeat_byte(block_number as u8),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = InputCache::new(Some(dir.path().to_path_buf()), None, None);
+        let input = input(1);
+        cache.put("taiko_a7", &input).unwrap();
+
+        let cached = cache.get("taiko_a7", 1, &input.block_hash).unwrap();
+        assert_eq!(cached.block_hash, input.block_hash);
+        // Another block hash at the same height, e.g. after a reorg.
+        assert!(cache.get("taiko_a7", 1, &B256::ZERO).is_none());
+        assert!(cache.get("holesky", 1, &input.block_hash).is_none());
+    }
+
+    #[test]
+    fn test_legacy_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = InputCache::new(Some(dir.path().to_path_buf()), None, None);
+        let input = input(1);
+        let path = get_input_path(dir.path(), 1, &input.block_hash, "taiko_a7");
+        let legacy_path = path.with_extension(LEGACY_EXTENSION);
+        std::fs::write(
+            &legacy_path,
+            input_codec::gzipped_input(1, input.block_hash, MptNode::default()),
+        )
+        .unwrap();
+
+        let cached = cache.get("taiko_a7", 1, &input.block_hash).unwrap();
+        assert_eq!(cached.block_hash, input.block_hash);
+        // The input is converted to the current format.
+        assert!(!legacy_path.exists());
+        assert!(
+            !input_codec::decode_input(File::open(&path).unwrap())
+                .unwrap()
+                .1
+        );
+        let cached = cache.get("taiko_a7", 1, &input.block_hash).unwrap();
+        assert_eq!(cached.block_hash, input.block_hash);
+    }
+
+    #[test]
+    fn test_remove_invalid_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = InputCache::new(Some(dir.path().to_path_buf()), None, None);
+        let invalid = input(2);
+        let invalid_path = get_input_path(dir.path(), 2, &invalid.block_hash, "taiko_a7");
+        std::fs::write(&invalid_path, b"corrupt").unwrap();
+
+        // An input that can't be decoded is removed, and cached again.
+        assert!(cache.get("taiko_a7", 2, &invalid.block_hash).is_none());
+        assert!(!invalid_path.exists());
+        cache.put("taiko_a7", &invalid).unwrap();
+        assert!(cache.get("taiko_a7", 2, &invalid.block_hash).is_some());
+    }
+
+    #[test]
+    fn test_prune_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = InputCache::new(Some(dir.path().to_path_buf()), None, None);
+        cache.put("taiko_a7", &input(1)).unwrap();
+        let size = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().metadata().unwrap().len())
+            .sum::<u64>();
+
+        // Room for two inputs only, the oldest one goes.
+        let cache = InputCache::new(Some(dir.path().to_path_buf()), Some(size * 5 / 2), None);
+        for block_number in 2..=3 {
+            std::thread::sleep(Duration::from_millis(10));
+            cache.put("taiko_a7", &input(block_number)).unwrap();
+        }
+        assert!(cache.get("taiko_a7", 1, &input(1).block_hash).is_none());
+        assert!(cache.get("taiko_a7", 2, &input(2).block_hash).is_some());
+        assert!(cache.get("taiko_a7", 3, &input(3).block_hash).is_some());
+
+        // Freeing disk space removes the oldest of the inputs that fit.
+        assert!(cache.evict(1).unwrap() > 0);
+        assert!(cache.get("taiko_a7", 2, &input(2).block_hash).is_none());
+        assert!(cache.get("taiko_a7", 3, &input(3).block_hash).is_some());
+    }
+}
@@ -0,0 +1,212 @@
+//! Decoders of the inputs written by older versions, converted into the current input.
+//!
+//! The gzipped inputs written before the header and version 1 store the tries as trees,
+//! version 2 stores them in node arenas. Neither has the Taiko forks in the chain spec, they are
+//! taken from the spec of the network of the input.
+
+use std::{collections::BTreeMap, io::Read};
+
+use alloy_consensus::Header as AlloyConsensusHeader;
+use alloy_primitives::{Address, Bytes, ChainId, B256};
+use alloy_rpc_types::Withdrawal as AlloyWithdrawal;
+use raiko_lib::{
+    consts::{get_network_spec, ChainSpec, Eip1559Constants, ForkCondition, Network},
+    input::{GuestInput, StorageEntry, TaikoGuestInput},
+    serde_with::{RlpBytes, StorageArena, TrieArena},
+};
+use raiko_primitives::mpt::MptNode;
+use revm::primitives::{HashMap, SpecId};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_with::serde_as;
+
+use crate::error::{HostError, HostResult};
+
+/// Decode an input of the given older version.
+pub(super) fn decode<R: Read>(version: u8, reader: R) -> HostResult<GuestInput> {
+    match version {
+        1 => decode_as::<TreeTries, _>(reader),
+        2 => decode_as::<ArenaTries, _>(reader),
+        _ => Err(super::unsupported_version(version)),
+    }
+}
+
+fn decode_as<T: DeserializeOwned + Into<Tries>, R: Read>(reader: R) -> HostResult<GuestInput> {
+    let input: Input<T> =
+        bincode::deserialize_from(reader).map_err(|e| HostError::Anyhow(e.into()))?;
+    Ok(input.into())
+}
+
+/// The state trie and the storage tries of an input.
+type Tries = (MptNode, HashMap<Address, StorageEntry>);
+
+/// The input of the older versions, with the tries serialized as `T`. bincode writes the fields
+/// of a struct one after the other, so the tries, which are adjacent fields of the input, are
+/// read as one struct.
+#[serde_as]
+#[derive(Default, Serialize, Deserialize)]
+struct Input<T> {
+    network: Network,
+    chain_spec: LegacyChainSpec,
+    block_number: u64,
+    gas_used: u64,
+    block_hash: B256,
+    #[serde_as(as = "RlpBytes")]
+    parent_header: AlloyConsensusHeader,
+    beneficiary: Address,
+    gas_limit: u64,
+    timestamp: u64,
+    extra_data: Bytes,
+    mix_hash: B256,
+    withdrawals: Vec<AlloyWithdrawal>,
+    tries: T,
+    contracts: Vec<Bytes>,
+    #[serde_as(as = "Vec<RlpBytes>")]
+    ancestor_headers: Vec<AlloyConsensusHeader>,
+    base_fee_per_gas: u64,
+    blob_gas_used: Option<u64>,
+    excess_blob_gas: Option<u64>,
+    parent_beacon_block_root: Option<B256>,
+    taiko: TaikoGuestInput,
+}
+
+/// The tries of the gzipped inputs and version 1, as trees.
+#[derive(Default, Serialize, Deserialize)]
+struct TreeTries {
+    parent_state_trie: MptNode,
+    parent_storage: HashMap<Address, StorageEntry>,
+}
+
+/// The tries of version 2, in node arenas like the current version.
+#[serde_as]
+#[derive(Default, Serialize, Deserialize)]
+struct ArenaTries {
+    #[serde_as(as = "TrieArena")]
+    parent_state_trie: MptNode,
+    #[serde_as(as = "StorageArena")]
+    parent_storage: HashMap<Address, StorageEntry>,
+}
+
+/// The chain spec before the Taiko forks.
+#[derive(Default, Serialize, Deserialize)]
+struct LegacyChainSpec {
+    chain_id: ChainId,
+    max_spec_id: SpecId,
+    hard_forks: BTreeMap<SpecId, ForkCondition>,
+    eip_1559_constants: Eip1559Constants,
+    l1_contract: Option<Address>,
+    l2_contract: Option<Address>,
+    sgx_verifier_address: Option<Address>,
+    genesis_time: u64,
+    seconds_per_slot: u64,
+    block_gas_limit: Option<u64>,
+    anchor_gas_limit: u64,
+}
+
+impl From<TreeTries> for Tries {
+    fn from(tries: TreeTries) -> Self {
+        (tries.parent_state_trie, tries.parent_storage)
+    }
+}
+
+impl From<ArenaTries> for Tries {
+    fn from(tries: ArenaTries) -> Self {
+        (tries.parent_state_trie, tries.parent_storage)
+    }
+}
+
+impl<T: Into<Tries>> From<Input<T>> for GuestInput {
+    fn from(input: Input<T>) -> Self {
+        let spec = input.chain_spec;
+        let (parent_state_trie, parent_storage) = input.tries.into();
+        GuestInput {
+            network: input.network,
+            chain_spec: ChainSpec {
+                chain_id: spec.chain_id,
+                max_spec_id: spec.max_spec_id,
+                hard_forks: spec.hard_forks,
+                eip_1559_constants: spec.eip_1559_constants,
+                l1_contract: spec.l1_contract,
+                l2_contract: spec.l2_contract,
+                sgx_verifier_address: spec.sgx_verifier_address,
+                genesis_time: spec.genesis_time,
+                seconds_per_slot: spec.seconds_per_slot,
+                block_gas_limit: spec.block_gas_limit,
+                anchor_gas_limit: spec.anchor_gas_limit,
+                taiko_forks: get_network_spec(input.network).taiko_forks,
+            },
+            block_number: input.block_number,
+            gas_used: input.gas_used,
+            block_hash: input.block_hash,
+            parent_header: input.parent_header,
+            beneficiary: input.beneficiary,
+            gas_limit: input.gas_limit,
+            timestamp: input.timestamp,
+            extra_data: input.extra_data,
+            mix_hash: input.mix_hash,
+            withdrawals: input.withdrawals,
+            parent_state_trie,
+            parent_storage,
+            contracts: input.contracts,
+            ancestor_headers: input.ancestor_headers,
+            base_fee_per_gas: input.base_fee_per_gas,
+            blob_gas_used: input.blob_gas_used,
+            excess_blob_gas: input.excess_blob_gas,
+            parent_beacon_block_root: input.parent_beacon_block_root,
+            taiko: input.taiko,
+        }
+    }
+}
+
+/// An input of the given block gzipped like before the header, for the tests of the readers.
+#[cfg(test)]
+pub(crate) fn gzipped_input(block_number: u64, block_hash: B256, trie: MptNode) -> Vec<u8> {
+    use flate2::{write::GzEncoder, Compression};
+
+    let input = Input {
+        network: Network::TaikoA7,
+        block_number,
+        block_hash,
+        tries: TreeTries {
+            parent_state_trie: trie,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    bincode::serialize_into(&mut encoder, &input).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use raiko_lib::consts::TaikoFork;
+
+    use super::*;
+
+    #[test]
+    fn test_decode_arena_tries() {
+        let mut trie = MptNode::default();
+        trie.insert(&[0x11; 32], vec![0x22; 40]).unwrap();
+        let mut storage = HashMap::default();
+        storage.insert(Address::repeat_byte(1), (trie.clone(), vec![]));
+        let input = Input {
+            network: Network::TaikoA6,
+            block_number: 10,
+            tries: ArenaTries {
+                parent_state_trie: trie.clone(),
+                parent_storage: storage,
+            },
+            ..Default::default()
+        };
+
+        let decoded = decode(2, &bincode::serialize(&input).unwrap()[..]).unwrap();
+        assert_eq!(decoded.block_number, 10);
+        assert_eq!(decoded.parent_state_trie.hash(), trie.hash());
+        assert_eq!(decoded.parent_storage.len(), 1);
+        assert_eq!(
+            decoded.chain_spec.taiko_forks.keys().collect::<Vec<_>>(),
+            vec![&TaikoFork::Katla]
+        );
+        assert!(decode(3, &b""[..]).is_err());
+    }
+}
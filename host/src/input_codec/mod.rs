@@ -0,0 +1,191 @@
+//! Encoding of guest inputs on disk and over the wire.
+//!
+//! An encoded input starts with a magic and a format version, followed by the input in bincode
+//! in a zstd stream. The input is serialized straight into the encoder and deserialized straight
+//! from the decoder, so neither side holds the uncompressed bytes. The version is bumped whenever
+//! the serialization of the input changes. Inputs of older versions, and the gzipped bincode
+//! written before the header, are still read by [`decode_input`] and converted into the current
+//! input, see [legacy].
+
+use std::io::{self, Cursor, Read, Write};
+
+use flate2::read::GzDecoder;
+use raiko_lib::input::GuestInput;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    error::{HostError, HostResult},
+    metrics::observe_input_size,
+};
+
+mod legacy;
+
+#[cfg(test)]
+pub(crate) use legacy::gzipped_input;
+
+/// The magic every encoded input starts with.
+const MAGIC: [u8; 4] = *b"RKIN";
+
+/// The version of the format after the magic. Version 2 stores the tries in node arenas,
+/// version 3 the Taiko forks of the chain spec.
+const VERSION: u8 = 3;
+
+/// The zstd level, the default trades the compression against the speed.
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// The magic of the gzipped inputs written before the header.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Encode the value into the writer, observing its size under the target.
+pub fn encode<T: Serialize, W: Write>(value: &T, writer: W, target: &str) -> HostResult<()> {
+    let mut writer = CountingWriter::new(writer);
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&[VERSION])?;
+    let mut encoder = CountingWriter::new(zstd::Encoder::new(writer, COMPRESSION_LEVEL)?);
+    bincode::serialize_into(&mut encoder, value).map_err(|e| HostError::Anyhow(e.into()))?;
+    let serialized = encoder.count;
+    let mut writer = encoder.inner.finish()?;
+    writer.flush()?;
+    observe_input_size(target, serialized, writer.count);
+    Ok(())
+}
+
+/// Encode the value into a buffer.
+pub fn encode_to_vec<T: Serialize>(value: &T, target: &str) -> HostResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    encode(value, &mut bytes, target)?;
+    Ok(bytes)
+}
+
+/// Decode a value written by [`encode`] in the current version.
+pub fn decode<T: DeserializeOwned, R: Read>(mut reader: R) -> HostResult<T> {
+    let mut header = [0u8; MAGIC.len() + 1];
+    reader.read_exact(&mut header)?;
+    let version = version(&header)?;
+    if version != VERSION {
+        return Err(unsupported_version(version));
+    }
+    let decoder = zstd::Decoder::new(reader)?;
+    bincode::deserialize_from(decoder).map_err(|e| HostError::Anyhow(e.into()))
+}
+
+/// Decode an input written by [`encode`] in the current or an older version, or gzipped before
+/// the header. Returns the input and whether it was written in an older format.
+pub fn decode_input<R: Read>(mut reader: R) -> HostResult<(GuestInput, bool)> {
+    let mut header = [0u8; MAGIC.len() + 1];
+    reader.read_exact(&mut header)?;
+    if header.starts_with(&GZIP_MAGIC) {
+        let decoder = GzDecoder::new(Cursor::new(header).chain(reader));
+        return Ok((legacy::decode(1, decoder)?, true));
+    }
+    let version = version(&header)?;
+    if version > VERSION {
+        return Err(unsupported_version(version));
+    }
+    let decoder = zstd::Decoder::new(reader)?;
+    if version == VERSION {
+        let input = bincode::deserialize_from(decoder).map_err(|e| HostError::Anyhow(e.into()))?;
+        return Ok((input, false));
+    }
+    Ok((legacy::decode(version, decoder)?, true))
+}
+
+/// The format version of the header.
+fn version(header: &[u8; MAGIC.len() + 1]) -> HostResult<u8> {
+    if !header.starts_with(&MAGIC) {
+        return Err(HostError::Io(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Not an encoded input",
+        )));
+    }
+    Ok(header[MAGIC.len()])
+}
+
+fn unsupported_version(version: u8) -> HostError {
+    HostError::Io(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Unsupported input format version {version}"),
+    ))
+}
+
+/// A writer counting the bytes written through it.
+struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::B256;
+    use raiko_primitives::mpt::MptNode;
+
+    use super::*;
+
+    fn input() -> GuestInput {
+        GuestInput {
+            block_number: 10,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let bytes = encode_to_vec(&input(), "test").unwrap();
+        assert!(bytes.starts_with(&MAGIC));
+        let decoded: GuestInput = decode(&bytes[..]).unwrap();
+        assert_eq!(decoded.block_number, 10);
+    }
+
+    #[test]
+    fn test_decode_legacy() {
+        let (decoded, outdated) =
+            decode_input(&encode_to_vec(&input(), "test").unwrap()[..]).unwrap();
+        assert_eq!(decoded.block_number, 10);
+        assert!(!outdated);
+
+        let gzipped = gzipped_input(10, B256::repeat_byte(1), MptNode::default());
+        let (decoded, outdated) = decode_input(&gzipped[..]).unwrap();
+        assert_eq!(decoded.block_hash, B256::repeat_byte(1));
+        assert!(!decoded.chain_spec.taiko_forks.is_empty());
+        assert!(outdated);
+        // Only inputs of the current version are decoded as values.
+        assert!(decode::<GuestInput, _>(&gzipped[..]).is_err());
+
+        // Version 1 is the zstd stream of the gzipped format.
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(1);
+        let mut decoder = GzDecoder::new(&gzipped[..]);
+        zstd::stream::copy_encode(&mut decoder, &mut bytes, COMPRESSION_LEVEL).unwrap();
+        let (decoded, outdated) = decode_input(&bytes[..]).unwrap();
+        assert_eq!(decoded.block_number, 10);
+        assert!(outdated);
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let mut bytes = encode_to_vec(&input(), "test").unwrap();
+        bytes[MAGIC.len()] = VERSION + 1;
+        assert!(decode::<GuestInput, _>(&bytes[..]).is_err());
+        assert!(decode::<GuestInput, _>(&b"garbage"[..]).is_err());
+        assert!(decode::<GuestInput, _>(&b""[..]).is_err());
+        assert!(decode_input(&bytes[..]).is_err());
+    }
+}
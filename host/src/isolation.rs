@@ -0,0 +1,435 @@
+//! Proving in a child process of the host.
+//!
+//! The provers of `--isolate-provers` prove in a child process, `raiko-host prove` with the
+//! options of the host, given the input and the request in files. A segfault or an abort in the
+//! libraries of the prover only ends the child, the job fails with a [ProverCrash] with the exit
+//! code, the signal and the end of the error output of the child, which is stored with the job.
+//! Crashes are retried like other transient errors, in a new child. The provers of
+//! `--prover-daemons` prove in long lived children instead, see [crate::daemons].
+//!
+//! `job_limits` of the config file limits the memory, in MiB, and the threads of the proofs of
+//! a prover, e.g. `{"risc0": {"memory": 65536, "threads": 16}}`, which isolates the prover as
+//! well. The data segment of the child is limited with `ulimit -d` and the threads of its thread
+//! pools with `RAYON_NUM_THREADS` and `TOKIO_WORKER_THREADS`. A proof that runs out of memory
+//! fails with a crash that isn't retried, instead of taking down the host and the proofs next
+//! to it.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
+};
+
+use lazy_static::lazy_static;
+use raiko_lib::{
+    input::GuestInput,
+    prover::{Proof, ProverError},
+};
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    error::{HostError, HostResult},
+    gc, gpu, input_codec,
+    metrics::inc_prover_crash_count,
+    request::{ProofRequest, ProofType},
+};
+
+/// The prefix of the directories of the files of the jobs.
+const JOB_DIR_PREFIX: &str = "raiko-job-";
+
+/// The messages of a process that ran out of memory.
+const OUT_OF_MEMORY: [&str; 3] = [
+    "memory allocation of",
+    "Cannot allocate memory",
+    "out of memory",
+];
+
+/// How the child prints an error of its prover, the debug output of [HostError::GuestError].
+const PROVER_ERROR: &str = "Error: GuestError(";
+
+/// The exit code of the child when it returns an error from main, any other exit is a crash.
+const ERROR_EXIT_CODE: i32 = 1;
+
+/// The signal of the OOM killer.
+const SIGKILL: i32 = 9;
+
+/// How much of the error output of a failed job is kept in its error.
+pub(crate) const STDERR_TAIL: usize = 2000;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// The resources a proof of a prover may use.
+pub struct JobLimits {
+    /// The memory of the proof in MiB.
+    #[serde(default)]
+    pub memory: Option<u64>,
+    /// The threads of the thread pools of the prover.
+    #[serde(default)]
+    pub threads: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+/// How the child process of a proof crashed.
+pub struct ProverCrash {
+    /// The exit code of the child, if it exited.
+    pub exit_code: Option<i32>,
+    /// The signal the child was terminated by, e.g. 11 for a segfault.
+    pub signal: Option<i32>,
+    /// Whether the child ran out of memory.
+    pub out_of_memory: bool,
+    /// The end of the error output of the child.
+    pub stderr: String,
+}
+
+impl ProverCrash {
+    /// The crash of the child, `None` if it failed with an error of the host or the prover.
+    pub(crate) fn from_output(status: &ExitStatus, stderr: &str) -> Option<Self> {
+        let signal = signal(status);
+        let out_of_memory = is_exhausted(signal, stderr);
+        if !out_of_memory && signal.is_none() && status.code() == Some(ERROR_EXIT_CODE) {
+            return None;
+        }
+        Some(ProverCrash {
+            exit_code: status.code(),
+            signal,
+            out_of_memory,
+            stderr: tail(stderr).to_string(),
+        })
+    }
+
+    /// The reason of the crash in the metrics.
+    fn reason(&self) -> &'static str {
+        if self.out_of_memory {
+            "out_of_memory"
+        } else if self.signal.is_some() {
+            "signal"
+        } else {
+            "exit"
+        }
+    }
+}
+
+impl fmt::Display for ProverCrash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.signal, self.exit_code) {
+            (Some(signal), _) => write!(f, "killed by signal {signal}")?,
+            (None, Some(code)) => write!(f, "exited with code {code}")?,
+            (None, None) => write!(f, "terminated")?,
+        }
+        if self.out_of_memory {
+            write!(f, ", out of memory")?;
+        }
+        if !self.stderr.is_empty() {
+            write!(f, "\n{}", self.stderr)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Isolation {
+    /// The provers proving in a child process without limits.
+    provers: BTreeSet<String>,
+    /// The provers proving in prover daemons, see [crate::daemons].
+    daemons: BTreeSet<String>,
+    limits: BTreeMap<String, JobLimits>,
+}
+
+lazy_static! {
+    static ref ISOLATION: RwLock<Isolation> = RwLock::new(Isolation::default());
+}
+
+/// Numbers the files of the jobs of the host.
+static JOB_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Replace the isolated provers, the provers proving in daemons and their limits.
+pub fn configure(provers: Vec<String>, daemons: Vec<String>, limits: BTreeMap<String, JobLimits>) {
+    if !provers.is_empty() {
+        info!("Proving {provers:?} in a child process");
+    }
+    if !daemons.is_empty() {
+        info!("Proving {daemons:?} in prover daemons");
+    }
+    if !limits.is_empty() {
+        info!("Limiting the resources of the proofs of {limits:?}");
+    }
+    *ISOLATION.write().unwrap() = Isolation {
+        provers: provers.into_iter().collect(),
+        daemons: daemons.into_iter().collect(),
+        limits,
+    };
+}
+
+/// The limits of the proofs of the prover if it proves in a child process, `None` if it
+/// proves in the host.
+pub fn limits(proof_type: &ProofType) -> Option<JobLimits> {
+    let isolation = ISOLATION.read().unwrap();
+    let proof_type = proof_type.to_string();
+    match isolation.limits.get(&proof_type) {
+        Some(limits) => Some(limits.clone()),
+        None => (isolation.provers.contains(&proof_type)
+            || isolation.daemons.contains(&proof_type))
+        .then(JobLimits::default),
+    }
+}
+
+/// Whether the prover proves in prover daemons instead of a child process per proof.
+pub fn uses_daemons(proof_type: &ProofType) -> bool {
+    cfg!(unix)
+        && ISOLATION
+            .read()
+            .unwrap()
+            .daemons
+            .contains(&proof_type.to_string())
+}
+
+/// The directory of the files of a job of this host.
+pub(crate) fn job_dir() -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "{JOB_DIR_PREFIX}{}-{}",
+        std::process::id(),
+        JOB_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Remove the files of the jobs of hosts that are gone, e.g. after a crash of the host.
+/// Returns the number of bytes removed.
+pub fn remove_stale_jobs() -> u64 {
+    // Without procfs it can't be told whether the host of the files still runs.
+    let Ok(entries) = std::fs::read_dir(std::env::temp_dir()) else {
+        return 0;
+    };
+    if !Path::new("/proc/self").exists() {
+        return 0;
+    }
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(pid) = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(JOB_DIR_PREFIX))
+            .and_then(|rest| rest.split('-').next())
+            .filter(|pid| pid.parse::<u32>().is_ok())
+        else {
+            continue;
+        };
+        if Path::new("/proc").join(pid).exists() {
+            continue;
+        }
+        let (size, _) = gc::usage(&entry.path());
+        match std::fs::remove_dir_all(entry.path()) {
+            Ok(()) => {
+                info!(
+                    "Removed the files of a stale job {}",
+                    entry.path().display()
+                );
+                removed += size;
+            }
+            Err(e) => warn!(
+                "Could not remove the files of a stale job {}: {e}",
+                entry.path().display()
+            ),
+        }
+    }
+    removed
+}
+
+/// Generate the proof of the input in a child process with the limits.
+pub async fn generate_proof(
+    proof_request: &ProofRequest,
+    input: &GuestInput,
+    limits: &JobLimits,
+) -> HostResult<Proof> {
+    #[cfg(unix)]
+    if uses_daemons(&proof_request.proof_type) {
+        return crate::daemons::generate_proof(proof_request, input).await;
+    }
+    let dir = job_dir();
+    std::fs::create_dir_all(&dir)?;
+    let result = run_job(&dir, proof_request, input, limits).await;
+    if let Err(e) = std::fs::remove_dir_all(&dir) {
+        warn!(
+            "Could not remove the files of the job {}: {e}",
+            dir.display()
+        );
+    }
+    result
+}
+
+async fn run_job(
+    dir: &Path,
+    proof_request: &ProofRequest,
+    input: &GuestInput,
+    limits: &JobLimits,
+) -> HostResult<Proof> {
+    let input_path = dir.join("input.bin.zst");
+    let request_path = dir.join("request.json");
+    let output_path = dir.join("proof.json");
+    input_codec::encode(input, std::fs::File::create(&input_path)?, "job")?;
+    std::fs::write(&request_path, serde_json::to_vec(proof_request)?)?;
+
+    let mut command = child_command(limits, gpu::cuda_device())?;
+    command
+        .arg("prove")
+        .arg(format!("--input={}", input_path.display()))
+        .arg(format!("--request={}", request_path.display()))
+        .arg(format!("--output={}", output_path.display()));
+    let output = command.spawn()?.wait_with_output().await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if let Some(crash) = ProverCrash::from_output(&output.status, &stderr) {
+            return Err(crashed(proof_request, crash));
+        }
+        // The prover failed in the child, which returns its error from main.
+        if stderr.contains(PROVER_ERROR) {
+            return Err(HostError::GuestError(ProverError::GuestError(
+                tail(&stderr).to_string(),
+            )));
+        }
+        return Err(HostError::Anyhow(anyhow::anyhow!(
+            "The {} proof of block {} failed: {}",
+            proof_request.proof_type,
+            proof_request.block_number,
+            tail(&stderr)
+        )));
+    }
+    Ok(serde_json::from_slice(&std::fs::read(&output_path)?)?)
+}
+
+/// The command of a child process of the host with the limits, on the CUDA device. The child
+/// takes the options of the host, followed by its command.
+pub(crate) fn child_command(limits: &JobLimits, device: Option<u32>) -> HostResult<Command> {
+    let exe = std::env::current_exe()?;
+    let mut command = match limits.memory {
+        Some(memory) => {
+            let mut command = Command::new("sh");
+            command
+                .arg("-c")
+                .arg(format!("ulimit -d {} && exec \"$@\"", memory * 1024))
+                .arg("raiko-job")
+                .arg(exe);
+            command
+        }
+        None => Command::new(exe),
+    };
+    command
+        .args(std::env::args_os().skip(1))
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(threads) = limits.threads {
+        command
+            .env("RAYON_NUM_THREADS", threads.to_string())
+            .env("TOKIO_WORKER_THREADS", threads.to_string());
+    }
+    if let Some(device) = device {
+        command.env("CUDA_VISIBLE_DEVICES", device.to_string());
+    }
+    Ok(command)
+}
+
+/// Log and count the crash of the prover of the request.
+pub(crate) fn crashed(proof_request: &ProofRequest, crash: ProverCrash) -> HostError {
+    warn!(
+        "The {} prover crashed proving block {}: {crash}",
+        proof_request.proof_type, proof_request.block_number
+    );
+    inc_prover_crash_count(&proof_request.proof_type, crash.reason());
+    HostError::ProverCrashed(crash)
+}
+
+/// The signal the process was terminated by.
+fn signal(status: &ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        std::os::unix::process::ExitStatusExt::signal(status)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+/// Whether the job failed for running out of memory, killed by the OOM killer or aborted for
+/// an allocation over its limit.
+fn is_exhausted(signal: Option<i32>, stderr: &str) -> bool {
+    signal == Some(SIGKILL) || OUT_OF_MEMORY.iter().any(|message| stderr.contains(message))
+}
+
+/// The end of the error output, which has the error of the job.
+pub(crate) fn tail(stderr: &str) -> &str {
+    let stderr = stderr.trim_end();
+    let mut start = stderr.len().saturating_sub(STDERR_TAIL);
+    while !stderr.is_char_boundary(start) {
+        start += 1;
+    }
+    &stderr[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_exhausted() {
+        assert!(is_exhausted(
+            Some(6),
+            "memory allocation of 8589934592 bytes failed\n"
+        ));
+        assert!(is_exhausted(Some(SIGKILL), ""));
+        assert!(!is_exhausted(
+            None,
+            "Error: There was a error with a guest prover"
+        ));
+        assert_eq!(tail("error\n"), "error");
+        assert_eq!(tail(&"é".repeat(STDERR_TAIL)).len(), STDERR_TAIL);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_crash_from_output() {
+        use std::os::unix::process::ExitStatusExt;
+
+        // A segfault, raw wait statuses have the signal in the low bits.
+        let crash = ProverCrash::from_output(&ExitStatus::from_raw(11), "").unwrap();
+        assert_eq!(crash.signal, Some(11));
+        assert!(!crash.out_of_memory);
+        assert_eq!(crash.reason(), "signal");
+        // A panic exits with 101.
+        let crash = ProverCrash::from_output(&ExitStatus::from_raw(101 << 8), "panicked").unwrap();
+        assert_eq!(crash.exit_code, Some(101));
+        assert_eq!(crash.to_string(), "exited with code 101\npanicked");
+        // An error returned from main is not a crash.
+        assert_eq!(
+            ProverCrash::from_output(&ExitStatus::from_raw(1 << 8), "Error: Anyhow"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        configure(
+            vec!["sp1".to_string()],
+            Vec::new(),
+            BTreeMap::from([(
+                "risc0".to_string(),
+                JobLimits {
+                    memory: Some(1024),
+                    threads: None,
+                },
+            )]),
+        );
+        assert_eq!(limits(&ProofType::Risc0).unwrap().memory, Some(1024));
+        assert_eq!(limits(&ProofType::Sp1), Some(JobLimits::default()));
+        assert_eq!(limits(&ProofType::Native), None);
+        configure(Vec::new(), Vec::new(), BTreeMap::new());
+    }
+}
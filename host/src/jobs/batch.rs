@@ -0,0 +1,265 @@
+//! Batches of jobs proving a range of blocks.
+//!
+//! A batch submits a job per block, so the blocks go through the preflight budget and the
+//! prover slots like any other job and can be followed and cancelled one by one. With
+//! `aggregate`, the proofs of the blocks are aggregated into a single proof once all of them
+//! succeeded, see [raiko_lib::aggregate]. Batches are kept in memory, the jobs of their blocks
+//! are persisted like other jobs.
+
+use std::sync::atomic::Ordering;
+
+use raiko_lib::prover::Proof;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::broadcast::error::RecvError, task::AbortHandle};
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    error::{HostError, HostResult},
+    jobs::{
+        progress::ProgressEvent, unix_timestamp, CurrentRequest, Job, JobId, JobManager, JobStatus,
+        JobSummary,
+    },
+    rate_limit::InFlightPermit,
+    request::ProofRequest,
+};
+
+/// The identifier of a batch.
+pub type BatchId = u64;
+
+/// A batch of jobs, see [JobManager::submit_batch].
+#[derive(Debug)]
+pub(super) struct Batch {
+    /// The jobs of the blocks, in block order.
+    jobs: Vec<JobId>,
+    aggregate: bool,
+    status: JobStatus,
+    proof: Option<Proof>,
+    error: Option<String>,
+    task: Option<AbortHandle>,
+    created_at: u64,
+    updated_at: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// The state of a batch and of the jobs of its blocks.
+pub struct BatchSummary {
+    /// The batch identifier.
+    pub id: BatchId,
+    /// `running` until the jobs of all blocks finished and, with `aggregate`, their proofs are
+    /// aggregated. `failed` once a job did not succeed.
+    pub status: JobStatus,
+    /// Whether the proofs of the blocks are aggregated into a single proof.
+    pub aggregate: bool,
+    /// The jobs of the blocks, in block order.
+    pub jobs: Vec<JobSummary>,
+    /// The proofs of the blocks in block order, set once the batch succeeded without
+    /// `aggregate`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Vec<Value>>)]
+    pub proofs: Option<Vec<Proof>>,
+    /// The aggregated proof, set once the batch succeeded with `aggregate`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Value>)]
+    pub proof: Option<Proof>,
+    /// The error, set once the batch failed.
+    pub error: Option<String>,
+    /// Unix timestamp of the batch creation.
+    pub created_at: u64,
+    /// Unix timestamp of the last status change.
+    pub updated_at: u64,
+}
+
+impl JobManager {
+    /// Submit a job for each of the proof requests, given in block order, and, with
+    /// `aggregate`, aggregate the proofs of the blocks with the prover of the first request
+    /// once all of them succeeded. The in-flight slot the requests were admitted with is held
+    /// until the batch finished.
+    pub fn submit_batch(
+        &self,
+        requests: Vec<ProofRequest>,
+        aggregate: bool,
+        permit: Option<InFlightPermit>,
+    ) -> HostResult<BatchSummary> {
+        let aggregation = requests.first().cloned().filter(|_| aggregate);
+        let mut jobs = Vec::with_capacity(requests.len());
+        for request in requests {
+            match self.submit_admitted(request, permit.clone()) {
+                Ok(summary) => jobs.push(summary.id),
+                Err(e) => {
+                    // Don't leave the first blocks of a batch that was never created.
+                    for job in jobs {
+                        let _ = self.cancel(job);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        let id = self.next_batch_id.fetch_add(1, Ordering::SeqCst);
+        let now = unix_timestamp();
+        info!("Created batch {id} of jobs {jobs:?}");
+        // Hold the lock until the handle is stored, so a fast batch can't finish before that.
+        let mut batches = self.batches.lock().unwrap();
+        let manager = self.clone();
+        let task = tokio::spawn({
+            let jobs = jobs.clone();
+            async move {
+                let _permit = permit;
+                manager.run_batch(id, jobs, aggregation).await
+            }
+        });
+        batches.insert(
+            id,
+            Batch {
+                jobs,
+                aggregate,
+                status: JobStatus::Running,
+                proof: None,
+                error: None,
+                task: Some(task.abort_handle()),
+                created_at: now,
+                updated_at: now,
+            },
+        );
+        drop(batches);
+        self.batch(id)
+    }
+
+    /// Get the batch with the given id, with the current state of its jobs.
+    pub fn batch(&self, id: BatchId) -> HostResult<BatchSummary> {
+        let (jobs, aggregate, status, proof, error, created_at, updated_at) = {
+            let batches = self.batches.lock().unwrap();
+            let batch = batches.get(&id).ok_or(HostError::BatchNotFound(id))?;
+            (
+                batch.jobs.clone(),
+                batch.aggregate,
+                batch.status,
+                batch.proof.clone(),
+                batch.error.clone(),
+                batch.created_at,
+                batch.updated_at,
+            )
+        };
+        let jobs = jobs
+            .into_iter()
+            .map(|job| self.get(job))
+            .collect::<HostResult<Vec<_>>>()?;
+        let proofs = (status == JobStatus::Success && !aggregate)
+            .then(|| jobs.iter().filter_map(|job| job.proof.clone()).collect());
+        Ok(BatchSummary {
+            id,
+            status,
+            aggregate,
+            jobs: jobs.iter().map(JobSummary::from).collect(),
+            proofs,
+            proof,
+            error,
+            created_at,
+            updated_at,
+        })
+    }
+
+    /// Cancel the batch with the given id and its unfinished jobs.
+    pub fn cancel_batch(&self, id: BatchId) -> HostResult<BatchSummary> {
+        let jobs = {
+            let mut batches = self.batches.lock().unwrap();
+            let batch = batches.get_mut(&id).ok_or(HostError::BatchNotFound(id))?;
+            if batch.status.is_terminal() {
+                return Err(HostError::BatchAlreadyFinished(id));
+            }
+            if let Some(task) = batch.task.take() {
+                task.abort();
+            }
+            batch.status = JobStatus::Cancelled;
+            batch.updated_at = unix_timestamp();
+            batch.jobs.clone()
+        };
+        for job in jobs {
+            match self.cancel(job) {
+                Ok(_) | Err(HostError::JobAlreadyFinished(_)) => {}
+                Err(e) => warn!("Could not cancel job {job} of batch {id}: {e}"),
+            }
+        }
+        info!("Cancelled batch {id}");
+        self.batch(id)
+    }
+
+    /// Wait for the jobs of the batch and aggregate their proofs with the prover of
+    /// `aggregation`, if any.
+    async fn run_batch(&self, id: BatchId, jobs: Vec<JobId>, aggregation: Option<ProofRequest>) {
+        let result = async {
+            let mut proofs = Vec::with_capacity(jobs.len());
+            for job in jobs {
+                let job = self.wait(job).await.map_err(|e| e.to_string())?;
+                match (job.status, job.proof) {
+                    (JobStatus::Success, Some(proof)) => proofs.push(proof),
+                    (status, _) => {
+                        return Err(format!(
+                            "Job {} of block {} is {status}: {}",
+                            job.id,
+                            job.request.block_number,
+                            job.error.unwrap_or_default()
+                        ))
+                    }
+                }
+            }
+            match &aggregation {
+                Some(request) => self
+                    .aggregate(request, proofs)
+                    .await
+                    .map(Some)
+                    .map_err(|e| e.to_string()),
+                None => Ok(None),
+            }
+        }
+        .await;
+
+        let mut batches = self.batches.lock().unwrap();
+        let Some(batch) = batches
+            .get_mut(&id)
+            .filter(|batch| !batch.status.is_terminal())
+        else {
+            return;
+        };
+        batch.task = None;
+        batch.updated_at = unix_timestamp();
+        match result {
+            Ok(proof) => {
+                info!("Batch {id} succeeded");
+                batch.status = JobStatus::Success;
+                batch.proof = proof;
+            }
+            Err(e) => {
+                error!("Batch {id} failed: {e}");
+                batch.status = JobStatus::Failed;
+                batch.error = Some(e);
+            }
+        }
+    }
+
+    /// Wait for the job with the given id to finish.
+    async fn wait(&self, id: JobId) -> HostResult<Job> {
+        let (_, receiver) = self.subscribe(id)?;
+        if let Some(mut receiver) = receiver {
+            loop {
+                match receiver.recv().await {
+                    Ok(ProgressEvent::Finished { .. }) | Err(RecvError::Closed) => break,
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                }
+            }
+        }
+        self.get(id)
+    }
+
+    /// Aggregate the proofs of a batch in a prover slot of the prover of the request.
+    async fn aggregate(&self, request: &ProofRequest, proofs: Vec<Proof>) -> HostResult<Proof> {
+        // The aggregation runs in the host, which can't pick the device of the slot.
+        let _slot = self
+            .acquire(&request.proof_type, request.priority, request.deadline)
+            .await?;
+        let prover_args = serde_json::to_value(&request.prover_args)?;
+        let _request = CurrentRequest::start();
+        request.proof_type.aggregate(proofs, &prover_args).await
+    }
+}
@@ -0,0 +1,228 @@
+//! The captured logs of the jobs.
+//!
+//! Jobs run in a span carrying their id. The [JobLogLayer] captures the events logged inside
+//! of it, so the logs of a job can be read at `GET /v2/jobs/{id}/log` instead of searching the
+//! logs of the host for its trace id. The logs of the last [MAX_JOBS] jobs are kept in memory,
+//! at most the last [MAX_LINES] lines per job.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::{Debug, Write},
+    sync::Mutex,
+};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id},
+    Event, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+use utoipa::ToSchema;
+
+use crate::{clock::unix_timestamp_millis, jobs::JobId};
+
+/// The number of jobs whose logs are kept.
+pub const MAX_JOBS: usize = 1000;
+
+/// The number of lines kept per job.
+pub const MAX_LINES: usize = 10_000;
+
+/// The number of lines a subscriber can fall behind before it skips lines.
+const LOG_CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+/// A line logged by a job.
+pub struct JobLogLine {
+    /// Unix timestamp of the line in milliseconds.
+    pub timestamp: u64,
+    pub level: String,
+    /// The module the line was logged in.
+    pub target: String,
+    /// The message, followed by the fields of the line.
+    pub message: String,
+}
+
+#[derive(Debug)]
+struct JobLog {
+    lines: VecDeque<JobLogLine>,
+    /// Unset once the job finished.
+    sender: Option<broadcast::Sender<JobLogLine>>,
+}
+
+impl JobLog {
+    fn new() -> Self {
+        Self {
+            lines: VecDeque::new(),
+            sender: Some(broadcast::channel(LOG_CHANNEL_CAPACITY).0),
+        }
+    }
+}
+
+lazy_static! {
+    static ref LOGS: Mutex<BTreeMap<JobId, JobLog>> = Mutex::new(BTreeMap::new());
+}
+
+/// Get the log of the job, adding it if it is new. The logs of the oldest jobs are dropped
+/// to keep at most [MAX_JOBS].
+fn job_log(logs: &mut BTreeMap<JobId, JobLog>, id: JobId) -> &mut JobLog {
+    if !logs.contains_key(&id) {
+        while logs.len() >= MAX_JOBS {
+            logs.pop_first();
+        }
+    }
+    logs.entry(id).or_insert_with(JobLog::new)
+}
+
+/// Add a line to the log of the job.
+pub fn capture(id: JobId, line: JobLogLine) {
+    let mut logs = LOGS.lock().unwrap_or_else(|e| e.into_inner());
+    let log = job_log(&mut logs, id);
+    if log.lines.len() >= MAX_LINES {
+        log.lines.pop_front();
+    }
+    if let Some(sender) = &log.sender {
+        let _ = sender.send(line.clone());
+    }
+    log.lines.push_back(line);
+}
+
+/// Get the lines logged by the job so far.
+pub fn lines(id: JobId) -> Vec<JobLogLine> {
+    LOGS.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&id)
+        .map(|log| log.lines.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Get the lines logged by the unfinished job so far, and a receiver for the lines it logs
+/// until it finishes.
+pub fn subscribe(id: JobId) -> (Vec<JobLogLine>, Option<broadcast::Receiver<JobLogLine>>) {
+    let mut logs = LOGS.lock().unwrap_or_else(|e| e.into_inner());
+    let log = job_log(&mut logs, id);
+    (
+        log.lines.iter().cloned().collect(),
+        log.sender.as_ref().map(|sender| sender.subscribe()),
+    )
+}
+
+/// Close the log of the finished job, which ends the streams of its subscribers.
+pub fn close(id: JobId) {
+    job_log(&mut LOGS.lock().unwrap_or_else(|e| e.into_inner()), id).sender = None;
+}
+
+/// The id of the job a span belongs to, kept in the extensions of the span.
+struct JobSpan(JobId);
+
+/// Finds the `job_id` field of a span.
+struct JobIdVisitor(Option<JobId>);
+
+impl Visit for JobIdVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "job_id" {
+            self.0 = Some(value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn Debug) {}
+}
+
+/// Formats the message and the fields of an event.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={value}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+}
+
+/// The layer capturing the events logged in the span of a job.
+pub struct JobLogLayer;
+
+impl<S> Layer<S> for JobLogLayer
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = JobIdVisitor(None);
+        attrs.record(&mut visitor);
+        if let (Some(job_id), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(JobSpan(job_id));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(job_id) = ctx.event_scope(event).and_then(|scope| {
+            scope
+                .from_root()
+                .find_map(|span| span.extensions().get::<JobSpan>().map(|job| job.0))
+        }) else {
+            return;
+        };
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        capture(
+            job_id,
+            JobLogLine {
+                timestamp: unix_timestamp_millis(),
+                level: metadata.level().to_string(),
+                target: metadata.target().to_string(),
+                message: visitor.message + &visitor.fields,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::{info, info_span, subscriber::with_default};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    #[test]
+    fn test_capture() {
+        let subscriber = tracing_subscriber::registry().with(JobLogLayer);
+        with_default(subscriber, || {
+            info!("Not in a job");
+            let _span = info_span!("job", job_id = 7001u64).entered();
+            let _inner = info_span!("preflight").entered();
+            info!(block = 10, "Preparing the input");
+        });
+        let (captured, receiver) = subscribe(7001);
+        assert_eq!(captured.len(), 1);
+        assert_eq!(captured[0].message, "Preparing the input block=10");
+        assert_eq!(captured[0].level, "INFO");
+
+        let mut receiver = receiver.unwrap();
+        let line = JobLogLine {
+            message: "Proving".to_string(),
+            ..captured[0].clone()
+        };
+        capture(7001, line.clone());
+        assert_eq!(receiver.try_recv().unwrap(), line);
+        close(7001);
+        assert!(subscribe(7001).1.is_none());
+        assert_eq!(lines(7001).len(), 2);
+    }
+}
@@ -0,0 +1,1056 @@
+//! Asynchronous proof jobs.
+//!
+//! Proof requests submitted through the v2 API are tracked as jobs. A job starts out as
+//! [`JobStatus::Queued`], moves to [`JobStatus::Running`] once a prover slot is available and
+//! ends in one of the terminal states. The prover slots are handed out by the [`Scheduler`].
+//!
+//! Jobs run as a pipeline of two stages with their own budget: the input of a job is prepared
+//! by the preflight, which is mostly waiting on the nodes, before it waits for a prover slot.
+//! So the inputs of the next blocks are prepared while the provers are busy with the current
+//! ones. A job holds its preflight permit until it got a prover slot, so at most
+//! `preflight_concurrency` inputs are kept in memory ahead of the provers.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use raiko_lib::{input::GuestInput, prover::Proof};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{broadcast, Semaphore},
+    task::AbortHandle,
+};
+use tracing::{error, info, info_span, warn, Instrument};
+use utoipa::ToSchema;
+
+pub use crate::jobs::{
+    batch::{BatchId, BatchSummary},
+    scheduler::{ProverSlot, Scheduler},
+    status::JobStatus,
+};
+use crate::{
+    artifacts, callback,
+    chain_registry::get_chain,
+    clock::unix_timestamp,
+    error::{HostError, HostResult},
+    estimate::proving_time,
+    execution::{fetch_block_input, prove_with_input},
+    finality, gpu,
+    input_cache::InputCache,
+    isolation::ProverCrash,
+    jobs::progress::{ProgressEvent, ProgressSender, PROGRESS_CHANNEL_CAPACITY},
+    messaging,
+    metrics::{
+        dec_current_req, inc_current_req, inc_rate_limited_count, inc_shutdown_job_count,
+        observe_deadline_remaining, observe_job_retries,
+    },
+    proof_cache::ProofCache,
+    queue::TaskStore,
+    rate_limit::InFlightPermit,
+    relayer::{Relayer, Submission},
+    reorgs::ProvenBlock,
+    request::{ProofRequest, ProofType},
+    retry::{RetryConfig, RetryPolicy},
+    trace::{current_trace_id, new_trace_id, with_trace_id},
+    upload::{Upload, Uploader},
+};
+
+mod batch;
+pub mod logs;
+pub mod progress;
+mod scheduler;
+mod status;
+
+/// The identifier of a proof job.
+pub type JobId = u64;
+
+/// How often [JobManager::drain] checks whether the running jobs finished.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The max length of the error of a job in a [JobRecord].
+const MAX_ERROR_SUMMARY_LEN: usize = 200;
+
+/// The `Retry-After` of the submissions rejected by a full queue before a job was proven.
+const DEFAULT_RETRY_AFTER: u64 = 60;
+
+/// The max `Retry-After` of the submissions rejected by a full queue.
+const MAX_RETRY_AFTER: u64 = 3600;
+
+/// The weight of the last job in the moving average of the proving time.
+const PROVING_TIME_WEIGHT: f64 = 0.2;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+/// The seconds a job spent in each phase, set once the phase is done. Include the retries.
+pub struct JobPhases {
+    /// Preparing the input, including the wait for a preflight permit.
+    pub preflight: Option<f64>,
+    /// Waiting for a prover slot with the input.
+    pub waiting: Option<f64>,
+    /// Generating the proof.
+    pub proving: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// A proof job together with its result.
+pub struct Job {
+    /// The job identifier.
+    pub id: JobId,
+    /// The proof request this job was created for.
+    pub request: ProofRequest,
+    /// The current state of the job.
+    pub status: JobStatus,
+    /// The generated proof, set once the job succeeded.
+    pub proof: Option<Proof>,
+    /// The error message, set once the job failed.
+    pub error: Option<String>,
+    /// How the child process of the prover crashed, set once the job failed for it.
+    #[serde(default)]
+    pub crash: Option<ProverCrash>,
+    /// The number of times the job was retried after a transient failure.
+    #[serde(default)]
+    pub retries: u32,
+    /// The submission of the proof to L1, set once the relayer picked it up.
+    #[serde(default)]
+    pub submission: Option<Submission>,
+    /// The objects the proof was uploaded to, set once it was uploaded.
+    #[serde(default)]
+    pub upload: Option<Upload>,
+    /// The trace id of the request that submitted the job, see [crate::trace].
+    #[serde(default)]
+    pub trace_id: String,
+    /// The time the job spent in each phase.
+    #[serde(default)]
+    pub phases: JobPhases,
+    /// Unix timestamp of the job creation.
+    pub created_at: u64,
+    /// Unix timestamp of the last status change.
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// The status report of a proof job, without the proof itself.
+pub struct JobSummary {
+    /// The job identifier.
+    pub id: JobId,
+    /// The current state of the job.
+    pub status: JobStatus,
+    /// The error message, set once the job failed.
+    pub error: Option<String>,
+    /// The number of times the job was retried after a transient failure.
+    pub retries: u32,
+    /// The submission of the proof to L1, set once the relayer picked it up.
+    pub submission: Option<Submission>,
+    /// The objects the proof was uploaded to, set once it was uploaded.
+    pub upload: Option<Upload>,
+    /// The trace id of the request that submitted the job, to find its logs by.
+    pub trace_id: String,
+    /// The position of the job in the queue when it was submitted, 1 for the next job to
+    /// start. Only set in the response to the submission.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_position: Option<usize>,
+    /// Unix timestamp of the job creation.
+    pub created_at: u64,
+    /// Unix timestamp of the last status change.
+    pub updated_at: u64,
+}
+
+impl Job {
+    /// The job with its request [redacted](ProofRequest::redacted), to return it to clients.
+    pub fn redacted(&self) -> Self {
+        Self {
+            request: self.request.redacted(),
+            ..self.clone()
+        }
+    }
+}
+
+impl From<&Job> for JobSummary {
+    fn from(job: &Job) -> Self {
+        Self {
+            id: job.id,
+            status: job.status,
+            error: job.error.clone(),
+            retries: job.retries,
+            submission: job.submission.clone(),
+            upload: job.upload.clone(),
+            trace_id: job.trace_id.clone(),
+            queue_position: None,
+            created_at: job.created_at,
+            updated_at: job.updated_at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// A job in the list of recent jobs, without the request and the proof.
+pub struct JobRecord {
+    /// The job identifier.
+    pub id: JobId,
+    /// The current state of the job.
+    pub status: JobStatus,
+    pub network: String,
+    pub block_number: u64,
+    pub proof_type: ProofType,
+    /// The number of times the job was retried after a transient failure.
+    pub retries: u32,
+    /// The first line of the error message, set once the job failed.
+    pub error: Option<String>,
+    /// How the child process of the prover crashed, set once the job failed for it.
+    pub crash: Option<ProverCrash>,
+    /// The trace id of the request that submitted the job, to find its logs by.
+    pub trace_id: String,
+    /// The time the job spent in each phase.
+    pub phases: JobPhases,
+    /// Unix timestamp of the job creation.
+    pub created_at: u64,
+    /// Unix timestamp of the last status change.
+    pub updated_at: u64,
+}
+
+impl From<&Job> for JobRecord {
+    fn from(job: &Job) -> Self {
+        Self {
+            id: job.id,
+            status: job.status,
+            network: job.request.network.clone(),
+            block_number: job.request.block_number,
+            proof_type: job.request.proof_type.clone(),
+            retries: job.retries,
+            error: job.error.as_deref().map(error_summary),
+            crash: job.crash.clone(),
+            trace_id: job.trace_id.clone(),
+            phases: job.phases.clone(),
+            created_at: job.created_at,
+            updated_at: job.updated_at,
+        }
+    }
+}
+
+/// The seconds until `excess` jobs are proven by `concurrency` provers, which take
+/// `proving_time` seconds per job on average.
+fn retry_after(excess: usize, concurrency: usize, proving_time: Option<f64>) -> u64 {
+    let Some(proving_time) = proving_time else {
+        return DEFAULT_RETRY_AFTER;
+    };
+    let rounds = excess.div_ceil(concurrency.max(1));
+    ((proving_time * rounds as f64).ceil() as u64).clamp(1, MAX_RETRY_AFTER)
+}
+
+/// The first line of the error, cut to [MAX_ERROR_SUMMARY_LEN] characters.
+fn error_summary(error: &str) -> String {
+    let line = error.lines().next().unwrap_or_default();
+    match line.char_indices().nth(MAX_ERROR_SUMMARY_LEN) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// The jobs at the end of [JobManager::drain].
+pub struct DrainReport {
+    /// The running jobs that finished within the drain timeout.
+    pub drained: usize,
+    /// The running jobs that were aborted at the drain timeout.
+    pub aborted: usize,
+    /// The jobs left for the next start of the host, including the aborted ones.
+    pub queued: usize,
+}
+
+/// Keeps track of all proof jobs and runs them in the background.
+#[derive(Clone, Debug)]
+pub struct JobManager {
+    store: Arc<Mutex<Box<dyn TaskStore>>>,
+    next_id: Arc<AtomicU64>,
+    scheduler: Scheduler,
+    preflights: Arc<Semaphore>,
+    retry: RetryConfig,
+    input_cache: InputCache,
+    proof_cache: ProofCache,
+    relayer: Option<Relayer>,
+    uploader: Option<Uploader>,
+    progress: Arc<Mutex<HashMap<JobId, broadcast::Sender<ProgressEvent>>>>,
+    tasks: Arc<Mutex<HashMap<JobId, AbortHandle>>>,
+    /// The blocks of the unfinished jobs with a prepared input, checked for reorgs by
+    /// [crate::reorgs].
+    blocks: Arc<Mutex<HashMap<JobId, ProvenBlock>>>,
+    /// The number of jobs preparing their input or waiting for a prover slot.
+    queued: Arc<AtomicUsize>,
+    /// Proof requests are rejected once this many jobs are queued, see
+    /// [JobManager::check_queue].
+    max_queue_depth: Arc<Mutex<Option<usize>>>,
+    /// The moving average of the seconds it takes to prove a job.
+    proving_time: Arc<Mutex<Option<f64>>>,
+    next_batch_id: Arc<AtomicU64>,
+    batches: Arc<Mutex<HashMap<BatchId, batch::Batch>>>,
+}
+
+/// A job counted as queued until dropped, once it got its prover slot or stopped.
+struct QueuedJob(Arc<AtomicUsize>);
+
+impl QueuedJob {
+    fn new(queued: &Arc<AtomicUsize>) -> Self {
+        queued.fetch_add(1, Ordering::SeqCst);
+        Self(queued.clone())
+    }
+}
+
+impl Drop for QueuedJob {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl JobManager {
+    /// Create a new job manager running at most `concurrency_limit` jobs at the same time,
+    /// and at most the given number of jobs per prover. Jobs due within `deadline_margin` are
+    /// run first. The inputs of at most `preflight_concurrency` jobs are prepared ahead of
+    /// them. The proofs are uploaded with the uploader and submitted to L1 with the relayer, if
+    /// any.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        concurrency_limit: usize,
+        prover_limits: &BTreeMap<String, usize>,
+        preflight_concurrency: usize,
+        deadline_margin: Duration,
+        retry: RetryConfig,
+        input_cache: InputCache,
+        proof_cache: ProofCache,
+        relayer: Option<Relayer>,
+        uploader: Option<Uploader>,
+        store: Box<dyn TaskStore>,
+    ) -> HostResult<Self> {
+        let next_id = store.list()?.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        Ok(Self {
+            store: Arc::new(Mutex::new(store)),
+            next_id: Arc::new(AtomicU64::new(next_id)),
+            scheduler: Scheduler::new(concurrency_limit, prover_limits, deadline_margin),
+            preflights: Arc::new(Semaphore::new(preflight_concurrency.max(1))),
+            retry,
+            input_cache,
+            proof_cache,
+            relayer,
+            uploader,
+            progress: Default::default(),
+            tasks: Default::default(),
+            blocks: Default::default(),
+            queued: Default::default(),
+            max_queue_depth: Default::default(),
+            proving_time: Default::default(),
+            next_batch_id: Arc::new(AtomicU64::new(1)),
+            batches: Default::default(),
+        })
+    }
+
+    /// Restart all jobs that were not finished when the host was stopped.
+    ///
+    /// Jobs that were running are moved back to the queue and proven from the start.
+    pub fn resume(&self) -> HostResult<()> {
+        let unfinished = self
+            .store
+            .lock()
+            .unwrap()
+            .list()?
+            .into_iter()
+            .filter(|job| !job.status.is_terminal())
+            .collect::<Vec<_>>();
+
+        for job in unfinished {
+            info!(
+                "Resuming {} job {} for block {} on {}",
+                job.status, job.id, job.request.block_number, job.request.network
+            );
+            if job.status == JobStatus::Running {
+                self.transition(job.id, JobStatus::Queued);
+            }
+            // Jobs stored before trace ids were added get a new one.
+            let trace_id = Some(job.trace_id)
+                .filter(|trace_id| !trace_id.is_empty())
+                .unwrap_or_else(new_trace_id);
+            self.spawn(job.id, job.request, trace_id, None);
+        }
+        Ok(())
+    }
+
+    /// Queue a new job for the given proof request and start it as soon as a prover slot is
+    /// available.
+    pub fn submit(&self, request: ProofRequest) -> HostResult<JobSummary> {
+        self.submit_admitted(request, None)
+    }
+
+    /// Queue a new job like [JobManager::submit], holding the in-flight slot its request was
+    /// admitted with until the job finished.
+    pub fn submit_admitted(
+        &self,
+        request: ProofRequest,
+        permit: Option<InFlightPermit>,
+    ) -> HostResult<JobSummary> {
+        if self.is_draining() {
+            return Err(HostError::ShuttingDown);
+        }
+        let now = unix_timestamp();
+        let job = Job {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            request,
+            status: JobStatus::Queued,
+            proof: None,
+            error: None,
+            crash: None,
+            retries: 0,
+            submission: None,
+            upload: None,
+            trace_id: current_trace_id().unwrap_or_else(new_trace_id),
+            phases: JobPhases::default(),
+            created_at: now,
+            updated_at: now,
+        };
+        let summary = JobSummary {
+            queue_position: Some(self.queue_depth() + 1),
+            ..JobSummary::from(&job)
+        };
+        info!(
+            "Queued job {} for block {} on {}",
+            job.id, job.request.block_number, job.request.network
+        );
+
+        self.store.lock().unwrap().put(&job)?;
+        messaging::publish(&job);
+
+        self.spawn(job.id, job.request, job.trace_id, permit);
+
+        Ok(summary)
+    }
+
+    /// Wait for a slot to generate a proof with the given prover in. Requests close to their
+    /// deadline and requests with a higher priority are served first.
+    ///
+    /// Used for proof requests that are not tracked as jobs, so that they count against the
+    /// same limits.
+    pub async fn acquire(
+        &self,
+        proof_type: &ProofType,
+        priority: u32,
+        deadline: Option<u64>,
+    ) -> HostResult<ProverSlot> {
+        self.scheduler.acquire(proof_type, priority, deadline).await
+    }
+
+    /// Change the number of jobs run at the same time, in total and per prover.
+    pub fn set_concurrency(
+        &self,
+        concurrency_limit: usize,
+        prover_limits: &BTreeMap<String, usize>,
+    ) {
+        self.scheduler.set_limits(concurrency_limit, prover_limits);
+    }
+
+    /// Reject proof requests once the given number of jobs is queued, unset to never reject
+    /// them.
+    pub fn set_max_queue_depth(&self, max_queue_depth: Option<usize>) {
+        *self.max_queue_depth.lock().unwrap() = max_queue_depth;
+    }
+
+    /// The number of jobs preparing their input or waiting for a prover slot.
+    pub fn queue_depth(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    /// The max number of queued jobs, if any, see [JobManager::set_max_queue_depth].
+    pub fn max_queue_depth(&self) -> Option<usize> {
+        *self.max_queue_depth.lock().unwrap()
+    }
+
+    /// Fail with [HostError::QueueFull] once the queue is at its max depth, so the client
+    /// retries once the provers caught up, instead of the host taking unbounded work. The
+    /// client is told to retry after the time the provers take to prove the jobs over the
+    /// max depth, by the average proving time of the jobs.
+    pub fn check_queue(&self) -> HostResult<()> {
+        let Some(max_queue_depth) = self.max_queue_depth() else {
+            return Ok(());
+        };
+        let depth = self.queue_depth();
+        if depth < max_queue_depth {
+            return Ok(());
+        }
+        inc_rate_limited_count("queue_depth");
+        Err(HostError::QueueFull(retry_after(
+            depth - max_queue_depth + 1,
+            self.scheduler.concurrency_limit(),
+            *self.proving_time.lock().unwrap(),
+        )))
+    }
+
+    /// Set the CUDA devices of the GPU backed provers, see [crate::gpu].
+    pub fn set_cuda_devices(&self, cuda_devices: &BTreeMap<String, Vec<u32>>) {
+        self.scheduler.set_cuda_devices(cuda_devices);
+    }
+
+    /// Whether the jobs are drained for the shutdown of the host.
+    pub fn is_draining(&self) -> bool {
+        self.scheduler.is_closed()
+    }
+
+    /// Drain the jobs for the shutdown of the host.
+    ///
+    /// No jobs are started afterwards and new submissions fail. The running jobs get up to
+    /// `timeout` to finish, the ones still running then are aborted. The aborted and the
+    /// queued jobs stay in the job store, which is flushed last, so [JobManager::resume] picks
+    /// them up on the next start.
+    pub async fn drain(&self, timeout: Duration) -> HostResult<DrainReport> {
+        self.scheduler.close();
+        self.preflights.close();
+        let started: Vec<JobId> = self.tasks.lock().unwrap().keys().copied().collect();
+
+        // The queued jobs stop waiting for a slot right away, so only the running jobs are
+        // waited for.
+        let deadline = Instant::now() + timeout;
+        while !self.tasks.lock().unwrap().is_empty() && Instant::now() < deadline {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+        let remaining: Vec<JobId> = {
+            let mut tasks = self.tasks.lock().unwrap();
+            tasks
+                .drain()
+                .map(|(id, task)| {
+                    task.abort();
+                    id
+                })
+                .collect()
+        };
+
+        let mut report = DrainReport::default();
+        for id in started {
+            let status = self.get(id)?.status;
+            if status.is_terminal() {
+                report.drained += 1;
+            } else if status == JobStatus::Running && remaining.contains(&id) {
+                warn!(
+                    "Aborted job {id} at the drain timeout, it is proven again on the next start"
+                );
+                report.aborted += 1;
+            }
+        }
+        report.queued = self
+            .list(None)?
+            .iter()
+            .filter(|job| !job.status.is_terminal())
+            .count();
+        self.store.lock().unwrap().flush()?;
+
+        inc_shutdown_job_count("drained", report.drained);
+        inc_shutdown_job_count("aborted", report.aborted);
+        Ok(report)
+    }
+
+    /// Get the policy for retrying failed requests with the given prover.
+    pub fn retry_policy(&self, proof_type: &ProofType) -> &RetryPolicy {
+        self.retry.policy(proof_type)
+    }
+
+    /// Get the job with the given id.
+    pub fn get(&self, id: JobId) -> HostResult<Job> {
+        self.store
+            .lock()
+            .unwrap()
+            .get(id)?
+            .ok_or(HostError::JobNotFound(id))
+    }
+
+    /// List the jobs, optionally only those in the given state, oldest first.
+    pub fn list(&self, status: Option<JobStatus>) -> HostResult<Vec<JobSummary>> {
+        let mut jobs = self.store.lock().unwrap().list()?;
+        jobs.retain(|job| status.map_or(true, |status| job.status == status));
+        jobs.sort_by_key(|job| job.id);
+        Ok(jobs.iter().map(JobSummary::from).collect())
+    }
+
+    /// List the most recent jobs, newest first, optionally only those in the given state or
+    /// of the given proof type.
+    pub fn recent(
+        &self,
+        status: Option<JobStatus>,
+        proof_type: Option<&ProofType>,
+        limit: usize,
+    ) -> HostResult<Vec<JobRecord>> {
+        let jobs = self.store.lock().unwrap().list()?;
+        Ok(jobs
+            .iter()
+            .rev()
+            .filter(|job| status.map_or(true, |status| job.status == status))
+            .filter(|job| {
+                proof_type.map_or(true, |proof_type| &job.request.proof_type == proof_type)
+            })
+            .take(limit)
+            .map(JobRecord::from)
+            .collect())
+    }
+
+    /// Get the captured log of the job with the given id, see [logs].
+    ///
+    /// Returns the lines logged so far and, unless the job already finished, a receiver for
+    /// the lines it logs until it finishes.
+    pub fn log(
+        &self,
+        id: JobId,
+    ) -> HostResult<(
+        Vec<logs::JobLogLine>,
+        Option<broadcast::Receiver<logs::JobLogLine>>,
+    )> {
+        // Hold the channel lock while loading the job so that it can't finish in between.
+        let progress = self.progress.lock().unwrap();
+        self.get(id)?;
+        if progress.contains_key(&id) {
+            Ok(logs::subscribe(id))
+        } else {
+            Ok((logs::lines(id), None))
+        }
+    }
+
+    /// Subscribe to the progress events of the job with the given id.
+    ///
+    /// Returns the current state of the job and, unless the job already finished, a receiver
+    /// for its upcoming progress events.
+    pub fn subscribe(
+        &self,
+        id: JobId,
+    ) -> HostResult<(JobSummary, Option<broadcast::Receiver<ProgressEvent>>)> {
+        // Hold the channel lock while loading the job so that it can't finish in between.
+        let progress = self.progress.lock().unwrap();
+        let summary = JobSummary::from(&self.get(id)?);
+        Ok((summary, progress.get(&id).map(|sender| sender.subscribe())))
+    }
+
+    /// Cancel the job with the given id.
+    ///
+    /// Queued jobs are removed from the queue. Running jobs are aborted, which kills the
+    /// prover processes spawned for them and frees their prover slot. Provers running inside
+    /// the host process are told through the cancellation token of their progress reporter
+    /// and stop at their next step, e.g. the next segment of a RISC Zero proof.
+    pub fn cancel(&self, id: JobId) -> HostResult<JobSummary> {
+        self.cancel_with(id, None)
+    }
+
+    /// Cancel the job with the given id like [JobManager::cancel], with the reason as its
+    /// error, e.g. once its block was reorged.
+    pub fn invalidate(&self, id: JobId, reason: String) -> HostResult<JobSummary> {
+        self.cancel_with(id, Some(reason))
+    }
+
+    /// The blocks of the unfinished jobs with a prepared input.
+    pub fn proven_blocks(&self) -> Vec<(JobId, ProvenBlock)> {
+        let blocks = self.blocks.lock().unwrap();
+        let mut blocks: Vec<_> = blocks
+            .iter()
+            .map(|(id, block)| (*id, block.clone()))
+            .collect();
+        blocks.sort_by_key(|(id, _)| *id);
+        blocks
+    }
+
+    fn cancel_with(&self, id: JobId, reason: Option<String>) -> HostResult<JobSummary> {
+        if self.get(id)?.status.is_terminal() {
+            return Err(HostError::JobAlreadyFinished(id));
+        }
+        if let Some(task) = self.tasks.lock().unwrap().remove(&id) {
+            task.abort();
+        }
+        self.blocks.lock().unwrap().remove(&id);
+        // The job may have finished while it was being aborted.
+        if !self.update(id, JobStatus::Cancelled, |job| job.error = reason) {
+            return Err(HostError::JobAlreadyFinished(id));
+        }
+        info!("Cancelled job {id}");
+        self.close_progress(id, JobStatus::Cancelled);
+
+        let job = self.get(id)?;
+        callback::notify(&job);
+        Ok(JobSummary::from(&job))
+    }
+
+    /// Open the progress channel of the job and run it in the background, in a span with the
+    /// trace id of the job.
+    fn spawn(
+        &self,
+        id: JobId,
+        request: ProofRequest,
+        trace_id: String,
+        permit: Option<InFlightPermit>,
+    ) {
+        let (sender, _) = broadcast::channel(PROGRESS_CHANNEL_CAPACITY);
+        self.progress.lock().unwrap().insert(id, sender.clone());
+
+        // Hold the lock until the handle is stored, so a fast job can't finish before that.
+        let mut tasks = self.tasks.lock().unwrap();
+        let manager = self.clone();
+        let queued = QueuedJob::new(&self.queued);
+        // Not a child of the request span, which would be kept open until the job finished.
+        let span = info_span!(
+            parent: None,
+            "job",
+            trace_id = %trace_id,
+            job_id = id,
+            block = request.block_number,
+            proof_type = %request.proof_type,
+        );
+        let task = tokio::spawn(
+            with_trace_id(
+                trace_id,
+                artifacts::with_job(id, async move {
+                    let _permit = permit;
+                    manager
+                        .run(id, request, ProgressSender::new(sender), queued)
+                        .await
+                }),
+            )
+            .instrument(span),
+        );
+        tasks.insert(id, task.abort_handle());
+    }
+
+    async fn run(
+        &self,
+        id: JobId,
+        request: ProofRequest,
+        progress: ProgressSender,
+        queued: QueuedJob,
+    ) {
+        let (request, progress) = (&request, &progress);
+        let policy = self.retry_policy(&request.proof_type);
+        let mut preflight_retries = 0;
+        let started = Instant::now();
+        let mut prepared_at = None;
+        let mut deadline = request.deadline;
+
+        // 1. Prepare the input, then wait for a prover slot with it.
+        let prepared = async {
+            let permit = self
+                .preflights
+                .acquire()
+                .await
+                .map_err(|_| HostError::ShuttingDown)?;
+            let input = policy
+                .run(
+                    &request.proof_type,
+                    |retry| {
+                        preflight_retries = retry;
+                        self.record_retry(id, retry);
+                    },
+                    move || async move {
+                        let _request = CurrentRequest::start();
+                        fetch_block_input(request, &self.input_cache, progress).await
+                    },
+                )
+                .await?;
+            // The other jobs run their preflights while the block becomes final.
+            drop(permit);
+            finality::check(request, &input).await?;
+            prepared_at = Some(Instant::now());
+            artifacts::save_input(id, &input);
+            self.blocks
+                .lock()
+                .unwrap()
+                .insert(id, ProvenBlock::new(request, &input));
+            // The deadline of the request can't be after the end of the proving window of the
+            // block, the input tells when the block was proposed.
+            let proposed_at = input.taiko.block_proposed.meta.timestamp;
+            if proposed_at != 0 {
+                if let Ok(chain) = get_chain(&request.network) {
+                    deadline = chain.clamp_deadline(
+                        &request.proof_type.to_string(),
+                        deadline,
+                        proposed_at,
+                    );
+                }
+            }
+            // The proof is due at the deadline, so proving has to start the time it is expected
+            // to take before it, see [crate::estimate].
+            let start_by = deadline.map(|deadline| {
+                let proving_time = proving_time(&request.proof_type, input.gas_used);
+                deadline.saturating_sub(proving_time.unwrap_or_default().as_secs())
+            });
+            let slot = self
+                .acquire(&request.proof_type, request.priority, start_by)
+                .await?;
+            Ok::<_, HostError>((input, slot))
+        }
+        .await;
+        let mut phases = match prepared_at {
+            Some(prepared_at) => JobPhases {
+                preflight: Some((prepared_at - started).as_secs_f64()),
+                waiting: Some(prepared_at.elapsed().as_secs_f64()),
+                proving: None,
+            },
+            None => JobPhases {
+                preflight: Some(started.elapsed().as_secs_f64()),
+                ..Default::default()
+            },
+        };
+        let (input, slot) = match prepared {
+            Ok(prepared) => prepared,
+            Err(HostError::ShuttingDown) => {
+                // Left in the queue for the next start of the host.
+                self.tasks.lock().unwrap().remove(&id);
+                self.blocks.lock().unwrap().remove(&id);
+                return;
+            }
+            Err(e) => {
+                self.finish(id, Err(e.to_string()), phases);
+                return;
+            }
+        };
+
+        // 2. Prove the block in the prover slot.
+        drop(queued);
+        self.update(id, JobStatus::Running, |job| job.phases = phases.clone());
+        let proving = Instant::now();
+        let prepared = &input;
+        let result = gpu::on_device(
+            slot.cuda_device(),
+            policy.run(
+                &request.proof_type,
+                |retry| self.record_retry(id, preflight_retries + retry),
+                move || {
+                    let input = prepared.clone();
+                    async move {
+                        let _request = CurrentRequest::start();
+                        prove_with_input(
+                            request,
+                            &self.input_cache,
+                            &self.proof_cache,
+                            Some(input),
+                            progress,
+                        )
+                        .await
+                    }
+                },
+            ),
+        )
+        .await;
+        phases.proving = Some(proving.elapsed().as_secs_f64());
+        if result.is_ok() {
+            let mut proving_time = self.proving_time.lock().unwrap();
+            let elapsed = proving.elapsed().as_secs_f64();
+            *proving_time = Some(match *proving_time {
+                Some(average) => average + PROVING_TIME_WEIGHT * (elapsed - average),
+                None => elapsed,
+            });
+        }
+
+        match result {
+            Ok((_, proof)) => {
+                if let Some(deadline) = deadline {
+                    let remaining = deadline as i64 - unix_timestamp() as i64;
+                    if remaining < 0 {
+                        warn!("Job {id} finished {}s after its deadline", -remaining);
+                    }
+                    observe_deadline_remaining(&request.proof_type, remaining);
+                }
+                self.upload(id, request, &input, &proof).await;
+                self.finish(id, Ok(proof.clone()), phases);
+                self.relay(id, request, &input, &proof).await;
+            }
+            Err(e) => {
+                if let HostError::ProverCrashed(crash) = &e {
+                    self.record_crash(id, crash);
+                }
+                self.finish(id, Err(e.to_string()), phases)
+            }
+        }
+    }
+
+    /// Upload the proof of the job and its input, if an uploader is configured. The job
+    /// succeeds without the URLs if the upload fails.
+    async fn upload(&self, id: JobId, request: &ProofRequest, input: &GuestInput, proof: &Proof) {
+        let Some(uploader) = &self.uploader else {
+            return;
+        };
+        match uploader.upload(request, input, proof).await {
+            Ok(upload) => {
+                info!("Uploaded the proof of job {id} to {}", upload.proof_url);
+                let mut store = self.store.lock().unwrap();
+                match store.get(id) {
+                    Ok(Some(mut job)) => {
+                        job.upload = Some(upload);
+                        if let Err(e) = store.put(&job) {
+                            error!("Could not store job {id}: {e}");
+                        }
+                    }
+                    Ok(None) => warn!("Job {id} disappeared before its proof was uploaded"),
+                    Err(e) => error!("Could not load job {id}: {e}"),
+                }
+            }
+            Err(e) => error!("Could not upload the proof of job {id}: {e}"),
+        }
+    }
+
+    /// Submit the proof of the job to L1, if the relayer submits proofs of its type.
+    async fn relay(&self, id: JobId, request: &ProofRequest, input: &GuestInput, proof: &Proof) {
+        let Some(relayer) = self
+            .relayer
+            .as_ref()
+            .filter(|r| r.submits(&request.proof_type))
+        else {
+            return;
+        };
+        relayer
+            .submit(request, input, proof, |submission| {
+                self.record_submission(id, submission)
+            })
+            .await;
+    }
+
+    /// Store the progress of the submission of the proof of the job.
+    fn record_submission(&self, id: JobId, submission: &Submission) {
+        let mut store = self.store.lock().unwrap();
+        match store.get(id) {
+            Ok(Some(mut job)) => {
+                job.submission = Some(submission.clone());
+                job.updated_at = unix_timestamp();
+                if let Err(e) = store.put(&job) {
+                    error!("Could not store job {id}: {e}");
+                }
+            }
+            Ok(None) => warn!("Job {id} disappeared before its proof was submitted"),
+            Err(e) => error!("Could not load job {id}: {e}"),
+        }
+    }
+
+    /// Store the crash of the prover the unfinished job failed with.
+    fn record_crash(&self, id: JobId, crash: &ProverCrash) {
+        let mut store = self.store.lock().unwrap();
+        match store.get(id) {
+            Ok(Some(mut job)) if !job.status.is_terminal() => {
+                job.crash = Some(crash.clone());
+                if let Err(e) = store.put(&job) {
+                    error!("Could not store job {id}: {e}");
+                }
+            }
+            Ok(_) => warn!("Could not record the crash of job {id}, it already finished"),
+            Err(e) => error!("Could not load job {id}: {e}"),
+        }
+    }
+
+    /// Store the number of retries of the unfinished job.
+    fn record_retry(&self, id: JobId, retries: u32) {
+        let mut store = self.store.lock().unwrap();
+        match store.get(id) {
+            Ok(Some(mut job)) if !job.status.is_terminal() => {
+                job.retries = retries;
+                job.updated_at = unix_timestamp();
+                if let Err(e) = store.put(&job) {
+                    error!("Could not store job {id}: {e}");
+                }
+            }
+            Ok(_) => warn!("Could not record retry {retries} of job {id}, it already finished"),
+            Err(e) => error!("Could not load job {id}: {e}"),
+        }
+    }
+
+    fn finish(&self, id: JobId, result: Result<Proof, String>, phases: JobPhases) {
+        self.tasks.lock().unwrap().remove(&id);
+        self.blocks.lock().unwrap().remove(&id);
+        artifacts::save_result(id, &result);
+        let status = match result {
+            Ok(proof) => {
+                self.update(id, JobStatus::Success, |job| {
+                    job.proof = Some(proof);
+                    job.phases = phases;
+                });
+                JobStatus::Success
+            }
+            Err(e) => {
+                error!("Job {id} failed: {e}");
+                self.update(id, JobStatus::Failed, |job| {
+                    job.error = Some(e);
+                    job.phases = phases;
+                });
+                JobStatus::Failed
+            }
+        };
+        if let Ok(job) = self.get(id) {
+            observe_job_retries(&job.request.proof_type, job.retries);
+            callback::notify(&job);
+        }
+        self.close_progress(id, status);
+    }
+
+    /// Publish the final status of the job and close its progress channel.
+    fn close_progress(&self, id: JobId, status: JobStatus) {
+        // Dropping the sender closes the channel for all subscribers after the last event.
+        let mut progress = self.progress.lock().unwrap();
+        if let Some(sender) = progress.remove(&id) {
+            let _ = sender.send(ProgressEvent::Finished { status });
+        }
+        logs::close(id);
+    }
+
+    /// Move the job to the next state. Returns whether the transition was applied.
+    fn transition(&self, id: JobId, next: JobStatus) -> bool {
+        self.update(id, next, |_| {})
+    }
+
+    /// Move the job to the next state, apply `f` to it and persist the result. Returns
+    /// whether the transition was applied.
+    fn update(&self, id: JobId, next: JobStatus, f: impl FnOnce(&mut Job)) -> bool {
+        let mut store = self.store.lock().unwrap();
+        let mut job = match store.get(id) {
+            Ok(Some(job)) => job,
+            Ok(None) => {
+                warn!("Job {id} disappeared before moving to {next}");
+                return false;
+            }
+            Err(e) => {
+                error!("Could not load job {id}: {e}");
+                return false;
+            }
+        };
+        if !job.status.can_transition_to(next) {
+            warn!("Job {id} can not move from {} to {next}", job.status);
+            return false;
+        }
+        job.status = next;
+        job.updated_at = unix_timestamp();
+        f(&mut job);
+        if let Err(e) = store.put(&job) {
+            error!("Could not store job {id}: {e}");
+            return false;
+        }
+        messaging::publish(&job);
+        true
+    }
+}
+
+/// Counts a job as a current request for as long as it is alive, including when the job is
+/// aborted.
+struct CurrentRequest;
+
+impl CurrentRequest {
+    fn start() -> Self {
+        inc_current_req();
+        Self
+    }
+}
+
+impl Drop for CurrentRequest {
+    fn drop(&mut self) {
+        dec_current_req();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_after() {
+        assert_eq!(retry_after(1, 4, None), DEFAULT_RETRY_AFTER);
+        // Four provers prove the five jobs over the limit in two rounds.
+        assert_eq!(retry_after(5, 4, Some(90.5)), 181);
+        assert_eq!(retry_after(1, 0, Some(0.1)), 1);
+        assert_eq!(retry_after(100, 1, Some(600.0)), MAX_RETRY_AFTER);
+    }
+}
@@ -0,0 +1,166 @@
+use raiko_lib::prover::{ProgressReporter, ProverProgress};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::{
+    jobs::JobStatus,
+    metrics::{inc_remote_session_count, observe_guest_execution},
+    request::ProofType,
+};
+
+/// The number of events buffered for slow subscribers before they start lagging behind.
+pub const PROGRESS_CHANNEL_CAPACITY: usize = 64;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "event", rename_all = "snake_case")]
+/// A progress event of a proof job.
+pub enum ProgressEvent {
+    /// The current status of the job, sent when subscribing.
+    Status { status: JobStatus },
+    /// The preflight for the block started.
+    PreflightStarted,
+    /// The guest input was prepared or loaded from the cache.
+    InputPrepared,
+    /// The given percentage of the guest execution is done.
+    GuestExecution { percent: u8 },
+    /// The proof is generated remotely by the given proving service in the given session.
+    RemoteSession { service: String, session_id: String },
+    /// The guest program ran for the given number of cycles, split into the given number of
+    /// segments or shards, if the prover knows them.
+    GuestExecuted {
+        cycles: Option<u64>,
+        segments: Option<u64>,
+    },
+    /// The prover generated the proof.
+    ProofGenerated,
+    /// The prover verified the generated proof.
+    ProofVerified,
+    /// The job finished with the given status.
+    Finished { status: JobStatus },
+}
+
+impl ProgressEvent {
+    /// The event of the progress of a prover, if it is published. The profile of the guest is
+    /// only added to the stats of the proof.
+    fn from_progress(progress: ProverProgress) -> Option<Self> {
+        Some(match progress {
+            ProverProgress::Execution(percent) => ProgressEvent::GuestExecution { percent },
+            ProverProgress::RemoteSession {
+                service,
+                session_id,
+            } => ProgressEvent::RemoteSession {
+                service,
+                session_id,
+            },
+            ProverProgress::Executed { cycles, segments } => {
+                ProgressEvent::GuestExecuted { cycles, segments }
+            }
+            ProverProgress::Profiled(_) => return None,
+            ProverProgress::Verified => ProgressEvent::ProofVerified,
+        })
+    }
+}
+
+/// Publishes the progress events of a single proof job.
+///
+/// The default sender drops all events, which is used for requests that are not tracked as
+/// jobs.
+#[derive(Clone, Debug, Default)]
+pub struct ProgressSender(Option<broadcast::Sender<ProgressEvent>>);
+
+impl ProgressSender {
+    pub fn new(sender: broadcast::Sender<ProgressEvent>) -> Self {
+        Self(Some(sender))
+    }
+
+    pub fn send(&self, event: ProgressEvent) {
+        if let Some(sender) = &self.0 {
+            // Nobody listening is fine, the events are only informational.
+            let _ = sender.send(event);
+        }
+    }
+
+    /// Create a reporter forwarding the progress of a prover driver as events.
+    ///
+    /// Remote proving sessions are also logged and counted in the metrics, and the cycles of
+    /// the guest execution observed, also for requests that are not tracked as jobs.
+    pub fn reporter(&self, proof_type: &ProofType) -> ProgressReporter {
+        let sender = self.0.clone();
+        let proof_type = proof_type.clone();
+        ProgressReporter::new(move |progress| {
+            if let ProverProgress::RemoteSession {
+                service,
+                session_id,
+            } = &progress
+            {
+                info!("Generating {proof_type} proof in {service} session {session_id}");
+                inc_remote_session_count(&proof_type, service);
+            }
+            if let ProverProgress::Executed { cycles, segments } = &progress {
+                observe_guest_execution(&proof_type, *cycles, *segments);
+            }
+            if let (Some(sender), Some(event)) = (&sender, ProgressEvent::from_progress(progress)) {
+                let _ = sender.send(event);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_event_serialization() {
+        assert_eq!(
+            serde_json::to_string(&ProgressEvent::GuestExecution { percent: 42 }).unwrap(),
+            r#"{"event":"guest_execution","percent":42}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&ProgressEvent::Finished {
+                status: JobStatus::Success
+            })
+            .unwrap(),
+            r#"{"event":"finished","status":"success"}"#
+        );
+    }
+
+    #[test]
+    fn test_reporter_forwards_prover_progress() {
+        let (sender, mut receiver) = broadcast::channel(PROGRESS_CHANNEL_CAPACITY);
+        let reporter = ProgressSender::new(sender).reporter(&ProofType::Native);
+        reporter.report(ProverProgress::Execution(50));
+        reporter.report(ProverProgress::RemoteSession {
+            service: "bonsai".to_string(),
+            session_id: "session".to_string(),
+        });
+        reporter.report(ProverProgress::Executed {
+            cycles: Some(1 << 20),
+            segments: Some(1),
+        });
+        reporter.report(ProverProgress::Profiled(Default::default()));
+        reporter.report(ProverProgress::Verified);
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            ProgressEvent::GuestExecution { percent: 50 }
+        );
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            ProgressEvent::RemoteSession {
+                service: "bonsai".to_string(),
+                session_id: "session".to_string(),
+            }
+        );
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            ProgressEvent::GuestExecuted {
+                cycles: Some(1 << 20),
+                segments: Some(1),
+            }
+        );
+        assert_eq!(receiver.try_recv().unwrap(), ProgressEvent::ProofVerified);
+        assert!(receiver.try_recv().is_err());
+    }
+}
@@ -0,0 +1,508 @@
+//! Limits the number of proofs generated at the same time.
+//!
+//! Every proof needs a slot of the host wide concurrency limit, and additionally a slot of
+//! its prover if a limit is configured for it. Provers like SGX or a GPU backed Risc0 can only
+//! run a few proofs at once without running out of memory, so the excess requests wait for a
+//! slot instead.
+//!
+//! Provers with CUDA devices configured additionally need a free device: every proof gets one
+//! of the devices of its prover for itself, so concurrent proofs don't share a card. Devices
+//! listed for several provers are shared between them.
+//!
+//! Waiting requests are handed the next free slot by priority, so urgent proofs jump ahead of
+//! backfill proofs. Requests with a deadline, the end of the proving window of their block, are
+//! served before all others once the deadline is less than the deadline margin away, the most
+//! urgent first. Otherwise requests with the same priority are served by deadline, then in the
+//! order they arrived. Jobs are due the time their proof is expected to take before the end of
+//! their proving window, with the calibration profile of the host.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::oneshot;
+
+use crate::{
+    clock::unix_timestamp,
+    error::{HostError, HostResult},
+    metrics::{
+        dec_active_workers, dec_queued_req, inc_active_workers, inc_queued_req, observe_queue_time,
+    },
+    request::ProofType,
+};
+
+/// Hands out the slots to generate proofs in.
+#[derive(Clone, Debug)]
+pub struct Scheduler {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    concurrency_limit: usize,
+    prover_limits: HashMap<String, usize>,
+    running: usize,
+    running_per_prover: HashMap<String, usize>,
+    /// The CUDA devices of the provers using GPUs.
+    cuda_devices: HashMap<String, Vec<u32>>,
+    /// The CUDA devices used by a running proof.
+    busy_devices: HashSet<u32>,
+    deadline_margin: Duration,
+    waiting: Vec<Waiter>,
+    next_seq: u64,
+    /// Whether the scheduler was closed for the shutdown of the host.
+    closed: bool,
+}
+
+/// A request waiting for a slot.
+#[derive(Debug)]
+struct Waiter {
+    priority: u32,
+    /// The unix timestamp the proof is due at.
+    deadline: Option<u64>,
+    seq: u64,
+    prover: String,
+    sender: oneshot::Sender<ProverSlot>,
+}
+
+/// A slot to generate a proof in. The slot is freed when dropped.
+#[derive(Debug)]
+pub struct ProverSlot {
+    scheduler: Scheduler,
+    prover: String,
+    cuda_device: Option<u32>,
+}
+
+impl Scheduler {
+    /// Create a scheduler running at most `concurrency_limit` proofs at the same time, and at
+    /// most the given number of proofs per prover. Requests due within `deadline_margin` are
+    /// served first.
+    pub fn new(
+        concurrency_limit: usize,
+        prover_limits: &BTreeMap<String, usize>,
+        deadline_margin: Duration,
+    ) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                concurrency_limit,
+                prover_limits: prover_limits
+                    .iter()
+                    .map(|(name, limit)| (name.trim().to_lowercase(), *limit))
+                    .collect(),
+                running: 0,
+                running_per_prover: HashMap::new(),
+                cuda_devices: HashMap::new(),
+                busy_devices: HashSet::new(),
+                deadline_margin,
+                waiting: Vec::new(),
+                next_seq: 0,
+                closed: false,
+            })),
+        }
+    }
+
+    /// Change the limits, e.g. at runtime. Running proofs over the new limits keep their slots,
+    /// the waiting requests get the slots freed up to the new limits.
+    pub fn set_limits(&self, concurrency_limit: usize, prover_limits: &BTreeMap<String, usize>) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.concurrency_limit = concurrency_limit;
+            state.prover_limits = prover_limits
+                .iter()
+                .map(|(name, limit)| (name.trim().to_lowercase(), *limit))
+                .collect();
+        }
+        self.dispatch();
+    }
+
+    /// The max number of proofs generated at the same time.
+    pub fn concurrency_limit(&self) -> usize {
+        self.state.lock().unwrap().concurrency_limit
+    }
+
+    /// Set the CUDA devices of the provers, e.g. `{"risc0": [0, 1]}`. Every proof of a prover
+    /// with devices waits for one of them to be free.
+    pub fn set_cuda_devices(&self, cuda_devices: &BTreeMap<String, Vec<u32>>) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.cuda_devices = cuda_devices
+                .iter()
+                .map(|(name, devices)| (name.trim().to_lowercase(), devices.clone()))
+                .collect();
+        }
+        self.dispatch();
+    }
+
+    /// Stop handing out slots, for the shutdown of the host. The waiting and the new requests
+    /// fail, the running proofs keep their slots.
+    pub fn close(&self) {
+        let waiting = {
+            let mut state = self.state.lock().unwrap();
+            state.closed = true;
+            std::mem::take(&mut state.waiting)
+        };
+        // Dropping the senders fails the waiting requests.
+        drop(waiting);
+    }
+
+    /// Whether the scheduler was closed with [Scheduler::close].
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+
+    /// Wait until a slot for the given prover is available. Requests close to their deadline
+    /// and requests with a higher priority are served first.
+    pub async fn acquire(
+        &self,
+        proof_type: &ProofType,
+        priority: u32,
+        deadline: Option<u64>,
+    ) -> HostResult<ProverSlot> {
+        let queued = QueuedRequest::start(proof_type);
+        let (sender, receiver) = oneshot::channel();
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.closed {
+                return Err(HostError::ShuttingDown);
+            }
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.waiting.push(Waiter {
+                priority,
+                deadline,
+                seq,
+                prover: proof_type.to_string(),
+                sender,
+            });
+        }
+        self.dispatch();
+
+        let slot = receiver.await.map_err(|_| HostError::ShuttingDown)?;
+        observe_queue_time(proof_type, queued.since.elapsed());
+        Ok(slot)
+    }
+
+    /// Hand the free slots to the waiting requests, by urgency and priority.
+    fn dispatch(&self) {
+        // Slots of requests that stopped waiting are freed once the lock is released.
+        let mut unclaimed = Vec::new();
+        {
+            let mut state = self.state.lock().unwrap();
+            // The urgency of the requests changes over time, so they are ranked on every
+            // dispatch.
+            let now = unix_timestamp();
+            let margin = state.deadline_margin.as_secs();
+            let mut waiting = std::mem::take(&mut state.waiting);
+            waiting.retain(|waiter| !waiter.sender.is_closed());
+            waiting.sort_by_key(|waiter| Reverse(waiter.rank(now, margin)));
+            for waiter in waiting {
+                // Requests for a prover at its limit must not block the other provers.
+                if state.running >= state.concurrency_limit || !state.has_capacity(&waiter.prover) {
+                    state.waiting.push(waiter);
+                    continue;
+                }
+                let cuda_device = match state.cuda_devices.get(&waiter.prover) {
+                    Some(devices) => {
+                        let free = devices
+                            .iter()
+                            .find(|device| !state.busy_devices.contains(device))
+                            .copied();
+                        let Some(device) = free else {
+                            state.waiting.push(waiter);
+                            continue;
+                        };
+                        state.busy_devices.insert(device);
+                        Some(device)
+                    }
+                    None => None,
+                };
+                state.running += 1;
+                *state
+                    .running_per_prover
+                    .entry(waiter.prover.clone())
+                    .or_default() += 1;
+                inc_active_workers(&waiter.prover);
+                let slot = ProverSlot {
+                    scheduler: self.clone(),
+                    prover: waiter.prover,
+                    cuda_device,
+                };
+                if let Err(slot) = waiter.sender.send(slot) {
+                    unclaimed.push(slot);
+                }
+            }
+        }
+        drop(unclaimed);
+    }
+
+    fn release(&self, prover: &str, cuda_device: Option<u32>) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.running -= 1;
+            if let Some(running) = state.running_per_prover.get_mut(prover) {
+                *running -= 1;
+            }
+            if let Some(device) = cuda_device {
+                state.busy_devices.remove(&device);
+            }
+        }
+        dec_active_workers(prover);
+        self.dispatch();
+    }
+}
+
+impl State {
+    fn has_capacity(&self, prover: &str) -> bool {
+        match self.prover_limits.get(prover) {
+            Some(limit) => self.running_per_prover.get(prover).copied().unwrap_or(0) < *limit,
+            None => true,
+        }
+    }
+}
+
+impl ProverSlot {
+    /// The CUDA device the proof runs on, if its prover uses GPUs.
+    pub fn cuda_device(&self) -> Option<u32> {
+        self.cuda_device
+    }
+}
+
+impl Drop for ProverSlot {
+    fn drop(&mut self) {
+        self.scheduler.release(&self.prover, self.cuda_device);
+    }
+}
+
+impl Waiter {
+    /// The rank of the waiter at `now`, the waiter with the highest rank is served first.
+    ///
+    /// Waiters due within `margin` seconds come first, the earliest deadline first. The others
+    /// are ranked by priority, then by deadline, and ties go to the oldest waiter.
+    fn rank(&self, now: u64, margin: u64) -> (bool, Reverse<u64>, u32, Reverse<u64>, Reverse<u64>) {
+        let deadline = self.deadline.unwrap_or(u64::MAX);
+        let urgent = deadline <= now.saturating_add(margin);
+        (
+            urgent,
+            Reverse(if urgent { deadline } else { 0 }),
+            self.priority,
+            Reverse(deadline),
+            Reverse(self.seq),
+        )
+    }
+}
+
+/// Counts a request as queued for as long as it waits for a slot.
+struct QueuedRequest {
+    proof_type: ProofType,
+    since: Instant,
+}
+
+impl QueuedRequest {
+    fn start(proof_type: &ProofType) -> Self {
+        inc_queued_req(proof_type);
+        Self {
+            proof_type: proof_type.clone(),
+            since: Instant::now(),
+        }
+    }
+}
+
+impl Drop for QueuedRequest {
+    fn drop(&mut self) {
+        dec_queued_req(&self.proof_type);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::{sleep, timeout};
+
+    use super::*;
+
+    const WAIT: Duration = Duration::from_millis(50);
+
+    #[tokio::test]
+    async fn test_prover_limit() {
+        let limits = BTreeMap::from([("Native".to_string(), 1)]);
+        let scheduler = Scheduler::new(2, &limits, Duration::ZERO);
+        let other = ProofType::Custom("other".to_string());
+
+        let slot = scheduler
+            .acquire(&ProofType::Native, 0, None)
+            .await
+            .unwrap();
+        // The native prover is at its limit, but the host isn't.
+        assert!(
+            timeout(WAIT, scheduler.acquire(&ProofType::Native, 0, None))
+                .await
+                .is_err()
+        );
+        let other_slot = scheduler.acquire(&other, 0, None).await.unwrap();
+        // Now the host is at its limit as well.
+        assert!(timeout(WAIT, scheduler.acquire(&other, 0, None))
+            .await
+            .is_err());
+
+        drop(slot);
+        drop(other_slot);
+        assert!(
+            timeout(WAIT, scheduler.acquire(&ProofType::Native, 0, None))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_limits() {
+        let scheduler = Scheduler::new(1, &BTreeMap::new(), Duration::ZERO);
+        let slot = scheduler
+            .acquire(&ProofType::Native, 0, None)
+            .await
+            .unwrap();
+        let waiter = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move { scheduler.acquire(&ProofType::Native, 0, None).await })
+        };
+        sleep(WAIT).await;
+        assert!(!waiter.is_finished());
+
+        // Raising the limit hands the new slot to the waiting request.
+        scheduler.set_limits(2, &BTreeMap::new());
+        let other_slot = timeout(WAIT, waiter).await.unwrap().unwrap().unwrap();
+
+        // Lowering it keeps the running proofs, but the next request waits for both.
+        scheduler.set_limits(1, &BTreeMap::new());
+        drop(slot);
+        assert!(
+            timeout(WAIT, scheduler.acquire(&ProofType::Native, 0, None))
+                .await
+                .is_err()
+        );
+        drop(other_slot);
+        assert!(
+            timeout(WAIT, scheduler.acquire(&ProofType::Native, 0, None))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_close() {
+        let scheduler = Scheduler::new(1, &BTreeMap::new(), Duration::ZERO);
+        let slot = scheduler
+            .acquire(&ProofType::Native, 0, None)
+            .await
+            .unwrap();
+        let waiter = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move { scheduler.acquire(&ProofType::Native, 0, None).await })
+        };
+        sleep(WAIT).await;
+
+        // The waiting and the new requests fail, the running proof keeps its slot.
+        scheduler.close();
+        let result = timeout(WAIT, waiter).await.unwrap().unwrap();
+        assert!(matches!(result, Err(HostError::ShuttingDown)));
+        assert!(matches!(
+            scheduler.acquire(&ProofType::Native, 0, None).await,
+            Err(HostError::ShuttingDown)
+        ));
+        assert_eq!(scheduler.state.lock().unwrap().running, 1);
+        drop(slot);
+        assert_eq!(scheduler.state.lock().unwrap().running, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cuda_devices() {
+        let scheduler = Scheduler::new(4, &BTreeMap::new(), Duration::ZERO);
+        scheduler.set_cuda_devices(&BTreeMap::from([("native".to_string(), vec![0, 1])]));
+        let first = scheduler
+            .acquire(&ProofType::Native, 0, None)
+            .await
+            .unwrap();
+        let second = scheduler
+            .acquire(&ProofType::Native, 0, None)
+            .await
+            .unwrap();
+        assert_eq!(first.cuda_device(), Some(0));
+        assert_eq!(second.cuda_device(), Some(1));
+        // Both devices are busy, the host isn't at its limit.
+        assert!(
+            timeout(WAIT, scheduler.acquire(&ProofType::Native, 0, None))
+                .await
+                .is_err()
+        );
+        let other = ProofType::Custom("other".to_string());
+        assert_eq!(
+            scheduler
+                .acquire(&other, 0, None)
+                .await
+                .unwrap()
+                .cuda_device(),
+            None
+        );
+
+        drop(first);
+        let third = timeout(WAIT, scheduler.acquire(&ProofType::Native, 0, None))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(third.cuda_device(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_priority_order() {
+        let scheduler = Scheduler::new(1, &BTreeMap::new(), Duration::ZERO);
+        let slot = scheduler
+            .acquire(&ProofType::Native, 0, None)
+            .await
+            .unwrap();
+
+        let spawn_waiter = |priority| {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move { scheduler.acquire(&ProofType::Native, priority, None).await })
+        };
+        let low = spawn_waiter(0);
+        sleep(WAIT).await;
+        let high = spawn_waiter(10);
+        sleep(WAIT).await;
+
+        // The later request with the higher priority gets the slot first.
+        drop(slot);
+        let high_slot = timeout(WAIT, high).await.unwrap().unwrap().unwrap();
+        assert!(!low.is_finished());
+
+        drop(high_slot);
+        assert!(timeout(WAIT, low).await.unwrap().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_deadline_rank() {
+        let waiter = |priority, deadline, seq| Waiter {
+            priority,
+            deadline,
+            seq,
+            prover: "native".to_string(),
+            sender: oneshot::channel().0,
+        };
+        let now = 1_000;
+        let backfill = waiter(10, None, 0);
+        let due_later = waiter(0, Some(now + 3_600), 1);
+        let due_soon = waiter(0, Some(now + 300), 2);
+        let due_sooner = waiter(0, Some(now + 60), 3);
+        let rank = |waiter: &Waiter| waiter.rank(now, 600);
+
+        // Requests within the margin go before any priority, the earliest deadline first.
+        assert!(rank(&due_sooner) > rank(&due_soon));
+        assert!(rank(&due_soon) > rank(&backfill));
+        // Outside of the margin the priority decides.
+        assert!(rank(&backfill) > rank(&due_later));
+        // With the same priority the request with a deadline goes first.
+        assert!(rank(&due_later) > rank(&waiter(0, None, 4)));
+    }
+}
@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+/// The state of a proof job.
+pub enum JobStatus {
+    /// The job was accepted and is waiting for a free prover slot.
+    Queued,
+    /// The job is currently being proven.
+    Running,
+    /// The proof was generated successfully.
+    Success,
+    /// The proof generation failed.
+    Failed,
+    /// The job was cancelled before it finished.
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Returns whether the job has reached a final state.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Success | JobStatus::Failed | JobStatus::Cancelled
+        )
+    }
+
+    /// Returns whether a job in this state is allowed to move to `next`.
+    ///
+    /// Running jobs can move back to the queue when they are resumed after a restart.
+    pub fn can_transition_to(&self, next: JobStatus) -> bool {
+        matches!(
+            (self, next),
+            (JobStatus::Queued, JobStatus::Running)
+                | (JobStatus::Queued, JobStatus::Failed)
+                | (JobStatus::Queued, JobStatus::Cancelled)
+                | (JobStatus::Running, JobStatus::Queued)
+                | (JobStatus::Running, JobStatus::Success)
+                | (JobStatus::Running, JobStatus::Failed)
+                | (JobStatus::Running, JobStatus::Cancelled)
+        )
+    }
+}
+
+impl std::fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Success => "success",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_status_transitions() {
+        assert!(JobStatus::Queued.can_transition_to(JobStatus::Running));
+        assert!(JobStatus::Running.can_transition_to(JobStatus::Success));
+        assert!(JobStatus::Running.can_transition_to(JobStatus::Failed));
+        assert!(JobStatus::Running.can_transition_to(JobStatus::Queued));
+        assert!(!JobStatus::Queued.can_transition_to(JobStatus::Success));
+        assert!(!JobStatus::Success.can_transition_to(JobStatus::Running));
+        assert!(!JobStatus::Failed.can_transition_to(JobStatus::Queued));
+        assert!(JobStatus::Queued.can_transition_to(JobStatus::Cancelled));
+        assert!(JobStatus::Running.can_transition_to(JobStatus::Cancelled));
+        assert!(!JobStatus::Success.can_transition_to(JobStatus::Cancelled));
+        assert!(!JobStatus::Cancelled.can_transition_to(JobStatus::Queued));
+    }
+
+    #[test]
+    fn test_job_status_terminal() {
+        assert!(!JobStatus::Queued.is_terminal());
+        assert!(!JobStatus::Running.is_terminal());
+        assert!(JobStatus::Success.is_terminal());
+        assert!(JobStatus::Failed.is_terminal());
+        assert!(JobStatus::Cancelled.is_terminal());
+    }
+}
@@ -0,0 +1,125 @@
+//! JWT authentication like the Engine API of the execution clients.
+//!
+//! With a secret file, the proof endpoints are only served to requests with a HS256 token
+//! signed with the secret in the `Authorization: Bearer <token>` header. The token has to
+//! carry an `iat` claim within [MAX_IAT_DRIFT] seconds of the time of the host, so the same
+//! secret file as for the execution client can be used.
+
+use std::{collections::HashSet, path::Path};
+
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use raiko_primitives::hex;
+use serde::Deserialize;
+
+use crate::{
+    clock::unix_timestamp,
+    error::{HostError, HostResult},
+};
+
+/// The max number of seconds the `iat` claim of a token may differ from the time of the host.
+pub const MAX_IAT_DRIFT: u64 = 60;
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    iat: u64,
+}
+
+/// Validates the tokens of requests against the shared secret.
+#[derive(Clone)]
+pub struct JwtAuth {
+    key: DecodingKey,
+    validation: Validation,
+}
+
+impl std::fmt::Debug for JwtAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwtAuth").finish_non_exhaustive()
+    }
+}
+
+impl JwtAuth {
+    /// Read the hex encoded 32 byte secret from the file at `path`.
+    pub fn from_file(path: &Path) -> HostResult<Self> {
+        let secret = std::fs::read_to_string(path)?;
+        Ok(Self::new(&parse_secret(&secret)?))
+    }
+
+    pub fn new(secret: &[u8]) -> Self {
+        let mut validation = Validation::new(Algorithm::HS256);
+        // The Engine API tokens only need an `iat` claim, which is checked by `validate`.
+        validation.required_spec_claims = HashSet::new();
+        validation.validate_exp = false;
+        Self {
+            key: DecodingKey::from_secret(secret),
+            validation,
+        }
+    }
+
+    /// Check the signature and the freshness of the token.
+    pub fn validate(&self, token: &str) -> HostResult<()> {
+        let claims = jsonwebtoken::decode::<Claims>(token, &self.key, &self.validation)
+            .map_err(|e| HostError::Unauthorized(format!("Invalid JWT: {e}")))?
+            .claims;
+        let now = unix_timestamp();
+        if claims.iat.abs_diff(now) > MAX_IAT_DRIFT {
+            return Err(HostError::Unauthorized(format!(
+                "Stale JWT, issued at {} but the time is {now}",
+                claims.iat
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check the token in the `Authorization` header value of a request.
+    pub fn validate_header(&self, header: Option<&str>) -> HostResult<()> {
+        let token = header
+            .and_then(|header| header.strip_prefix("Bearer "))
+            .ok_or_else(|| HostError::Unauthorized("Missing JWT".to_string()))?;
+        self.validate(token.trim())
+    }
+}
+
+/// Decode the 32 byte secret of a secret file, with or without the `0x` prefix.
+fn parse_secret(secret: &str) -> HostResult<Vec<u8>> {
+    let secret = hex::decode(secret.trim())
+        .map_err(|e| HostError::InvalidRequestConfig(format!("Invalid JWT secret: {e}")))?;
+    if secret.len() != 32 {
+        return Err(HostError::InvalidRequestConfig(format!(
+            "The JWT secret has {} bytes instead of 32",
+            secret.len()
+        )));
+    }
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+
+    use super::*;
+
+    fn token(secret: &[u8], iat: u64) -> String {
+        jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            &json!({ "iat": iat }),
+            &EncodingKey::from_secret(secret),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validate() {
+        let secret = parse_secret(&format!("0x{}\n", "ab".repeat(32))).unwrap();
+        assert!(parse_secret("abcd").is_err());
+        let auth = JwtAuth::new(&secret);
+        let now = unix_timestamp();
+
+        auth.validate(&token(&secret, now)).unwrap();
+        auth.validate_header(Some(&format!("Bearer {}", token(&secret, now - 30))))
+            .unwrap();
+        assert!(auth.validate(&token(&secret, now - 120)).is_err());
+        assert!(auth.validate(&token(&[0; 32], now)).is_err());
+        assert!(auth.validate_header(None).is_err());
+    }
+}
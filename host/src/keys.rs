@@ -0,0 +1,418 @@
+//! The large artifacts of the provers, e.g. proving keys, SRS and trusted setups.
+//!
+//! `keys` of the config file lists the artifacts with their version, the URL they are
+//! downloaded from and their SHA-256 checksum:
+//!
+//! ```json
+//! [{"name": "groth16-srs", "version": "v1", "url": "https://.../srs.bin", "sha256": "0x...",
+//!   "env": "GROTH16_SRS_PATH"}]
+//! ```
+//!
+//! They are kept in `--keys-dir`, as `<name>/<version>/<file>`, so several versions can be
+//! kept side by side and switching back doesn't download the old version again. Downloads are
+//! written to a partial file while they are hashed and only moved in place if the checksum
+//! matches. A verified file is marked with its checksum, so a restart doesn't hash gigabytes
+//! again. The path of a ready artifact is set in the environment variable `env`, where the
+//! SDKs of the provers look for it. `raiko-host setup` fetches all of them ahead of time, e.g.
+//! to copy the directory to an air-gapped machine.
+
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use alloy_primitives::B256;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+use crate::{
+    error::{HostError, HostResult},
+    Cli, SetupArgs,
+};
+
+/// The suffix of the mark of a verified file, which holds its checksum.
+const VERIFIED_SUFFIX: &str = ".sha256";
+
+/// The suffix of a file being downloaded.
+const PARTIAL_SUFFIX: &str = ".partial";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// A large artifact of a prover.
+pub struct KeyConfig {
+    /// The name of the artifact, e.g. `groth16-srs`.
+    pub name: String,
+    /// The version of the artifact, several versions are kept side by side.
+    pub version: String,
+    /// The URL the artifact is downloaded from.
+    pub url: String,
+    /// The SHA-256 checksum of the artifact.
+    pub sha256: B256,
+    /// The name of the file, the last segment of the URL by default.
+    #[serde(default)]
+    pub file: Option<String>,
+    /// The environment variable set to the path of the artifact once it is ready.
+    #[serde(default)]
+    pub env: Option<String>,
+}
+
+impl KeyConfig {
+    fn file_name(&self) -> &str {
+        self.file.as_deref().unwrap_or_else(|| {
+            self.url
+                .rsplit('/')
+                .find(|segment| !segment.is_empty())
+                .unwrap_or(&self.name)
+        })
+    }
+
+    /// The path of the artifact in the directory of the keys.
+    pub fn path(&self, dir: &Path) -> PathBuf {
+        dir.join(&self.name)
+            .join(&self.version)
+            .join(self.file_name())
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+/// The state of an artifact in the directory of the keys.
+pub struct KeyStatus {
+    pub name: String,
+    pub version: String,
+    pub path: PathBuf,
+    /// Whether the file is there with the expected checksum.
+    pub ready: bool,
+    /// Whether the file was downloaded now.
+    pub downloaded: bool,
+    /// Why the artifact isn't ready.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+lazy_static! {
+    static ref KEYS: RwLock<(PathBuf, Vec<KeyConfig>)> =
+        RwLock::new((PathBuf::from("keys"), Vec::new()));
+}
+
+/// Check the artifacts: their names are unique and they are kept in a file of their own.
+pub fn validate(keys: &[KeyConfig]) -> HostResult<()> {
+    for (i, key) in keys.iter().enumerate() {
+        if keys[..i].iter().any(|other| other.name == key.name) {
+            return Err(HostError::InvalidRequestConfig(format!(
+                "Invalid key {}: the name is used twice",
+                key.name
+            )));
+        }
+        let escapes =
+            |part: &str| part.is_empty() || part == "." || part == ".." || part.contains('/');
+        if [key.name.as_str(), key.version.as_str(), key.file_name()]
+            .into_iter()
+            .any(escapes)
+        {
+            return Err(HostError::InvalidRequestConfig(format!(
+                "Invalid key {}: the name, version and file must not leave the directory",
+                key.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Replace the artifacts and export the paths of the ones that are ready.
+pub fn configure(dir: PathBuf, keys: Vec<KeyConfig>) -> HostResult<()> {
+    validate(&keys)?;
+    for key in &keys {
+        if is_verified(key, &key.path(&dir)) {
+            export(key, &dir);
+        }
+    }
+    *KEYS.write().unwrap() = (dir, keys);
+    Ok(())
+}
+
+/// The path of the artifact if it is ready.
+pub fn path(name: &str) -> Option<PathBuf> {
+    let (dir, keys) = &*KEYS.read().unwrap();
+    let key = keys.iter().find(|key| key.name == name)?;
+    let path = key.path(dir);
+    is_verified(key, &path).then_some(path)
+}
+
+/// Set the environment variable of the artifact to its path.
+fn export(key: &KeyConfig, dir: &Path) {
+    if let Some(env) = &key.env {
+        let path = key.path(dir);
+        // The SDKs read the variables when they look for the file.
+        std::env::set_var(env, &path);
+        info!("Using {} {} from {}", key.name, key.version, path.display());
+    }
+}
+
+/// The path with the suffix appended, e.g. `srs.bin.sha256`.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// The SHA-256 checksum of the file.
+fn checksum(path: &Path) -> std::io::Result<B256> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 20];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(B256::from_slice(&hasher.finalize()))
+}
+
+/// Whether the file was verified to have the checksum of the artifact.
+fn is_verified(key: &KeyConfig, path: &Path) -> bool {
+    path.is_file()
+        && fs::read_to_string(with_suffix(path, VERIFIED_SUFFIX))
+            .is_ok_and(|mark| mark.trim() == key.sha256.to_string())
+}
+
+/// Hash the file and mark it as verified if it has the checksum of the artifact.
+fn verify(key: &KeyConfig, path: &Path) -> HostResult<()> {
+    let sha256 = checksum(path)?;
+    if sha256 != key.sha256 {
+        return Err(HostError::Anyhow(anyhow::anyhow!(
+            "{} has the checksum {sha256} instead of {}",
+            path.display(),
+            key.sha256
+        )));
+    }
+    fs::write(with_suffix(path, VERIFIED_SUFFIX), sha256.to_string())?;
+    Ok(())
+}
+
+/// Download the artifact to its path, hashing it on the way.
+async fn download(client: &reqwest::Client, key: &KeyConfig, path: &Path) -> HostResult<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let partial = with_suffix(path, PARTIAL_SUFFIX);
+    info!("Downloading {} {} from {}", key.name, key.version, key.url);
+    let mut response = client
+        .get(&key.url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| HostError::Anyhow(e.into()))?;
+    let mut file = tokio::fs::File::create(&partial).await?;
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| HostError::Anyhow(e.into()))?
+    {
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    let sha256 = B256::from_slice(&hasher.finalize());
+    if sha256 != key.sha256 {
+        let _ = fs::remove_file(&partial);
+        return Err(HostError::Anyhow(anyhow::anyhow!(
+            "The download of {} has the checksum {sha256} instead of {}",
+            key.url,
+            key.sha256
+        )));
+    }
+    fs::rename(&partial, path)?;
+    fs::write(with_suffix(path, VERIFIED_SUFFIX), sha256.to_string())?;
+    Ok(())
+}
+
+/// Make the artifact ready: verify the file if it isn't marked yet, and download it if it is
+/// missing or corrupt, unless only verifying.
+async fn prepare(
+    client: &reqwest::Client,
+    dir: &Path,
+    key: &KeyConfig,
+    download_missing: bool,
+) -> KeyStatus {
+    let path = key.path(dir);
+    let mut status = KeyStatus {
+        name: key.name.clone(),
+        version: key.version.clone(),
+        path: path.clone(),
+        ready: false,
+        downloaded: false,
+        error: None,
+    };
+    let result = if is_verified(key, &path) {
+        Ok(())
+    } else if path.is_file() {
+        match verify(key, &path) {
+            Err(e) if download_missing => {
+                warn!("Downloading {} again: {e}", key.name);
+                status.downloaded = true;
+                download(client, key, &path).await
+            }
+            result => result,
+        }
+    } else if download_missing {
+        status.downloaded = true;
+        download(client, key, &path).await
+    } else {
+        Err(HostError::Anyhow(anyhow::anyhow!(
+            "{} is missing",
+            path.display()
+        )))
+    };
+    match result {
+        Ok(()) => {
+            status.ready = true;
+            export(key, dir);
+        }
+        Err(e) => status.error = Some(e.to_string()),
+    }
+    status
+}
+
+/// Make all artifacts ready, one after the other.
+pub async fn prepare_all(download_missing: bool) -> Vec<KeyStatus> {
+    let (dir, keys) = KEYS.read().unwrap().clone();
+    let client = reqwest::Client::new();
+    let mut statuses = Vec::new();
+    for key in &keys {
+        statuses.push(prepare(&client, &dir, key, download_missing).await);
+    }
+    statuses
+}
+
+/// Download the missing artifacts in the background.
+pub fn spawn_fetch() {
+    if KEYS.read().unwrap().1.is_empty() {
+        return;
+    }
+    tokio::spawn(async {
+        for status in prepare_all(true).await {
+            if let Some(error) = status.error {
+                warn!("The key {} is not ready: {error}", status.name);
+            }
+        }
+    });
+}
+
+/// Remove the versions of the artifacts that are not configured.
+fn prune(dir: &Path, keys: &[KeyConfig]) -> HostResult<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    let Ok(names) = fs::read_dir(dir) else {
+        return Ok(removed);
+    };
+    for name in names.flatten() {
+        let name_path = name.path();
+        if !name_path.is_dir() {
+            continue;
+        }
+        for version in fs::read_dir(&name_path)?.flatten() {
+            let configured = keys.iter().any(|key| {
+                name.file_name() == key.name.as_str() && version.file_name() == key.version.as_str()
+            });
+            if !configured && version.path().is_dir() {
+                fs::remove_dir_all(version.path())?;
+                removed.push(version.path());
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/// Fetch and verify all artifacts of the config file, see `raiko-host setup`.
+pub async fn setup_command(opts: &Cli, args: &SetupArgs) -> HostResult<()> {
+    configure(opts.keys_dir.clone(), opts.keys.clone())?;
+    let statuses = prepare_all(!args.verify).await;
+    println!("{}", serde_json::to_string_pretty(&statuses)?);
+    if args.prune {
+        for path in prune(&opts.keys_dir, &opts.keys)? {
+            println!("# Removed {}", path.display());
+        }
+    }
+    let failed: Vec<&str> = statuses
+        .iter()
+        .filter(|status| !status.ready)
+        .map(|status| status.name.as_str())
+        .collect();
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(HostError::Anyhow(anyhow::anyhow!(
+            "Keys not ready: {}",
+            failed.join(", ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_prepare() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = |version: &str| KeyConfig {
+            name: "srs".to_string(),
+            version: version.to_string(),
+            url: "http://localhost:1/keys/srs.bin".to_string(),
+            sha256: B256::from_slice(&Sha256::digest(b"srs")),
+            file: None,
+            env: None,
+        };
+        let path = key("v1").path(dir.path());
+        assert_eq!(path, dir.path().join("srs/v1/srs.bin"));
+        let client = reqwest::Client::new();
+
+        // A missing file isn't downloaded when only verifying.
+        let status = prepare(&client, dir.path(), &key("v1"), false).await;
+        assert!(!status.ready && status.error.is_some());
+
+        // A file put in place, e.g. copied to an air-gapped machine, is verified and marked.
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, b"srs").unwrap();
+        assert!(!is_verified(&key("v1"), &path));
+        let status = prepare(&client, dir.path(), &key("v1"), false).await;
+        assert!(status.ready && !status.downloaded);
+        assert!(is_verified(&key("v1"), &path));
+
+        // A corrupt file isn't accepted.
+        let mut corrupt = key("v1");
+        corrupt.sha256 = B256::ZERO;
+        assert!(!is_verified(&corrupt, &path));
+        assert!(verify(&corrupt, &path).is_err());
+
+        fs::create_dir_all(dir.path().join("srs/v0")).unwrap();
+        assert_eq!(
+            prune(dir.path(), &[key("v1")]).unwrap(),
+            vec![dir.path().join("srs/v0")]
+        );
+        assert!(path.is_file());
+    }
+
+    #[test]
+    fn test_validate() {
+        let key = |name: &str, file: Option<&str>| KeyConfig {
+            name: name.to_string(),
+            version: "v1".to_string(),
+            url: "https://example.com/keys/".to_string(),
+            sha256: B256::ZERO,
+            file: file.map(str::to_string),
+            env: None,
+        };
+        assert_eq!(key("pk", None).file_name(), "keys");
+        assert!(validate(&[key("pk", None), key("srs", None)]).is_ok());
+        assert!(validate(&[key("pk", None), key("pk", None)]).is_err());
+        assert!(validate(&[key("pk", Some("../pk"))]).is_err());
+        assert!(validate(&[key("..", None)]).is_err());
+    }
+}
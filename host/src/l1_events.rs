@@ -0,0 +1,259 @@
+//! Reading the events of the TaikoL1 contract on L1.
+//!
+//! The `BlockProposed` events are read by the preflight for the tx list of a block, by the
+//! [watchers](crate::watcher) for the new blocks and, before a block is proven, to check its
+//! input against the proposal on L1. The input of a block may come from the input cache or a
+//! misbehaving node, so before hours are spent proving it, the event of the block is read again
+//! from the L1 block after the anchor block of the input, and its metadata and tx list hash are
+//! compared with the input. A mismatch fails the request with `proposal_mismatch`. Soft blocks
+//! are not proposed yet, only their anchor block is checked to be on L1.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use alloy_consensus::Sealable;
+use alloy_primitives::{Address, Log, B256};
+use alloy_provider::{Provider, ProviderBuilder, ReqwestProvider, RootProvider};
+use alloy_rpc_types::{Filter, Log as RpcLog};
+use alloy_sol_types::{SolEvent, SolValue};
+use anyhow::{anyhow, Result};
+use raiko_client::ErrorCode;
+use raiko_lib::input::{
+    taiko_a6::BlockProposed as TestnetBlockProposed, BlockProposed, GuestInput,
+};
+use raiko_primitives::keccak::keccak;
+use tracing::debug;
+
+use crate::{
+    chain_registry::{get_chain, ChainConfig},
+    error::{HostError, HostResult},
+    fixtures,
+    request::ProofRequest,
+};
+
+static CHECK_INCLUSION: AtomicBool = AtomicBool::new(true);
+
+/// Set whether the inputs are checked against their proposal on L1 before proving them.
+pub fn configure(check_inclusion: bool) {
+    CHECK_INCLUSION.store(check_inclusion, Ordering::Relaxed);
+}
+
+/// Decode a `BlockProposed` log of the TaikoL1 contract, of the current or the testnet version
+/// of the event.
+pub fn decode_block_proposed(log: &RpcLog) -> Result<BlockProposed> {
+    let log = Log::new(
+        log.address(),
+        log.topics().to_vec(),
+        log.data().data.clone(),
+    )
+    .ok_or_else(|| anyhow!("Invalid BlockProposed log"))?;
+    if log.topics().first() == Some(&TestnetBlockProposed::SIGNATURE_HASH) {
+        Ok(TestnetBlockProposed::decode_log(&log, false)?.data.into())
+    } else {
+        Ok(BlockProposed::decode_log(&log, false)?.data)
+    }
+}
+
+/// Find the `BlockProposed` event of the L2 block in the L1 block, with the hash of the
+/// transaction proposing it. There can be multiple blocks proposed in the same L1 block and
+/// even in the same transaction.
+pub async fn find_block_proposed(
+    provider: &ReqwestProvider,
+    testnet: bool,
+    l1_address: Address,
+    l1_block_hash: B256,
+    l2_block_number: u64,
+) -> Result<Option<(B256, BlockProposed)>> {
+    // The event signature differs between the chains
+    let event_signature = if testnet {
+        TestnetBlockProposed::SIGNATURE_HASH
+    } else {
+        BlockProposed::SIGNATURE_HASH
+    };
+    let filter = Filter::new()
+        .address(l1_address)
+        .at_block_hash(l1_block_hash)
+        .event_signature(event_signature);
+    for log in provider.get_logs(&filter).await? {
+        let event = decode_block_proposed(&log)?;
+        if event.blockId == raiko_primitives::U256::from(l2_block_number) {
+            let tx_hash = log
+                .transaction_hash
+                .ok_or_else(|| anyhow!("BlockProposed log without its transaction"))?;
+            return Ok(Some((tx_hash, event)));
+        }
+    }
+    Ok(None)
+}
+
+/// Check the input of the block of the request against the `BlockProposed` event of the block
+/// on L1, failing with `proposal_mismatch` when the block was not proposed like the input says.
+pub async fn check_inclusion(proof_request: &ProofRequest, input: &GuestInput) -> HostResult<()> {
+    let Some(chain) = checked_chain(proof_request, input)? else {
+        return Ok(());
+    };
+    // The contract of the chain, the chain spec of the input is not trusted
+    let l1_address = chain.chain_spec.l1_contract.ok_or_else(|| {
+        anyhow!(
+            "Missing the L1 contract in the chain spec of {}",
+            chain.name
+        )
+    })?;
+    let testnet = chain
+        .chain_spec
+        .fork_rules(input.block_number, input.timestamp)?
+        .testnet_proposal();
+
+    // The block is proposed in the L1 block after the anchor block of its input.
+    let l1_block_number = input.taiko.l1_header.number + 1;
+    let provider = l1_provider(proof_request)?;
+    let l1_block_hash = l1_block_hash(&provider, l1_block_number).await?;
+    let proposed = find_block_proposed(
+        &provider,
+        testnet,
+        l1_address,
+        l1_block_hash,
+        input.block_number,
+    )
+    .await?;
+
+    let result = match proposed {
+        Some((_, proposed)) => check_proposal(input, &proposed),
+        None => Err(format!(
+            "Block {} was not proposed in L1 block {l1_block_number}, the block after the \
+             anchor block of its input",
+            input.block_number
+        )),
+    };
+    result.map_err(|detail| {
+        HostError::invalid(ErrorCode::ProposalMismatch, "block_number", detail)
+    })?;
+    debug!(
+        "Block {} is proposed in L1 block {l1_block_number} like its input says",
+        input.block_number
+    );
+    Ok(())
+}
+
+/// Check the input of a soft block against L1, which has to have the anchor block of the input.
+/// The block is not proposed yet, so there is no proposal to check it against.
+pub async fn check_soft_block(proof_request: &ProofRequest, input: &GuestInput) -> HostResult<()> {
+    if checked_chain(proof_request, input)?.is_none() {
+        return Ok(());
+    }
+    let l1_header = &input.taiko.l1_header;
+    let provider = l1_provider(proof_request)?;
+    let l1_block_hash = l1_block_hash(&provider, l1_header.number).await?;
+    if l1_block_hash != l1_header.hash() {
+        return Err(HostError::invalid(
+            ErrorCode::InvalidBlock,
+            "block",
+            format!(
+                "The anchor block {} of block {} is not L1 block {l1_block_hash}",
+                l1_header.number, input.block_number
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// The chain of the request if its inputs are checked against L1.
+fn checked_chain(
+    proof_request: &ProofRequest,
+    input: &GuestInput,
+) -> HostResult<Option<ChainConfig>> {
+    let chain = get_chain(&proof_request.network)?;
+    if !CHECK_INCLUSION.load(Ordering::Relaxed) || !chain.network.is_taiko() {
+        return Ok(None);
+    }
+    // Only the responses of the nodes to the preflight are in the fixtures.
+    if fixtures::fixture_path(&chain.name, input.block_number).is_some() {
+        return Ok(None);
+    }
+    Ok(Some(chain))
+}
+
+fn l1_provider(proof_request: &ProofRequest) -> HostResult<ReqwestProvider> {
+    Ok(ProviderBuilder::new().provider(RootProvider::new_http(
+        reqwest::Url::parse(&proof_request.l1_rpc).map_err(|e| anyhow!(e))?,
+    )))
+}
+
+async fn l1_block_hash(provider: &ReqwestProvider, l1_block_number: u64) -> HostResult<B256> {
+    Ok(provider
+        .get_block_by_number(l1_block_number.into(), false)
+        .await
+        .map_err(|e| anyhow!(e))?
+        .and_then(|block| block.header.hash)
+        .ok_or_else(|| anyhow!("No data for L1 block {l1_block_number}"))?)
+}
+
+/// Check the input of the block matches the `BlockProposed` event of the block.
+fn check_proposal(input: &GuestInput, proposed: &BlockProposed) -> Result<(), String> {
+    let block_number = input.block_number;
+    let meta = &input.taiko.block_proposed.meta;
+
+    let l1_hash = input.taiko.l1_header.hash();
+    if proposed.meta.l1Hash != l1_hash {
+        return Err(format!(
+            "Block {block_number} was proposed with the L1 block {}, not with the anchor block \
+             {l1_hash} of its input",
+            proposed.meta.l1Hash
+        ));
+    }
+
+    let meta_hash: B256 = keccak(meta.abi_encode()).into();
+    let proposed_meta_hash: B256 = keccak(proposed.meta.abi_encode()).into();
+    if meta_hash != proposed_meta_hash {
+        return Err(format!(
+            "The meta hash {meta_hash} of the input of block {block_number} doesn't match the \
+             meta hash {proposed_meta_hash} of its BlockProposed event"
+        ));
+    }
+
+    let tx_list_hash = if meta.blobUsed {
+        input.taiko.tx_blob_hash.unwrap_or_default()
+    } else {
+        keccak(input.taiko.tx_list.as_slice()).into()
+    };
+    if tx_list_hash != proposed.meta.blobHash {
+        return Err(format!(
+            "The tx list hash {tx_list_hash} of the input of block {block_number} doesn't match \
+             the tx list hash {} of its BlockProposed event",
+            proposed.meta.blobHash
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_proposal() {
+        let mut input = GuestInput {
+            block_number: 1000,
+            ..Default::default()
+        };
+        input.taiko.tx_list = vec![0xc0];
+        input.taiko.block_proposed.meta.l1Hash = input.taiko.l1_header.hash();
+        input.taiko.block_proposed.meta.blobHash = keccak(input.taiko.tx_list.as_slice()).into();
+        let proposed = input.taiko.block_proposed.clone();
+        assert_eq!(check_proposal(&input, &proposed), Ok(()));
+
+        let mut tampered = input.clone();
+        tampered.taiko.tx_list = vec![0xc1];
+        let error = check_proposal(&tampered, &proposed).unwrap_err();
+        assert!(error.starts_with("The tx list hash"), "{error}");
+
+        let mut tampered = input.clone();
+        tampered.taiko.block_proposed.meta.gasLimit = 1;
+        let error = check_proposal(&tampered, &proposed).unwrap_err();
+        assert!(error.starts_with("The meta hash"), "{error}");
+
+        let mut other = proposed.clone();
+        other.meta.l1Hash = B256::repeat_byte(1);
+        let error = check_proposal(&input, &other).unwrap_err();
+        assert!(error.contains("not with the anchor block"), "{error}");
+    }
+}
@@ -12,23 +12,105 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod api_keys;
+pub mod artifacts;
+pub mod bench;
+pub mod blob_provider;
+pub mod breakers;
+pub mod builder;
+pub mod callback;
+pub mod calldata;
+pub mod chain_registry;
+pub mod clock;
+pub mod composite;
+pub mod config_reload;
+pub mod costs;
+#[cfg(unix)]
+pub mod daemons;
 pub mod error;
+pub mod estimate;
 pub mod execution;
+pub mod faults;
+pub mod finality;
+pub mod fixtures;
+pub mod gc;
+pub mod gpu;
+pub mod guests;
+pub mod header_store;
+pub mod images;
+pub mod input_cache;
+pub mod input_codec;
+pub mod isolation;
+pub mod jobs;
+pub mod jwt;
+pub mod keys;
+pub mod l1_events;
+pub mod messaging;
 pub mod metrics;
+pub mod mock;
 pub mod preflight;
+pub mod proof_cache;
+pub mod proof_output;
 pub mod provider_db;
+pub mod provider_pool;
+pub mod queue;
+pub mod rate_limit;
+pub mod readiness;
+pub mod registry;
+pub mod relayer;
+pub mod reorgs;
 pub mod request;
+pub mod retry;
+pub mod runtime_config;
+pub mod runtimes;
 pub mod server;
+pub mod soft_block;
+pub mod trace;
+pub mod upload;
+pub mod verify;
+pub mod watcher;
+pub mod workers;
+pub mod workspace;
 
-use std::{alloc, fmt::Debug, path::PathBuf};
+use std::{alloc, collections::BTreeMap, fmt::Debug, path::PathBuf, time::Duration};
 
 use anyhow::{Context, Result};
 use cap::Cap;
-use clap::Parser;
+use clap::{builder::RangedU64ValueParser, Args, Parser, Subcommand};
+use raiko_signer::SignerConfig;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::{error::HostError, request::ProofRequestOpt};
+use crate::{
+    api_keys::{ApiKeyConfig, ApiKeys},
+    blob_provider::BlobSource,
+    breakers::BreakerConfig,
+    costs::CostConfig,
+    error::HostError,
+    finality::FinalityConfig,
+    fixtures::FixtureMode,
+    gc::GcConfig,
+    guests::GuestVersion,
+    input_cache::InputCache,
+    isolation::JobLimits,
+    jobs::JobManager,
+    jwt::JwtAuth,
+    keys::KeyConfig,
+    messaging::MessagingConfig,
+    proof_cache::{ProofCache, ProofCacheKind},
+    provider_pool::Selection,
+    queue::{open_task_store, TaskStoreKind},
+    rate_limit::{RateLimitConfig, RateLimiter},
+    relayer::{Relayer, RelayerConfig},
+    reorgs::ReorgConfig,
+    request::ProofRequestOpt,
+    retry::RetryConfig,
+    runtime_config::RuntimeSettings,
+    server::TlsConfig,
+    trace::LogFormat,
+    upload::{UploadConfig, Uploader},
+    watcher::{Watcher, WatcherConfig},
+};
 
 #[global_allocator]
 static ALLOCATOR: Cap<alloc::System> = Cap::new(alloc::System, usize::MAX);
@@ -49,6 +131,54 @@ fn default_config_path() -> PathBuf {
     PathBuf::from("host/config/config.json")
 }
 
+fn default_rpc_health_check_interval() -> u64 {
+    30
+}
+
+fn default_pushgateway_interval() -> u64 {
+    60
+}
+
+fn default_header_sync_interval() -> u64 {
+    12
+}
+
+fn default_deadline_margin() -> u64 {
+    600
+}
+
+fn default_preflight_concurrency() -> usize {
+    4
+}
+
+fn default_drain_timeout() -> u64 {
+    300
+}
+
+fn default_worker_timeout() -> u64 {
+    30
+}
+
+fn default_blob_sources() -> Vec<BlobSource> {
+    vec![
+        BlobSource::Archive,
+        BlobSource::Beacon,
+        BlobSource::Blobscan,
+    ]
+}
+
+fn default_fixture_dir() -> PathBuf {
+    PathBuf::from("fixtures")
+}
+
+fn default_keys_dir() -> PathBuf {
+    PathBuf::from("keys")
+}
+
+fn default_proof_cache_size() -> usize {
+    128
+}
+
 fn default_log_level() -> String {
     "info".to_string()
 }
@@ -58,6 +188,11 @@ fn default_log_level() -> String {
 #[command(about = "The taiko prover host", long_about = None)]
 #[serde(default)]
 pub struct Cli {
+    #[command(subcommand)]
+    #[serde(skip)]
+    /// Run a command instead of serving
+    pub command: Option<Command>,
+
     #[arg(long, require_equals = true, default_value = "0.0.0.0:8080")]
     #[serde(default = "default_address")]
     /// Server bind address
@@ -69,6 +204,197 @@ pub struct Cli {
     /// Limit the max number of in-flight requests
     pub concurrency_limit: usize,
 
+    #[arg(skip)]
+    /// Limit the max number of in-flight requests per prover, e.g. `{"sgx": 1, "risc0": 2}`.
+    /// Only read from the config file. Provers without a limit are only bound by
+    /// `concurrency_limit`.
+    pub prover_concurrency: BTreeMap<String, usize>,
+
+    #[arg(skip)]
+    /// The CUDA devices of the GPU backed provers, e.g. `{"risc0": [0, 1], "sp1": [2]}`. Every
+    /// proof of these provers gets a device for itself. Only read from the config file.
+    pub cuda_devices: BTreeMap<String, Vec<u32>>,
+
+    #[arg(long, require_equals = true, value_delimiter = ',')]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    /// Prove with these provers in a child process, e.g. `risc0,sp1`, so a segfault or an
+    /// abort of a prover only fails its proof.
+    pub isolate_provers: Vec<String>,
+
+    #[arg(long, require_equals = true, value_delimiter = ',')]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    /// Prove with these provers in long lived child processes, e.g. `risc0,sp1`, which set up
+    /// the prover once instead of for every proof.
+    pub prover_daemons: Vec<String>,
+
+    #[arg(skip)]
+    /// The memory in MiB and the threads of every proof of a prover, e.g. `{"risc0":
+    /// {"memory": 65536, "threads": 16}}`. These provers prove in a child process, see
+    /// `--isolate-provers`, which fails alone when it runs out of memory. Only read from the
+    /// config file.
+    pub job_limits: BTreeMap<String, JobLimits>,
+
+    #[arg(long, require_equals = true, default_value = "4")]
+    #[serde(default = "default_preflight_concurrency")]
+    /// Max number of jobs preparing their input or waiting for a prover slot with it, so the
+    /// inputs of the next blocks are prepared while the provers are busy.
+    pub preflight_concurrency: usize,
+
+    #[arg(long, require_equals = true)]
+    /// Reject new proof requests with 503 and a `Retry-After` once this many jobs are preparing
+    /// their input or waiting for a prover slot, and report not ready at `/readyz`.
+    pub max_queue_depth: Option<usize>,
+
+    #[arg(
+        long,
+        require_equals = true,
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    /// Threads of the runtime serving the API and preparing the inputs. Defaults to the number
+    /// of cores.
+    pub worker_threads: Option<usize>,
+
+    #[arg(
+        long,
+        require_equals = true,
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    /// Max threads of the runtime for blocking work, e.g. the native execution of the blocks.
+    /// Defaults to 512.
+    pub max_blocking_threads: Option<usize>,
+
+    #[arg(
+        long,
+        require_equals = true,
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    /// Run the provers on a runtime of their own with this many threads, so the API stays
+    /// responsive while the provers saturate the CPU.
+    pub prover_threads: Option<usize>,
+
+    #[arg(long, require_equals = true, default_value = "600")]
+    #[serde(default = "default_deadline_margin")]
+    /// Seconds before the deadline of a request it is proven before all other requests
+    pub deadline_margin: u64,
+
+    #[arg(long, require_equals = true, default_value = "300")]
+    #[serde(default = "default_drain_timeout")]
+    /// Seconds the running jobs get to finish on shutdown before they are aborted, to be
+    /// proven again on the next start.
+    pub drain_timeout: u64,
+
+    #[arg(long, require_equals = true)]
+    /// Reject proof requests for blocks more than this many blocks behind the head of the
+    /// chain. Blocks after the head are always rejected.
+    pub max_block_age: Option<u64>,
+
+    #[arg(skip)]
+    /// Retry policy for failed proof requests, e.g. `{"max_attempts": 3, "provers": {"sgx":
+    /// {"max_attempts": 5, "retry_prover_errors": true}}}`. Only read from the config file.
+    pub retry: Option<RetryConfig>,
+
+    #[arg(skip)]
+    /// Check the blocks of the jobs for reorgs and cancel the jobs of the reorged blocks, e.g.
+    /// `{"interval": 12, "webhook_url": "https://ops.example.com/reorgs"}`. Only read from the
+    /// config file.
+    pub reorg_monitor: Option<ReorgConfig>,
+
+    #[arg(skip)]
+    /// Only prove the blocks proposed in final L1 blocks, waiting for them or rejecting them
+    /// until they are, e.g. `{"confirmations": 12, "action": "wait"}`. Only read from the config
+    /// file.
+    pub finality: Option<FinalityConfig>,
+
+    #[arg(skip)]
+    /// Circuit breakers of the provers and the nodes of the networks, e.g.
+    /// `{"failure_threshold": 5, "open_duration": 60}`. Only read from the config file.
+    pub circuit_breaker: Option<BreakerConfig>,
+
+    #[arg(skip)]
+    /// Submit the proofs of the jobs to L1 from the account of `RELAYER_PRIV_KEY` or the
+    /// configured signer, e.g. `{"tiers": {"sgx": 200}, "confirmations": 2}`. Only read from
+    /// the config file.
+    pub relayer: Option<RelayerConfig>,
+
+    #[arg(skip)]
+    /// The signer of the SGX instance registrations, e.g. `{"type": "web3signer", "url":
+    /// "http://localhost:9000", "address": "0x..."}`. Defaults to the private key in
+    /// `SENDER_PRIV_KEY`. Only read from the config file.
+    pub sgx_signer: Option<SignerConfig>,
+
+    #[arg(skip)]
+    /// Upload the proofs of the jobs and their inputs to an S3 compatible bucket, e.g.
+    /// `{"bucket": "raiko-proofs", "prefix": "proofs/", "encryption": "AES256"}`. Only read
+    /// from the config file.
+    pub upload: Option<UploadConfig>,
+
+    #[arg(skip)]
+    /// Take proof requests from and publish the status changes of the jobs to a NATS or Kafka
+    /// broker, e.g. `{"broker": "nats", "url": "nats://localhost:4222", "requests":
+    /// "raiko.requests", "results": "raiko.results"}`. Only read from the config file.
+    pub messaging: Option<MessagingConfig>,
+
+    #[arg(skip)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    /// Prove the new blocks of chains without proof requests, e.g. `[{"network": "taiko_a7",
+    /// "proof_types": ["sgx"], "delay": 30}]`. Only read from the config file.
+    pub watchers: Vec<WatcherConfig>,
+
+    #[arg(skip)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    /// Versions of the Risc0 and SP1 guests besides the embedded ones, selected by the block
+    /// or by the `guest` of the proof request, e.g. `[{"name": "ontake", "proof_type": "risc0",
+    /// "elf": "ontake.elf", "program_id": "0x...", "from_block": 1000000}]`. Only read from the
+    /// config file.
+    pub guests: Vec<GuestVersion>,
+
+    #[arg(skip)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    /// The API keys of the clients, e.g. `[{"name": "alice", "key": "...", "rate_limit": 60,
+    /// "daily_quota": 100}]`. Without keys the API is open to everyone. Only read from the
+    /// config file.
+    pub api_keys: Vec<ApiKeyConfig>,
+
+    #[arg(skip)]
+    /// Rate limits of the proof requests, e.g. `{"requests_per_minute": 30, "burst": 10,
+    /// "max_in_flight": 100}`. Only read from the config file.
+    pub rate_limit: Option<RateLimitConfig>,
+
+    #[arg(long, require_equals = true)]
+    /// Path to a hex encoded 32 byte secret, e.g. the `jwt.hex` of the execution client. When
+    /// set, the proof endpoints need a HS256 JWT signed with it, like the Engine API.
+    pub jwt_secret: Option<PathBuf>,
+
+    #[arg(skip)]
+    /// Serve the REST API over TLS, e.g. `{"cert": "cert.pem", "key": "key.pem", "client_ca":
+    /// "ca.pem"}`. With `client_ca` clients need a certificate signed by it. Needs the `tls`
+    /// feature. Only read from the config file.
+    pub tls: Option<TlsConfig>,
+
+    #[arg(long, require_equals = true)]
+    /// Serve the gRPC API on this address as well, e.g. `0.0.0.0:50052`. Needs the `grpc`
+    /// feature.
+    pub grpc_address: Option<String>,
+
+    #[arg(long, require_equals = true)]
+    /// Run as a worker of the coordinator at this URL, e.g. `http://coordinator:8080`: register
+    /// the proof types of this host there and prove the blocks it sends.
+    pub coordinator: Option<String>,
+
+    #[arg(long, require_equals = true)]
+    /// The URL the coordinator reaches this worker at. Defaults to `http://<address>`.
+    pub advertise_url: Option<String>,
+
+    #[arg(long, require_equals = true)]
+    /// The API key the coordinator and its workers send each other. Needs to be an admin key
+    /// of the coordinator, which only serves `/admin` with one.
+    pub worker_api_key: Option<String>,
+
+    #[arg(long, require_equals = true, default_value = "30")]
+    #[serde(default = "default_worker_timeout")]
+    /// Seconds after the last heartbeat of a worker the coordinator drops it.
+    pub worker_timeout: u64,
+
     #[arg(long, require_equals = true)]
     pub log_path: Option<PathBuf>,
 
@@ -76,28 +402,368 @@ pub struct Cli {
     #[serde(default = "default_max_log")]
     pub max_log: usize,
 
+    #[arg(long)]
+    #[serde(default)]
+    /// Label the metrics by proof type, network and success only, not by block. The blocks of
+    /// the recent requests are listed at `/metrics/recent` instead.
+    pub bounded_metrics: bool,
+
+    #[arg(long)]
+    #[serde(default)]
+    /// Only report ready at `/readyz` once a GPU is visible, for hosts proving with CUDA.
+    pub require_gpu: bool,
+
+    #[arg(long)]
+    #[serde(default)]
+    /// Don't check the input of a block against its `BlockProposed` event on L1 before proving
+    /// it.
+    pub skip_inclusion_check: bool,
+
+    #[arg(skip)]
+    /// The rates the cost of the proofs is estimated with, e.g. `{"hourly": {"risc0": 3.2},
+    /// "bonsai_per_mcycle": 0.0005, "sp1_network_per_proof": 0.1}`. Only read from the config
+    /// file.
+    pub costs: Option<CostConfig>,
+
+    #[arg(long, require_equals = true)]
+    /// Path to the calibration profile written by the `bench` command. `/estimate` estimates
+    /// the cycles and the proving time of a block with it, and the scheduler starts the jobs
+    /// with a deadline by when their proof is expected to be done.
+    pub calibration_path: Option<PathBuf>,
+
+    #[arg(long, require_equals = true, value_enum)]
+    /// Record the responses of the nodes to the preflight of every block into a fixture in
+    /// `--fixture-dir` (record), or serve them from the fixture without the nodes (playback).
+    pub fixture_mode: Option<FixtureMode>,
+
+    #[arg(long, require_equals = true, default_value = "fixtures")]
+    #[serde(default = "default_fixture_dir")]
+    /// The directory of the fixtures of the blocks, see `--fixture-mode`.
+    pub fixture_dir: PathBuf,
+
+    #[arg(long, require_equals = true, default_value = "keys")]
+    #[serde(default = "default_keys_dir")]
+    /// The directory of the proving keys and the other large artifacts of the provers.
+    pub keys_dir: PathBuf,
+
+    #[arg(skip)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    /// The proving keys and the other large artifacts of the provers, e.g. `[{"name":
+    /// "groth16-srs", "version": "v1", "url": "https://...", "sha256": "0x...", "env":
+    /// "GROTH16_SRS_PATH"}]`, kept in `--keys-dir`. Only read from the config file.
+    pub keys: Vec<KeyConfig>,
+
+    #[arg(long, require_equals = true)]
+    /// Push the metrics to the Prometheus Pushgateway at this URL, e.g.
+    /// `http://localhost:9091`, periodically and when the host stops.
+    pub pushgateway_url: Option<String>,
+
+    #[arg(long, require_equals = true, default_value = "60")]
+    #[serde(default = "default_pushgateway_interval")]
+    /// Seconds between pushing the metrics to the Pushgateway.
+    pub pushgateway_interval: u64,
+
+    #[arg(long, require_equals = true)]
+    /// Write the metrics to this file as JSON when the host stops.
+    pub metrics_snapshot: Option<PathBuf>,
+
     #[arg(long, require_equals = true, default_value = "host/config/config.json")]
     #[serde(default = "default_config_path")]
     /// Path to a config file that includes sufficent json args to request
     /// a proof of specified type. Curl json-rpc overrides its contents
     config_path: PathBuf,
 
+    #[arg(long)]
+    #[serde(default)]
+    /// Reload the config file when it changes. It is always reloaded on SIGHUP.
+    watch_config: bool,
+
+    #[arg(long, require_equals = true)]
+    /// Path of the journal of the changes made through `/admin/config` and config reloads.
+    /// Defaults to `config-journal.jsonl` next to the config file.
+    config_journal: Option<PathBuf>,
+
     #[arg(long, require_equals = true)]
     /// Use a local directory as a cache for input. Accepts a custom directory.
     cache_path: Option<PathBuf>,
 
+    #[arg(long, require_equals = true)]
+    /// Directory for the checkpoints of the provers, so interrupted Risc0 and SP1 proofs
+    /// resume from them instead of starting over.
+    workspace_dir: Option<PathBuf>,
+
+    #[arg(long, require_equals = true)]
+    /// Max size of the input cache in MB. The oldest inputs are removed first.
+    cache_max_size: Option<u64>,
+
+    #[arg(long, require_equals = true)]
+    /// Max age of the cached inputs in hours.
+    cache_max_age: Option<u64>,
+
+    #[arg(long, require_equals = true, env = "CALLBACK_SECRET")]
+    /// Secret the callbacks of the jobs are signed with, see `callback_url` in the proof
+    /// requests.
+    callback_secret: Option<String>,
+
+    #[arg(long, require_equals = true)]
+    /// Directory to keep the input, checkpoints, logs and proof of every job in, listed at
+    /// `GET /v2/jobs/{id}/artifacts`.
+    artifacts_dir: Option<PathBuf>,
+
+    #[arg(long, require_equals = true)]
+    /// Max size of the job artifacts in MB. The artifacts of the oldest jobs are removed first.
+    artifacts_max_size: Option<u64>,
+
+    #[arg(long, require_equals = true)]
+    /// Max age of the job artifacts in hours.
+    artifacts_max_age: Option<u64>,
+
+    #[arg(skip)]
+    /// Garbage collection of the files of the host, e.g. `{"interval": 600, "min_free_space":
+    /// 20480, "max_workspace_age": 48}`. Only read from the config file.
+    pub gc: Option<GcConfig>,
+
+    #[arg(long, require_equals = true)]
+    /// Path to a JSON list of additional chains to serve proofs for.
+    chain_spec_path: Option<PathBuf>,
+
+    #[arg(long, require_equals = true)]
+    /// Directory with a JSON or TOML chain spec file per additional chain.
+    chain_spec_dir: Option<PathBuf>,
+
+    #[arg(long, require_equals = true, value_enum, default_value_t = Selection::RoundRobin)]
+    /// How the preflight picks one of the endpoints of a node (round-robin or least-latency)
+    rpc_selection: Selection,
+
+    #[arg(long, require_equals = true, default_value = "30")]
+    #[serde(default = "default_rpc_health_check_interval")]
+    /// Seconds between the health checks of nodes with several endpoints, 0 disables them.
+    rpc_health_check_interval: u64,
+
+    #[arg(long, require_equals = true, default_value = "12")]
+    #[serde(default = "default_header_sync_interval")]
+    /// Seconds between syncing the recent headers of the Taiko chains with a default RPC URL,
+    /// 0 disables the sync.
+    header_sync_interval: u64,
+
+    #[arg(
+        long,
+        require_equals = true,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "archive,beacon,blobscan"
+    )]
+    #[serde(default = "default_blob_sources")]
+    /// The sources of blobs in priority order (archive, beacon and blobscan). Sources a chain
+    /// doesn't have are skipped.
+    blob_sources: Vec<BlobSource>,
+
+    #[arg(long, require_equals = true)]
+    /// Directory of the local blob archive. Fetched blobs are kept in the archive.
+    blob_archive_dir: Option<PathBuf>,
+
+    #[arg(long, require_equals = true, value_enum, default_value_t = TaskStoreKind::Memory)]
+    /// The store used to persist proof jobs across restarts (memory, sqlite or rocksdb)
+    task_store: TaskStoreKind,
+
+    #[arg(long, require_equals = true)]
+    /// Path to the task store database. Required for the sqlite and rocksdb task stores.
+    task_db_path: Option<PathBuf>,
+
+    #[arg(long, require_equals = true, value_enum, default_value_t = ProofCacheKind::Disabled)]
+    /// The cache for generated proofs (disabled, memory, disk or redis)
+    proof_cache: ProofCacheKind,
+
+    #[arg(long, require_equals = true)]
+    /// Directory of the disk proof cache.
+    proof_cache_path: Option<PathBuf>,
+
+    #[arg(long, require_equals = true)]
+    /// Url of the Redis server of the redis proof cache.
+    proof_cache_url: Option<String>,
+
+    #[arg(long, require_equals = true, default_value = "128")]
+    #[serde(default = "default_proof_cache_size")]
+    /// The max number of proofs kept by the memory proof cache.
+    proof_cache_size: usize,
+
+    #[arg(long, require_equals = true)]
+    /// Url of the PCCS to verify SGX quotes with. Defaults to the Intel PCS.
+    sgx_pccs_url: Option<String>,
+
     #[arg(long, require_equals = true, env = "RUST_LOG", default_value = "info")]
     #[serde(default = "default_log_level")]
     /// Set the log level
     pub log_level: String,
 
+    #[arg(long, require_equals = true, value_enum, default_value_t = LogFormat::Text)]
+    #[serde(default)]
+    /// The format of the logs on stdout (text or json). The log files are always JSON.
+    pub log_format: LogFormat,
+
     #[command(flatten)]
     #[serde(flatten)]
     /// Proof request options
     pub proof_request_opt: ProofRequestOpt,
 }
 
+#[derive(Clone, Debug, Subcommand)]
+pub enum Command {
+    /// Verify a proof produced before against the public inputs of its block, without proving
+    /// the block again. The block is taken from the proof request options.
+    Verify(VerifyArgs),
+    /// Prove a block and exit, without running the server: the block given with `--block`, or
+    /// the block of an input saved before with `--input`, e.g. a file of the input cache,
+    /// without the preflight. The other options are taken from the proof request options.
+    Prove(ProveArgs),
+    /// Count the cycles the precompiles of the zkVM of the proof type save, by running each of
+    /// them next to a software implementation in the guest.
+    BenchPrecompiles(BenchArgs),
+    /// Prove a set of reference blocks with the provers of the host and write the calibration
+    /// profile of the machine, which the estimates and the scheduler expect the proofs to take.
+    Bench(CalibrateArgs),
+    /// Check the programs of the provers.
+    Image(ImageArgs),
+    /// Download and verify the proving keys and the other artifacts of the config file into
+    /// `--keys-dir`, e.g. to copy them to an air-gapped machine.
+    Setup(SetupArgs),
+    /// Prove the blocks the host sends over the socket, see `--prover-daemons`.
+    #[cfg(unix)]
+    #[command(hide = true)]
+    ProverDaemon(DaemonArgs),
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct VerifyArgs {
+    #[arg(long, require_equals = true)]
+    /// The file with the proof, as returned for the proof request
+    pub proof: PathBuf,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct ProveArgs {
+    #[arg(long, require_equals = true, conflicts_with = "block")]
+    /// The file with the input, e.g. `input-taiko_a7-1000-<block hash>.bin.zst` of the input
+    /// cache
+    pub input: Option<PathBuf>,
+
+    #[arg(long, require_equals = true)]
+    /// The block to prove, after preparing its input with the preflight. Defaults to the block
+    /// number of the proof request options
+    pub block: Option<u64>,
+
+    #[arg(long, require_equals = true)]
+    /// The chain of the block, instead of the network of the proof request options
+    pub network: Option<String>,
+
+    #[arg(long, require_equals = true)]
+    /// The prover, instead of the proof type of the proof request options
+    pub proof_type: Option<String>,
+
+    #[arg(long, require_equals = true, requires = "input")]
+    /// The file with the proof request of the input, instead of the proof request options
+    pub request: Option<PathBuf>,
+
+    #[arg(long, require_equals = true, visible_alias = "out")]
+    /// The file to write the proof to. Defaults to printing it
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct SetupArgs {
+    #[arg(long)]
+    /// Only verify the artifacts in the directory, without downloading the missing ones
+    pub verify: bool,
+
+    #[arg(long)]
+    /// Remove the versions of the artifacts that are not in the config file
+    pub prune: bool,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct DaemonArgs {
+    #[arg(long, require_equals = true)]
+    /// The Unix socket the host sends the proofs to
+    pub socket: PathBuf,
+
+    #[arg(long, require_equals = true)]
+    /// The prover of the daemon, set up before the first proof
+    pub prover: String,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct BenchArgs {
+    #[arg(long, require_equals = true, default_value_t = 10)]
+    /// How many times every precompile is run
+    pub iterations: u32,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct CalibrateArgs {
+    #[arg(long, require_equals = true, value_delimiter = ',', required = true)]
+    /// The reference blocks to prove, ideally from empty to full blocks
+    pub blocks: Vec<u64>,
+
+    #[arg(long, require_equals = true, value_delimiter = ',')]
+    /// The provers to calibrate. Defaults to every prover enabled on the host
+    pub proof_types: Vec<String>,
+
+    #[arg(long, require_equals = true, default_value = "calibration.json")]
+    /// The calibration profile to write, the calibrations of other provers in it are kept
+    pub output: PathBuf,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct ImageArgs {
+    #[command(subcommand)]
+    pub command: ImageCommand,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum ImageCommand {
+    /// Rebuild the guest of the proof type reproducibly in docker, compute its program id and
+    /// compare it with the program id of the host and, with `--verifier`, with the ones trusted
+    /// on-chain.
+    Verify(ImageVerifyArgs),
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct ImageVerifyArgs {
+    #[arg(long, require_equals = true)]
+    /// The prover of the guest, risc0 or sp1, instead of the proof type of the proof request
+    /// options
+    pub proof_type: Option<String>,
+
+    #[arg(long, require_equals = true, default_value = ".")]
+    /// The raiko checkout to build the guest from
+    pub source: PathBuf,
+
+    #[arg(long, require_equals = true)]
+    /// Check this ELF instead of rebuilding the guest
+    pub elf: Option<PathBuf>,
+
+    #[arg(long, require_equals = true)]
+    /// The URL of the running host to compare with, e.g. `http://localhost:8080`. Defaults to
+    /// the guest built into this binary
+    pub host: Option<String>,
+
+    #[arg(long, require_equals = true)]
+    /// The address of the verifier of the proof type on L1, to check that it trusts the program
+    pub verifier: Option<String>,
+
+    #[arg(long, require_equals = true)]
+    /// The L1 node to call the verifier on, instead of the L1 node of the proof request options
+    pub l1_rpc: Option<String>,
+}
+
 impl Cli {
+    /// Read the command line arguments and the config file.
+    pub fn load() -> Result<Self, HostError> {
+        let mut opts = Cli::parse();
+        opts.merge_from_file()?;
+        Ok(opts)
+    }
+
     /// Read the options from a file and merge it with the current options.
     pub fn merge_from_file(&mut self) -> Result<(), HostError> {
         let file = std::fs::File::open(&self.config_path)?;
@@ -105,9 +771,40 @@ impl Cli {
         let mut config: Value = serde_json::from_reader(reader)?;
         let this = serde_json::to_value(&self)?;
         merge(&mut config, &this);
+        let command = self.command.take();
         *self = serde_json::from_value(config)?;
+        self.command = command;
         Ok(())
     }
+
+    /// Load the chains of the chain spec file and directory into the chain registry.
+    pub fn load_chains(&self) -> Result<(), HostError> {
+        if let Some(chain_spec_path) = &self.chain_spec_path {
+            chain_registry::load_chains(chain_spec_path)?;
+        }
+        if let Some(chain_spec_dir) = &self.chain_spec_dir {
+            chain_registry::load_chain_dir(chain_spec_dir)?;
+        }
+        Ok(())
+    }
+
+    /// Take the enclaves out of the `sgx` config, so they and the tokens of their shims aren't
+    /// handed to the provers with the requests, see [sgx_prover::configure_enclaves].
+    #[cfg(feature = "sgx")]
+    fn take_sgx_enclaves(&mut self) -> Result<Vec<sgx_prover::EnclaveConfig>, HostError> {
+        let enclaves = self
+            .proof_request_opt
+            .prover_args
+            .sgx
+            .as_mut()
+            .and_then(Value::as_object_mut)
+            .and_then(|sgx| sgx.remove("enclaves"));
+        let Some(enclaves) = enclaves else {
+            return Ok(Vec::new());
+        };
+        serde_json::from_value(enclaves)
+            .map_err(|e| HostError::InvalidRequestConfig(format!("Invalid SGX enclaves: {e}")))
+    }
 }
 
 /// Merges two json's together, overwriting `a` with the values of `b`
@@ -127,15 +824,21 @@ fn merge(a: &mut Value, b: &Value) {
 #[derive(Debug, Clone)]
 pub struct ProverState {
     pub opts: Cli,
+    pub jobs: JobManager,
+    pub input_cache: InputCache,
+    pub proof_cache: ProofCache,
+    pub api_keys: ApiKeys,
+    pub jwt: Option<JwtAuth>,
+    pub rate_limiter: RateLimiter,
+    pub runtime: RuntimeSettings,
 }
 
 impl ProverState {
-    pub fn init() -> Result<Self, HostError> {
-        // Read the command line arguments;
-        let mut opts = Cli::parse();
-        // Read the config file.
-        opts.merge_from_file()?;
+    pub async fn init() -> Result<Self, HostError> {
+        Self::init_with_opts(Cli::load()?).await
+    }
 
+    pub async fn init_with_opts(opts: Cli) -> Result<Self, HostError> {
         // Check if the cache path exists and create it if it doesn't.
         if let Some(cache_path) = &opts.cache_path {
             if !cache_path.exists() {
@@ -143,7 +846,179 @@ impl ProverState {
             }
         }
 
-        Ok(Self { opts })
+        provider_pool::configure(
+            opts.rpc_selection,
+            Duration::from_secs(opts.rpc_health_check_interval),
+        );
+        blob_provider::configure(opts.blob_sources.clone(), opts.blob_archive_dir.clone());
+        metrics::configure(opts.bounded_metrics);
+        costs::configure(opts.costs.clone().unwrap_or_default());
+        fixtures::configure(opts.fixture_mode, opts.fixture_dir.clone());
+        if let Some(calibration_path) = &opts.calibration_path {
+            estimate::configure(estimate::load_calibration(calibration_path)?);
+        }
+        workspace::configure(opts.workspace_dir.clone());
+        #[cfg(feature = "sgx")]
+        let opts = {
+            let mut opts = opts;
+            sgx_prover::configure_enclaves(opts.take_sgx_enclaves()?);
+            opts
+        };
+        breakers::configure(opts.circuit_breaker.clone());
+        l1_events::configure(!opts.skip_inclusion_check);
+        finality::configure(opts.finality.clone());
+        if let Some(threads) = opts.prover_threads {
+            runtimes::configure(threads)?;
+        }
+        guests::configure(opts.guests.clone())?;
+        isolation::configure(
+            opts.isolate_provers.clone(),
+            opts.prover_daemons.clone(),
+            opts.job_limits.clone(),
+        );
+        isolation::remove_stale_jobs();
+        keys::configure(opts.keys_dir.clone(), opts.keys.clone())?;
+        keys::spawn_fetch();
+        callback::configure(opts.callback_secret.clone());
+        artifacts::configure(
+            opts.artifacts_dir.clone(),
+            opts.artifacts_max_size.map(|size| size * 1024 * 1024),
+            opts.artifacts_max_age
+                .map(|hours| Duration::from_secs(hours * 60 * 60)),
+        )?;
+        gpu::check_devices(
+            &opts.cuda_devices,
+            &opts.isolate_provers,
+            &opts.prover_daemons,
+        )?;
+        if opts.coordinator.is_some() && opts.worker_api_key.is_none() {
+            return Err(HostError::InvalidRequestConfig(
+                "A worker needs the key of its coordinator in --worker-api-key".to_string(),
+            ));
+        }
+        workers::configure(
+            Duration::from_secs(opts.worker_timeout),
+            opts.worker_api_key.clone(),
+        );
+        if let Some(pushgateway_url) = &opts.pushgateway_url {
+            metrics::spawn_push(
+                pushgateway_url.clone(),
+                Duration::from_secs(opts.pushgateway_interval),
+            );
+        }
+        opts.load_chains()?;
+        if opts.header_sync_interval > 0 {
+            for chain in chain_registry::list_chains() {
+                if let (true, Some(rpc)) = (chain.network.is_taiko(), chain.rpc) {
+                    header_store::spawn_sync(
+                        chain.name,
+                        rpc,
+                        Duration::from_secs(opts.header_sync_interval),
+                    );
+                }
+            }
+        }
+
+        let input_cache = InputCache::new(
+            opts.cache_path.clone(),
+            opts.cache_max_size.map(|size| size * 1024 * 1024),
+            opts.cache_max_age
+                .map(|hours| Duration::from_secs(hours * 60 * 60)),
+        );
+        input_cache.prune()?;
+        gc::configure(opts.gc.clone().unwrap_or_default());
+        if opts.gc.is_some() {
+            gc::spawn(input_cache.clone());
+        }
+
+        let proof_cache = ProofCache::open(
+            opts.proof_cache,
+            opts.proof_cache_path.as_deref(),
+            opts.proof_cache_url.as_deref(),
+            opts.proof_cache_size,
+        )?;
+
+        let relayer = match opts.relayer.clone() {
+            Some(config) => Some(Relayer::new(config).await?),
+            None => None,
+        };
+
+        let uploader = match opts.upload.clone() {
+            Some(config) => Some(Uploader::new(config).await?),
+            None => None,
+        };
+
+        // Open the task store and pick up the jobs left over from the last run.
+        let store = open_task_store(opts.task_store, opts.task_db_path.as_deref())?;
+        let jobs = JobManager::new(
+            opts.concurrency_limit,
+            &opts.prover_concurrency,
+            opts.preflight_concurrency,
+            Duration::from_secs(opts.deadline_margin),
+            opts.retry.clone().unwrap_or_default(),
+            input_cache.clone(),
+            proof_cache.clone(),
+            relayer,
+            uploader,
+            store,
+        )?;
+        jobs.set_cuda_devices(&opts.cuda_devices);
+        jobs.set_max_queue_depth(opts.max_queue_depth);
+        jobs.resume()?;
+        if !opts.cuda_devices.is_empty() {
+            gpu::spawn_metrics();
+        }
+        #[cfg(unix)]
+        daemons::warm_up(&opts.prover_daemons, &opts.cuda_devices);
+
+        for config in opts.watchers.clone() {
+            Watcher::new(config, &opts.proof_request_opt, jobs.clone())?.spawn();
+        }
+        if let Some(config) = opts.reorg_monitor.clone() {
+            reorgs::spawn(config, jobs.clone(), input_cache.clone());
+        }
+
+        let api_keys = ApiKeys::new(&opts.api_keys)?;
+        let jwt = opts
+            .jwt_secret
+            .as_deref()
+            .map(JwtAuth::from_file)
+            .transpose()?;
+        let rate_limiter = RateLimiter::new(opts.rate_limit.clone().unwrap_or_default());
+        let journal = opts
+            .config_journal
+            .clone()
+            .unwrap_or_else(|| opts.config_path.with_file_name("config-journal.jsonl"));
+        let runtime = RuntimeSettings::new(&opts, Some(journal));
+
+        if let Some(config) = &opts.messaging {
+            let broker = messaging::connect(config).await?;
+            if let Some(topic) = &config.results {
+                messaging::spawn_publisher(broker.clone(), topic.clone());
+            }
+            if let Some(topic) = &config.requests {
+                messaging::spawn_intake(
+                    broker,
+                    topic.clone(),
+                    config.group.clone(),
+                    opts.proof_request_opt.clone(),
+                    opts.max_block_age,
+                    runtime.clone(),
+                    jobs.clone(),
+                );
+            }
+        }
+
+        Ok(Self {
+            opts,
+            jobs,
+            input_cache,
+            proof_cache,
+            api_keys,
+            jwt,
+            rate_limiter,
+            runtime,
+        })
     }
 }
 
@@ -167,3 +1042,117 @@ mod memory {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_command() {
+        let mut opts = Cli::try_parse_from([
+            "raiko",
+            "--config-path=config/config.json",
+            "--block-number=10",
+            "verify",
+            "--proof=proof.json",
+        ])
+        .unwrap();
+        // The command is not part of the config file.
+        opts.merge_from_file().unwrap();
+        let Some(Command::Verify(args)) = &opts.command else {
+            panic!("no verify command");
+        };
+        assert_eq!(args.proof, PathBuf::from("proof.json"));
+        assert_eq!(opts.proof_request_opt.block_number, Some(10));
+    }
+
+    #[test]
+    fn test_config_file_api_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        std::fs::write(
+            &config_path,
+            r#"{"api_keys": [{"name": "ops", "key": "secret", "admin": true}]}"#,
+        )
+        .unwrap();
+        let mut opts = Cli::try_parse_from([
+            "raiko".to_string(),
+            format!("--config-path={}", config_path.display()),
+        ])
+        .unwrap();
+        // The keys of the file are not overwritten by the empty list of the command line.
+        opts.merge_from_file().unwrap();
+        assert_eq!(opts.api_keys.len(), 1);
+
+        let api_keys = ApiKeys::new(&opts.api_keys).unwrap();
+        assert!(matches!(
+            api_keys.authorize(None, false, true),
+            Err(HostError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            api_keys.authorize(Some("wrong"), true, false),
+            Err(HostError::Unauthorized(_))
+        ));
+        assert_eq!(
+            api_keys.authorize(Some("secret"), true, false).unwrap(),
+            Some("ops".to_string())
+        );
+    }
+
+    #[test]
+    fn test_no_threads() {
+        assert!(Cli::try_parse_from(["raiko", "--worker-threads=0"]).is_err());
+        assert!(Cli::try_parse_from(["raiko", "--prover-threads=0"]).is_err());
+        let opts = Cli::try_parse_from(["raiko", "--max-blocking-threads=8"]).unwrap();
+        assert_eq!(opts.max_blocking_threads, Some(8));
+    }
+
+    #[test]
+    fn test_prove_command() {
+        let opts = Cli::try_parse_from([
+            "raiko",
+            "--proof-type=sgx",
+            "prove",
+            "--input=input-taiko_a7-1000.bin.zst",
+        ])
+        .unwrap();
+        let Some(Command::Prove(args)) = &opts.command else {
+            panic!("no prove command");
+        };
+        assert_eq!(
+            args.input,
+            Some(PathBuf::from("input-taiko_a7-1000.bin.zst"))
+        );
+        assert_eq!(args.output, None);
+    }
+
+    #[test]
+    fn test_bench_precompiles_command() {
+        let opts =
+            Cli::try_parse_from(["raiko", "--proof-type=risc0", "bench-precompiles"]).unwrap();
+        let Some(Command::BenchPrecompiles(args)) = &opts.command else {
+            panic!("no bench-precompiles command");
+        };
+        assert_eq!(args.iterations, 10);
+        assert_eq!(opts.proof_request_opt.proof_type.as_deref(), Some("risc0"));
+    }
+
+    #[test]
+    fn test_bench_command() {
+        let opts = Cli::try_parse_from([
+            "raiko",
+            "--network=taiko_a7",
+            "bench",
+            "--blocks=1000,2000",
+            "--proof-types=risc0,sgx",
+        ])
+        .unwrap();
+        let Some(Command::Bench(args)) = &opts.command else {
+            panic!("no bench command");
+        };
+        assert_eq!(args.blocks, vec![1000, 2000]);
+        assert_eq!(args.proof_types, vec!["risc0", "sgx"]);
+        assert_eq!(args.output, PathBuf::from("calibration.json"));
+        assert!(Cli::try_parse_from(["raiko", "bench"]).is_err());
+    }
+}
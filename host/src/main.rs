@@ -1,40 +1,142 @@
 #![allow(incomplete_features)]
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
-use raiko_host::{error::HostResult, server::serve, ProverState};
-use tracing::debug;
+use raiko_host::{
+    bench::{bench_calibration, bench_precompiles},
+    config_reload,
+    error::HostResult,
+    execution::prove_input_file,
+    images::verify_image_command,
+    jobs::{logs::JobLogLayer, JobManager},
+    keys::setup_command,
+    metrics, runtimes,
+    server::serve,
+    trace::{otlp_layer, set_log_reload, shutdown_otlp, LogFormat},
+    verify::verify_proof_file,
+    workers, Cli, Command, ImageCommand, ProverState,
+};
+use tracing::{debug, error, info, warn};
 use tracing_appender::{
     non_blocking::WorkerGuard,
     rolling::{Builder, Rotation},
 };
-use tracing_subscriber::FmtSubscriber;
+use tracing_subscriber::{
+    fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer,
+};
 
-#[tokio::main]
-async fn main() -> HostResult<()> {
-    env_logger::init();
-    let state = ProverState::init()?;
-    debug!("Start config:\n{:#?}", state.opts.proof_request_opt);
-    debug!("Args:\n{:#?}", state.opts);
+fn main() -> HostResult<()> {
+    let opts = Cli::load()?;
+    runtimes::build(opts.worker_threads, opts.max_blocking_threads)?.block_on(run(opts))
+}
 
+async fn run(opts: Cli) -> HostResult<()> {
     let _guard = subscribe_log(
-        &state.opts.log_path,
-        &state.opts.log_level,
-        state.opts.max_log,
-    );
+        &opts.log_path,
+        &opts.log_level,
+        opts.log_format,
+        opts.max_log,
+    )?;
+
+    match &opts.command {
+        Some(Command::Verify(args)) => return verify_proof_file(&opts, args).await,
+        Some(Command::Prove(args)) => return prove_input_file(&opts, args).await,
+        Some(Command::BenchPrecompiles(args)) => return bench_precompiles(&opts, args).await,
+        Some(Command::Bench(args)) => return bench_calibration(&opts, args).await,
+        Some(Command::Image(args)) => match &args.command {
+            ImageCommand::Verify(args) => return verify_image_command(&opts, args).await,
+        },
+        Some(Command::Setup(args)) => return setup_command(&opts, args).await,
+        #[cfg(unix)]
+        Some(Command::ProverDaemon(args)) => {
+            return raiko_host::daemons::serve_daemon(&opts, args).await
+        }
+        None => {}
+    }
 
-    serve(state).await?;
+    let state = ProverState::init_with_opts(opts).await?;
+    config_reload::spawn(state.clone());
+    if let Some(coordinator) = state.opts.coordinator.clone() {
+        workers::spawn_worker(state.clone(), coordinator);
+    }
+    debug!("Start config:\n{:#?}", state.opts.proof_request_opt);
+    debug!("Args:\n{:#?}", state.opts);
+
+    let opts = state.opts.clone();
+    let jobs = state.jobs.clone();
+    let server = serve(state);
+    tokio::pin!(server);
+    let result = tokio::select! {
+        result = &mut server => result,
+        _ = shutdown_signal() => {
+            info!("Shutting down");
+            // The status of the jobs is still served while they are drained.
+            tokio::select! {
+                result = &mut server => result,
+                _ = drain_jobs(&jobs, Duration::from_secs(opts.drain_timeout)) => Ok(()),
+            }
+        }
+    };
+    flush_metrics(&opts).await;
+    shutdown_otlp();
+    result?;
     Ok(())
 }
 
+/// Wait for Ctrl-C or, on Unix, SIGTERM.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                .expect("installing the SIGTERM handler failed");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Let the running jobs finish, stopping the new ones, and report how many did.
+async fn drain_jobs(jobs: &JobManager, timeout: Duration) {
+    info!("Draining the running jobs for up to {}s", timeout.as_secs());
+    match jobs.drain(timeout).await {
+        Ok(report) => info!(
+            "Drained {} running jobs, aborted {}, {} jobs left for the next start",
+            report.drained, report.aborted, report.queued
+        ),
+        Err(e) => error!("Could not drain the jobs: {e}"),
+    }
+}
+
+/// Push the final metrics to the Pushgateway and write the metrics snapshot, if configured.
+async fn flush_metrics(opts: &Cli) {
+    if let Some(pushgateway_url) = &opts.pushgateway_url {
+        if let Err(e) = metrics::push(pushgateway_url).await {
+            warn!("Could not push the metrics to {pushgateway_url}: {e}");
+        }
+    }
+    if let Some(path) = &opts.metrics_snapshot {
+        match metrics::write_snapshot(path) {
+            Ok(()) => info!("Wrote the metrics snapshot to {}", path.display()),
+            Err(e) => warn!(
+                "Could not write the metrics snapshot to {}: {e}",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Log to stdout, or to daily rotated JSON files in `log_path`, and export the spans over
+/// OTLP if it is enabled. The `log` records of the dependencies are logged as well.
 fn subscribe_log(
     log_path: &Option<PathBuf>,
     log_level: &String,
+    log_format: LogFormat,
     max_log: usize,
-) -> Option<WorkerGuard> {
-    let subscriber_builder = FmtSubscriber::builder()
-        .with_env_filter(log_level)
-        .with_test_writer();
-    match log_path {
+) -> HostResult<Option<WorkerGuard>> {
+    let (fmt_layer, guard) = match log_path {
         Some(ref log_path) => {
             let file_appender = Builder::new()
                 .rotation(Rotation::DAILY)
@@ -42,15 +144,35 @@ fn subscribe_log(
                 .max_log_files(max_log)
                 .build(log_path)
                 .expect("initializing rolling file appender failed");
-            let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
-            let subscriber = subscriber_builder.json().with_writer(non_blocking).finish();
-            tracing::subscriber::set_global_default(subscriber).unwrap();
-            Some(_guard)
+            let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
+            let layer = fmt::layer()
+                .json()
+                .with_span_list(true)
+                .with_writer(non_blocking)
+                .boxed();
+            (layer, Some(guard))
         }
         None => {
-            let subscriber = subscriber_builder.finish();
-            tracing::subscriber::set_global_default(subscriber).unwrap();
-            None
+            let layer = match log_format {
+                LogFormat::Text => fmt::layer().with_test_writer().boxed(),
+                LogFormat::Json => fmt::layer()
+                    .json()
+                    .with_span_list(true)
+                    .with_test_writer()
+                    .boxed(),
+            };
+            (layer, None)
         }
-    }
+    };
+
+    // The log level can be changed at runtime through the admin API.
+    let (filter, handle) = reload::Layer::new(EnvFilter::new(log_level));
+    tracing_subscriber::registry()
+        .with(fmt_layer)
+        .with(JobLogLayer)
+        .with(otlp_layer()?)
+        .with(filter)
+        .init();
+    set_log_reload(move |filter| Ok(handle.reload(filter)?));
+    Ok(guard)
 }
@@ -0,0 +1,77 @@
+use std::{fmt, time::Duration};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::{stream::BoxStream, StreamExt};
+use rdkafka::{
+    consumer::{Consumer, StreamConsumer},
+    producer::{FutureProducer, FutureRecord},
+    ClientConfig, Message,
+};
+use tracing::warn;
+
+use super::Broker;
+
+/// A Kafka cluster. Requests are taken with a consumer group, committing their offsets
+/// automatically.
+pub struct KafkaBroker {
+    servers: String,
+    producer: FutureProducer,
+}
+
+impl KafkaBroker {
+    pub fn connect(servers: &str) -> Result<Self> {
+        Ok(Self {
+            servers: servers.to_string(),
+            producer: ClientConfig::new()
+                .set("bootstrap.servers", servers)
+                .set("message.timeout.ms", "30000")
+                .create()?,
+        })
+    }
+}
+
+impl fmt::Debug for KafkaBroker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KafkaBroker")
+            .field("servers", &self.servers)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl Broker for KafkaBroker {
+    async fn publish(&self, topic: &str, key: &str, payload: Vec<u8>) -> Result<()> {
+        self.producer
+            .send(
+                FutureRecord::to(topic).key(key).payload(&payload),
+                Duration::from_secs(0),
+            )
+            .await
+            .map_err(|(e, _)| e)?;
+        Ok(())
+    }
+
+    async fn subscribe(&self, topic: &str, group: &str) -> Result<BoxStream<'static, Vec<u8>>> {
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", &self.servers)
+            .set("group.id", group)
+            .set("enable.auto.commit", "true")
+            .create()?;
+        consumer.subscribe(&[topic])?;
+        Ok(futures::stream::unfold(consumer, |consumer| async move {
+            loop {
+                let payload = match consumer.recv().await {
+                    Ok(message) => message.payload().unwrap_or_default().to_vec(),
+                    Err(e) => {
+                        warn!("Could not receive a message from Kafka: {e}");
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        continue;
+                    }
+                };
+                return Some((payload, consumer));
+            }
+        })
+        .boxed())
+    }
+}
@@ -0,0 +1,249 @@
+//! Integration with message brokers.
+//!
+//! With a [MessagingConfig], the host takes proof requests from a topic of a NATS or Kafka
+//! broker and publishes every status change of its jobs to another topic, so it can be used in
+//! event-driven pipelines without polling the HTTP API. The requests are the same JSON objects
+//! as the body of `POST /v2/proof`, and the status changes the same JSON objects as the
+//! [callbacks](crate::callback), keyed by the job id. Every status change of a job is
+//! published, not only the changes of the jobs taken from the broker.
+
+use std::{fmt::Debug, sync::Arc, sync::RwLock, time::Duration};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::{stream::BoxStream, StreamExt};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::{
+    callback::CallbackPayload,
+    error::HostResult,
+    jobs::{Job, JobManager, JobSummary},
+    request::{ProofRequest, ProofRequestOpt},
+    runtime_config::RuntimeSettings,
+};
+
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "nats")]
+mod nats;
+
+/// How long to wait before subscribing again after the subscription failed.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    static ref EVENTS: RwLock<Option<mpsc::UnboundedSender<CallbackPayload>>> = RwLock::new(None);
+}
+
+/// A message broker.
+#[async_trait]
+pub trait Broker: Send + Sync + Debug {
+    /// Publish the payload to the topic, with the key the messages are partitioned by.
+    async fn publish(&self, topic: &str, key: &str, payload: Vec<u8>) -> Result<()>;
+
+    /// Receive the payloads of the messages of the topic, shared with the other subscribers
+    /// of the same group.
+    async fn subscribe(&self, topic: &str, group: &str) -> Result<BoxStream<'static, Vec<u8>>>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Available message brokers.
+pub enum BrokerKind {
+    Nats,
+    Kafka,
+}
+
+fn default_group() -> String {
+    "raiko".to_string()
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MessagingConfig {
+    /// The kind of the broker.
+    pub broker: BrokerKind,
+    /// The URL of the NATS server, or the bootstrap servers of the Kafka cluster.
+    pub url: String,
+    /// The topic to take proof requests from.
+    pub requests: Option<String>,
+    /// The topic to publish the status changes of the jobs to.
+    pub results: Option<String>,
+    /// The consumer group of the hosts taking requests from the same topic, each request is
+    /// taken by one of them.
+    #[serde(default = "default_group")]
+    pub group: String,
+}
+
+/// Connect to the broker of the config.
+pub async fn connect(config: &MessagingConfig) -> Result<Arc<dyn Broker>> {
+    match config.broker {
+        BrokerKind::Nats => {
+            #[cfg(feature = "nats")]
+            return Ok(Arc::new(nats::NatsBroker::connect(&config.url).await?));
+
+            #[cfg(not(feature = "nats"))]
+            anyhow::bail!("Cannot connect to NATS, the host was built without the `nats` feature");
+        }
+        BrokerKind::Kafka => {
+            #[cfg(feature = "kafka")]
+            return Ok(Arc::new(kafka::KafkaBroker::connect(&config.url)?));
+
+            #[cfg(not(feature = "kafka"))]
+            anyhow::bail!(
+                "Cannot connect to Kafka, the host was built without the `kafka` feature"
+            );
+        }
+    }
+}
+
+/// Publish the status changes of the jobs to the topic, in the order they happen.
+pub fn spawn_publisher(broker: Arc<dyn Broker>, topic: String) {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<CallbackPayload>();
+    *EVENTS.write().unwrap() = Some(sender);
+    info!("Publishing the status changes of the jobs to {topic}");
+    tokio::spawn(async move {
+        while let Some(event) = receiver.recv().await {
+            let result = match serde_json::to_vec(&event) {
+                Ok(payload) => broker.publish(&topic, &event.id.to_string(), payload).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                warn!(
+                    "Could not publish the {} status of job {} to {topic}: {e}",
+                    event.status, event.id
+                );
+            }
+        }
+    });
+}
+
+/// Publish the status of the job, if the status changes are published.
+pub fn publish(job: &Job) {
+    if let Some(sender) = EVENTS.read().unwrap().as_ref() {
+        let _ = sender.send(CallbackPayload::from(job));
+    }
+}
+
+/// Queue a job for every proof request taken from the topic. The requests are completed with
+/// the proof request config of the host, like the requests to `POST /v2/proof`.
+pub fn spawn_intake(
+    broker: Arc<dyn Broker>,
+    topic: String,
+    group: String,
+    base: ProofRequestOpt,
+    max_block_age: Option<u64>,
+    runtime: RuntimeSettings,
+    jobs: JobManager,
+) {
+    info!("Taking proof requests from {topic} in group {group}");
+    tokio::spawn(async move {
+        loop {
+            match broker.subscribe(&topic, &group).await {
+                Ok(mut requests) => {
+                    while let Some(payload) = requests.next().await {
+                        match intake(&payload, &base, max_block_age, &runtime, &jobs).await {
+                            Ok(job) => info!("Queued job {} from {topic}", job.id),
+                            Err(e) => warn!("Rejected a proof request from {topic}: {e}"),
+                        }
+                    }
+                    warn!("The subscription to {topic} ended");
+                }
+                Err(e) => warn!("Could not subscribe to {topic}: {e}"),
+            }
+            tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+        }
+    });
+}
+
+async fn intake(
+    payload: &[u8],
+    base: &ProofRequestOpt,
+    max_block_age: Option<u64>,
+    runtime: &RuntimeSettings,
+    jobs: &JobManager,
+) -> HostResult<JobSummary> {
+    let req: Value = serde_json::from_slice(payload)?;
+    ProofRequestOpt::check_fields(&req, &[])?;
+    let mut config = runtime.request_config(base);
+    config.merge(&req)?;
+    let proof_request = ProofRequest::try_from(config)?;
+    proof_request.check_block(max_block_age).await?;
+    jobs.submit(proof_request)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::jobs::JobStatus;
+
+    #[derive(Debug, Default)]
+    struct RecordingBroker(Mutex<Vec<(String, Vec<u8>)>>);
+
+    #[async_trait]
+    impl Broker for RecordingBroker {
+        async fn publish(&self, _topic: &str, key: &str, payload: Vec<u8>) -> Result<()> {
+            self.0.lock().unwrap().push((key.to_string(), payload));
+            Ok(())
+        }
+
+        async fn subscribe(
+            &self,
+            _topic: &str,
+            _group: &str,
+        ) -> Result<BoxStream<'static, Vec<u8>>> {
+            Ok(futures::stream::empty().boxed())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_in_order() {
+        let broker = Arc::new(RecordingBroker::default());
+        spawn_publisher(broker.clone(), "results".to_string());
+        let mut job: Job = serde_json::from_value(serde_json::json!({
+            "id": 3,
+            "request": {
+                "block_number": 10,
+                "rpc": "http://localhost:8545",
+                "l1_rpc": "http://localhost:8546",
+                "beacon_rpc": "http://localhost:5052",
+                "network": "taiko_a7",
+                "l1_network": "holesky",
+                "graffiti": alloy_primitives::B256::ZERO,
+                "prover": "0x0000000000000000000000000000000000000000",
+                "proof_type": "native",
+            },
+            "status": "queued",
+            "proof": null,
+            "error": null,
+            "created_at": 0,
+            "updated_at": 0,
+        }))
+        .unwrap();
+        for status in [JobStatus::Queued, JobStatus::Running, JobStatus::Failed] {
+            job.status = status;
+            publish(&job);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        *EVENTS.write().unwrap() = None;
+
+        let published = broker.0.lock().unwrap();
+        let statuses: Vec<_> = published
+            .iter()
+            .map(|(key, payload)| {
+                assert_eq!(key, "3");
+                serde_json::from_slice::<CallbackPayload>(payload)
+                    .unwrap()
+                    .status
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![JobStatus::Queued, JobStatus::Running, JobStatus::Failed]
+        );
+    }
+}
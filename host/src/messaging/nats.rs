@@ -0,0 +1,37 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::{stream::BoxStream, StreamExt};
+
+use super::Broker;
+
+/// A NATS server. Requests are taken with a queue group.
+#[derive(Debug)]
+pub struct NatsBroker {
+    client: async_nats::Client,
+}
+
+impl NatsBroker {
+    pub async fn connect(url: &str) -> Result<Self> {
+        Ok(Self {
+            client: async_nats::connect(url).await?,
+        })
+    }
+}
+
+#[async_trait]
+impl Broker for NatsBroker {
+    async fn publish(&self, topic: &str, _key: &str, payload: Vec<u8>) -> Result<()> {
+        self.client
+            .publish(topic.to_string(), payload.into())
+            .await?;
+        Ok(())
+    }
+
+    async fn subscribe(&self, topic: &str, group: &str) -> Result<BoxStream<'static, Vec<u8>>> {
+        let subscriber = self
+            .client
+            .queue_subscribe(topic.to_string(), group.to_string())
+            .await?;
+        Ok(subscriber.map(|message| message.payload.to_vec()).boxed())
+    }
+}
@@ -1,58 +1,90 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::BufWriter,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
 use lazy_static::lazy_static;
 use prometheus::{
-    labels, register_histogram_vec, register_int_counter_vec, register_int_gauge, HistogramVec,
-    IntCounterVec, IntGauge,
+    exponential_buckets, labels,
+    proto::{MetricFamily, MetricType},
+    register_counter_vec, register_gauge_vec, register_histogram_vec, register_int_counter,
+    register_int_counter_vec, register_int_gauge, register_int_gauge_vec, CounterVec, Encoder,
+    GaugeVec, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
 };
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::{clock::unix_timestamp, gpu::GpuStats, request::ProofType};
+
+/// The number of finished requests kept for [recent_requests].
+const MAX_RECENT_REQUESTS: usize = 1_000;
 
-use crate::request::ProofType;
+/// Whether the per block metrics are not labeled by block, see [configure].
+static BOUNDED_LABELS: AtomicBool = AtomicBool::new(false);
 
 lazy_static! {
+    static ref RECENT_REQUESTS: Mutex<VecDeque<RecentRequest>> = Default::default();
     pub static ref HOST_REQ_COUNT: IntCounterVec = register_int_counter_vec!(
         "host_request_count",
         "the number of requests sent to the host",
-        &["block_id"]
+        &["network", "block_id"]
     )
     .unwrap();
     pub static ref HOST_ERROR_COUNT: IntCounterVec = register_int_counter_vec!(
         "host_error_count",
         "the number of failed requests produced by the host",
-        &["block_id"]
+        &["network", "block_id"]
     )
     .unwrap();
     pub static ref GUEST_PROOF_REQ_COUNT: IntCounterVec = register_int_counter_vec!(
         "guest_proof_request_count",
         "the number of requests sent to this guest",
-        &["guest", "block_id"]
+        &["guest", "network", "block_id"]
     )
     .unwrap();
     pub static ref GUEST_PROOF_SUCCESS_COUNT: IntCounterVec = register_int_counter_vec!(
         "guest_proof_success_count",
         "the number of successful proofs generated by this guest",
-        &["guest", "block_id"]
+        &["guest", "network", "block_id"]
     )
     .unwrap();
     pub static ref GUEST_PROOF_ERROR_COUNT: IntCounterVec = register_int_counter_vec!(
         "guest_proof_error_count",
         "the number of failed proofs generated by this guest",
-        &["guest", "block_id"]
+        &["guest", "network", "block_id"]
     )
     .unwrap();
     pub static ref GUEST_PROOF_TIME: HistogramVec = register_histogram_vec!(
         "guest_proof_time_histogram",
         "time taken for proof generation by this guest",
-        &["guest", "block_id", "success"]
+        &["guest", "network", "block_id", "success"]
     )
     .unwrap();
     pub static ref PREPARE_INPUT_TIME: HistogramVec = register_histogram_vec!(
         "prepare_input_time_histogram",
         "time taken for prepare input",
-        &["block_id", "success"]
+        &["network", "block_id", "success"]
     )
     .unwrap();
     pub static ref TOTAL_TIME: HistogramVec = register_histogram_vec!(
         "total_time_histogram",
         "time taken for the whole request",
-        &["block_id", "success"]
+        &["network", "block_id", "success"]
+    )
+    .unwrap();
+    pub static ref REMOTE_SESSION_COUNT: IntCounterVec = register_int_counter_vec!(
+        "remote_session_count",
+        "number of proofs generated in sessions of a remote proving service",
+        &["guest", "service"]
     )
     .unwrap();
     pub static ref CONCURRENT_REQUESTS: IntGauge = register_int_gauge!(
@@ -60,6 +92,241 @@ lazy_static! {
         "number of requests currently being processed"
     )
     .unwrap();
+    pub static ref PROOF_CACHE_HIT_COUNT: IntCounterVec = register_int_counter_vec!(
+        "proof_cache_hit_count",
+        "number of proofs returned from the proof cache",
+        &["guest"]
+    )
+    .unwrap();
+    pub static ref PROOF_VERIFICATION_COUNT: IntCounterVec = register_int_counter_vec!(
+        "proof_verification_count",
+        "number of generated proofs verified locally, by whether they verified",
+        &["guest", "verified"]
+    )
+    .unwrap();
+    pub static ref INPUT_CACHE_HIT_COUNT: IntCounter = register_int_counter!(
+        "input_cache_hit_count",
+        "number of inputs loaded from the input cache"
+    )
+    .unwrap();
+    pub static ref INPUT_CACHE_MISS_COUNT: IntCounter = register_int_counter!(
+        "input_cache_miss_count",
+        "number of inputs not found in the input cache"
+    )
+    .unwrap();
+    pub static ref RETRY_COUNT: IntCounterVec = register_int_counter_vec!(
+        "retry_count",
+        "number of retries of failed proof requests",
+        &["guest"]
+    )
+    .unwrap();
+    pub static ref QUEUED_REQUESTS: IntGaugeVec = register_int_gauge_vec!(
+        "queued_requests",
+        "number of requests waiting for a prover slot",
+        &["guest"]
+    )
+    .unwrap();
+    pub static ref QUEUE_TIME: HistogramVec = register_histogram_vec!(
+        "queue_time_seconds",
+        "time requests waited for a prover slot",
+        &["guest"],
+        vec![0.1, 1.0, 10.0, 60.0, 300.0, 900.0, 1800.0, 3600.0, 7200.0]
+    )
+    .unwrap();
+    pub static ref ACTIVE_WORKERS: IntGaugeVec = register_int_gauge_vec!(
+        "active_workers",
+        "number of proofs being generated by the prover backend",
+        &["guest"]
+    )
+    .unwrap();
+    pub static ref JOB_RETRIES: HistogramVec = register_histogram_vec!(
+        "job_retries",
+        "number of retries of a job once it finished",
+        &["guest"],
+        vec![0.0, 1.0, 2.0, 3.0, 5.0, 10.0]
+    )
+    .unwrap();
+    pub static ref PROOF_CACHE_MISS_COUNT: IntCounterVec = register_int_counter_vec!(
+        "proof_cache_miss_count",
+        "number of proofs not found in the proof cache",
+        &["guest"]
+    )
+    .unwrap();
+    pub static ref GUEST_CYCLES: HistogramVec = register_histogram_vec!(
+        "guest_cycles",
+        "number of cycles of the guest execution, if the prover reports them",
+        &["guest"],
+        exponential_buckets(1e6, 4.0, 12).unwrap()
+    )
+    .unwrap();
+    pub static ref GUEST_SEGMENTS: HistogramVec = register_histogram_vec!(
+        "guest_segments",
+        "number of segments or shards of the guest execution, if the prover reports them",
+        &["guest"],
+        exponential_buckets(1.0, 2.0, 14).unwrap()
+    )
+    .unwrap();
+    pub static ref PROOF_SIZE: HistogramVec = register_histogram_vec!(
+        "proof_size_bytes",
+        "size of the generated proofs",
+        &["guest"],
+        exponential_buckets(256.0, 4.0, 12).unwrap()
+    )
+    .unwrap();
+    pub static ref INPUT_SERIALIZED_SIZE: HistogramVec = register_histogram_vec!(
+        "input_serialized_bytes",
+        "size of the encoded guest inputs before compression",
+        &["target"],
+        exponential_buckets(1e5, 4.0, 10).unwrap()
+    )
+    .unwrap();
+    pub static ref INPUT_COMPRESSED_SIZE: HistogramVec = register_histogram_vec!(
+        "input_compressed_bytes",
+        "size of the encoded guest inputs after compression",
+        &["target"],
+        exponential_buckets(1e5, 4.0, 10).unwrap()
+    )
+    .unwrap();
+    pub static ref PROVER_PEAK_MEMORY: HistogramVec = register_histogram_vec!(
+        "prover_peak_memory_bytes",
+        "peak memory allocated by the host while generating a proof",
+        &["guest"],
+        exponential_buckets(1e7, 2.0, 14).unwrap()
+    )
+    .unwrap();
+    pub static ref PROOF_COST_TOTAL: CounterVec = register_counter_vec!(
+        "proof_cost_total",
+        "estimated cost of the generated proofs",
+        &["guest", "source"]
+    )
+    .unwrap();
+    pub static ref PROOF_COST: HistogramVec = register_histogram_vec!(
+        "proof_cost",
+        "estimated cost of a generated proof",
+        &["guest", "source"],
+        exponential_buckets(0.001, 4.0, 12).unwrap()
+    )
+    .unwrap();
+    pub static ref COST_TODAY: GaugeVec = register_gauge_vec!(
+        "proof_cost_today",
+        "estimated cost of the proofs generated today (UTC)",
+        &["guest"]
+    )
+    .unwrap();
+    pub static ref DEADLINE_REMAINING: HistogramVec = register_histogram_vec!(
+        "deadline_remaining_seconds",
+        "time remaining until the deadline of the request when its proof is generated",
+        &["guest"],
+        vec![-600.0, -60.0, 0.0, 60.0, 300.0, 600.0, 1800.0, 3600.0, 7200.0]
+    )
+    .unwrap();
+    pub static ref WATCHED_BLOCK_COUNT: IntCounterVec = register_int_counter_vec!(
+        "watched_block_count",
+        "number of new blocks seen by the watchers",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref API_KEY_REQUEST_COUNT: IntCounterVec = register_int_counter_vec!(
+        "api_key_request_count",
+        "number of requests made with the API key",
+        &["key"]
+    )
+    .unwrap();
+    pub static ref RATE_LIMITED_COUNT: IntCounterVec = register_int_counter_vec!(
+        "rate_limited_count",
+        "number of proof requests rejected by the rate limits",
+        &["limit"]
+    )
+    .unwrap();
+    pub static ref SHUTDOWN_JOB_COUNT: IntCounterVec = register_int_counter_vec!(
+        "shutdown_job_count",
+        "number of running jobs drained or aborted on shutdown",
+        &["result"]
+    )
+    .unwrap();
+    pub static ref WORKER_DISPATCH_COUNT: IntCounterVec = register_int_counter_vec!(
+        "worker_dispatch_count",
+        "number of proofs sent to remote workers",
+        &["result"]
+    )
+    .unwrap();
+    pub static ref CONFIG_RELOAD_COUNT: IntCounterVec = register_int_counter_vec!(
+        "config_reload_count",
+        "number of reloads of the config file",
+        &["result"]
+    )
+    .unwrap();
+    pub static ref API_KEY_PROOF_COUNT: IntCounterVec = register_int_counter_vec!(
+        "api_key_proof_count",
+        "number of proof requests made with the API key",
+        &["key"]
+    )
+    .unwrap();
+    pub static ref GPU_UTILIZATION: GaugeVec = register_gauge_vec!(
+        "gpu_utilization_percent",
+        "percentage of time a kernel ran on the CUDA device",
+        &["device"]
+    )
+    .unwrap();
+    pub static ref GPU_MEMORY_USED: IntGaugeVec = register_int_gauge_vec!(
+        "gpu_memory_used_bytes",
+        "memory used on the CUDA device",
+        &["device"]
+    )
+    .unwrap();
+    pub static ref GPU_MEMORY_TOTAL: IntGaugeVec = register_int_gauge_vec!(
+        "gpu_memory_total_bytes",
+        "memory of the CUDA device",
+        &["device"]
+    )
+    .unwrap();
+    pub static ref PROVER_CRASH_COUNT: IntCounterVec = register_int_counter_vec!(
+        "prover_crash_count",
+        "number of crashes of the child processes of the provers",
+        &["guest", "reason"]
+    )
+    .unwrap();
+    pub static ref GC_RECLAIMED_BYTES: IntCounterVec = register_int_counter_vec!(
+        "gc_reclaimed_bytes",
+        "bytes removed by the garbage collection, by what they were",
+        &["target"]
+    )
+    .unwrap();
+    pub static ref CIRCUIT_BREAKER_STATE: IntGaugeVec = register_int_gauge_vec!(
+        "circuit_breaker_state",
+        "state of the circuit breaker of a backend, 0 closed, 1 half open and 2 open",
+        &["breaker"]
+    )
+    .unwrap();
+    pub static ref CIRCUIT_BREAKER_TRIPS: IntCounterVec = register_int_counter_vec!(
+        "circuit_breaker_trips",
+        "number of times the circuit breaker of a backend opened",
+        &["breaker"]
+    )
+    .unwrap();
+    pub static ref REORG_COUNT: IntCounterVec = register_int_counter_vec!(
+        "reorg_count",
+        "number of reorgs of blocks being proven, by the chain of the reorged block",
+        &["network", "layer"]
+    )
+    .unwrap();
+    pub static ref REORG_CANCELLED_JOBS: IntCounterVec = register_int_counter_vec!(
+        "reorg_cancelled_jobs",
+        "number of jobs cancelled because their block was reorged",
+        &["guest"]
+    )
+    .unwrap();
+    pub static ref UNFINALIZED_BLOCKS: IntCounterVec = register_int_counter_vec!(
+        "unfinalized_blocks",
+        "number of requests of blocks proposed in L1 blocks that were not final yet",
+        &["network", "action"]
+    )
+    .unwrap();
+    pub static ref DISK_FREE_BYTES: IntGauge = register_int_gauge!(
+        "disk_free_bytes",
+        "free bytes of the disk with the least free space of the directories of the host"
+    )
+    .unwrap();
 }
 
 /// Increase the count of requests currently being processed.
@@ -72,64 +339,342 @@ pub fn dec_current_req() {
     CONCURRENT_REQUESTS.dec();
 }
 
+/// Increase the count of requests waiting for a slot of the given guest.
+pub fn inc_queued_req(guest: &ProofType) {
+    let guest = guest.to_string();
+    QUEUED_REQUESTS
+        .with(&labels! { "guest" => guest.as_str() })
+        .inc();
+}
+
+/// Decrease the count of requests waiting for a slot of the given guest.
+pub fn dec_queued_req(guest: &ProofType) {
+    let guest = guest.to_string();
+    QUEUED_REQUESTS
+        .with(&labels! { "guest" => guest.as_str() })
+        .dec();
+}
+
+/// Increase the count of proofs being generated by the given guest.
+pub fn inc_active_workers(guest: &str) {
+    ACTIVE_WORKERS.with(&labels! { "guest" => guest }).inc();
+}
+
+/// Decrease the count of proofs being generated by the given guest.
+pub fn dec_active_workers(guest: &str) {
+    ACTIVE_WORKERS.with(&labels! { "guest" => guest }).dec();
+}
+
+/// Observe the time a request waited for a slot of the given guest.
+pub fn observe_queue_time(guest: &ProofType, time: Duration) {
+    let guest = guest.to_string();
+    QUEUE_TIME
+        .with(&labels! { "guest" => guest.as_str() })
+        .observe(time.as_secs_f64());
+}
+
+/// Observe the number of retries of a finished job of the given guest.
+pub fn observe_job_retries(guest: &ProofType, retries: u32) {
+    let guest = guest.to_string();
+    JOB_RETRIES
+        .with(&labels! { "guest" => guest.as_str() })
+        .observe(f64::from(retries));
+}
+
+/// Observe the cycles and segments of a guest execution of the given guest.
+pub fn observe_guest_execution(guest: &ProofType, cycles: Option<u64>, segments: Option<u64>) {
+    let guest = guest.to_string();
+    let labels = labels! { "guest" => guest.as_str() };
+    if let Some(cycles) = cycles {
+        GUEST_CYCLES.with(&labels).observe(cycles as f64);
+    }
+    if let Some(segments) = segments {
+        GUEST_SEGMENTS.with(&labels).observe(segments as f64);
+    }
+}
+
+/// Observe the size of a proof and the peak memory used to generate it by the given guest.
+pub fn observe_proof_size(guest: &ProofType, proof_size: usize, peak_memory: usize) {
+    let guest = guest.to_string();
+    let labels = labels! { "guest" => guest.as_str() };
+    PROOF_SIZE.with(&labels).observe(proof_size as f64);
+    PROVER_PEAK_MEMORY.with(&labels).observe(peak_memory as f64);
+}
+
+/// Observe the size of a guest input encoded for the target, before and after compression.
+pub fn observe_input_size(target: &str, serialized: u64, compressed: u64) {
+    let labels = labels! { "target" => target };
+    INPUT_SERIALIZED_SIZE
+        .with(&labels)
+        .observe(serialized as f64);
+    INPUT_COMPRESSED_SIZE
+        .with(&labels)
+        .observe(compressed as f64);
+}
+
+/// Observe the estimated cost of a proof generated by the given guest and source.
+pub fn observe_proof_cost(guest: &ProofType, source: &str, cost: f64) {
+    let guest = guest.to_string();
+    let labels = labels! { "guest" => guest.as_str(), "source" => source };
+    PROOF_COST_TOTAL.with(&labels).inc_by(cost);
+    PROOF_COST.with(&labels).observe(cost);
+}
+
+/// Set the cost of the proofs generated today per guest, the guests without proofs today are
+/// reset.
+pub fn set_cost_today(costs: &[(String, f64)]) {
+    COST_TODAY.reset();
+    for (guest, cost) in costs {
+        COST_TODAY
+            .with(&labels! { "guest" => guest.as_str() })
+            .set(*cost);
+    }
+}
+
+/// Set the utilization and the memory of a CUDA device.
+pub fn set_gpu_stats(stats: &GpuStats) {
+    let device = stats.device.to_string();
+    let labels = labels! { "device" => device.as_str() };
+    GPU_UTILIZATION.with(&labels).set(stats.utilization);
+    GPU_MEMORY_USED.with(&labels).set(stats.memory_used as i64);
+    GPU_MEMORY_TOTAL
+        .with(&labels)
+        .set(stats.memory_total as i64);
+}
+
+/// Increment the count of crashes of the prover, by `out_of_memory`, `signal` or `exit`.
+pub fn inc_prover_crash_count(guest: &ProofType, reason: &str) {
+    let guest = guest.to_string();
+    PROVER_CRASH_COUNT
+        .with(&labels! { "guest" => guest.as_str(), "reason" => reason })
+        .inc();
+}
+
+/// Add the bytes removed by the garbage collection.
+pub fn inc_gc_reclaimed_bytes(target: &str, bytes: u64) {
+    GC_RECLAIMED_BYTES
+        .with(&labels! { "target" => target })
+        .inc_by(bytes);
+}
+
+/// Set the free space of the disks of the host.
+pub fn set_disk_free_bytes(bytes: u64) {
+    DISK_FREE_BYTES.set(bytes as i64);
+}
+
+/// Set the state of the circuit breaker of a backend.
+pub fn set_circuit_breaker_state(breaker: &str, state: i64) {
+    CIRCUIT_BREAKER_STATE
+        .with(&labels! { "breaker" => breaker })
+        .set(state);
+}
+
+/// Increment the count of times the circuit breaker of a backend opened.
+pub fn inc_circuit_breaker_trips(breaker: &str) {
+    CIRCUIT_BREAKER_TRIPS
+        .with(&labels! { "breaker" => breaker })
+        .inc();
+}
+
+/// Increment the count of reorgs of blocks being proven on the network, by `l1` or `l2`.
+pub fn inc_reorg_count(network: &str, layer: &str) {
+    REORG_COUNT
+        .with(&labels! { "network" => network, "layer" => layer })
+        .inc();
+}
+
+/// Increment the count of jobs of the guest cancelled for a reorg.
+pub fn inc_reorg_cancelled_jobs(guest: &ProofType) {
+    let guest = guest.to_string();
+    REORG_CANCELLED_JOBS
+        .with(&labels! { "guest" => guest.as_str() })
+        .inc();
+}
+
+/// Increment the count of requests of blocks on the network proposed in L1 blocks that were
+/// not final yet, by `wait` or `reject`.
+pub fn inc_unfinalized_blocks(network: &str, action: &str) {
+    UNFINALIZED_BLOCKS
+        .with(&labels! { "network" => network, "action" => action })
+        .inc();
+}
+
 /// Increment the request count for the host.
-pub fn inc_host_req_count(block_id: u64) {
-    let block_id = block_id.to_string();
+pub fn inc_host_req_count(network: &str, block_id: u64) {
+    let block_id = block_label(block_id);
     let labels = labels! {
+        "network" => network,
         "block_id" => block_id.as_str(),
     };
     HOST_REQ_COUNT.with(&labels).inc();
 }
 
 /// Increment the error count for the host.
-pub fn inc_host_error(block_id: u64) {
-    let block_id = block_id.to_string();
+pub fn inc_host_error(network: &str, block_id: u64) {
+    let block_id = block_label(block_id);
     let labels = labels! {
+        "network" => network,
         "block_id" => block_id.as_str(),
     };
     HOST_ERROR_COUNT.with(&labels).inc();
 }
 
 /// Increment the request count for the given guest.
-pub fn inc_guest_req_count(guest: &ProofType, block_id: u64) {
+pub fn inc_guest_req_count(guest: &ProofType, network: &str, block_id: u64) {
     let guest = guest.to_string();
-    let block_id = block_id.to_string();
+    let block_id = block_label(block_id);
     let labels = labels! {
         "guest" => guest.as_str(),
+        "network" => network,
         "block_id" => &block_id,
     };
     GUEST_PROOF_REQ_COUNT.with(&labels).inc();
 }
 
 /// Increment the success count for the given guest.
-pub fn inc_guest_success(guest: &ProofType, block_id: u64) {
+pub fn inc_guest_success(guest: &ProofType, network: &str, block_id: u64) {
     let guest = guest.to_string();
-    let block_id = block_id.to_string();
+    let block_id = block_label(block_id);
     let labels = labels! {
         "guest" => guest.as_str(),
+        "network" => network,
         "block_id" => &block_id,
     };
     GUEST_PROOF_SUCCESS_COUNT.with(&labels).inc();
 }
 
 /// Increment the error count for the given guest.
-pub fn inc_guest_error(guest: &ProofType, block_id: u64) {
+pub fn inc_guest_error(guest: &ProofType, network: &str, block_id: u64) {
     let guest = guest.to_string();
-    let block_id = block_id.to_string();
+    let block_id = block_label(block_id);
     let labels = labels! {
         "guest" => guest.as_str(),
+        "network" => network,
         "block_id" => &block_id,
     };
     GUEST_PROOF_ERROR_COUNT.with(&labels).inc();
 }
 
+/// Increment the count of sessions started on a remote proving service for the given guest.
+pub fn inc_remote_session_count(guest: &ProofType, service: &str) {
+    let guest = guest.to_string();
+    let labels = labels! {
+        "guest" => guest.as_str(),
+        "service" => service,
+    };
+    REMOTE_SESSION_COUNT.with(&labels).inc();
+}
+
+/// Increment the count of proofs of the given guest returned from the proof cache.
+pub fn inc_proof_cache_hit(guest: &ProofType) {
+    let guest = guest.to_string();
+    PROOF_CACHE_HIT_COUNT
+        .with(&labels! { "guest" => guest.as_str() })
+        .inc();
+}
+
+/// Increment the count of proofs of the given guest verified locally.
+pub fn inc_proof_verification(guest: &ProofType, verified: bool) {
+    let guest = guest.to_string();
+    let verified = verified.to_string();
+    PROOF_VERIFICATION_COUNT
+        .with(&labels! { "guest" => guest.as_str(), "verified" => verified.as_str() })
+        .inc();
+}
+
+/// Increment the count of proofs of the given guest not found in the proof cache.
+pub fn inc_proof_cache_miss(guest: &ProofType) {
+    let guest = guest.to_string();
+    PROOF_CACHE_MISS_COUNT
+        .with(&labels! { "guest" => guest.as_str() })
+        .inc();
+}
+
+/// Increment the count of inputs loaded from the input cache.
+pub fn inc_input_cache_hit() {
+    INPUT_CACHE_HIT_COUNT.inc();
+}
+
+/// Increment the count of inputs not found in the input cache.
+pub fn inc_input_cache_miss() {
+    INPUT_CACHE_MISS_COUNT.inc();
+}
+
+/// Increment the count of retried proof requests for the given guest.
+pub fn inc_retry_count(guest: &ProofType) {
+    let guest = guest.to_string();
+    RETRY_COUNT
+        .with(&labels! { "guest" => guest.as_str() })
+        .inc();
+}
+
+/// Increment the count of new blocks seen by the watcher of the given network.
+pub fn inc_watched_block_count(network: &str) {
+    WATCHED_BLOCK_COUNT
+        .with(&labels! { "network" => network })
+        .inc();
+}
+
+/// Increment the count of proof requests rejected by the given limit, `client` or `in_flight`.
+pub fn inc_rate_limited_count(limit: &str) {
+    RATE_LIMITED_COUNT.with(&labels! { "limit" => limit }).inc();
+}
+
+/// Add to the count of running jobs with the given result on shutdown, `drained` or `aborted`.
+pub fn inc_shutdown_job_count(result: &str, count: usize) {
+    SHUTDOWN_JOB_COUNT
+        .with(&labels! { "result" => result })
+        .inc_by(count as u64);
+}
+
+/// Increment the count of proofs sent to workers with the given result, `success` or
+/// `failed`.
+pub fn inc_worker_dispatch_count(result: &str) {
+    WORKER_DISPATCH_COUNT
+        .with(&labels! { "result" => result })
+        .inc();
+}
+
+/// Increment the count of config reloads with the given result, `applied` or `rejected`.
+pub fn inc_config_reload_count(result: &str) {
+    CONFIG_RELOAD_COUNT
+        .with(&labels! { "result" => result })
+        .inc();
+}
+
+/// Increment the count of requests made with the API key of the given name.
+pub fn inc_api_key_request_count(key: &str) {
+    API_KEY_REQUEST_COUNT.with(&labels! { "key" => key }).inc();
+}
+
+/// Increment the count of proof requests made with the API key of the given name.
+pub fn inc_api_key_proof_count(key: &str) {
+    API_KEY_PROOF_COUNT.with(&labels! { "key" => key }).inc();
+}
+
+/// Observe the time remaining until the deadline of a request once its proof is generated,
+/// negative if the deadline was missed.
+pub fn observe_deadline_remaining(guest: &ProofType, remaining: i64) {
+    let guest = guest.to_string();
+    DEADLINE_REMAINING
+        .with(&labels! { "guest" => guest.as_str() })
+        .observe(remaining as f64);
+}
+
 /// Observe the time taken for the given guest to generate a proof.
-pub fn observe_guest_time(guest: &ProofType, block_id: u64, time: u128, success: bool) {
+pub fn observe_guest_time(
+    guest: &ProofType,
+    network: &str,
+    block_id: u64,
+    time: u128,
+    success: bool,
+) {
     let guest = guest.to_string();
-    let block_id = block_id.to_string();
+    let block_id = block_label(block_id);
     let success = success.to_string();
     let labels = labels! {
         "guest" => guest.as_str(),
+        "network" => network,
         "block_id" => &block_id,
         "success" => &success,
     };
@@ -137,23 +682,231 @@ pub fn observe_guest_time(guest: &ProofType, block_id: u64, time: u128, success:
 }
 
 /// Observe the time taken for prepare input.
-pub fn observe_prepare_input_time(block_id: u64, time: u128, success: bool) {
-    let block_id = block_id.to_string();
+pub fn observe_prepare_input_time(network: &str, block_id: u64, time: u128, success: bool) {
+    let block_id = block_label(block_id);
     let success = success.to_string();
     let labels = labels! {
+        "network" => network,
         "block_id" => block_id.as_str(),
         "success" => &success,
     };
     PREPARE_INPUT_TIME.with(&labels).observe(time as f64);
 }
 
-/// Observe the time taken for prepare input.
-pub fn observe_total_time(block_id: u64, time: u128, success: bool) {
-    let block_id = block_id.to_string();
+/// Observe the time taken for the whole request, and keep the request in the list of recent
+/// requests.
+pub fn observe_total_time(
+    guest: &ProofType,
+    network: &str,
+    block_id: u64,
+    time: u128,
+    success: bool,
+) {
+    record_recent_request(RecentRequest {
+        network: network.to_string(),
+        block_number: block_id,
+        proof_type: guest.to_string(),
+        success,
+        total_time: time as u64,
+        finished_at: unix_timestamp(),
+    });
+    let block_id = block_label(block_id);
     let success = success.to_string();
     let labels = labels! {
+        "network" => network,
         "block_id" => block_id.as_str(),
         "success" => &success,
     };
     TOTAL_TIME.with(&labels).observe(time as f64);
 }
+
+/// Label the per block metrics by network and success only, instead of by block as well. This
+/// keeps the number of series of long running hosts bounded, the blocks of the recent requests
+/// are listed by [recent_requests] instead.
+pub fn configure(bounded_labels: bool) {
+    BOUNDED_LABELS.store(bounded_labels, Ordering::Relaxed);
+}
+
+/// The `block_id` label of a block, empty with bounded labels.
+fn block_label(block_id: u64) -> String {
+    if BOUNDED_LABELS.load(Ordering::Relaxed) {
+        String::new()
+    } else {
+        block_id.to_string()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// A finished proof request.
+pub struct RecentRequest {
+    pub network: String,
+    pub block_number: u64,
+    pub proof_type: String,
+    /// Whether the proof was generated.
+    pub success: bool,
+    /// The time taken for the whole request in milliseconds.
+    pub total_time: u64,
+    /// Unix timestamp the request finished at.
+    pub finished_at: u64,
+}
+
+fn record_recent_request(request: RecentRequest) {
+    let mut recent = RECENT_REQUESTS.lock().unwrap_or_else(|e| e.into_inner());
+    if recent.len() >= MAX_RECENT_REQUESTS {
+        recent.pop_back();
+    }
+    recent.push_front(request);
+}
+
+/// The last finished proof requests, the most recent first.
+pub fn recent_requests() -> Vec<RecentRequest> {
+    let recent = RECENT_REQUESTS.lock().unwrap_or_else(|e| e.into_inner());
+    recent.iter().cloned().collect()
+}
+
+/// The job name of the metrics pushed to a Prometheus Pushgateway.
+const PUSHGATEWAY_JOB: &str = "raiko";
+
+/// Push the metrics to the Pushgateway at `url` every `interval`.
+pub fn spawn_push(url: String, interval: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = push(&url).await {
+                warn!("Could not push the metrics to {url}: {e}");
+            }
+        }
+    });
+}
+
+/// Push the metrics to the Pushgateway at `url`, replacing the metrics pushed before.
+pub async fn push(url: &str) -> anyhow::Result<()> {
+    let encoder = TextEncoder::new();
+    let mut buffer = vec![];
+    encoder.encode(&prometheus::gather(), &mut buffer)?;
+    reqwest::Client::new()
+        .put(format!(
+            "{}/metrics/job/{PUSHGATEWAY_JOB}",
+            url.trim_end_matches('/')
+        ))
+        .header(reqwest::header::CONTENT_TYPE, encoder.format_type())
+        .body(buffer)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Write the current value of all metrics to the file at `path` as JSON.
+pub fn write_snapshot(path: &Path) -> anyhow::Result<()> {
+    let file = File::create(path)?;
+    serde_json::to_writer_pretty(
+        BufWriter::new(file),
+        &json!({
+            "timestamp": unix_timestamp(),
+            "metrics": snapshot(&prometheus::gather()),
+        }),
+    )?;
+    Ok(())
+}
+
+/// The metric families as JSON, by name with the samples of every label set.
+fn snapshot(families: &[MetricFamily]) -> Value {
+    let families = families
+        .iter()
+        .map(|family| {
+            let samples = family
+                .get_metric()
+                .iter()
+                .map(|metric| {
+                    let labels: Map<String, Value> = metric
+                        .get_label()
+                        .iter()
+                        .map(|label| (label.get_name().to_string(), label.get_value().into()))
+                        .collect();
+                    match family.get_field_type() {
+                        MetricType::COUNTER => {
+                            json!({ "labels": labels, "value": metric.get_counter().get_value() })
+                        }
+                        MetricType::GAUGE => {
+                            json!({ "labels": labels, "value": metric.get_gauge().get_value() })
+                        }
+                        MetricType::HISTOGRAM => {
+                            let histogram = metric.get_histogram();
+                            let buckets: Map<String, Value> = histogram
+                                .get_bucket()
+                                .iter()
+                                .map(|bucket| {
+                                    (
+                                        bucket.get_upper_bound().to_string(),
+                                        bucket.get_cumulative_count().into(),
+                                    )
+                                })
+                                .collect();
+                            json!({
+                                "labels": labels,
+                                "count": histogram.get_sample_count(),
+                                "sum": histogram.get_sample_sum(),
+                                "buckets": buckets,
+                            })
+                        }
+                        MetricType::SUMMARY => {
+                            let summary = metric.get_summary();
+                            json!({
+                                "labels": labels,
+                                "count": summary.get_sample_count(),
+                                "sum": summary.get_sample_sum(),
+                            })
+                        }
+                        MetricType::UNTYPED => {
+                            json!({ "labels": labels, "value": metric.get_untyped().get_value() })
+                        }
+                    }
+                })
+                .collect::<Vec<_>>();
+            let kind = format!("{:?}", family.get_field_type()).to_lowercase();
+            (
+                family.get_name().to_string(),
+                json!({ "help": family.get_help(), "type": kind, "samples": samples }),
+            )
+        })
+        .collect::<Map<String, Value>>();
+    Value::Object(families)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot() {
+        let registry = prometheus::Registry::new();
+        let counter = IntCounterVec::new(
+            prometheus::Opts::new("test_count", "a test counter"),
+            &["guest"],
+        )
+        .unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        counter.with(&labels! { "guest" => "native" }).inc_by(3);
+
+        assert_eq!(
+            snapshot(&registry.gather()),
+            json!({
+                "test_count": {
+                    "help": "a test counter",
+                    "type": "counter",
+                    "samples": [{ "labels": { "guest": "native" }, "value": 3.0 }],
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_bounded_labels() {
+        assert_eq!(block_label(10), "10");
+        configure(true);
+        assert_eq!(block_label(10), "");
+        configure(false);
+    }
+}
@@ -0,0 +1,221 @@
+//! A mock prover for load testing.
+//!
+//! The mock prover doesn't prove anything: after an artificial latency it returns a fake proof
+//! of the configured size, derived from the protocol instance hash of the block, so the same
+//! block always gets the same proof. A share of the proofs can be failed on purpose. This lets
+//! relayers, queues and other integrations downstream of the host be load tested without
+//! spending hours of GPU time per proof.
+
+use std::time::Duration;
+
+use alloy_primitives::B256;
+use raiko_lib::{
+    input::{GuestInput, GuestOutput},
+    protocol_instance::{EvidenceType, ProtocolInstance},
+    prover::{
+        to_proof, ProgressReporter, Proof, Prover, ProverConfig, ProverError, ProverProgress,
+        ProverResult,
+    },
+};
+use raiko_primitives::hex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub struct MockProver;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MockParam {
+    /// The milliseconds generating a proof takes.
+    pub latency_ms: u64,
+    /// The maximum milliseconds randomly added to the latency.
+    pub jitter_ms: u64,
+    /// The share of proofs that fail, between 0 and 1.
+    pub failure_rate: f64,
+    /// The size of the fake proof in bytes.
+    pub proof_size: usize,
+}
+
+impl Default for MockParam {
+    fn default() -> Self {
+        Self {
+            latency_ms: 0,
+            jitter_ms: 0,
+            failure_rate: 0.0,
+            proof_size: 256,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MockResponse {
+    /// The fake proof, hex encoded.
+    pub proof: String,
+    /// The protocol instance hash the proof is derived from.
+    pub pi_hash: B256,
+}
+
+/// The number of progress updates reported while waiting out the latency.
+const PROGRESS_STEPS: u32 = 10;
+
+impl MockParam {
+    fn from_config(config: &ProverConfig) -> ProverResult<Self> {
+        Ok(config
+            .get("mock")
+            .map(MockParam::deserialize)
+            .transpose()
+            .map_err(|e| ProverError::GuestError(format!("Mock: invalid params: {e}")))?
+            .unwrap_or_default())
+    }
+
+    fn latency(&self) -> Duration {
+        let jitter = match self.jitter_ms {
+            0 => 0,
+            jitter_ms => rand::random::<u64>() % (jitter_ms + 1),
+        };
+        Duration::from_millis(self.latency_ms + jitter)
+    }
+
+    fn fails(&self) -> bool {
+        rand::random::<f64>() < self.failure_rate
+    }
+}
+
+/// The fake proof of the given size for the protocol instance hash, the concatenated SHA-256
+/// hashes of the instance hash and a counter.
+fn mock_proof(pi_hash: &B256, size: usize) -> Vec<u8> {
+    let mut proof = Vec::with_capacity(size);
+    let mut counter = 0u64;
+    while proof.len() < size {
+        let block = Sha256::new()
+            .chain_update(pi_hash)
+            .chain_update(counter.to_be_bytes())
+            .finalize();
+        let len = block.len().min(size - proof.len());
+        proof.extend_from_slice(&block[..len]);
+        counter += 1;
+    }
+    proof
+}
+
+fn pi_hash(output: &GuestOutput) -> ProverResult<B256> {
+    match output {
+        GuestOutput::Success((_, pi_hash)) => Ok(*pi_hash),
+        GuestOutput::Failure => Err(ProverError::GuestError(
+            "Mock: the block failed to execute".to_string(),
+        )),
+    }
+}
+
+/// Check that the proof is the fake proof of the output.
+fn verify_proof(proof: &Proof, output: &GuestOutput) -> ProverResult<()> {
+    let response = MockResponse::deserialize(proof)
+        .map_err(|e| ProverError::GuestError(format!("Mock: invalid proof: {e}")))?;
+    let pi_hash = pi_hash(output)?;
+    let size = response.proof.len() / 2;
+    if response.pi_hash != pi_hash || response.proof != hex::encode(mock_proof(&pi_hash, size)) {
+        return Err(ProverError::GuestError(
+            "Mock: the proof doesn't match the output".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+impl Prover for MockProver {
+    async fn run(
+        input: GuestInput,
+        output: GuestOutput,
+        config: &ProverConfig,
+    ) -> ProverResult<Proof> {
+        Self::run_with_progress(input, output, config, &ProgressReporter::default()).await
+    }
+
+    async fn run_with_progress(
+        _input: GuestInput,
+        output: GuestOutput,
+        config: &ProverConfig,
+        progress: &ProgressReporter,
+    ) -> ProverResult<Proof> {
+        let param = MockParam::from_config(config)?;
+        let pi_hash = pi_hash(&output)?;
+
+        let step = param.latency() / PROGRESS_STEPS;
+        for i in 1..=PROGRESS_STEPS {
+            tokio::time::sleep(step).await;
+            progress.report(ProverProgress::Execution((i * 100 / PROGRESS_STEPS) as u8));
+        }
+        if param.fails() {
+            return Err(ProverError::GuestError(
+                "Mock: injected proof failure".to_string(),
+            ));
+        }
+
+        to_proof(Ok(MockResponse {
+            proof: hex::encode(mock_proof(&pi_hash, param.proof_size)),
+            pi_hash,
+        }))
+    }
+
+    fn instance_hash(pi: ProtocolInstance) -> B256 {
+        pi.instance_hash(EvidenceType::Native)
+    }
+
+    fn verify(
+        proof: &Proof,
+        _input: &GuestInput,
+        output: &GuestOutput,
+    ) -> Option<ProverResult<()>> {
+        Some(verify_proof(proof, output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use raiko_lib::input::WrappedHeader;
+
+    use super::*;
+
+    fn output(pi_hash: B256) -> GuestOutput {
+        GuestOutput::Success((
+            WrappedHeader {
+                header: Default::default(),
+            },
+            pi_hash,
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_mock_prover() {
+        let config = serde_json::json!({"mock": {"latency_ms": 10, "proof_size": 100}});
+        let proof = MockProver::run(GuestInput::default(), output(B256::repeat_byte(1)), &config)
+            .await
+            .unwrap();
+        // The proof is deterministic and verifies against its output only.
+        let again = MockProver::run(GuestInput::default(), output(B256::repeat_byte(1)), &config)
+            .await
+            .unwrap();
+        assert_eq!(proof, again);
+        assert_eq!(proof["proof"].as_str().unwrap().len(), 200);
+        assert!(MockProver::verify(
+            &proof,
+            &GuestInput::default(),
+            &output(B256::repeat_byte(1))
+        )
+        .unwrap()
+        .is_ok());
+        assert!(MockProver::verify(
+            &proof,
+            &GuestInput::default(),
+            &output(B256::repeat_byte(2))
+        )
+        .unwrap()
+        .is_err());
+
+        let config = serde_json::json!({"mock": {"failure_rate": 1.0}});
+        assert!(
+            MockProver::run(GuestInput::default(), output(B256::ZERO), &config)
+                .await
+                .is_err()
+        );
+    }
+}
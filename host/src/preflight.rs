@@ -1,61 +1,273 @@
-use std::sync::Arc;
-
 use alloy_consensus::{
-    SignableTransaction, TxEip1559, TxEip2930, TxEip4844, TxEip4844Variant, TxEnvelope, TxLegacy,
+    Header as AlloyConsensusHeader, SignableTransaction, TxEip1559, TxEip2930, TxEip4844,
+    TxEip4844Variant, TxEnvelope, TxLegacy,
 };
 pub use alloy_primitives::*;
 use alloy_provider::{Provider, ProviderBuilder, ReqwestProvider, RootProvider};
-use alloy_rpc_types::{
-    Block as AlloyBlock, BlockTransactions, Filter, Transaction as AlloyRpcTransaction,
-};
-use alloy_sol_types::{SolCall, SolEvent};
-use anyhow::{anyhow, bail, Result};
-use c_kzg::{Blob, KzgCommitment};
-use hashbrown::HashSet;
+use alloy_rpc_types::{Block as AlloyBlock, BlockTransactions, Transaction as AlloyRpcTransaction};
+use alloy_sol_types::SolCall;
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use futures::future::try_join_all;
+use hashbrown::HashMap;
 use raiko_lib::{
+    anchor::AnchorRules,
     builder::{
         prepare::TaikoHeaderPrepStrategy, BlockBuilder, OptimisticDatabase, TkoTxExecStrategy,
     },
-    consts::{get_network_spec, Network},
-    input::{
-        decode_anchor, proposeBlockCall, taiko_a6::BlockProposed as TestnetBlockProposed,
-        BlockProposed, GuestInput, TaikoGuestInput, TaikoProverData,
-    },
-    taiko_utils::{generate_transactions, to_header},
+    consts::{get_network_spec, ChainSpec, Network},
+    input::{proposeBlockCall, BlockProposed, GuestInput, TaikoGuestInput, TaikoProverData},
+    protocol_instance::build_protocol_instance,
+    taiko_utils::{generate_transactions, to_header, verify_blob},
     Measurement,
 };
-use raiko_primitives::{
-    eip4844::{kzg_to_versioned_hash, MAINNET_KZG_TRUSTED_SETUP},
-    mpt::proofs_to_tries,
-};
+use raiko_primitives::{keccak::KECCAK_EMPTY, mpt::proofs_to_tries};
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 
-use crate::provider_db::ProviderDb;
+use crate::{
+    blob_provider::BlobProviders, header_store::HeaderStore, l1_events::find_block_proposed,
+    provider_db::ProviderDb, soft_block::SoftBlock,
+};
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// How the preflight finds the state accessed by the block.
+pub enum PreflightStrategy {
+    /// Execute the block until all accessed state is fetched.
+    #[default]
+    Execute,
+    /// Get the accessed state with a `debug_traceBlockByNumber` call first, then execute the
+    /// block for anything missing. Falls back to `Execute` if the node doesn't support tracing.
+    Trace,
+}
 
 pub fn preflight(
     rpc_url: Option<String>,
     block_number: u64,
     network: Network,
+    chain_spec: ChainSpec,
+    strategy: PreflightStrategy,
+    header_store: &HeaderStore,
     prover_data: TaikoProverData,
     l1_rpc_url: Option<String>,
-    beacon_rpc_url: Option<String>,
+    blob_providers: BlobProviders,
 ) -> Result<GuestInput> {
     let provider = ProviderBuilder::new().provider(RootProvider::new_http(
         reqwest::Url::parse(&rpc_url.clone().unwrap()).expect("invalid rpc url"),
     ));
+
+    let (input, _header) = prepare_block_data(
+        &provider,
+        block_number,
+        network,
+        chain_spec,
+        prover_data,
+        l1_rpc_url,
+        blob_providers,
+    )?;
+    collect_state(provider, input, strategy, header_store, true)
+}
+
+/// The preflight of a block that is not proposed yet, see [crate::soft_block]. The block is
+/// taken from the request instead of the node, only the parent of the block and its anchor
+/// block are fetched, and the state the block accesses.
+#[allow(clippy::too_many_arguments)]
+pub fn soft_block_preflight(
+    rpc_url: String,
+    l1_rpc_url: String,
+    block: &SoftBlock,
+    network: Network,
+    chain_spec: ChainSpec,
+    strategy: PreflightStrategy,
+    header_store: &HeaderStore,
+    prover_data: TaikoProverData,
+) -> Result<GuestInput> {
+    let provider = ProviderBuilder::new().provider(RootProvider::new_http(
+        reqwest::Url::parse(&rpc_url).expect("invalid rpc url"),
+    ));
+    let provider_l1 = ProviderBuilder::new().provider(RootProvider::new_http(
+        reqwest::Url::parse(&l1_rpc_url).expect("invalid rpc url"),
+    ));
+
+    let input = prepare_soft_block_data(
+        &provider,
+        &provider_l1,
+        block,
+        network,
+        chain_spec,
+        prover_data,
+    )?;
+    // A node following the preconfirmations may have the block already, and the state after it.
+    let on_node = tokio::runtime::Handle::current()
+        .block_on(provider.get_block_by_number(block.number.into(), false))?
+        .and_then(|on_node| on_node.header.hash)
+        == Some(block.hash);
+    // The node can only trace the blocks it has.
+    let strategy = if on_node {
+        strategy
+    } else {
+        PreflightStrategy::Execute
+    };
+    collect_state(provider, input, strategy, header_store, on_node)
+}
+
+/// Execute the block of the input on the state of its parent on the node, and add the state
+/// the block accesses to the input. Without the block on the node, the proofs of the state
+/// after the block are taken at its parent, so the trie nodes a deletion of the block moves
+/// up are missing and such a block fails to execute in the prover.
+fn collect_state(
+    provider: ReqwestProvider,
+    input: GuestInput,
+    strategy: PreflightStrategy,
+    header_store: &HeaderStore,
+    block_on_node: bool,
+) -> Result<GuestInput> {
     let is_local = provider.client().is_local();
+    let block_number = input.block_number;
+
+    // Create the block builder, run the transactions and extract the DB
+    let mut provider_db = ProviderDb::new(provider, input.network, block_number - 1, header_store)?;
+    if !block_on_node {
+        provider_db.final_block_number = block_number - 1;
+    }
+    if strategy == PreflightStrategy::Trace && !is_local {
+        let measurement = Measurement::start("Prefetching state from trace...", true);
+        match provider_db.prefetch_from_trace() {
+            Ok((num_accounts, num_slots)) => measurement
+                .stop_with_count(&format!("[{num_accounts} Account/{num_slots} Storage]")),
+            Err(e) => {
+                measurement.stop();
+                warn!("Tracing block {block_number} failed, falling back to execution: {e}");
+            }
+        }
+    }
+
+    let mut builder = BlockBuilder::new(&input)
+        .with_db(provider_db)
+        .prepare_header::<TaikoHeaderPrepStrategy>()?;
+
+    // Optimize data gathering by executing the transactions multiple times so data can be requested in batches
+    let max_iterations = if is_local { 1 } else { 50 };
+    let mut done = false;
+    let mut num_iterations = 0;
+    while !done {
+        println!("Execution iteration {num_iterations}...");
+        builder.mut_db().unwrap().optimistic = num_iterations + 1 < max_iterations;
+        builder = builder.execute_transactions::<TkoTxExecStrategy>()?;
+        if builder.mut_db().unwrap().fetch_data() {
+            done = true;
+        }
+        num_iterations += 1;
+    }
+    builder = builder.prepare_header::<TaikoHeaderPrepStrategy>()?;
+    let provider_db = builder.mut_db().unwrap();
+
+    // Gather inclusion proofs for the initial and final state
+    let measurement = Measurement::start("Fetching storage proofs...", true);
+    let (parent_proofs, proofs, num_storage_proofs) = provider_db.get_proofs()?;
+    measurement.stop_with_count(&format!(
+        "[{} Account/{num_storage_proofs} Storage]",
+        parent_proofs.len() + proofs.len(),
+    ));
+
+    // Construct the state trie and storage from the storage proofs.
+    let measurement = Measurement::start("Constructing MPT...", true);
+    let (state_trie, storage) =
+        proofs_to_tries(input.parent_header.state_root, parent_proofs, proofs)?;
+    measurement.stop();
+
+    // Gather proofs for block history
+    let measurement = Measurement::start("Fetching historical block headers...", true);
+    let ancestor_headers = provider_db.get_ancestor_headers()?;
+    measurement.stop();
+
+    // Get the contracts from the initial db.
+    let measurement = Measurement::start("Fetching contract code...", true);
+    // The code is keyed by its hash, so the code shared by accounts is sent once, and the
+    // empty code of the accounts without code isn't sent at all.
+    let mut contracts = HashMap::new();
+    let initial_db = &provider_db.initial_db;
+    for account in initial_db.accounts.values() {
+        let code_hash = account.info.code_hash;
+        if code_hash.0 == KECCAK_EMPTY.0 {
+            continue;
+        }
+        if let Some(code) = &account.info.code {
+            contracts
+                .entry(code_hash)
+                .or_insert_with(|| code.bytecode.0.clone());
+        }
+    }
+    measurement.stop();
 
+    // Add the collected data to the input
+    Ok(GuestInput {
+        parent_state_trie: state_trie,
+        parent_storage: storage,
+        contracts: contracts.into_values().map(Bytes).collect(),
+        ancestor_headers,
+        ..input
+    })
+}
+
+/// The input of the block and its header without the state the block accesses, enough to
+/// assemble the protocol instance of the block without executing it.
+pub fn block_data(
+    rpc_url: String,
+    block_number: u64,
+    network: Network,
+    chain_spec: ChainSpec,
+    prover_data: TaikoProverData,
+    l1_rpc_url: Option<String>,
+    blob_providers: BlobProviders,
+) -> Result<(GuestInput, AlloyConsensusHeader)> {
+    let provider = ProviderBuilder::new().provider(RootProvider::new_http(
+        reqwest::Url::parse(&rpc_url).expect("invalid rpc url"),
+    ));
+    prepare_block_data(
+        &provider,
+        block_number,
+        network,
+        chain_spec,
+        prover_data,
+        l1_rpc_url,
+        blob_providers,
+    )
+}
+
+/// Fetch the block and its Taiko data, returning the input without the state and the header of
+/// the block.
+fn prepare_block_data(
+    provider: &ReqwestProvider,
+    block_number: u64,
+    network: Network,
+    chain_spec: ChainSpec,
+    prover_data: TaikoProverData,
+    l1_rpc_url: Option<String>,
+    blob_providers: BlobProviders,
+) -> Result<(GuestInput, AlloyConsensusHeader)> {
     let measurement = Measurement::start("Fetching block data...", true);
 
-    let block = get_block(&provider, block_number, true).unwrap();
-    let parent_block = get_block(&provider, block_number - 1, false).unwrap();
+    let [block, parent_block] =
+        get_blocks(provider, [(block_number, true), (block_number - 1, false)])?;
 
     println!("\nblock.hash: {:?}", block.header.hash.unwrap());
     println!("block.parent_hash: {:?}", block.header.parent_hash);
     println!("block gas used: {:?}", block.header.gas_used.as_limbs()[0]);
     println!("block transactions: {:?}", block.transactions.len());
 
-    let taiko_guest_input = if network.is_taiko() {
+    // The rules of the forks active at the block
+    let rules = chain_spec.fork_rules(block_number, block.header.timestamp.try_into().unwrap())?;
+    ensure!(
+        block.header.parent_beacon_block_root.is_some() == rules.has_blob_gas(),
+        "Block {block_number} doesn't match the blob rules of {:?}",
+        rules.spec_id
+    );
+
+    let anchor_rules = AnchorRules::new(&chain_spec, &rules);
+    let (taiko_guest_input, anchor) = if network.is_taiko() {
+        let anchor_rules =
+            anchor_rules.ok_or_else(|| anyhow!("Missing the Taiko forks in the chain spec"))?;
         let provider_l1 = ProviderBuilder::new().provider(RootProvider::new_http(
             reqwest::Url::parse(&l1_rpc_url.clone().unwrap()).expect("invalid rpc url"),
         ));
@@ -65,17 +277,22 @@ pub fn preflight(
             BlockTransactions::Full(txs) => txs[0].to_owned(),
             _ => unreachable!(),
         };
-        let anchor_call = decode_anchor(anchor_tx.input.as_ref())?;
+        let anchor_call = anchor_rules.decode(anchor_tx.input.as_ref())?;
         // The L1 blocks we need
-        let l1_state_block_number = anchor_call.l1BlockId;
+        let l1_state_block_number = anchor_call.l1_block_id;
         let l1_inclusion_block_number = l1_state_block_number + 1;
 
-        println!("anchor L1 block id: {:?}", anchor_call.l1BlockId);
-        println!("anchor L1 state root: {:?}", anchor_call.l1StateRoot);
+        println!("anchor L1 block id: {:?}", anchor_call.l1_block_id);
+        println!("anchor L1 state root: {:?}", anchor_call.l1_state_root);
 
         // Get the L1 state block header so that we can prove the L1 state root
-        let l1_inclusion_block = get_block(&provider_l1, l1_inclusion_block_number, false).unwrap();
-        let l1_state_block = get_block(&provider_l1, l1_state_block_number, false).unwrap();
+        let [l1_inclusion_block, l1_state_block] = get_blocks(
+            &provider_l1,
+            [
+                (l1_inclusion_block_number, false),
+                (l1_state_block_number, false),
+            ],
+        )?;
         println!(
             "l1_state_root_block hash: {:?}",
             l1_state_block.header.hash.unwrap()
@@ -84,47 +301,46 @@ pub fn preflight(
         // Get the block proposal data
         let (proposal_tx, proposal_event) = get_block_proposed_event(
             &provider_l1,
-            network,
+            rules.testnet_proposal(),
+            chain_spec
+                .l1_contract
+                .expect("missing L1 contract in the chain spec"),
             l1_inclusion_block.header.hash.unwrap(),
             block_number,
         )?;
 
         // Fetch the tx list
-        let (tx_list, tx_blob_hash) = if proposal_event.meta.blobUsed {
+        let (tx_list, tx_blob_hash, blob_commitment, blob_proof) = if proposal_event.meta.blobUsed {
             println!("blob active");
             // Get the blob hashes attached to the propose tx
             let blob_hashes = proposal_tx.blob_versioned_hashes.unwrap_or_default();
             assert!(!blob_hashes.is_empty());
             // Currently the protocol enforces the first blob hash to be used
             let blob_hash = blob_hashes[0];
-            let l2_chain_spec = get_network_spec(network);
             // Get the blob data for this block
             let slot_id = block_time_to_block_slot(
                 l1_inclusion_block.header.timestamp.as_limbs()[0],
-                l2_chain_spec.genesis_time,
-                l2_chain_spec.seconds_per_slot,
+                chain_spec.genesis_time,
+                chain_spec.seconds_per_slot,
             )?;
-            let blobs = get_blob_data(&beacon_rpc_url.clone().unwrap(), slot_id)?;
-            assert!(!blobs.data.is_empty(), "blob data not available anymore");
-            // Get the blob data for the blob storing the tx list
-            let tx_blob = blobs
-                .data
-                .iter()
-                .find(|blob| {
-                    // calculate from plain blob
-                    blob_hash == calc_blob_versioned_hash(&blob.blob)
-                })
-                .cloned();
-            assert!(tx_blob.is_some());
-            (blob_to_bytes(&tx_blob.unwrap().blob), Some(blob_hash))
+            // Get the blob storing the tx list, the versioned hash of its KZG commitment
+            // matches the blob hash
+            let tx_blob = tokio::runtime::Handle::current()
+                .block_on(blob_providers.get_blob(slot_id, blob_hash))?;
+            (
+                tx_blob.blob.to_vec(),
+                Some(blob_hash),
+                Some(tx_blob.kzg_commitment),
+                Some(tx_blob.kzg_proof),
+            )
         } else {
             // Get the tx list data directly from the propose transaction data
             let proposal_call = proposeBlockCall::abi_decode(&proposal_tx.input, false).unwrap();
-            (proposal_call.txList.as_ref().to_owned(), None)
+            (proposal_call.txList.as_ref().to_owned(), None, None, None)
         };
 
         // Create the transactions from the proposed tx list
-        let transactions = generate_transactions(
+        let mut transactions = generate_transactions(
             proposal_event.meta.blobUsed,
             &tx_list,
             Some(anchor_tx.clone()),
@@ -136,26 +352,36 @@ pub fn preflight(
         );
 
         // Create the input struct without the block data set
-        TaikoGuestInput {
+        let taiko_input = TaikoGuestInput {
             l1_header: to_header(&l1_state_block.header),
             tx_list,
             anchor_tx: serde_json::to_string(&anchor_tx).unwrap(),
             tx_blob_hash,
+            blob_commitment,
+            blob_proof,
             block_proposed: proposal_event,
             prover_data,
+        };
+        // Check the blob against its commitment now, instead of failing in the prover
+        if let Some(blob_hash) = tx_blob_hash {
+            verify_blob(&taiko_input, blob_hash)?;
         }
+        (taiko_input, Some(transactions.remove(0)))
     } else {
         // For Ethereum blocks we just convert the block transactions in a tx_list
         // so that we don't have to supports separate paths.
-        TaikoGuestInput {
+        let taiko_input = TaikoGuestInput {
             tx_list: alloy_rlp::encode(&get_transactions_from_block(&block)),
             ..Default::default()
-        }
+        };
+        (taiko_input, None)
     };
     measurement.stop();
 
+    let header = to_header(&block.header);
     let input = GuestInput {
         network,
+        chain_spec,
         block_number,
         gas_used: block.header.gas_used.try_into().unwrap(),
         block_hash: block.header.hash.unwrap(),
@@ -176,73 +402,114 @@ pub fn preflight(
         parent_beacon_block_root: block.header.parent_beacon_block_root,
         taiko: taiko_guest_input,
     };
-
-    // Create the block builder, run the transactions and extract the DB
-    let provider_db = ProviderDb::new(
-        provider,
-        network,
-        parent_block.header.number.unwrap().try_into().unwrap(),
-    )?;
-
-    let mut builder = BlockBuilder::new(&input)
-        .with_db(provider_db)
-        .prepare_header::<TaikoHeaderPrepStrategy>()?;
-
-    // Optimize data gathering by executing the transactions multiple times so data can be requested in batches
-    let max_iterations = if is_local { 1 } else { 50 };
-    let mut done = false;
-    let mut num_iterations = 0;
-    while !done {
-        println!("Execution iteration {num_iterations}...");
-        builder.mut_db().unwrap().optimistic = num_iterations + 1 < max_iterations;
-        builder = builder.execute_transactions::<TkoTxExecStrategy>()?;
-        if builder.mut_db().unwrap().fetch_data() {
-            done = true;
-        }
-        num_iterations += 1;
+    // Check the anchor tx now, instead of failing in the prover
+    if let (Some(anchor_rules), Some(anchor)) = (anchor_rules, anchor) {
+        anchor_rules
+            .check_signed(&input, &anchor)
+            .context("Invalid anchor tx")?;
     }
-    builder = builder.prepare_header::<TaikoHeaderPrepStrategy>()?;
-    let provider_db = builder.mut_db().unwrap();
 
-    // Gather inclusion proofs for the initial and final state
-    let measurement = Measurement::start("Fetching storage proofs...", true);
-    let (parent_proofs, proofs, num_storage_proofs) = provider_db.get_proofs()?;
-    measurement.stop_with_count(&format!(
-        "[{} Account/{num_storage_proofs} Storage]",
-        parent_proofs.len() + proofs.len(),
-    ));
+    Ok((input, header))
+}
 
-    // Construct the state trie and storage from the storage proofs.
-    let measurement = Measurement::start("Constructing MPT...", true);
-    let (state_trie, storage) =
-        proofs_to_tries(input.parent_header.state_root, parent_proofs, proofs)?;
+/// The input of the soft block without the state, with the metadata the block is to be proposed
+/// with. The block is checked against its parent before, see [SoftBlock::check_parent].
+fn prepare_soft_block_data(
+    provider: &ReqwestProvider,
+    provider_l1: &ReqwestProvider,
+    block: &SoftBlock,
+    network: Network,
+    chain_spec: ChainSpec,
+    prover_data: TaikoProverData,
+) -> Result<GuestInput> {
+    let measurement = Measurement::start("Fetching parent block data...", true);
+    ensure!(
+        network.is_taiko(),
+        "Only the blocks of Taiko networks are proposed"
+    );
+
+    let [parent_block] = get_blocks(provider, [(block.number - 1, false)])?;
+    ensure!(
+        parent_block.header.hash == Some(block.parent_hash),
+        "The parent of block {} was reorged",
+        block.number
+    );
+
+    let rules = chain_spec.fork_rules(block.number, block.timestamp)?;
+    ensure!(
+        !rules.has_blob_gas(),
+        "Soft blocks of forks with blob gas are not supported"
+    );
+    let anchor_rules = AnchorRules::new(&chain_spec, &rules)
+        .ok_or_else(|| anyhow!("Missing the Taiko forks in the chain spec"))?;
+
+    // Get the L1 state block header the anchor tx refers to, which is on L1 already
+    let anchor_call = anchor_rules.decode(block.anchor_tx.input.as_ref())?;
+    let [l1_state_block] = get_blocks(provider_l1, [(anchor_call.l1_block_id, false)])?;
+    let anchor =
+        generate_transactions(false, &block.tx_list, Some(block.anchor_tx.clone())).remove(0);
     measurement.stop();
 
-    // Gather proofs for block history
-    let measurement = Measurement::start("Fetching historical block headers...", true);
-    let ancestor_headers = provider_db.get_ancestor_headers()?;
-    measurement.stop();
+    let mut input = GuestInput {
+        network,
+        chain_spec,
+        block_number: block.number,
+        gas_used: block.gas_used,
+        block_hash: block.hash,
+        beneficiary: block.beneficiary,
+        gas_limit: block.gas_limit,
+        timestamp: block.timestamp,
+        extra_data: block.extra_data.clone(),
+        mix_hash: block.mix_hash,
+        withdrawals: Default::default(),
+        parent_state_trie: Default::default(),
+        parent_storage: Default::default(),
+        contracts: Default::default(),
+        parent_header: to_header(&parent_block.header),
+        ancestor_headers: Default::default(),
+        base_fee_per_gas: block.base_fee_per_gas,
+        blob_gas_used: None,
+        excess_blob_gas: None,
+        parent_beacon_block_root: None,
+        taiko: TaikoGuestInput {
+            l1_header: to_header(&l1_state_block.header),
+            tx_list: block.tx_list.to_vec(),
+            anchor_tx: serde_json::to_string(&block.anchor_tx).unwrap(),
+            prover_data,
+            ..Default::default()
+        },
+    };
 
-    // Get the contracts from the initial db.
-    let measurement = Measurement::start("Fetching contract code...", true);
-    let mut contracts = HashSet::new();
-    let initial_db = &provider_db.initial_db;
-    for account in initial_db.accounts.values() {
-        let code = &account.info.code;
-        if let Some(code) = code {
-            contracts.insert(code.bytecode.0.clone());
-        }
-    }
-    measurement.stop();
+    // The block is not proposed yet, so it is proven with the metadata of the proposal of its
+    // tx list in calldata, with the fields not derived from the block taken from the request.
+    let meta = &mut input.taiko.block_proposed.meta;
+    meta.difficulty = block.mix_hash;
+    meta.minTier = block.min_tier;
+    meta.parentMetaHash = block.parent_meta_hash;
+    meta.sender = block.sender;
+    let header = AlloyConsensusHeader {
+        parent_hash: block.parent_hash,
+        number: block.number,
+        base_fee_per_gas: Some(input.base_fee_per_gas),
+        beneficiary: input.beneficiary,
+        gas_limit: input.gas_limit,
+        timestamp: input.timestamp,
+        mix_hash: input.mix_hash,
+        extra_data: input.extra_data.clone(),
+        ..Default::default()
+    };
+    input.taiko.block_proposed = BlockProposed {
+        blockId: U256::from(block.number),
+        meta: build_protocol_instance(&input, &header)?.block_metadata,
+        ..Default::default()
+    };
 
-    // Add the collected data to the input
-    Ok(GuestInput {
-        parent_state_trie: state_trie,
-        parent_storage: storage,
-        contracts: contracts.into_iter().map(Bytes).collect(),
-        ancestor_headers,
-        ..input
-    })
+    // Check the anchor tx now, instead of failing in the prover
+    anchor_rules
+        .check_signed(&input, &anchor)
+        .context("Invalid anchor tx")?;
+
+    Ok(input)
 }
 
 // block_time_to_block_slot returns the slots of the given timestamp.
@@ -260,153 +527,73 @@ fn block_time_to_block_slot(
     }
 }
 
-fn blob_to_bytes(blob_str: &str) -> Vec<u8> {
-    match hex::decode(blob_str.to_lowercase().trim_start_matches("0x")) {
-        Ok(b) => b,
-        Err(_) => Vec::new(),
-    }
-}
-
-fn calc_blob_versioned_hash(blob_str: &str) -> [u8; 32] {
-    let blob_bytes: Vec<u8> =
-        hex::decode(blob_str.to_lowercase().trim_start_matches("0x")).unwrap();
-    let kzg_settings = Arc::clone(&*MAINNET_KZG_TRUSTED_SETUP);
-    let blob = Blob::from_bytes(&blob_bytes).unwrap();
-    let kzg_commit = KzgCommitment::blob_to_kzg_commitment(&blob, &kzg_settings).unwrap();
-    let version_hash: [u8; 32] = kzg_to_versioned_hash(kzg_commit).0;
-    version_hash
-}
-
-fn get_blob_data(beacon_rpc_url: &str, block_id: u64) -> Result<GetBlobsResponse> {
-    let tokio_handle = tokio::runtime::Handle::current();
-    tokio_handle.block_on(async {
-        let url = format!(
-            "{}/eth/v1/beacon/blob_sidecars/{block_id}",
-            beacon_rpc_url.trim_end_matches('/'),
-        );
-        let response = reqwest::get(url.clone()).await?;
-        if response.status().is_success() {
-            let blob_response: GetBlobsResponse = response.json().await?;
-            Ok(blob_response)
-        } else {
-            Err(anyhow::anyhow!(
-                "Request failed with status code: {}",
-                response.status()
-            ))
-        }
-    })
-}
-
-// Blob data from the beacon chain
-// type Sidecar struct {
-// Index                    string                   `json:"index"`
-// Blob                     string                   `json:"blob"`
-// SignedBeaconBlockHeader  *SignedBeaconBlockHeader `json:"signed_block_header"`
-// KzgCommitment            string                   `json:"kzg_commitment"`
-// KzgProof                 string                   `json:"kzg_proof"`
-// CommitmentInclusionProof []string
-// `json:"kzg_commitment_inclusion_proof"` }
-#[derive(Clone, Debug, Deserialize, Serialize)]
-struct GetBlobData {
-    pub index: String,
-    pub blob: String,
-    // pub signed_block_header: SignedBeaconBlockHeader, // ignore for now
-    pub kzg_commitment: String,
-    pub kzg_proof: String,
-    pub kzg_commitment_inclusion_proof: Vec<String>,
+/// Get the hash of the given block.
+pub async fn get_block_hash(rpc_url: &str, block_number: u64) -> Result<B256> {
+    let provider =
+        ProviderBuilder::new().provider(RootProvider::new_http(reqwest::Url::parse(rpc_url)?));
+    let block = provider
+        .get_block_by_number(block_number.into(), false)
+        .await?
+        .ok_or_else(|| anyhow!("No data for {block_number:?}"))?;
+    block
+        .header
+        .hash
+        .ok_or_else(|| anyhow!("No hash for block {block_number}"))
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-struct GetBlobsResponse {
-    pub data: Vec<GetBlobData>,
+/// Get the header of the given block.
+pub async fn get_block_header(rpc_url: &str, block_number: u64) -> Result<AlloyConsensusHeader> {
+    let provider =
+        ProviderBuilder::new().provider(RootProvider::new_http(reqwest::Url::parse(rpc_url)?));
+    let block = provider
+        .get_block_by_number(block_number.into(), false)
+        .await?
+        .ok_or_else(|| anyhow!("No data for {block_number:?}"))?;
+    Ok(to_header(&block.header))
 }
 
-pub fn get_block(provider: &ReqwestProvider, block_number: u64, full: bool) -> Result<AlloyBlock> {
+/// Fetch the given blocks, with or without the full transactions, at the same time.
+pub fn get_blocks<const N: usize>(
+    provider: &ReqwestProvider,
+    blocks: [(u64, bool); N],
+) -> Result<[AlloyBlock; N]> {
     let tokio_handle = tokio::runtime::Handle::current();
-    let response = tokio_handle.block_on(async {
-        provider
-            .get_block_by_number((block_number).into(), full)
-            .await
-    })?;
-    match response {
-        Some(out) => Ok(out),
-        None => Err(anyhow!("No data for {block_number:?}")),
-    }
+    let responses =
+        tokio_handle.block_on(try_join_all(blocks.map(|(block_number, full)| {
+            provider.get_block_by_number(block_number.into(), full)
+        })))?;
+    let blocks = responses
+        .into_iter()
+        .zip(blocks)
+        .map(|(response, (block_number, _))| {
+            response.ok_or_else(|| anyhow!("No data for {block_number:?}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(blocks.try_into().unwrap())
 }
 
 fn get_block_proposed_event(
     provider: &ReqwestProvider,
-    network: Network,
+    testnet: bool,
+    l1_address: Address,
     block_hash: B256,
     l2_block_number: u64,
 ) -> Result<(AlloyRpcTransaction, BlockProposed)> {
     let tokio_handle = tokio::runtime::Handle::current();
-
-    // Get the address that emitted the event
-    let l1_address = get_network_spec(network).l1_contract.unwrap();
-
-    // Get the event signature (value can differ between chains)
-    let event_signature = if network == Network::TaikoA6 {
-        TestnetBlockProposed::SIGNATURE_HASH
-    } else {
-        BlockProposed::SIGNATURE_HASH
+    let Some((tx_hash, event)) = tokio_handle.block_on(find_block_proposed(
+        provider,
+        testnet,
+        l1_address,
+        block_hash,
+        l2_block_number,
+    ))?
+    else {
+        bail!("No BlockProposed event found for block {l2_block_number}");
     };
-    // Setup the filter to get the relevant events
-    let filter = Filter::new()
-        .address(l1_address)
-        .at_block_hash(block_hash)
-        .event_signature(event_signature);
-    // Now fetch the events
-    let logs = tokio_handle.block_on(async { provider.get_logs(&filter).await })?;
-
-    // Run over the logs returned to find the matching event for the specified L2 block number
-    // (there can be multiple blocks proposed in the same block and even same tx)
-    for log in logs {
-        if network == Network::TaikoA6 {
-            let event = TestnetBlockProposed::decode_log(
-                &Log::new(
-                    log.address(),
-                    log.topics().to_vec(),
-                    log.data().data.clone(),
-                )
-                .unwrap(),
-                false,
-            )
-            .unwrap();
-            if event.blockId == raiko_primitives::U256::from(l2_block_number) {
-                let tx = tokio_handle
-                    .block_on(async {
-                        provider
-                            .get_transaction_by_hash(log.transaction_hash.unwrap())
-                            .await
-                    })
-                    .expect("could not find the propose tx");
-                return Ok((tx, event.data.into()));
-            }
-        } else {
-            let event = BlockProposed::decode_log(
-                &Log::new(
-                    log.address(),
-                    log.topics().to_vec(),
-                    log.data().data.clone(),
-                )
-                .unwrap(),
-                false,
-            )
-            .unwrap();
-            if event.blockId == raiko_primitives::U256::from(l2_block_number) {
-                let tx = tokio_handle
-                    .block_on(async {
-                        provider
-                            .get_transaction_by_hash(log.transaction_hash.unwrap())
-                            .await
-                    })
-                    .expect("could not find the propose tx");
-                return Ok((tx, event.data));
-            }
-        }
-    }
-    bail!("No BlockProposed event found for block {l2_block_number}");
+    let tx = tokio_handle
+        .block_on(async { provider.get_transaction_by_hash(tx_hash).await })
+        .expect("could not find the propose tx");
+    Ok((tx, event))
 }
 
 fn get_transactions_from_block(block: &AlloyBlock) -> Vec<TxEnvelope> {
@@ -509,12 +696,24 @@ fn from_block_tx(tx: &AlloyRpcTransaction) -> TxEnvelope {
 
 #[cfg(test)]
 mod test {
+    use std::sync::Arc;
+
     use ethers_core::types::Transaction;
     use raiko_lib::taiko_utils::decode_transactions;
-    use raiko_primitives::{eip4844::parse_kzg_trusted_setup, kzg::KzgSettings};
+    use raiko_primitives::{
+        eip4844::{kzg_to_versioned_hash, parse_kzg_trusted_setup, MAINNET_KZG_TRUSTED_SETUP},
+        kzg::{KzgCommitment, KzgSettings},
+    };
 
     use super::*;
 
+    fn blob_to_bytes(blob_str: &str) -> Vec<u8> {
+        match hex::decode(blob_str.to_lowercase().trim_start_matches("0x")) {
+            Ok(b) => b,
+            Err(_) => Vec::new(),
+        }
+    }
+
     #[allow(dead_code)]
     fn calc_commit_versioned_hash(commitment: &str) -> [u8; 32] {
         let commit_bytes = hex::decode(commitment.to_lowercase().trim_start_matches("0x")).unwrap();
@@ -0,0 +1,49 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use raiko_lib::prover::Proof;
+
+use crate::proof_cache::{ProofKey, ProofStore};
+
+/// A proof store keeping every proof as a JSON file in a directory.
+#[derive(Debug)]
+pub struct DiskProofStore {
+    dir: PathBuf,
+}
+
+impl DiskProofStore {
+    /// Open the directory at the given path, creating it if it doesn't exist.
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    fn path(&self, key: &ProofKey) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+}
+
+impl ProofStore for DiskProofStore {
+    fn get(&mut self, key: &ProofKey) -> Result<Option<Proof>> {
+        match fs::read(self.path(key)) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn put(&mut self, key: &ProofKey, proof: &Proof) -> Result<()> {
+        // Write to a temporary file first, so a crash never leaves a partial proof behind.
+        let path = self.path(key);
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec(proof)?)?;
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+//! Cache for generated proofs.
+//!
+//! Proofs are cached by the block they prove and everything else that ends up in the proof,
+//! so a re-requested proof is returned immediately instead of being generated again. Requests
+//! with `force` set always generate a new proof, which then replaces the cached one.
+
+use std::{
+    fmt::Debug,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use alloy_primitives::B256;
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use lru_time_cache::LruCache;
+use raiko_lib::prover::Proof;
+use raiko_primitives::keccak::keccak;
+use serde::{Deserialize, Serialize};
+use tokio::task;
+use tracing::warn;
+
+use crate::{error::HostResult, guests, request::ProofRequest};
+
+mod disk;
+#[cfg(feature = "redis")]
+mod redis_store;
+
+/// A store for cached proofs. The stores may block, they are accessed on the blocking threads
+/// of the runtime.
+pub trait ProofStore: Send + Debug {
+    /// Get the proof cached under the given key.
+    fn get(&mut self, key: &ProofKey) -> Result<Option<Proof>>;
+
+    /// Cache the proof under the given key, replacing the proof cached before.
+    fn put(&mut self, key: &ProofKey, proof: &Proof) -> Result<()>;
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+/// Available proof cache backends.
+pub enum ProofCacheKind {
+    /// Don't cache proofs.
+    #[default]
+    Disabled,
+    /// Keep the most recently used proofs in memory.
+    Memory,
+    /// Store the proofs as files in a directory.
+    Disk,
+    /// Store the proofs in Redis.
+    Redis,
+}
+
+/// The key a proof is cached under.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProofKey(B256);
+
+impl ProofKey {
+    /// Derive the key of the proof for the given request of the block with the given hash.
+    ///
+    /// Next to the network, block hash, proof type and guest program id, the key also covers
+    /// the request params which end up in the proof, like the prover address.
+    pub fn new(proof_request: &ProofRequest, block_hash: B256) -> HostResult<Self> {
+        let proof_type = proof_request.proof_type.to_string();
+        let data = serde_json::to_vec(&(
+            proof_request.network.to_string(),
+            block_hash,
+            &proof_type,
+            guests::program_id(proof_request)?,
+            proof_request.prover,
+            proof_request.graffiti,
+            proof_request.compress,
+            proof_request.prover_args.get(&proof_type),
+        ))?;
+        Ok(Self(keccak(data).into()))
+    }
+}
+
+impl std::fmt::Display for ProofKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:x}", self.0)
+    }
+}
+
+/// Shared handle to the configured proof store. Failing to access the store is logged, but
+/// doesn't fail the request.
+#[derive(Clone, Debug, Default)]
+pub struct ProofCache(Option<Arc<Mutex<Box<dyn ProofStore>>>>);
+
+impl ProofCache {
+    /// Open the proof cache of the given kind. The path is used by the disk cache and the url
+    /// by the Redis cache, the memory cache holds at most `capacity` proofs.
+    pub fn open(
+        kind: ProofCacheKind,
+        path: Option<&Path>,
+        url: Option<&str>,
+        capacity: usize,
+    ) -> Result<Self> {
+        let store: Box<dyn ProofStore> = match kind {
+            ProofCacheKind::Disabled => return Ok(Self::default()),
+            ProofCacheKind::Memory => Box::new(InMemoryProofStore::new(capacity)),
+            ProofCacheKind::Disk => {
+                let Some(path) = path else {
+                    bail!("A proof cache path is required for the disk proof cache");
+                };
+                Box::new(disk::DiskProofStore::open(path)?)
+            }
+            ProofCacheKind::Redis => {
+                let Some(url) = url else {
+                    bail!("A proof cache url is required for the Redis proof cache");
+                };
+                #[cfg(feature = "redis")]
+                {
+                    Box::new(redis_store::RedisProofStore::open(url)?)
+                }
+                #[cfg(not(feature = "redis"))]
+                bail!("Cannot connect to {url}, the host was built without the `redis` feature");
+            }
+        };
+        Ok(Self(Some(Arc::new(Mutex::new(store)))))
+    }
+
+    /// Whether a proof store is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Get the proof cached under the given key.
+    pub async fn get(&self, key: &ProofKey) -> Option<Proof> {
+        let store = self.0.clone()?;
+        let stored_key = key.clone();
+        run_blocking(move || store.lock().unwrap().get(&stored_key))
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Could not load cached proof {key}: {e}");
+                None
+            })
+    }
+
+    /// Cache the proof under the given key.
+    pub async fn put(&self, key: &ProofKey, proof: &Proof) {
+        let Some(store) = self.0.clone() else {
+            return;
+        };
+        let (stored_key, proof) = (key.clone(), proof.clone());
+        if let Err(e) = run_blocking(move || store.lock().unwrap().put(&stored_key, &proof)).await {
+            warn!("Could not cache proof {key}: {e}");
+        }
+    }
+}
+
+/// Run the access to the proof store on a blocking thread, so the disk and Redis stores don't
+/// block the runtime.
+async fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    task::spawn_blocking(f).await?
+}
+
+/// A proof store that keeps the most recently used proofs in memory.
+pub struct InMemoryProofStore {
+    proofs: LruCache<ProofKey, Proof>,
+}
+
+impl InMemoryProofStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            proofs: LruCache::with_capacity(capacity),
+        }
+    }
+}
+
+impl Debug for InMemoryProofStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InMemoryProofStore")
+            .field("len", &self.proofs.len())
+            .finish()
+    }
+}
+
+impl ProofStore for InMemoryProofStore {
+    fn get(&mut self, key: &ProofKey) -> Result<Option<Proof>> {
+        Ok(self.proofs.get(key).cloned())
+    }
+
+    fn put(&mut self, key: &ProofKey, proof: &Proof) -> Result<()> {
+        self.proofs.insert(key.clone(), proof.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_cache_evicts_least_recently_used() {
+        let cache = ProofCache::open(ProofCacheKind::Memory, None, None, 2).unwrap();
+        let keys = (1..=3)
+            .map(|i| ProofKey(B256::repeat_byte(i)))
+            .collect::<Vec<_>>();
+
+        cache.put(&keys[0], &json!(0)).await;
+        cache.put(&keys[1], &json!(1)).await;
+        assert_eq!(cache.get(&keys[0]).await, Some(json!(0)));
+        cache.put(&keys[2], &json!(2)).await;
+
+        assert_eq!(cache.get(&keys[0]).await, Some(json!(0)));
+        assert_eq!(cache.get(&keys[1]).await, None);
+        assert_eq!(cache.get(&keys[2]).await, Some(json!(2)));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_cache() {
+        let cache = ProofCache::default();
+        let key = ProofKey(B256::ZERO);
+        cache.put(&key, &json!(0)).await;
+        assert_eq!(cache.get(&key).await, None);
+    }
+}
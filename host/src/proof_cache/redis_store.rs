@@ -0,0 +1,41 @@
+use anyhow::Result;
+use raiko_lib::prover::Proof;
+use redis::{Client, Commands, Connection};
+
+use crate::proof_cache::{ProofKey, ProofStore};
+
+/// The prefix of the Redis keys the proofs are stored under.
+const KEY_PREFIX: &str = "raiko:proof:";
+
+/// A proof store keeping the proofs as JSON in Redis.
+pub struct RedisProofStore {
+    conn: Connection,
+}
+
+impl RedisProofStore {
+    /// Connect to the Redis server at the given url.
+    pub fn open(url: &str) -> Result<Self> {
+        Ok(Self {
+            conn: Client::open(url)?.get_connection()?,
+        })
+    }
+}
+
+impl std::fmt::Debug for RedisProofStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisProofStore").finish_non_exhaustive()
+    }
+}
+
+impl ProofStore for RedisProofStore {
+    fn get(&mut self, key: &ProofKey) -> Result<Option<Proof>> {
+        let data: Option<String> = self.conn.get(format!("{KEY_PREFIX}{key}"))?;
+        Ok(data.map(|data| serde_json::from_str(&data)).transpose()?)
+    }
+
+    fn put(&mut self, key: &ProofKey, proof: &Proof) -> Result<()> {
+        self.conn
+            .set::<_, _, ()>(format!("{KEY_PREFIX}{key}"), serde_json::to_string(proof)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+//! The typed outputs of the proofs.
+//!
+//! Every prover returns its own JSON object as the proof, with at least the `proof` submitted
+//! on-chain, hex encoded. [ProofOutput] is the same model for all proof types, with the
+//! transition the proof is for and the metadata of the block, which the host adds to the proof
+//! when it generates it, see [annotate]. It is served in the [ProofFormat] of the client.
+
+use alloy_primitives::{Bytes, B256};
+use raiko_client::ErrorCode;
+use raiko_lib::{
+    input::{GuestInput, GuestOutput, Transition},
+    prover::Proof,
+};
+use raiko_primitives::hex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::{
+    calldata::ProveBlockCall,
+    error::{HostError, HostResult},
+    request::ProofRequest,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+/// The serialization of a proof.
+pub enum ProofFormat {
+    /// The [ProofOutput] as JSON.
+    Json,
+    /// The ABI encoded call of `proveBlock` submitting the proof to the TaikoL1 contract.
+    Calldata,
+    /// The [ProofOutput] as an SSZ container, see [ProofOutput::to_ssz].
+    Ssz,
+    /// The raw proof of the prover, e.g. the receipt of Risc0, or the on-chain proof.
+    Binary,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+/// The transition of the chain a proof is for.
+pub struct ProvenTransition {
+    pub parent_hash: B256,
+    pub block_hash: B256,
+    pub state_root: B256,
+    pub graffiti: B256,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+/// A proof, the same for all proof types.
+pub struct ProofOutput {
+    /// The name of the chain of the block.
+    pub network: String,
+    /// The number of the block.
+    pub block_number: u64,
+    /// The proof type.
+    pub proof_type: String,
+    /// The proof submitted on-chain.
+    #[schema(value_type = String)]
+    pub proof: Bytes,
+    /// The raw proof of the prover, if it differs from the on-chain proof, e.g. the receipt of
+    /// Risc0.
+    #[schema(value_type = Option<String>)]
+    pub receipt: Option<Bytes>,
+    /// The transition the proof is for.
+    pub transition: ProvenTransition,
+    /// The metadata of the block, as proposed.
+    #[schema(value_type = Object)]
+    pub block_meta: Value,
+    /// Whether the proof verified locally, if the prover verifies its proofs.
+    pub verified: Option<bool>,
+}
+
+/// Add the transition and the metadata of the block to the proof, for its [ProofOutput].
+pub fn annotate(
+    proof: &mut Proof,
+    request: &ProofRequest,
+    input: &GuestInput,
+    output: &GuestOutput,
+) -> HostResult<()> {
+    let (GuestOutput::Success((header, _)), Some(proof)) = (output, proof.as_object_mut()) else {
+        return Ok(());
+    };
+    let transition = ProvenTransition {
+        parent_hash: header.header.parent_hash,
+        block_hash: input.block_hash,
+        state_root: header.header.state_root,
+        graffiti: request.graffiti,
+    };
+    proof.insert("transition".to_string(), serde_json::to_value(transition)?);
+    proof.insert(
+        "block_meta".to_string(),
+        serde_json::to_value(&input.taiko.block_proposed.meta)?,
+    );
+    Ok(())
+}
+
+fn decode_hex(proof: &Proof, field: &str) -> HostResult<Option<Bytes>> {
+    let Some(value) = proof.get(field).and_then(Value::as_str) else {
+        return Ok(None);
+    };
+    let bytes = hex::decode(value.trim_start_matches("0x")).map_err(|e| {
+        HostError::invalid(
+            ErrorCode::InvalidHex,
+            field,
+            format!("The {field} of the proof is not hex encoded: {e}"),
+        )
+    })?;
+    Ok(Some(bytes.into()))
+}
+
+fn not_available(field: &str) -> HostError {
+    HostError::invalid(
+        ErrorCode::InvalidRequest,
+        "format",
+        format!("The proof has no {field}, it was generated by an older version of the host"),
+    )
+}
+
+impl ProofOutput {
+    /// The output of the proof of the request.
+    pub fn new(request: &ProofRequest, proof: &Proof) -> HostResult<Self> {
+        let on_chain = decode_hex(proof, "proof")?.ok_or_else(|| not_available("proof"))?;
+        let receipt = decode_hex(proof, "receipt")?.filter(|receipt| !receipt.is_empty());
+        Ok(Self {
+            network: request.network.clone(),
+            block_number: request.block_number,
+            proof_type: request.proof_type.to_string(),
+            proof: on_chain,
+            receipt,
+            transition: proof
+                .get("transition")
+                .cloned()
+                .map(serde_json::from_value)
+                .transpose()?
+                .ok_or_else(|| not_available("transition"))?,
+            block_meta: proof
+                .get("block_meta")
+                .cloned()
+                .ok_or_else(|| not_available("block_meta"))?,
+            verified: proof.get("verified").and_then(Value::as_bool),
+        })
+    }
+
+    /// The call of `proveBlock` submitting the proof as a proof of the given tier.
+    pub fn calldata(&self, tier: u16) -> HostResult<Bytes> {
+        let call = ProveBlockCall {
+            block_id: self.block_number,
+            meta: serde_json::from_value(self.block_meta.clone())?,
+            transition: Transition {
+                parentHash: self.transition.parent_hash,
+                blockHash: self.transition.block_hash,
+                stateRoot: self.transition.state_root,
+                graffiti: self.transition.graffiti,
+            },
+            tier,
+            data: self.proof.clone(),
+        };
+        Ok(call.encode())
+    }
+
+    /// The raw proof of the prover.
+    pub fn binary(&self) -> Bytes {
+        self.receipt.clone().unwrap_or_else(|| self.proof.clone())
+    }
+
+    /// The SSZ container `{block_number: uint64, parent_hash: Bytes32, block_hash: Bytes32,
+    /// state_root: Bytes32, graffiti: Bytes32, proof: List[uint8], receipt: List[uint8]}`,
+    /// with an empty receipt if the prover has none.
+    pub fn to_ssz(&self) -> Vec<u8> {
+        let receipt = self.receipt.as_deref().unwrap_or_default();
+        // The fixed part, with the offsets of the two variable size fields.
+        let fixed_size = 8 + 4 * 32 + 4 + 4;
+        let mut ssz = Vec::with_capacity(fixed_size + self.proof.len() + receipt.len());
+        ssz.extend_from_slice(&self.block_number.to_le_bytes());
+        for hash in [
+            &self.transition.parent_hash,
+            &self.transition.block_hash,
+            &self.transition.state_root,
+            &self.transition.graffiti,
+        ] {
+            ssz.extend_from_slice(hash.as_slice());
+        }
+        ssz.extend_from_slice(&(fixed_size as u32).to_le_bytes());
+        ssz.extend_from_slice(&((fixed_size + self.proof.len()) as u32).to_le_bytes());
+        ssz.extend_from_slice(&self.proof);
+        ssz.extend_from_slice(receipt);
+        ssz
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_sol_types::SolCall;
+    use raiko_lib::input::{proveBlockCall, BlockMetadata};
+
+    use super::*;
+
+    fn request() -> ProofRequest {
+        serde_json::from_value(serde_json::json!({
+            "block_number": 10,
+            "rpc": "http://localhost:8545",
+            "l1_rpc": "http://localhost:8546",
+            "beacon_rpc": "http://localhost:5052",
+            "network": "taiko_a7",
+            "l1_network": "holesky",
+            "graffiti": B256::ZERO,
+            "prover": "0x0000000000000000000000000000000000000000",
+            "proof_type": "risc0",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_proof_output() {
+        let proof = serde_json::json!({
+            "proof": "0x0102",
+            "receipt": "030405",
+            "transition": ProvenTransition {
+                parent_hash: B256::repeat_byte(1),
+                block_hash: B256::repeat_byte(2),
+                state_root: B256::repeat_byte(3),
+                graffiti: B256::ZERO,
+            },
+            "block_meta": BlockMetadata::default(),
+            "verified": true,
+        });
+        let output = ProofOutput::new(&request(), &proof).unwrap();
+        assert_eq!(output.proof, Bytes::from(vec![1, 2]));
+        assert_eq!(output.binary(), Bytes::from(vec![3, 4, 5]));
+        assert_eq!(output.verified, Some(true));
+
+        let calldata = output.calldata(250).unwrap();
+        assert_eq!(&calldata[..4], proveBlockCall::SELECTOR.as_slice());
+        let call = proveBlockCall::abi_decode(&calldata, true).unwrap();
+        assert_eq!(call.blockId, 10);
+
+        let ssz = output.to_ssz();
+        assert_eq!(ssz.len(), 144 + 2 + 3);
+        assert_eq!(&ssz[..8], &10u64.to_le_bytes());
+        assert_eq!(&ssz[136..140], &144u32.to_le_bytes());
+        assert_eq!(&ssz[140..144], &146u32.to_le_bytes());
+        assert_eq!(&ssz[144..], &[1, 2, 3, 4, 5]);
+
+        // Proofs without the transition can't be typed.
+        let proof = serde_json::json!({"proof": "0x01"});
+        assert!(ProofOutput::new(&request(), &proof).is_err());
+    }
+}
@@ -11,7 +11,11 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use std::{collections::HashSet, mem::take};
+use std::{
+    collections::{BTreeMap, HashSet},
+    mem::take,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use alloy_consensus::Header as AlloyConsensusHeader;
 use alloy_primitives::{Bytes, StorageKey, Uint};
@@ -19,6 +23,7 @@ use alloy_provider::{Provider, ReqwestProvider};
 use alloy_rpc_client::{ClientBuilder, RpcClient};
 use alloy_rpc_types::{Block, BlockId, BlockNumberOrTag, EIP1186AccountProofResponse};
 use alloy_transport_http::Http;
+use futures::{stream, try_join, StreamExt, TryStreamExt};
 use raiko_lib::{
     builder::OptimisticDatabase, clear_line, consts::Network, inplace_print, mem_db::MemDb,
     taiko_utils::to_header,
@@ -29,14 +34,20 @@ use revm::{
     primitives::{Account, AccountInfo, Bytecode, HashMap},
     Database, DatabaseCommit,
 };
+use serde::Deserialize;
+use serde_json::json;
 use tokio::runtime::Handle;
+use tracing::{info_span, Instrument, Span};
 
-use crate::preflight::get_block;
+use crate::{faults, header_store::HeaderStore};
 
 pub struct ProviderDb {
     pub provider: ReqwestProvider,
     pub client: RpcClient<Http<Client>>,
     pub block_number: u64,
+    /// The block the proofs of the state after the block are fetched at, the block itself
+    /// unless the node doesn't have it.
+    pub final_block_number: u64,
     pub initial_db: MemDb,
     pub initial_headers: HashMap<u64, AlloyConsensusHeader>,
     pub current_db: MemDb,
@@ -51,11 +62,69 @@ pub struct ProviderDb {
 
 type StorageProofs = HashMap<Address, EIP1186AccountProofResponse>;
 
+/// The state before a transaction, as reported by the `prestateTracer`.
+#[derive(Debug, Deserialize)]
+struct PrestateTrace {
+    result: BTreeMap<Address, PrestateAccount>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PrestateAccount {
+    #[serde(default)]
+    storage: BTreeMap<B256, B256>,
+}
+
+/// The max number of JSON-RPC batches sent to the node at the same time.
+const MAX_CONCURRENT_BATCHES: usize = 8;
+
+/// The max number of account and storage proofs in a single batch.
+const STORAGE_PROOF_BATCH_LIMIT: usize = 1000;
+
+/// The span of sending a JSON-RPC batch, so the time of every batch shows up in the traces.
+fn rpc_batch_span(method: &str, calls: usize) -> Span {
+    info_span!("rpc_batch", method, calls)
+}
+
+/// Split the proofs of the given accounts and their storage keys into `eth_getProof` batches
+/// of at most `batch_limit` proofs, counting the account proof as well. The storage keys of an
+/// account are split over multiple requests if needed.
+fn split_proof_requests(
+    accounts: HashMap<Address, Vec<U256>>,
+    batch_limit: usize,
+) -> Vec<Vec<(Address, Vec<StorageKey>)>> {
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    let mut batch_size = 0;
+    for (address, keys) in accounts {
+        let mut keys = keys.into_iter().map(StorageKey::from).collect::<Vec<_>>();
+        loop {
+            // Leave room for the account proof itself
+            let num_keys = keys.len().min(batch_limit - batch_size - 1);
+            let rest = keys.split_off(num_keys);
+            batch_size += 1 + num_keys;
+            batch.push((address, keys));
+            if batch_size >= batch_limit {
+                batches.push(take(&mut batch));
+                batch_size = 0;
+            }
+            if rest.is_empty() {
+                break;
+            }
+            keys = rest;
+        }
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
 impl ProviderDb {
     pub fn new(
         provider: ReqwestProvider,
         network: Network,
         block_number: u64,
+        header_store: &HeaderStore,
     ) -> Result<Self, anyhow::Error> {
         let client = ClientBuilder::default()
             .reqwest_http(reqwest::Url::parse(provider.client().transport().url()).unwrap());
@@ -64,6 +133,7 @@ impl ProviderDb {
             provider,
             client,
             block_number,
+            final_block_number: block_number + 1,
             initial_db: Default::default(),
             initial_headers: Default::default(),
             current_db: Default::default(),
@@ -75,61 +145,90 @@ impl ProviderDb {
             pending_block_hashes: HashSet::new(),
         };
         if network.is_taiko() {
-            // Get the 256 history block hashes from the provider at first time for anchor
-            // transaction.
+            // Get the 256 history block hashes at first time for anchor transaction. The
+            // latest block is always fetched, the stored headers linking up with it are used
+            // and only the remaining headers are fetched from the provider.
             let start = block_number.saturating_sub(255);
-            let block_numbers = (start..=block_number).collect::<Vec<_>>();
-            let initial_history_blocks = provider_db.fetch_blocks(&block_numbers)?;
-            for block in initial_history_blocks {
+            let latest_block = provider_db.fetch_blocks(&[block_number])?.remove(0);
+            let mut history =
+                header_store.ancestors(block_number, latest_block.header.parent_hash, start);
+            let missing_end = history
+                .last()
+                .map(|(number, ..)| *number)
+                .unwrap_or(block_number);
+            let block_numbers = (start..missing_end).collect::<Vec<_>>();
+            for block in
+                std::iter::once(latest_block).chain(provider_db.fetch_blocks(&block_numbers)?)
+            {
                 let block_number: u64 = block.header.number.unwrap().try_into().unwrap();
                 let block_hash = block.header.hash.unwrap();
+                let header = to_header(&block.header);
+                header_store.insert(block_number, block_hash, header.clone())?;
+                history.push((block_number, block_hash, header));
+            }
+            for (block_number, block_hash, header) in history {
                 provider_db
                     .initial_db
                     .insert_block_hash(block_number, block_hash);
-                provider_db
-                    .initial_headers
-                    .insert(block_number, to_header(&block.header));
+                provider_db.initial_headers.insert(block_number, header);
             }
         }
         Ok(provider_db)
     }
 
-    fn fetch_blocks(&mut self, block_numbers: &[u64]) -> Result<Vec<Block>, anyhow::Error> {
-        let mut all_blocks = Vec::new();
+    fn fetch_blocks(&self, block_numbers: &[u64]) -> Result<Vec<Block>, anyhow::Error> {
+        self.async_executor.block_on(self.blocks(block_numbers))
+    }
 
-        let max_batch_size = 32;
-        for block_numbers in block_numbers.chunks(max_batch_size) {
-            let mut batch = self.client.new_batch();
-            let mut requests = vec![];
+    fn fetch_accounts(&self, accounts: &[Address]) -> Result<Vec<AccountInfo>, anyhow::Error> {
+        self.async_executor.block_on(self.accounts(accounts))
+    }
 
-            for block_number in block_numbers.iter() {
-                requests.push(Box::pin(batch.add_call(
-                    "eth_getBlockByNumber",
-                    &(BlockNumberOrTag::from(*block_number), false),
-                )?));
-            }
+    fn fetch_storage_slots(
+        &self,
+        accounts: &[(Address, U256)],
+    ) -> Result<Vec<U256>, anyhow::Error> {
+        self.async_executor.block_on(self.storage_slots(accounts))
+    }
 
-            let mut blocks = self.async_executor.block_on(async {
-                batch.send().await?;
+    async fn blocks(&self, block_numbers: &[u64]) -> Result<Vec<Block>, anyhow::Error> {
+        let max_batch_size = 32;
+        let batches = block_numbers
+            .chunks(max_batch_size)
+            .map(|block_numbers| async move {
+                let mut batch = self.client.new_batch();
+                let mut requests = vec![];
+
+                for block_number in block_numbers.iter() {
+                    requests.push(Box::pin(batch.add_call(
+                        "eth_getBlockByNumber",
+                        &(BlockNumberOrTag::from(*block_number), false),
+                    )?));
+                }
+
+                batch
+                    .send()
+                    .instrument(rpc_batch_span("eth_getBlockByNumber", requests.len()))
+                    .await?;
+                faults::drop_rpc_response("eth_getBlockByNumber")?;
                 let mut blocks = vec![];
                 // Collect the data from the batch
                 for request in requests.into_iter() {
                     blocks.push(request.await?);
                 }
                 Ok::<_, anyhow::Error>(blocks)
-            })?;
-
-            all_blocks.append(&mut blocks);
-        }
+            });
 
-        Ok(all_blocks)
+        let blocks: Vec<Vec<Block>> = stream::iter(batches)
+            .buffered(MAX_CONCURRENT_BATCHES)
+            .try_collect()
+            .await?;
+        Ok(blocks.into_iter().flatten().collect())
     }
 
-    fn fetch_accounts(&self, accounts: &[Address]) -> Result<Vec<AccountInfo>, anyhow::Error> {
-        let mut all_accounts = Vec::new();
-
+    async fn accounts(&self, accounts: &[Address]) -> Result<Vec<AccountInfo>, anyhow::Error> {
         let max_batch_size = 250;
-        for accounts in accounts.chunks(max_batch_size) {
+        let batches = accounts.chunks(max_batch_size).map(|accounts| async move {
             let mut batch = self.client.new_batch();
 
             let mut nonce_requests = Vec::new();
@@ -137,181 +236,138 @@ impl ProviderDb {
             let mut code_requests = Vec::new();
 
             for address in accounts {
-                nonce_requests.push(Box::pin(
-                    batch
-                        .add_call::<_, Uint<64, 1>>(
-                            "eth_getTransactionCount",
-                            &(address, Some(BlockId::from(self.block_number))),
-                        )
-                        .unwrap(),
-                ));
-                balance_requests.push(Box::pin(
-                    batch
-                        .add_call::<_, Uint<256, 4>>(
-                            "eth_getBalance",
-                            &(address, Some(BlockId::from(self.block_number))),
-                        )
-                        .unwrap(),
-                ));
-                code_requests.push(Box::pin(
-                    batch
-                        .add_call::<_, Bytes>(
-                            "eth_getCode",
-                            &(address, Some(BlockId::from(self.block_number))),
-                        )
-                        .unwrap(),
-                ));
+                nonce_requests.push(Box::pin(batch.add_call::<_, Uint<64, 1>>(
+                    "eth_getTransactionCount",
+                    &(address, Some(BlockId::from(self.block_number))),
+                )?));
+                balance_requests.push(Box::pin(batch.add_call::<_, Uint<256, 4>>(
+                    "eth_getBalance",
+                    &(address, Some(BlockId::from(self.block_number))),
+                )?));
+                code_requests.push(Box::pin(batch.add_call::<_, Bytes>(
+                    "eth_getCode",
+                    &(address, Some(BlockId::from(self.block_number))),
+                )?));
             }
 
-            let mut accounts = self.async_executor.block_on(async {
-                batch.send().await?;
-                let mut accounts = vec![];
-                // Collect the data from the batch
-                for (nonce_request, (balance_request, code_request)) in nonce_requests
-                    .into_iter()
-                    .zip(balance_requests.into_iter().zip(code_requests.into_iter()))
-                {
-                    let (nonce, balance, code) = (
-                        nonce_request.await?,
-                        balance_request.await?,
-                        code_request.await?,
-                    );
-
-                    let account_info = AccountInfo::new(
-                        balance,
-                        nonce.try_into().unwrap(),
-                        Bytecode::new_raw(code.clone()).hash_slow(),
-                        Bytecode::new_raw(code),
-                    );
-
-                    accounts.push(account_info);
-                }
-                Ok::<_, anyhow::Error>(accounts)
-            })?;
-
-            all_accounts.append(&mut accounts);
-        }
-
-        Ok(all_accounts)
+            batch
+                .send()
+                .instrument(rpc_batch_span(
+                    "eth_getTransactionCount/eth_getBalance/eth_getCode",
+                    3 * accounts.len(),
+                ))
+                .await?;
+            faults::drop_rpc_response("eth_getTransactionCount/eth_getBalance/eth_getCode")?;
+            let mut accounts = vec![];
+            // Collect the data from the batch
+            for (nonce_request, (balance_request, code_request)) in nonce_requests
+                .into_iter()
+                .zip(balance_requests.into_iter().zip(code_requests.into_iter()))
+            {
+                let (nonce, balance, code) = (
+                    nonce_request.await?,
+                    balance_request.await?,
+                    code_request.await?,
+                );
+
+                let account_info = AccountInfo::new(
+                    balance,
+                    nonce.try_into().unwrap(),
+                    Bytecode::new_raw(code.clone()).hash_slow(),
+                    Bytecode::new_raw(code),
+                );
+
+                accounts.push(account_info);
+            }
+            Ok::<_, anyhow::Error>(accounts)
+        });
+
+        let accounts: Vec<Vec<AccountInfo>> = stream::iter(batches)
+            .buffered(MAX_CONCURRENT_BATCHES)
+            .try_collect()
+            .await?;
+        Ok(accounts.into_iter().flatten().collect())
     }
 
-    fn fetch_storage_slots(
+    async fn storage_slots(
         &self,
         accounts: &[(Address, U256)],
     ) -> Result<Vec<U256>, anyhow::Error> {
-        let mut all_values = Vec::new();
-
         let max_batch_size = 1000;
-        for accounts in accounts.chunks(max_batch_size) {
+        let batches = accounts.chunks(max_batch_size).map(|accounts| async move {
             let mut batch = self.client.new_batch();
 
             let mut requests = Vec::new();
 
             for (address, key) in accounts {
-                requests.push(Box::pin(
-                    batch
-                        .add_call::<_, U256>(
-                            "eth_getStorageAt",
-                            &(address, key, Some(BlockId::from(self.block_number))),
-                        )
-                        .unwrap(),
-                ));
+                requests.push(Box::pin(batch.add_call::<_, U256>(
+                    "eth_getStorageAt",
+                    &(address, key, Some(BlockId::from(self.block_number))),
+                )?));
             }
 
-            let mut values = self.async_executor.block_on(async {
-                batch.send().await?;
-                let mut values = vec![];
-                // Collect the data from the batch
-                for request in requests.into_iter() {
-                    values.push(request.await?);
-                }
-                Ok::<_, anyhow::Error>(values)
-            })?;
-
-            all_values.append(&mut values);
-        }
-
-        Ok(all_values)
+            batch
+                .send()
+                .instrument(rpc_batch_span("eth_getStorageAt", requests.len()))
+                .await?;
+            faults::drop_rpc_response("eth_getStorageAt")?;
+            let mut values = vec![];
+            // Collect the data from the batch
+            for request in requests.into_iter() {
+                values.push(request.await?);
+            }
+            Ok::<_, anyhow::Error>(values)
+        });
+
+        let values: Vec<Vec<U256>> = stream::iter(batches)
+            .buffered(MAX_CONCURRENT_BATCHES)
+            .try_collect()
+            .await?;
+        Ok(values.into_iter().flatten().collect())
     }
 
-    fn get_storage_proofs(
-        &mut self,
+    async fn storage_proofs(
+        &self,
         block_number: u64,
         accounts: HashMap<Address, Vec<U256>>,
-        offset: usize,
+        fetched: &AtomicUsize,
         num_storage_proofs: usize,
     ) -> Result<StorageProofs, anyhow::Error> {
-        let mut storage_proofs: HashMap<Address, EIP1186AccountProofResponse> = HashMap::new();
-        let mut idx = offset;
-
-        let mut accounts = accounts.clone();
-
-        let batch_limit = 1000;
-        while !accounts.is_empty() {
-            inplace_print(&format!(
-                "fetching storage proof {idx}/{num_storage_proofs}..."
-            ));
-
-            // Create a batch for all storage proofs
-            let mut batch = self.client.new_batch();
-
-            // Collect all requests
-            let mut requests = Vec::new();
-
-            let mut batch_size = 0;
-            while !accounts.is_empty() && batch_size < batch_limit {
-                let mut address_to_remove = None;
-                if let Some((address, keys)) = accounts.iter_mut().next() {
-                    // Calculate how many keys we can still process
-                    let num_keys_to_process = if batch_size + keys.len() < batch_limit {
-                        keys.len()
-                    } else {
-                        batch_limit - batch_size
-                    };
-
-                    // If we can process all keys, remove the address from the map after the loop
-                    if num_keys_to_process == keys.len() {
-                        address_to_remove = Some(*address);
-                    }
-
-                    // Extract the keys to process
-                    let keys_to_process = keys
-                        .drain(0..num_keys_to_process)
-                        .map(StorageKey::from)
-                        .collect::<Vec<_>>();
-
-                    // Add the request
-                    requests.push(Box::pin(
-                        batch
-                            .add_call::<_, EIP1186AccountProofResponse>(
-                                "eth_getProof",
-                                &(
-                                    address,
-                                    keys_to_process.clone(),
-                                    BlockId::from(block_number),
-                                ),
-                            )
-                            .unwrap(),
-                    ));
-
-                    // Keep track of how many keys were processed
-                    // Add an additional 1 for the account proof itself
-                    batch_size += 1 + keys_to_process.len();
+        let batches = split_proof_requests(accounts, STORAGE_PROOF_BATCH_LIMIT)
+            .into_iter()
+            .map(|requests| async move {
+                // Create a batch for all storage proofs
+                let mut batch = self.client.new_batch();
+                let mut calls = Vec::new();
+                for (address, keys) in requests.iter() {
+                    calls.push(Box::pin(batch.add_call::<_, EIP1186AccountProofResponse>(
+                        "eth_getProof",
+                        &(address, keys, BlockId::from(block_number)),
+                    )?));
                 }
 
-                // Remove the address if all keys were processed for this account
-                if let Some(address) = address_to_remove {
-                    accounts.remove(&address);
+                batch
+                    .send()
+                    .instrument(rpc_batch_span("eth_getProof", calls.len()))
+                    .await?;
+                faults::drop_rpc_response("eth_getProof")?;
+                let mut proofs = Vec::new();
+                for call in calls.into_iter() {
+                    proofs.push(call.await?);
                 }
-            }
-
-            // Send the batch
-            self.async_executor.block_on(async { batch.send().await })?;
-
-            // Collect the data from the batch
-            for request in requests.into_iter() {
-                let mut proof = self.async_executor.block_on(request)?;
-                idx += proof.storage_proof.len();
+                Ok::<_, anyhow::Error>(proofs)
+            });
+
+        // The order of the proofs doesn't matter, so handle the batches as they come in
+        let mut batches = stream::iter(batches).buffer_unordered(MAX_CONCURRENT_BATCHES);
+        let mut storage_proofs: StorageProofs = HashMap::new();
+        while let Some(proofs) = batches.try_next().await? {
+            for mut proof in proofs {
+                let idx = fetched.fetch_add(proof.storage_proof.len(), Ordering::Relaxed)
+                    + proof.storage_proof.len();
+                inplace_print(&format!(
+                    "fetching storage proof {idx}/{num_storage_proofs}..."
+                ));
                 if let Some(map_proof) = storage_proofs.get_mut(&proof.address) {
                     map_proof.storage_proof.append(&mut proof.storage_proof);
                 } else {
@@ -319,11 +375,52 @@ impl ProviderDb {
                 }
             }
         }
-        clear_line();
 
         Ok(storage_proofs)
     }
 
+    /// Discover the accounts and storage slots touched by the block with a `prestateTracer`
+    /// trace, and load them at once. Returns the number of loaded accounts and slots.
+    ///
+    /// The block is still executed afterwards to find the state the tracer doesn't report, but
+    /// mostly without fetching more data.
+    pub fn prefetch_from_trace(&mut self) -> Result<(usize, usize), anyhow::Error> {
+        let block_number = BlockNumberOrTag::from(self.block_number + 1);
+        let traces: Vec<PrestateTrace> = self.async_executor.block_on(self.client.request(
+            "debug_traceBlockByNumber",
+            (block_number, json!({ "tracer": "prestateTracer" })),
+        ))?;
+
+        let mut accounts = HashSet::new();
+        let mut slots = HashSet::new();
+        for trace in traces {
+            for (address, account) in trace.result {
+                accounts.insert(address);
+                slots.extend(
+                    account
+                        .storage
+                        .into_keys()
+                        .map(|slot| (address, U256::from_be_bytes(slot.0))),
+                );
+            }
+        }
+        let accounts = accounts.into_iter().collect::<Vec<_>>();
+        let slots = slots.into_iter().collect::<Vec<_>>();
+
+        let (infos, values) = self
+            .async_executor
+            .block_on(async { try_join!(self.accounts(&accounts), self.storage_slots(&slots)) })?;
+        for (address, info) in accounts.iter().zip(infos) {
+            self.initial_db.insert_account_info(*address, info);
+        }
+        for ((address, index), value) in slots.iter().zip(values) {
+            self.initial_db
+                .insert_account_storage(address, *index, value);
+        }
+
+        Ok((accounts.len(), slots.len()))
+    }
+
     pub fn get_proofs(&mut self) -> Result<(StorageProofs, StorageProofs, usize), anyhow::Error> {
         // Latest proof keys
         let mut storage_keys = self.initial_db.storage_keys();
@@ -346,44 +443,50 @@ impl ProviderDb {
         let num_latest_values: usize = storage_keys.iter().map(|(_address, keys)| keys.len()).sum();
         let num_storage_proofs = num_initial_values + num_latest_values;
 
-        // Initial proofs
-        let initial_proofs = self.get_storage_proofs(
-            self.block_number,
-            self.initial_db.storage_keys(),
-            0,
-            num_storage_proofs,
-        )?;
-        let latest_proofs = self.get_storage_proofs(
-            self.block_number + 1,
-            storage_keys,
-            num_initial_values,
-            num_storage_proofs,
-        )?;
+        // Fetch the initial and the latest proofs at the same time
+        let fetched = AtomicUsize::new(0);
+        let (initial_proofs, latest_proofs) = self.async_executor.block_on(async {
+            try_join!(
+                self.storage_proofs(
+                    self.block_number,
+                    self.initial_db.storage_keys(),
+                    &fetched,
+                    num_storage_proofs,
+                ),
+                self.storage_proofs(
+                    self.final_block_number,
+                    storage_keys,
+                    &fetched,
+                    num_storage_proofs,
+                ),
+            )
+        })?;
+        clear_line();
 
         Ok((initial_proofs, latest_proofs, num_storage_proofs))
     }
 
     pub fn get_ancestor_headers(&mut self) -> Result<Vec<AlloyConsensusHeader>, anyhow::Error> {
-        let earliest_block = self
+        let earliest_block = *self
             .initial_db
             .block_hashes
             .keys()
             .min()
             .unwrap_or(&self.block_number);
-        let headers = (*earliest_block..self.block_number)
+
+        // Fetch all missing headers in batches
+        let missing = (earliest_block..self.block_number)
+            .filter(|block_number| !self.initial_headers.contains_key(block_number))
+            .collect::<Vec<_>>();
+        for block in self.fetch_blocks(&missing)? {
+            let block_number: u64 = block.header.number.unwrap().try_into().unwrap();
+            self.initial_headers
+                .insert(block_number, to_header(&block.header));
+        }
+
+        let headers = (earliest_block..self.block_number)
             .rev()
-            .map(|block_number| {
-                self.initial_headers
-                    .get(&block_number)
-                    .cloned()
-                    .unwrap_or_else(|| {
-                        to_header(
-                            &get_block(&self.provider, block_number, false)
-                                .unwrap()
-                                .header,
-                        )
-                    })
-            })
+            .map(|block_number| self.initial_headers[&block_number].clone())
             .collect();
         Ok(headers)
     }
@@ -523,9 +626,25 @@ impl OptimisticDatabase for ProviderDb {
         // This run was valid when no pending work was scheduled
         let valid_run = self.is_valid_run();
 
-        let accounts = self
-            .fetch_accounts(&self.pending_accounts.iter().cloned().collect::<Vec<_>>())
+        // Fetch all pending data at the same time
+        let pending_accounts = self.pending_accounts.iter().cloned().collect::<Vec<_>>();
+        let pending_slots = self.pending_slots.iter().cloned().collect::<Vec<_>>();
+        let pending_block_hashes = self
+            .pending_block_hashes
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        let (accounts, slots, blocks) = self
+            .async_executor
+            .block_on(async {
+                try_join!(
+                    self.accounts(&pending_accounts),
+                    self.storage_slots(&pending_slots),
+                    self.blocks(&pending_block_hashes),
+                )
+            })
             .unwrap();
+
         for (address, account) in take(&mut self.pending_accounts)
             .into_iter()
             .zip(accounts.iter())
@@ -534,24 +653,12 @@ impl OptimisticDatabase for ProviderDb {
                 .insert_account_info(address, account.clone());
         }
 
-        let slots = self
-            .fetch_storage_slots(&self.pending_slots.iter().cloned().collect::<Vec<_>>())
-            .unwrap();
         for ((address, index), value) in take(&mut self.pending_slots).into_iter().zip(slots.iter())
         {
             self.staging_db
                 .insert_account_storage(&address, index, *value);
         }
 
-        let blocks = self
-            .fetch_blocks(
-                &self
-                    .pending_block_hashes
-                    .iter()
-                    .cloned()
-                    .collect::<Vec<_>>(),
-            )
-            .unwrap();
         for (block_number, block) in take(&mut self.pending_block_hashes)
             .into_iter()
             .zip(blocks.iter())
@@ -574,3 +681,29 @@ impl OptimisticDatabase for ProviderDb {
         self.optimistic
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_proof_requests() {
+        let (a, b) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let keys = |n: u64| (0..n).map(U256::from).collect::<Vec<_>>();
+        let accounts = HashMap::from_iter([(a, keys(7)), (b, Vec::new())]);
+
+        let batches = split_proof_requests(accounts, 4);
+        let sizes = batches
+            .iter()
+            .map(|batch| batch.iter().map(|(_, keys)| 1 + keys.len()).sum::<usize>())
+            .collect::<Vec<_>>();
+        assert!(sizes.iter().all(|size| *size <= 4));
+
+        // Every key is requested exactly once, and every account at least once.
+        let mut requested = HashMap::<Address, usize>::new();
+        for (address, keys) in batches.into_iter().flatten() {
+            *requested.entry(address).or_default() += keys.len();
+        }
+        assert_eq!(requested, HashMap::from_iter([(a, 7), (b, 0)]));
+    }
+}
@@ -0,0 +1,387 @@
+//! Pools of node endpoints used by the preflight.
+//!
+//! A chain can list several endpoints for each of its L2, L1 and beacon nodes. The preflight
+//! takes an endpoint of every pool, either round robin or the one with the lowest latency, and
+//! switches to other endpoints when the preflight fails, so a single flaky archive node doesn't
+//! fail the proof. Endpoints failing their health check are skipped until they pass it again.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, warn};
+
+/// The time a health check may take before the endpoint is considered down.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The most pools kept, the requests can name their own endpoints. The pool used the longest
+/// ago is dropped for a new one, which stops its health checks.
+const MAX_POOLS: usize = 64;
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+/// How to pick the endpoint of a pool.
+pub enum Selection {
+    /// Cycle through the healthy endpoints.
+    #[default]
+    RoundRobin,
+    /// Use the healthy endpoint with the lowest latency in its last health check.
+    LeastLatency,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// The API served by the endpoints of a pool.
+pub enum EndpointKind {
+    /// The JSON-RPC API of an execution node.
+    Rpc,
+    /// The REST API of a beacon node.
+    Beacon,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    state: Mutex<EndpointState>,
+}
+
+#[derive(Debug)]
+struct EndpointState {
+    healthy: bool,
+    latency: Option<Duration>,
+    last_failure: Option<Instant>,
+}
+
+/// The endpoints of a node, with their health.
+#[derive(Debug)]
+pub struct ProviderPool {
+    kind: EndpointKind,
+    selection: Selection,
+    endpoints: Vec<Endpoint>,
+    next: AtomicUsize,
+}
+
+impl ProviderPool {
+    /// Create a pool of the given endpoints, which are all considered healthy until checked.
+    pub fn new(kind: EndpointKind, urls: &[String], selection: Selection) -> Self {
+        let mut endpoints: Vec<Endpoint> = Vec::with_capacity(urls.len());
+        for url in urls {
+            if endpoints.iter().all(|endpoint| &endpoint.url != url) {
+                endpoints.push(Endpoint {
+                    url: url.clone(),
+                    state: Mutex::new(EndpointState {
+                        healthy: true,
+                        latency: None,
+                        last_failure: None,
+                    }),
+                });
+            }
+        }
+        Self {
+            kind,
+            selection,
+            endpoints,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// The number of endpoints in the pool.
+    pub fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    /// Whether the pool has no endpoints.
+    pub fn is_empty(&self) -> bool {
+        self.endpoints.is_empty()
+    }
+
+    /// Pick the endpoint to use next. Endpoints which were not `tried` yet are preferred. If
+    /// all endpoints are down, the one that failed the longest ago is returned.
+    pub fn select(&self, tried: &[String]) -> Option<String> {
+        let healthy = self
+            .endpoints
+            .iter()
+            .filter(|endpoint| endpoint.state.lock().unwrap().healthy)
+            .collect::<Vec<_>>();
+        let untried = healthy
+            .iter()
+            .copied()
+            .filter(|endpoint| !tried.contains(&endpoint.url))
+            .collect::<Vec<_>>();
+        let candidates = if untried.is_empty() { healthy } else { untried };
+        if candidates.is_empty() {
+            return self
+                .endpoints
+                .iter()
+                .min_by_key(|endpoint| endpoint.state.lock().unwrap().last_failure)
+                .map(|endpoint| endpoint.url.clone());
+        }
+
+        let endpoint = match self.selection {
+            Selection::RoundRobin => {
+                candidates[self.next.fetch_add(1, Ordering::Relaxed) % candidates.len()]
+            }
+            Selection::LeastLatency => candidates
+                .iter()
+                .copied()
+                .min_by_key(|endpoint| {
+                    endpoint
+                        .state
+                        .lock()
+                        .unwrap()
+                        .latency
+                        .unwrap_or(Duration::MAX)
+                })
+                .unwrap(),
+        };
+        Some(endpoint.url.clone())
+    }
+
+    /// Check the health of all endpoints, returning the number of healthy ones.
+    pub async fn check_health(&self) -> usize {
+        let mut healthy = 0;
+        for endpoint in &self.endpoints {
+            if self.check(&endpoint.url).await {
+                healthy += 1;
+            }
+        }
+        healthy
+    }
+
+    /// Check the health of the given endpoint, returning whether it is healthy.
+    pub async fn check(&self, url: &str) -> bool {
+        let Some(endpoint) = self.endpoints.iter().find(|endpoint| endpoint.url == url) else {
+            return false;
+        };
+        let start = Instant::now();
+        let result = match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, self.ping(url)).await {
+            Ok(result) => result,
+            Err(_) => Err(anyhow::anyhow!("timed out after {HEALTH_CHECK_TIMEOUT:?}")),
+        };
+
+        let mut state = endpoint.state.lock().unwrap();
+        match result {
+            Ok(()) => {
+                if !state.healthy {
+                    info!("Endpoint {url} is healthy again");
+                }
+                state.healthy = true;
+                state.latency = Some(start.elapsed());
+                true
+            }
+            Err(e) => {
+                if state.healthy {
+                    warn!("Endpoint {url} failed its health check: {e}");
+                }
+                state.healthy = false;
+                state.last_failure = Some(Instant::now());
+                false
+            }
+        }
+    }
+
+    async fn ping(&self, url: &str) -> Result<()> {
+        let client = reqwest::Client::new();
+        let response = match self.kind {
+            EndpointKind::Rpc => {
+                client
+                    .post(url)
+                    .json(&json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "method": "eth_blockNumber",
+                        "params": [],
+                    }))
+                    .send()
+                    .await?
+            }
+            EndpointKind::Beacon => {
+                client
+                    .get(format!("{}/eth/v1/node/health", url.trim_end_matches('/')))
+                    .send()
+                    .await?
+            }
+        };
+        if !response.status().is_success() {
+            bail!("status code {}", response.status());
+        }
+        if self.kind == EndpointKind::Rpc {
+            let body: serde_json::Value = response.json().await?;
+            if let Some(error) = body.get("error") {
+                bail!("{error}");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct PoolConfig {
+    selection: Selection,
+    health_check_interval: Duration,
+}
+
+lazy_static! {
+    static ref CONFIG: RwLock<PoolConfig> = RwLock::new(PoolConfig {
+        selection: Selection::default(),
+        health_check_interval: Duration::ZERO,
+    });
+    static ref POOLS: Mutex<Pools> = Mutex::new(Pools::default());
+}
+
+type PoolKey = (EndpointKind, Vec<String>);
+
+/// The shared pools, by their endpoints, with the count of uses when they were last used.
+#[derive(Default)]
+struct Pools {
+    pools: HashMap<PoolKey, (Arc<ProviderPool>, u64)>,
+    uses: u64,
+}
+
+impl Pools {
+    fn get(
+        &mut self,
+        key: PoolKey,
+        create: impl FnOnce(&PoolKey) -> Arc<ProviderPool>,
+    ) -> Arc<ProviderPool> {
+        self.uses += 1;
+        if let Some((pool, last_used)) = self.pools.get_mut(&key) {
+            *last_used = self.uses;
+            return pool.clone();
+        }
+        if self.pools.len() >= MAX_POOLS {
+            let oldest = self
+                .pools
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.pools.remove(&oldest);
+            }
+        }
+        let pool = create(&key);
+        self.pools.insert(key, (pool.clone(), self.uses));
+        pool
+    }
+}
+
+/// Set how endpoints are selected and how often their health is checked, `0` disabling the
+/// periodic health checks. The current pools are dropped and created again on their next use,
+/// the running preflights keep theirs until they finish.
+pub fn configure(selection: Selection, health_check_interval: Duration) {
+    *CONFIG.write().unwrap() = PoolConfig {
+        selection,
+        health_check_interval,
+    };
+    POOLS.lock().unwrap().pools.clear();
+}
+
+/// Get the pool of the given endpoints. Pools are shared, so the health of the endpoints is
+/// tracked across requests, up to [MAX_POOLS] of them.
+pub fn get_pool(kind: EndpointKind, urls: Vec<String>) -> Arc<ProviderPool> {
+    let config = *CONFIG.read().unwrap();
+    POOLS.lock().unwrap().get((kind, urls), |(kind, urls)| {
+        let pool = Arc::new(ProviderPool::new(*kind, urls, config.selection));
+        if !config.health_check_interval.is_zero() && pool.len() > 1 {
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                let pool = Arc::downgrade(&pool);
+                handle.spawn(async move {
+                    let mut interval = tokio::time::interval(config.health_check_interval);
+                    loop {
+                        interval.tick().await;
+                        // Stop once the pool was dropped and is no longer used.
+                        let Some(pool) = pool.upgrade() else {
+                            break;
+                        };
+                        pool.check_health().await;
+                    }
+                });
+            }
+        }
+        pool
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(selection: Selection) -> ProviderPool {
+        let urls = ["http://a", "http://b", "http://c", "http://a"].map(str::to_string);
+        ProviderPool::new(EndpointKind::Rpc, &urls, selection)
+    }
+
+    fn set_state(pool: &ProviderPool, url: &str, healthy: bool, latency: u64) {
+        let endpoint = pool.endpoints.iter().find(|e| e.url == url).unwrap();
+        let mut state = endpoint.state.lock().unwrap();
+        state.healthy = healthy;
+        state.latency = Some(Duration::from_millis(latency));
+        if !healthy {
+            state.last_failure = Some(Instant::now());
+        }
+    }
+
+    #[test]
+    fn test_round_robin_skips_unhealthy() {
+        let pool = pool(Selection::RoundRobin);
+        assert_eq!(pool.len(), 3);
+        set_state(&pool, "http://b", false, 0);
+
+        let selected = (0..4)
+            .map(|_| pool.select(&[]).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(selected, ["http://a", "http://c", "http://a", "http://c"]);
+        // Endpoints which weren't tried yet are preferred.
+        assert_eq!(
+            pool.select(&["http://a".to_string()]).as_deref(),
+            Some("http://c")
+        );
+    }
+
+    #[test]
+    fn test_least_latency() {
+        let pool = pool(Selection::LeastLatency);
+        set_state(&pool, "http://a", true, 300);
+        set_state(&pool, "http://b", true, 100);
+        set_state(&pool, "http://c", true, 200);
+        assert_eq!(pool.select(&[]).as_deref(), Some("http://b"));
+        assert_eq!(
+            pool.select(&["http://b".to_string()]).as_deref(),
+            Some("http://c")
+        );
+
+        // All endpoints down, the one failing first gets another chance.
+        for url in ["http://a", "http://b", "http://c"] {
+            set_state(&pool, url, false, 0);
+        }
+        assert_eq!(pool.select(&[]).as_deref(), Some("http://a"));
+    }
+
+    #[test]
+    fn test_max_pools() {
+        let mut pools = Pools::default();
+        let key = |i: usize| (EndpointKind::Rpc, vec![format!("http://{i}")]);
+        let create = |(kind, urls): &PoolKey| {
+            Arc::new(ProviderPool::new(*kind, urls, Selection::RoundRobin))
+        };
+        let first = pools.get(key(0), create);
+        for i in 1..MAX_POOLS {
+            pools.get(key(i), create);
+        }
+        // The first pool is used again, so the second one is dropped for a new one.
+        assert!(Arc::ptr_eq(&pools.get(key(0), create), &first));
+        pools.get(key(MAX_POOLS), create);
+        assert_eq!(pools.pools.len(), MAX_POOLS);
+        assert!(pools.pools.contains_key(&key(0)));
+        assert!(!pools.pools.contains_key(&key(1)));
+    }
+}
@@ -0,0 +1,196 @@
+//! Persistent storage for proof jobs.
+//!
+//! The [`JobManager`](crate::jobs::JobManager) writes every state change of a job to a
+//! [`TaskStore`], so that queued and running jobs can be picked up again when the host
+//! restarts.
+
+use std::{collections::BTreeMap, fmt::Debug, path::Path};
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::jobs::{Job, JobId};
+
+#[cfg(feature = "rocksdb")]
+mod rocks;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+/// A store for proof jobs.
+pub trait TaskStore: Send + Debug {
+    /// Insert the job or replace the stored job with the same id.
+    fn put(&mut self, job: &Job) -> Result<()>;
+
+    /// Get the job with the given id.
+    fn get(&self, id: JobId) -> Result<Option<Job>>;
+
+    /// Get all stored jobs, ordered by id. Records which can't be decoded are logged and
+    /// skipped, so one bad record doesn't keep the host from starting.
+    fn list(&self) -> Result<Vec<Job>>;
+
+    /// Write the buffered changes to disk, before the host exits.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+/// Available task store backends.
+pub enum TaskStoreKind {
+    /// Keep jobs in memory only, they are lost on restart.
+    #[default]
+    Memory,
+    /// Persist jobs in a SQLite database.
+    Sqlite,
+    /// Persist jobs in a RocksDB database.
+    Rocksdb,
+}
+
+/// Open the task store of the given kind.
+pub fn open_task_store(kind: TaskStoreKind, path: Option<&Path>) -> Result<Box<dyn TaskStore>> {
+    let path = match (kind, path) {
+        (TaskStoreKind::Memory, _) => return Ok(Box::<InMemoryTaskStore>::default()),
+        (_, Some(path)) => path,
+        (_, None) => bail!("A task db path is required for the {kind:?} task store"),
+    };
+
+    match kind {
+        TaskStoreKind::Sqlite => {
+            #[cfg(feature = "sqlite")]
+            return Ok(Box::new(sqlite::SqliteTaskStore::open(path)?));
+
+            #[cfg(not(feature = "sqlite"))]
+            bail!("Cannot open {path:?}, the host was built without the `sqlite` feature");
+        }
+        TaskStoreKind::Rocksdb => {
+            #[cfg(feature = "rocksdb")]
+            return Ok(Box::new(rocks::RocksDbTaskStore::open(path)?));
+
+            #[cfg(not(feature = "rocksdb"))]
+            bail!("Cannot open {path:?}, the host was built without the `rocksdb` feature");
+        }
+        TaskStoreKind::Memory => unreachable!(),
+    }
+}
+
+/// Decode a stored job, logging and skipping records which can't be decoded.
+fn decode_job(id: impl std::fmt::Display, data: &[u8]) -> Option<Job> {
+    serde_json::from_slice(data)
+        .inspect_err(|e| {
+            warn!(
+                "Skipping job record {id} which can't be decoded: {e}: {}",
+                String::from_utf8_lossy(data)
+            )
+        })
+        .ok()
+}
+
+/// A task store that keeps the jobs in memory.
+#[derive(Default, Debug)]
+pub struct InMemoryTaskStore {
+    jobs: BTreeMap<JobId, Job>,
+}
+
+impl TaskStore for InMemoryTaskStore {
+    fn put(&mut self, job: &Job) -> Result<()> {
+        self.jobs.insert(job.id, job.clone());
+        Ok(())
+    }
+
+    fn get(&self, id: JobId) -> Result<Option<Job>> {
+        Ok(self.jobs.get(&id).cloned())
+    }
+
+    fn list(&self) -> Result<Vec<Job>> {
+        Ok(self.jobs.values().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::{jobs::JobStatus, request::ProofRequest};
+
+    use super::*;
+
+    /// A job for block `10 + id`, for the tests of the stores and the API.
+    pub(crate) fn job(id: JobId, status: JobStatus) -> Job {
+        let request: ProofRequest = serde_json::from_value(serde_json::json!({
+            "block_number": 10 + id,
+            "rpc": "http://localhost:8545",
+            "l1_rpc": "http://localhost:8546",
+            "beacon_rpc": "http://localhost:5052",
+            "network": "taiko_a7",
+            "l1_network": "holesky",
+            "graffiti": "0x0000000000000000000000000000000000000000000000000000000000000000",
+            "prover": "0x0000000000000000000000000000000000000000",
+            "proof_type": "native",
+        }))
+        .unwrap();
+        Job {
+            id,
+            request,
+            status,
+            proof: None,
+            error: None,
+            crash: None,
+            retries: 0,
+            submission: None,
+            upload: None,
+            trace_id: format!("trace-{id}"),
+            phases: Default::default(),
+            created_at: 100,
+            updated_at: 100 + id,
+        }
+    }
+
+    fn summary(job: &Job) -> (JobId, JobStatus, u64, String, u64) {
+        (
+            job.id,
+            job.status,
+            job.request.block_number,
+            job.trace_id.clone(),
+            job.updated_at,
+        )
+    }
+
+    /// Store jobs and read them back, for the tests of every store.
+    pub(crate) fn round_trip(store: &mut dyn TaskStore) {
+        store.put(&job(2, JobStatus::Running)).unwrap();
+        store.put(&job(1, JobStatus::Queued)).unwrap();
+        store.put(&job(1, JobStatus::Success)).unwrap();
+
+        assert_eq!(
+            store.get(1).unwrap().map(|job| summary(&job)),
+            Some(summary(&job(1, JobStatus::Success)))
+        );
+        assert!(store.get(3).unwrap().is_none());
+        assert_eq!(
+            store
+                .list()
+                .unwrap()
+                .iter()
+                .map(summary)
+                .collect::<Vec<_>>(),
+            vec![
+                summary(&job(1, JobStatus::Success)),
+                summary(&job(2, JobStatus::Running))
+            ]
+        );
+        store.flush().unwrap();
+    }
+
+    #[test]
+    fn test_in_memory_round_trip() {
+        round_trip(&mut InMemoryTaskStore::default());
+    }
+
+    #[test]
+    fn test_decode_job() {
+        let data = serde_json::to_vec(&job(1, JobStatus::Queued)).unwrap();
+        assert_eq!(decode_job(1, &data).map(|job| job.id), Some(1));
+        assert!(decode_job(2, b"{\"id\": 2}").is_none());
+    }
+}
@@ -0,0 +1,83 @@
+use std::path::Path;
+
+use anyhow::Result;
+use rocksdb::{IteratorMode, DB};
+
+use crate::{
+    jobs::{Job, JobId},
+    queue::{decode_job, TaskStore},
+};
+
+/// A task store persisting the jobs as JSON in a RocksDB database.
+///
+/// Jobs are keyed by their big-endian id so iteration returns them in order.
+#[derive(Debug)]
+pub struct RocksDbTaskStore {
+    db: DB,
+}
+
+impl RocksDbTaskStore {
+    /// Open the database at the given path, creating it if it doesn't exist.
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self {
+            db: DB::open_default(path)?,
+        })
+    }
+}
+
+impl TaskStore for RocksDbTaskStore {
+    fn put(&mut self, job: &Job) -> Result<()> {
+        self.db
+            .put(job.id.to_be_bytes(), serde_json::to_vec(job)?)?;
+        Ok(())
+    }
+
+    fn get(&self, id: JobId) -> Result<Option<Job>> {
+        Ok(self
+            .db
+            .get(id.to_be_bytes())?
+            .map(|data| serde_json::from_slice(&data))
+            .transpose()?)
+    }
+
+    fn list(&self) -> Result<Vec<Job>> {
+        let mut jobs = Vec::new();
+        for item in self.db.iterator(IteratorMode::Start) {
+            let (key, data) = item?;
+            let id = <[u8; 8]>::try_from(&*key).map_or_else(
+                |_| format!("{key:?}"),
+                |id| JobId::from_be_bytes(id).to_string(),
+            );
+            jobs.extend(decode_job(id, &data));
+        }
+        Ok(jobs)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.db.flush()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::queue::tests::round_trip;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        round_trip(&mut RocksDbTaskStore::open(dir.path()).unwrap());
+
+        // The jobs are still there after reopening, and a bad record is skipped.
+        let mut store = RocksDbTaskStore::open(dir.path()).unwrap();
+        store.db.put(3u64.to_be_bytes(), b"not a job").unwrap();
+        let ids = store
+            .list()
+            .unwrap()
+            .iter()
+            .map(|job| job.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2]);
+    }
+}
@@ -0,0 +1,90 @@
+use std::path::Path;
+
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{
+    jobs::{Job, JobId},
+    queue::{decode_job, TaskStore},
+};
+
+/// A task store persisting the jobs as JSON in a SQLite database.
+#[derive(Debug)]
+pub struct SqliteTaskStore {
+    conn: Connection,
+}
+
+impl SqliteTaskStore {
+    /// Open the database at the given path, creating it if it doesn't exist.
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS jobs (id INTEGER PRIMARY KEY, data TEXT NOT NULL)",
+            [],
+        )?;
+        Ok(Self { conn })
+    }
+}
+
+impl TaskStore for SqliteTaskStore {
+    fn put(&mut self, job: &Job) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO jobs (id, data) VALUES (?1, ?2)",
+            params![job.id as i64, serde_json::to_string(job)?],
+        )?;
+        Ok(())
+    }
+
+    fn get(&self, id: JobId) -> Result<Option<Job>> {
+        let data = self
+            .conn
+            .query_row(
+                "SELECT data FROM jobs WHERE id = ?1",
+                params![id as i64],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        Ok(data.map(|data| serde_json::from_str(&data)).transpose()?)
+    }
+
+    fn list(&self) -> Result<Vec<Job>> {
+        let mut stmt = self.conn.prepare("SELECT id, data FROM jobs ORDER BY id")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut jobs = Vec::new();
+        for row in rows {
+            let (id, data) = row?;
+            jobs.extend(decode_job(id, data.as_bytes()));
+        }
+        Ok(jobs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::queue::tests::round_trip;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jobs.db");
+        round_trip(&mut SqliteTaskStore::open(&path).unwrap());
+
+        // The jobs are still there after reopening, and a bad record is skipped.
+        let store = SqliteTaskStore::open(&path).unwrap();
+        store
+            .conn
+            .execute("INSERT INTO jobs (id, data) VALUES (3, 'not a job')", [])
+            .unwrap();
+        let ids = store
+            .list()
+            .unwrap()
+            .iter()
+            .map(|job| job.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2]);
+    }
+}
@@ -0,0 +1,244 @@
+//! Rate limiting of the proof requests, so a single client cannot flood the queue.
+//!
+//! Every client IP gets a token bucket refilled at `requests_per_minute`, holding up to `burst`
+//! requests, and `max_in_flight` caps the proof requests served at once across all clients,
+//! until their jobs finished. Rejected requests get a `429` with a `Retry-After` hint.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::metrics::inc_rate_limited_count;
+
+/// The number of client buckets kept before the full ones, and then the oldest ones, are
+/// dropped.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// The retry hint when all in-flight slots are taken.
+const IN_FLIGHT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+/// The rate limits of the proof requests, e.g. `{"requests_per_minute": 30, "burst": 10,
+/// "max_in_flight": 100}`.
+pub struct RateLimitConfig {
+    /// The proof requests per minute a client IP can make on average, unlimited if not set.
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    /// The proof requests a client IP can make at once, defaults to `requests_per_minute`.
+    #[serde(default)]
+    pub burst: Option<u32>,
+    /// The max number of proof requests served at once, unlimited if not set.
+    #[serde(default)]
+    pub max_in_flight: Option<usize>,
+    /// Take the client IP from the `X-Forwarded-For` header. Only enable this behind a proxy
+    /// that sets it, otherwise clients can pick their own IP.
+    #[serde(default)]
+    pub forwarded_for: bool,
+    /// The number of proxies in front of the host that append to `X-Forwarded-For`, defaults
+    /// to 1. The client IP is the entry appended by the farthest of them.
+    #[serde(default)]
+    pub forwarded_hops: Option<usize>,
+}
+
+/// The in-flight slot of an admitted proof request, held by the request and the jobs it
+/// queued until all of them finished.
+#[derive(Clone, Debug)]
+pub struct InFlightPermit(Arc<OwnedSemaphorePermit>);
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    /// Take a token, or return how long until one is available.
+    fn take(&mut self, per_second: f64, burst: f64, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_second).min(burst);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / per_second))
+        }
+    }
+}
+
+/// Limits the proof requests per client IP and in total.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Arc<Mutex<HashMap<IpAddr, Bucket>>>,
+    in_flight: Option<Arc<Semaphore>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        let in_flight = config
+            .max_in_flight
+            .map(|max_in_flight| Arc::new(Semaphore::new(max_in_flight)));
+        Self {
+            config,
+            buckets: Default::default(),
+            in_flight,
+        }
+    }
+
+    /// The client IP of a request, from the `X-Forwarded-For` header when enabled and from the
+    /// address of the peer otherwise. Every proxy appends the address it got the request
+    /// from, so the entries left of the trusted proxies can be set by the client.
+    pub fn client(&self, forwarded_for: Option<&str>, peer: Option<IpAddr>) -> Option<IpAddr> {
+        if !self.config.forwarded_for {
+            return peer;
+        }
+        let hops = self.config.forwarded_hops.unwrap_or(1).max(1);
+        forwarded_for
+            .and_then(|value| value.rsplit(',').nth(hops - 1))
+            .and_then(|client| client.trim().parse().ok())
+            .or(peer)
+    }
+
+    /// Admit a proof request of the client. The returned permit holds the in-flight slot of
+    /// the request until it is dropped. Rejected requests get the time to wait before retrying.
+    pub fn admit(&self, client: Option<IpAddr>) -> Result<Option<InFlightPermit>, Duration> {
+        if let (Some(client), Some(requests_per_minute)) = (client, self.config.requests_per_minute)
+        {
+            self.take(client, requests_per_minute, Instant::now())
+                .inspect_err(|_| inc_rate_limited_count("client"))?;
+        }
+        match &self.in_flight {
+            Some(in_flight) => match in_flight.clone().try_acquire_owned() {
+                Ok(permit) => Ok(Some(InFlightPermit(Arc::new(permit)))),
+                Err(_) => {
+                    inc_rate_limited_count("in_flight");
+                    Err(IN_FLIGHT_RETRY_AFTER)
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn take(&self, client: IpAddr, requests_per_minute: u32, now: Instant) -> Result<(), Duration> {
+        let per_second = f64::from(requests_per_minute.max(1)) / 60.0;
+        let burst = f64::from(self.config.burst.unwrap_or(requests_per_minute).max(1));
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&client) {
+            // Clients with a full bucket are in the same state as new ones.
+            buckets.retain(|_, bucket| {
+                let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+                bucket.tokens + elapsed * per_second < burst
+            });
+        }
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&client) {
+            let oldest = buckets
+                .iter()
+                .min_by_key(|(_, bucket)| bucket.updated)
+                .map(|(client, _)| *client);
+            if let Some(oldest) = oldest {
+                buckets.remove(&oldest);
+            }
+        }
+        buckets
+            .entry(client)
+            .or_insert(Bucket {
+                tokens: burst,
+                updated: now,
+            })
+            .take(per_second, burst, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn test_client_bucket() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            requests_per_minute: Some(60),
+            burst: Some(2),
+            ..Default::default()
+        });
+        let (client, other) = ([10, 0, 0, 1].into(), [10, 0, 0, 2].into());
+        let now = Instant::now();
+        limiter.take(client, 60, now).unwrap();
+        limiter.take(client, 60, now).unwrap();
+        let retry_after = limiter.take(client, 60, now).unwrap_err();
+        assert_eq!(retry_after, Duration::from_secs(1));
+        // Other clients have their own bucket
+        limiter.take(other, 60, now).unwrap();
+        // A token is refilled every second
+        limiter
+            .take(client, 60, now + Duration::from_secs(1))
+            .unwrap();
+        assert!(limiter
+            .take(client, 60, now + Duration::from_secs(1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_max_tracked_clients() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            requests_per_minute: Some(1),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        for i in 0..=MAX_TRACKED_CLIENTS as u32 {
+            limiter
+                .take(
+                    Ipv4Addr::from(i).into(),
+                    1,
+                    now + Duration::from_millis(i.into()),
+                )
+                .unwrap();
+        }
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.len(), MAX_TRACKED_CLIENTS);
+        // The oldest bucket was dropped
+        assert!(!buckets.contains_key(&IpAddr::from([0, 0, 0, 0])));
+    }
+
+    #[test]
+    fn test_forwarded_client() {
+        let peer = Some([10, 0, 0, 1].into());
+        let header = Some("1.1.1.1, 2.2.2.2, 3.3.3.3");
+        let limiter = RateLimiter::new(RateLimitConfig::default());
+        assert_eq!(limiter.client(header, peer), peer);
+        let limiter = RateLimiter::new(RateLimitConfig {
+            forwarded_for: true,
+            ..Default::default()
+        });
+        // The entry appended by the proxy, not the ones the client sent
+        assert_eq!(limiter.client(header, peer), Some([3, 3, 3, 3].into()));
+        assert_eq!(limiter.client(None, peer), peer);
+        let limiter = RateLimiter::new(RateLimitConfig {
+            forwarded_for: true,
+            forwarded_hops: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(limiter.client(header, peer), Some([2, 2, 2, 2].into()));
+    }
+
+    #[test]
+    fn test_max_in_flight() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            max_in_flight: Some(1),
+            ..Default::default()
+        });
+        let permit = limiter.admit(None).unwrap();
+        assert!(permit.is_some());
+        assert_eq!(limiter.admit(None).unwrap_err(), IN_FLIGHT_RETRY_AFTER);
+        drop(permit);
+        assert!(limiter.admit(None).is_ok());
+    }
+}
@@ -0,0 +1,289 @@
+//! The readiness checks of the dependencies of the host, served at `/readyz`.
+//!
+//! The host is ready once the nodes of the default proof request and of the watchers have a
+//! healthy endpoint, the proof types they prove with are registered, the SGX enclave was
+//! bootstrapped and, with `--require-gpu`, a GPU is visible. It is not ready while its queue is
+//! full, with `max_queue_depth`, while the circuit breaker of a backend is open, and no longer
+//! ready once it drains its jobs for the shutdown.
+
+use std::collections::HashSet;
+
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    breakers::{self, BreakerState, BreakerStatus},
+    chain_registry::get_chain,
+    jobs::JobManager,
+    provider_pool::{get_pool, EndpointKind},
+    registry::get_prover,
+    request::ProofRequestOpt,
+    Cli,
+};
+
+/// The directory the NVIDIA driver lists the visible GPUs in.
+const NVIDIA_GPUS_DIR: &str = "/proc/driver/nvidia/gpus";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// The check of a dependency of the host.
+pub struct DependencyCheck {
+    /// The dependency, e.g. `rpc:taiko_a7`, `prover:sgx`, `sgx_enclave`, `gpu`, `queue`,
+    /// `breaker:prover:risc0` or `shutdown`.
+    pub name: String,
+    pub status: CheckStatus,
+    /// What the check found, e.g. the number of healthy endpoints or the error.
+    pub detail: String,
+}
+
+impl DependencyCheck {
+    fn new(name: impl Into<String>, result: Result<String, String>) -> Self {
+        let (status, detail) = match result {
+            Ok(detail) => (CheckStatus::Ok, detail),
+            Err(detail) => (CheckStatus::Failed, detail),
+        };
+        Self {
+            name: name.into(),
+            status,
+            detail,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// Whether the host is ready to prove, with the check of every dependency.
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Vec<DependencyCheck>,
+}
+
+/// Check the dependencies of the host and the queue of its jobs.
+pub async fn check(opts: &Cli, jobs: &JobManager) -> Readiness {
+    let requests = requests(opts);
+    let mut checks = Vec::new();
+    if jobs.is_draining() {
+        checks.push(DependencyCheck::new(
+            "shutdown",
+            Err("Draining the jobs for the shutdown".to_string()),
+        ));
+    }
+    checks.extend(check_nodes(&requests).await);
+    checks.extend(check_provers(opts, &requests));
+    #[cfg(feature = "sgx")]
+    checks.push(check_sgx_enclave());
+    if opts.require_gpu {
+        checks.push(check_gpu());
+    }
+    checks.extend(check_queue(jobs.queue_depth(), jobs.max_queue_depth()));
+    checks.extend(breakers::statuses().iter().map(check_breaker));
+    Readiness {
+        ready: checks.iter().all(|check| check.status == CheckStatus::Ok),
+        checks,
+    }
+}
+
+/// The default proof request and the proof requests of the watchers.
+fn requests(opts: &Cli) -> Vec<ProofRequestOpt> {
+    let mut requests = vec![opts.proof_request_opt.clone()];
+    for watcher in &opts.watchers {
+        let mut request = opts.proof_request_opt.clone();
+        // The watchers check their request on startup.
+        if request.merge(&watcher.request).is_ok() {
+            request.network = Some(watcher.network.clone());
+            requests.push(request);
+        }
+    }
+    requests
+}
+
+/// Check every node of the requests has a healthy endpoint.
+async fn check_nodes(requests: &[ProofRequestOpt]) -> Vec<DependencyCheck> {
+    let mut seen = HashSet::new();
+    let mut nodes = Vec::new();
+    for request in requests {
+        let Some(chain) = request
+            .network
+            .as_deref()
+            .and_then(|network| get_chain(network).ok())
+        else {
+            continue;
+        };
+        let endpoints = [
+            (
+                "rpc",
+                EndpointKind::Rpc,
+                &request.rpc,
+                &chain.rpc,
+                &chain.rpc_fallbacks,
+            ),
+            (
+                "l1_rpc",
+                EndpointKind::Rpc,
+                &request.l1_rpc,
+                &chain.l1_rpc,
+                &chain.l1_rpc_fallbacks,
+            ),
+            (
+                "beacon_rpc",
+                EndpointKind::Beacon,
+                &request.beacon_rpc,
+                &chain.beacon_rpc,
+                &chain.beacon_rpc_fallbacks,
+            ),
+        ];
+        for (name, kind, url, default_url, fallbacks) in endpoints {
+            // The same endpoints as the preflight, so their pool is shared with it.
+            let Some(url) = url.clone().or(default_url.clone()) else {
+                continue;
+            };
+            let mut urls = vec![url];
+            urls.extend(fallbacks.iter().cloned());
+            if seen.insert((kind, urls.clone())) {
+                nodes.push((format!("{name}:{}", chain.name), kind, urls));
+            }
+        }
+    }
+
+    join_all(nodes.into_iter().map(|(name, kind, urls)| async move {
+        let pool = get_pool(kind, urls);
+        let healthy = pool.check_health().await;
+        let detail = format!("{healthy} of {} endpoints healthy", pool.len());
+        DependencyCheck::new(name, if healthy > 0 { Ok(detail) } else { Err(detail) })
+    }))
+    .await
+}
+
+/// Check the proof types of the requests are registered.
+fn check_provers(opts: &Cli, requests: &[ProofRequestOpt]) -> Vec<DependencyCheck> {
+    let mut proof_types: Vec<String> = requests
+        .iter()
+        .filter_map(|request| request.proof_type.clone())
+        .collect();
+    for watcher in &opts.watchers {
+        proof_types.extend(watcher.proof_types.iter().cloned());
+    }
+    proof_types.sort();
+    proof_types.dedup();
+    proof_types
+        .into_iter()
+        .map(|proof_type| {
+            let result = match get_prover(&proof_type) {
+                Some(_) => Ok("registered".to_string()),
+                None => Err("not built into the host or registered".to_string()),
+            };
+            DependencyCheck::new(format!("prover:{proof_type}"), result)
+        })
+        .collect()
+}
+
+/// Check the local SGX enclave is up.
+#[cfg(feature = "sgx")]
+fn check_sgx_enclave() -> DependencyCheck {
+    let result = sgx_prover::check_enclave()
+        .map(|key| format!("bootstrapped with instance {}", key.instance_address))
+        .map_err(|e| e.to_string());
+    DependencyCheck::new("sgx_enclave", result)
+}
+
+/// Check a GPU is visible to the host.
+fn check_gpu() -> DependencyCheck {
+    let gpus = std::fs::read_dir(NVIDIA_GPUS_DIR)
+        .map(|dir| dir.count())
+        .unwrap_or_default();
+    let result = if gpus > 0 {
+        Ok(format!("{gpus} visible"))
+    } else {
+        Err(format!("No GPU in {NVIDIA_GPUS_DIR}"))
+    };
+    DependencyCheck::new("gpu", result)
+}
+
+/// Check the queue has room for more jobs, if its depth is limited.
+fn check_queue(depth: usize, max_queue_depth: Option<usize>) -> Option<DependencyCheck> {
+    let max_queue_depth = max_queue_depth?;
+    let detail = format!("{depth} of {max_queue_depth} jobs queued");
+    let result = if depth < max_queue_depth {
+        Ok(detail)
+    } else {
+        Err(format!("The queue is full, {detail}"))
+    };
+    Some(DependencyCheck::new("queue", result))
+}
+
+/// Check the circuit breaker of a backend is not open.
+fn check_breaker(status: &BreakerStatus) -> DependencyCheck {
+    let result = match status.state {
+        BreakerState::Closed => Ok(format!("closed, {} consecutive failures", status.failures)),
+        BreakerState::HalfOpen => Ok("half open, probing the backend".to_string()),
+        BreakerState::Open => Err(format!(
+            "open after {} consecutive failures, probing the backend in {}s",
+            status.failures,
+            status.retry_after.unwrap_or_default()
+        )),
+    };
+    DependencyCheck::new(format!("breaker:{}", status.name), result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_provers() {
+        let opts = Cli::default();
+        let request = ProofRequestOpt {
+            proof_type: Some("native".to_string()),
+            ..Default::default()
+        };
+        let unknown = ProofRequestOpt {
+            proof_type: Some("unknown".to_string()),
+            ..Default::default()
+        };
+        let checks = check_provers(&opts, &[request.clone(), request, unknown]);
+        assert_eq!(checks.len(), 2);
+        assert_eq!(checks[0].name, "prover:native");
+        assert_eq!(checks[0].status, CheckStatus::Ok);
+        assert_eq!(checks[1].name, "prover:unknown");
+        assert_eq!(checks[1].status, CheckStatus::Failed);
+    }
+
+    #[test]
+    fn test_check_queue() {
+        assert!(check_queue(100, None).is_none());
+        let check = check_queue(3, Some(10)).unwrap();
+        assert_eq!(check.status, CheckStatus::Ok);
+        assert_eq!(check.detail, "3 of 10 jobs queued");
+        assert_eq!(
+            check_queue(10, Some(10)).unwrap().status,
+            CheckStatus::Failed
+        );
+    }
+
+    #[test]
+    fn test_check_breaker() {
+        let mut status = BreakerStatus {
+            name: "prover:risc0".to_string(),
+            state: BreakerState::Closed,
+            failures: 2,
+            retry_after: None,
+        };
+        let check = check_breaker(&status);
+        assert_eq!(check.name, "breaker:prover:risc0");
+        assert_eq!(check.status, CheckStatus::Ok);
+        status.state = BreakerState::Open;
+        status.retry_after = Some(30);
+        let check = check_breaker(&status);
+        assert_eq!(check.status, CheckStatus::Failed);
+        assert_eq!(
+            check.detail,
+            "open after 2 consecutive failures, probing the backend in 30s"
+        );
+    }
+}
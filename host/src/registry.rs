@@ -0,0 +1,227 @@
+//! The registry of available prover backends.
+//!
+//! The provers built into the host are registered depending on the enabled features. Other
+//! backends, e.g. from an out-of-tree driver crate, can be added with [register_prover]
+//! before the server is started and are then available under their name as proof type.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+};
+
+use alloy_primitives::B256;
+use anyhow::anyhow;
+use lazy_static::lazy_static;
+use raiko_lib::{
+    input::{GuestInput, GuestOutput},
+    protocol_instance::ProtocolInstance,
+    prover::{
+        BoxFuture, ProgressReporter, Proof, Prover, ProverBackend, ProverConfig, ProverResult,
+    },
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[cfg(all(feature = "sgx", any(feature = "risc0", feature = "sp1")))]
+use crate::composite::SgxAndZkBackend;
+use crate::{
+    error::{HostError, HostResult},
+    execution::NativeProver,
+    mock::MockProver,
+};
+
+/// Implement [ProverBackend] for one of the built-in provers.
+macro_rules! builtin_backend {
+    ($backend:ident, $prover:ty, $name:literal) => {
+        struct $backend;
+
+        impl ProverBackend for $backend {
+            fn name(&self) -> &str {
+                $name
+            }
+
+            fn run<'a>(
+                &'a self,
+                input: GuestInput,
+                output: GuestOutput,
+                config: &'a ProverConfig,
+                progress: &'a ProgressReporter,
+            ) -> BoxFuture<'a, ProverResult<Proof>> {
+                Box::pin(<$prover>::run_with_progress(
+                    input, output, config, progress,
+                ))
+            }
+
+            fn instance_hash(&self, pi: ProtocolInstance) -> B256 {
+                <$prover>::instance_hash(pi)
+            }
+
+            fn program_id(&self) -> Option<B256> {
+                <$prover>::program_id()
+            }
+
+            fn warm_up(&self) -> ProverResult<()> {
+                <$prover>::warm_up()
+            }
+
+            fn verify(
+                &self,
+                proof: &Proof,
+                input: &GuestInput,
+                output: &GuestOutput,
+            ) -> Option<ProverResult<()>> {
+                <$prover>::verify(proof, input, output)
+            }
+        }
+    };
+}
+
+builtin_backend!(NativeBackend, NativeProver, "native");
+builtin_backend!(MockBackend, MockProver, "mock");
+#[cfg(feature = "sp1")]
+builtin_backend!(Sp1Backend, sp1_prover::Sp1Prover, "sp1");
+#[cfg(feature = "risc0")]
+builtin_backend!(Risc0Backend, risc0_prover::Risc0Prover, "risc0");
+#[cfg(feature = "sgx")]
+builtin_backend!(SgxBackend, sgx_prover::SgxProver, "sgx");
+#[cfg(feature = "jolt")]
+builtin_backend!(JoltBackend, jolt_prover::JoltProver, "jolt");
+#[cfg(feature = "plonky3")]
+builtin_backend!(Plonky3Backend, plonky3_prover::Plonky3Prover, "plonky3");
+
+/// The ZK prover of the `sgx_and_zk` proof type, risc0 if both are enabled.
+#[cfg(all(feature = "sgx", any(feature = "risc0", feature = "sp1")))]
+const SGX_AND_ZK_PROVER: &str = if cfg!(feature = "risc0") {
+    "risc0"
+} else {
+    "sp1"
+};
+
+struct Entry {
+    backend: Arc<dyn ProverBackend>,
+    builtin: bool,
+}
+
+lazy_static! {
+    static ref PROVERS: RwLock<BTreeMap<String, Entry>> = {
+        let builtins: Vec<Arc<dyn ProverBackend>> = vec![
+            Arc::new(NativeBackend),
+            Arc::new(MockBackend),
+            #[cfg(feature = "sp1")]
+            Arc::new(Sp1Backend),
+            #[cfg(feature = "risc0")]
+            Arc::new(Risc0Backend),
+            #[cfg(feature = "sgx")]
+            Arc::new(SgxBackend),
+            #[cfg(feature = "jolt")]
+            Arc::new(JoltBackend),
+            #[cfg(feature = "plonky3")]
+            Arc::new(Plonky3Backend),
+            #[cfg(all(feature = "sgx", any(feature = "risc0", feature = "sp1")))]
+            Arc::new(SgxAndZkBackend::new(SGX_AND_ZK_PROVER)),
+        ];
+        RwLock::new(
+            builtins
+                .into_iter()
+                .map(|backend| {
+                    let entry = Entry {
+                        backend,
+                        builtin: true,
+                    };
+                    (entry.backend.name().to_string(), entry)
+                })
+                .collect(),
+        )
+    };
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// A prover backend available on this host.
+pub struct ProverInfo {
+    /// The name of the prover, used as the proof type in proof requests.
+    pub name: String,
+    /// Whether the prover is built into the host.
+    pub builtin: bool,
+}
+
+/// Register a new prover backend under its name.
+pub fn register_prover(backend: Arc<dyn ProverBackend>) -> HostResult<()> {
+    let name = backend.name().trim().to_lowercase();
+    let mut provers = PROVERS.write().unwrap();
+    if provers.contains_key(&name) {
+        return Err(HostError::Anyhow(anyhow!(
+            "Prover {name} is already registered"
+        )));
+    }
+    provers.insert(
+        name,
+        Entry {
+            backend,
+            builtin: false,
+        },
+    );
+    Ok(())
+}
+
+/// Get the prover backend registered under the given name.
+pub fn get_prover(name: &str) -> Option<Arc<dyn ProverBackend>> {
+    PROVERS
+        .read()
+        .unwrap()
+        .get(&name.trim().to_lowercase())
+        .map(|entry| entry.backend.clone())
+}
+
+/// List all registered prover backends, ordered by name.
+pub fn list_provers() -> Vec<ProverInfo> {
+    PROVERS
+        .read()
+        .unwrap()
+        .iter()
+        .map(|(name, entry)| ProverInfo {
+            name: name.clone(),
+            builtin: entry.builtin,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestBackend;
+
+    impl ProverBackend for TestBackend {
+        fn name(&self) -> &str {
+            "Test"
+        }
+
+        fn run<'a>(
+            &'a self,
+            _input: GuestInput,
+            _output: GuestOutput,
+            _config: &'a ProverConfig,
+            _progress: &'a ProgressReporter,
+        ) -> BoxFuture<'a, ProverResult<Proof>> {
+            Box::pin(async { Ok(Proof::Null) })
+        }
+
+        fn instance_hash(&self, _pi: ProtocolInstance) -> B256 {
+            B256::default()
+        }
+    }
+
+    #[test]
+    fn test_register_prover() {
+        assert!(get_prover("native").is_some());
+        assert!(get_prover("test").is_none());
+
+        register_prover(Arc::new(TestBackend)).unwrap();
+        assert!(get_prover("test").is_some());
+        assert!(register_prover(Arc::new(TestBackend)).is_err());
+
+        let provers = list_provers();
+        assert!(provers.iter().any(|p| p.name == "native" && p.builtin));
+        assert!(provers.iter().any(|p| p.name == "test" && !p.builtin));
+    }
+}
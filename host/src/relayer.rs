@@ -0,0 +1,546 @@
+//! Submission of generated proofs to L1.
+//!
+//! With a [RelayerConfig], the host sends the `proveBlock` transaction for the proofs of its
+//! jobs itself. Nonces are handed out per account by the [Relayer], so concurrent jobs don't
+//! collide, and a transaction that is not mined in time is replaced with a higher gas price.
+//! The progress of the submission is reported in the job, see [Submission].
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use alloy_primitives::{Address, Bytes, B256, U256};
+use alloy_provider::{Provider, ProviderBuilder, RootProvider};
+use alloy_rpc_client::RpcClient;
+use alloy_rpc_types::BlockNumberOrTag;
+use alloy_sol_types::sol;
+use alloy_transport_http::Http;
+use raiko_lib::{
+    input::{GuestInput, Transition},
+    prover::Proof,
+};
+use raiko_signer::{RaikoSigner, SignerConfig};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use url::Url;
+use utoipa::ToSchema;
+
+use crate::{
+    calldata::ProveBlockCall,
+    chain_registry::get_chain,
+    error::{HostError, HostResult},
+    request::{ProofRequest, ProofType},
+};
+
+/// The environment variable with the private key of the account the proofs are submitted from,
+/// used when no signer is configured.
+pub const RELAYER_PRIV_KEY: &str = "RELAYER_PRIV_KEY";
+
+/// How often to check for the receipt of a submitted transaction.
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+sol! {
+    #[sol(rpc)]
+    contract TaikoL1 {
+        function proveBlock(uint64 blockId, bytes calldata input) external;
+    }
+}
+
+fn default_confirmations() -> u64 {
+    1
+}
+
+fn default_resubmit_after() -> u64 {
+    60
+}
+
+fn default_gas_bump_percent() -> u64 {
+    20
+}
+
+fn default_confirm_timeout() -> u64 {
+    15 * 60
+}
+
+fn default_max_attempts() -> u32 {
+    5
+}
+
+fn default_gas_limit() -> u64 {
+    3_000_000
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// How to submit the generated proofs to L1.
+pub struct RelayerConfig {
+    /// The tier of the proofs of each proof type, e.g. `{"sgx": 200}`. Proofs of other types
+    /// are not submitted.
+    pub tiers: BTreeMap<String, u16>,
+    /// The L1 node URL to submit the proofs with. The L1 node of the requests is never used, as
+    /// it could hide the transactions of the account or their receipts.
+    pub l1_rpc: String,
+    /// The number of blocks on top of the block with the transaction before a submission is
+    /// confirmed.
+    #[serde(default = "default_confirmations")]
+    pub confirmations: u64,
+    /// Seconds to wait for the confirmations of a mined transaction, e.g. when a reorg dropped
+    /// it, before the submission fails.
+    #[serde(default = "default_confirm_timeout")]
+    pub confirm_timeout: u64,
+    /// Seconds to wait for the transaction to be mined before replacing it with a higher gas
+    /// price.
+    #[serde(default = "default_resubmit_after")]
+    pub resubmit_after: u64,
+    /// The percentage the gas price is raised by for every replacement. Nodes only accept
+    /// replacements paying at least 10% more.
+    #[serde(default = "default_gas_bump_percent")]
+    pub gas_bump_percent: u64,
+    /// The max gas price in wei. The gas price is not raised above it, and no transaction is
+    /// sent while the gas price of the node is above it.
+    pub max_gas_price: u128,
+    /// The max number of transactions sent for a proof, including the replacements.
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// The gas limit of the `proveBlock` transactions.
+    #[serde(default = "default_gas_limit")]
+    pub gas_limit: u64,
+    /// The signer of the transactions. Defaults to the private key in [RELAYER_PRIV_KEY].
+    #[serde(default)]
+    pub signer: Option<SignerConfig>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+/// The state of the submission of a proof.
+pub enum SubmissionStatus {
+    /// The transaction is being sent or waits to be mined.
+    Pending,
+    /// The transaction was mined and has enough confirmations.
+    Confirmed,
+    /// The transaction reverted or could not be mined.
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// The submission of a proof to L1.
+pub struct Submission {
+    pub status: SubmissionStatus,
+    /// The hash of the last transaction sent.
+    #[schema(value_type = Option<String>)]
+    pub tx_hash: Option<B256>,
+    /// The account the transaction is sent from.
+    #[schema(value_type = String)]
+    pub from: Address,
+    /// The nonce of the transaction, kept for its replacements.
+    pub nonce: Option<u64>,
+    /// The gas price of the last transaction sent, in wei.
+    #[schema(value_type = Option<String>)]
+    pub gas_price: Option<U256>,
+    /// The number of transactions sent, including the replacements.
+    pub attempts: u32,
+    /// The L1 block the transaction was mined in.
+    pub block_number: Option<u64>,
+    /// The gas used by the mined transaction.
+    pub gas_used: Option<u128>,
+    /// The error message, set once the submission failed.
+    pub error: Option<String>,
+}
+
+/// Submits proofs to L1 from a single account.
+#[derive(Clone, Debug)]
+pub struct Relayer {
+    config: RelayerConfig,
+    signer: RaikoSigner,
+    /// The next nonce per L1 node and account.
+    nonces: Arc<Mutex<HashMap<(String, Address), u64>>>,
+}
+
+impl Relayer {
+    /// Create a relayer submitting from the account of the configured signer.
+    pub async fn new(config: RelayerConfig) -> HostResult<Self> {
+        let signer_config = config
+            .signer
+            .clone()
+            .unwrap_or_else(|| SignerConfig::private_key(RELAYER_PRIV_KEY));
+        let signer = RaikoSigner::new(&signer_config)
+            .await
+            .map_err(|e| HostError::InvalidRequestConfig(format!("Invalid relayer signer: {e}")))?;
+        info!("Submitting proofs from {}", signer.address());
+        Ok(Self {
+            config,
+            signer,
+            nonces: Default::default(),
+        })
+    }
+
+    /// Whether proofs of the given type are submitted.
+    pub fn submits(&self, proof_type: &ProofType) -> bool {
+        self.config.tiers.contains_key(&proof_type.to_string())
+    }
+
+    /// Submit the proof of the block to the TaikoL1 contract of its chain. `on_update` is
+    /// called whenever the submission changes, e.g. when a replacement is sent.
+    pub async fn submit(
+        &self,
+        request: &ProofRequest,
+        input: &GuestInput,
+        proof: &Proof,
+        on_update: impl Fn(&Submission),
+    ) -> Submission {
+        let mut submission = Submission {
+            status: SubmissionStatus::Pending,
+            tx_hash: None,
+            from: self.signer.address(),
+            nonce: None,
+            gas_price: None,
+            attempts: 0,
+            block_number: None,
+            gas_used: None,
+            error: None,
+        };
+        on_update(&submission);
+        if let Err(e) = self
+            .send(request, input, proof, &mut submission, &on_update)
+            .await
+        {
+            warn!(
+                "Could not submit the proof of block {} on {}: {e}",
+                request.block_number, request.network
+            );
+            submission.status = SubmissionStatus::Failed;
+            submission.error = Some(e.to_string());
+        }
+        on_update(&submission);
+        submission
+    }
+
+    async fn send(
+        &self,
+        request: &ProofRequest,
+        input: &GuestInput,
+        proof: &Proof,
+        submission: &mut Submission,
+        on_update: &impl Fn(&Submission),
+    ) -> anyhow::Result<()> {
+        let tier = self.config.tiers[&request.proof_type.to_string()];
+        let chain = get_chain(&request.network)?;
+        let l1_chain = get_chain(&request.l1_network)?;
+        let contract_address = chain
+            .chain_spec
+            .l1_contract
+            .ok_or_else(|| anyhow::anyhow!("No TaikoL1 contract on {}", chain.name))?;
+        let l1_rpc = &self.config.l1_rpc;
+
+        let prove_input = prove_block_input(request, input, proof, tier).await?;
+
+        let provider = ProviderBuilder::new()
+            .signer(self.signer.ethereum_signer(l1_chain.chain_spec.chain_id))
+            .with_recommended_layers()
+            .provider(RootProvider::new(RpcClient::new(
+                Http::new(Url::parse(l1_rpc)?),
+                false,
+            )));
+        let contract = TaikoL1::new(contract_address, &provider);
+        let from = self.signer.address();
+        let gas_limit = U256::from(self.config.gas_limit);
+
+        // Don't spend gas on proofs the contract rejects, e.g. when the block is proven already
+        contract
+            .proveBlock(input.block_number, prove_input.clone())
+            .from(from)
+            .call()
+            .await
+            .map_err(|e| anyhow::anyhow!("proveBlock reverts: {e}"))?;
+
+        let gas_price = provider.get_gas_price().await?;
+        if gas_price > U256::from(self.config.max_gas_price) {
+            anyhow::bail!(
+                "The gas price {gas_price} is above the max gas price {}",
+                self.config.max_gas_price
+            );
+        }
+        let transaction = Transaction {
+            contract_address,
+            block_id: input.block_number,
+            prove_input,
+            from,
+            gas_limit,
+        };
+        let result = self
+            .send_transaction(
+                &provider,
+                l1_rpc,
+                &transaction,
+                gas_price,
+                submission,
+                on_update,
+            )
+            .await;
+        // The nonce handed out may be unused or taken by now, the next submission reads it from
+        // the node again.
+        if result.is_err() {
+            self.reset_nonce(l1_rpc, from).await;
+        }
+        result
+    }
+
+    /// Send the transaction, replacing it with a higher gas price until it's mined, and wait
+    /// for its confirmations.
+    async fn send_transaction<P: Provider<Http<reqwest_alloy::Client>>>(
+        &self,
+        provider: &P,
+        l1_rpc: &str,
+        transaction: &Transaction,
+        mut gas_price: U256,
+        submission: &mut Submission,
+        on_update: &impl Fn(&Submission),
+    ) -> anyhow::Result<()> {
+        let Transaction {
+            contract_address,
+            block_id,
+            prove_input,
+            from,
+            gas_limit,
+        } = transaction;
+        let (from, gas_limit) = (*from, *gas_limit);
+        let contract = TaikoL1::new(*contract_address, provider);
+        let mut nonce = self.reserve_nonce(provider, l1_rpc, from).await?;
+        let mut hashes = Vec::new();
+        submission.nonce = Some(nonce);
+        while submission.attempts < self.config.max_attempts {
+            submission.attempts += 1;
+            submission.gas_price = Some(gas_price);
+            let sent = contract
+                .proveBlock(*block_id, prove_input.clone())
+                .from(from)
+                .nonce(nonce)
+                .gas_price(gas_price)
+                .gas(gas_limit)
+                .send()
+                .await;
+            match sent {
+                Ok(pending) => {
+                    let tx_hash = *pending.tx_hash();
+                    info!("Sent proof of block {block_id} in {tx_hash} with nonce {nonce}");
+                    hashes.push(tx_hash);
+                    submission.tx_hash = Some(tx_hash);
+                }
+                Err(e) if is_nonce_error(&e.to_string()) => {
+                    // Another transaction of the account took the nonce, or a nonce handed out
+                    // was never used, unless one of ours was mined in the meantime.
+                    if self.find_receipt(provider, &hashes, submission).await? {
+                        break;
+                    }
+                    self.reset_nonce(l1_rpc, from).await;
+                    nonce = self.reserve_nonce(provider, l1_rpc, from).await?;
+                    hashes.clear();
+                    submission.nonce = Some(nonce);
+                    continue;
+                }
+                // The previous transaction is still in the mempool, wait for it
+                Err(e) if e.to_string().contains("underpriced") && !hashes.is_empty() => {
+                    warn!("Replacement of {:?} was rejected: {e}", submission.tx_hash);
+                }
+                Err(e) => return Err(e.into()),
+            }
+            on_update(submission);
+
+            let deadline = Instant::now() + Duration::from_secs(self.config.resubmit_after);
+            while Instant::now() < deadline {
+                if self.find_receipt(provider, &hashes, submission).await? {
+                    return self.confirm(provider, submission, on_update).await;
+                }
+                tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+            }
+            gas_price = self.bump_gas_price(gas_price);
+        }
+        if submission.block_number.is_some() {
+            return self.confirm(provider, submission, on_update).await;
+        }
+        anyhow::bail!(
+            "Transaction was not mined after {} attempts",
+            submission.attempts
+        )
+    }
+
+    /// Look for a receipt of any of the transactions sent for the submission. Fails if the
+    /// transaction reverted.
+    async fn find_receipt<P: Provider<Http<reqwest_alloy::Client>>>(
+        &self,
+        provider: &P,
+        hashes: &[B256],
+        submission: &mut Submission,
+    ) -> anyhow::Result<bool> {
+        for hash in hashes {
+            if let Some(receipt) = provider.get_transaction_receipt(*hash).await? {
+                submission.tx_hash = Some(*hash);
+                submission.block_number = receipt.block_number;
+                submission.gas_used = Some(receipt.gas_used);
+                if !receipt.status() {
+                    anyhow::bail!("Transaction {hash} reverted");
+                }
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Wait until the mined transaction has enough confirmations. Fails when a reorg dropped
+    /// the transaction or it isn't confirmed within the confirmation timeout.
+    async fn confirm<P: Provider<Http<reqwest_alloy::Client>>>(
+        &self,
+        provider: &P,
+        submission: &mut Submission,
+        on_update: &impl Fn(&Submission),
+    ) -> anyhow::Result<()> {
+        on_update(submission);
+        let tx_hash = submission.tx_hash.unwrap_or_default();
+        let deadline = Instant::now() + Duration::from_secs(self.config.confirm_timeout);
+        loop {
+            // A reorg can drop the transaction or mine it in another block
+            let Some(receipt) = provider.get_transaction_receipt(tx_hash).await? else {
+                anyhow::bail!("Transaction {tx_hash} was dropped by a reorg");
+            };
+            submission.block_number = receipt.block_number;
+            let mined = receipt.block_number.unwrap_or_default();
+            if provider.get_block_number().await? + 1 >= mined + self.config.confirmations {
+                break;
+            }
+            if Instant::now() >= deadline {
+                anyhow::bail!(
+                    "Transaction {tx_hash} was not confirmed within {} seconds",
+                    self.config.confirm_timeout
+                );
+            }
+            tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+        }
+        info!(
+            "Proof transaction {tx_hash} confirmed in block {:?}",
+            submission.block_number
+        );
+        submission.status = SubmissionStatus::Confirmed;
+        Ok(())
+    }
+
+    fn bump_gas_price(&self, gas_price: U256) -> U256 {
+        let bumped = gas_price * U256::from(100 + self.config.gas_bump_percent) / U256::from(100);
+        bumped.min(U256::from(self.config.max_gas_price))
+    }
+
+    /// Hand out the next nonce of the account on the node, which is at least the number of
+    /// transactions of the account the node knows of, including the ones in its mempool.
+    async fn reserve_nonce<P: Provider<Http<reqwest_alloy::Client>>>(
+        &self,
+        provider: &P,
+        l1_rpc: &str,
+        from: Address,
+    ) -> anyhow::Result<u64> {
+        let mut nonces = self.nonces.lock().await;
+        let pending = provider
+            .get_transaction_count(from, Some(BlockNumberOrTag::Pending.into()))
+            .await?
+            .to::<u64>();
+        let key = (l1_rpc.to_string(), from);
+        let nonce = nonces.get(&key).copied().unwrap_or_default().max(pending);
+        nonces.insert(key, nonce + 1);
+        Ok(nonce)
+    }
+
+    /// Forget the nonces handed out for the account, so the next one is read from the node
+    /// again.
+    async fn reset_nonce(&self, l1_rpc: &str, from: Address) {
+        self.nonces.lock().await.remove(&(l1_rpc.to_string(), from));
+    }
+}
+
+/// The `proveBlock` transaction of a submission, sent again with every replacement.
+struct Transaction {
+    contract_address: Address,
+    block_id: u64,
+    prove_input: Bytes,
+    from: Address,
+    gas_limit: U256,
+}
+
+/// Whether the node rejected the transaction for its nonce, e.g. `nonce too low` when another
+/// transaction of the account took it or `nonce too high` after a gap. Other errors keep the
+/// nonces handed out.
+fn is_nonce_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    error.contains("nonce too low") || error.contains("nonce too high")
+}
+
+/// Encode the input of `proveBlock`, the block metadata, the transition and the tier proof.
+async fn prove_block_input(
+    request: &ProofRequest,
+    input: &GuestInput,
+    proof: &Proof,
+    tier: u16,
+) -> anyhow::Result<Bytes> {
+    // The state root is only known from the block itself
+    let provider =
+        ProviderBuilder::new().provider(RootProvider::new_http(Url::parse(&request.rpc)?));
+    let block = provider
+        .get_block_by_number(input.block_number.into(), false)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("No data for block {}", input.block_number))?;
+    if block.header.hash != Some(input.block_hash) {
+        anyhow::bail!("Block {} was reorged", input.block_number);
+    }
+
+    let transition = Transition {
+        parentHash: block.header.parent_hash,
+        blockHash: input.block_hash,
+        stateRoot: block.header.state_root,
+        graffiti: request.graffiti,
+    };
+    let call = ProveBlockCall::from_proof(
+        input.block_number,
+        input.taiko.block_proposed.meta.clone(),
+        transition,
+        tier,
+        proof,
+    )?;
+    Ok(call.input())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_signer_wallet::LocalWallet;
+
+    use super::*;
+
+    #[test]
+    fn test_bump_gas_price() {
+        let config: RelayerConfig = serde_json::from_value(serde_json::json!({
+            "tiers": {"sgx": 200},
+            "l1_rpc": "http://localhost:8545",
+            "max_gas_price": 30,
+        }))
+        .unwrap();
+        let relayer = Relayer {
+            config,
+            signer: RaikoSigner::Local(LocalWallet::random()),
+            nonces: Default::default(),
+        };
+        assert_eq!(relayer.bump_gas_price(U256::from(10)), U256::from(12));
+        assert_eq!(relayer.bump_gas_price(U256::from(28)), U256::from(30));
+        assert!(relayer.submits(&ProofType::Sgx));
+        assert!(!relayer.submits(&ProofType::Native));
+    }
+
+    #[test]
+    fn test_is_nonce_error() {
+        assert!(is_nonce_error(
+            "server returned an error response: nonce too low"
+        ));
+        assert!(is_nonce_error("Nonce too high"));
+        assert!(!is_nonce_error("replacement transaction underpriced"));
+        assert!(!is_nonce_error(
+            "insufficient funds for gas * price + value"
+        ));
+    }
+}
@@ -0,0 +1,348 @@
+//! Detection of the reorgs of the blocks being proven.
+//!
+//! A job proves the block its input was prepared for. Once that block is reorged out of its
+//! chain, or the L1 block its anchor transaction refers to is, the proof is worthless and the
+//! hours left proving it are wasted. With `reorg_monitor` in the config file, e.g.
+//! `{"interval": 12, "webhook_url": "https://ops.example.com/reorgs"}`, the blocks of the jobs
+//! with a prepared input are checked against the nodes every `interval` seconds. When the hash
+//! of a block changed:
+//!
+//! - its jobs are cancelled with the reorg as their error, which sends their callbacks,
+//! - the cached input of the block is removed, see [crate::input_cache], when the node of the
+//!   chain config sees the reorg as well. The input cache is shared by all requests, so the
+//!   node of a request can't evict it,
+//! - the reorg is counted in the `reorg_count` metric and the cancelled jobs in
+//!   `reorg_cancelled_jobs`,
+//! - and a [ReorgEvent] is posted to the `webhook_url`, signed like the callbacks.
+//!
+//! Cached inputs and proofs of other blocks are never used for a reorged block, as they are
+//! looked up by the hash of the block.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    time::Duration,
+};
+
+use alloy_consensus::Sealable;
+use alloy_primitives::B256;
+use alloy_provider::{Provider, ProviderBuilder, RootProvider};
+use anyhow::{anyhow, Result};
+use futures::future::join_all;
+use raiko_lib::input::GuestInput;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::{
+    callback,
+    chain_registry::get_chain,
+    clock::unix_timestamp,
+    error::HostError,
+    input_cache::InputCache,
+    jobs::{JobId, JobManager},
+    metrics::{inc_reorg_cancelled_jobs, inc_reorg_count},
+    request::ProofRequest,
+};
+
+fn default_interval() -> u64 {
+    12
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// How often the blocks of the jobs are checked for reorgs, and who to tell about them.
+pub struct ReorgConfig {
+    /// Seconds between the checks.
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    /// The URL a [ReorgEvent] is posted to for every reorg.
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// The chain a reorged block is on.
+pub enum Layer {
+    /// The L1 block the anchor transaction of the proven block refers to.
+    L1,
+    /// The proven block itself.
+    L2,
+}
+
+impl std::fmt::Display for Layer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Layer::L1 => "l1",
+            Layer::L2 => "l2",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A block a job proves, with the L1 block its anchor transaction refers to.
+pub struct ProvenBlock {
+    pub network: String,
+    pub rpc: String,
+    pub l1_rpc: String,
+    pub block_number: u64,
+    pub block_hash: B256,
+    /// The number and hash of the anchor block, unset for blocks without an anchor.
+    pub l1_block: Option<(u64, B256)>,
+}
+
+impl ProvenBlock {
+    pub fn new(request: &ProofRequest, input: &GuestInput) -> Self {
+        let l1_block = input.network.is_taiko().then(|| {
+            let l1_header = &input.taiko.l1_header;
+            (l1_header.number, l1_header.hash())
+        });
+        Self {
+            network: request.network.clone(),
+            rpc: request.rpc.clone(),
+            l1_rpc: request.l1_rpc.clone(),
+            block_number: input.block_number,
+            block_hash: input.block_hash,
+            l1_block,
+        }
+    }
+
+    /// The node, number and hash of the blocks to check, the block itself and its anchor block.
+    fn blocks(&self) -> impl Iterator<Item = (Layer, &str, u64, B256)> {
+        let l2 = (
+            Layer::L2,
+            self.rpc.as_str(),
+            self.block_number,
+            self.block_hash,
+        );
+        let l1 = self
+            .l1_block
+            .map(|(number, hash)| (Layer::L1, self.l1_rpc.as_str(), number, hash));
+        std::iter::once(l2).chain(l1)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// The body of the webhook of a reorg of blocks being proven.
+pub struct ReorgEvent {
+    /// The network of the proven blocks.
+    pub network: String,
+    pub layer: Layer,
+    /// The number of the reorged block.
+    pub block_number: u64,
+    /// The hash of the block the jobs were proving.
+    pub old_hash: B256,
+    /// The hash of the block at the number now.
+    pub new_hash: B256,
+    /// The jobs cancelled for the reorg.
+    pub cancelled_jobs: Vec<JobId>,
+    /// Unix timestamp of the detection.
+    pub detected_at: u64,
+}
+
+/// Check the blocks of the jobs for reorgs every `interval` seconds, in the background.
+pub fn spawn(config: ReorgConfig, jobs: JobManager, input_cache: InputCache) {
+    if config.interval == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(config.interval));
+        loop {
+            interval.tick().await;
+            check(&config, &jobs, &input_cache).await;
+        }
+    });
+}
+
+/// Check the blocks of the jobs against the nodes and cancel the jobs of the reorged ones.
+async fn check(config: &ReorgConfig, jobs: &JobManager, input_cache: &InputCache) {
+    let proven = jobs.proven_blocks();
+    if proven.is_empty() {
+        return;
+    }
+    // The blocks are checked on the nodes of the requests, and on the nodes of the chains for
+    // the input cache.
+    let blocks: HashSet<(String, u64)> = proven
+        .iter()
+        .flat_map(|(_, block)| {
+            block.blocks().flat_map(|(layer, rpc, number, _)| {
+                [Some(rpc.to_string()), chain_node(&block.network, layer)]
+                    .into_iter()
+                    .flatten()
+                    .map(move |rpc| (rpc, number))
+            })
+        })
+        .collect();
+    let hashes: HashMap<(String, u64), B256> =
+        join_all(blocks.into_iter().map(|(rpc, number)| async move {
+            let hash = block_hash(&rpc, number).await;
+            ((rpc, number), hash)
+        }))
+        .await
+        .into_iter()
+        .filter_map(|(block, hash)| match hash {
+            Ok(hash) => Some((block, hash)),
+            Err(e) => {
+                // Checked again on the next round.
+                debug!(
+                    "Could not get the hash of block {} from {}: {e}",
+                    block.1, block.0
+                );
+                None
+            }
+        })
+        .collect();
+
+    for mut event in find_reorgs(&proven, &hashes) {
+        warn!(
+            "Reorg of {} block {} on {}, the block of jobs {:?} changed from {} to {}",
+            event.layer,
+            event.block_number,
+            event.network,
+            event.cancelled_jobs,
+            event.old_hash,
+            event.new_hash
+        );
+        inc_reorg_count(&event.network, &event.layer.to_string());
+        let reason = format!(
+            "Cancelled for the reorg of {} block {} ({})",
+            event.layer, event.block_number, event.old_hash
+        );
+        let on_chain_node = chain_node(&event.network, event.layer)
+            .and_then(|rpc| hashes.get(&(rpc, event.block_number)))
+            .is_some_and(|hash| *hash != event.old_hash);
+        event.cancelled_jobs.retain(|id| {
+            match jobs.invalidate(*id, reason.clone()) {
+                Ok(summary) => {
+                    info!("Cancelled job {} for the reorg", summary.id);
+                    if let Some((_, block)) = proven.iter().find(|(job, _)| job == id) {
+                        if on_chain_node {
+                            input_cache.remove(
+                                &block.network,
+                                block.block_number,
+                                &block.block_hash,
+                            );
+                        }
+                    }
+                    if let Ok(job) = jobs.get(*id) {
+                        inc_reorg_cancelled_jobs(&job.request.proof_type);
+                    }
+                    true
+                }
+                // Finished before it could be cancelled.
+                Err(HostError::JobAlreadyFinished(_)) => false,
+                Err(e) => {
+                    warn!("Could not cancel job {id} for the reorg: {e}");
+                    false
+                }
+            }
+        });
+        if let Some(url) = config.webhook_url.clone() {
+            tokio::spawn(async move {
+                if let Err(e) = callback::post(&url, &event, "reorg event").await {
+                    warn!("Could not send the reorg event to {url}: {e}");
+                }
+            });
+        }
+    }
+}
+
+/// The node of the chain config for the blocks of the layer.
+fn chain_node(network: &str, layer: Layer) -> Option<String> {
+    let chain = get_chain(network).ok()?;
+    match layer {
+        Layer::L1 => chain.l1_rpc,
+        Layer::L2 => chain.rpc,
+    }
+}
+
+/// The reorgs of the proven blocks, by the hashes of the blocks at their numbers now. Blocks
+/// without a known hash, e.g. a node that doesn't have the block (yet), are skipped.
+fn find_reorgs(
+    proven: &[(JobId, ProvenBlock)],
+    hashes: &HashMap<(String, u64), B256>,
+) -> Vec<ReorgEvent> {
+    let detected_at = unix_timestamp();
+    let mut reorgs: BTreeMap<(String, Layer, u64, B256), ReorgEvent> = BTreeMap::new();
+    for (id, block) in proven {
+        for (layer, rpc, number, hash) in block.blocks() {
+            let Some(new_hash) = hashes.get(&(rpc.to_string(), number)) else {
+                continue;
+            };
+            if *new_hash == hash {
+                continue;
+            }
+            reorgs
+                .entry((block.network.clone(), layer, number, hash))
+                .or_insert_with(|| ReorgEvent {
+                    network: block.network.clone(),
+                    layer,
+                    block_number: number,
+                    old_hash: hash,
+                    new_hash: *new_hash,
+                    cancelled_jobs: Vec::new(),
+                    detected_at,
+                })
+                .cancelled_jobs
+                .push(*id);
+        }
+    }
+    reorgs.into_values().collect()
+}
+
+/// The hash of the block at the number. A node without a block at the number, e.g. one
+/// lagging behind or load balanced with one, doesn't tell whether the block was reorged, so
+/// it is an error like a failed request.
+async fn block_hash(rpc: &str, number: u64) -> Result<B256> {
+    let provider =
+        ProviderBuilder::new().provider(RootProvider::new_http(reqwest::Url::parse(rpc)?));
+    provider
+        .get_block_by_number(number.into(), false)
+        .await?
+        .ok_or_else(|| anyhow!("No block {number}"))?
+        .header
+        .hash
+        .ok_or_else(|| anyhow!("No hash for block {number}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_reorgs() {
+        let hash = |n: u8| B256::repeat_byte(n);
+        let block = |block_number: u64, block_hash: B256| ProvenBlock {
+            network: "taiko_a7".to_string(),
+            rpc: "http://l2".to_string(),
+            l1_rpc: "http://l1".to_string(),
+            block_number,
+            block_hash,
+            l1_block: Some((500, hash(50))),
+        };
+        // Two jobs proving block 10 with different proof types, one proving block 11.
+        let proven = vec![
+            (1, block(10, hash(10))),
+            (2, block(10, hash(10))),
+            (3, block(11, hash(11))),
+        ];
+        let hashes = HashMap::from([
+            (("http://l2".to_string(), 10), hash(20)),
+            (("http://l1".to_string(), 500), hash(50)),
+        ]);
+        let reorgs = find_reorgs(&proven, &hashes);
+        assert_eq!(reorgs.len(), 1);
+        assert_eq!(reorgs[0].layer, Layer::L2);
+        assert_eq!(reorgs[0].block_number, 10);
+        assert_eq!(reorgs[0].new_hash, hash(20));
+        assert_eq!(reorgs[0].cancelled_jobs, vec![1, 2]);
+
+        // The anchor block changed, which affects all the jobs.
+        let hashes = HashMap::from([(("http://l1".to_string(), 500), hash(51))]);
+        let reorgs = find_reorgs(&proven, &hashes);
+        assert_eq!(reorgs.len(), 1);
+        assert_eq!(reorgs[0].layer, Layer::L1);
+        assert_eq!(reorgs[0].cancelled_jobs, vec![1, 2, 3]);
+
+        // Blocks the node returned no hash for are unknown, not reorged.
+        assert!(find_reorgs(&proven, &HashMap::new()).is_empty());
+    }
+}
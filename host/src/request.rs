@@ -1,13 +1,16 @@
 use core::fmt::Debug;
-use std::{collections::HashMap, path::Path, str::FromStr};
+use std::{collections::HashMap, path::Path, str::FromStr, sync::Arc};
 
 use alloy_primitives::{Address, B256};
-use clap::{Args, ValueEnum};
+use alloy_provider::{Provider, ProviderBuilder, RootProvider};
+use clap::Args;
+use raiko_client::{ErrorCode, Problem};
+#[cfg(any(feature = "risc0", feature = "sp1"))]
+use raiko_lib::aggregate::Aggregator;
 use raiko_lib::{
-    consts::Network,
     input::{GuestInput, GuestOutput},
     protocol_instance::ProtocolInstance,
-    prover::{Proof, Prover},
+    prover::{ProgressReporter, Proof, ProverBackend},
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -15,14 +18,16 @@ use serde_with::{serde_as, DisplayFromStr};
 use utoipa::ToSchema;
 
 use crate::{
+    callback,
+    chain_registry::get_chain,
+    clock::unix_timestamp,
+    composite::SGX_AND_ZK,
     error::{HostError, HostResult},
-    execution::NativeProver,
-    merge,
+    fixtures, merge,
+    registry::get_prover,
 };
 
-#[derive(
-    PartialEq, Eq, PartialOrd, Ord, Clone, Debug, Deserialize, Serialize, ToSchema, Hash, ValueEnum,
-)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug, ToSchema, Hash)]
 /// Available proof types.
 pub enum ProofType {
     /// # Native
@@ -41,6 +46,27 @@ pub enum ProofType {
     ///
     /// Uses the RISC0 prover to build the block.
     Risc0,
+    /// # Jolt
+    ///
+    /// Uses the Jolt prover to build the block.
+    Jolt,
+    /// # Plonky3
+    ///
+    /// Uses the experimental Plonky3 backend of powdr to build the block.
+    Plonky3,
+    /// # SgxAndZk
+    ///
+    /// Proves the block with the SGX prover and a ZK prover at once, for the dual tier, see
+    /// [crate::composite].
+    SgxAndZk,
+    /// # Mock
+    ///
+    /// Returns fake proofs after an artificial latency, for load testing.
+    Mock,
+    /// # Custom
+    ///
+    /// Uses a prover backend registered at runtime, see [crate::registry].
+    Custom(String),
 }
 
 impl std::fmt::Display for ProofType {
@@ -50,6 +76,11 @@ impl std::fmt::Display for ProofType {
             ProofType::Sp1 => "sp1",
             ProofType::Sgx => "sgx",
             ProofType::Risc0 => "risc0",
+            ProofType::Jolt => "jolt",
+            ProofType::Plonky3 => "plonky3",
+            ProofType::SgxAndZk => SGX_AND_ZK,
+            ProofType::Mock => "mock",
+            ProofType::Custom(name) => name.as_str(),
         })
     }
 }
@@ -63,35 +94,64 @@ impl FromStr for ProofType {
             "sp1" => Ok(ProofType::Sp1),
             "sgx" => Ok(ProofType::Sgx),
             "risc0" => Ok(ProofType::Risc0),
+            "jolt" => Ok(ProofType::Jolt),
+            "plonky3" => Ok(ProofType::Plonky3),
+            SGX_AND_ZK => Ok(ProofType::SgxAndZk),
+            "mock" => Ok(ProofType::Mock),
+            name if get_prover(name).is_some() => Ok(ProofType::Custom(name.to_string())),
             _ => Err(HostError::InvalidProofType(s.to_string())),
         }
     }
 }
 
+impl Serialize for ProofType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ProofType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
 impl ProofType {
+    /// Get the registered prover backend for the proof type.
+    fn backend(&self) -> HostResult<Arc<dyn ProverBackend>> {
+        get_prover(&self.to_string())
+            .ok_or_else(|| HostError::FeatureNotSupportedError(self.clone()))
+    }
+
     /// Get the instance hash for the protocol instance depending on the proof type.
     pub fn instance_hash(&self, pi: ProtocolInstance) -> HostResult<B256> {
-        match self {
-            ProofType::Native => Ok(NativeProver::instance_hash(pi)),
-            ProofType::Sp1 => {
-                #[cfg(feature = "sp1")]
-                return Ok(sp1_prover::Sp1Prover::instance_hash(pi));
+        Ok(self.backend()?.instance_hash(pi))
+    }
 
-                Err(HostError::FeatureNotSupportedError(self.clone()))
-            }
-            ProofType::Risc0 => {
-                #[cfg(feature = "risc0")]
-                return Ok(risc0_prover::Risc0Prover::instance_hash(pi));
+    /// Get the id of the guest program of the prover, if it has one.
+    pub fn program_id(&self) -> HostResult<Option<B256>> {
+        Ok(self.backend()?.program_id())
+    }
 
-                Err(HostError::FeatureNotSupportedError(self.clone()))
-            }
-            ProofType::Sgx => {
-                #[cfg(feature = "sgx")]
-                return Ok(sgx_prover::SgxProver::instance_hash(pi));
+    /// Set up the prover ahead of its first proof.
+    pub fn warm_up(&self) -> HostResult<()> {
+        Ok(self.backend()?.warm_up()?)
+    }
 
-                Err(HostError::FeatureNotSupportedError(self.clone()))
-            }
-        }
+    /// Verify a proof of the input locally depending on the proof type. Returns `None` if the
+    /// prover can't verify its proofs, and the reason if the proof doesn't verify.
+    pub fn verify(
+        &self,
+        proof: &Proof,
+        input: &GuestInput,
+        output: &GuestOutput,
+    ) -> HostResult<Option<Result<(), String>>> {
+        Ok(self
+            .backend()?
+            .verify(proof, input, output)
+            .map(|result| result.map_err(|e| e.to_string())))
     }
 
     /// Run the prover driver depending on the proof type.
@@ -100,35 +160,46 @@ impl ProofType {
         input: GuestInput,
         output: GuestOutput,
         config: &Value,
+        progress: &ProgressReporter,
     ) -> HostResult<Proof> {
-        match self {
-            ProofType::Native => NativeProver::run(input, output, config)
-                .await
-                .map_err(|e| e.into()),
-            ProofType::Sp1 => {
-                #[cfg(feature = "sp1")]
-                return sp1_prover::Sp1Prover::run(input, output, config)
-                    .await
-                    .map_err(|e| e.into());
+        self.backend()?
+            .run(input, output, config, progress)
+            .await
+            .map_err(|e| e.into())
+    }
 
-                Err(HostError::FeatureNotSupportedError(self.clone()))
-            }
+    /// Whether the prover of the proof type can aggregate block proofs, see [Self::aggregate].
+    pub fn aggregates(&self) -> bool {
+        matches!(self, ProofType::Risc0 | ProofType::Sp1)
+    }
+
+    /// Aggregate the given block proofs into a single proof depending on the proof type.
+    #[cfg_attr(not(any(feature = "risc0", feature = "sp1")), allow(unused_variables))]
+    pub async fn aggregate(&self, proofs: Vec<Proof>, config: &Value) -> HostResult<Proof> {
+        match self {
             ProofType::Risc0 => {
                 #[cfg(feature = "risc0")]
-                return risc0_prover::Risc0Prover::run(input, output, config)
+                return risc0_prover::Risc0Prover::aggregate(proofs, config)
                     .await
                     .map_err(|e| e.into());
 
                 Err(HostError::FeatureNotSupportedError(self.clone()))
             }
-            ProofType::Sgx => {
-                #[cfg(feature = "sgx")]
-                return sgx_prover::SgxProver::run(input, output, config)
+            ProofType::Sp1 => {
+                #[cfg(feature = "sp1")]
+                return sp1_prover::Sp1Prover::aggregate(proofs, config)
                     .await
                     .map_err(|e| e.into());
 
                 Err(HostError::FeatureNotSupportedError(self.clone()))
             }
+            ProofType::Native
+            | ProofType::Sgx
+            | ProofType::Jolt
+            | ProofType::Plonky3
+            | ProofType::SgxAndZk
+            | ProofType::Mock
+            | ProofType::Custom(_) => Err(HostError::FeatureNotSupportedError(self.clone())),
         }
     }
 }
@@ -146,8 +217,8 @@ pub struct ProofRequest {
     pub l1_rpc: String,
     /// The beacon node URL for retrieving data blobs.
     pub beacon_rpc: String,
-    /// The network to generate the proof for.
-    pub network: Network,
+    /// The name of the chain to generate the proof for, see [crate::chain_registry].
+    pub network: String,
     /// The L1 network to grnerate the proof for.
     pub l1_network: String,
     /// Graffiti.
@@ -157,11 +228,168 @@ pub struct ProofRequest {
     pub prover: Address,
     /// The proof type.
     pub proof_type: ProofType,
+    /// Whether to wrap the proof into a SNARK that can be verified on-chain.
+    #[serde(default)]
+    pub compress: bool,
+    /// The scheduling priority. Requests with a higher priority are proven first when the
+    /// provers are busy.
+    #[serde(default)]
+    pub priority: u32,
+    /// The unix timestamp the proving window of the block ends at. Requests close to their
+    /// deadline are proven before all others.
+    #[serde(default)]
+    pub deadline: Option<u64>,
+    /// Generate a new proof even if a proof for the block is in the proof cache.
+    #[serde(default)]
+    pub force: bool,
+    /// Prove the on-chain block while executing it natively, and fail as soon as the native
+    /// execution doesn't reproduce it instead of after proving.
+    #[serde(default)]
+    pub cross_check: bool,
+    /// Have the guest report its cycles per phase, its peak memory and the trie nodes it
+    /// visited, added to the stats of the proof.
+    #[serde(default)]
+    pub profile_guest: bool,
+    /// The URL the result of the job is posted to once it finished, see [crate::callback].
+    #[serde(default)]
+    pub callback_url: Option<String>,
+    /// The name of the guest version to prove with, see [crate::guests]. By default the version
+    /// of the block height is selected.
+    #[serde(default)]
+    pub guest: Option<String>,
     #[serde(flatten)]
     /// Additional prover params.
     pub prover_args: HashMap<String, Value>,
 }
 
+impl ProofRequest {
+    /// The request without its prover params, which can hold secrets like the API key of a
+    /// proving network, to describe the request in responses.
+    pub fn redacted(&self) -> Self {
+        Self {
+            prover_args: HashMap::new(),
+            ..self.clone()
+        }
+    }
+
+    /// Check that the block is on the chain and, with `max_block_age`, at most that many blocks
+    /// behind the head, so requests for blocks that cannot be proven are rejected right away.
+    /// Failing to reach the node is a [ErrorCode::BackendUnavailable] error the client can
+    /// retry.
+    pub async fn check_block(&self, max_block_age: Option<u64>) -> HostResult<()> {
+        // The head of the chain is not in the fixtures.
+        if fixtures::is_playback() {
+            return Ok(());
+        }
+        let url = reqwest::Url::parse(&self.rpc)
+            .map_err(|e| HostError::invalid(ErrorCode::InvalidRequest, "rpc", e.to_string()))?;
+        let head = ProviderBuilder::new()
+            .provider(RootProvider::new_http(url))
+            .get_block_number()
+            .await
+            .map_err(|e| {
+                HostError::Validation(Problem::new(
+                    ErrorCode::BackendUnavailable,
+                    format!("Could not get the head of {}: {e}", self.network),
+                ))
+            })?;
+        check_block_number(self.block_number, head, max_block_age)
+    }
+}
+
+fn check_block_number(block_number: u64, head: u64, max_block_age: Option<u64>) -> HostResult<()> {
+    if block_number > head {
+        return Err(HostError::invalid(
+            ErrorCode::BlockTooNew,
+            "block_number",
+            format!("Block {block_number} is after the head of the chain {head}"),
+        ));
+    }
+    match max_block_age {
+        Some(max_block_age) if head - block_number > max_block_age => Err(HostError::invalid(
+            ErrorCode::BlockTooOld,
+            "block_number",
+            format!(
+                "Block {block_number} is more than {max_block_age} blocks behind the head {head}"
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// A request to aggregate block proofs.
+pub struct AggregationRequest {
+    /// The block proofs to aggregate, in block order.
+    #[schema(value_type = Vec<Value>)]
+    pub proofs: Vec<Proof>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// A request to verify a proof produced before.
+pub struct ProofVerificationRequest {
+    /// The proof, as returned for the proof request.
+    #[schema(value_type = Value)]
+    pub proof: Proof,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, ToSchema)]
+/// An inclusive range of blocks to generate proofs for.
+pub struct BlockRange {
+    /// The first block of the range.
+    pub from_block: u64,
+    /// The last block of the range.
+    pub to_block: u64,
+    /// Aggregate the proofs of the blocks into a single proof, see `/v2/aggregate`.
+    #[serde(default)]
+    pub aggregate: bool,
+}
+
+impl BlockRange {
+    /// The number of blocks in the range.
+    pub fn len(&self) -> u64 {
+        self.to_block
+            .checked_sub(self.from_block)
+            .map_or(0, |blocks| blocks.saturating_add(1))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// The proof types to prove a single block with, e.g. for tiers that need an SGX and a ZK
+/// proof of the same block.
+pub struct ProofTypes {
+    /// The proof types, the block is proven with all of them in parallel.
+    #[schema(value_type = Vec<String>)]
+    pub proof_types: Vec<ProofType>,
+}
+
+impl ProofTypes {
+    /// Check that there is at least one proof type and that none is repeated.
+    pub fn check(&self) -> HostResult<()> {
+        if self.proof_types.is_empty() {
+            return Err(HostError::invalid(
+                ErrorCode::InvalidRequest,
+                "proof_types",
+                "At least one proof type is required",
+            ));
+        }
+        for (i, proof_type) in self.proof_types.iter().enumerate() {
+            if self.proof_types[..i].contains(proof_type) {
+                return Err(HostError::invalid(
+                    ErrorCode::InvalidRequest,
+                    "proof_types",
+                    format!("The proof type {proof_type} is repeated"),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Default, Clone, Serialize, Deserialize, Debug, ToSchema, Args)]
 #[serde(default)]
 /// A partial proof request config.
@@ -180,7 +408,7 @@ pub struct ProofRequestOpt {
     /// The beacon node URL for retrieving data blobs.
     pub beacon_rpc: Option<String>,
     #[arg(long, require_equals = true)]
-    /// The network to generate the proof for.
+    /// The name of the chain to generate the proof for.
     pub network: Option<String>,
     #[arg(long, require_equals = true)]
     /// The L1 network to generate the proof for.
@@ -194,6 +422,35 @@ pub struct ProofRequestOpt {
     #[arg(long, require_equals = true)]
     /// The proof type.
     pub proof_type: Option<String>,
+    #[arg(long, require_equals = true)]
+    /// Whether to wrap the proof into a SNARK that can be verified on-chain.
+    pub compress: Option<bool>,
+    #[arg(long, require_equals = true)]
+    /// The scheduling priority. Requests with a higher priority are proven first when the
+    /// provers are busy.
+    pub priority: Option<u32>,
+    #[arg(long, require_equals = true)]
+    /// The unix timestamp the proving window of the block ends at. Requests close to their
+    /// deadline are proven before all others. Clamped to the proving window of the proof type
+    /// on the chain, see [crate::chain_registry::ChainConfig::proving_windows].
+    pub deadline: Option<u64>,
+    #[arg(long, require_equals = true)]
+    /// Generate a new proof even if a proof for the block is in the proof cache.
+    pub force: Option<bool>,
+    #[arg(long, require_equals = true)]
+    /// Prove the on-chain block while executing it natively, and fail as soon as the native
+    /// execution doesn't reproduce it instead of after proving.
+    pub cross_check: Option<bool>,
+    #[arg(long, require_equals = true)]
+    /// Have the guest report its cycles per phase, its peak memory and the trie nodes it
+    /// visited, added to the stats of the proof.
+    pub profile_guest: Option<bool>,
+    #[arg(long, require_equals = true)]
+    /// The URL the result of the job is posted to once it finished.
+    pub callback_url: Option<String>,
+    #[arg(long, require_equals = true)]
+    /// The name of the guest version to prove with.
+    pub guest: Option<String>,
     #[command(flatten)]
     /// Any additional prover params in JSON format.
     pub prover_args: ProverSpecificOpts,
@@ -205,6 +462,9 @@ pub struct ProverSpecificOpts {
     pub sgx: Option<Value>,
     pub sp1: Option<Value>,
     pub risc0: Option<Value>,
+    pub jolt: Option<Value>,
+    pub plonky3: Option<Value>,
+    pub sgx_and_zk: Option<Value>,
 }
 
 impl From<ProverSpecificOpts> for HashMap<String, Value> {
@@ -215,6 +475,9 @@ impl From<ProverSpecificOpts> for HashMap<String, Value> {
                 ("sgx", value.sgx.clone()),
                 ("sp1", value.sp1.clone()),
                 ("risc0", value.risc0.clone()),
+                ("jolt", value.jolt.clone()),
+                ("plonky3", value.plonky3.clone()),
+                (SGX_AND_ZK, value.sgx_and_zk.clone()),
             ]
             .into_iter()
             .filter_map(|(name, value)| value.map(|v| (name.to_string(), v))),
@@ -238,60 +501,262 @@ impl ProofRequestOpt {
     pub fn merge(&mut self, other: &Value) -> Result<(), HostError> {
         let mut this = serde_json::to_value(&self)?;
         merge(&mut this, other);
-        *self = serde_json::from_value(this)?;
+        *self = serde_json::from_value(this).map_err(|e| {
+            HostError::Validation(Problem::new(ErrorCode::MalformedBody, e.to_string()))
+        })?;
         Ok(())
     }
+
+    /// Check that the request only has the fields of a proof request and the `extra` fields of
+    /// the endpoint, so misspelled fields are not silently ignored.
+    pub fn check_fields(request: &Value, extra: &[&str]) -> Result<(), HostError> {
+        let Value::Object(request) = request else {
+            return Err(HostError::Validation(Problem::new(
+                ErrorCode::MalformedBody,
+                "The proof request has to be a JSON object",
+            )));
+        };
+        let known = serde_json::to_value(Self::default())?;
+        let unknown = request
+            .keys()
+            .find(|field| known.get(field).is_none() && !extra.contains(&field.as_str()));
+        match unknown {
+            Some(field) => Err(HostError::invalid(
+                ErrorCode::UnknownField,
+                field,
+                format!("Unknown field: {field}"),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The error of a required field set neither in the request nor in the config.
+fn missing(field: &str) -> HostError {
+    HostError::invalid(ErrorCode::MissingField, field, format!("Missing {field}"))
 }
 
 impl TryFrom<ProofRequestOpt> for ProofRequest {
     type Error = HostError;
 
     fn try_from(value: ProofRequestOpt) -> Result<Self, Self::Error> {
+        // The endpoints of the chain are used unless the request sets its own.
+        let chain = get_chain(&value.network.ok_or_else(|| missing("network"))?)?;
+        let proof_type: ProofType = value
+            .proof_type
+            .ok_or_else(|| missing("proof_type"))?
+            .parse()?;
+        // The block was proposed by now, so its proving window ends before the window from
+        // now. The jobs clamp it to the window from the proposal once the input is fetched.
+        let deadline =
+            chain.clamp_deadline(&proof_type.to_string(), value.deadline, unix_timestamp());
         Ok(Self {
-            block_number: value.block_number.ok_or(HostError::InvalidRequestConfig(
-                "Missing block number".to_string(),
-            ))?,
-            rpc: value
-                .rpc
-                .ok_or(HostError::InvalidRequestConfig("Missing rpc".to_string()))?,
-            l1_rpc: value.l1_rpc.ok_or(HostError::InvalidRequestConfig(
-                "Missing l1_rpc".to_string(),
-            ))?,
-            beacon_rpc: value.beacon_rpc.ok_or(HostError::InvalidRequestConfig(
-                "Missing beacon_rpc".to_string(),
-            ))?,
-            network: value
-                .network
-                .ok_or(HostError::InvalidRequestConfig(
-                    "Missing network".to_string(),
-                ))?
-                .parse()
-                .map_err(|_| HostError::InvalidRequestConfig("Invalid network".to_string()))?,
-            l1_network: value.l1_network.ok_or(HostError::InvalidRequestConfig(
-                "Missing l1_network".to_string(),
-            ))?,
+            block_number: value.block_number.ok_or_else(|| missing("block_number"))?,
+            rpc: value.rpc.or(chain.rpc).ok_or_else(|| missing("rpc"))?,
+            l1_rpc: value
+                .l1_rpc
+                .or(chain.l1_rpc)
+                .ok_or_else(|| missing("l1_rpc"))?,
+            beacon_rpc: value
+                .beacon_rpc
+                .or(chain.beacon_rpc)
+                .ok_or_else(|| missing("beacon_rpc"))?,
+            network: chain.name,
+            l1_network: value
+                .l1_network
+                .or(chain.l1_network)
+                .ok_or_else(|| missing("l1_network"))?,
             graffiti: value
                 .graffiti
-                .ok_or(HostError::InvalidRequestConfig(
-                    "Missing graffiti".to_string(),
-                ))?
+                .ok_or_else(|| missing("graffiti"))?
                 .parse()
-                .map_err(|_| HostError::InvalidRequestConfig("Invalid graffiti".to_string()))?,
+                .map_err(|e| {
+                    HostError::invalid(
+                        ErrorCode::InvalidHex,
+                        "graffiti",
+                        format!("Invalid graffiti, expected 32 hex encoded bytes: {e}"),
+                    )
+                })?,
             prover: value
                 .prover
-                .ok_or(HostError::InvalidRequestConfig(
-                    "Missing prover".to_string(),
-                ))?
-                .parse()
-                .map_err(|_| HostError::InvalidRequestConfig("Invalid prover".to_string()))?,
-            proof_type: value
-                .proof_type
-                .ok_or(HostError::InvalidRequestConfig(
-                    "Missing proof_type".to_string(),
-                ))?
+                .ok_or_else(|| missing("prover"))?
                 .parse()
-                .map_err(|_| HostError::InvalidRequestConfig("Invalid proof_type".to_string()))?,
+                .map_err(|e| {
+                    HostError::invalid(
+                        ErrorCode::InvalidHex,
+                        "prover",
+                        format!("Invalid prover, expected a hex encoded address: {e}"),
+                    )
+                })?,
+            proof_type,
+            compress: value.compress.unwrap_or_default(),
+            priority: value.priority.unwrap_or_default(),
+            deadline,
+            force: value.force.unwrap_or_default(),
+            cross_check: value.cross_check.unwrap_or_default(),
+            profile_guest: value.profile_guest.unwrap_or_default(),
+            callback_url: value
+                .callback_url
+                .map(|url| {
+                    callback::validate_url(&url).map(|()| url).map_err(|e| {
+                        HostError::invalid(ErrorCode::InvalidRequest, "callback_url", e)
+                    })
+                })
+                .transpose()?,
+            guest: value.guest,
             prover_args: value.prover_args.into(),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_block_range_len() {
+        let range = |from_block, to_block| BlockRange {
+            from_block,
+            to_block,
+            aggregate: false,
+        };
+        assert_eq!(range(10, 10).len(), 1);
+        assert_eq!(range(10, 19).len(), 10);
+        assert!(range(11, 10).is_empty());
+        assert_eq!(range(u64::MAX - 1, u64::MAX).len(), 2);
+        assert_eq!(range(0, u64::MAX).len(), u64::MAX);
+    }
+
+    #[test]
+    fn test_proof_types() {
+        let proof_types: ProofTypes =
+            serde_json::from_value(json!({"proof_types": ["sgx", "risc0"]})).unwrap();
+        assert_eq!(
+            proof_types.proof_types,
+            vec![ProofType::Sgx, ProofType::Risc0]
+        );
+        proof_types.check().unwrap();
+        let repeated: ProofTypes =
+            serde_json::from_value(json!({"proof_types": ["sgx", "sgx"]})).unwrap();
+        assert_eq!(
+            repeated.check().unwrap_err().problem().field.as_deref(),
+            Some("proof_types")
+        );
+        assert!(ProofTypes {
+            proof_types: vec![]
+        }
+        .check()
+        .is_err());
+    }
+
+    #[test]
+    fn test_redacted() {
+        let request: ProofRequest = serde_json::from_value(json!({
+            "block_number": 10,
+            "network": "taiko_a7",
+            "l1_network": "holesky",
+            "rpc": "http://localhost:8545",
+            "l1_rpc": "http://localhost:8546",
+            "beacon_rpc": "http://localhost:5052",
+            "graffiti": B256::ZERO,
+            "prover": "0x70997970C51812dc3A010C7d01b50e0d17dc79C8",
+            "proof_type": "sp1",
+            "sp1": {"network": {"api_key": "secret"}},
+        }))
+        .unwrap();
+        assert!(request.prover_args.contains_key("sp1"));
+        let redacted = serde_json::to_string(&request.redacted()).unwrap();
+        assert!(!redacted.contains("secret"));
+        assert!(redacted.contains("taiko_a7"));
+    }
+
+    #[test]
+    fn test_check_fields() {
+        let request = json!({"block_number": 10, "proof_type": "sgx", "prover_args": {}});
+        ProofRequestOpt::check_fields(&request, &[]).unwrap();
+        let err = ProofRequestOpt::check_fields(&json!({"block_numbr": 10}), &[]).unwrap_err();
+        assert_eq!(err.problem().code, ErrorCode::UnknownField);
+        assert_eq!(err.problem().field.as_deref(), Some("block_numbr"));
+        ProofRequestOpt::check_fields(&json!({"from_block": 10}), &["from_block"]).unwrap();
+        assert!(ProofRequestOpt::check_fields(&json!([]), &[]).is_err());
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let mut config = ProofRequestOpt::default();
+        config
+            .merge(&json!({
+                "network": "taiko_a7",
+                "block_number": 10,
+                "l1_network": "holesky",
+                "rpc": "http://localhost:8545",
+                "l1_rpc": "http://localhost:8546",
+                "beacon_rpc": "http://localhost:5052",
+                "graffiti": "0x1234",
+                "prover": "0x70997970C51812dc3A010C7d01b50e0d17dc79C8",
+                "proof_type": "native",
+            }))
+            .unwrap();
+        let problem = ProofRequest::try_from(config.clone())
+            .unwrap_err()
+            .problem();
+        assert_eq!(problem.code, ErrorCode::InvalidHex);
+        assert_eq!(problem.field.as_deref(), Some("graffiti"));
+
+        config.proof_type = Some("groth16".to_string());
+        config.graffiti = Some(B256::ZERO.to_string());
+        let problem = ProofRequest::try_from(config.clone())
+            .unwrap_err()
+            .problem();
+        assert_eq!(problem.code, ErrorCode::UnsupportedProofType);
+
+        config.network = None;
+        let problem = ProofRequest::try_from(config.clone())
+            .unwrap_err()
+            .problem();
+        assert_eq!(problem.code, ErrorCode::MissingField);
+        assert_eq!(problem.field.as_deref(), Some("network"));
+
+        let problem = config
+            .merge(&json!({"block_number": "latest"}))
+            .unwrap_err()
+            .problem();
+        assert_eq!(problem.code, ErrorCode::MalformedBody);
+        assert_eq!(problem.status, 400);
+    }
+
+    #[test]
+    fn test_check_block_number() {
+        let code = |block_number, max_block_age| {
+            check_block_number(block_number, 100, max_block_age)
+                .err()
+                .map(|e| e.problem().code)
+        };
+        assert_eq!(code(100, None), None);
+        assert_eq!(code(101, None), Some(ErrorCode::BlockTooNew));
+        assert_eq!(code(0, None), None);
+        assert_eq!(code(50, Some(50)), None);
+        assert_eq!(code(49, Some(50)), Some(ErrorCode::BlockTooOld));
+    }
+
+    #[tokio::test]
+    async fn test_check_block_unreachable_node() {
+        let request: ProofRequest = serde_json::from_value(json!({
+            "block_number": 10,
+            "rpc": "http://127.0.0.1:1",
+            "l1_rpc": "http://127.0.0.1:1",
+            "beacon_rpc": "http://127.0.0.1:1",
+            "network": "taiko_a7",
+            "l1_network": "holesky",
+            "graffiti": B256::ZERO,
+            "prover": Address::ZERO,
+            "proof_type": "native",
+        }))
+        .unwrap();
+        let problem = request.check_block(None).await.unwrap_err().problem();
+        assert_eq!(problem.code, ErrorCode::BackendUnavailable);
+        assert_eq!(problem.status, 503);
+    }
+}
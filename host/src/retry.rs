@@ -0,0 +1,204 @@
+//! Retries for proof requests failing with transient errors.
+//!
+//! A failing RPC node during the preflight or a prover running out of memory shouldn't fail
+//! the whole request. The [RetryConfig] holds a default [RetryPolicy], which can be overridden
+//! per proof type, e.g. to retry the prover errors of SGX only.
+
+use std::{collections::BTreeMap, future::Future, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    error::{HostError, HostResult},
+    metrics::inc_retry_count,
+    request::ProofType,
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+/// How often and when to retry a failed proof request.
+pub struct RetryPolicy {
+    /// The max number of attempts, including the first one. `1` disables retries.
+    pub max_attempts: u32,
+    /// The delay before the first retry in seconds.
+    pub initial_backoff: u64,
+    /// The max delay between two attempts in seconds.
+    pub max_backoff: u64,
+    /// The factor the delay grows by after every retry.
+    pub multiplier: u32,
+    /// Whether to also retry errors returned by the prover, e.g. when it ran out of memory.
+    /// Provers mostly fail deterministically, so these are not retried by default.
+    pub retry_prover_errors: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: 5,
+            max_backoff: 300,
+            multiplier: 2,
+            retry_prover_errors: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+/// The retry policies of the host.
+pub struct RetryConfig {
+    #[serde(flatten)]
+    /// The policy used for proof types without their own policy.
+    pub default: RetryPolicy,
+    #[serde(default)]
+    /// The policies of specific proof types, by proof type name.
+    pub provers: BTreeMap<String, RetryPolicy>,
+}
+
+impl RetryConfig {
+    /// Get the retry policy of the given proof type.
+    pub fn policy(&self, proof_type: &ProofType) -> &RetryPolicy {
+        self.provers
+            .get(&proof_type.to_string())
+            .unwrap_or(&self.default)
+    }
+}
+
+impl RetryPolicy {
+    /// The delay before the given retry, starting at `1` for the first retry.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = self.multiplier.saturating_pow(retry.saturating_sub(1));
+        Duration::from_secs(
+            self.initial_backoff
+                .saturating_mul(factor as u64)
+                .min(self.max_backoff),
+        )
+    }
+
+    /// Whether the error may go away when trying again.
+    pub fn is_retryable(&self, error: &HostError) -> bool {
+        match error {
+            // Mostly failing RPC requests, which panic the preflight task.
+            HostError::Io(_) | HostError::Anyhow(_) => true,
+            HostError::JoinHandle(e) => !e.is_cancelled(),
+            HostError::GuestError(_) => self.retry_prover_errors,
+            // Crashes are retried in a new process, but not over the memory limit again.
+            HostError::ProverCrashed(crash) => !crash.out_of_memory,
+            HostError::InvalidProofType(_)
+            | HostError::InvalidRequestConfig(_)
+            | HostError::InvalidAddress(_)
+            | HostError::InvalidQuote(_)
+            | HostError::Validation(_)
+            | HostError::JobNotFound(_)
+            | HostError::JobAlreadyFinished(_)
+            | HostError::BatchNotFound(_)
+            | HostError::BatchAlreadyFinished(_)
+            | HostError::Unauthorized(_)
+            | HostError::Forbidden(_)
+            | HostError::TooManyRequests(_)
+            | HostError::ShuttingDown
+            | HostError::QueueFull(_)
+            | HostError::CircuitOpen(..)
+            | HostError::ResourceExhausted(_)
+            | HostError::Serde(_)
+            | HostError::FeatureNotSupportedError(_) => false,
+        }
+    }
+
+    /// Run `f` until it succeeds, fails with an error that is not retryable or all attempts
+    /// are used up. `on_retry` is called with the number of the retry before it is started.
+    pub async fn run<T, Fut>(
+        &self,
+        proof_type: &ProofType,
+        mut on_retry: impl FnMut(u32),
+        mut f: impl FnMut() -> Fut,
+    ) -> HostResult<T>
+    where
+        Fut: Future<Output = HostResult<T>>,
+    {
+        let mut retry = 0;
+        loop {
+            match f().await {
+                Err(e) if retry + 1 < self.max_attempts && self.is_retryable(&e) => {
+                    retry += 1;
+                    let backoff = self.backoff(retry);
+                    warn!(
+                        "{proof_type} proof failed, retry {retry}/{} in {backoff:?}: {e}",
+                        self.max_attempts - 1
+                    );
+                    inc_retry_count(proof_type);
+                    on_retry(retry);
+                    tokio::time::sleep(backoff).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use raiko_lib::prover::ProverError;
+
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            initial_backoff: 5,
+            max_backoff: 30,
+            multiplier: 2,
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_secs(5));
+        assert_eq!(policy.backoff(2), Duration::from_secs(10));
+        assert_eq!(policy.backoff(3), Duration::from_secs(20));
+        assert_eq!(policy.backoff(4), Duration::from_secs(30));
+        assert_eq!(policy.backoff(100), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_config() {
+        let config: RetryConfig = serde_json::from_value(serde_json::json!({
+            "max_attempts": 3,
+            "provers": { "sgx": { "max_attempts": 5, "retry_prover_errors": true } }
+        }))
+        .unwrap();
+        assert_eq!(config.policy(&ProofType::Native).max_attempts, 3);
+        let sgx = config.policy(&ProofType::Sgx);
+        assert_eq!(sgx.max_attempts, 5);
+        assert_eq!(sgx.initial_backoff, 5);
+        assert!(
+            sgx.is_retryable(&HostError::GuestError(ProverError::GuestError(
+                "out of memory".to_string()
+            )))
+        );
+        assert!(!config
+            .policy(&ProofType::Native)
+            .is_retryable(&HostError::InvalidAddress("0x".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_run_retries() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: 0,
+            ..Default::default()
+        };
+        let mut attempts = 0;
+        let mut retries = Vec::new();
+        let result: HostResult<()> = policy
+            .run(
+                &ProofType::Native,
+                |retry| retries.push(retry),
+                || {
+                    attempts += 1;
+                    async { Err(HostError::Anyhow(anyhow::anyhow!("RPC error"))) }
+                },
+            )
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+        assert_eq!(retries, vec![1, 2]);
+    }
+}
@@ -0,0 +1,311 @@
+//! The settings that can be changed while the host runs, through `/admin/config`.
+//!
+//! Only settings that are safe to change with jobs in flight are included: the concurrency
+//! limits, the max queue depth, the default node endpoints of the proof requests, the limits
+//! of the input cache and the log level. Changes apply to the requests and proofs started
+//! afterwards and last until the host restarts or the config file is reloaded, see
+//! [crate::config_reload]. Every change is appended to the journal file, with the trace id of
+//! the request and the settings before and after it.
+
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use raiko_client::{ErrorCode, Problem};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::info;
+use tracing_subscriber::EnvFilter;
+use utoipa::ToSchema;
+
+use crate::{
+    clock::unix_timestamp,
+    error::{HostError, HostResult},
+    input_cache::InputCache,
+    jobs::JobManager,
+    request::ProofRequestOpt,
+    trace::{current_trace_id, set_log_level},
+    Cli,
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+/// The settings that can be changed at runtime.
+pub struct RuntimeConfig {
+    /// The max number of proofs generated at the same time.
+    pub concurrency_limit: usize,
+    /// The max number of proofs generated at the same time per prover.
+    pub prover_concurrency: BTreeMap<String, usize>,
+    /// The max number of queued jobs before proof requests are rejected.
+    pub max_queue_depth: Option<usize>,
+    /// The default L2 node of the proof requests.
+    pub rpc: Option<String>,
+    /// The default L1 node of the proof requests.
+    pub l1_rpc: Option<String>,
+    /// The default beacon node of the proof requests.
+    pub beacon_rpc: Option<String>,
+    /// The max size of the input cache in MB.
+    pub cache_max_size: Option<u64>,
+    /// The max age of the cached inputs in hours.
+    pub cache_max_age: Option<u64>,
+    /// The log level, e.g. `debug` or `info,raiko_host=trace` like `RUST_LOG`.
+    pub log_level: String,
+}
+
+impl RuntimeConfig {
+    pub fn new(opts: &Cli) -> Self {
+        Self {
+            concurrency_limit: opts.concurrency_limit,
+            prover_concurrency: opts.prover_concurrency.clone(),
+            max_queue_depth: opts.max_queue_depth,
+            rpc: opts.proof_request_opt.rpc.clone(),
+            l1_rpc: opts.proof_request_opt.l1_rpc.clone(),
+            beacon_rpc: opts.proof_request_opt.beacon_rpc.clone(),
+            cache_max_size: opts.cache_max_size,
+            cache_max_age: opts.cache_max_age,
+            log_level: opts.log_level.clone(),
+        }
+    }
+
+    /// The settings with the JSON merge patch (RFC 7386) applied: the fields of the patch
+    /// replace the current ones and `null` unsets them.
+    pub fn patch(&self, patch: &Value) -> HostResult<Self> {
+        let Value::Object(changes) = patch else {
+            return Err(HostError::Validation(Problem::new(
+                ErrorCode::MalformedBody,
+                "The patch has to be a JSON object",
+            )));
+        };
+        let mut config = serde_json::to_value(self)?;
+        if let Some(field) = changes.keys().find(|field| config.get(field).is_none()) {
+            return Err(HostError::invalid(
+                ErrorCode::UnknownField,
+                field,
+                format!("Unknown field or not changeable at runtime: {field}"),
+            ));
+        }
+        merge_patch(&mut config, patch);
+        let config: Self = serde_json::from_value(config).map_err(|e| {
+            HostError::Validation(Problem::new(
+                ErrorCode::MalformedBody,
+                format!("Invalid config: {e}"),
+            ))
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> HostResult<()> {
+        if self.concurrency_limit == 0 {
+            return Err(HostError::invalid(
+                ErrorCode::InvalidRequest,
+                "concurrency_limit",
+                "The concurrency limit has to be at least 1",
+            ));
+        }
+        for (field, url) in [
+            ("rpc", &self.rpc),
+            ("l1_rpc", &self.l1_rpc),
+            ("beacon_rpc", &self.beacon_rpc),
+        ] {
+            if let Some(url) = url {
+                reqwest::Url::parse(url).map_err(|e| {
+                    HostError::invalid(
+                        ErrorCode::InvalidRequest,
+                        field,
+                        format!("Invalid URL: {e}"),
+                    )
+                })?;
+            }
+        }
+        EnvFilter::try_new(&self.log_level).map_err(|e| {
+            HostError::invalid(
+                ErrorCode::InvalidRequest,
+                "log_level",
+                format!("Invalid log level: {e}"),
+            )
+        })?;
+        Ok(())
+    }
+}
+
+/// Apply a JSON merge patch to `target`.
+fn merge_patch(target: &mut Value, patch: &Value) {
+    match (target, patch) {
+        (Value::Object(fields), Value::Object(changes)) => {
+            for (field, value) in changes {
+                if value.is_null() {
+                    fields.remove(field);
+                } else {
+                    merge_patch(fields.entry(field.clone()).or_insert(Value::Null), value);
+                }
+            }
+        }
+        (target, Value::Object(_)) => {
+            *target = json!({});
+            merge_patch(target, patch);
+        }
+        (target, patch) => *target = patch.clone(),
+    }
+}
+
+/// The current runtime settings, shared by the handlers of the host.
+#[derive(Clone, Debug)]
+pub struct RuntimeSettings {
+    config: Arc<Mutex<RuntimeConfig>>,
+    journal: Option<PathBuf>,
+}
+
+impl RuntimeSettings {
+    /// Start with the settings of the options, journaling the changes to `journal`.
+    pub fn new(opts: &Cli, journal: Option<PathBuf>) -> Self {
+        Self {
+            config: Arc::new(Mutex::new(RuntimeConfig::new(opts))),
+            journal,
+        }
+    }
+
+    /// The current settings.
+    pub fn get(&self) -> RuntimeConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// The default proof request config, with the current default nodes.
+    pub fn request_config(&self, base: &ProofRequestOpt) -> ProofRequestOpt {
+        let config = self.config.lock().unwrap();
+        ProofRequestOpt {
+            rpc: config.rpc.clone(),
+            l1_rpc: config.l1_rpc.clone(),
+            beacon_rpc: config.beacon_rpc.clone(),
+            ..base.clone()
+        }
+    }
+
+    /// Change the settings with a JSON merge patch and apply them. The change is journaled
+    /// before it is applied, so an invalid patch or a failed journal changes nothing.
+    pub fn update(
+        &self,
+        patch: &Value,
+        jobs: &JobManager,
+        input_cache: &InputCache,
+    ) -> HostResult<RuntimeConfig> {
+        let mut config = self.config.lock().unwrap();
+        let updated = config.patch(patch)?;
+        if updated == *config {
+            return Ok(updated);
+        }
+        self.journal(patch, &config, &updated)?;
+
+        if updated.log_level != config.log_level {
+            set_log_level(&updated.log_level)?;
+        }
+        jobs.set_concurrency(updated.concurrency_limit, &updated.prover_concurrency);
+        jobs.set_max_queue_depth(updated.max_queue_depth);
+        input_cache.set_limits(
+            updated.cache_max_size.map(|size| size * 1024 * 1024),
+            updated
+                .cache_max_age
+                .map(|hours| Duration::from_secs(hours * 60 * 60)),
+        );
+        info!("Changed the runtime config: {patch}");
+        *config = updated.clone();
+        Ok(updated)
+    }
+
+    /// Append the change to the journal as a line of JSON.
+    fn journal(
+        &self,
+        patch: &Value,
+        before: &RuntimeConfig,
+        after: &RuntimeConfig,
+    ) -> HostResult<()> {
+        let Some(path) = &self.journal else {
+            return Ok(());
+        };
+        let timestamp = unix_timestamp();
+        let entry = json!({
+            "timestamp": timestamp,
+            "trace_id": current_trace_id(),
+            "patch": patch,
+            "before": before,
+            "after": after,
+        });
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{entry}")?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts() -> Cli {
+        Cli {
+            concurrency_limit: 4,
+            log_level: "info".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_patch() {
+        let config = RuntimeConfig::new(&opts());
+        let patched = config
+            .patch(&json!({
+                "concurrency_limit": 8,
+                "prover_concurrency": {"sgx": 2},
+                "rpc": "http://localhost:8545",
+            }))
+            .unwrap();
+        assert_eq!(patched.concurrency_limit, 8);
+        assert_eq!(
+            patched.prover_concurrency,
+            BTreeMap::from([("sgx".to_string(), 2)])
+        );
+        assert_eq!(patched.rpc.as_deref(), Some("http://localhost:8545"));
+        assert_eq!(patched.log_level, "info");
+
+        // Null unsets a field.
+        let unset = patched
+            .patch(&json!({"rpc": null, "prover_concurrency": {"sgx": null}}))
+            .unwrap();
+        assert_eq!(unset.rpc, None);
+        assert!(unset.prover_concurrency.is_empty());
+
+        assert!(config.patch(&json!({"address": "0.0.0.0:1"})).is_err());
+        assert!(config.patch(&json!({"concurrency_limit": 0})).is_err());
+        assert!(config.patch(&json!({"concurrency_limit": "many"})).is_err());
+        assert!(config.patch(&json!({"rpc": "not a url"})).is_err());
+        assert!(config
+            .patch(&json!({"log_level": "info,[invalid"}))
+            .is_err());
+    }
+
+    #[test]
+    fn test_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let settings = RuntimeSettings::new(&opts(), Some(path.clone()));
+        let before = settings.get();
+        let after = before.patch(&json!({"cache_max_size": 100})).unwrap();
+        settings
+            .journal(&json!({"cache_max_size": 100}), &before, &after)
+            .unwrap();
+        settings.journal(&json!({}), &after, &after).unwrap();
+
+        let journal = std::fs::read_to_string(&path).unwrap();
+        let entries: Vec<Value> = journal
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["after"]["cache_max_size"], 100);
+        assert_eq!(entries[0]["before"]["cache_max_size"], Value::Null);
+    }
+}
@@ -0,0 +1,99 @@
+//! The tokio runtimes of the host.
+//!
+//! The API, the preflight and the jobs run on the main runtime, with `--worker-threads` threads
+//! and at most `--max-blocking-threads` threads for blocking work. Provers keep the threads
+//! they run on busy for minutes, Risc0 and SP1 even block them, so a host proving on all of its
+//! cores stops answering its API. With `--prover-threads` the provers run on a runtime of their
+//! own with that many threads, leaving the threads of the main runtime to the API.
+
+use std::{future::Future, sync::OnceLock};
+
+use tokio::{
+    runtime::{Builder, Runtime},
+    task::JoinHandle,
+};
+
+use crate::error::{HostError, HostResult};
+
+/// The name of the threads of the runtime of the provers.
+pub const PROVER_THREAD_NAME: &str = "raiko-prover";
+
+static PROVER_RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+/// Build the main runtime, with the default of tokio for the unset options.
+pub fn build(
+    worker_threads: Option<usize>,
+    max_blocking_threads: Option<usize>,
+) -> HostResult<Runtime> {
+    let mut builder = Builder::new_multi_thread();
+    builder.enable_all();
+    if let Some(threads) = worker_threads {
+        builder.worker_threads(check_threads("worker-threads", threads)?);
+    }
+    if let Some(threads) = max_blocking_threads {
+        builder.max_blocking_threads(check_threads("max-blocking-threads", threads)?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Run the provers on a runtime of their own with the given number of threads.
+pub fn configure(threads: usize) -> HostResult<()> {
+    let threads = check_threads("prover-threads", threads)?;
+    if PROVER_RUNTIME.get().is_some() {
+        return Ok(());
+    }
+    let runtime = Builder::new_multi_thread()
+        .worker_threads(threads)
+        .thread_name(PROVER_THREAD_NAME)
+        .enable_all()
+        .build()?;
+    if let Err(runtime) = PROVER_RUNTIME.set(runtime) {
+        // A runtime can't be dropped in the async context of the other one.
+        runtime.shutdown_background();
+    }
+    Ok(())
+}
+
+/// Check the thread count of the option, tokio panics on a runtime without threads. The
+/// arguments are checked when they are parsed, the config file isn't.
+fn check_threads(option: &str, threads: usize) -> HostResult<usize> {
+    if threads == 0 {
+        return Err(HostError::InvalidRequestConfig(format!(
+            "--{option} needs at least one thread"
+        )));
+    }
+    Ok(threads)
+}
+
+/// Spawn the prover on the runtime of the provers, or on the current one without it.
+pub fn spawn_prover<F>(prover: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    match PROVER_RUNTIME.get() {
+        Some(runtime) => runtime.spawn(prover),
+        None => tokio::spawn(prover),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_threads() {
+        assert!(build(Some(0), None).is_err());
+        assert!(build(None, Some(0)).is_err());
+        assert!(configure(0).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_spawn_prover() {
+        configure(1).unwrap();
+        let thread = spawn_prover(async { std::thread::current().name().map(String::from) })
+            .await
+            .unwrap();
+        assert_eq!(thread.as_deref(), Some(PROVER_THREAD_NAME));
+    }
+}
@@ -1,25 +1,84 @@
-use std::{net::SocketAddr, str::FromStr};
+use std::{net::SocketAddr, path::PathBuf, str::FromStr};
 
 use anyhow::Context;
+use axum::Router;
+use serde::{Deserialize, Serialize};
 use tokio::net::TcpListener;
-use tracing::debug;
+use tracing::{debug, error};
 
 use crate::{error::HostError, server::api::create_router, ProverState};
 
 pub mod api;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "tls")]
+pub mod tls;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// The TLS certificate of the server, e.g. `{"cert": "cert.pem", "key": "key.pem"}`.
+pub struct TlsConfig {
+    /// Path to the PEM encoded certificate chain.
+    pub cert: PathBuf,
+    /// Path to the PEM encoded private key.
+    pub key: PathBuf,
+    /// Path to the PEM encoded CA certificates of the clients. When set, only clients with a
+    /// certificate signed by one of them are served.
+    #[serde(default)]
+    pub client_ca: Option<PathBuf>,
+}
 
 /// Starts the proverd server.
 pub async fn serve(state: ProverState) -> anyhow::Result<()> {
     let addr = SocketAddr::from_str(&state.opts.address)
         .map_err(|_| HostError::InvalidAddress(state.opts.address.clone()))?;
-    let listener = TcpListener::bind(addr).await?;
 
+    if let Some(grpc_address) = &state.opts.grpc_address {
+        spawn_grpc(state.clone(), grpc_address)?;
+    }
+
+    let tls = state.opts.tls.clone();
+    let router = create_router(&state).with_state(state);
+    if let Some(tls) = tls {
+        return serve_tls(router, addr, &tls).await;
+    }
+
+    let listener = TcpListener::bind(addr).await?;
     debug!("Listening on: {}", listener.local_addr()?);
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .context("Server couldn't serve")?;
 
-    let router = create_router(state.opts.concurrency_limit).with_state(state);
-    axum::serve(listener, router)
-        .await
-        .context("Server couldn't serve")?;
+    Ok(())
+}
 
+#[cfg(feature = "grpc")]
+fn spawn_grpc(state: ProverState, address: &str) -> anyhow::Result<()> {
+    let addr = SocketAddr::from_str(address)
+        .map_err(|_| HostError::InvalidAddress(address.to_string()))?;
+    tokio::spawn(async move {
+        if let Err(e) = grpc::serve(state, addr).await {
+            error!("gRPC server stopped: {e}");
+        }
+    });
     Ok(())
 }
+
+#[cfg(not(feature = "grpc"))]
+fn spawn_grpc(_state: ProverState, _address: &str) -> anyhow::Result<()> {
+    error!("The gRPC API is not served, raiko was built without the grpc feature");
+    Ok(())
+}
+
+#[cfg(feature = "tls")]
+async fn serve_tls(router: Router, addr: SocketAddr, config: &TlsConfig) -> anyhow::Result<()> {
+    tls::serve(router, addr, config).await
+}
+
+#[cfg(not(feature = "tls"))]
+async fn serve_tls(_router: Router, _addr: SocketAddr, _config: &TlsConfig) -> anyhow::Result<()> {
+    // Never fall back to plain HTTP when TLS is configured.
+    anyhow::bail!("TLS is configured, but raiko was built without the tls feature")
+}
@@ -0,0 +1,315 @@
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde_json::Value;
+use utoipa::OpenApi;
+
+use crate::{
+    api_keys::{ApiKeyConfig, ApiKeyUsage},
+    costs::{self, CostReport, DailyCost, ProofCost},
+    error::HostResult,
+    faults::{self, FaultConfig},
+    gc::{self, GcReport},
+    runtime_config::RuntimeConfig,
+    workers::{self, WorkerInfo, WorkerRegistration},
+    ProverState,
+};
+
+#[utoipa::path(post, path = "/admin/keys",
+    tag = "Admin",
+    request_body = ApiKeyConfig,
+    responses (
+        (status = 200, description = "The added API key", body = ApiKeyConfig),
+        (status = 400, description = "A key with the same name already exists"),
+        (status = 403, description = "The request was not made with an admin key")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Add an API key.
+///
+/// The key is generated when the request has none and is only returned in this response.
+/// Added keys are not persisted and are gone after the host restarts.
+async fn add_key_handler(
+    State(ProverState { api_keys, .. }): State<ProverState>,
+    Json(config): Json<ApiKeyConfig>,
+) -> HostResult<Json<ApiKeyConfig>> {
+    Ok(Json(api_keys.add(config)?))
+}
+
+#[utoipa::path(delete, path = "/admin/keys/{name}",
+    tag = "Admin",
+    params(
+        ("name" = String, Path, description = "The name of the API key")
+    ),
+    responses (
+        (status = 200, description = "The API key was removed"),
+        (status = 400, description = "No API key with the given name exists"),
+        (status = 403, description = "The request was not made with an admin key")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Remove an API key.
+async fn remove_key_handler(
+    State(ProverState { api_keys, .. }): State<ProverState>,
+    Path(name): Path<String>,
+) -> HostResult<()> {
+    api_keys.remove(&name)
+}
+
+#[utoipa::path(get, path = "/admin/keys/usage",
+    tag = "Admin",
+    responses (
+        (status = 200, description = "The usage of the API keys", body = [ApiKeyUsage]),
+        (status = 403, description = "The request was not made with an admin key")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Get the usage of the API keys.
+///
+/// Returns the requests and proof requests of every key in the current minute and day
+/// against its rate limit and daily quota, and in total since the host started.
+async fn usage_handler(
+    State(ProverState { api_keys, .. }): State<ProverState>,
+) -> Json<Vec<ApiKeyUsage>> {
+    Json(api_keys.usage())
+}
+
+#[utoipa::path(get, path = "/admin/costs",
+    tag = "Admin",
+    responses (
+        (status = 200, description = "The cost of the generated proofs", body = CostReport),
+        (status = 403, description = "The request was not made with an admin key")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Get the cost of the generated proofs.
+///
+/// Returns the estimated cost of the proofs per day and proof type for the last 90 days, and
+/// of each of the last 1000 proofs, since the host started.
+async fn costs_handler() -> Json<CostReport> {
+    Json(costs::report())
+}
+
+#[utoipa::path(get, path = "/admin/config",
+    tag = "Admin",
+    responses (
+        (status = 200, description = "The current runtime config", body = RuntimeConfig),
+        (status = 403, description = "The request was not made with an admin key")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Get the runtime config.
+///
+/// Returns the settings that can be changed with `PATCH /admin/config` at their current value.
+async fn config_handler(
+    State(ProverState { runtime, .. }): State<ProverState>,
+) -> Json<RuntimeConfig> {
+    Json(runtime.get())
+}
+
+#[utoipa::path(patch, path = "/admin/config",
+    tag = "Admin",
+    request_body = Value,
+    responses (
+        (status = 200, description = "The changed runtime config", body = RuntimeConfig),
+        (status = 400, description = "The patch is invalid, nothing was changed"),
+        (status = 403, description = "The request was not made with an admin key")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Change the runtime config.
+///
+/// Takes a JSON merge patch of the runtime config, where `null` unsets a field, and applies
+/// it to the requests and proofs started afterwards. Every change is appended to the config
+/// journal. The changes are not persisted and are gone after the host restarts.
+async fn patch_config_handler(
+    State(ProverState {
+        runtime,
+        jobs,
+        input_cache,
+        ..
+    }): State<ProverState>,
+    Json(patch): Json<Value>,
+) -> HostResult<Json<RuntimeConfig>> {
+    Ok(Json(runtime.update(&patch, &jobs, &input_cache)?))
+}
+
+#[utoipa::path(post, path = "/admin/workers",
+    tag = "Admin",
+    request_body = WorkerRegistration,
+    responses (
+        (status = 200, description = "The registered worker", body = WorkerInfo),
+        (status = 400, description = "The registration is invalid"),
+        (status = 403, description = "The request was not made with an admin key")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Register a worker.
+///
+/// Called by the hosts started with `--coordinator`. The coordinator sends the proofs of the
+/// proof types of the worker to it, up to its capacity, until it misses its heartbeats.
+async fn register_worker_handler(
+    Json(registration): Json<WorkerRegistration>,
+) -> HostResult<Json<WorkerInfo>> {
+    Ok(Json(workers::register(registration)?))
+}
+
+#[utoipa::path(post, path = "/admin/workers/{id}/heartbeat",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "The id of the worker")
+    ),
+    responses (
+        (status = 200, description = "The worker stays registered"),
+        (status = 403, description = "The request was not made with an admin key"),
+        (status = 404, description = "The worker is not registered, it has to register again")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Send the heartbeat of a worker.
+async fn worker_heartbeat_handler(Path(id): Path<String>) -> HostResult<()> {
+    workers::heartbeat(&id)
+}
+
+#[utoipa::path(delete, path = "/admin/workers/{id}",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "The id of the worker")
+    ),
+    responses (
+        (status = 200, description = "The worker was removed"),
+        (status = 403, description = "The request was not made with an admin key"),
+        (status = 404, description = "The worker is not registered")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Remove a worker.
+///
+/// The proofs running on the worker fail and are retried elsewhere with a retry policy.
+async fn remove_worker_handler(Path(id): Path<String>) -> HostResult<()> {
+    workers::deregister(&id)
+}
+
+#[utoipa::path(get, path = "/admin/workers",
+    tag = "Admin",
+    responses (
+        (status = 200, description = "The registered workers", body = [WorkerInfo]),
+        (status = 403, description = "The request was not made with an admin key")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// List the registered workers.
+///
+/// Returns the workers with their proof types, their capacity and the proofs running on them.
+async fn workers_handler() -> Json<Vec<WorkerInfo>> {
+    Json(workers::list())
+}
+
+#[utoipa::path(get, path = "/admin/faults",
+    tag = "Admin",
+    responses (
+        (status = 200, description = "The injected faults", body = FaultConfig),
+        (status = 403, description = "The request was not made with an admin key"),
+        (status = 405, description = "The host is built without the faults feature")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Get the faults injected into the host.
+async fn faults_handler() -> HostResult<Json<FaultConfig>> {
+    Ok(Json(faults::get()?))
+}
+
+#[utoipa::path(put, path = "/admin/faults",
+    tag = "Admin",
+    request_body = FaultConfig,
+    responses (
+        (status = 200, description = "The injected faults", body = FaultConfig),
+        (status = 400, description = "The faults are invalid"),
+        (status = 403, description = "The request was not made with an admin key"),
+        (status = 405, description = "The host is built without the faults feature")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Set the faults injected into the host.
+///
+/// Replaces the faults set before, `{}` stops injecting faults. Only available on hosts built
+/// with the `faults` feature. The faults are not persisted and are gone after the host
+/// restarts.
+async fn set_faults_handler(Json(config): Json<FaultConfig>) -> HostResult<Json<FaultConfig>> {
+    Ok(Json(faults::set(config)?))
+}
+
+#[utoipa::path(post, path = "/admin/gc",
+    tag = "Admin",
+    responses (
+        (status = 200, description = "What the garbage collection removed", body = GcReport),
+        (status = 403, description = "The request was not made with an admin key")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Collect the garbage.
+///
+/// Prunes the artifacts and the input cache, removes the stale workspaces and files of the
+/// jobs and frees disk space down to the `gc` thresholds of the config file now, instead of
+/// waiting for the next garbage collection.
+async fn gc_handler(
+    State(ProverState { input_cache, .. }): State<ProverState>,
+) -> HostResult<Json<GcReport>> {
+    Ok(Json(gc::collect(&input_cache).await?))
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        add_key_handler,
+        remove_key_handler,
+        usage_handler,
+        costs_handler,
+        config_handler,
+        patch_config_handler,
+        register_worker_handler,
+        worker_heartbeat_handler,
+        remove_worker_handler,
+        workers_handler,
+        faults_handler,
+        set_faults_handler,
+        gc_handler
+    ),
+    components(schemas(
+        ApiKeyConfig,
+        ApiKeyUsage,
+        CostReport,
+        DailyCost,
+        FaultConfig,
+        GcReport,
+        ProofCost,
+        RuntimeConfig,
+        WorkerInfo,
+        WorkerRegistration
+    ))
+)]
+struct Docs;
+
+pub fn create_docs() -> utoipa::openapi::OpenApi {
+    Docs::openapi()
+}
+
+pub fn create_router() -> Router<ProverState> {
+    Router::new()
+        .route("/keys", post(add_key_handler))
+        .route("/keys/usage", get(usage_handler))
+        .route("/keys/:name", delete(remove_key_handler))
+        .route("/costs", get(costs_handler))
+        .route("/config", get(config_handler).patch(patch_config_handler))
+        .route(
+            "/workers",
+            get(workers_handler).post(register_worker_handler),
+        )
+        .route("/workers/:id", delete(remove_worker_handler))
+        .route("/workers/:id/heartbeat", post(worker_heartbeat_handler))
+        .route("/faults", get(faults_handler).put(set_faults_handler))
+        .route("/gc", post(gc_handler))
+}
@@ -0,0 +1,64 @@
+use axum::{
+    debug_handler,
+    extract::{rejection::JsonRejection, State},
+    routing::post,
+    Json, Router,
+};
+use raiko_client::Problem;
+use serde_json::Value;
+use utoipa::OpenApi;
+
+use crate::{
+    error::HostResult,
+    estimate::{estimate, BackendEstimate, ProofEstimate},
+    request::{ProofRequest, ProofRequestOpt},
+    ProverState,
+};
+
+#[utoipa::path(post, path = "/estimate",
+    tag = "Proving",
+    request_body = ProofRequestOpt,
+    responses (
+        (status = 200, description = "The estimated cycles, proving time and cost of the block per prover", body = ProofEstimate),
+        (status = 400, description = "The request is invalid", body = Problem, content_type = "application/problem+json")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Estimate the cost of proving a block.
+///
+/// Prepares the input of the block of the proof request and executes the block natively,
+/// without proving it. Returns the gas the block used and, for every prover of the calibration
+/// profile of the host, the estimated cycles, proving time and cost of the proof, locally and
+/// on the remote proving service of the prover. The input is cached for proving the block
+/// afterwards.
+async fn estimate_handler(
+    State(ProverState {
+        opts,
+        runtime,
+        input_cache,
+        ..
+    }): State<ProverState>,
+    req: Result<Json<Value>, JsonRejection>,
+) -> HostResult<Json<ProofEstimate>> {
+    let Json(req) = req?;
+    ProofRequestOpt::check_fields(&req, &[])?;
+    let mut config = runtime.request_config(&opts.proof_request_opt);
+    config.merge(&req)?;
+    let proof_request = ProofRequest::try_from(config)?;
+    Ok(Json(estimate(&proof_request, &input_cache).await?))
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(estimate_handler),
+    components(schemas(ProofEstimate, BackendEstimate))
+)]
+struct Docs;
+
+pub fn create_docs() -> utoipa::openapi::OpenApi {
+    Docs::openapi()
+}
+
+pub fn create_router() -> Router<ProverState> {
+    Router::new().route("/", post(estimate_handler))
+}
@@ -1,7 +1,18 @@
-use axum::{debug_handler, http::StatusCode, routing::get, Router};
+use axum::{
+    debug_handler,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde_json::json;
 use utoipa::OpenApi;
 
-use crate::ProverState;
+use crate::{
+    readiness::{self, CheckStatus, DependencyCheck, Readiness},
+    ProverState,
+};
 
 #[utoipa::path(
     get,
@@ -20,8 +31,64 @@ async fn health_handler() -> StatusCode {
     StatusCode::OK
 }
 
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "Health",
+    responses (
+        (status = 200, description = "The host is alive"),
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Liveness probe
+///
+/// Responds with an OK status as long as the host serves requests, without checking its
+/// dependencies, so a failing node does not get the host restarted.
+async fn healthz_handler() -> Json<serde_json::Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "Health",
+    responses (
+        (status = 200, description = "The host is ready to prove", body = Readiness),
+        (status = 503, description = "A dependency of the host failed its check", body = Readiness),
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Readiness probe
+///
+/// Checks the nodes of the default proof request and of the watchers have a healthy endpoint,
+/// the proof types they prove with are registered, the SGX enclave was bootstrapped and, with
+/// `--require-gpu`, a GPU is visible. Fails while the queue of the jobs is full and while the
+/// host drains its jobs for the shutdown.
+/// Returns the status of every dependency.
+async fn readyz_handler(
+    State(ProverState {
+        mut opts,
+        runtime,
+        jobs,
+        ..
+    }): State<ProverState>,
+) -> Response {
+    // Check the nodes changed through `/admin/config`.
+    opts.proof_request_opt = runtime.request_config(&opts.proof_request_opt);
+    let readiness = readiness::check(&opts, &jobs).await;
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness)).into_response()
+}
+
 #[derive(OpenApi)]
-#[openapi(paths(health_handler))]
+#[openapi(
+    paths(health_handler, healthz_handler, readyz_handler),
+    components(schemas(Readiness, DependencyCheck, CheckStatus))
+)]
 struct Docs;
 
 pub fn create_docs() -> utoipa::openapi::OpenApi {
@@ -31,3 +98,10 @@ pub fn create_docs() -> utoipa::openapi::OpenApi {
 pub fn create_router() -> Router<ProverState> {
     Router::new().route("/", get(health_handler))
 }
+
+/// The Kubernetes probes, served at the root.
+pub fn create_probes_router() -> Router<ProverState> {
+    Router::new()
+        .route("/healthz", get(healthz_handler))
+        .route("/readyz", get(readyz_handler))
+}
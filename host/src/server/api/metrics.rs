@@ -1,8 +1,14 @@
-use axum::{body::Body, debug_handler, http::header, response::Response, routing::get, Router};
+use axum::{
+    body::Body, debug_handler, http::header, response::Response, routing::get, Json, Router,
+};
 use prometheus::{Encoder, TextEncoder};
 use utoipa::OpenApi;
 
-use crate::{error::HostResult, ProverState};
+use crate::{
+    error::HostResult,
+    metrics::{recent_requests, RecentRequest},
+    ProverState,
+};
 
 #[utoipa::path(
     get,
@@ -25,6 +31,8 @@ use crate::{error::HostResult, ProverState};
 /// - guest_proof_time_histogram - time taken for proof generation by this guest
 /// - prepare_input_time_histogram - time taken for prepare input
 /// - total_time_histogram - time taken for the whole proof request
+///
+/// The per request metrics are labeled by block, unless the host runs with bounded metrics.
 /// - process_cpu_seconds_total - total user and system CPU time spent in seconds
 /// - process_open_fds - number of open file descriptors
 /// - process_max_fds - maximum number of open file descriptors
@@ -44,8 +52,28 @@ async fn metrics_handler() -> HostResult<Response> {
         .map_err(|e| anyhow::anyhow!(e).into())
 }
 
+#[utoipa::path(
+    get,
+    path = "/metrics/recent",
+    tag = "Metrics",
+    responses (
+        (status = 200, description = "The recent proof requests", body = [RecentRequest]),
+    ),
+)]
+#[debug_handler(state = ProverState)]
+/// Get the recent proof requests
+///
+/// Lists the last 1000 finished proof requests, the most recent first, with their block,
+/// proof type, result and time taken. With bounded metrics this is the only per block data.
+async fn recent_handler() -> Json<Vec<RecentRequest>> {
+    Json(recent_requests())
+}
+
 #[derive(OpenApi)]
-#[openapi(paths(metrics_handler))]
+#[openapi(
+    paths(metrics_handler, recent_handler),
+    components(schemas(RecentRequest))
+)]
 struct Docs;
 
 pub fn create_docs() -> utoipa::openapi::OpenApi {
@@ -53,5 +81,7 @@ pub fn create_docs() -> utoipa::openapi::OpenApi {
 }
 
 pub fn create_router() -> Router<ProverState> {
-    Router::new().route("/", get(metrics_handler))
+    Router::new()
+        .route("/", get(metrics_handler))
+        .route("/recent", get(recent_handler))
 }
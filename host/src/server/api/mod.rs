@@ -1,11 +1,15 @@
+use std::net::SocketAddr;
+
 use axum::{
     body::HttpBody,
-    extract::Request,
+    extract::{ConnectInfo, Request, State},
     http::{header, HeaderName, HeaderValue, Method, StatusCode, Uri},
     middleware::{self, Next},
-    response::Response,
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
     Router,
 };
+use raiko_client::{ErrorCode, Problem, PROBLEM_JSON};
 use tower::ServiceBuilder;
 use tower_http::{
     compression::CompressionLayer,
@@ -13,14 +17,32 @@ use tower_http::{
     set_header::SetResponseHeaderLayer,
     trace::TraceLayer,
 };
+use tracing::{info_span, Instrument};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::ProverState;
+use crate::{
+    api_keys::ApiKeys,
+    error::HostError,
+    jobs::JobManager,
+    jwt::JwtAuth,
+    rate_limit::RateLimiter,
+    trace::{new_trace_id, parse_trace_id, with_trace_id, TRACE_ID_HEADER},
+    workers::MAX_TASK_SIZE,
+    ProverState,
+};
 
+mod admin;
+mod estimate;
 mod health;
 mod metrics;
 mod proof;
+mod provers;
+mod sgx;
+mod v2;
+mod verify;
+mod worker;
+mod ws;
 
 #[derive(OpenApi)]
 #[openapi(
@@ -42,12 +64,15 @@ mod proof;
         schemas(
             crate::request::ProofRequestOpt,
             crate::error::HostError,
+            raiko_client::Problem,
+            raiko_client::ErrorCode,
         )
     ),
     tags(
         (name = "Prooving", description = "Routes that handle prooving requests"),
         (name = "Health", description = "Routes that report the server health status"),
-        (name = "Metrics", description = "Routes that give detailed insight into the server")
+        (name = "Metrics", description = "Routes that give detailed insight into the server"),
+        (name = "Admin", description = "Routes that manage the API keys, the runtime config and the workers, and report the proof costs")
     )
 )]
 /// The root API struct which is generated from the `OpenApi` derive macro.
@@ -56,9 +81,17 @@ pub struct Docs;
 #[must_use]
 pub fn create_docs() -> utoipa::openapi::OpenApi {
     [
+        admin::create_docs(),
+        estimate::create_docs(),
         health::create_docs(),
         metrics::create_docs(),
         proof::create_docs(),
+        provers::create_docs(),
+        sgx::create_docs(),
+        v2::create_docs(),
+        verify::create_docs(),
+        worker::create_docs(),
+        ws::create_docs(),
     ]
     .into_iter()
     .fold(Docs::openapi(), |mut doc, sub_doc| {
@@ -67,58 +100,387 @@ pub fn create_docs() -> utoipa::openapi::OpenApi {
     })
 }
 
-pub fn create_router(concurrency_limit: usize) -> Router<ProverState> {
+pub fn create_router(state: &ProverState) -> Router<ProverState> {
     let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
         .allow_headers([
             header::ORIGIN,
             header::ORIGIN,
             header::ACCEPT,
+            header::AUTHORIZATION,
             HeaderName::from_static("x-requested-with"),
+            HeaderName::from_static("x-api-key"),
+            HeaderName::from_static(TRACE_ID_HEADER),
         ])
+        .expose_headers([HeaderName::from_static(TRACE_ID_HEADER)])
         .allow_origin(cors::Any);
     let compression = CompressionLayer::new();
 
+    // Keep the content type of the problem details of errors and of binary proofs.
+    let content_type = |response: &Response| match response.headers().get(header::CONTENT_TYPE) {
+        Some(content_type)
+            if content_type == PROBLEM_JSON || content_type == "application/octet-stream" =>
+        {
+            Some(content_type.clone())
+        }
+        _ => Some(HeaderValue::from_static("application/json")),
+    };
     let middleware = ServiceBuilder::new().layer(cors).layer(compression).layer(
-        SetResponseHeaderLayer::overriding(
-            header::CONTENT_TYPE,
-            HeaderValue::from_static("application/json"),
-        ),
+        SetResponseHeaderLayer::overriding(header::CONTENT_TYPE, content_type),
     );
 
     let trace = TraceLayer::new_for_http();
 
-    Router::new()
+    let mut router = Router::new()
         // Only add the concurrency limit to the proof route. We want to still be able to call
         // healthchecks and metrics to have insight into the system.
         .nest(
             "/proof",
             proof::create_router()
-                .layer(ServiceBuilder::new().concurrency_limit(concurrency_limit)),
+                .layer(ServiceBuilder::new().concurrency_limit(state.opts.concurrency_limit)),
         )
+        .nest("/v2", v2::create_router())
+        .nest("/provers", provers::create_router())
+        .nest("/sgx", sgx::create_router())
+        .nest("/verify", verify::create_router())
+        .nest("/estimate", estimate::create_router())
         .nest("/health", health::create_router())
+        .merge(health::create_probes_router())
         .nest("/metrics", metrics::create_router())
+        .nest(
+            "/admin",
+            admin::create_router().nest("/sgx", sgx::create_admin_router()),
+        );
+    // Only workers prove for a coordinator.
+    if state.opts.coordinator.is_some() {
+        router = router.nest("/worker", worker::create_router());
+    }
+
+    router
         .layer(middleware)
+        // The WebSocket upgrade response must not be compressed or get a JSON content type.
+        .nest("/ws", ws::create_router())
+        .layer(middleware::from_fn_with_state(
+            state.jobs.clone(),
+            check_queue,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.rate_limiter.clone(),
+            check_rate_limit,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.api_keys.clone(),
+            check_api_key,
+        ))
+        .layer(middleware::from_fn_with_state(state.jwt.clone(), check_jwt))
         .layer(middleware::from_fn(check_max_body_size))
+        .layer(middleware::from_fn(trace_request))
         .layer(trace)
-        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", create_docs()))
+        .merge(SwaggerUi::new("/swagger-ui").url("/openapi.json", create_docs()))
+        // The path the specification was served at before.
+        .route(
+            "/api-docs/openapi.json",
+            get(|| async { Redirect::permanent("/openapi.json") }),
+        )
         .fallback(|uri: Uri| async move {
             (StatusCode::NOT_FOUND, format!("No handler found for {uri}"))
         })
 }
 
+/// Handle the request in a span with its trace id and return the id in the response, see
+/// [crate::trace].
+async fn trace_request(req: Request, next: Next) -> Response {
+    let trace_id = req
+        .headers()
+        .get(TRACE_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_trace_id)
+        .unwrap_or_else(new_trace_id);
+    let span = info_span!(
+        "request",
+        trace_id = %trace_id,
+        method = %req.method(),
+        path = %req.uri().path(),
+    );
+    let mut response = with_trace_id(trace_id.clone(), next.run(req))
+        .instrument(span)
+        .await;
+    if let Ok(value) = HeaderValue::from_str(&trace_id) {
+        response.headers_mut().insert(TRACE_ID_HEADER, value);
+    }
+    response
+}
+
+/// The maximum body size of aggregation and proof verification requests, which carry the full
+/// block receipts.
+pub(crate) const MAX_AGGREGATE_BODY_SIZE: u64 = 1 << 28;
+
 async fn check_max_body_size(req: Request, next: Next) -> Response {
     const MAX_BODY_SIZE: u64 = 1 << 20;
+    let path = req.uri().path();
+    let max_body_size = if path.starts_with("/v2/aggregate") || path.starts_with("/verify/proof") {
+        MAX_AGGREGATE_BODY_SIZE
+    } else if path.starts_with("/worker/") || path.starts_with("/proof/replay") {
+        MAX_TASK_SIZE
+    } else {
+        MAX_BODY_SIZE
+    };
     let response_content_length = match req.body().size_hint().upper() {
         Some(v) => v,
-        None => MAX_BODY_SIZE + 1,
+        None => max_body_size + 1,
     };
 
-    if response_content_length > MAX_BODY_SIZE {
-        let mut resp = Response::new(axum::body::Body::from("request too large"));
-        *resp.status_mut() = StatusCode::BAD_REQUEST;
-        return resp;
+    if response_content_length > max_body_size {
+        return HostError::Validation(Problem::new(
+            ErrorCode::MalformedBody,
+            format!("Request too large, the limit is {max_body_size} bytes"),
+        ))
+        .into_response();
+    }
+
+    next.run(req).await
+}
+
+/// Whether the request submits proof requests, as opposed to e.g. polling a job.
+fn is_proof_request(req: &Request) -> bool {
+    let path = req.uri().path().trim_end_matches('/');
+    req.method() == Method::POST
+        && [
+            "/proof",
+            "/proof/replay",
+            "/proof/soft",
+            "/v2/proof",
+            "/v2/proof/batch",
+            "/v2/aggregate",
+            "/worker/prove",
+        ]
+        .contains(&path)
+}
+
+/// Reject proof requests over the rate limits with a `Retry-After` hint, see
+/// [crate::rate_limit].
+async fn check_rate_limit(
+    State(rate_limiter): State<RateLimiter>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    if !is_proof_request(&req) {
+        return next.run(req).await;
+    }
+    // A proxy may add the header more than once
+    let forwarded_for = req
+        .headers()
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",");
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let forwarded_for = Some(forwarded_for.as_str()).filter(|value| !value.is_empty());
+    let client = rate_limiter.client(forwarded_for, peer);
+
+    match rate_limiter.admit(client) {
+        Ok(permit) => {
+            // The jobs of the request hold the slot until they finished
+            if let Some(permit) = &permit {
+                req.extensions_mut().insert(permit.clone());
+            }
+            next.run(req).await
+        }
+        Err(retry_after) => {
+            let retry_after = (retry_after.as_secs_f64().ceil() as u64).max(1);
+            let mut response =
+                HostError::TooManyRequests(format!("Rate limit reached, retry in {retry_after}s"))
+                    .into_response();
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+            response
+        }
+    }
+}
+
+/// Reject proof requests with a `Retry-After` hint while the queue of the host is full, see
+/// [JobManager::check_queue].
+async fn check_queue(State(jobs): State<JobManager>, req: Request, next: Next) -> Response {
+    if !is_proof_request(&req) {
+        return next.run(req).await;
+    }
+    match jobs.check_queue() {
+        Ok(()) => next.run(req).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Only serve requests with a valid API key once keys are configured, see [crate::api_keys].
+async fn check_api_key(State(api_keys): State<ApiKeys>, req: Request, next: Next) -> Response {
+    let path = req.uri().path().trim_end_matches('/');
+    if path.starts_with("/health") || path == "/readyz" || path.starts_with("/metrics") {
+        return next.run(req).await;
+    }
+    let admin = path.starts_with("/admin");
+    // Without an admin key, nobody could be told apart from an admin, so the admin endpoints
+    // are not served at all.
+    if admin && !api_keys.has_admin_key() {
+        let uri = req.uri();
+        return (StatusCode::NOT_FOUND, format!("No handler found for {uri}")).into_response();
+    }
+    let proof = is_proof_request(&req);
+    let key = req
+        .headers()
+        .get("x-api-key")
+        .or_else(|| req.headers().get(header::AUTHORIZATION))
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.strip_prefix("Bearer ").unwrap_or(value));
+
+    match api_keys.authorize(key, admin, proof) {
+        Ok(_) => next.run(req).await,
+        Err(e) => e.into_response(),
     }
+}
 
+/// Only serve the proof endpoints to requests with a valid JWT once a secret is configured,
+/// see [crate::jwt].
+async fn check_jwt(State(jwt): State<Option<JwtAuth>>, req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    let proof_endpoint = ["/proof", "/v2/", "/ws/"]
+        .iter()
+        .any(|prefix| path.starts_with(prefix));
+    if let (Some(jwt), true) = (jwt, proof_endpoint) {
+        let header = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok());
+        if let Err(e) = jwt.validate_header(header) {
+            return e.into_response();
+        }
+    }
     next.run(req).await
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use alloy_primitives::{Address, B256};
+    use raiko_client::{JobStatus, ProofRequest, Submission, SubmissionStatus, Upload};
+    use serde::{de::DeserializeOwned, Serialize};
+    use serde_json::Value;
+
+    use super::*;
+
+    /// The properties of a schema of the spec.
+    fn properties(spec: &Value, schema: &str) -> BTreeSet<String> {
+        spec["components"]["schemas"][schema]["properties"]
+            .as_object()
+            .unwrap_or_else(|| panic!("{schema} is not an object schema of the spec"))
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /// The fields of a client type, serialized with all of them set.
+    fn fields(value: impl Serialize) -> BTreeSet<String> {
+        serde_json::to_value(value)
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /// Every value of an enum schema of the spec is a variant of the client type.
+    fn check_enum<T: DeserializeOwned>(spec: &Value, schema: &str) {
+        let values = spec["components"]["schemas"][schema]["enum"]
+            .as_array()
+            .unwrap_or_else(|| panic!("{schema} is not an enum schema of the spec"));
+        for value in values {
+            serde_json::from_value::<T>(value.clone())
+                .unwrap_or_else(|e| panic!("{value} of {schema} is unknown to the client: {e}"));
+        }
+    }
+
+    #[test]
+    fn test_client_matches_spec() {
+        let spec = serde_json::to_value(create_docs()).unwrap();
+
+        let request = ProofRequest {
+            block_number: Some(10),
+            network: Some("taiko_a7".to_string()),
+            l1_network: Some("holesky".to_string()),
+            rpc: Some("http://localhost:8545".to_string()),
+            l1_rpc: Some("http://localhost:8546".to_string()),
+            beacon_rpc: Some("http://localhost:5052".to_string()),
+            graffiti: Some(B256::ZERO),
+            prover: Some(Address::ZERO),
+            proof_type: Some("sgx".to_string()),
+            compress: Some(false),
+            priority: Some(1),
+            deadline: Some(1700000000),
+            force: Some(false),
+            cross_check: Some(false),
+            profile_guest: Some(false),
+            callback_url: Some("https://example.com/callback".to_string()),
+            guest: Some("v1".to_string()),
+            prover_args: [("sgx".to_string(), Value::Null)].into(),
+        };
+        assert_eq!(fields(&request), properties(&spec, "ProofRequestOpt"));
+
+        let submission = Submission {
+            status: SubmissionStatus::Pending,
+            tx_hash: None,
+            from: Address::ZERO,
+            nonce: None,
+            gas_price: None,
+            attempts: 0,
+            block_number: None,
+            gas_used: None,
+            error: None,
+        };
+        assert_eq!(fields(&submission), properties(&spec, "Submission"));
+        let upload = Upload {
+            proof_url: "s3://proofs/1.json".to_string(),
+            input_url: None,
+        };
+        assert_eq!(fields(&upload), properties(&spec, "Upload"));
+        let summary = raiko_client::JobSummary {
+            id: 1,
+            status: JobStatus::Queued,
+            error: None,
+            retries: 0,
+            submission: None,
+            upload: None,
+            trace_id: String::new(),
+            queue_position: Some(1),
+            created_at: 0,
+            updated_at: 0,
+        };
+        assert_eq!(fields(&summary), properties(&spec, "JobSummary"));
+
+        check_enum::<JobStatus>(&spec, "JobStatus");
+        check_enum::<SubmissionStatus>(&spec, "SubmissionStatus");
+    }
+
+    #[test]
+    fn test_client_parses_job() {
+        // The full job has no schema, it is checked against the job the host returns.
+        let job = crate::queue::tests::job(1, crate::jobs::JobStatus::Success).redacted();
+        let json = serde_json::to_value(&job).unwrap();
+        let client_job: raiko_client::Job = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(client_job.id, 1);
+        assert_eq!(client_job.status, JobStatus::Success);
+        let host_fields = json.as_object().unwrap().keys().cloned().collect();
+        assert!(fields(&client_job).is_subset(&host_fields));
+    }
+}
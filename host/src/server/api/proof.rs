@@ -1,61 +1,41 @@
-use std::{fs::File, path::PathBuf};
+use std::collections::BTreeMap;
 
-use axum::{debug_handler, extract::State, routing::post, Json, Router};
-use raiko_lib::{
-    input::{get_input_path, GuestInput},
-    Measurement,
+use axum::{
+    body::Bytes,
+    debug_handler,
+    extract::{rejection::JsonRejection, DefaultBodyLimit, Query, State},
+    routing::post,
+    Json, Router,
 };
+use futures::future::try_join_all;
+use raiko_client::{ErrorCode, Problem};
+use raiko_lib::{input::GuestInput, prover::Proof};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use utoipa::OpenApi;
+use utoipa::{IntoParams, OpenApi};
 
 use crate::{
     error::{HostError, HostResult},
-    execution::execute,
-    metrics::{
-        dec_current_req, inc_current_req, inc_guest_error, inc_guest_success, inc_host_error,
-        inc_host_req_count, observe_total_time,
-    },
-    request::ProofRequest,
-    ProverState,
+    execution::{generate_proof, prepare_block_input, prove, prove_with_input, replay_request},
+    gpu,
+    input_cache::InputCache,
+    input_codec,
+    jobs::{progress::ProgressSender, JobManager},
+    l1_events,
+    metrics::{dec_current_req, inc_current_req},
+    proof_cache::ProofCache,
+    request::{ProofRequest, ProofRequestOpt, ProofTypes},
+    soft_block::{self, soft_block_request, SoftBlock},
+    workers::MAX_TASK_SIZE,
+    Cli, ProverState,
 };
 
-fn get_cached_input(
-    cache_path: &Option<PathBuf>,
-    block_number: u64,
-    network: &str,
-) -> Option<GuestInput> {
-    cache_path
-        .as_ref()
-        .map(|dir| get_input_path(dir, block_number, network))
-        .and_then(|path| {
-            File::open(path)
-                .map(|file| bincode::deserialize_from(file).ok())
-                .ok()
-                .flatten()
-        })
-}
-
-fn set_cached_input(
-    cache_path: &Option<PathBuf>,
-    block_number: u64,
-    network: &str,
-    input: GuestInput,
-) -> HostResult<()> {
-    if let Some(dir) = cache_path.as_ref() {
-        let path = get_input_path(dir, block_number, network);
-        if !path.exists() {
-            let file = File::create(&path).map_err(<std::io::Error as Into<HostError>>::into)?;
-            println!("caching input for {path:?}");
-            bincode::serialize_into(file, &input).map_err(|e| HostError::Anyhow(e.into()))?;
-        }
-    }
-    Ok(())
-}
-
 #[utoipa::path(post, path = "/proof",
     tag = "Proving",
+    request_body = ProofRequestOpt,
     responses (
-        (status = 200, description = "Successfully created proof for request")
+        (status = 200, description = "Successfully created proof for request"),
+        (status = 400, description = "The proof request is invalid", body = Problem, content_type = "application/problem+json")
     )
 )]
 #[debug_handler(state = ProverState)]
@@ -67,75 +47,264 @@ fn set_cached_input(
 /// - sgx - uses the sgx environment to construct a block and produce proof of execution
 /// - sp1 - uses the sp1 prover
 /// - risc0 - uses the risc0 prover
+///
+/// With `proof_types` instead of `proof_type`, the block is proven with all of them from a
+/// single preflight, the provers running in parallel, and the proofs are returned keyed by
+/// proof type. The request fails if any of the proofs fails.
 async fn proof_handler(
-    State(ProverState { opts }): State<ProverState>,
-    Json(req): Json<Value>,
+    State(ProverState {
+        opts,
+        jobs,
+        input_cache,
+        proof_cache,
+        runtime,
+        ..
+    }): State<ProverState>,
+    req: Result<Json<Value>, JsonRejection>,
 ) -> HostResult<Json<Value>> {
-    inc_current_req();
+    let Json(req) = req?;
+    ProofRequestOpt::check_fields(&req, &["proof_types"])?;
     // Override the existing proof request config from the config file and command line
     // options with the request from the client.
-    let mut config = opts.proof_request_opt.clone();
+    let mut config = runtime.request_config(&opts.proof_request_opt);
     config.merge(&req)?;
 
+    if req.get("proof_types").is_some() {
+        return multi_proof(&req, config, &opts, &jobs, &input_cache, &proof_cache).await;
+    }
+
     // Construct the actual proof request from the available configs.
-    let proof_request = ProofRequest::try_from(config).map_err(|e| {
-        dec_current_req();
-        e
-    })?;
-    inc_host_req_count(proof_request.block_number);
+    let proof_request = ProofRequest::try_from(config)?;
+    proof_request.check_block(opts.max_block_age).await?;
 
-    println!(
-        "# Generating proof for block {} on {}",
-        proof_request.block_number, proof_request.network
-    );
+    inc_current_req();
+    let proof = prove_in_slot(&jobs, &proof_request, &input_cache, &proof_cache, None).await;
+    dec_current_req();
 
-    // Check for a cached input for the given request config.
-    let cached_input = get_cached_input(
-        &opts.cache_path,
-        proof_request.block_number,
-        &proof_request.network.to_string(),
-    );
+    Ok(Json(proof?))
+}
+
+/// Prove the block of the request with all the proof types of the request.
+async fn multi_proof(
+    req: &Value,
+    config: ProofRequestOpt,
+    opts: &Cli,
+    jobs: &JobManager,
+    input_cache: &InputCache,
+    proof_cache: &ProofCache,
+) -> HostResult<Json<Value>> {
+    if req.get("proof_type").is_some() {
+        return Err(HostError::invalid(
+            ErrorCode::InvalidRequest,
+            "proof_type",
+            "Only one of proof_type and proof_types can be set",
+        ));
+    }
+    let proof_types = ProofTypes::deserialize(req).map_err(|e| {
+        HostError::invalid(
+            ErrorCode::MalformedBody,
+            "proof_types",
+            format!("Invalid proof types: {e}"),
+        )
+    })?;
+    proof_types.check()?;
+    let proof_requests = proof_types
+        .proof_types
+        .iter()
+        .map(|proof_type| {
+            let mut config = config.clone();
+            config.proof_type = Some(proof_type.to_string());
+            ProofRequest::try_from(config)
+        })
+        .collect::<HostResult<Vec<_>>>()?;
+    proof_requests[0].check_block(opts.max_block_age).await?;
 
-    // Execute the proof generation.
-    let total_time = Measurement::start("", false);
-    let (input, proof) = execute(&proof_request, cached_input).await.map_err(|e| {
-        dec_current_req();
-        let total_time = total_time.stop_with("====> Proof generation failed");
-        observe_total_time(proof_request.block_number, total_time.as_millis(), false);
-        match e {
-            HostError::GuestError(e) => {
-                inc_guest_error(&proof_request.proof_type, proof_request.block_number);
-                HostError::GuestError(e)
+    inc_current_req();
+    let proofs = async {
+        // The input is the same for all proof types, so the preflight only runs once.
+        let input =
+            prepare_block_input(&proof_requests[0], input_cache, &ProgressSender::default())
+                .await?;
+        try_join_all(proof_requests.iter().map(|proof_request| {
+            let input = &input;
+            async move {
+                let proof =
+                    prove_in_slot(jobs, proof_request, input_cache, proof_cache, Some(input))
+                        .await?;
+                HostResult::Ok((proof_request.proof_type.to_string(), proof))
             }
-            e => {
-                inc_host_error(proof_request.block_number);
-                e
+        }))
+        .await
+    }
+    .await;
+    dec_current_req();
+
+    let proofs = proofs?.into_iter().collect::<BTreeMap<_, _>>();
+    Ok(Json(serde_json::to_value(proofs)?))
+}
+
+/// Prove the request once a prover slot is free, retrying transient failures, from the given
+/// input or else the cached or prepared input of the block.
+async fn prove_in_slot(
+    jobs: &JobManager,
+    proof_request: &ProofRequest,
+    input_cache: &InputCache,
+    proof_cache: &ProofCache,
+    input: Option<&GuestInput>,
+) -> HostResult<Proof> {
+    let slot = jobs
+        .acquire(
+            &proof_request.proof_type,
+            proof_request.priority,
+            proof_request.deadline,
+        )
+        .await?;
+    let proving = jobs.retry_policy(&proof_request.proof_type).run(
+        &proof_request.proof_type,
+        |_| {},
+        move || async move {
+            let progress = ProgressSender::default();
+            match input {
+                Some(input) => prove_with_input(
+                    proof_request,
+                    input_cache,
+                    proof_cache,
+                    Some(input.clone()),
+                    &progress,
+                )
+                .await
+                .map(|(_, proof)| proof),
+                None => prove(proof_request, input_cache, proof_cache, &progress).await,
             }
-        }
-    })?;
-    inc_guest_success(&proof_request.proof_type, proof_request.block_number);
-    let total_time = total_time.stop_with("====> Complete proof generated");
-    observe_total_time(proof_request.block_number, total_time.as_millis(), true);
-
-    // Cache the input for future use.
-    set_cached_input(
-        &opts.cache_path,
-        proof_request.block_number,
-        &proof_request.network.to_string(),
-        input,
+        },
+    );
+    gpu::on_device(slot.cuda_device(), proving).await
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ReplayParams {
+    /// The proof type to prove the block with, defaults to the proof type of the host config.
+    proof_type: Option<String>,
+    /// The chain the input is of, defaults to the network of the host config.
+    network: Option<String>,
+}
+
+#[utoipa::path(post, path = "/proof/replay",
+    tag = "Proving",
+    params(ReplayParams),
+    request_body(
+        content = Vec<u8>,
+        content_type = "application/octet-stream",
+        description = "The input of the block, as saved in the input cache"
+    ),
+    responses (
+        (status = 200, description = "Successfully created proof for the input"),
+        (status = 400, description = "The input or the proof request is invalid", body = Problem, content_type = "application/problem+json")
     )
-    .map_err(|e| {
-        dec_current_req();
-        e
+)]
+#[debug_handler(state = ProverState)]
+/// Generate a proof for a saved input.
+///
+/// Accepts the input of a block saved before, e.g. a file of the input cache, and generates a
+/// proof for the block without the preflight. Only the proposal of the block is read from L1 to
+/// check the input against. The other options of the proof request are taken from the host
+/// config.
+async fn replay_handler(
+    State(ProverState {
+        opts,
+        jobs,
+        runtime,
+        ..
+    }): State<ProverState>,
+    Query(params): Query<ReplayParams>,
+    body: Bytes,
+) -> HostResult<Json<Value>> {
+    let (input, _) = input_codec::decode_input(&body[..]).map_err(|e| {
+        HostError::invalid(
+            ErrorCode::MalformedBody,
+            "input",
+            format!("Invalid input: {e}"),
+        )
     })?;
+    let mut config = runtime.request_config(&opts.proof_request_opt);
+    config.merge(&serde_json::to_value(&params)?)?;
+    let proof_request = replay_request(config, &input)?;
+
+    inc_current_req();
+    let proof = async {
+        l1_events::check_inclusion(&proof_request, &input).await?;
+        let slot = jobs
+            .acquire(
+                &proof_request.proof_type,
+                proof_request.priority,
+                proof_request.deadline,
+            )
+            .await?;
+        let proving = generate_proof(&proof_request, &input, &ProgressSender::default());
+        gpu::on_device(slot.cuda_device(), proving).await
+    }
+    .await;
+    dec_current_req();
+
+    Ok(Json(proof?))
+}
+
+#[utoipa::path(post, path = "/proof/soft",
+    tag = "Proving",
+    params(ReplayParams),
+    request_body = SoftBlock,
+    responses (
+        (status = 200, description = "Successfully created proof for the block"),
+        (status = 400, description = "The block or the proof request is invalid", body = Problem, content_type = "application/problem+json")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Generate a proof for a block that is not proposed yet.
+///
+/// Accepts the block in the body instead of fetching it from the node, e.g. a preconfirmed
+/// block, and generates a proof for it with the metadata of its proposal in calldata. Only its
+/// parent, the state it accesses and its anchor block are fetched. The other options of the
+/// proof request are taken from the host config.
+async fn soft_block_handler(
+    State(ProverState {
+        opts,
+        jobs,
+        runtime,
+        ..
+    }): State<ProverState>,
+    Query(params): Query<ReplayParams>,
+    req: Result<Json<SoftBlock>, JsonRejection>,
+) -> HostResult<Json<Value>> {
+    let Json(block) = req?;
+    let mut config = runtime.request_config(&opts.proof_request_opt);
+    config.merge(&serde_json::to_value(&params)?)?;
+    let proof_request = soft_block_request(config, &block)?;
 
+    inc_current_req();
+    let proof = async {
+        let input = soft_block::prepare_input(&proof_request, block).await?;
+        let slot = jobs
+            .acquire(
+                &proof_request.proof_type,
+                proof_request.priority,
+                proof_request.deadline,
+            )
+            .await?;
+        let proving = generate_proof(&proof_request, &input, &ProgressSender::default());
+        gpu::on_device(slot.cuda_device(), proving).await
+    }
+    .await;
     dec_current_req();
 
-    Ok(Json(proof))
+    Ok(Json(proof?))
 }
 
 #[derive(OpenApi)]
-#[openapi(paths(proof_handler))]
+#[openapi(
+    paths(proof_handler, replay_handler, soft_block_handler),
+    components(schemas(ProofTypes, SoftBlock))
+)]
 struct Docs;
 
 pub fn create_docs() -> utoipa::openapi::OpenApi {
@@ -143,5 +312,11 @@ pub fn create_docs() -> utoipa::openapi::OpenApi {
 }
 
 pub fn create_router() -> Router<ProverState> {
-    Router::new().route("/", post(proof_handler))
+    Router::new()
+        .route("/", post(proof_handler))
+        .route(
+            "/replay",
+            post(replay_handler).layer(DefaultBodyLimit::max(MAX_TASK_SIZE as usize)),
+        )
+        .route("/soft", post(soft_block_handler))
 }
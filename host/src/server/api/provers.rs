@@ -0,0 +1,65 @@
+use axum::{debug_handler, routing::get, Json, Router};
+use utoipa::OpenApi;
+
+use crate::{
+    images::{list_images, ProverImage},
+    registry::{list_provers, ProverInfo},
+    ProverState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/provers",
+    tag = "Proving",
+    responses (
+        (status = 200, description = "The provers available on this host", body = [ProverInfo]),
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// List the available provers.
+///
+/// Returns the provers that were enabled when building the host, together with any prover
+/// backends registered at runtime. The name of a prover is used as `proof_type` in proof
+/// requests.
+async fn provers_handler() -> Json<Vec<ProverInfo>> {
+    Json(list_provers())
+}
+
+#[utoipa::path(
+    get,
+    path = "/provers/images",
+    tag = "Proving",
+    responses (
+        (status = 200, description = "The programs the provers on this host prove with", body = [ProverImage]),
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// List the programs of the provers.
+///
+/// Returns the program of every prover, to compare with the programs registered on-chain
+/// before requesting proofs:
+/// - risc0 - the image id of the guest
+/// - sp1 - the keccak hash of the ELF of the guest
+/// - sgx - the MRENCLAVE and MRSIGNER of the enclave, from the quote of its bootstrapped key
+///
+/// The guests built into the host come with the commit they were built from.
+async fn images_handler() -> Json<Vec<ProverImage>> {
+    Json(list_images())
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(provers_handler, images_handler),
+    components(schemas(ProverInfo, ProverImage))
+)]
+struct Docs;
+
+pub fn create_docs() -> utoipa::openapi::OpenApi {
+    Docs::openapi()
+}
+
+pub fn create_router() -> Router<ProverState> {
+    Router::new()
+        .route("/", get(provers_handler))
+        .route("/images", get(images_handler))
+}
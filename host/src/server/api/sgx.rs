@@ -0,0 +1,390 @@
+use axum::{
+    debug_handler,
+    extract::{Query, State},
+    routing::{get, post},
+    Json, Router,
+};
+#[cfg(feature = "sgx")]
+use raiko_client::{ErrorCode, Problem};
+#[cfg(feature = "sgx")]
+use raiko_primitives::hex;
+use raiko_primitives::B256;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+#[cfg(feature = "sgx")]
+use crate::chain_registry::get_chain;
+#[cfg(not(feature = "sgx"))]
+use crate::request::ProofType;
+use crate::{
+    error::{HostError, HostResult},
+    Cli, ProverState,
+};
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AttestationParams {
+    #[param(value_type = Option<String>)]
+    /// A 32 byte hex value to include in the report data, to check the quote is fresh.
+    nonce: Option<B256>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// A request to verify an SGX quote.
+pub struct QuoteVerificationRequest {
+    /// The hex encoded DCAP quote.
+    pub quote: String,
+    /// The nonce the report data of the quote has to include.
+    #[schema(value_type = Option<String>)]
+    pub nonce: Option<B256>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+/// A request to rotate the SGX key.
+pub struct RotateKeyRequest {
+    /// The network whose SGX verifier the key is registered with, defaults to the config.
+    pub network: Option<String>,
+    /// The L1 node URL to send the registration with, defaults to the config.
+    pub l1_rpc: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+/// A request to refresh the SGX collateral cache.
+pub struct RefreshCollateralRequest {
+    /// The FMSPCs of the platforms to download the TCB info of, in addition to the cached ones.
+    /// Defaults to the local platform when the cache is empty.
+    pub fmspcs: Vec<String>,
+}
+
+#[utoipa::path(get, path = "/sgx/attestation",
+    tag = "Proving",
+    params(AttestationParams),
+    responses (
+        (status = 200, description = "A fresh quote for the enclave key"),
+        (status = 405, description = "The host was built without SGX support")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Attest the SGX enclave.
+///
+/// Returns a fresh DCAP quote of the enclave, with the instance address of the key it signs
+/// proofs with and the optional nonce in the report data.
+async fn attestation_handler(
+    Query(AttestationParams { nonce }): Query<AttestationParams>,
+) -> HostResult<Json<Value>> {
+    Ok(Json(attest(nonce).await?))
+}
+
+#[utoipa::path(post, path = "/sgx/attestation/verify",
+    tag = "Proving",
+    request_body = QuoteVerificationRequest,
+    responses (
+        (status = 200, description = "The attested enclave and the TCB status of its platform"),
+        (status = 400, description = "The quote is invalid, from a debug enclave or revoked"),
+        (status = 503, description = "The collateral could not be fetched or expired"),
+        (status = 405, description = "The host was built without SGX support")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Verify an SGX quote.
+///
+/// Verifies the quote and its certificate chain up to the Intel SGX Root CA, and looks up the
+/// TCB status of the platform with the collateral of the configured PCCS. Quotes of debug
+/// enclaves and revoked platforms are rejected, as is expired collateral. Returns the enclave
+/// measurements and the report data, so callers can check the enclave before trusting the
+/// signatures of its instance address.
+async fn verify_handler(
+    State(ProverState { opts, .. }): State<ProverState>,
+    Json(req): Json<QuoteVerificationRequest>,
+) -> HostResult<Json<Value>> {
+    Ok(Json(verify_quote(&opts, req).await?))
+}
+
+#[utoipa::path(get, path = "/sgx/key",
+    tag = "Proving",
+    responses (
+        (status = 200, description = "The current key of the enclave"),
+        (status = 405, description = "The host was built without SGX support")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Export the SGX key.
+///
+/// Returns the public key and instance address the enclave currently signs proofs with, its
+/// version and the quote attesting it at the time it was created.
+async fn key_handler() -> HostResult<Json<Value>> {
+    Ok(Json(load_key()?))
+}
+
+#[utoipa::path(post, path = "/admin/sgx/key/rotate",
+    tag = "Admin",
+    request_body = RotateKeyRequest,
+    responses (
+        (status = 200, description = "The new key and the instance it was registered as"),
+        (status = 403, description = "The request was not made with an admin key"),
+        (status = 405, description = "The host was built without SGX support")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Rotate the SGX key.
+///
+/// Generates a new signing key in the enclave with the next sealed version and registers it
+/// with the SGX verifier of the network, signed by the configured `sgx_signer`, or with
+/// `SENDER_PRIV_KEY`. The enclave only switches to the new key once the registration
+/// succeeded, and proofs use the instance of the new key from then on.
+async fn rotate_handler(
+    State(ProverState { opts, .. }): State<ProverState>,
+    Json(req): Json<RotateKeyRequest>,
+) -> HostResult<Json<Value>> {
+    Ok(Json(rotate_key(&opts, req).await?))
+}
+
+#[utoipa::path(get, path = "/sgx/instances",
+    tag = "Proving",
+    responses (
+        (status = 200, description = "The registered instances, oldest first"),
+        (status = 405, description = "The host was built without SGX support")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// List the SGX instances.
+///
+/// Returns the instance ids the keys of the enclave were registered as by this host, with the
+/// key version and the verifier of each registration.
+async fn instances_handler() -> HostResult<Json<Value>> {
+    Ok(Json(list_instances()?))
+}
+
+#[utoipa::path(get, path = "/sgx/collateral",
+    tag = "Proving",
+    responses (
+        (status = 200, description = "The cached collateral objects"),
+        (status = 405, description = "The host was built without SGX support")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// List the SGX collateral cache.
+///
+/// Returns the TCB info, QE identity and CRLs cached to verify quotes, with their source and
+/// the time they were downloaded.
+async fn collateral_handler() -> HostResult<Json<Value>> {
+    Ok(Json(list_collateral()?))
+}
+
+#[utoipa::path(post, path = "/admin/sgx/collateral/refresh",
+    tag = "Admin",
+    request_body = RefreshCollateralRequest,
+    responses (
+        (status = 200, description = "The refreshed collateral objects"),
+        (status = 403, description = "The request was not made with an admin key"),
+        (status = 405, description = "The host was built without SGX support")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Refresh the SGX collateral cache.
+///
+/// Downloads the QE identity, the PCK and Root CA CRLs and the TCB info of the cached and
+/// requested FMSPCs from the configured PCCS. Quotes are verified with the cache when the PCCS
+/// can't be reached, so hosts with restricted outbound internet can refresh it while a route
+/// is open.
+async fn refresh_collateral_handler(
+    State(ProverState { opts, .. }): State<ProverState>,
+    Json(req): Json<RefreshCollateralRequest>,
+) -> HostResult<Json<Value>> {
+    Ok(Json(refresh_collateral(&opts, req).await?))
+}
+
+#[cfg(feature = "sgx")]
+fn pccs_url(opts: &Cli) -> &str {
+    opts.sgx_pccs_url
+        .as_deref()
+        .unwrap_or(sgx_prover::INTEL_PCS_URL)
+}
+
+#[cfg(feature = "sgx")]
+fn quote_error(e: sgx_prover::QuoteError) -> HostError {
+    match e {
+        sgx_prover::QuoteError::Invalid(e) => HostError::InvalidQuote(e),
+        // The quote may verify once the collateral is refreshed
+        sgx_prover::QuoteError::Collateral(e) => {
+            HostError::Validation(Problem::new(ErrorCode::BackendUnavailable, e))
+        }
+    }
+}
+
+#[cfg(feature = "sgx")]
+async fn attest(nonce: Option<B256>) -> HostResult<Value> {
+    Ok(serde_json::to_value(sgx_prover::attest(nonce).await?)?)
+}
+
+#[cfg(feature = "sgx")]
+async fn verify_quote(opts: &Cli, req: QuoteVerificationRequest) -> HostResult<Value> {
+    let quote = hex::decode(&req.quote).map_err(|e| HostError::InvalidQuote(e.to_string()))?;
+    let verification = sgx_prover::verify_quote(&quote, pccs_url(opts))
+        .await
+        .map_err(quote_error)?;
+    if let Some(nonce) = req.nonce {
+        if verification.nonce != nonce {
+            return Err(HostError::InvalidQuote(format!(
+                "Report data has nonce {} instead of {nonce}",
+                verification.nonce
+            )));
+        }
+    }
+    Ok(serde_json::to_value(verification)?)
+}
+
+#[cfg(feature = "sgx")]
+fn load_key() -> HostResult<Value> {
+    Ok(serde_json::to_value(sgx_prover::load_key()?)?)
+}
+
+#[cfg(feature = "sgx")]
+async fn rotate_key(opts: &Cli, req: RotateKeyRequest) -> HostResult<Value> {
+    // Check the registration config before generating the key
+    let chain = get_chain(
+        &req.network
+            .or(opts.proof_request_opt.network.clone())
+            .ok_or(HostError::InvalidRequestConfig(
+                "Missing network".to_string(),
+            ))?,
+    )?;
+    let l1_chain = get_chain(
+        &opts
+            .proof_request_opt
+            .l1_network
+            .clone()
+            .or(chain.l1_network.clone())
+            .ok_or(HostError::InvalidRequestConfig(
+                "Missing l1_network".to_string(),
+            ))?,
+    )?;
+    let l1_rpc = req
+        .l1_rpc
+        .or(opts.proof_request_opt.l1_rpc.clone())
+        .or(chain.l1_rpc.clone())
+        .ok_or(HostError::InvalidRequestConfig(
+            "Missing l1_rpc".to_string(),
+        ))?;
+    let verifier_address = chain.chain_spec.sgx_verifier_address.ok_or_else(|| {
+        HostError::InvalidRequestConfig(format!("No SGX verifier on {}", chain.name))
+    })?;
+    let signer_config = opts
+        .sgx_signer
+        .clone()
+        .unwrap_or_else(|| sgx_prover::SignerConfig::private_key("SENDER_PRIV_KEY"));
+    let signer = sgx_prover::RaikoSigner::new(&signer_config)
+        .await
+        .map_err(|e| HostError::InvalidRequestConfig(format!("Invalid SGX signer: {e}")))?;
+
+    let key = sgx_prover::rotate_key().await?;
+    let instance = sgx_prover::register_key(
+        &key,
+        &l1_rpc,
+        l1_chain.chain_spec.chain_id,
+        verifier_address,
+        &signer,
+    )
+    .await?;
+    // Only sign with the new key once proofs with it are accepted
+    let key = sgx_prover::activate_key().await?;
+    Ok(serde_json::json!({ "key": key, "instance": instance }))
+}
+
+#[cfg(feature = "sgx")]
+fn list_instances() -> HostResult<Value> {
+    Ok(serde_json::to_value(sgx_prover::list_instances()?)?)
+}
+
+#[cfg(feature = "sgx")]
+fn list_collateral() -> HostResult<Value> {
+    Ok(serde_json::to_value(sgx_prover::list_collateral()?)?)
+}
+
+#[cfg(feature = "sgx")]
+async fn refresh_collateral(opts: &Cli, req: RefreshCollateralRequest) -> HostResult<Value> {
+    let collateral = sgx_prover::refresh_collateral(pccs_url(opts), &req.fmspcs)
+        .await
+        .map_err(quote_error)?;
+    Ok(serde_json::to_value(collateral)?)
+}
+
+#[cfg(not(feature = "sgx"))]
+async fn attest(_nonce: Option<B256>) -> HostResult<Value> {
+    Err(HostError::FeatureNotSupportedError(ProofType::Sgx))
+}
+
+#[cfg(not(feature = "sgx"))]
+async fn verify_quote(_opts: &Cli, _req: QuoteVerificationRequest) -> HostResult<Value> {
+    Err(HostError::FeatureNotSupportedError(ProofType::Sgx))
+}
+
+#[cfg(not(feature = "sgx"))]
+fn load_key() -> HostResult<Value> {
+    Err(HostError::FeatureNotSupportedError(ProofType::Sgx))
+}
+
+#[cfg(not(feature = "sgx"))]
+async fn rotate_key(_opts: &Cli, _req: RotateKeyRequest) -> HostResult<Value> {
+    Err(HostError::FeatureNotSupportedError(ProofType::Sgx))
+}
+
+#[cfg(not(feature = "sgx"))]
+fn list_instances() -> HostResult<Value> {
+    Err(HostError::FeatureNotSupportedError(ProofType::Sgx))
+}
+
+#[cfg(not(feature = "sgx"))]
+fn list_collateral() -> HostResult<Value> {
+    Err(HostError::FeatureNotSupportedError(ProofType::Sgx))
+}
+
+#[cfg(not(feature = "sgx"))]
+async fn refresh_collateral(_opts: &Cli, _req: RefreshCollateralRequest) -> HostResult<Value> {
+    Err(HostError::FeatureNotSupportedError(ProofType::Sgx))
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        attestation_handler,
+        verify_handler,
+        key_handler,
+        instances_handler,
+        collateral_handler
+    ),
+    components(schemas(QuoteVerificationRequest))
+)]
+struct Docs;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(rotate_handler, refresh_collateral_handler),
+    components(schemas(RotateKeyRequest, RefreshCollateralRequest))
+)]
+struct AdminDocs;
+
+pub fn create_docs() -> utoipa::openapi::OpenApi {
+    let mut docs = Docs::openapi();
+    docs.merge(AdminDocs::openapi());
+    docs
+}
+
+pub fn create_router() -> Router<ProverState> {
+    Router::new()
+        .route("/attestation", get(attestation_handler))
+        .route("/attestation/verify", post(verify_handler))
+        .route("/key", get(key_handler))
+        .route("/instances", get(instances_handler))
+        .route("/collateral", get(collateral_handler))
+}
+
+/// The SGX routes that change the enclave, mounted under `/admin`.
+pub fn create_admin_router() -> Router<ProverState> {
+    Router::new()
+        .route("/key/rotate", post(rotate_handler))
+        .route("/collateral/refresh", post(refresh_collateral_handler))
+}
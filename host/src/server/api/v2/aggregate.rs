@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use axum::{
+    debug_handler,
+    extract::{rejection::JsonRejection, DefaultBodyLimit, State},
+    routing::post,
+    Json, Router,
+};
+use raiko_client::ErrorCode;
+use serde::Deserialize;
+use serde_json::Value;
+use utoipa::OpenApi;
+
+use crate::{
+    error::{HostError, HostResult},
+    metrics::{dec_current_req, inc_current_req},
+    request::{AggregationRequest, ProofRequestOpt, ProofType},
+    server::api::MAX_AGGREGATE_BODY_SIZE,
+    ProverState,
+};
+
+#[utoipa::path(post, path = "/v2/aggregate",
+    tag = "Proving",
+    request_body = AggregationRequest,
+    responses (
+        (status = 200, description = "Successfully aggregated the proofs")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Aggregate block proofs into a single proof.
+///
+/// Accepts block proofs generated with `/proof` or `/v2/proof` and recursively aggregates
+/// them into one proof. The `proof_type` and prover params are taken from the request, falling
+/// back to the host config. Supported by the risc0 and the sp1 prover, which only aggregates
+/// compressed proofs.
+async fn aggregate_handler(
+    State(ProverState {
+        opts,
+        jobs,
+        runtime,
+        ..
+    }): State<ProverState>,
+    req: Result<Json<Value>, JsonRejection>,
+) -> HostResult<Json<Value>> {
+    let Json(req) = req?;
+    ProofRequestOpt::check_fields(&req, &["proofs"])?;
+    let AggregationRequest { proofs } = AggregationRequest::deserialize(&req).map_err(|e| {
+        HostError::invalid(
+            ErrorCode::MalformedBody,
+            "proofs",
+            format!("Invalid proofs: {e}"),
+        )
+    })?;
+    if proofs.is_empty() {
+        return Err(HostError::invalid(
+            ErrorCode::MissingField,
+            "proofs",
+            "Missing proofs to aggregate",
+        ));
+    }
+
+    // Override the existing proof request config from the config file and command line
+    // options with the request from the client.
+    let mut config = runtime.request_config(&opts.proof_request_opt);
+    config.merge(&req)?;
+
+    let proof_type: ProofType = config
+        .proof_type
+        .ok_or_else(|| {
+            HostError::invalid(ErrorCode::MissingField, "proof_type", "Missing proof_type")
+        })?
+        .parse()?;
+    let prover_args = serde_json::to_value(HashMap::<String, Value>::from(config.prover_args))?;
+
+    // The aggregation runs in the host, which can't pick the device of the slot.
+    let _slot = jobs
+        .acquire(
+            &proof_type,
+            config.priority.unwrap_or_default(),
+            config.deadline,
+        )
+        .await?;
+    inc_current_req();
+    let proof = proof_type.aggregate(proofs, &prover_args).await;
+    dec_current_req();
+
+    Ok(Json(proof?))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(aggregate_handler), components(schemas(AggregationRequest)))]
+struct Docs;
+
+pub fn create_docs() -> utoipa::openapi::OpenApi {
+    Docs::openapi()
+}
+
+pub fn create_router() -> Router<ProverState> {
+    Router::new()
+        .route("/", post(aggregate_handler))
+        .layer(DefaultBodyLimit::max(MAX_AGGREGATE_BODY_SIZE as usize))
+}
@@ -0,0 +1,186 @@
+use axum::{
+    body::Body,
+    debug_handler,
+    extract::{Path, Query, State},
+    routing::get,
+    Json, Router,
+};
+use futures::{stream, StreamExt};
+use raiko_client::{ErrorCode, Problem};
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+use utoipa::{IntoParams, OpenApi};
+
+use crate::{
+    artifacts::{self, Artifact},
+    error::{HostError, HostResult},
+    isolation::ProverCrash,
+    jobs::{logs::JobLogLine, JobId, JobPhases, JobRecord, JobStatus},
+    request::ProofType,
+    ProverState,
+};
+
+/// The number of jobs listed by default.
+const DEFAULT_LIMIT: usize = 100;
+
+/// The maximum number of jobs that can be listed at once.
+const MAX_LIMIT: usize = 1000;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct JobsParams {
+    /// Only list the jobs in this state.
+    status: Option<JobStatus>,
+    /// Only list the jobs of this proof type.
+    proof_type: Option<String>,
+    /// The max number of jobs to list, 100 by default and at most 1000.
+    limit: Option<usize>,
+}
+
+#[utoipa::path(get, path = "/v2/jobs",
+    tag = "Proving",
+    params(JobsParams),
+    responses (
+        (status = 200, description = "The most recent proof jobs, newest first", body = [JobRecord]),
+        (status = 400, description = "The filter is invalid", body = Problem, content_type = "application/problem+json")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// List the recent proof jobs.
+///
+/// Returns the most recent jobs from the job store, newest first, optionally only those in
+/// the given state or of the given proof type. Every job comes with its timestamps, the
+/// seconds it spent in each phase, its retries and the first line of its error.
+async fn jobs_handler(
+    State(ProverState { jobs, .. }): State<ProverState>,
+    Query(params): Query<JobsParams>,
+) -> HostResult<Json<Vec<JobRecord>>> {
+    let proof_type = params
+        .proof_type
+        .as_deref()
+        .map(str::parse::<ProofType>)
+        .transpose()?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+    if limit == 0 || limit > MAX_LIMIT {
+        return Err(HostError::invalid(
+            ErrorCode::InvalidRequest,
+            "limit",
+            format!("The limit must be between 1 and {MAX_LIMIT}"),
+        ));
+    }
+    Ok(Json(jobs.recent(
+        params.status,
+        proof_type.as_ref(),
+        limit,
+    )?))
+}
+
+#[utoipa::path(get, path = "/v2/jobs/{id}/log",
+    tag = "Proving",
+    params(
+        ("id" = u64, Path, description = "The proof job id")
+    ),
+    responses (
+        (status = 200, description = "The log lines of the proof job, one JSON object per line", body = JobLogLine),
+        (status = 404, description = "No proof job with the given id exists")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Stream the log of a proof job.
+///
+/// Returns the lines the job logged, one JSON object per line. The stream of an unfinished
+/// job follows its log until the job finishes. The logs are kept in memory for the last 1000
+/// jobs since the host started.
+async fn log_handler(
+    State(ProverState { jobs, .. }): State<ProverState>,
+    Path(id): Path<JobId>,
+) -> HostResult<Body> {
+    let (lines, receiver) = jobs.log(id)?;
+    let live = stream::unfold(receiver, move |receiver| async move {
+        let mut receiver = receiver?;
+        loop {
+            match receiver.recv().await {
+                Ok(line) => return Some((line, Some(receiver))),
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Log subscriber for job {id} skipped {skipped} lines");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    let body = stream::iter(lines).chain(live).map(|line| {
+        serde_json::to_vec(&line).map(|mut json| {
+            json.push(b'\n');
+            json
+        })
+    });
+    Ok(Body::from_stream(body))
+}
+
+#[utoipa::path(get, path = "/v2/jobs/{id}/artifacts",
+    tag = "Proving",
+    params(
+        ("id" = u64, Path, description = "The proof job id")
+    ),
+    responses (
+        (status = 200, description = "The artifacts kept for the proof job", body = [Artifact]),
+        (status = 400, description = "The host keeps no artifacts", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "No proof job with the given id exists")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// List the artifacts of a proof job.
+///
+/// Returns the files kept for the job in the artifacts directory of the host: its input, the
+/// checkpoints of its prover, the output of the prover, its log and its proof or error. The
+/// checkpoints of an unfinished proof are listed from its workspace.
+async fn artifacts_handler(
+    State(ProverState { jobs, .. }): State<ProverState>,
+    Path(id): Path<JobId>,
+) -> HostResult<Json<Vec<Artifact>>> {
+    let job = jobs.get(id)?;
+    Ok(Json(artifacts::list(id, &job.request)?))
+}
+
+#[utoipa::path(get, path = "/v2/jobs/{id}/artifacts/{path}",
+    tag = "Proving",
+    params(
+        ("id" = u64, Path, description = "The proof job id"),
+        ("path" = String, Path, description = "The path of the artifact, as listed")
+    ),
+    responses (
+        (status = 200, description = "The content of the artifact"),
+        (status = 400, description = "No such artifact exists", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "No proof job with the given id exists")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Download an artifact of a proof job.
+async fn artifact_handler(
+    State(ProverState { jobs, .. }): State<ProverState>,
+    Path((id, path)): Path<(JobId, String)>,
+) -> HostResult<Body> {
+    let job = jobs.get(id)?;
+    let path = artifacts::path(id, &job.request, &path)?;
+    Ok(Body::from(tokio::fs::read(path).await?))
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(jobs_handler, log_handler, artifacts_handler, artifact_handler),
+    components(schemas(JobRecord, JobPhases, ProverCrash, JobLogLine, Artifact))
+)]
+struct Docs;
+
+pub fn create_docs() -> utoipa::openapi::OpenApi {
+    Docs::openapi()
+}
+
+pub fn create_router() -> Router<ProverState> {
+    Router::new()
+        .route("/", get(jobs_handler))
+        .route("/:id/log", get(log_handler))
+        .route("/:id/artifacts", get(artifacts_handler))
+        .route("/:id/artifacts/*path", get(artifact_handler))
+}
@@ -0,0 +1,21 @@
+use axum::Router;
+
+use crate::ProverState;
+
+mod aggregate;
+mod jobs;
+mod proof;
+
+pub fn create_docs() -> utoipa::openapi::OpenApi {
+    let mut docs = proof::create_docs();
+    docs.merge(aggregate::create_docs());
+    docs.merge(jobs::create_docs());
+    docs
+}
+
+pub fn create_router() -> Router<ProverState> {
+    Router::new()
+        .nest("/proof", proof::create_router())
+        .nest("/aggregate", aggregate::create_router())
+        .nest("/jobs", jobs::create_router())
+}
@@ -0,0 +1,363 @@
+use axum::{
+    debug_handler,
+    extract::{rejection::JsonRejection, Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use raiko_client::{ErrorCode, Problem};
+use serde::Deserialize;
+use serde_json::Value;
+use utoipa::{IntoParams, OpenApi};
+
+use crate::{
+    error::{HostError, HostResult},
+    jobs::{BatchId, BatchSummary, Job, JobId, JobSummary},
+    proof_output::{ProofFormat, ProofOutput},
+    rate_limit::InFlightPermit,
+    request::{BlockRange, ProofRequest, ProofRequestOpt},
+    ProverState,
+};
+
+/// The maximum number of blocks that can be proven with a single batch request.
+const MAX_BATCH_SIZE: u64 = 64;
+
+#[utoipa::path(post, path = "/v2/proof",
+    tag = "Proving",
+    request_body = ProofRequestOpt,
+    responses (
+        (status = 200, description = "Successfully queued a proof job for request", body = JobSummary),
+        (status = 400, description = "The proof request is invalid", body = Problem, content_type = "application/problem+json")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Submit a proof request for asynchronous generation.
+///
+/// Accepts the same proof request as `/proof`, but returns a job id immediately instead of
+/// waiting for the proof. Use `/v2/proof/{id}/status` to poll the progress and
+/// `/v2/proof/{id}` to fetch the final proof.
+async fn submit_handler(
+    State(ProverState {
+        opts,
+        jobs,
+        runtime,
+        ..
+    }): State<ProverState>,
+    permit: Option<Extension<InFlightPermit>>,
+    req: Result<Json<Value>, JsonRejection>,
+) -> HostResult<Json<JobSummary>> {
+    let Json(req) = req?;
+    ProofRequestOpt::check_fields(&req, &[])?;
+    // Override the existing proof request config from the config file and command line
+    // options with the request from the client.
+    let mut config = runtime.request_config(&opts.proof_request_opt);
+    config.merge(&req)?;
+
+    // Construct the actual proof request from the available configs.
+    let proof_request = ProofRequest::try_from(config)?;
+    proof_request.check_block(opts.max_block_age).await?;
+
+    Ok(Json(jobs.submit_admitted(
+        proof_request,
+        permit.map(|Extension(permit)| permit),
+    )?))
+}
+
+#[utoipa::path(post, path = "/v2/proof/batch",
+    tag = "Proving",
+    request_body = BlockRange,
+    responses (
+        (status = 200, description = "Successfully queued a proof job for every block in the range", body = BatchSummary),
+        (status = 400, description = "The proof request is invalid", body = Problem, content_type = "application/problem+json")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Submit proof requests for a range of blocks.
+///
+/// Accepts the same proof request as `/proof`, with `from_block` and `to_block` instead of
+/// `block_number`. Queues a proof job for every block and returns the batch of the jobs
+/// immediately. Use `/v2/proof/batch/{id}` to poll the batch, which has the proofs in block
+/// order once all jobs succeeded. With `aggregate`, the proofs are aggregated into a single
+/// proof instead, see `/v2/aggregate`.
+async fn batch_handler(
+    State(ProverState {
+        opts,
+        jobs,
+        runtime,
+        ..
+    }): State<ProverState>,
+    permit: Option<Extension<InFlightPermit>>,
+    req: Result<Json<Value>, JsonRejection>,
+) -> HostResult<Json<BatchSummary>> {
+    let Json(req) = req?;
+    ProofRequestOpt::check_fields(&req, &["from_block", "to_block", "aggregate"])?;
+    let range = BlockRange::deserialize(&req).map_err(|e| {
+        HostError::Validation(Problem::new(
+            ErrorCode::MalformedBody,
+            format!("Invalid block range: {e}"),
+        ))
+    })?;
+    if range.is_empty() || range.len() > MAX_BATCH_SIZE {
+        return Err(HostError::invalid(
+            ErrorCode::InvalidRequest,
+            "to_block",
+            format!("The block range must contain between 1 and {MAX_BATCH_SIZE} blocks"),
+        ));
+    }
+
+    // Override the existing proof request config from the config file and command line
+    // options with the request from the client.
+    let mut config = runtime.request_config(&opts.proof_request_opt);
+    config.merge(&req)?;
+
+    // Construct the actual proof requests for every block in the range.
+    let proof_requests = (range.from_block..=range.to_block)
+        .map(|block_number| {
+            let mut config = config.clone();
+            config.block_number = Some(block_number);
+            ProofRequest::try_from(config)
+        })
+        .collect::<HostResult<Vec<_>>>()?;
+    if range.aggregate && !proof_requests[0].proof_type.aggregates() {
+        return Err(HostError::invalid(
+            ErrorCode::InvalidRequest,
+            "aggregate",
+            format!(
+                "The {} prover can not aggregate proofs",
+                proof_requests[0].proof_type
+            ),
+        ));
+    }
+    // The first block is the oldest, the last one the newest of the range.
+    for proof_request in [
+        &proof_requests[0],
+        &proof_requests[proof_requests.len() - 1],
+    ] {
+        proof_request.check_block(opts.max_block_age).await?;
+    }
+
+    Ok(Json(jobs.submit_batch(
+        proof_requests,
+        range.aggregate,
+        permit.map(|Extension(permit)| permit),
+    )?))
+}
+
+#[utoipa::path(get, path = "/v2/proof/batch/{id}",
+    tag = "Proving",
+    params(
+        ("id" = u64, Path, description = "The batch id")
+    ),
+    responses (
+        (status = 200, description = "The batch including the proofs once it succeeded", body = BatchSummary),
+        (status = 404, description = "No batch with the given id exists")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Get a batch.
+///
+/// Returns the status of the batch and of the jobs of its blocks. Once all jobs succeeded,
+/// the batch has the proofs of the blocks in block order or, with `aggregate`, the aggregated
+/// proof. The batch fails as soon as one of its jobs does not succeed.
+async fn batch_status_handler(
+    State(ProverState { jobs, .. }): State<ProverState>,
+    Path(id): Path<BatchId>,
+) -> HostResult<Json<BatchSummary>> {
+    Ok(Json(jobs.batch(id)?))
+}
+
+#[utoipa::path(delete, path = "/v2/proof/batch/{id}",
+    tag = "Proving",
+    params(
+        ("id" = u64, Path, description = "The batch id")
+    ),
+    responses (
+        (status = 200, description = "The batch was cancelled", body = BatchSummary),
+        (status = 404, description = "No batch with the given id exists"),
+        (status = 409, description = "The batch already finished")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Cancel a batch.
+///
+/// Cancels the unfinished jobs of the batch like `DELETE /v2/proof/{id}`.
+async fn batch_cancel_handler(
+    State(ProverState { jobs, .. }): State<ProverState>,
+    Path(id): Path<BatchId>,
+) -> HostResult<Json<BatchSummary>> {
+    Ok(Json(jobs.cancel_batch(id)?))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct JobParams {
+    /// Only return the proof, in this format, instead of the job.
+    format: Option<ProofFormat>,
+    /// The tier of the proof in the `calldata` format, defaults to the tier of the proof type
+    /// in the relayer config.
+    tier: Option<u16>,
+}
+
+#[utoipa::path(get, path = "/v2/proof/{id}",
+    tag = "Proving",
+    params(
+        ("id" = u64, Path, description = "The proof job id"),
+        JobParams
+    ),
+    responses (
+        (status = 200, description = "The proof job including the proof once it succeeded"),
+        (status = 200, description = "The proof in the `json` format", body = ProofOutput),
+        (status = 200, description = "The proof in the `ssz` or `binary` format", content_type = "application/octet-stream"),
+        (status = 400, description = "The job has no proof in the format", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "No proof job with the given id exists")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Get a proof job.
+///
+/// Returns the full job record, including the original request without its prover params and
+/// the proof once the job has succeeded. With a `format`, returns only the proof of a succeeded job:
+/// - json - the proof with the transition it is for, the same for all proof types
+/// - calldata - the hex encoded call of `proveBlock`, ready to be sent to the TaikoL1 contract
+/// - ssz - the proof with the transition as an SSZ container
+/// - binary - the raw proof of the prover, e.g. the receipt of Risc0
+async fn job_handler(
+    State(ProverState { opts, jobs, .. }): State<ProverState>,
+    Path(id): Path<JobId>,
+    Query(params): Query<JobParams>,
+) -> HostResult<Response> {
+    let job = jobs.get(id)?;
+    let Some(format) = params.format else {
+        return Ok(Json(job.redacted()).into_response());
+    };
+    let Some(proof) = &job.proof else {
+        return Err(HostError::invalid(
+            ErrorCode::InvalidRequest,
+            "format",
+            format!("Job {id} has no proof, it is {}", job.status),
+        ));
+    };
+    let output = ProofOutput::new(&job.request, proof)?;
+    let octet_stream = |bytes: Vec<u8>| {
+        ([(header::CONTENT_TYPE, "application/octet-stream")], bytes).into_response()
+    };
+    Ok(match format {
+        ProofFormat::Json => Json(output).into_response(),
+        ProofFormat::Calldata => {
+            let tier = params
+                .tier
+                .or_else(|| {
+                    opts.relayer
+                        .as_ref()?
+                        .tiers
+                        .get(&job.request.proof_type.to_string())
+                        .copied()
+                })
+                .ok_or_else(|| {
+                    HostError::invalid(
+                        ErrorCode::MissingField,
+                        "tier",
+                        format!(
+                            "The relayer config has no tier for {} proofs",
+                            job.request.proof_type
+                        ),
+                    )
+                })?;
+            Json(output.calldata(tier)?).into_response()
+        }
+        ProofFormat::Ssz => octet_stream(output.to_ssz()),
+        ProofFormat::Binary => octet_stream(output.binary().to_vec()),
+    })
+}
+
+#[utoipa::path(get, path = "/v2/proof/{id}/status",
+    tag = "Proving",
+    params(
+        ("id" = u64, Path, description = "The proof job id")
+    ),
+    responses (
+        (status = 200, description = "The current status of the proof job", body = JobSummary),
+        (status = 404, description = "No proof job with the given id exists")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Get the status of a proof job.
+///
+/// The job goes through the following states:
+/// - queued - waiting for a free prover slot
+/// - running - the proof is being generated
+/// - success - the proof is available at `/v2/proof/{id}`
+/// - failed - the proof generation failed, see the `error` field
+/// - cancelled - the job was cancelled with `DELETE /v2/proof/{id}`
+async fn status_handler(
+    State(ProverState { jobs, .. }): State<ProverState>,
+    Path(id): Path<JobId>,
+) -> HostResult<Json<JobSummary>> {
+    Ok(Json(JobSummary::from(&jobs.get(id)?)))
+}
+
+#[utoipa::path(delete, path = "/v2/proof/{id}",
+    tag = "Proving",
+    params(
+        ("id" = u64, Path, description = "The proof job id")
+    ),
+    responses (
+        (status = 200, description = "The proof job was cancelled", body = JobSummary),
+        (status = 404, description = "No proof job with the given id exists"),
+        (status = 409, description = "The proof job already finished")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Cancel a proof job.
+///
+/// Removes a queued job from the queue or aborts the running prover and frees its prover
+/// slot. The job ends up in the `cancelled` state.
+async fn cancel_handler(
+    State(ProverState { jobs, .. }): State<ProverState>,
+    Path(id): Path<JobId>,
+) -> HostResult<Json<JobSummary>> {
+    Ok(Json(jobs.cancel(id)?))
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        submit_handler,
+        batch_handler,
+        batch_status_handler,
+        batch_cancel_handler,
+        job_handler,
+        status_handler,
+        cancel_handler
+    ),
+    components(schemas(
+        JobSummary,
+        BatchSummary,
+        crate::jobs::JobStatus,
+        crate::relayer::Submission,
+        crate::relayer::SubmissionStatus,
+        crate::upload::Upload,
+        ProofOutput,
+        crate::proof_output::ProvenTransition,
+        ProofFormat,
+        BlockRange
+    ))
+)]
+struct Docs;
+
+pub fn create_docs() -> utoipa::openapi::OpenApi {
+    Docs::openapi()
+}
+
+pub fn create_router() -> Router<ProverState> {
+    Router::new()
+        .route("/", post(submit_handler))
+        .route("/batch", post(batch_handler))
+        .route(
+            "/batch/:id",
+            get(batch_status_handler).delete(batch_cancel_handler),
+        )
+        .route("/:id", get(job_handler).delete(cancel_handler))
+        .route("/:id/status", get(status_handler))
+}
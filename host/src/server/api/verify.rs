@@ -0,0 +1,99 @@
+use axum::{
+    debug_handler,
+    extract::{rejection::JsonRejection, DefaultBodyLimit, State},
+    routing::post,
+    Json, Router,
+};
+use raiko_client::{ErrorCode, Problem};
+use serde::Deserialize;
+use serde_json::Value;
+use utoipa::OpenApi;
+
+use crate::{
+    error::{HostError, HostResult},
+    request::{ProofRequest, ProofRequestOpt, ProofVerificationRequest},
+    server::api::MAX_AGGREGATE_BODY_SIZE,
+    verify::{check_protocol_instance, verify_proof, ProofVerification, ProtocolInstanceCheck},
+    ProverState,
+};
+
+#[utoipa::path(post, path = "/verify/protocol-instance",
+    tag = "Proving",
+    request_body = ProofRequestOpt,
+    responses (
+        (status = 200, description = "The protocol instance of the block, checked against the proposed metadata", body = ProtocolInstanceCheck),
+        (status = 400, description = "The request is invalid", body = Problem, content_type = "application/problem+json")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Check the protocol instance of a block.
+///
+/// Assembles the protocol instance of the block of the proof request from the nodes, without
+/// executing the block, and compares its metadata with the metadata of the `BlockProposed`
+/// event on L1. Returns whether they match, the fields which differ, and the metadata hash and
+/// public input hash raiko proves the block with.
+async fn protocol_instance_handler(
+    State(ProverState { opts, runtime, .. }): State<ProverState>,
+    req: Result<Json<Value>, JsonRejection>,
+) -> HostResult<Json<ProtocolInstanceCheck>> {
+    let Json(req) = req?;
+    ProofRequestOpt::check_fields(&req, &[])?;
+    let mut config = runtime.request_config(&opts.proof_request_opt);
+    config.merge(&req)?;
+    let proof_request = ProofRequest::try_from(config)?;
+    Ok(Json(check_protocol_instance(&proof_request).await?))
+}
+
+#[utoipa::path(post, path = "/verify/proof",
+    tag = "Proving",
+    request_body = ProofVerificationRequest,
+    responses (
+        (status = 200, description = "Whether the proof verifies against the public inputs of its block", body = ProofVerification),
+        (status = 400, description = "The request is invalid", body = Problem, content_type = "application/problem+json")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Verify a proof.
+///
+/// Verifies a proof produced before, as returned by `/proof` or `/v2/proof`, against the public
+/// inputs of the block of the proof request, without proving the block again. The block and
+/// the proof type are taken from the request, falling back to the host config.
+async fn proof_handler(
+    State(ProverState { opts, runtime, .. }): State<ProverState>,
+    req: Result<Json<Value>, JsonRejection>,
+) -> HostResult<Json<ProofVerification>> {
+    let Json(req) = req?;
+    ProofRequestOpt::check_fields(&req, &["proof"])?;
+    let ProofVerificationRequest { proof } =
+        ProofVerificationRequest::deserialize(&req).map_err(|e| {
+            HostError::invalid(
+                ErrorCode::MalformedBody,
+                "proof",
+                format!("Invalid proof: {e}"),
+            )
+        })?;
+    let mut config = runtime.request_config(&opts.proof_request_opt);
+    config.merge(&req)?;
+    let proof_request = ProofRequest::try_from(config)?;
+    Ok(Json(verify_proof(&proof_request, proof).await?))
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(protocol_instance_handler, proof_handler),
+    components(schemas(ProtocolInstanceCheck, ProofVerificationRequest, ProofVerification))
+)]
+struct Docs;
+
+pub fn create_docs() -> utoipa::openapi::OpenApi {
+    Docs::openapi()
+}
+
+pub fn create_router() -> Router<ProverState> {
+    Router::new()
+        .route("/protocol-instance", post(protocol_instance_handler))
+        .route(
+            "/proof",
+            post(proof_handler).layer(DefaultBodyLimit::max(MAX_AGGREGATE_BODY_SIZE as usize)),
+        )
+}
@@ -0,0 +1,87 @@
+use axum::{
+    body::Bytes,
+    debug_handler,
+    extract::{DefaultBodyLimit, State},
+    http::{header::AUTHORIZATION, HeaderMap},
+    routing::post,
+    Json, Router,
+};
+use raiko_lib::prover::Proof;
+use serde_json::Value;
+use utoipa::OpenApi;
+
+use crate::{
+    error::{HostError, HostResult},
+    execution::generate_proof,
+    gpu,
+    jobs::progress::ProgressSender,
+    workers::{ProofTask, MAX_TASK_SIZE},
+    ProverState,
+};
+
+#[utoipa::path(post, path = "/worker/prove",
+    tag = "Proving",
+    request_body(
+        content = Vec<u8>,
+        content_type = "application/octet-stream",
+        description = "The proof request and the input of the block, encoded like the inputs of the input cache"
+    ),
+    responses (
+        (status = 200, description = "The generated proof", body = Value),
+        (status = 401, description = "The request doesn't have the key of the coordinator"),
+        (status = 424, description = "The prover failed to generate the proof"),
+        (status = 503, description = "The worker is shutting down")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Generate a proof for the coordinator.
+///
+/// Called by the coordinator this host is a worker of, see `--coordinator`, with the
+/// `--worker-api-key` they share. Runs the block with the input prepared by the coordinator
+/// and generates the proof with the prover of the request, once the prover has a free slot.
+async fn prove_handler(
+    State(ProverState { jobs, opts, .. }): State<ProverState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> HostResult<Json<Proof>> {
+    let key = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if key.is_none() || key != opts.worker_api_key.as_deref() {
+        return Err(HostError::Unauthorized(
+            "Missing or invalid key of the coordinator".to_string(),
+        ));
+    }
+    if jobs.is_draining() {
+        return Err(HostError::ShuttingDown);
+    }
+    let (proof_request, input) = ProofTask::decode(&body)?;
+    let slot = jobs
+        .acquire(
+            &proof_request.proof_type,
+            proof_request.priority,
+            proof_request.deadline,
+        )
+        .await?;
+    let proof = gpu::on_device(
+        slot.cuda_device(),
+        generate_proof(&proof_request, &input, &ProgressSender::default()),
+    )
+    .await?;
+    Ok(Json(proof))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(prove_handler))]
+struct Docs;
+
+pub fn create_docs() -> utoipa::openapi::OpenApi {
+    Docs::openapi()
+}
+
+pub fn create_router() -> Router<ProverState> {
+    Router::new()
+        .route("/prove", post(prove_handler))
+        .layer(DefaultBodyLimit::max(MAX_TASK_SIZE as usize))
+}
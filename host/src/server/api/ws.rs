@@ -0,0 +1,100 @@
+use axum::{
+    debug_handler,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, State,
+    },
+    response::Response,
+    routing::get,
+    Router,
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+use utoipa::OpenApi;
+
+use crate::{
+    error::HostResult,
+    jobs::{progress::ProgressEvent, JobId, JobSummary},
+    ProverState,
+};
+
+#[utoipa::path(get, path = "/ws/proof/{id}",
+    tag = "Proving",
+    params(
+        ("id" = u64, Path, description = "The id of the proof job")
+    ),
+    responses (
+        (status = 101, description = "Switched to a WebSocket streaming the job progress events", body = ProgressEvent),
+        (status = 404, description = "No job with the given id exists")
+    )
+)]
+#[debug_handler(state = ProverState)]
+/// Stream the progress of a proof job.
+///
+/// Upgrades the connection to a WebSocket which sends the current job status first, followed
+/// by one JSON message per progress event. The socket is closed once the job finished.
+async fn proof_progress_handler(
+    State(ProverState { jobs, .. }): State<ProverState>,
+    Path(id): Path<JobId>,
+    ws: WebSocketUpgrade,
+) -> HostResult<Response> {
+    let (summary, receiver) = jobs.subscribe(id)?;
+    Ok(ws.on_upgrade(move |socket| stream_progress(socket, summary, receiver)))
+}
+
+async fn stream_progress(
+    mut socket: WebSocket,
+    summary: JobSummary,
+    receiver: Option<broadcast::Receiver<ProgressEvent>>,
+) {
+    let status = ProgressEvent::Status {
+        status: summary.status,
+    };
+    if send_event(&mut socket, &status).await {
+        if let Some(mut receiver) = receiver {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => {
+                        if !send_event(&mut socket, &event).await {
+                            break;
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "Progress subscriber for job {} skipped {skipped} events",
+                            summary.id
+                        );
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+    let _ = socket.close().await;
+}
+
+/// Send the event over the socket. Returns whether the client is still connected.
+async fn send_event(socket: &mut WebSocket, event: &ProgressEvent) -> bool {
+    match serde_json::to_string(event) {
+        Ok(message) => socket.send(Message::Text(message)).await.is_ok(),
+        Err(e) => {
+            warn!("Could not serialize progress event: {e}");
+            true
+        }
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(proof_progress_handler),
+    components(schemas(ProgressEvent, crate::jobs::JobStatus))
+)]
+struct Docs;
+
+pub fn create_docs() -> utoipa::openapi::OpenApi {
+    Docs::openapi()
+}
+
+pub fn create_router() -> Router<ProverState> {
+    Router::new().route("/proof/:id", get(proof_progress_handler))
+}
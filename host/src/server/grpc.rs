@@ -0,0 +1,375 @@
+//! The gRPC API of the proof jobs, see `proto/raiko/v1/proof.proto`.
+//!
+//! The service mirrors the `/v2/proof` endpoints and shares the job manager with the REST
+//! server, so jobs submitted through either API show up in both.
+
+use std::{net::SocketAddr, pin::Pin};
+
+use futures::{stream, Stream, StreamExt};
+use raiko_proto::v1::{
+    self as proto,
+    progress_event::Event,
+    proof_service_server::{ProofService, ProofServiceServer},
+};
+use serde_json::{Map, Value};
+use tokio::sync::broadcast::error::RecvError;
+use tonic::{transport::Server, Request, Response, Status};
+use tracing::{info, warn};
+
+use crate::{
+    error::HostError,
+    jobs::{progress::ProgressEvent, Job, JobStatus, JobSummary},
+    rate_limit::InFlightPermit,
+    relayer::{Submission, SubmissionStatus},
+    request::ProofRequest,
+    ProverState,
+};
+
+/// Serve the gRPC API on the given address.
+pub async fn serve(state: ProverState, addr: SocketAddr) -> anyhow::Result<()> {
+    info!("gRPC API listening on: {addr}");
+    Server::builder()
+        .add_service(ProofServiceServer::new(GrpcService { state }))
+        .serve(addr)
+        .await?;
+    Ok(())
+}
+
+struct GrpcService {
+    state: ProverState,
+}
+
+impl GrpcService {
+    /// Check the JWT in the `authorization` metadata and the API key in the `x-api-key` or
+    /// `authorization` metadata, like the REST API.
+    fn authorize<T>(&self, request: &Request<T>, proof: bool) -> Result<(), Status> {
+        let metadata = request.metadata();
+        if let Some(jwt) = &self.state.jwt {
+            jwt.validate_header(
+                metadata
+                    .get("authorization")
+                    .and_then(|value| value.to_str().ok()),
+            )?;
+        }
+        let key = metadata
+            .get("x-api-key")
+            .or_else(|| metadata.get("authorization"))
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.strip_prefix("Bearer ").unwrap_or(value));
+        self.state.api_keys.authorize(key, false, proof)?;
+        Ok(())
+    }
+
+    /// Admit a proof request under the rate limits of the REST API, see [crate::rate_limit].
+    fn admit<T>(&self, request: &Request<T>) -> Result<Option<InFlightPermit>, Status> {
+        let forwarded_for = request
+            .metadata()
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+        let forwarded_for = Some(forwarded_for.as_str()).filter(|value| !value.is_empty());
+        let peer = request.remote_addr().map(|addr| addr.ip());
+        let rate_limiter = &self.state.rate_limiter;
+        let permit = rate_limiter
+            .admit(rate_limiter.client(forwarded_for, peer))
+            .map_err(|retry_after| {
+                let retry_after = (retry_after.as_secs_f64().ceil() as u64).max(1);
+                HostError::TooManyRequests(format!("Rate limit reached, retry in {retry_after}s"))
+            })?;
+        Ok(permit)
+    }
+}
+
+type ProgressStream = Pin<Box<dyn Stream<Item = Result<proto::ProgressEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl ProofService for GrpcService {
+    type WatchJobStream = ProgressStream;
+
+    async fn submit(
+        &self,
+        request: Request<proto::SubmitRequest>,
+    ) -> Result<Response<proto::JobSummary>, Status> {
+        self.authorize(&request, true)?;
+        let permit = self.admit(&request)?;
+        // Override the proof request config from the config file and command line options
+        // with the request, like the REST API does.
+        let mut config = self
+            .state
+            .runtime
+            .request_config(&self.state.opts.proof_request_opt);
+        config.merge(&submit_request_value(request.into_inner())?)?;
+        let proof_request = ProofRequest::try_from(config)?;
+        proof_request
+            .check_block(self.state.opts.max_block_age)
+            .await?;
+        self.state.jobs.check_queue()?;
+        let summary = self.state.jobs.submit_admitted(proof_request, permit)?;
+        Ok(Response::new(summary.into()))
+    }
+
+    async fn get_job(
+        &self,
+        request: Request<proto::JobRequest>,
+    ) -> Result<Response<proto::Job>, Status> {
+        self.authorize(&request, false)?;
+        let job = self.state.jobs.get(request.into_inner().id)?;
+        Ok(Response::new(job_message(&job)?))
+    }
+
+    async fn watch_job(
+        &self,
+        request: Request<proto::JobRequest>,
+    ) -> Result<Response<Self::WatchJobStream>, Status> {
+        self.authorize(&request, false)?;
+        let id = request.into_inner().id;
+        let (summary, receiver) = self.state.jobs.subscribe(id)?;
+        let status = stream::once(async move {
+            Ok(ProgressEvent::Status {
+                status: summary.status,
+            }
+            .into())
+        });
+        let Some(receiver) = receiver else {
+            return Ok(Response::new(status.boxed()));
+        };
+        let events = stream::unfold(receiver, move |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((Ok(event.into()), receiver)),
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Progress subscriber for job {id} skipped {skipped} events");
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        Ok(Response::new(status.chain(events).boxed()))
+    }
+
+    async fn cancel(
+        &self,
+        request: Request<proto::JobRequest>,
+    ) -> Result<Response<proto::JobSummary>, Status> {
+        self.authorize(&request, false)?;
+        let summary = self.state.jobs.cancel(request.into_inner().id)?;
+        Ok(Response::new(summary.into()))
+    }
+
+    async fn list_jobs(
+        &self,
+        request: Request<proto::ListJobsRequest>,
+    ) -> Result<Response<proto::ListJobsResponse>, Status> {
+        self.authorize(&request, false)?;
+        let status = match request.into_inner().status {
+            Some(status) => Some(job_status(status)?),
+            None => None,
+        };
+        let jobs = self
+            .state
+            .jobs
+            .list(status)?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        Ok(Response::new(proto::ListJobsResponse { jobs }))
+    }
+}
+
+/// The JSON of the fields set in the request, to merge into the proof request config.
+fn submit_request_value(request: proto::SubmitRequest) -> Result<Value, HostError> {
+    let mut value = Map::new();
+    let mut set = |key: &str, field: Option<Value>| {
+        if let Some(field) = field {
+            value.insert(key.to_string(), field);
+        }
+    };
+    set("block_number", request.block_number.map(Into::into));
+    set("network", request.network.map(Into::into));
+    set("l1_network", request.l1_network.map(Into::into));
+    set("rpc", request.rpc.map(Into::into));
+    set("l1_rpc", request.l1_rpc.map(Into::into));
+    set("beacon_rpc", request.beacon_rpc.map(Into::into));
+    set("graffiti", request.graffiti.map(Into::into));
+    set("prover", request.prover.map(Into::into));
+    set("proof_type", request.proof_type.map(Into::into));
+    set("compress", request.compress.map(Into::into));
+    set("priority", request.priority.map(Into::into));
+    set("deadline", request.deadline.map(Into::into));
+    set("force", request.force.map(Into::into));
+    set("cross_check", request.cross_check.map(Into::into));
+    set("profile_guest", request.profile_guest.map(Into::into));
+    set("callback_url", request.callback_url.map(Into::into));
+    set("guest", request.guest.map(Into::into));
+    if let Some(prover_args) = request.prover_args {
+        let prover_args: Value = serde_json::from_str(&prover_args)
+            .map_err(|e| HostError::InvalidRequestConfig(format!("Invalid prover_args: {e}")))?;
+        set("prover_args", Some(prover_args));
+    }
+    Ok(Value::Object(value))
+}
+
+fn job_message(job: &Job) -> Result<proto::Job, HostError> {
+    Ok(proto::Job {
+        summary: Some(JobSummary::from(job).into()),
+        request: serde_json::to_string(&job.request.redacted())?,
+        proof: job.proof.as_ref().map(serde_json::to_string).transpose()?,
+    })
+}
+
+fn job_status(status: i32) -> Result<JobStatus, Status> {
+    match proto::JobStatus::try_from(status) {
+        Ok(proto::JobStatus::Queued) => Ok(JobStatus::Queued),
+        Ok(proto::JobStatus::Running) => Ok(JobStatus::Running),
+        Ok(proto::JobStatus::Success) => Ok(JobStatus::Success),
+        Ok(proto::JobStatus::Failed) => Ok(JobStatus::Failed),
+        Ok(proto::JobStatus::Cancelled) => Ok(JobStatus::Cancelled),
+        _ => Err(Status::invalid_argument(format!(
+            "Invalid job status: {status}"
+        ))),
+    }
+}
+
+impl From<JobStatus> for proto::JobStatus {
+    fn from(status: JobStatus) -> Self {
+        match status {
+            JobStatus::Queued => proto::JobStatus::Queued,
+            JobStatus::Running => proto::JobStatus::Running,
+            JobStatus::Success => proto::JobStatus::Success,
+            JobStatus::Failed => proto::JobStatus::Failed,
+            JobStatus::Cancelled => proto::JobStatus::Cancelled,
+        }
+    }
+}
+
+impl From<JobSummary> for proto::JobSummary {
+    fn from(summary: JobSummary) -> Self {
+        Self {
+            id: summary.id,
+            status: proto::JobStatus::from(summary.status).into(),
+            error: summary.error,
+            retries: summary.retries,
+            submission: summary.submission.map(Into::into),
+            created_at: summary.created_at,
+            updated_at: summary.updated_at,
+        }
+    }
+}
+
+impl From<Submission> for proto::Submission {
+    fn from(submission: Submission) -> Self {
+        let status = match submission.status {
+            SubmissionStatus::Pending => proto::SubmissionStatus::Pending,
+            SubmissionStatus::Confirmed => proto::SubmissionStatus::Confirmed,
+            SubmissionStatus::Failed => proto::SubmissionStatus::Failed,
+        };
+        Self {
+            status: status.into(),
+            tx_hash: submission.tx_hash.map(|hash| hash.to_string()),
+            from: submission.from.to_string(),
+            nonce: submission.nonce,
+            gas_price: submission.gas_price.map(|price| price.to_string()),
+            attempts: submission.attempts,
+            block_number: submission.block_number,
+            gas_used: submission.gas_used.map(|gas| gas.to_string()),
+            error: submission.error,
+        }
+    }
+}
+
+impl From<ProgressEvent> for proto::ProgressEvent {
+    fn from(event: ProgressEvent) -> Self {
+        let event = match event {
+            ProgressEvent::Status { status } => {
+                Event::Status(proto::JobStatus::from(status).into())
+            }
+            ProgressEvent::PreflightStarted => Event::PreflightStarted(true),
+            ProgressEvent::InputPrepared => Event::InputPrepared(true),
+            ProgressEvent::GuestExecution { percent } => Event::GuestExecution(percent.into()),
+            ProgressEvent::RemoteSession {
+                service,
+                session_id,
+            } => Event::RemoteSession(proto::RemoteSession {
+                service,
+                session_id,
+            }),
+            ProgressEvent::GuestExecuted { cycles, segments } => {
+                Event::GuestExecuted(proto::GuestExecuted { cycles, segments })
+            }
+            ProgressEvent::ProofGenerated => Event::ProofGenerated(true),
+            ProgressEvent::ProofVerified => Event::ProofVerified(true),
+            ProgressEvent::Finished { status } => {
+                Event::Finished(proto::JobStatus::from(status).into())
+            }
+        };
+        Self { event: Some(event) }
+    }
+}
+
+impl From<HostError> for Status {
+    /// The gRPC codes of the HTTP status codes of the REST API.
+    fn from(e: HostError) -> Self {
+        match e {
+            HostError::InvalidProofType(_)
+            | HostError::InvalidRequestConfig(_)
+            | HostError::InvalidAddress(_)
+            | HostError::InvalidQuote(_)
+            | HostError::Validation(_) => Status::invalid_argument(e.to_string()),
+            HostError::JobNotFound(_) | HostError::BatchNotFound(_) => {
+                Status::not_found(e.to_string())
+            }
+            HostError::JobAlreadyFinished(_) | HostError::BatchAlreadyFinished(_) => {
+                Status::failed_precondition(e.to_string())
+            }
+            HostError::Unauthorized(_) => Status::unauthenticated(e.to_string()),
+            HostError::Forbidden(_) => Status::permission_denied(e.to_string()),
+            HostError::TooManyRequests(_) | HostError::ResourceExhausted(_) => {
+                Status::resource_exhausted(e.to_string())
+            }
+            HostError::ProverCrashed(ref crash) if crash.out_of_memory => {
+                Status::resource_exhausted(e.to_string())
+            }
+            HostError::FeatureNotSupportedError(_) => Status::unimplemented(e.to_string()),
+            HostError::GuestError(_)
+            | HostError::ProverCrashed(_)
+            | HostError::ShuttingDown
+            | HostError::QueueFull(_)
+            | HostError::CircuitOpen(..) => Status::unavailable(e.to_string()),
+            HostError::Io(_)
+            | HostError::Serde(_)
+            | HostError::JoinHandle(_)
+            | HostError::Anyhow(_) => Status::internal(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_submit_request_value() {
+        let value = submit_request_value(proto::SubmitRequest {
+            block_number: Some(10),
+            proof_type: Some("sgx".to_string()),
+            prover_args: Some(r#"{"sgx": {"instance_id": 1}}"#.to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "block_number": 10,
+                "proof_type": "sgx",
+                "prover_args": {"sgx": {"instance_id": 1}},
+            })
+        );
+        assert!(submit_request_value(proto::SubmitRequest {
+            prover_args: Some("{".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
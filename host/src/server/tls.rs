@@ -0,0 +1,93 @@
+//! TLS termination of the REST API, with optional client certificate verification.
+
+use std::{fs::File, io::BufReader, net::SocketAddr, path::Path, sync::Arc};
+
+use anyhow::{anyhow, Context, Result};
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::{
+    server::AllowAnyAuthenticatedClient, Certificate, PrivateKey, RootCertStore, ServerConfig,
+};
+use rustls_pemfile::Item;
+use tracing::info;
+
+use crate::server::TlsConfig;
+
+/// Serve the router over TLS on the given address.
+pub async fn serve(router: Router, addr: SocketAddr, config: &TlsConfig) -> Result<()> {
+    let rustls_config = RustlsConfig::from_config(Arc::new(server_config(config)?));
+    info!(
+        "Serving TLS{} on: {addr}",
+        if config.client_ca.is_some() {
+            " with client certificates"
+        } else {
+            ""
+        }
+    );
+    axum_server::bind_rustls(addr, rustls_config)
+        .serve(router.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .context("Server couldn't serve")
+}
+
+fn server_config(config: &TlsConfig) -> Result<ServerConfig> {
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = match &config.client_ca {
+        Some(client_ca) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(client_ca)? {
+                roots.add(&cert)?;
+            }
+            builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots).boxed())
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut server_config = builder
+        .with_single_cert(load_certs(&config.cert)?, load_private_key(&config.key)?)
+        .context("Invalid TLS certificate or key")?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(server_config)
+}
+
+/// The certificates of a PEM file.
+fn load_certs(path: &Path) -> Result<Vec<Certificate>> {
+    let mut reader = BufReader::new(
+        File::open(path).with_context(|| format!("Could not open {}", path.display()))?,
+    );
+    let certs: Vec<_> = rustls_pemfile::certs(&mut reader)?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    if certs.is_empty() {
+        return Err(anyhow!("No certificates in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+/// The first PKCS#1, PKCS#8 or SEC1 private key of a PEM file.
+fn load_private_key(path: &Path) -> Result<PrivateKey> {
+    let mut reader = BufReader::new(
+        File::open(path).with_context(|| format!("Could not open {}", path.display()))?,
+    );
+    while let Some(item) = rustls_pemfile::read_one(&mut reader)? {
+        if let Item::RSAKey(key) | Item::PKCS8Key(key) | Item::ECKey(key) = item {
+            return Ok(PrivateKey(key));
+        }
+    }
+    Err(anyhow!("No private key in {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_pem_items() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("empty.pem");
+        std::fs::write(&path, "not a pem file\n").unwrap();
+        assert!(load_certs(&path).is_err());
+        assert!(load_private_key(&path).is_err());
+        assert!(load_certs(&dir.path().join("missing.pem")).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+//! Proving soft blocks, the blocks that are not proposed on L1 yet.
+//!
+//! A preconfirmed block is promised to its users before its tx list is proposed, and proving it
+//! right away takes the proving time off the path to the proposal. The node may not even have
+//! the block, so instead of fetching it, `POST /proof/soft` takes the block in the request body
+//! as a [SoftBlock]. Only its parent, the state it accesses and the L1 block of its anchor tx
+//! are fetched from the nodes. Before it is proven, the block is checked
+//!
+//! - on its own, e.g. that its anchor tx is complete and its gas fits its gas limit,
+//! - against its parent on the node, by the parent hash and the state root hint of the request,
+//! - and by executing it, which has to reproduce its hash,
+//!
+//! failing the request with `invalid_block` otherwise. The block is proven for the metadata of
+//! the proposal of its tx list in calldata.
+
+use alloy_consensus::{Header as AlloyConsensusHeader, Sealable};
+use alloy_primitives::{Address, Bytes, B256};
+use alloy_rpc_types::Transaction as AlloyRpcTransaction;
+use raiko_client::ErrorCode;
+use raiko_lib::{
+    builder::{BlockBuilderStrategy, TaikoStrategy},
+    consts::ChainSpec,
+    input::{GuestInput, TaikoProverData},
+};
+use serde::{Deserialize, Serialize};
+use tracing::Span;
+use utoipa::ToSchema;
+
+use crate::{
+    chain_registry::get_chain,
+    error::{HostError, HostResult},
+    header_store::get_store,
+    l1_events,
+    preflight::{get_block_header, soft_block_preflight},
+    request::{ProofRequest, ProofRequestOpt},
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// A block that is not proposed yet, with everything of it the preflight would fetch from the
+/// nodes.
+pub struct SoftBlock {
+    pub number: u64,
+    /// The hash of the block, which executing the block has to reproduce.
+    #[schema(value_type = String)]
+    pub hash: B256,
+    /// The hash of the block before it, which has to be on the node.
+    #[schema(value_type = String)]
+    pub parent_hash: B256,
+    /// The state root of the parent, checked against the parent on the node when set.
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    pub parent_state_root: Option<B256>,
+    #[schema(value_type = String)]
+    pub beneficiary: Address,
+    pub gas_limit: u64,
+    pub gas_used: u64,
+    pub timestamp: u64,
+    #[serde(default)]
+    #[schema(value_type = String)]
+    pub extra_data: Bytes,
+    #[schema(value_type = String)]
+    pub mix_hash: B256,
+    pub base_fee_per_gas: u64,
+    /// The signed anchor tx, the first tx of the block, as returned by the node.
+    #[schema(value_type = Object)]
+    pub anchor_tx: AlloyRpcTransaction,
+    /// The compressed tx list of the block without the anchor tx, as it is to be proposed in
+    /// calldata.
+    #[schema(value_type = String)]
+    pub tx_list: Bytes,
+    /// The min tier of the proposal.
+    #[serde(default)]
+    pub min_tier: u16,
+    /// The meta hash of the proposal of the parent.
+    #[serde(default)]
+    #[schema(value_type = String)]
+    pub parent_meta_hash: B256,
+    /// The account proposing the block.
+    #[serde(default)]
+    #[schema(value_type = String)]
+    pub sender: Address,
+}
+
+impl SoftBlock {
+    /// Check the block is consistent on its own, before anything is fetched for it.
+    fn check(&self, chain_spec: &ChainSpec) -> Result<(), String> {
+        let number = self.number;
+        if number == 0 {
+            return Err("The genesis block is not proposed".to_string());
+        }
+        if self.extra_data.len() > 32 {
+            return Err(format!(
+                "The extra data of block {number} is {} bytes, more than 32",
+                self.extra_data.len()
+            ));
+        }
+        if self.gas_used > self.gas_limit {
+            return Err(format!(
+                "Block {number} used {} gas, more than its gas limit {}",
+                self.gas_used, self.gas_limit
+            ));
+        }
+        // The proposal of the block has its gas limit without the anchor gas limit, as a u32.
+        match self.gas_limit.checked_sub(chain_spec.anchor_gas_limit) {
+            None | Some(0) => {
+                return Err(format!(
+                    "The gas limit {} of block {number} leaves no gas after the anchor gas limit \
+                     {}",
+                    self.gas_limit, chain_spec.anchor_gas_limit
+                ));
+            }
+            Some(gas_limit) if u32::try_from(gas_limit).is_err() => {
+                return Err(format!(
+                    "The gas limit {} of block {number} is above the max gas limit of a proposal",
+                    self.gas_limit
+                ));
+            }
+            Some(_) => {}
+        }
+
+        let anchor = &self.anchor_tx;
+        if anchor.chain_id != Some(chain_spec.chain_id) {
+            return Err(format!(
+                "The anchor tx of block {number} is of chain {:?}, not of chain {}",
+                anchor.chain_id, chain_spec.chain_id
+            ));
+        }
+        let missing = [
+            ("to", anchor.to.is_none()),
+            ("signature", anchor.signature.is_none()),
+            ("maxFeePerGas", anchor.max_fee_per_gas.is_none()),
+            (
+                "maxPriorityFeePerGas",
+                anchor.max_priority_fee_per_gas.is_none(),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(field, missing)| missing.then_some(field))
+        .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(format!(
+                "The anchor tx of block {number} is missing {}",
+                missing.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check the block follows the parent on the node.
+    fn check_parent(&self, parent: &AlloyConsensusHeader) -> Result<(), String> {
+        let number = self.number;
+        let parent_hash = parent.hash();
+        if parent_hash != self.parent_hash {
+            return Err(format!(
+                "The parent hash {} of block {number} is not the hash {parent_hash} of block {} on \
+                 the node",
+                self.parent_hash, parent.number
+            ));
+        }
+        if let Some(state_root) = self
+            .parent_state_root
+            .filter(|state_root| *state_root != parent.state_root)
+        {
+            return Err(format!(
+                "The parent state root {state_root} of block {number} is not the state root {} \
+                 of its parent on the node",
+                parent.state_root
+            ));
+        }
+        if self.timestamp < parent.timestamp {
+            return Err(format!(
+                "The timestamp {} of block {number} is before the timestamp {} of its parent",
+                self.timestamp, parent.timestamp
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The proof request to prove the soft block with, the other options are taken from the
+/// request options. The block is not on the chain, so it is not cross checked.
+pub fn soft_block_request(
+    mut request_opt: ProofRequestOpt,
+    block: &SoftBlock,
+) -> HostResult<ProofRequest> {
+    request_opt.block_number = Some(block.number);
+    let mut proof_request = ProofRequest::try_from(request_opt)?;
+    proof_request.cross_check = false;
+    Ok(proof_request)
+}
+
+/// Check the soft block and prepare its input with the preflight.
+pub async fn prepare_input(
+    proof_request: &ProofRequest,
+    block: SoftBlock,
+) -> HostResult<GuestInput> {
+    let invalid = |detail| HostError::invalid(ErrorCode::InvalidBlock, "block", detail);
+    let chain = get_chain(&proof_request.network)?;
+    if !chain.network.is_taiko() {
+        return Err(HostError::invalid(
+            ErrorCode::InvalidRequest,
+            "network",
+            format!("The blocks of {} are not proposed", chain.name),
+        ));
+    }
+    block.check(&chain.chain_spec).map_err(invalid)?;
+    let parent = get_block_header(&proof_request.rpc, block.number - 1).await?;
+    block.check_parent(&parent).map_err(invalid)?;
+
+    let (number, hash) = (block.number, block.hash);
+    let (rpc, l1_rpc) = (proof_request.rpc.clone(), proof_request.l1_rpc.clone());
+    let prover_data = TaikoProverData {
+        graffiti: proof_request.graffiti,
+        prover: proof_request.prover,
+    };
+    let header_store = get_store(&chain.name);
+    // Keep the span of the request, so the preflight logs carry its trace id.
+    let span = Span::current();
+    let (input, executed) = tokio::task::spawn_blocking(move || {
+        let _span = span.enter();
+        let input = soft_block_preflight(
+            rpc,
+            l1_rpc,
+            &block,
+            chain.network,
+            chain.chain_spec,
+            chain.preflight,
+            &header_store,
+            prover_data,
+        )?;
+        // The prover would spend hours on a block that doesn't reproduce its hash.
+        let executed = TaikoStrategy::build_from(&input).map(|(header, _)| header.hash());
+        Ok::<_, HostError>((input, executed))
+    })
+    .await??;
+    match executed {
+        Ok(executed) if executed == hash => {
+            l1_events::check_soft_block(proof_request, &input).await?;
+            Ok(input)
+        }
+        Ok(executed) => Err(invalid(format!(
+            "Block {number} executes to the hash {executed}, not to its hash {hash}"
+        ))),
+        Err(e) => Err(invalid(format!("Block {number} fails to execute: {e}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use raiko_lib::consts::{get_network_spec, Network};
+
+    use super::*;
+
+    fn block(chain_spec: &ChainSpec, parent: &AlloyConsensusHeader) -> SoftBlock {
+        SoftBlock {
+            number: parent.number + 1,
+            hash: B256::repeat_byte(2),
+            parent_hash: parent.hash(),
+            parent_state_root: Some(parent.state_root),
+            beneficiary: Address::repeat_byte(3),
+            gas_limit: chain_spec.anchor_gas_limit + 15_000_000,
+            gas_used: 21_000,
+            timestamp: parent.timestamp + 12,
+            extra_data: Bytes::new(),
+            mix_hash: B256::repeat_byte(4),
+            base_fee_per_gas: 10_000_000,
+            anchor_tx: AlloyRpcTransaction {
+                chain_id: Some(chain_spec.chain_id),
+                to: Some(Address::repeat_byte(5)),
+                signature: Some(Default::default()),
+                max_fee_per_gas: Some(Default::default()),
+                max_priority_fee_per_gas: Some(Default::default()),
+                ..Default::default()
+            },
+            tx_list: Bytes::new(),
+            min_tier: 0,
+            parent_meta_hash: B256::ZERO,
+            sender: Address::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let chain_spec = get_network_spec(Network::TaikoA7);
+        let parent = AlloyConsensusHeader {
+            number: 999,
+            timestamp: 1_718_000_000,
+            state_root: B256::repeat_byte(1),
+            ..Default::default()
+        };
+        let valid = block(&chain_spec, &parent);
+        assert_eq!(valid.check(&chain_spec), Ok(()));
+        assert_eq!(valid.check_parent(&parent), Ok(()));
+
+        let mut invalid = valid.clone();
+        invalid.gas_used = invalid.gas_limit + 1;
+        let error = invalid.check(&chain_spec).unwrap_err();
+        assert!(error.contains("more than its gas limit"), "{error}");
+
+        let mut invalid = valid.clone();
+        invalid.gas_limit = chain_spec.anchor_gas_limit;
+        invalid.gas_used = 0;
+        let error = invalid.check(&chain_spec).unwrap_err();
+        assert!(error.contains("leaves no gas"), "{error}");
+
+        let mut invalid = valid.clone();
+        invalid.gas_limit = u64::MAX;
+        let error = invalid.check(&chain_spec).unwrap_err();
+        assert!(error.contains("above the max gas limit"), "{error}");
+
+        let mut invalid = valid.clone();
+        invalid.anchor_tx.signature = None;
+        invalid.anchor_tx.to = None;
+        let error = invalid.check(&chain_spec).unwrap_err();
+        assert!(error.ends_with("missing to, signature"), "{error}");
+
+        let mut invalid = valid.clone();
+        invalid.parent_hash = B256::ZERO;
+        let error = invalid.check_parent(&parent).unwrap_err();
+        assert!(error.starts_with("The parent hash"), "{error}");
+
+        let mut invalid = valid.clone();
+        invalid.parent_state_root = Some(B256::ZERO);
+        let error = invalid.check_parent(&parent).unwrap_err();
+        assert!(error.starts_with("The parent state root"), "{error}");
+
+        let mut invalid = valid;
+        invalid.timestamp = parent.timestamp - 1;
+        let error = invalid.check_parent(&parent).unwrap_err();
+        assert!(error.contains("before the timestamp"), "{error}");
+    }
+}
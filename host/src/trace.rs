@@ -0,0 +1,152 @@
+//! Trace ids tying together the logs of a proof request.
+//!
+//! Every request gets a trace id, taken from its `X-Request-Id` header or generated, which is
+//! returned in the same header of the response. Jobs keep the trace id of the request that
+//! submitted them and run in a span carrying it, so the logs of the preflight, the prover and
+//! the captured guest output can all be found by it.
+//!
+//! With the `otlp` feature, the spans are exported to an OpenTelemetry collector once
+//! `OTEL_EXPORTER_OTLP_ENDPOINT` or `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` is set, see [otlp].
+
+use std::{future::Future, sync::OnceLock};
+
+#[cfg(not(feature = "otlp"))]
+use anyhow::bail;
+use clap::ValueEnum;
+use raiko_primitives::hex;
+use serde::{Deserialize, Serialize};
+use tracing::{warn, Subscriber};
+use tracing_subscriber::{registry::LookupSpan, EnvFilter, Layer};
+
+#[cfg(feature = "otlp")]
+pub mod otlp;
+
+/// The header carrying the trace id of a request and its response.
+pub const TRACE_ID_HEADER: &str = "x-request-id";
+
+/// The max length of a trace id taken from a request.
+const MAX_TRACE_ID_LEN: usize = 64;
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+/// The format of the logs.
+pub enum LogFormat {
+    /// Human readable lines, with the fields of the spans in front of the message.
+    #[default]
+    Text,
+    /// One JSON object per line, with the fields of the spans next to the message.
+    Json,
+}
+
+tokio::task_local! {
+    static TRACE_ID: String;
+}
+
+/// A function replacing the log filter of the subscriber, see [set_log_reload].
+type LogReload = Box<dyn Fn(EnvFilter) -> anyhow::Result<()> + Send + Sync>;
+
+static LOG_RELOAD: OnceLock<LogReload> = OnceLock::new();
+
+/// Generate a new random trace id.
+pub fn new_trace_id() -> String {
+    hex::encode(rand::random::<[u8; 8]>())
+}
+
+/// The trace id given by a client, if it is safe to log and echo back.
+pub fn parse_trace_id(value: &str) -> Option<String> {
+    let valid = !value.is_empty()
+        && value.len() <= MAX_TRACE_ID_LEN
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    valid.then(|| value.to_string())
+}
+
+/// The trace id of the request being handled by the current task.
+pub fn current_trace_id() -> Option<String> {
+    TRACE_ID.try_with(Clone::clone).ok()
+}
+
+/// Run the future with the given trace id as the current one.
+pub async fn with_trace_id<F: Future>(trace_id: String, future: F) -> F::Output {
+    TRACE_ID.scope(trace_id, future).await
+}
+
+/// Let [set_log_level] replace the log filter of the subscriber with the given function.
+pub fn set_log_reload(reload: impl Fn(EnvFilter) -> anyhow::Result<()> + Send + Sync + 'static) {
+    let _ = LOG_RELOAD.set(Box::new(reload));
+}
+
+/// Change the log level, e.g. `debug` or `info,raiko_host=trace` like `RUST_LOG`.
+pub fn set_log_level(log_level: &str) -> anyhow::Result<()> {
+    let filter = EnvFilter::try_new(log_level)?;
+    match LOG_RELOAD.get() {
+        Some(reload) => reload(filter),
+        None => {
+            warn!("The log level can't be changed without a reloadable subscriber");
+            Ok(())
+        }
+    }
+}
+
+/// Whether an OTLP endpoint is set in the environment and the SDK is not disabled.
+pub fn otlp_enabled() -> bool {
+    let is_set = |name| std::env::var(name).is_ok_and(|value| !value.is_empty());
+    (is_set("OTEL_EXPORTER_OTLP_ENDPOINT") || is_set("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT"))
+        && !std::env::var("OTEL_SDK_DISABLED").is_ok_and(|value| value.eq_ignore_ascii_case("true"))
+}
+
+/// The layer exporting the spans over OTLP, if it is enabled.
+#[cfg(feature = "otlp")]
+pub fn otlp_layer<S>() -> anyhow::Result<Option<Box<dyn Layer<S> + Send + Sync>>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    if !otlp_enabled() {
+        return Ok(None);
+    }
+    Ok(Some(otlp::layer()?.boxed()))
+}
+
+#[cfg(not(feature = "otlp"))]
+pub fn otlp_layer<S>() -> anyhow::Result<Option<Box<dyn Layer<S> + Send + Sync>>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    if otlp_enabled() {
+        bail!("An OTLP endpoint is set, but raiko was built without the otlp feature");
+    }
+    Ok(None)
+}
+
+/// Export the spans that were not exported yet, before the host exits.
+pub fn shutdown_otlp() {
+    #[cfg(feature = "otlp")]
+    otlp::shutdown();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_trace_id() {
+        assert_eq!(
+            parse_trace_id("abc-123_x.y").as_deref(),
+            Some("abc-123_x.y")
+        );
+        assert_eq!(parse_trace_id(""), None);
+        assert_eq!(parse_trace_id("with space"), None);
+        assert_eq!(parse_trace_id("line\nbreak"), None);
+        assert_eq!(parse_trace_id(&"a".repeat(65)), None);
+        assert_eq!(new_trace_id().len(), 16);
+        assert!(parse_trace_id(&new_trace_id()).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_current_trace_id() {
+        assert_eq!(current_trace_id(), None);
+        let trace_id = with_trace_id("abc".to_string(), async { current_trace_id() }).await;
+        assert_eq!(trace_id.as_deref(), Some("abc"));
+    }
+}
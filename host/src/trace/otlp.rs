@@ -0,0 +1,38 @@
+//! Export of the spans to an OpenTelemetry collector over OTLP/gRPC, e.g. to Jaeger or Tempo.
+
+use anyhow::{Context, Result};
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// The service name of the spans, unless `OTEL_SERVICE_NAME` is set.
+const DEFAULT_SERVICE_NAME: &str = "raiko-host";
+
+/// The layer exporting the spans in batches. The endpoint, timeout, sampler and resource are
+/// taken from the `OTEL_*` environment variables.
+pub fn layer<S>() -> Result<OpenTelemetryLayer<S, trace::Tracer>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let mut resource = Resource::default();
+    if std::env::var("OTEL_SERVICE_NAME").is_err() {
+        resource = resource.merge(&Resource::new([KeyValue::new(
+            "service.name",
+            DEFAULT_SERVICE_NAME,
+        )]));
+    }
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic())
+        .with_trace_config(trace::config().with_resource(resource))
+        .install_batch(runtime::Tokio)
+        .context("Could not set up the OTLP trace export")?;
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Export the spans that were not exported yet.
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
@@ -0,0 +1,219 @@
+//! Upload of the proofs to object storage.
+//!
+//! With an [UploadConfig], the proof of every job and the input it was proven with are
+//! uploaded to a bucket of an S3 compatible object storage, e.g. AWS S3, or Google Cloud
+//! Storage with HMAC keys and its `https://storage.googleapis.com` endpoint. The objects are
+//! named `<prefix><network>-<block>-<proof type>/proof.json` and `.../input.bin.zst`, and their
+//! URLs are returned in the job, see [Upload], so the proofs outlive the disk of the host and
+//! can be shared with relayers. The credentials are read from the environment like the other
+//! AWS clients. Uploading requires the `s3` feature.
+
+use raiko_lib::{input::GuestInput, prover::Proof};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    error::{HostError, HostResult},
+    input_codec,
+    request::ProofRequest,
+};
+
+fn default_inputs() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UploadConfig {
+    /// The bucket the objects are uploaded to.
+    pub bucket: String,
+    /// The prefix of the names of the objects, e.g. `proofs/`.
+    #[serde(default)]
+    pub prefix: String,
+    /// The endpoint of an S3 compatible object storage other than AWS S3, addressed with path
+    /// style URLs.
+    pub endpoint: Option<String>,
+    /// The region of the bucket, defaults to the region of the environment.
+    pub region: Option<String>,
+    /// The server-side encryption of the objects, `AES256` or `aws:kms`.
+    pub encryption: Option<String>,
+    /// The KMS key the objects are encrypted with, for `aws:kms`.
+    pub kms_key_id: Option<String>,
+    /// Whether to upload the inputs next to the proofs.
+    #[serde(default = "default_inputs")]
+    pub inputs: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+/// The objects the proof of a job was uploaded to.
+pub struct Upload {
+    /// The URL of the proof.
+    pub proof_url: String,
+    /// The URL of the input the block was proven with, unless inputs aren't uploaded.
+    pub input_url: Option<String>,
+}
+
+/// Uploads the proofs to a bucket.
+#[derive(Clone, Debug)]
+pub struct Uploader {
+    config: UploadConfig,
+    /// The region of the bucket, resolved from the environment if not configured.
+    region: Option<String>,
+    #[cfg(feature = "s3")]
+    client: aws_sdk_s3::Client,
+}
+
+impl Uploader {
+    /// Create the client of the bucket.
+    #[cfg(feature = "s3")]
+    pub async fn new(config: UploadConfig) -> HostResult<Self> {
+        use aws_config::{BehaviorVersion, Region};
+
+        let mut loader = aws_config::defaults(BehaviorVersion::latest());
+        if let Some(region) = &config.region {
+            loader = loader.region(Region::new(region.clone()));
+        }
+        if let Some(endpoint) = &config.endpoint {
+            loader = loader.endpoint_url(endpoint);
+        }
+        let sdk_config = loader.load().await;
+        let client = aws_sdk_s3::Client::from_conf(
+            aws_sdk_s3::config::Builder::from(&sdk_config)
+                .force_path_style(config.endpoint.is_some())
+                .build(),
+        );
+        Ok(Self {
+            region: sdk_config.region().map(|region| region.to_string()),
+            config,
+            client,
+        })
+    }
+
+    #[cfg(not(feature = "s3"))]
+    pub async fn new(_config: UploadConfig) -> HostResult<Self> {
+        Err(HostError::Anyhow(anyhow::anyhow!(
+            "raiko was built without object storage support, enable the s3 feature"
+        )))
+    }
+
+    /// Upload the proof of the request and the input it was proven with, and return their
+    /// URLs.
+    pub async fn upload(
+        &self,
+        request: &ProofRequest,
+        input: &GuestInput,
+        proof: &Proof,
+    ) -> HostResult<Upload> {
+        let dir = object_dir(&self.config.prefix, request);
+        let proof_key = format!("{dir}/proof.json");
+        self.put(&proof_key, serde_json::to_vec(proof)?, "application/json")
+            .await?;
+        let input_url = if self.config.inputs {
+            let input_key = format!("{dir}/input.bin.zst");
+            self.put(
+                &input_key,
+                input_codec::encode_to_vec(input, "upload")?,
+                "application/octet-stream",
+            )
+            .await?;
+            Some(self.url(&input_key))
+        } else {
+            None
+        };
+        Ok(Upload {
+            proof_url: self.url(&proof_key),
+            input_url,
+        })
+    }
+
+    #[cfg(feature = "s3")]
+    async fn put(&self, key: &str, body: Vec<u8>, content_type: &str) -> HostResult<()> {
+        use aws_sdk_s3::{primitives::ByteStream, types::ServerSideEncryption};
+
+        self.client
+            .put_object()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .content_type(content_type)
+            .body(ByteStream::from(body))
+            .set_server_side_encryption(
+                self.config
+                    .encryption
+                    .as_deref()
+                    .map(ServerSideEncryption::from),
+            )
+            .set_ssekms_key_id(self.config.kms_key_id.clone())
+            .send()
+            .await
+            .map_err(|e| {
+                HostError::Anyhow(anyhow::anyhow!(
+                    "Could not upload {key} to {}: {}",
+                    self.config.bucket,
+                    aws_sdk_s3::error::DisplayErrorContext(e)
+                ))
+            })?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "s3"))]
+    async fn put(&self, _key: &str, _body: Vec<u8>, _content_type: &str) -> HostResult<()> {
+        unreachable!("the uploader can't be created without the s3 feature")
+    }
+
+    fn url(&self, key: &str) -> String {
+        object_url(
+            self.config.endpoint.as_deref(),
+            self.region.as_deref(),
+            &self.config.bucket,
+            key,
+        )
+    }
+}
+
+fn object_dir(prefix: &str, request: &ProofRequest) -> String {
+    format!(
+        "{prefix}{}-{}-{}",
+        request.network, request.block_number, request.proof_type
+    )
+}
+
+/// The URL of the object, path style on a custom endpoint and virtual-hosted style on AWS S3.
+fn object_url(endpoint: Option<&str>, region: Option<&str>, bucket: &str, key: &str) -> String {
+    match (endpoint, region) {
+        (Some(endpoint), _) => format!("{}/{bucket}/{key}", endpoint.trim_end_matches('/')),
+        (None, Some(region)) => format!("https://{bucket}.s3.{region}.amazonaws.com/{key}"),
+        (None, None) => format!("https://{bucket}.s3.amazonaws.com/{key}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::B256;
+
+    use super::*;
+
+    #[test]
+    fn test_object_url() {
+        let request: ProofRequest = serde_json::from_value(serde_json::json!({
+            "block_number": 10,
+            "rpc": "http://localhost:8545",
+            "l1_rpc": "http://localhost:8546",
+            "beacon_rpc": "http://localhost:5052",
+            "network": "taiko_a7",
+            "l1_network": "holesky",
+            "graffiti": B256::ZERO,
+            "prover": "0x0000000000000000000000000000000000000000",
+            "proof_type": "sgx",
+        }))
+        .unwrap();
+        let key = format!("{}/proof.json", object_dir("proofs/", &request));
+        assert_eq!(key, "proofs/taiko_a7-10-sgx/proof.json");
+        assert_eq!(
+            object_url(None, Some("eu-west-1"), "raiko", &key),
+            "https://raiko.s3.eu-west-1.amazonaws.com/proofs/taiko_a7-10-sgx/proof.json"
+        );
+        assert_eq!(
+            object_url(Some("https://storage.googleapis.com/"), None, "raiko", &key),
+            "https://storage.googleapis.com/raiko/proofs/taiko_a7-10-sgx/proof.json"
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Verification of what raiko proves against the protocol.
+//!
+//! The protocol instance of a block is assembled from the block and its Taiko data like in the
+//! guest, and its metadata is compared with the metadata of the `BlockProposed` event on L1,
+//! so a disagreement between raiko and the protocol shows before any proving time is spent.
+//! Proofs produced before are verified against the same protocol instance, without proving the
+//! block again.
+
+use std::{fs::File, io::BufReader};
+
+use alloy_consensus::Header as AlloyConsensusHeader;
+use alloy_primitives::B256;
+use alloy_sol_types::SolValue;
+use anyhow::anyhow;
+use raiko_client::ErrorCode;
+use raiko_lib::{
+    input::{GuestInput, GuestOutput, TaikoProverData, WrappedHeader},
+    protocol_instance::{assemble_protocol_instance, build_protocol_instance},
+    prover::Proof,
+};
+use raiko_primitives::keccak::keccak;
+use serde::{Deserialize, Serialize};
+use tracing::Span;
+use utoipa::ToSchema;
+
+use crate::{
+    blob_provider::get_providers,
+    chain_registry::get_chain,
+    error::{HostError, HostResult},
+    preflight::block_data,
+    request::ProofRequest,
+    Cli, VerifyArgs,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// The protocol instance of a block, checked against the metadata it was proposed with.
+pub struct ProtocolInstanceCheck {
+    pub network: String,
+    pub block_number: u64,
+    /// Whether the assembled metadata matches the metadata of the `BlockProposed` event.
+    pub matches: bool,
+    /// The hash of the assembled metadata.
+    #[schema(value_type = String)]
+    pub meta_hash: B256,
+    /// The hash of the metadata of the `BlockProposed` event.
+    #[schema(value_type = String)]
+    pub proposed_meta_hash: B256,
+    /// The fields of the assembled metadata which differ from the proposed metadata.
+    pub mismatched_fields: Vec<String>,
+    #[schema(value_type = String)]
+    pub parent_hash: B256,
+    #[schema(value_type = String)]
+    pub block_hash: B256,
+    #[schema(value_type = String)]
+    pub state_root: B256,
+    /// The public input hash of the block for the proof type of the request.
+    #[schema(value_type = String)]
+    pub instance_hash: B256,
+}
+
+/// Assemble the protocol instance of the block of the request from the nodes of the request,
+/// without executing the block, and check it against the proposed metadata.
+pub async fn check_protocol_instance(
+    proof_request: &ProofRequest,
+) -> HostResult<ProtocolInstanceCheck> {
+    let chain = get_chain(&proof_request.network)?;
+    if !chain.network.is_taiko() {
+        return Err(HostError::invalid(
+            ErrorCode::InvalidRequest,
+            "network",
+            format!("{} has no proposed blocks", proof_request.network),
+        ));
+    }
+    let (input, header) = fetch_block(proof_request).await?;
+
+    let pi = build_protocol_instance(&input, &header)?;
+    let proposed = &input.taiko.block_proposed.meta;
+    let mismatched_fields = mismatched_fields(
+        &serde_json::to_value(&pi.block_metadata)?,
+        &serde_json::to_value(proposed)?,
+    );
+    let meta_hash = pi.meta_hash();
+    let proposed_meta_hash: B256 = keccak(proposed.abi_encode()).into();
+    Ok(ProtocolInstanceCheck {
+        network: proof_request.network.clone(),
+        block_number: proof_request.block_number,
+        matches: meta_hash == proposed_meta_hash,
+        meta_hash,
+        proposed_meta_hash,
+        mismatched_fields,
+        parent_hash: pi.transition.parentHash,
+        block_hash: pi.transition.blockHash,
+        state_root: pi.transition.stateRoot,
+        instance_hash: proof_request.proof_type.instance_hash(pi)?,
+    })
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// The result of verifying a proof against the public inputs of its block.
+pub struct ProofVerification {
+    pub network: String,
+    pub block_number: u64,
+    pub proof_type: String,
+    /// Whether the proof verifies against the public inputs of the block.
+    pub verified: bool,
+    /// The reason the proof doesn't verify.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The public input hash of the block the proof was verified against.
+    #[schema(value_type = String)]
+    pub instance_hash: B256,
+}
+
+/// Verify a proof produced before for the block of the request, against the public inputs
+/// assembled from the block without executing it.
+pub async fn verify_proof(
+    proof_request: &ProofRequest,
+    proof: Proof,
+) -> HostResult<ProofVerification> {
+    let (input, header) = fetch_block(proof_request).await?;
+    let proof_type = proof_request.proof_type.clone();
+    let instance_hash = proof_type.instance_hash(assemble_protocol_instance(&input, &header)?)?;
+    let output = GuestOutput::Success((WrappedHeader { header }, instance_hash));
+    let span = Span::current();
+    let verification = tokio::task::spawn_blocking(move || {
+        let _span = span.enter();
+        proof_type.verify(&proof, &input, &output)
+    })
+    .await??
+    .ok_or_else(|| HostError::FeatureNotSupportedError(proof_request.proof_type.clone()))?;
+    Ok(ProofVerification {
+        network: proof_request.network.clone(),
+        block_number: proof_request.block_number,
+        proof_type: proof_request.proof_type.to_string(),
+        verified: verification.is_ok(),
+        error: verification.err(),
+        instance_hash,
+    })
+}
+
+/// Verify the proof in the file of the `verify` command against the block of the proof
+/// request options, and print the result. Fails if the proof doesn't verify.
+pub async fn verify_proof_file(opts: &Cli, args: &VerifyArgs) -> HostResult<()> {
+    opts.load_chains()?;
+    let proof: Proof = serde_json::from_reader(BufReader::new(File::open(&args.proof)?))?;
+    let proof_request = ProofRequest::try_from(opts.proof_request_opt.clone())?;
+    let verification = verify_proof(&proof_request, proof).await?;
+    println!("{}", serde_json::to_string_pretty(&verification)?);
+    match verification.error {
+        Some(error) => Err(HostError::Anyhow(anyhow!(
+            "The proof does not verify: {error}"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Fetch the block of the request and its Taiko data from the nodes of the request, without
+/// the state needed to execute it.
+async fn fetch_block(
+    proof_request: &ProofRequest,
+) -> HostResult<(GuestInput, AlloyConsensusHeader)> {
+    let chain = get_chain(&proof_request.network)?;
+    let blob_providers = get_providers(
+        Some(proof_request.beacon_rpc.clone()),
+        chain.blobscan_url.clone(),
+    );
+    let (rpc, l1_rpc) = (proof_request.rpc.clone(), proof_request.l1_rpc.clone());
+    let (network, chain_spec) = (chain.network, chain.chain_spec);
+    let block_number = proof_request.block_number;
+    let prover_data = TaikoProverData {
+        prover: proof_request.prover,
+        graffiti: proof_request.graffiti,
+    };
+    let span = Span::current();
+    let block = tokio::task::spawn_blocking(move || {
+        let _span = span.enter();
+        block_data(
+            rpc,
+            block_number,
+            network,
+            chain_spec,
+            prover_data,
+            Some(l1_rpc),
+            blob_providers,
+        )
+    })
+    .await??;
+    Ok(block)
+}
+
+/// The names of the fields of the two objects with different values.
+fn mismatched_fields(value: &serde_json::Value, expected: &serde_json::Value) -> Vec<String> {
+    let (Some(value), Some(expected)) = (value.as_object(), expected.as_object()) else {
+        return Vec::new();
+    };
+    value
+        .iter()
+        .filter(|(field, value)| expected.get(field.as_str()) != Some(value))
+        .map(|(field, _)| field.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_mismatched_fields() {
+        let value = json!({"id": 10, "gasLimit": 240000000, "blobUsed": true});
+        let expected = json!({"id": 10, "gasLimit": 239000000, "blobUsed": true});
+        assert_eq!(mismatched_fields(&value, &expected), vec!["gasLimit"]);
+        assert!(mismatched_fields(&value, &value).is_empty());
+    }
+}
@@ -0,0 +1,345 @@
+//! Automatic proving of new blocks.
+//!
+//! A watcher follows a Taiko chain, either through the `BlockProposed` events of its TaikoL1
+//! contract or through the heads of the L2 node, and queues a proof job for every new block
+//! that passes its [WatcherConfig] policy. This way the host proves a chain on its own instead
+//! of waiting for proof requests.
+//!
+//! The jobs are due at the end of the proving window of their proof type, see
+//! [ChainConfig::proving_windows], so the scheduler runs them ahead of other jobs once their
+//! deadline gets close.
+
+use std::time::Duration;
+
+use alloy_primitives::Address;
+use alloy_provider::{Provider, ProviderBuilder, ReqwestProvider, RootProvider};
+use alloy_rpc_types::Filter;
+use alloy_sol_types::SolEvent;
+use anyhow::{anyhow, Result};
+use raiko_lib::input::{taiko_a6::BlockProposed as TestnetBlockProposed, BlockProposed};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::{
+    chain_registry::{get_chain, ChainConfig},
+    clock::unix_timestamp,
+    error::HostResult,
+    jobs::JobManager,
+    l1_events::decode_block_proposed,
+    metrics::inc_watched_block_count,
+    request::{ProofRequest, ProofRequestOpt},
+};
+
+/// The max number of L1 blocks the logs are fetched for at once.
+const LOG_BATCH_SIZE: u64 = 1000;
+
+fn default_every() -> u64 {
+    1
+}
+
+fn default_poll_interval() -> u64 {
+    12
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// How new blocks are noticed.
+pub enum WatchSource {
+    /// The `BlockProposed` events of the TaikoL1 contract on L1.
+    #[default]
+    BlockProposed,
+    /// The new heads of the L2 node.
+    L2Heads,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// What to prove on a chain, e.g. `{"network": "taiko_a7", "proof_types": ["sgx"]}`.
+pub struct WatcherConfig {
+    /// The chain to prove.
+    pub network: String,
+    #[serde(default)]
+    pub source: WatchSource,
+    /// The proof types to request for every block.
+    pub proof_types: Vec<String>,
+    /// Overrides of the proof request of the config file for the jobs of the watcher, e.g. the
+    /// `prover` or the `priority`.
+    #[serde(default)]
+    pub request: Value,
+    /// Seconds to wait after a block is seen before its jobs are queued, e.g. to let the L2
+    /// node catch up with L1.
+    #[serde(default)]
+    pub delay: u64,
+    /// Only prove blocks from this block on.
+    #[serde(default)]
+    pub from_block: Option<u64>,
+    /// Only prove every n-th block.
+    #[serde(default = "default_every")]
+    pub every: u64,
+    /// Only prove blocks assigned to one of these provers. Only checked with the
+    /// `block_proposed` source, an empty list proves all blocks.
+    #[serde(default)]
+    pub assigned_provers: Vec<Address>,
+    /// Seconds between checking for new blocks.
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+}
+
+impl WatcherConfig {
+    /// Whether the policy proves the block.
+    fn wants(&self, block_id: u64, assigned_prover: Option<Address>) -> bool {
+        if self
+            .from_block
+            .is_some_and(|from_block| block_id < from_block)
+        {
+            return false;
+        }
+        if block_id % self.every.max(1) != 0 {
+            return false;
+        }
+        match assigned_prover {
+            Some(prover) if !self.assigned_provers.is_empty() => {
+                self.assigned_provers.contains(&prover)
+            }
+            _ => true,
+        }
+    }
+}
+
+/// A new block of the watched chain.
+struct NewBlock {
+    block_id: u64,
+    assigned_prover: Option<Address>,
+    /// The unix timestamp the block was proposed at.
+    proposed_at: u64,
+}
+
+/// Follows a chain and queues the proof jobs of its new blocks.
+pub struct Watcher {
+    config: WatcherConfig,
+    chain: ChainConfig,
+    /// The proof request of the config file, with the overrides of the watcher.
+    request: ProofRequestOpt,
+    jobs: JobManager,
+    /// The last L1 block the events were fetched for, or the last L2 head.
+    last_block: Option<u64>,
+}
+
+impl Watcher {
+    pub fn new(
+        config: WatcherConfig,
+        request: &ProofRequestOpt,
+        jobs: JobManager,
+    ) -> HostResult<Self> {
+        let chain = get_chain(&config.network)?;
+        let mut request = request.clone();
+        request.merge(&config.request)?;
+        request.network = Some(chain.name.clone());
+        // Check the request once, so a misconfigured watcher fails on startup
+        for proof_type in &config.proof_types {
+            let mut request = request.clone();
+            request.block_number = Some(0);
+            request.proof_type = Some(proof_type.clone());
+            ProofRequest::try_from(request)?;
+        }
+        Ok(Self {
+            config,
+            chain,
+            request,
+            jobs,
+            last_block: None,
+        })
+    }
+
+    /// Keep checking for new blocks in the background.
+    pub fn spawn(mut self) {
+        info!(
+            "Watching {} for new blocks, proving with {:?}",
+            self.chain.name, self.config.proof_types
+        );
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(self.config.poll_interval.max(1)));
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.poll().await {
+                    warn!("Could not check {} for new blocks: {e}", self.chain.name);
+                }
+            }
+        });
+    }
+
+    /// Queue the jobs of the blocks that are new since the last poll.
+    async fn poll(&mut self) -> Result<()> {
+        let blocks = match self.config.source {
+            WatchSource::BlockProposed => self.proposed_blocks().await?,
+            WatchSource::L2Heads => self.new_heads().await?,
+        };
+        for block in blocks {
+            inc_watched_block_count(&self.chain.name);
+            if !self.config.wants(block.block_id, block.assigned_prover) {
+                continue;
+            }
+            let requests = self
+                .config
+                .proof_types
+                .iter()
+                .map(|proof_type| {
+                    let mut request = self.request.clone();
+                    request.block_number = Some(block.block_id);
+                    request.proof_type = Some(proof_type.clone());
+                    request.deadline = self.chain.proving_deadline(proof_type, block.proposed_at);
+                    ProofRequest::try_from(request)
+                })
+                .collect::<HostResult<Vec<_>>>()?;
+            let jobs = self.jobs.clone();
+            let delay = Duration::from_secs(self.config.delay);
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                for request in requests {
+                    if let Err(e) = jobs.submit(request) {
+                        warn!("Could not queue the job of a watched block: {e}");
+                    }
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// The blocks proposed in the L1 blocks since the last poll. The first poll starts at the
+    /// head of L1.
+    async fn proposed_blocks(&mut self) -> Result<Vec<NewBlock>> {
+        let l1_rpc = self
+            .request
+            .l1_rpc
+            .clone()
+            .or(self.chain.l1_rpc.clone())
+            .ok_or_else(|| anyhow!("No L1 node for {}", self.chain.name))?;
+        let contract = self
+            .chain
+            .chain_spec
+            .l1_contract
+            .ok_or_else(|| anyhow!("No TaikoL1 contract on {}", self.chain.name))?;
+        let provider = provider(&l1_rpc)?;
+        let head = provider.get_block_number().await?;
+        let from = match self.last_block {
+            Some(last_block) if last_block >= head => return Ok(Vec::new()),
+            Some(last_block) => last_block + 1,
+            None => head,
+        };
+
+        // The logs are fetched a batch at a time, and the cursor only moves past the batches
+        // fetched, so the blocks of a failed batch are fetched again on the next poll.
+        let mut blocks = Vec::new();
+        for (from, to) in log_batches(from, head) {
+            // The event signature depends on the fork of the proposed block, which is only
+            // known from the event, so both are watched
+            let filter = Filter::new()
+                .address(contract)
+                .from_block(from)
+                .to_block(to)
+                .event_signature(vec![
+                    BlockProposed::SIGNATURE_HASH,
+                    TestnetBlockProposed::SIGNATURE_HASH,
+                ]);
+            let batch = match provider.get_logs(&filter).await {
+                Ok(logs) => logs
+                    .iter()
+                    .map(|log| {
+                        let event = decode_block_proposed(log)?;
+                        Ok(NewBlock {
+                            block_id: event.blockId.to(),
+                            assigned_prover: Some(event.assignedProver),
+                            proposed_at: event.meta.timestamp,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>(),
+                Err(e) => Err(e.into()),
+            };
+            match batch {
+                Ok(batch) => blocks.extend(batch),
+                Err(e) if blocks.is_empty() => return Err(e),
+                Err(e) => {
+                    warn!(
+                        "Could not get the proposed blocks of {} from L1 block {from}: {e}",
+                        self.chain.name
+                    );
+                    break;
+                }
+            }
+            self.last_block = Some(to);
+        }
+        Ok(blocks)
+    }
+
+    /// The L2 blocks since the last poll. The first poll starts at the head of L2.
+    async fn new_heads(&mut self) -> Result<Vec<NewBlock>> {
+        let rpc = self
+            .request
+            .rpc
+            .clone()
+            .or(self.chain.rpc.clone())
+            .ok_or_else(|| anyhow!("No L2 node for {}", self.chain.name))?;
+        let head = provider(&rpc)?.get_block_number().await?;
+        let from = match self.last_block {
+            Some(last_block) if last_block >= head => return Ok(Vec::new()),
+            Some(last_block) => last_block + 1,
+            None => head,
+        };
+        self.last_block = Some(head);
+        // The heads are seen within a poll interval of their proposal
+        let proposed_at = unix_timestamp();
+        Ok((from..=head)
+            .map(|block_id| NewBlock {
+                block_id,
+                assigned_prover: None,
+                proposed_at,
+            })
+            .collect())
+    }
+}
+
+/// The L1 blocks from `from` to `to` in batches of at most [LOG_BATCH_SIZE] blocks.
+fn log_batches(from: u64, to: u64) -> impl Iterator<Item = (u64, u64)> {
+    (from..=to)
+        .step_by(LOG_BATCH_SIZE as usize)
+        .map(move |start| (start, to.min(start + LOG_BATCH_SIZE - 1)))
+}
+
+fn provider(rpc: &str) -> Result<ReqwestProvider> {
+    Ok(ProviderBuilder::new().provider(RootProvider::new_http(reqwest::Url::parse(rpc)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watcher_policy() {
+        let prover = Address::repeat_byte(1);
+        let config: WatcherConfig = serde_json::from_value(serde_json::json!({
+            "network": "taiko_a7",
+            "proof_types": ["sgx"],
+            "from_block": 100,
+            "every": 2,
+            "assigned_provers": [prover],
+        }))
+        .unwrap();
+        assert_eq!(config.source, WatchSource::BlockProposed);
+        assert!(config.wants(100, Some(prover)));
+        assert!(!config.wants(98, Some(prover)));
+        assert!(!config.wants(101, Some(prover)));
+        assert!(!config.wants(102, Some(Address::repeat_byte(2))));
+        // The assigned prover is unknown for L2 heads
+        assert!(config.wants(102, None));
+    }
+
+    #[test]
+    fn test_log_batches() {
+        assert_eq!(log_batches(5, 5).collect::<Vec<_>>(), vec![(5, 5)]);
+        assert_eq!(
+            log_batches(1, 2500).collect::<Vec<_>>(),
+            vec![(1, 1000), (1001, 2000), (2001, 2500)]
+        );
+    }
+}
@@ -0,0 +1,566 @@
+//! Distributed proving on remote workers.
+//!
+//! A host started with `--coordinator` is a worker: it registers its proof types and capacity
+//! at `/admin/workers` of the coordinator, sends it heartbeats and proves the blocks the
+//! coordinator sends to `/worker/prove`. The coordinator keeps doing the preflight and the
+//! scheduling of the proofs, and sends the input of every proof to the least busy worker with
+//! the proof type and a free slot. It proves locally when there is none. A worker missing its
+//! heartbeats for `--worker-timeout` seconds is dropped, the proofs running on it fail with a
+//! retryable error and are retried on another worker with a [retry policy](crate::retry).
+
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use raiko_client::{ErrorCode, Problem};
+use raiko_lib::{
+    input::GuestInput,
+    prover::{Proof, ProverError, ProverProgress},
+};
+use raiko_primitives::hex;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    error::{HostError, HostResult},
+    input_codec,
+    jobs::progress::ProgressSender,
+    metrics::inc_worker_dispatch_count,
+    registry::list_provers,
+    request::{ProofRequest, ProofType},
+    ProverState,
+};
+
+/// The content type of the proof tasks sent to the workers.
+pub const TASK_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// The max size of a proof task, which carries the full input of the block.
+pub const MAX_TASK_SIZE: u64 = 1 << 28;
+
+/// The number of heartbeats a worker sends within the timeout of the coordinator.
+const HEARTBEATS_PER_TIMEOUT: u32 = 3;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+/// The registration of a worker at the coordinator, e.g. `{"url": "http://gpu-1:8080",
+/// "proof_types": ["risc0", "sp1"], "capacity": 2}`.
+pub struct WorkerRegistration {
+    /// The URL the coordinator reaches the worker at.
+    pub url: String,
+    /// The proof types the worker generates proofs for.
+    pub proof_types: Vec<String>,
+    /// The max number of proofs generated on the worker at the same time.
+    pub capacity: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+/// A worker registered at the coordinator.
+pub struct WorkerInfo {
+    /// The id the worker sends its heartbeats with.
+    pub id: String,
+    pub url: String,
+    pub proof_types: Vec<String>,
+    pub capacity: usize,
+    /// The number of proofs being generated on the worker.
+    pub running: usize,
+    /// The seconds between the heartbeats the coordinator expects from the worker.
+    pub heartbeat_interval: u64,
+    /// The seconds since the last heartbeat of the worker.
+    pub last_heartbeat: u64,
+}
+
+#[derive(Debug)]
+struct Worker {
+    registration: WorkerRegistration,
+    running: usize,
+    last_heartbeat: Instant,
+}
+
+/// The workers registered at the coordinator.
+#[derive(Debug)]
+struct Workers {
+    workers: BTreeMap<String, Worker>,
+    timeout: Duration,
+}
+
+impl Workers {
+    fn new(timeout: Duration) -> Self {
+        Self {
+            workers: BTreeMap::new(),
+            timeout,
+        }
+    }
+
+    /// Register a worker, replacing an earlier registration with the same URL.
+    fn register(&mut self, registration: WorkerRegistration) -> HostResult<WorkerInfo> {
+        reqwest::Url::parse(&registration.url).map_err(|e| {
+            HostError::invalid(
+                ErrorCode::InvalidRequest,
+                "url",
+                format!("Invalid URL: {e}"),
+            )
+        })?;
+        if registration.capacity == 0 {
+            return Err(HostError::invalid(
+                ErrorCode::InvalidRequest,
+                "capacity",
+                "The capacity has to be at least 1",
+            ));
+        }
+        // The proofs running on the earlier registration fail, as it is dropped.
+        self.workers
+            .retain(|_, worker| worker.registration.url != registration.url);
+        let id = hex::encode(rand::random::<[u8; 8]>());
+        self.workers.insert(
+            id.clone(),
+            Worker {
+                registration,
+                running: 0,
+                last_heartbeat: Instant::now(),
+            },
+        );
+        self.info(&id)
+    }
+
+    fn heartbeat(&mut self, id: &str) -> HostResult<()> {
+        self.prune();
+        let worker = self.workers.get_mut(id).ok_or_else(|| not_found(id))?;
+        worker.last_heartbeat = Instant::now();
+        Ok(())
+    }
+
+    fn remove(&mut self, id: &str) -> HostResult<()> {
+        self.workers
+            .remove(id)
+            .map(|_| ())
+            .ok_or_else(|| not_found(id))
+    }
+
+    fn is_registered(&mut self, id: &str) -> bool {
+        self.prune();
+        self.workers.contains_key(id)
+    }
+
+    fn list(&mut self) -> Vec<WorkerInfo> {
+        self.prune();
+        self.workers
+            .keys()
+            .filter_map(|id| self.info(id).ok())
+            .collect()
+    }
+
+    /// Take a slot on the least busy worker with the proof type and a free slot, returning
+    /// its id and URL.
+    fn acquire(&mut self, proof_type: &str) -> Option<(String, String)> {
+        self.prune();
+        let (id, worker) = self
+            .workers
+            .iter_mut()
+            .filter(|(_, worker)| {
+                worker.running < worker.registration.capacity
+                    && worker
+                        .registration
+                        .proof_types
+                        .iter()
+                        .any(|name| name == proof_type)
+            })
+            .min_by_key(|(_, worker)| worker.running * 1000 / worker.registration.capacity)?;
+        worker.running += 1;
+        Some((id.clone(), worker.registration.url.clone()))
+    }
+
+    fn release(&mut self, id: &str) {
+        if let Some(worker) = self.workers.get_mut(id) {
+            worker.running = worker.running.saturating_sub(1);
+        }
+    }
+
+    /// Drop the workers that missed their heartbeats.
+    fn prune(&mut self) {
+        let timeout = self.timeout;
+        self.workers.retain(|id, worker| {
+            let alive = worker.last_heartbeat.elapsed() <= timeout;
+            if !alive {
+                warn!(
+                    "Dropping worker {id} at {}, it missed its heartbeats",
+                    worker.registration.url
+                );
+            }
+            alive
+        });
+    }
+
+    fn info(&self, id: &str) -> HostResult<WorkerInfo> {
+        let worker = self.workers.get(id).ok_or_else(|| not_found(id))?;
+        Ok(WorkerInfo {
+            id: id.to_string(),
+            url: worker.registration.url.clone(),
+            proof_types: worker.registration.proof_types.clone(),
+            capacity: worker.registration.capacity,
+            running: worker.running,
+            heartbeat_interval: (self.timeout / HEARTBEATS_PER_TIMEOUT).as_secs().max(1),
+            last_heartbeat: worker.last_heartbeat.elapsed().as_secs(),
+        })
+    }
+}
+
+fn not_found(id: &str) -> HostError {
+    HostError::Validation(Problem::new(
+        ErrorCode::WorkerNotFound,
+        format!("Worker not found: {id}"),
+    ))
+}
+
+lazy_static! {
+    static ref WORKERS: Mutex<Workers> = Mutex::new(Workers::new(Duration::from_secs(30)));
+    static ref API_KEY: RwLock<Option<String>> = RwLock::new(None);
+    static ref CLIENT: reqwest::Client = reqwest::Client::new();
+}
+
+/// Set the time after the last heartbeat a worker is dropped, and the API key the coordinator
+/// and the workers send each other.
+pub fn configure(timeout: Duration, api_key: Option<String>) {
+    WORKERS.lock().unwrap().timeout = timeout;
+    *API_KEY.write().unwrap() = api_key;
+}
+
+/// Register a worker at the coordinator.
+pub fn register(registration: WorkerRegistration) -> HostResult<WorkerInfo> {
+    let info = WORKERS.lock().unwrap().register(registration)?;
+    info!(
+        "Registered worker {} at {} for {}",
+        info.id,
+        info.url,
+        info.proof_types.join(", ")
+    );
+    Ok(info)
+}
+
+/// Keep the worker registered, fails once it was dropped.
+pub fn heartbeat(id: &str) -> HostResult<()> {
+    WORKERS.lock().unwrap().heartbeat(id)
+}
+
+/// Remove a worker, e.g. when it shuts down. Its running proofs are retried elsewhere.
+pub fn deregister(id: &str) -> HostResult<()> {
+    WORKERS.lock().unwrap().remove(id)?;
+    info!("Deregistered worker {id}");
+    Ok(())
+}
+
+/// The registered workers, ordered by id.
+pub fn list() -> Vec<WorkerInfo> {
+    WORKERS.lock().unwrap().list()
+}
+
+/// A slot on a worker, released when dropped.
+pub struct WorkerLease {
+    id: String,
+    url: String,
+}
+
+impl Drop for WorkerLease {
+    fn drop(&mut self) {
+        WORKERS.lock().unwrap().release(&self.id);
+    }
+}
+
+/// Take a slot on a worker for a proof of the proof type, if any has a free one.
+pub fn acquire(proof_type: &ProofType) -> Option<WorkerLease> {
+    let (id, url) = WORKERS.lock().unwrap().acquire(&proof_type.to_string())?;
+    Some(WorkerLease { id, url })
+}
+
+#[derive(Serialize, Deserialize)]
+/// A proof for a worker to generate, sent [encoded](crate::input_codec).
+pub struct ProofTask {
+    /// The proof request in JSON, the prover args can't be encoded in bincode.
+    request: String,
+    input: GuestInput,
+}
+
+impl ProofTask {
+    pub fn new(request: &ProofRequest, input: GuestInput) -> HostResult<Self> {
+        Ok(Self {
+            request: serde_json::to_string(request)?,
+            input,
+        })
+    }
+
+    pub fn encode(&self) -> HostResult<Vec<u8>> {
+        input_codec::encode_to_vec(self, "worker")
+    }
+
+    pub fn decode(bytes: &[u8]) -> HostResult<(ProofRequest, GuestInput)> {
+        let task: Self = input_codec::decode(bytes).map_err(|e| {
+            HostError::Validation(Problem::new(
+                ErrorCode::MalformedBody,
+                format!("Invalid proof task: {e}"),
+            ))
+        })?;
+        Ok((serde_json::from_str(&task.request)?, task.input))
+    }
+}
+
+impl WorkerLease {
+    /// Generate the proof on the worker. Fails with a retryable error when the worker can't
+    /// be reached or misses its heartbeats while proving.
+    pub async fn prove(
+        &self,
+        proof_request: &ProofRequest,
+        input: GuestInput,
+        progress: &ProgressSender,
+    ) -> HostResult<Proof> {
+        progress
+            .reporter(&proof_request.proof_type)
+            .report(ProverProgress::RemoteSession {
+                service: "worker".to_string(),
+                session_id: self.id.clone(),
+            });
+        let body = ProofTask::new(proof_request, input)?.encode()?;
+        let mut request = CLIENT
+            .post(format!("{}/worker/prove", self.url.trim_end_matches('/')))
+            .header(CONTENT_TYPE, TASK_CONTENT_TYPE)
+            .body(body);
+        if let Some(api_key) = API_KEY.read().unwrap().as_ref() {
+            request = request.header(AUTHORIZATION, format!("Bearer {api_key}"));
+        }
+
+        let result = tokio::select! {
+            response = request.send() => self.read_proof(response).await,
+            _ = self.lost() => Err(HostError::Anyhow(anyhow::anyhow!(
+                "Worker {} missed its heartbeats while proving",
+                self.id
+            ))),
+        };
+        inc_worker_dispatch_count(if result.is_ok() { "success" } else { "failed" });
+        result
+    }
+
+    async fn read_proof(&self, response: reqwest::Result<reqwest::Response>) -> HostResult<Proof> {
+        let response = response.map_err(|e| {
+            HostError::Anyhow(anyhow::anyhow!("Worker {} unreachable: {e}", self.id))
+        })?;
+        if response.status().is_success() {
+            return response.json().await.map_err(|e| {
+                HostError::Anyhow(anyhow::anyhow!(
+                    "Invalid proof from worker {}: {e}",
+                    self.id
+                ))
+            });
+        }
+        let status = response.status();
+        match response.json::<Problem>().await {
+            // The prover failed on the worker, it would fail locally as well.
+            Ok(problem) if problem.code == ErrorCode::ProverError => Err(HostError::GuestError(
+                ProverError::GuestError(problem.detail),
+            )),
+            Ok(problem) if problem.code == ErrorCode::ResourceExhausted => {
+                Err(HostError::ResourceExhausted(problem.detail))
+            }
+            Ok(problem) if problem.status < 500 => Err(HostError::Validation(problem)),
+            Ok(problem) => Err(HostError::Anyhow(anyhow::anyhow!(
+                "Worker {} failed: {problem}",
+                self.id
+            ))),
+            Err(_) => Err(HostError::Anyhow(anyhow::anyhow!(
+                "Worker {} failed with status {status}",
+                self.id
+            ))),
+        }
+    }
+
+    /// Wait until the worker was dropped by the coordinator.
+    async fn lost(&self) {
+        loop {
+            let timeout = WORKERS.lock().unwrap().timeout;
+            tokio::time::sleep(timeout / HEARTBEATS_PER_TIMEOUT).await;
+            if !WORKERS.lock().unwrap().is_registered(&self.id) {
+                return;
+            }
+        }
+    }
+}
+
+/// Register this host as a worker at the coordinator and keep it registered with heartbeats,
+/// until the host drains its jobs for the shutdown.
+pub fn spawn_worker(state: ProverState, coordinator: String) {
+    let coordinator = coordinator.trim_end_matches('/').to_string();
+    let url = state
+        .opts
+        .advertise_url
+        .clone()
+        .unwrap_or_else(|| format!("http://{}", state.opts.address));
+    tokio::spawn(async move {
+        let mut registered: Option<WorkerInfo> = None;
+        loop {
+            if state.jobs.is_draining() {
+                if let Some(worker) = registered {
+                    let path = format!("/admin/workers/{}", worker.id);
+                    match send(CLIENT.delete(format!("{coordinator}{path}"))).await {
+                        Ok(_) => info!("Deregistered from the coordinator {coordinator}"),
+                        Err(e) => warn!("Could not deregister from the coordinator: {e}"),
+                    }
+                }
+                return;
+            }
+
+            let interval = match &registered {
+                Some(worker) => {
+                    let path = format!("/admin/workers/{}/heartbeat", worker.id);
+                    match send(CLIENT.post(format!("{coordinator}{path}"))).await {
+                        Ok(_) => Duration::from_secs(worker.heartbeat_interval),
+                        Err(e) => {
+                            warn!("Heartbeat to the coordinator failed, registering again: {e}");
+                            registered = None;
+                            continue;
+                        }
+                    }
+                }
+                None => {
+                    let registration = WorkerRegistration {
+                        url: url.clone(),
+                        proof_types: list_provers()
+                            .into_iter()
+                            .map(|prover| prover.name)
+                            .collect(),
+                        capacity: state.runtime.get().concurrency_limit,
+                    };
+                    let request = CLIENT
+                        .post(format!("{coordinator}/admin/workers"))
+                        .json(&registration);
+                    match send(request).await {
+                        Ok(response) => match response.json::<WorkerInfo>().await {
+                            Ok(worker) => {
+                                info!("Registered as worker {} at {coordinator}", worker.id);
+                                let interval = Duration::from_secs(worker.heartbeat_interval);
+                                registered = Some(worker);
+                                interval
+                            }
+                            Err(e) => {
+                                warn!("Invalid registration from the coordinator: {e}");
+                                Duration::from_secs(5)
+                            }
+                        },
+                        Err(e) => {
+                            warn!("Could not register at the coordinator {coordinator}: {e}");
+                            Duration::from_secs(5)
+                        }
+                    }
+                }
+            };
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+/// Send a request to the coordinator with the API key, failing on an error status.
+async fn send(mut request: reqwest::RequestBuilder) -> anyhow::Result<reqwest::Response> {
+    if let Some(api_key) = API_KEY.read().unwrap().as_ref() {
+        request = request.header(AUTHORIZATION, format!("Bearer {api_key}"));
+    }
+    Ok(request.send().await?.error_for_status()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registration(url: &str, proof_types: &[&str], capacity: usize) -> WorkerRegistration {
+        WorkerRegistration {
+            url: url.to_string(),
+            proof_types: proof_types.iter().map(|name| name.to_string()).collect(),
+            capacity,
+        }
+    }
+
+    #[test]
+    fn test_acquire() {
+        let mut workers = Workers::new(Duration::from_secs(30));
+        let gpu = workers
+            .register(registration("http://gpu:8080", &["risc0", "sp1"], 2))
+            .unwrap();
+        let sgx = workers
+            .register(registration("http://sgx:8080", &["sgx"], 1))
+            .unwrap();
+
+        assert_eq!(workers.acquire("sgx").unwrap().0, sgx.id);
+        // The only SGX worker is busy.
+        assert_eq!(workers.acquire("sgx"), None);
+        assert_eq!(workers.acquire("risc0").unwrap().0, gpu.id);
+        assert_eq!(workers.acquire("sp1").unwrap().0, gpu.id);
+        assert_eq!(workers.acquire("sp1"), None);
+        assert_eq!(workers.acquire("native"), None);
+
+        workers.release(&gpu.id);
+        assert_eq!(workers.acquire("sp1").unwrap().0, gpu.id);
+
+        assert!(workers
+            .register(registration("not a url", &["sgx"], 1))
+            .is_err());
+        assert!(workers
+            .register(registration("http://sgx:8080", &["sgx"], 0))
+            .is_err());
+    }
+
+    #[test]
+    fn test_heartbeat() {
+        let mut workers = Workers::new(Duration::from_millis(50));
+        let worker = workers
+            .register(registration("http://gpu:8080", &["risc0"], 1))
+            .unwrap();
+        assert!(workers.heartbeat(&worker.id).is_ok());
+        assert_eq!(workers.list().len(), 1);
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(workers.heartbeat(&worker.id).is_err());
+        assert!(workers.list().is_empty());
+        assert_eq!(workers.acquire("risc0"), None);
+
+        // Registering again with the same URL replaces the earlier registration.
+        let first = workers
+            .register(registration("http://gpu:8080", &["risc0"], 1))
+            .unwrap();
+        let second = workers
+            .register(registration("http://gpu:8080", &["risc0"], 1))
+            .unwrap();
+        assert_ne!(first.id, second.id);
+        assert_eq!(workers.list().len(), 1);
+    }
+
+    #[test]
+    fn test_proof_task() {
+        let proof_request: ProofRequest = serde_json::from_value(serde_json::json!({
+            "block_number": 10,
+            "rpc": "http://localhost:8545",
+            "l1_rpc": "http://localhost:8546",
+            "beacon_rpc": "http://localhost:5052",
+            "network": "taiko_a7",
+            "l1_network": "holesky",
+            "graffiti": "0x0000000000000000000000000000000000000000000000000000000000000000",
+            "prover": "0x0000000000000000000000000000000000000000",
+            "proof_type": "sgx",
+            "prover_args": {"sgx": {"instance_id": 1}},
+        }))
+        .unwrap();
+        let input = GuestInput {
+            block_number: 10,
+            ..Default::default()
+        };
+        let encoded = ProofTask::new(&proof_request, input)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let (decoded_request, decoded_input) = ProofTask::decode(&encoded).unwrap();
+        assert_eq!(
+            serde_json::to_value(decoded_request).unwrap(),
+            serde_json::to_value(proof_request).unwrap()
+        );
+        assert_eq!(decoded_input.block_number, 10);
+        assert!(ProofTask::decode(b"garbage").is_err());
+    }
+}
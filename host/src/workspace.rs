@@ -0,0 +1,92 @@
+//! The workspaces of the proofs.
+//!
+//! With `--workspace-dir`, every proof gets a directory in it, named after its network, block
+//! and proof type, which is passed to the prover as `workspace` in its config. Provers keep
+//! their checkpoints there, e.g. the proven segments of Risc0 or the id of the proof on the
+//! SP1 prover network, so a proof interrupted by a crash or a restart of the host resumes from
+//! them instead of starting over. The workspace is removed once the proof was generated, or
+//! moved into the [artifacts](crate::artifacts) of its job.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use lazy_static::lazy_static;
+use tracing::warn;
+
+use crate::{error::HostResult, request::ProofRequest};
+
+lazy_static! {
+    static ref DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+}
+
+/// Set the directory the workspaces are created in, unset to run the provers without one.
+pub fn configure(dir: Option<PathBuf>) {
+    *DIR.write().unwrap() = dir;
+}
+
+/// The directory the workspaces are created in, unset without workspaces.
+pub fn dir() -> Option<PathBuf> {
+    DIR.read().unwrap().clone()
+}
+
+/// Create the workspace of the proof, or return the one left by an interrupted attempt.
+pub fn create(proof_request: &ProofRequest) -> HostResult<Option<PathBuf>> {
+    let Some(dir) = DIR.read().unwrap().clone() else {
+        return Ok(None);
+    };
+    let workspace = workspace_path(&dir, proof_request);
+    std::fs::create_dir_all(&workspace)?;
+    Ok(Some(workspace))
+}
+
+/// The path of the workspace of the proof, whether it exists or not.
+pub fn path(proof_request: &ProofRequest) -> Option<PathBuf> {
+    let dir = DIR.read().unwrap().clone()?;
+    Some(workspace_path(&dir, proof_request))
+}
+
+/// Remove the workspace of a generated proof.
+pub fn remove(workspace: &Path) {
+    if let Err(e) = std::fs::remove_dir_all(workspace) {
+        warn!(
+            "Could not remove the workspace {}: {e}",
+            workspace.display()
+        );
+    }
+}
+
+fn workspace_path(dir: &Path, proof_request: &ProofRequest) -> PathBuf {
+    dir.join(format!(
+        "{}-{}-{}",
+        proof_request.network, proof_request.block_number, proof_request.proof_type
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::B256;
+
+    use super::*;
+
+    #[test]
+    fn test_workspace_path() {
+        let proof_request: ProofRequest = serde_json::from_value(serde_json::json!({
+            "block_number": 10,
+            "rpc": "http://localhost:8545",
+            "l1_rpc": "http://localhost:8546",
+            "beacon_rpc": "http://localhost:5052",
+            "network": "taiko_a7",
+            "l1_network": "holesky",
+            "graffiti": B256::ZERO,
+            "prover": "0x0000000000000000000000000000000000000000",
+            "proof_type": "risc0",
+        }))
+        .unwrap();
+        assert_eq!(
+            workspace_path(Path::new("/workspaces"), &proof_request),
+            Path::new("/workspaces/taiko_a7-10-risc0")
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use alloy_primitives::B256;
+use alloy_sol_types::SolValue;
+use raiko_primitives::keccak::keccak;
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(feature = "std"))]
+use crate::no_std::*;
+use crate::{
+    input::GuestOutput,
+    prover::{Proof, ProverConfig, ProverResult},
+};
+
+/// The program id of the words of a program id, each in little endian like the digests of
+/// Risc0.
+pub fn program_id_from_words(words: [u32; 8]) -> B256 {
+    let mut bytes = [0u8; 32];
+    for (chunk, word) in bytes.chunks_exact_mut(4).zip(words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    B256::from(bytes)
+}
+
+/// The input of the aggregation guest program.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregationGuestInput {
+    /// The id of the block guest program the block proofs were generated with, the image id
+    /// of Risc0 or the digest of the verifying key of SP1.
+    pub program_id: [u32; 8],
+    /// The outputs of the block proofs to aggregate, in block order.
+    pub block_outputs: Vec<GuestOutput>,
+}
+
+/// The output of the aggregation guest program.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AggregationGuestOutput {
+    /// The hash committing to the block guest program and all aggregated blocks.
+    pub hash: B256,
+}
+
+impl AggregationGuestOutput {
+    /// Build the aggregation output for the given block proof outputs.
+    ///
+    /// Returns `None` if any of the blocks failed to build, those can't be aggregated.
+    pub fn new(program_id: B256, block_outputs: &[GuestOutput]) -> Option<Self> {
+        let instance_hashes = block_outputs
+            .iter()
+            .map(|output| match output {
+                GuestOutput::Success((_, instance_hash)) => Some(*instance_hash),
+                GuestOutput::Failure => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            hash: keccak((program_id, instance_hashes).abi_encode()).into(),
+        })
+    }
+}
+
+/// A prover that can recursively aggregate multiple block proofs into a single proof.
+pub trait Aggregator {
+    /// Aggregate the given block proofs, generated by the same prover, in block order.
+    #[allow(async_fn_in_trait)]
+    async fn aggregate(proofs: Vec<Proof>, config: &ProverConfig) -> ProverResult<Proof>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::WrappedHeader;
+
+    #[test]
+    fn test_aggregation_output_rejects_failed_blocks() {
+        let success = GuestOutput::Success((
+            WrappedHeader {
+                header: Default::default(),
+            },
+            B256::repeat_byte(1),
+        ));
+        let program_id = B256::repeat_byte(2);
+
+        let output = AggregationGuestOutput::new(program_id, &[success.clone()]).unwrap();
+        assert_ne!(
+            output,
+            AggregationGuestOutput::new(program_id, &[success.clone(), success.clone()]).unwrap()
+        );
+        assert!(
+            AggregationGuestOutput::new(program_id, &[success, GuestOutput::Failure]).is_none()
+        );
+    }
+
+    #[test]
+    fn test_program_id_from_words() {
+        let program_id = program_id_from_words([1, 0, 0, 0, 0, 0, 0, 0x0200_0000]);
+        assert_eq!(program_id[0], 1);
+        assert_eq!(program_id[31], 2);
+    }
+}
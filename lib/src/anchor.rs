@@ -0,0 +1,382 @@
+//! Validation of the anchor transaction of Taiko blocks.
+//!
+//! Every Taiko block starts with the anchor transaction, which the node signs with the golden
+//! touch key and which commits the block to its L1 origin. Its call and what is checked about
+//! it change with the protocol, so the checks are described by [AnchorRules], selected by the
+//! Taiko fork of the block. The guest checks the anchor while executing the block, and the
+//! host runs the same checks before handing the input to a prover.
+
+use core::str::FromStr;
+
+use alloy_consensus::{Signed, TxEip1559, TxEnvelope};
+use alloy_primitives::{uint, Address, TxKind, B256, U256};
+use alloy_sol_types::{sol, SolCall};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use lazy_static::lazy_static;
+
+#[cfg(not(feature = "std"))]
+use crate::no_std::*;
+use crate::{
+    consts::{ChainSpec, ForkRules, TaikoFork},
+    input::GuestInput,
+    taiko_utils::HeaderHasher,
+};
+
+/// The default gas limit of the anchor transaction.
+pub const ANCHOR_GAS_LIMIT: u64 = 250_000;
+
+lazy_static! {
+    pub static ref GOLDEN_TOUCH_ACCOUNT: Address = {
+        Address::from_str("0x0000777735367b36bC9B61C50022d9D0700dB4Ec")
+            .expect("invalid golden touch account")
+    };
+    static ref GX1: U256 =
+        uint!(0x79BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798_U256);
+    static ref N: U256 =
+        uint!(0xfffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141_U256);
+    static ref GX1_MUL_PRIVATEKEY: U256 =
+        uint!(0x4341adf5a780b4a87939938fd7a032f6e6664c7da553c121d3b4947429639122_U256);
+    static ref GX2: U256 =
+        uint!(0xc6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5_U256);
+}
+
+sol! {
+    #[derive(Debug, Default, PartialEq, Eq)]
+    struct BaseFeeConfig {
+        uint8 adjustmentQuotient;
+        uint8 sharingPctg;
+        uint32 gasIssuancePerSecond;
+        uint64 minGasExcess;
+        uint32 maxGasIssuancePerBlock;
+    }
+
+    function anchor(
+        bytes32 l1Hash,
+        bytes32 l1StateRoot,
+        uint64 l1BlockId,
+        uint32 parentGasUsed
+    )
+        external
+    {}
+
+    function anchorV2(
+        uint64 _anchorBlockId,
+        bytes32 _anchorStateRoot,
+        uint32 _parentGasUsed,
+        BaseFeeConfig calldata _baseFeeConfig
+    )
+        external
+    {}
+
+    function anchorV3(
+        uint64 _anchorBlockId,
+        bytes32 _anchorStateRoot,
+        uint32 _parentGasUsed,
+        BaseFeeConfig calldata _baseFeeConfig,
+        bytes32[] calldata _signalSlots
+    )
+        external
+    {}
+}
+
+/// The base fee config of the protocol, which `anchorV2` and `anchorV3` commit to.
+pub const BASE_FEE_CONFIG: BaseFeeConfig = BaseFeeConfig {
+    adjustmentQuotient: 8,
+    sharingPctg: 75,
+    gasIssuancePerSecond: 5_000_000,
+    minGasExcess: 1_340_000_000,
+    maxGasIssuancePerBlock: 600_000_000,
+};
+
+/// The version of the call of the anchor transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnchorVersion {
+    /// `anchor`, committing to the hash, state root and number of the L1 block.
+    V1,
+    /// `anchorV2`, committing to the state root and number of the L1 block and the base fee
+    /// config of the chain.
+    V2,
+    /// `anchorV3`, additionally committing to the signal slots of the block.
+    V3,
+}
+
+/// The values of the anchor call the block is checked against, whatever its version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnchorCall {
+    /// The hash of the L1 block, only committed to by `anchor`.
+    pub l1_hash: Option<B256>,
+    pub l1_state_root: B256,
+    pub l1_block_id: u64,
+    pub parent_gas_used: u32,
+    /// The base fee config, only committed to from `anchorV2`.
+    pub base_fee_config: Option<BaseFeeConfig>,
+}
+
+/// The checks of the anchor transaction of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnchorRules {
+    pub version: AnchorVersion,
+    /// The gas limit the anchor transaction has to have.
+    pub gas_limit: u64,
+    /// Whether the L1 state root of the call has to match the L1 block.
+    pub check_l1_state_root: bool,
+    /// Whether the max fee per gas of the anchor transaction has to be the base fee of the
+    /// block.
+    pub check_base_fee: bool,
+}
+
+impl AnchorRules {
+    /// The rules of the anchor transaction of the Taiko fork of the block, or `None` for L1
+    /// blocks. The gas limit is the one of the [trusted](ChainSpec::trusted) spec.
+    pub fn new(chain_spec: &ChainSpec, rules: &ForkRules) -> Option<Self> {
+        let gas_limit = chain_spec.trusted().anchor_gas_limit;
+        let (version, check_l1_state_root) = match rules.taiko_fork? {
+            TaikoFork::Katla => (AnchorVersion::V1, false),
+            TaikoFork::Hekla => (AnchorVersion::V1, true),
+            TaikoFork::Ontake => (AnchorVersion::V2, true),
+            TaikoFork::Pacaya => (AnchorVersion::V3, true),
+        };
+        let rules = AnchorRules {
+            version,
+            gas_limit,
+            check_l1_state_root,
+            check_base_fee: true,
+        };
+        Some(rules)
+    }
+
+    /// Decode the call data of the anchor transaction of this version.
+    pub fn decode(&self, data: &[u8]) -> Result<AnchorCall> {
+        let call = match self.version {
+            AnchorVersion::V1 => {
+                let call = anchorCall::abi_decode(data, true).map_err(|e| anyhow!(e))?;
+                AnchorCall {
+                    l1_hash: Some(call.l1Hash),
+                    l1_state_root: call.l1StateRoot,
+                    l1_block_id: call.l1BlockId,
+                    parent_gas_used: call.parentGasUsed,
+                    base_fee_config: None,
+                }
+            }
+            AnchorVersion::V2 => {
+                let call = anchorV2Call::abi_decode(data, true).map_err(|e| anyhow!(e))?;
+                AnchorCall {
+                    l1_hash: None,
+                    l1_state_root: call._anchorStateRoot,
+                    l1_block_id: call._anchorBlockId,
+                    parent_gas_used: call._parentGasUsed,
+                    base_fee_config: Some(call._baseFeeConfig),
+                }
+            }
+            AnchorVersion::V3 => {
+                let call = anchorV3Call::abi_decode(data, true).map_err(|e| anyhow!(e))?;
+                AnchorCall {
+                    l1_hash: None,
+                    l1_state_root: call._anchorStateRoot,
+                    l1_block_id: call._anchorBlockId,
+                    parent_gas_used: call._parentGasUsed,
+                    base_fee_config: Some(call._baseFeeConfig),
+                }
+            }
+        };
+        Ok(call)
+    }
+
+    /// Check the anchor transaction sent by `from` against the block of the input.
+    pub fn check(&self, input: &GuestInput, anchor: &TxEnvelope, from: &Address) -> Result<()> {
+        let TxEnvelope::Eip1559(tx) = anchor else {
+            bail!("invalid anchor tx type");
+        };
+        // Check the signature
+        check_anchor_signature(tx).context(anyhow!("failed to check anchor signature"))?;
+
+        let tx = tx.tx();
+        // Check that it's from the golden touch address
+        ensure!(
+            *from == *GOLDEN_TOUCH_ACCOUNT,
+            "anchor transaction from mismatch"
+        );
+        // Check that the L2 contract is being called
+        let TxKind::Call(to) = tx.to else {
+            bail!("anchor tx not a smart contract call");
+        };
+        ensure!(
+            Some(to) == input.chain_spec.l2_contract,
+            "anchor transaction to mismatch"
+        );
+        // Tx can't have any ETH attached
+        ensure!(
+            tx.value == U256::from(0),
+            "anchor transaction value mismatch"
+        );
+        // Tx needs to have the expected gas limit
+        ensure!(
+            tx.gas_limit == self.gas_limit,
+            "anchor transaction gas limit mismatch: expected {}, got {}",
+            self.gas_limit,
+            tx.gas_limit
+        );
+        // Check needs to have the base fee set to the block base fee
+        if self.check_base_fee {
+            ensure!(
+                tx.max_fee_per_gas == input.base_fee_per_gas.into(),
+                "anchor transaction base fee mismatch"
+            );
+        }
+
+        // Okay now let's decode the anchor tx to verify the inputs
+        let call = self.decode(&tx.input)?;
+        let l1_header = &input.taiko.l1_header;
+        // The L1 blockhash needs to match the expected value
+        if let Some(l1_hash) = call.l1_hash {
+            ensure!(l1_hash == l1_header.hash(), "L1 hash mismatch");
+        }
+        if self.check_l1_state_root {
+            ensure!(
+                call.l1_state_root == l1_header.state_root,
+                "L1 state root mismatch"
+            );
+        }
+        ensure!(
+            call.l1_block_id == l1_header.number,
+            "L1 block number mismatch"
+        );
+        // The base fee config needs to be the one of the protocol
+        if let Some(base_fee_config) = call.base_fee_config {
+            ensure!(
+                base_fee_config == BASE_FEE_CONFIG,
+                "base fee config mismatch: expected {BASE_FEE_CONFIG:?}, got {base_fee_config:?}"
+            );
+        }
+        // The parent gas used input needs to match the gas used value of the parent block
+        ensure!(
+            call.parent_gas_used == input.parent_header.gas_used as u32,
+            "parentGasUsed mismatch"
+        );
+        Ok(())
+    }
+
+    /// Check the anchor transaction of the block of the input, recovering its sender, e.g. in
+    /// the host before the input is handed to a prover.
+    pub fn check_signed(&self, input: &GuestInput, anchor: &TxEnvelope) -> Result<()> {
+        let TxEnvelope::Eip1559(tx) = anchor else {
+            bail!("invalid anchor tx type");
+        };
+        let from = tx
+            .recover_signer()
+            .map_err(|e| anyhow!("Error recovering anchor signature: {e}"))?;
+        self.check(input, anchor, &from)
+    }
+}
+
+/// check the anchor signature with fixed K value
+fn check_anchor_signature(anchor: &Signed<TxEip1559>) -> Result<()> {
+    let sign = anchor.signature();
+    if sign.r() == *GX1 {
+        return Ok(());
+    }
+    let msg_hash = anchor.signature_hash();
+    let msg_hash: U256 = msg_hash.into();
+    if sign.r() == *GX2 {
+        // when r == GX2 require s == 0 if k == 1
+        // alias: when r == GX2 require N == msg_hash + *GX1_MUL_PRIVATEKEY
+        if *N != msg_hash + *GX1_MUL_PRIVATEKEY {
+            bail!(
+                "r == GX2, but N != msg_hash + *GX1_MUL_PRIVATEKEY, N: {}, msg_hash: {msg_hash}, *GX1_MUL_PRIVATEKEY: {}",
+                *N, *GX1_MUL_PRIVATEKEY
+            );
+        }
+        return Ok(());
+    }
+    Err(anyhow!(
+        "r != *GX1 && r != GX2, r: {}, *GX1: {}, GX2: {}",
+        sign.r(),
+        *GX1,
+        *GX2
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::{ForkCondition, TAIKO_A6_CHAIN_SPEC, TAIKO_A7_CHAIN_SPEC};
+
+    #[test]
+    fn test_rules_by_fork() {
+        let rules = |chain_spec: &ChainSpec| {
+            AnchorRules::new(chain_spec, &chain_spec.fork_rules(10, 0).unwrap()).unwrap()
+        };
+        assert!(!rules(&TAIKO_A6_CHAIN_SPEC).check_l1_state_root);
+        assert!(rules(&TAIKO_A7_CHAIN_SPEC).check_l1_state_root);
+
+        // The gas limit of a built-in chain is the one of its built-in spec
+        let mut chain_spec = TAIKO_A7_CHAIN_SPEC.clone();
+        chain_spec.anchor_gas_limit = 1_000_000;
+        assert_eq!(rules(&chain_spec).gas_limit, ANCHOR_GAS_LIMIT);
+        chain_spec.chain_id = 167100;
+        assert_eq!(rules(&chain_spec).gas_limit, 1_000_000);
+
+        chain_spec
+            .taiko_forks
+            .insert(TaikoFork::Ontake, ForkCondition::Block(10));
+        assert_eq!(rules(&chain_spec).version, AnchorVersion::V2);
+        chain_spec
+            .taiko_forks
+            .insert(TaikoFork::Pacaya, ForkCondition::Block(10));
+        assert_eq!(rules(&chain_spec).version, AnchorVersion::V3);
+        chain_spec.taiko_forks.clear();
+        assert_eq!(
+            AnchorRules::new(&chain_spec, &chain_spec.fork_rules(10, 0).unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_decode_versions() {
+        let mut rules = AnchorRules {
+            version: AnchorVersion::V1,
+            gas_limit: ANCHOR_GAS_LIMIT,
+            check_l1_state_root: true,
+            check_base_fee: true,
+        };
+        let v1 = anchorCall {
+            l1Hash: B256::repeat_byte(1),
+            l1StateRoot: B256::repeat_byte(2),
+            l1BlockId: 100,
+            parentGasUsed: 21000,
+        }
+        .abi_encode();
+        let call = rules.decode(&v1).unwrap();
+        assert_eq!(call.l1_hash, Some(B256::repeat_byte(1)));
+        assert_eq!((call.l1_block_id, call.parent_gas_used), (100, 21000));
+
+        let v2 = anchorV2Call {
+            _anchorBlockId: 100,
+            _anchorStateRoot: B256::repeat_byte(2),
+            _parentGasUsed: 21000,
+            _baseFeeConfig: BASE_FEE_CONFIG,
+        }
+        .abi_encode();
+        let v3 = anchorV3Call {
+            _anchorBlockId: 100,
+            _anchorStateRoot: B256::repeat_byte(2),
+            _parentGasUsed: 21000,
+            _baseFeeConfig: BASE_FEE_CONFIG,
+            _signalSlots: vec![B256::repeat_byte(3)],
+        }
+        .abi_encode();
+        // The call has to be of the version of the rules
+        assert!(rules.decode(&v2).is_err());
+
+        rules.version = AnchorVersion::V2;
+        let call = rules.decode(&v2).unwrap();
+        assert_eq!(call.l1_hash, None);
+        assert_eq!(call.l1_state_root, B256::repeat_byte(2));
+        assert_eq!(call.base_fee_config, Some(BASE_FEE_CONFIG));
+        assert!(rules.decode(&v1).is_err());
+
+        rules.version = AnchorVersion::V3;
+        assert_eq!(rules.decode(&v3).unwrap().l1_block_id, 100);
+        assert!(rules.decode(&v2).is_err());
+    }
+}
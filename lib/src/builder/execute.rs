@@ -24,6 +24,7 @@ use raiko_primitives::{
     RlpBytes,
 };
 use revm::{
+    inspector_handle_register,
     interpreter::Host,
     primitives::{
         Account, Address, EVMError, HandlerCfg, ResultAndState, SpecId, TransactTo, TxEnv,
@@ -34,11 +35,12 @@ use revm::{
 
 use super::{OptimisticDatabase, TxExecStrategy};
 use crate::{
+    anchor::AnchorRules,
     builder::BlockBuilder,
     clear_line,
-    consts::{get_network_spec, GWEI_TO_WEI},
+    consts::GWEI_TO_WEI,
     guest_mem_forget, inplace_print, print_duration,
-    taiko_utils::{check_anchor_tx, generate_transactions},
+    taiko_utils::generate_transactions,
     time::{AddAssign, Duration, Instant},
     Measurement,
 };
@@ -63,11 +65,12 @@ impl TxExecStrategy for TkoTxExecStrategy {
             .header
             .as_mut()
             .expect("Header is not initialized");
-        // Compute the spec id
-        let spec_id = block_builder
+        // Compute the rules of the forks active at the block
+        let rules = block_builder
             .chain_spec
-            .active_fork(header.number, header.timestamp)
+            .fork_rules(header.number, header.timestamp)
             .unwrap();
+        let spec_id = rules.spec_id;
         if !SpecId::enabled(spec_id, MIN_SPEC_ID) {
             bail!("Invalid protocol version: expected >= {MIN_SPEC_ID:?}, got {spec_id:?}")
         }
@@ -76,6 +79,27 @@ impl TxExecStrategy for TkoTxExecStrategy {
 
         let network = block_builder.input.network;
         let is_taiko = network.is_taiko();
+        let anchor_rules = AnchorRules::new(&block_builder.chain_spec, &rules);
+        ensure!(
+            !is_taiko || anchor_rules.is_some(),
+            "Missing the Taiko forks in the chain spec"
+        );
+
+        if let Some(block_gas_limit) = block_builder.chain_spec.block_gas_limit {
+            let anchor_gas_limit = if is_taiko {
+                block_builder.chain_spec.trusted().anchor_gas_limit
+            } else {
+                0
+            };
+            let max_gas_limit = block_gas_limit
+                .checked_add(anchor_gas_limit)
+                .ok_or_else(|| anyhow!("The max gas limit of the chain overflows"))?;
+            ensure!(
+                block_builder.input.gas_limit <= max_gas_limit,
+                "Block gas limit {} exceeds the max gas limit {block_gas_limit} of the chain",
+                block_builder.input.gas_limit
+            );
+        }
 
         // generate the transactions from the tx list
         // For taiko blocks, insert the anchor tx as the first transaction
@@ -91,8 +115,11 @@ impl TxExecStrategy for TkoTxExecStrategy {
         );
 
         // Setup the EVM environment
+        let profiler = block_builder.profiler.take();
+        let profiling = profiler.is_some();
         let evm = Evm::builder()
             .with_db(block_builder.db.take().unwrap())
+            .with_external_context(profiler.unwrap_or_default())
             .with_handler_cfg(HandlerCfg::new_with_taiko(spec_id, is_taiko))
             .modify_cfg_env(|cfg_env| {
                 // set the EVM configuration
@@ -116,10 +143,15 @@ impl TxExecStrategy for TkoTxExecStrategy {
         } else {
             evm
         };
+        let evm = if profiling {
+            evm.append_handler_register(inspector_handle_register)
+        } else {
+            evm
+        };
         let mut evm = evm.build();
 
         // Set the beacon block root in the EVM
-        if spec_id >= SpecId::CANCUN {
+        if rules.has_blob_gas() {
             let parent_beacon_block_root = header.parent_beacon_block_root.unwrap();
 
             // From EIP-4788 Beacon block root in the EVM (Cancun):
@@ -176,7 +208,7 @@ impl TxExecStrategy for TkoTxExecStrategy {
                 // set if the tx is the anchor tx
                 tx_env.taiko.is_anchor = is_anchor;
                 // set the treasury address
-                tx_env.taiko.treasury = get_network_spec(network).l2_contract.unwrap_or_default();
+                tx_env.taiko.treasury = block_builder.chain_spec.l2_contract.unwrap_or_default();
 
                 // Data blobs are not allowed on L2
                 ensure!(tx_env.blob_hashes.len() == 0);
@@ -197,18 +229,17 @@ impl TxExecStrategy for TkoTxExecStrategy {
             }
 
             // verify the anchor tx
-            if is_anchor {
-                check_anchor_tx(
-                    &block_builder.input,
-                    &tx,
-                    &tx_env.caller,
-                    block_builder.input.network,
-                )
-                .expect("invalid anchor tx");
+            if let Some(anchor_rules) = anchor_rules.filter(|_| is_anchor) {
+                anchor_rules
+                    .check(&block_builder.input, &tx, &tx_env.caller)
+                    .expect("invalid anchor tx");
             }
 
             // verify transaction gas
-            let block_available_gas = block_builder.input.gas_limit - cumulative_gas_used;
+            let block_available_gas = block_builder
+                .input
+                .gas_limit
+                .saturating_sub(cumulative_gas_used);
             if block_available_gas < tx_env.gas_limit {
                 if is_optimistic {
                     continue;
@@ -339,14 +370,18 @@ impl TxExecStrategy for TkoTxExecStrategy {
         header.receipts_root = receipt_trie.hash();
         header.logs_bloom = logs_bloom;
         header.gas_used = cumulative_gas_used;
-        if spec_id >= SpecId::SHANGHAI {
+        if rules.has_withdrawals() {
             header.withdrawals_root = Some(withdrawals_trie.hash());
         };
-        if spec_id >= SpecId::CANCUN {
+        if rules.has_blob_gas() {
             header.blob_gas_used = Some(blob_gas_used);
         }
         measurement.stop();
 
+        if profiling {
+            block_builder.profiler = Some(take(&mut evm.context.external));
+        }
+
         // Leak memory, save cycles
         guest_mem_forget([tx_trie, receipt_trie, withdrawals_trie]);
         // Return block builder with updated database
@@ -23,8 +23,9 @@ use crate::{
         finalize::{BlockFinalizeStrategy, MemDbBlockFinalizeStrategy},
         initialize::{DbInitStrategy, MemDbInitStrategy},
         prepare::{HeaderPrepStrategy, TaikoHeaderPrepStrategy},
+        profiler::EvmProfiler,
     },
-    consts::{get_network_spec, ChainSpec},
+    consts::ChainSpec,
     input::GuestInput,
     mem_db::MemDb,
 };
@@ -33,6 +34,7 @@ pub mod execute;
 mod finalize;
 mod initialize;
 pub mod prepare;
+pub mod profiler;
 
 /// Optimistic database
 pub trait OptimisticDatabase {
@@ -50,6 +52,7 @@ pub struct BlockBuilder<D> {
     pub(crate) input: GuestInput,
     pub(crate) db: Option<D>,
     pub(crate) header: Option<AlloyConsensusHeader>,
+    pub(crate) profiler: Option<EvmProfiler>,
 }
 
 impl<D> BlockBuilder<D>
@@ -60,10 +63,11 @@ where
     /// Creates a new block builder.
     pub fn new(input: &GuestInput) -> BlockBuilder<D> {
         BlockBuilder {
-            chain_spec: get_network_spec(input.network),
+            chain_spec: input.chain_spec.clone(),
             db: None,
             header: None,
             input: input.clone(),
+            profiler: None,
         }
     }
 
@@ -73,6 +77,12 @@ where
         self
     }
 
+    /// Profiles the execution of the transactions with the given profiler.
+    pub fn with_profiler(mut self, profiler: EvmProfiler) -> Self {
+        self.profiler = Some(profiler);
+        self
+    }
+
     /// Initializes the database from the input.
     pub fn initialize_database<T: DbInitStrategy<D>>(self) -> Result<Self> {
         T::initialize_database(self)
@@ -113,11 +123,21 @@ pub trait BlockBuilderStrategy {
 
     /// Builds a block from the given input.
     fn build_from(input: &GuestInput) -> Result<(AlloyConsensusHeader, MptNode)> {
-        BlockBuilder::<MemDb>::new(input)
+        Self::build_from_with(input, || {})
+    }
+
+    /// Builds a block from the given input, calling `executed` after the transactions are
+    /// executed and before the block is finalized, e.g. to profile the phases of the guest.
+    fn build_from_with(
+        input: &GuestInput,
+        executed: impl FnOnce(),
+    ) -> Result<(AlloyConsensusHeader, MptNode)> {
+        let builder = BlockBuilder::<MemDb>::new(input)
             .initialize_database::<Self::DbInitStrategy>()?
             .prepare_header::<Self::HeaderPrepStrategy>()?
-            .execute_transactions::<Self::TxExecStrategy>()?
-            .finalize::<Self::BlockFinalizeStrategy>()
+            .execute_transactions::<Self::TxExecStrategy>()?;
+        executed();
+        builder.finalize::<Self::BlockFinalizeStrategy>()
     }
 }
 
@@ -0,0 +1,296 @@
+//! Profiling of the EVM execution of a block.
+//!
+//! The profiler is a revm inspector aggregating the gas and the time spent per opcode, per
+//! contract and per precompile. It only runs when a block is profiled on request, inspecting
+//! every opcode slows the execution down considerably.
+
+use alloc::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use revm::{
+    interpreter::{
+        CallInputs, CallOutcome, CreateInputs, CreateOutcome, Interpreter, OPCODE_JUMPMAP,
+    },
+    primitives::Address,
+    Database, EvmContext, Inspector,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    builder::{BlockBuilder, BlockBuilderStrategy},
+    input::GuestInput,
+    mem_db::MemDb,
+    time::Instant,
+};
+
+/// The gas and the time spent on an opcode, a contract or a precompile.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionCost {
+    /// The number of times it was executed.
+    pub count: u64,
+    /// The gas it used, without the gas of the calls it made.
+    pub gas: u64,
+    /// The wall-time it took in nanoseconds, without the time of the calls it made.
+    pub time_ns: u64,
+}
+
+impl ExecutionCost {
+    fn add(&mut self, gas: u64, time_ns: u64) {
+        self.count += 1;
+        self.gas += gas;
+        self.time_ns += time_ns;
+    }
+}
+
+/// The cost of the EVM execution of a block.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvmProfile {
+    /// The cost of every opcode executed, by opcode name.
+    pub opcodes: BTreeMap<String, ExecutionCost>,
+    /// The cost of the opcodes executed in the code of every contract, by contract address.
+    pub contracts: BTreeMap<Address, ExecutionCost>,
+    /// The cost of every precompile called, by precompile name.
+    pub precompiles: BTreeMap<String, ExecutionCost>,
+}
+
+/// The name of the precompile at the given address, if it is one of the precompiles up to
+/// Cancun.
+fn precompile_name(address: &Address) -> Option<&'static str> {
+    let (zeros, index) = address.0.split_at(19);
+    if zeros.iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    Some(match index[0] {
+        1 => "ecrecover",
+        2 => "sha256",
+        3 => "ripemd160",
+        4 => "identity",
+        5 => "modexp",
+        6 => "bn254_add",
+        7 => "bn254_mul",
+        8 => "bn254_pairing",
+        9 => "blake2f",
+        10 => "kzg_point_evaluation",
+        _ => return None,
+    })
+}
+
+/// CREATE, CALL, CALLCODE, DELEGATECALL, CREATE2 and STATICCALL.
+const FRAME_OPCODES: [u8; 6] = [0xf0, 0xf1, 0xf2, 0xf4, 0xf5, 0xfa];
+
+/// The opcode being executed.
+#[derive(Clone, Copy, Debug)]
+struct Step {
+    opcode: u8,
+    contract: Address,
+    gas_remaining: u64,
+    start: Instant,
+}
+
+/// A call or create frame, with the opcode of the caller that started it.
+#[derive(Clone, Copy, Debug)]
+struct Frame {
+    caller: Option<(u8, Address)>,
+    start: Instant,
+}
+
+/// The revm inspector collecting the [EvmProfile] of the execution.
+#[derive(Clone, Debug, Default)]
+pub struct EvmProfiler {
+    profile: EvmProfile,
+    step: Option<Step>,
+    /// The last opcode executed, which started the next call or create.
+    last: Option<(u8, Address)>,
+    frames: Vec<Frame>,
+}
+
+impl EvmProfiler {
+    /// The profile of the execution so far.
+    pub fn into_profile(self) -> EvmProfile {
+        self.profile
+    }
+
+    fn enter(&mut self) {
+        // The calls of the transactions themselves are not started by an opcode.
+        let caller = self
+            .last
+            .take()
+            .filter(|(opcode, _)| FRAME_OPCODES.contains(opcode));
+        self.frames.push(Frame {
+            caller,
+            start: Instant::now(),
+        });
+    }
+
+    /// Leave the frame given the gas forwarded to it, which was charged to the opcode that
+    /// started it but is accounted to the opcodes of the frame. Calls of precompiles have no
+    /// frame of opcodes and are accounted to the precompile.
+    fn exit(&mut self, forwarded: u64, gas_used: u64, precompile: Option<&str>) {
+        let Some(frame) = self.frames.pop() else {
+            return;
+        };
+        if let Some(name) = precompile {
+            let time_ns = frame.start.elapsed().as_nanos() as u64;
+            self.profile
+                .precompiles
+                .entry(name.to_string())
+                .or_default()
+                .add(gas_used, time_ns);
+        }
+        if let Some((opcode, contract)) = frame.caller {
+            let mut refund =
+                |cost: &mut ExecutionCost| cost.gas = cost.gas.saturating_sub(forwarded);
+            refund(self.profile.opcodes.entry(opcode_name(opcode)).or_default());
+            refund(self.profile.contracts.entry(contract).or_default());
+        }
+    }
+}
+
+fn opcode_name(opcode: u8) -> String {
+    OPCODE_JUMPMAP[opcode as usize].map_or_else(|| format!("0x{opcode:02x}"), str::to_string)
+}
+
+impl<DB: Database> Inspector<DB> for EvmProfiler {
+    fn step(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        self.step = Some(Step {
+            opcode: interp.current_opcode(),
+            contract: interp.contract.address,
+            gas_remaining: interp.gas.remaining(),
+            start: Instant::now(),
+        });
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        let Some(step) = self.step.take() else {
+            return;
+        };
+        let time_ns = step.start.elapsed().as_nanos() as u64;
+        let gas = step.gas_remaining.saturating_sub(interp.gas.remaining());
+        self.profile
+            .opcodes
+            .entry(opcode_name(step.opcode))
+            .or_default()
+            .add(gas, time_ns);
+        self.profile
+            .contracts
+            .entry(step.contract)
+            .or_default()
+            .add(gas, time_ns);
+        self.last = Some((step.opcode, step.contract));
+    }
+
+    fn call(
+        &mut self,
+        _context: &mut EvmContext<DB>,
+        _inputs: &mut CallInputs,
+    ) -> Option<CallOutcome> {
+        self.enter();
+        None
+    }
+
+    fn call_end(
+        &mut self,
+        _context: &mut EvmContext<DB>,
+        inputs: &CallInputs,
+        outcome: CallOutcome,
+    ) -> CallOutcome {
+        // The stipend of calls with value is forwarded without being charged to the caller.
+        let stipend = if inputs.transfer.value.is_zero() {
+            0
+        } else {
+            revm::interpreter::gas::CALL_STIPEND
+        };
+        self.exit(
+            inputs.gas_limit.saturating_sub(stipend),
+            outcome.result.gas.spent(),
+            precompile_name(&inputs.contract),
+        );
+        outcome
+    }
+
+    fn create(
+        &mut self,
+        _context: &mut EvmContext<DB>,
+        _inputs: &mut CreateInputs,
+    ) -> Option<CreateOutcome> {
+        self.enter();
+        None
+    }
+
+    fn create_end(
+        &mut self,
+        _context: &mut EvmContext<DB>,
+        inputs: &CreateInputs,
+        outcome: CreateOutcome,
+    ) -> CreateOutcome {
+        self.exit(inputs.gas_limit, outcome.result.gas.spent(), None);
+        outcome
+    }
+}
+
+/// Execute the transactions of the block of the input with the profiler and return the
+/// profile of the execution.
+pub fn profile_block<S: BlockBuilderStrategy>(input: &GuestInput) -> Result<EvmProfile> {
+    let builder = BlockBuilder::<MemDb>::new(input)
+        .with_profiler(EvmProfiler::default())
+        .initialize_database::<S::DbInitStrategy>()?
+        .prepare_header::<S::HeaderPrepStrategy>()?
+        .execute_transactions::<S::TxExecStrategy>()?;
+    builder
+        .profiler
+        .map(EvmProfiler::into_profile)
+        .context("The transactions were executed without the profiler")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precompile_name() {
+        let precompile = |index: u8| {
+            let mut address = Address::ZERO;
+            address.0[19] = index;
+            precompile_name(&address)
+        };
+        assert_eq!(precompile(1), Some("ecrecover"));
+        assert_eq!(precompile(8), Some("bn254_pairing"));
+        assert_eq!(precompile(0), None);
+        assert_eq!(precompile(11), None);
+        let mut address = Address::ZERO;
+        address.0[0] = 1;
+        address.0[19] = 1;
+        assert_eq!(precompile_name(&address), None);
+    }
+
+    #[test]
+    fn test_frame_gas() {
+        let mut profiler = EvmProfiler::default();
+        let contract = Address::repeat_byte(1);
+        // A CALL charged 100 gas to the caller and forwarded 90 of it to the callee.
+        profiler.last = Some((0xf1, contract));
+        profiler.profile.opcodes.insert(
+            "CALL".to_string(),
+            ExecutionCost {
+                count: 1,
+                gas: 100,
+                time_ns: 0,
+            },
+        );
+        profiler.profile.contracts.insert(
+            contract,
+            ExecutionCost {
+                count: 1,
+                gas: 100,
+                time_ns: 0,
+            },
+        );
+        profiler.enter();
+        profiler.exit(90, 3000, Some("sha256"));
+        let profile = profiler.into_profile();
+        assert_eq!(profile.opcodes["CALL"].gas, 10);
+        assert_eq!(profile.contracts[&contract].gas, 10);
+        assert_eq!(profile.precompiles["sha256"].count, 1);
+        assert_eq!(profile.precompiles["sha256"].gas, 3000);
+    }
+}
@@ -25,6 +25,7 @@ use raiko_primitives::{uint, BlockNumber, ChainId, U256};
 use revm::primitives::SpecId;
 use serde::{Deserialize, Serialize};
 
+use crate::anchor::ANCHOR_GAS_LIMIT;
 #[cfg(not(feature = "std"))]
 use crate::no_std::*;
 
@@ -66,6 +67,9 @@ lazy_static! {
             sgx_verifier_address: None,
             genesis_time: 0u64,
             seconds_per_slot: 1u64,
+            block_gas_limit: None,
+            anchor_gas_limit: ANCHOR_GAS_LIMIT,
+            taiko_forks: BTreeMap::new(),
         };
 
     /// The Ethereum testnet "holesky" specification.
@@ -90,6 +94,9 @@ lazy_static! {
             sgx_verifier_address: None,
             genesis_time: 0u64,
             seconds_per_slot: 1u64,
+            block_gas_limit: None,
+            anchor_gas_limit: ANCHOR_GAS_LIMIT,
+            taiko_forks: BTreeMap::new(),
         };
 
     /// The Taiko A6 specification.
@@ -113,6 +120,9 @@ lazy_static! {
         ),
         genesis_time: 0u64,
         seconds_per_slot: 1u64,
+        block_gas_limit: None,
+        anchor_gas_limit: ANCHOR_GAS_LIMIT,
+        taiko_forks: BTreeMap::from([(TaikoFork::Katla, ForkCondition::Block(0))]),
     };
 
     /// The Taiko A7 specification.
@@ -136,6 +146,9 @@ lazy_static! {
         ),
         genesis_time: 1695902400u64,
         seconds_per_slot: 12u64,
+        block_gas_limit: None,
+        anchor_gas_limit: ANCHOR_GAS_LIMIT,
+        taiko_forks: BTreeMap::from([(TaikoFork::Hekla, ForkCondition::Block(0))]),
     };
 }
 
@@ -148,8 +161,33 @@ pub fn get_network_spec(network: Network) -> ChainSpec {
     }
 }
 
+/// The built-in spec of the chain with the given id, if raiko knows the chain.
+fn builtin_spec(chain_id: ChainId) -> Option<&'static ChainSpec> {
+    [
+        &*ETH_MAINNET_CHAIN_SPEC,
+        &*ETH_HOLESKY_CHAIN_SPEC,
+        &*TAIKO_A6_CHAIN_SPEC,
+        &*TAIKO_A7_CHAIN_SPEC,
+    ]
+    .into_iter()
+    .find(|spec| spec.chain_id == chain_id)
+}
+
+/// Check the chain spec of an input against the spec of its network built into raiko. The
+/// guests take the chain spec from the input, which the prover supplies and the instance hash
+/// doesn't commit to, so they only prove blocks of the built-in chains with their own rules.
+pub fn verify_chain_spec(network: Network, chain_spec: &ChainSpec) -> Result<()> {
+    if *chain_spec != get_network_spec(network) {
+        bail!(
+            "the chain spec of chain {} is not the spec of {network}",
+            chain_spec.chain_id
+        );
+    }
+    Ok(())
+}
+
 /// The condition at which a fork is activated.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ForkCondition {
     /// The fork is activated with a certain block.
     Block(BlockNumber),
@@ -192,7 +230,7 @@ impl Default for Eip1559Constants {
 }
 
 /// Specification of a specific chain.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ChainSpec {
     pub chain_id: ChainId,
     pub max_spec_id: SpecId,
@@ -203,6 +241,20 @@ pub struct ChainSpec {
     pub sgx_verifier_address: Option<Address>,
     pub genesis_time: u64,
     pub seconds_per_slot: u64,
+    /// The max gas limit of the blocks, excluding the gas of the anchor transaction. Not
+    /// checked if unset.
+    #[serde(default)]
+    pub block_gas_limit: Option<u64>,
+    /// The gas limit of the anchor transaction of Taiko blocks.
+    #[serde(default = "default_anchor_gas_limit")]
+    pub anchor_gas_limit: u64,
+    /// The Taiko protocol forks of the chain, empty for L1 chains.
+    #[serde(default)]
+    pub taiko_forks: BTreeMap<TaikoFork, ForkCondition>,
+}
+
+fn default_anchor_gas_limit() -> u64 {
+    ANCHOR_GAS_LIMIT
 }
 
 impl ChainSpec {
@@ -222,12 +274,21 @@ impl ChainSpec {
             sgx_verifier_address: None,
             genesis_time: 0u64,
             seconds_per_slot: 1u64,
+            block_gas_limit: None,
+            anchor_gas_limit: ANCHOR_GAS_LIMIT,
+            taiko_forks: BTreeMap::new(),
         }
     }
     /// Returns the network chain ID.
     pub fn chain_id(&self) -> ChainId {
         self.chain_id
     }
+    /// Returns the spec the protocol rules of the chain are taken from: the built-in spec of the
+    /// chain ID, which the proof commits to, for the chains raiko knows, whatever this spec
+    /// says, or this spec for other chains.
+    pub fn trusted(&self) -> &ChainSpec {
+        builtin_spec(self.chain_id).unwrap_or(self)
+    }
     /// Returns the [SpecId] for a given block number and timestamp or an error if not
     /// supported.
     pub fn active_fork(&self, block_no: BlockNumber, timestamp: u64) -> Result<SpecId> {
@@ -242,6 +303,25 @@ impl ChainSpec {
             None => bail!("no supported fork for block {block_no}"),
         }
     }
+    /// Returns the rules of the forks active at a given block number and timestamp, or an error
+    /// if the block is before the first supported fork. The Taiko forks are the ones of the
+    /// [trusted](Self::trusted) spec.
+    pub fn fork_rules(&self, block_no: BlockNumber, timestamp: u64) -> Result<ForkRules> {
+        let spec_id = self.active_fork(block_no, timestamp)?;
+        let taiko_forks = &self.trusted().taiko_forks;
+        let taiko_fork = taiko_forks
+            .iter()
+            .rev()
+            .find(|(_, fork)| fork.active(block_no, timestamp))
+            .map(|(taiko_fork, _)| *taiko_fork);
+        if taiko_fork.is_none() && !taiko_forks.is_empty() {
+            bail!("no supported Taiko fork for block {block_no}");
+        }
+        Ok(ForkRules {
+            spec_id,
+            taiko_fork,
+        })
+    }
     /// Returns the Eip1559 constants
     pub fn gas_constants(&self) -> &Eip1559Constants {
         &self.eip_1559_constants
@@ -257,6 +337,47 @@ impl ChainSpec {
     }
 }
 
+/// The forks of the Taiko protocol, in activation order.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum TaikoFork {
+    /// The protocol of the Katla testnet (A6)
+    Katla,
+    /// The protocol of the Hekla testnet (A7)
+    Hekla,
+    /// The Ontake protocol, anchoring blocks with `anchorV2` and the base fee config
+    Ontake,
+    /// The Pacaya protocol, anchoring blocks with `anchorV3` and the signal slots
+    Pacaya,
+}
+
+/// The rules a block is built and proven with, selected by the forks active at the block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForkRules {
+    /// The EVM spec the block is executed with.
+    pub spec_id: SpecId,
+    /// The Taiko fork of the block, unset for L1 blocks.
+    pub taiko_fork: Option<TaikoFork>,
+}
+
+impl ForkRules {
+    /// Whether the header has a withdrawals root (Shanghai).
+    pub fn has_withdrawals(&self) -> bool {
+        self.spec_id >= SpecId::SHANGHAI
+    }
+
+    /// Whether the header has the blob gas fields and the parent beacon block root, and the
+    /// beacon roots contract is called before the transactions (Cancun).
+    pub fn has_blob_gas(&self) -> bool {
+        self.spec_id >= SpecId::CANCUN
+    }
+
+    /// Whether the block is proposed with the `BlockProposed` event of the Katla testnet.
+    pub fn testnet_proposal(&self) -> bool {
+        self.taiko_fork == Some(TaikoFork::Katla)
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Network {
     /// The Ethereum Mainnet
@@ -327,4 +448,87 @@ mod tests {
             Some(SpecId::SHANGHAI)
         );
     }
+
+    #[test]
+    fn test_verify_chain_spec() {
+        assert!(verify_chain_spec(Network::TaikoA7, &TAIKO_A7_CHAIN_SPEC).is_ok());
+        assert!(verify_chain_spec(Network::TaikoA6, &TAIKO_A7_CHAIN_SPEC).is_err());
+        let mut chain_spec = TAIKO_A7_CHAIN_SPEC.clone();
+        chain_spec.taiko_forks = BTreeMap::from([(TaikoFork::Katla, ForkCondition::Block(0))]);
+        assert!(verify_chain_spec(Network::TaikoA7, &chain_spec).is_err());
+    }
+
+    #[test]
+    fn test_fork_rules() {
+        let rules = |block_no, timestamp| {
+            ETH_MAINNET_CHAIN_SPEC
+                .fork_rules(block_no, timestamp)
+                .unwrap()
+        };
+        assert!(!rules(17034869, 0).has_withdrawals());
+        assert!(rules(17034870, 0).has_withdrawals());
+        assert!(!rules(17034870, 1710338134).has_blob_gas());
+        assert!(rules(17034870, 1710338135).has_blob_gas());
+        assert_eq!(rules(17034870, 1710338135).taiko_fork, None);
+
+        let a7 = TAIKO_A7_CHAIN_SPEC.fork_rules(10, 0).unwrap();
+        assert_eq!(a7.taiko_fork, Some(TaikoFork::Hekla));
+        assert!(!a7.testnet_proposal() && !a7.has_blob_gas());
+        let a6 = TAIKO_A6_CHAIN_SPEC.fork_rules(10, 0).unwrap();
+        assert!(a6.testnet_proposal());
+    }
+
+    #[test]
+    fn test_trusted_spec() {
+        // The rules of a built-in chain don't follow the spec of the input
+        let mut chain_spec = TAIKO_A7_CHAIN_SPEC.clone();
+        chain_spec.anchor_gas_limit = 1_000_000;
+        chain_spec.taiko_forks = BTreeMap::from([(TaikoFork::Katla, ForkCondition::Block(0))]);
+        assert_eq!(chain_spec.trusted(), &*TAIKO_A7_CHAIN_SPEC);
+        assert_eq!(
+            chain_spec.fork_rules(10, 0).unwrap().taiko_fork,
+            Some(TaikoFork::Hekla)
+        );
+
+        chain_spec.chain_id = 167100;
+        assert_eq!(chain_spec.trusted(), &chain_spec);
+        assert_eq!(
+            chain_spec.fork_rules(10, 0).unwrap().taiko_fork,
+            Some(TaikoFork::Katla)
+        );
+    }
+
+    #[test]
+    fn test_taiko_fork_boundary() {
+        let mut chain_spec = TAIKO_A6_CHAIN_SPEC.clone();
+        chain_spec.chain_id = 167100;
+        chain_spec.max_spec_id = SpecId::CANCUN;
+        chain_spec
+            .hard_forks
+            .insert(SpecId::CANCUN, ForkCondition::Timestamp(1000));
+        chain_spec
+            .taiko_forks
+            .insert(TaikoFork::Hekla, ForkCondition::Block(100));
+        let katla = chain_spec.fork_rules(99, 999).unwrap();
+        assert_eq!(katla.taiko_fork, Some(TaikoFork::Katla));
+        assert!(katla.testnet_proposal() && !katla.has_blob_gas());
+        let hekla = chain_spec.fork_rules(100, 999).unwrap();
+        assert_eq!(hekla.taiko_fork, Some(TaikoFork::Hekla));
+        assert!(!hekla.testnet_proposal() && !hekla.has_blob_gas());
+        assert!(chain_spec.fork_rules(100, 1000).unwrap().has_blob_gas());
+
+        // A Taiko chain without an active protocol fork is not supported.
+        chain_spec
+            .taiko_forks
+            .insert(TaikoFork::Katla, ForkCondition::Block(50));
+        assert!(chain_spec.fork_rules(49, 0).is_err());
+        assert!(chain_spec.fork_rules(50, 0).is_ok());
+
+        let chain_spec: ChainSpec =
+            serde_json::from_value(serde_json::to_value(&chain_spec).unwrap()).unwrap();
+        assert_eq!(
+            chain_spec.fork_rules(100, 0).unwrap().taiko_fork,
+            Some(TaikoFork::Hekla)
+        );
+    }
 }
@@ -17,8 +17,7 @@ use std::path::PathBuf;
 
 use alloy_consensus::Header as AlloyConsensusHeader;
 use alloy_rpc_types::Withdrawal as AlloyWithdrawal;
-use alloy_sol_types::{sol, SolCall};
-use anyhow::{anyhow, Result};
+use alloy_sol_types::sol;
 use raiko_primitives::{mpt::MptNode, Address, Bytes, FixedBytes, B256, U256};
 use revm::primitives::HashMap;
 use serde::{Deserialize, Serialize};
@@ -26,7 +25,14 @@ use serde_with::serde_as;
 
 #[cfg(not(feature = "std"))]
 use crate::no_std::*;
-use crate::{consts::Network, serde_with::RlpBytes};
+use crate::{
+    consts::{ChainSpec, Network},
+    serde_with::{RlpBytes, StorageArena, TrieArena},
+};
+
+/// Inputs whose tries and contracts are read in place, for the local provers.
+#[cfg(feature = "rkyv")]
+pub mod zero_copy;
 
 /// Represents the state of an account's storage.
 /// The storage trie together with the used storage slots allow us to reconstruct all the
@@ -39,6 +45,8 @@ pub type StorageEntry = (MptNode, Vec<U256>);
 pub struct GuestInput {
     /// The network to generate the proof for
     pub network: Network,
+    /// The specification of the chain the block is on
+    pub chain_spec: ChainSpec,
     /// Block number
     pub block_number: u64,
     /// Block gas used
@@ -61,10 +69,12 @@ pub struct GuestInput {
     /// List of stake withdrawals for execution
     pub withdrawals: Vec<AlloyWithdrawal>,
     /// State trie of the parent block.
+    #[serde_as(as = "TrieArena")]
     pub parent_state_trie: MptNode,
     /// Maps each address with its storage trie and the used storage slots.
+    #[serde_as(as = "StorageArena")]
     pub parent_storage: HashMap<Address, StorageEntry>,
-    /// The code of all unique contracts.
+    /// The code of all unique contracts, once per code hash.
     pub contracts: Vec<Bytes>,
     /// List of at most 256 previous block headers
     #[serde_as(as = "Vec<RlpBytes>")]
@@ -91,6 +101,10 @@ pub struct TaikoGuestInput {
     pub block_proposed: BlockProposed,
     pub prover_data: TaikoProverData,
     pub tx_blob_hash: Option<B256>,
+    /// The KZG commitment of the blob holding the tx list.
+    pub blob_commitment: Option<FixedBytes<48>>,
+    /// The KZG proof of the blob holding the tx list.
+    pub blob_proof: Option<FixedBytes<48>>,
 }
 
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
@@ -112,23 +126,6 @@ pub struct WrappedHeader {
     pub header: AlloyConsensusHeader,
 }
 
-sol! {
-    function anchor(
-        bytes32 l1Hash,
-        bytes32 l1StateRoot,
-        uint64 l1BlockId,
-        uint32 parentGasUsed
-    )
-        external
-    {}
-}
-
-#[inline]
-pub fn decode_anchor(bytes: &[u8]) -> Result<anchorCall> {
-    anchorCall::abi_decode(bytes, true).map_err(|e| anyhow!(e))
-    // .context("Invalid anchor call")
-}
-
 sol! {
     #[derive(Debug, Default, Deserialize, Serialize)]
     struct EthDeposit {
@@ -332,8 +329,10 @@ impl From<taiko_a6::BlockProposed> for BlockProposed {
 use std::path::Path;
 
 #[cfg(feature = "std")]
-pub fn get_input_path(dir: &Path, block_number: u64, network: &str) -> PathBuf {
-    dir.join(format!("input-{network}-{block_number}.bin"))
+pub fn get_input_path(dir: &Path, block_number: u64, block_hash: &B256, network: &str) -> PathBuf {
+    dir.join(format!(
+        "input-{network}-{block_number}-{block_hash:x}.bin.zst"
+    ))
 }
 
 #[cfg(test)]
@@ -346,4 +345,23 @@ mod tests {
         let input = GuestInput::default();
         let _: GuestInput = bincode::deserialize(&bincode::serialize(&input).unwrap()).unwrap();
     }
+
+    #[test]
+    fn input_storage_roundtrip() {
+        let mut trie = MptNode::default();
+        trie.insert(&[0x11; 32], vec![0x22; 40]).unwrap();
+        let mut input = GuestInput::default();
+        for address in [Address::repeat_byte(1), Address::repeat_byte(2)] {
+            input
+                .parent_storage
+                .insert(address, (trie.clone(), vec![U256::from(1)]));
+        }
+        let decoded: GuestInput =
+            bincode::deserialize(&bincode::serialize(&input).unwrap()).unwrap();
+        assert_eq!(decoded.parent_storage.len(), 2);
+        for (trie_decoded, slots) in decoded.parent_storage.values() {
+            assert_eq!(trie_decoded.hash(), trie.hash());
+            assert_eq!(slots, &vec![U256::from(1)]);
+        }
+    }
 }
@@ -0,0 +1,155 @@
+//! An encoding of the [GuestInput] for local provers, whose tries and contract code are read from
+//! an archive instead of being deserialized.
+//!
+//! The encoded input starts with a magic and the length of the rest of the input in bincode,
+//! without its tries and contracts. They follow in an [rkyv] archive, aligned for it, which is
+//! copied out of the encoded input, validated once and then read in place: the tries are rebuilt
+//! straight from the archived nodes and the contracts are copied out of it. The encoded input may
+//! be in memory the prover can change at any time, e.g. the shared memory of the SGX guest, so
+//! nothing the decoded input holds points into it.
+
+use std::io::Write;
+
+use anyhow::{anyhow, ensure, Result};
+use raiko_primitives::{mpt::arena::archive::Tries, Bytes};
+use rkyv::{
+    ser::{
+        serializers::{AllocScratch, CompositeSerializer, SharedSerializeMap, WriteSerializer},
+        Serializer,
+    },
+    AlignedVec, Archive, Serialize,
+};
+
+use crate::input::GuestInput;
+
+/// The magic every zero-copy input starts with.
+pub const MAGIC: [u8; 4] = *b"RKZC";
+
+/// The magic followed by the length of the bincode part.
+const HEADER_LEN: usize = MAGIC.len() + 8;
+
+/// The alignment of the archive in the encoded input.
+const ARCHIVE_ALIGN: usize = 16;
+
+/// The parts of the input read in place.
+#[derive(Archive, Serialize)]
+#[archive(check_bytes)]
+struct Blobs {
+    tries: Tries,
+    /// The code of the contracts, once per code hash.
+    contracts: Vec<Vec<u8>>,
+}
+
+/// Whether the bytes are an input encoded by [encode].
+pub fn is_encoded(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Encode the input into the writer. The tries and contracts are taken out of the input while
+/// the rest of it is serialized, and put back before returning.
+pub fn encode<W: Write>(input: &mut GuestInput, mut writer: W) -> Result<()> {
+    let state_trie = core::mem::take(&mut input.parent_state_trie);
+    let storage = core::mem::take(&mut input.parent_storage);
+    let contracts = core::mem::take(&mut input.contracts);
+    let rest = bincode::serialize(&*input);
+    input.parent_state_trie = state_trie;
+    input.parent_storage = storage;
+    input.contracts = contracts;
+    let rest = rest?;
+
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&(rest.len() as u64).to_le_bytes())?;
+    writer.write_all(&rest)?;
+    let padding = archive_offset(rest.len()) - HEADER_LEN - rest.len();
+    writer.write_all(&[0; ARCHIVE_ALIGN][..padding])?;
+
+    let blobs = Blobs {
+        tries: Tries::new(&input.parent_state_trie, &input.parent_storage),
+        contracts: input.contracts.iter().map(|code| code.to_vec()).collect(),
+    };
+    // The positions in the archive are relative to its start, which is aligned in the input.
+    let mut serializer = CompositeSerializer::new(
+        WriteSerializer::new(&mut writer),
+        AllocScratch::default(),
+        SharedSerializeMap::default(),
+    );
+    serializer
+        .serialize_value(&blobs)
+        .map_err(|e| anyhow!("Failed to archive the input: {e}"))?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Decode an input encoded by [encode]. The archive is copied before it is validated, so the
+/// bytes can change while and after they're decoded without invalidating the input.
+pub fn decode(bytes: &[u8]) -> Result<GuestInput> {
+    ensure!(is_encoded(bytes), "Not a zero-copy input");
+    ensure!(bytes.len() >= HEADER_LEN, "Truncated zero-copy input");
+    let len = u64::from_le_bytes(bytes[MAGIC.len()..HEADER_LEN].try_into().unwrap()) as usize;
+    let rest = bytes
+        .get(HEADER_LEN..HEADER_LEN + len)
+        .ok_or_else(|| anyhow!("Truncated zero-copy input"))?;
+    let mut input: GuestInput = bincode::deserialize(rest)?;
+
+    let mut archive = AlignedVec::new();
+    archive.extend_from_slice(
+        bytes
+            .get(archive_offset(len)..)
+            .ok_or_else(|| anyhow!("Truncated zero-copy input"))?,
+    );
+    let blobs = rkyv::check_archived_root::<Blobs>(&archive)
+        .map_err(|e| anyhow!("Invalid input archive: {e}"))?;
+    input.parent_state_trie = blobs.tries.state_trie()?;
+    input.parent_storage = blobs.tries.storage()?;
+    input.contracts = blobs
+        .contracts
+        .iter()
+        .map(|code| Bytes::copy_from_slice(code.as_slice()))
+        .collect();
+    Ok(input)
+}
+
+/// The offset of the archive after the bincode part of the given length.
+fn archive_offset(len: usize) -> usize {
+    (HEADER_LEN + len).div_ceil(ARCHIVE_ALIGN) * ARCHIVE_ALIGN
+}
+
+#[cfg(test)]
+mod tests {
+    use raiko_primitives::{mpt::MptNode, Address, U256};
+
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut trie = MptNode::default();
+        trie.insert(&[0x11; 32], vec![0x22; 40]).unwrap();
+        let mut input = GuestInput {
+            block_number: 10,
+            parent_state_trie: trie.clone(),
+            contracts: vec![Bytes::from_static(&[0x60, 0x00])],
+            ..Default::default()
+        };
+        input
+            .parent_storage
+            .insert(Address::repeat_byte(1), (trie.clone(), vec![U256::from(1)]));
+
+        let mut encoded = Vec::new();
+        encode(&mut input, &mut encoded).unwrap();
+        // The input is left as it was.
+        assert_eq!(input.parent_state_trie.hash(), trie.hash());
+        assert_eq!(input.contracts.len(), 1);
+
+        // The decoded input doesn't depend on the bytes, which don't have to be aligned.
+        let mut bytes = vec![0];
+        bytes.extend_from_slice(&encoded);
+        let decoded = decode(&bytes[1..]).unwrap();
+        bytes.fill(0);
+        assert_eq!(decoded.block_number, 10);
+        assert_eq!(decoded.parent_state_trie.hash(), trie.hash());
+        assert_eq!(decoded.contracts, input.contracts);
+        let (storage_trie, slots) = &decoded.parent_storage[&Address::repeat_byte(1)];
+        assert_eq!(storage_trie.hash(), trie.hash());
+        assert_eq!(slots, &vec![U256::from(1)]);
+    }
+}
@@ -28,6 +28,8 @@ mod no_std {
     };
 }
 
+pub mod aggregate;
+pub mod anchor;
 pub mod builder;
 pub mod consts;
 pub mod input;
@@ -75,6 +77,10 @@ mod time {
         pub fn subsec_millis(&self) -> u64 {
             0
         }
+
+        pub fn as_nanos(&self) -> u128 {
+            0
+        }
     }
 
     impl AddAssign for Duration {
@@ -170,6 +176,11 @@ where
 }
 
 pub mod serde_with {
+    use raiko_primitives::{
+        mpt::{arena::NodeArena, MptNode, StorageEntry},
+        Address, U256,
+    };
+    use revm::primitives::HashMap;
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
     use serde_with::{DeserializeAs, SerializeAs};
 
@@ -202,4 +213,67 @@ pub mod serde_with {
             T::decode_bytes(bytes).map_err(serde::de::Error::custom)
         }
     }
+
+    /// Serializes a trie through a [NodeArena], so its identical subtries are written once.
+    pub struct TrieArena {}
+
+    impl SerializeAs<MptNode> for TrieArena {
+        fn serialize_as<S>(source: &MptNode, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            let mut arena = NodeArena::default();
+            let root = arena.insert(source);
+            (arena, root).serialize(serializer)
+        }
+    }
+
+    impl<'de> DeserializeAs<'de, MptNode> for TrieArena {
+        fn deserialize_as<D>(deserializer: D) -> Result<MptNode, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let (arena, root) = <(NodeArena, u32)>::deserialize(deserializer)?;
+            arena.get(root).map_err(serde::de::Error::custom)
+        }
+    }
+
+    /// Serializes the storage tries of the accounts through one [NodeArena], so the subtries
+    /// repeated across accounts are written once.
+    pub struct StorageArena {}
+
+    impl SerializeAs<HashMap<Address, StorageEntry>> for StorageArena {
+        fn serialize_as<S>(
+            source: &HashMap<Address, StorageEntry>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            let mut arena = NodeArena::default();
+            let accounts: Vec<_> = source
+                .iter()
+                .map(|(address, (trie, slots))| (address, arena.insert(trie), slots))
+                .collect();
+            (arena, accounts).serialize(serializer)
+        }
+    }
+
+    impl<'de> DeserializeAs<'de, HashMap<Address, StorageEntry>> for StorageArena {
+        fn deserialize_as<D>(deserializer: D) -> Result<HashMap<Address, StorageEntry>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let (arena, accounts) =
+                <(NodeArena, Vec<(Address, u32, Vec<U256>)>)>::deserialize(deserializer)?;
+            let tries = arena
+                .get_all(accounts.iter().map(|(_, root, _)| *root))
+                .map_err(serde::de::Error::custom)?;
+            Ok(accounts
+                .into_iter()
+                .zip(tries)
+                .map(|((address, _, slots), trie)| (address, (trie, slots)))
+                .collect())
+        }
+    }
 }
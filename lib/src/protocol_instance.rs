@@ -1,16 +1,14 @@
 use alloy_consensus::Header as AlloyConsensusHeader;
 use alloy_primitives::{Address, TxHash, B256};
 use alloy_sol_types::SolValue;
-use anyhow::{ensure, Result};
+use anyhow::{anyhow, ensure, Result};
 use raiko_primitives::keccak::keccak;
 
-use super::taiko_utils::ANCHOR_GAS_LIMIT;
 #[cfg(not(feature = "std"))]
 use crate::no_std::*;
 use crate::{
-    consts::get_network_spec,
     input::{BlockMetadata, EthDeposit, GuestInput, Transition},
-    taiko_utils::HeaderHasher,
+    taiko_utils::{verify_blob, HeaderHasher},
 };
 
 #[derive(Debug)]
@@ -48,7 +46,6 @@ impl ProtocolInstance {
             )
             .into(),
             EvidenceType::PseZk => todo!(),
-            EvidenceType::Powdr => todo!(),
             EvidenceType::Succinct => keccak(
                 (
                     self.transition.clone(),
@@ -59,13 +56,19 @@ impl ProtocolInstance {
                     .abi_encode(),
             )
             .into(),
-            EvidenceType::Risc0 | EvidenceType::Native => {
+            EvidenceType::Risc0
+            | EvidenceType::Jolt
+            | EvidenceType::Powdr
+            | EvidenceType::Native => {
                 keccak((self.transition.clone(), self.prover, self.meta_hash()).abi_encode()).into()
             }
         }
     }
 }
 
+/// The length of the data of an SGX tier proof: 4b(id)+20b(pubkey)+65b(signature).
+pub const SGX_PROOF_LEN: usize = 89;
+
 #[derive(Debug)]
 pub enum EvidenceType {
     Sgx {
@@ -75,6 +78,7 @@ pub enum EvidenceType {
     Powdr,
     Succinct,
     Risc0,
+    Jolt,
     Native,
 }
 
@@ -82,10 +86,37 @@ pub enum EvidenceType {
 pub fn assemble_protocol_instance(
     input: &GuestInput,
     header: &AlloyConsensusHeader,
+) -> Result<ProtocolInstance> {
+    let pi = build_protocol_instance(input, header)?;
+
+    // Sanity check
+    if input.network.is_taiko() {
+        ensure!(
+            pi.block_metadata.abi_encode() == input.taiko.block_proposed.meta.abi_encode(),
+            format!(
+                "block hash mismatch, expected: {:?}, got: {:?}",
+                input.taiko.block_proposed.meta, pi.block_metadata
+            )
+        );
+    }
+
+    Ok(pi)
+}
+
+/// Assemble the protocol instance of the block, without checking its metadata against the
+/// metadata the block was proposed with.
+pub fn build_protocol_instance(
+    input: &GuestInput,
+    header: &AlloyConsensusHeader,
 ) -> Result<ProtocolInstance> {
     let blob_used = input.taiko.block_proposed.meta.blobUsed;
     let tx_list_hash = if blob_used {
-        input.taiko.tx_blob_hash.unwrap()
+        let tx_blob_hash = input
+            .taiko
+            .tx_blob_hash
+            .ok_or_else(|| anyhow!("Missing the blob hash of the tx list"))?;
+        verify_blob(&input.taiko, tx_blob_hash)?;
+        tx_blob_hash
     } else {
         TxHash::from(keccak(input.taiko.tx_list.as_slice()))
     };
@@ -100,8 +131,20 @@ pub fn assemble_protocol_instance(
         })
         .collect::<Vec<_>>();
 
-    let chain_spec = get_network_spec(input.network);
-    let gas_limit: u64 = header.gas_limit;
+    let chain_spec = &input.chain_spec;
+    // The proposal has the gas limit of the block without the gas of the anchor tx.
+    let anchor_gas_limit = chain_spec.trusted().anchor_gas_limit;
+    let gas_limit = header
+        .gas_limit
+        .checked_sub(anchor_gas_limit)
+        .and_then(|gas_limit| u32::try_from(gas_limit).ok())
+        .ok_or_else(|| {
+            anyhow!(
+                "Block gas limit {} is not the anchor gas limit {anchor_gas_limit} plus a 32-bit \
+                 gas limit",
+                header.gas_limit
+            )
+        })?;
     let pi = ProtocolInstance {
         transition: Transition {
             parentHash: header.parent_hash,
@@ -117,7 +160,7 @@ pub fn assemble_protocol_instance(
             depositsHash: keccak(deposits.abi_encode()).into(),
             coinbase: header.beneficiary,
             id: header.number,
-            gasLimit: (gas_limit - ANCHOR_GAS_LIMIT) as u32,
+            gasLimit: gas_limit,
             timestamp: header.timestamp,
             l1Height: input.taiko.l1_header.number,
             minTier: input.taiko.block_proposed.meta.minTier,
@@ -130,17 +173,6 @@ pub fn assemble_protocol_instance(
         sgx_verifier_address: chain_spec.sgx_verifier_address.unwrap_or_default(),
     };
 
-    // Sanity check
-    if input.network.is_taiko() {
-        ensure!(
-            pi.block_metadata.abi_encode() == input.taiko.block_proposed.meta.abi_encode(),
-            format!(
-                "block hash mismatch, expected: {:?}, got: {:?}",
-                input.taiko.block_proposed.meta, pi.block_metadata
-            )
-        );
-    }
-
     Ok(pi)
 }
 
@@ -173,6 +205,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_proposal_gas_limit() {
+        let input = GuestInput {
+            network: crate::consts::Network::TaikoA7,
+            chain_spec: crate::consts::get_network_spec(crate::consts::Network::TaikoA7),
+            ..Default::default()
+        };
+        let anchor_gas_limit = input.chain_spec.anchor_gas_limit;
+        let pi = |gas_limit| {
+            let header = AlloyConsensusHeader {
+                gas_limit,
+                ..Default::default()
+            };
+            build_protocol_instance(&input, &header).map(|pi| pi.block_metadata.gasLimit)
+        };
+
+        assert_eq!(pi(anchor_gas_limit + 15_000_000).unwrap(), 15_000_000);
+        assert!(pi(anchor_gas_limit - 1).is_err());
+        assert!(pi(anchor_gas_limit + u32::MAX as u64 + 1).is_err());
+    }
+
     #[test]
     fn test_calc_eip712_pi_hash() {
         let trans = Transition {
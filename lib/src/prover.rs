@@ -1,7 +1,15 @@
-use std::fmt;
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+};
 
 use alloy_primitives::B256;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use thiserror::Error as ThisError;
 
 use crate::{
@@ -32,6 +40,90 @@ pub type ProverResult<T, E = ProverError> = core::result::Result<T, E>;
 pub type ProverConfig = serde_json::Value;
 pub type Proof = serde_json::Value;
 
+/// A progress update reported by a prover while generating a proof.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum ProverProgress {
+    /// The given percentage of the guest execution is done.
+    Execution(u8),
+    /// The proof is generated remotely by the given proving service in the session with the
+    /// given id.
+    RemoteSession { service: String, session_id: String },
+    /// The guest program ran for the given number of cycles, split into the given number of
+    /// segments (Risc0) or shards (SP1). Either is unset if the prover doesn't know it.
+    Executed {
+        cycles: Option<u64>,
+        segments: Option<u64>,
+    },
+    /// The guest reported its profile, in the instrumentation mode of provers that support it.
+    Profiled(GuestProfile),
+    /// The generated proof was verified by the prover.
+    Verified,
+}
+
+/// Tells a prover that its proof is no longer needed, e.g. because its job was cancelled.
+/// Provers blocking their thread check it between the steps of a proof and stop early.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Reports the progress of a prover back to the caller, and tells the prover when the proof
+/// was cancelled.
+///
+/// The default reporter drops all updates.
+#[derive(Clone, Default)]
+pub struct ProgressReporter {
+    report: Option<Arc<dyn Fn(ProverProgress) + Send + Sync>>,
+    cancellation: CancellationToken,
+}
+
+impl ProgressReporter {
+    pub fn new(f: impl Fn(ProverProgress) + Send + Sync + 'static) -> Self {
+        Self {
+            report: Some(Arc::new(f)),
+            cancellation: CancellationToken::default(),
+        }
+    }
+
+    /// The reporter with the given cancellation token, e.g. of the reporter it wraps.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    pub fn cancellation(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    /// Whether the proof was cancelled, see [CancellationToken].
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    pub fn report(&self, progress: ProverProgress) {
+        if let Some(f) = &self.report {
+            f(progress);
+        }
+    }
+}
+
+impl fmt::Debug for ProgressReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressReporter")
+            .field("report", &self.report.is_some())
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
 pub trait Prover {
     #[allow(async_fn_in_trait)]
     async fn run(
@@ -40,7 +132,184 @@ pub trait Prover {
         config: &ProverConfig,
     ) -> ProverResult<Proof>;
 
+    /// Same as [Prover::run], but reports the progress of the proof generation. Provers that
+    /// can't report their progress just run.
+    #[allow(async_fn_in_trait)]
+    async fn run_with_progress(
+        input: GuestInput,
+        output: GuestOutput,
+        config: &ProverConfig,
+        _progress: &ProgressReporter,
+    ) -> ProverResult<Proof> {
+        Self::run(input, output, config).await
+    }
+
     fn instance_hash(pi: ProtocolInstance) -> B256;
+
+    /// The id of the guest program the proofs are generated for, e.g. the image id. Proofs
+    /// generated by a different program are not interchangeable.
+    fn program_id() -> Option<B256> {
+        None
+    }
+
+    /// Set up what every proof needs, e.g. the proving keys, ahead of the first proof of a
+    /// process that generates many proofs.
+    fn warm_up() -> ProverResult<()> {
+        Ok(())
+    }
+
+    /// Verify a proof generated by [Prover::run] for the input, and check that it proves the
+    /// expected output. Returns `None` for provers which can't verify their proofs.
+    fn verify(
+        _proof: &Proof,
+        _input: &GuestInput,
+        _output: &GuestOutput,
+    ) -> Option<ProverResult<()>> {
+        None
+    }
+}
+
+/// The boxed future returned by a [ProverBackend].
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// An object safe version of [Prover], which allows prover backends to be registered at
+/// runtime, including backends implemented outside of this repository.
+pub trait ProverBackend: Send + Sync {
+    /// The unique name of the prover, used as the proof type in proof requests.
+    fn name(&self) -> &str;
+
+    /// Generate a proof, see [Prover::run_with_progress].
+    fn run<'a>(
+        &'a self,
+        input: GuestInput,
+        output: GuestOutput,
+        config: &'a ProverConfig,
+        progress: &'a ProgressReporter,
+    ) -> BoxFuture<'a, ProverResult<Proof>>;
+
+    /// Get the instance hash for the protocol instance, see [Prover::instance_hash].
+    fn instance_hash(&self, pi: ProtocolInstance) -> B256;
+
+    /// Get the id of the guest program, see [Prover::program_id].
+    fn program_id(&self) -> Option<B256> {
+        None
+    }
+
+    /// Set up the prover ahead of its first proof, see [Prover::warm_up].
+    fn warm_up(&self) -> ProverResult<()> {
+        Ok(())
+    }
+
+    /// Verify a proof for the input and the expected output, see [Prover::verify].
+    fn verify(
+        &self,
+        _proof: &Proof,
+        _input: &GuestInput,
+        _output: &GuestOutput,
+    ) -> Option<ProverResult<()>> {
+        None
+    }
+}
+
+/// The cycles a zkVM guest spends on a precompile, with the zkVM accelerated implementation
+/// and with a software implementation of the same operation.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrecompileCycles {
+    pub precompile: String,
+    pub accelerated: u64,
+    pub software: u64,
+}
+
+impl PrecompileCycles {
+    /// The share of the software cycles saved by the acceleration, in percent.
+    pub fn savings(&self) -> f64 {
+        if self.software == 0 {
+            return 0.0;
+        }
+        100.0 * (1.0 - self.accelerated as f64 / self.software as f64)
+    }
+}
+
+/// The file descriptor the guest writes its [GuestProfile] to, in the instrumentation mode.
+pub const GUEST_PROFILE_FD: u32 = 10;
+
+/// The profile of a guest run, reported back to the host in the instrumentation mode.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuestProfile {
+    /// The cycles of each phase of the guest.
+    pub cycles: PhaseCycles,
+    /// The high-water mark of the heap of the guest in bytes.
+    pub peak_memory: u64,
+    /// The trie nodes visited by the reads of the tries.
+    pub trie_node_reads: u64,
+    /// The trie nodes visited by the inserts and deletes of the tries.
+    pub trie_node_writes: u64,
+}
+
+/// The cycles of the phases of the guest.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseCycles {
+    /// Reading and deserializing the input.
+    pub deserialize: u64,
+    /// Initializing the database from the input and executing the transactions.
+    pub execute: u64,
+    /// Computing the state root and the header, and the protocol instance.
+    pub finalize: u64,
+}
+
+/// A guest ELF the host selected for a proof instead of the guest built into the prover,
+/// passed to the prover as `guest` in its config.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuestSelection {
+    /// The name of the guest version.
+    pub name: String,
+    /// The path of the ELF of the guest.
+    pub elf: String,
+    /// The program id the ELF has to have, e.g. the image id registered on-chain.
+    pub program_id: B256,
+}
+
+/// The guests the host configured besides the built-in ones, see [configure_guests].
+static GUESTS: RwLock<Vec<GuestSelection>> = RwLock::new(Vec::new());
+
+/// Set the guests the host proves with besides the built-in guests of the provers. Proofs name
+/// the guest they were generated with, and are only verified against the built-in guests and
+/// these, never against a program the proof points to.
+pub fn configure_guests(guests: Vec<GuestSelection>) {
+    *GUESTS.write().unwrap() = guests;
+}
+
+impl GuestSelection {
+    /// The configured guest with the program id, see [configure_guests].
+    pub fn configured(program_id: B256) -> Option<Self> {
+        GUESTS
+            .read()
+            .unwrap()
+            .iter()
+            .find(|guest| guest.program_id == program_id)
+            .cloned()
+    }
+
+    /// The guest selected in the config of the prover, if any.
+    pub fn from_config(config: &ProverConfig) -> ProverResult<Option<Self>> {
+        config
+            .get("guest")
+            .map(|guest| {
+                Self::deserialize(guest)
+                    .map_err(|e| ProverError::GuestError(format!("Invalid guest: {e}")))
+            })
+            .transpose()
+    }
+}
+
+/// The CUDA device the host assigned to the proof, `cuda_device` in the config of the prover.
+/// The host proves on it in a child process which only sees that device, or in its own process
+/// if it is the only device of the prover, so the prover uses the first device it sees.
+pub fn cuda_device(config: &ProverConfig) -> Option<u32> {
+    config
+        .get("cuda_device")
+        .and_then(|device| device.as_u64())
+        .and_then(|device| u32::try_from(device).ok())
 }
 
 pub fn to_proof(proof: ProverResult<impl Serialize>) -> ProverResult<Proof> {
@@ -1,39 +1,17 @@
-use core::str::FromStr;
 // TODO(Cecilia): fix for no-std
 use std::io::Read;
 
 use alloy_consensus::{Header as AlloyConsensusHeader, Signed, TxEip1559, TxEnvelope};
-use alloy_primitives::{uint, Address, Signature, TxKind, U256};
+use alloy_primitives::{Signature, TxKind};
 use alloy_rlp::*;
 use alloy_rpc_types::{Header as AlloyHeader, Transaction as AlloyTransaction};
-use anyhow::{anyhow, bail, ensure, Context, Result};
-use lazy_static::lazy_static;
+use anyhow::{anyhow, ensure, Result};
 use libflate::zlib::Decoder as zlibDecoder;
-use raiko_primitives::{keccak256, B256};
+use raiko_primitives::{eip4844::commitment_to_versioned_hash, keccak256, B256};
 
+use crate::input::TaikoGuestInput;
 #[cfg(not(feature = "std"))]
 use crate::no_std::*;
-use crate::{
-    consts::{get_network_spec, Network},
-    input::{decode_anchor, GuestInput},
-};
-
-pub const ANCHOR_GAS_LIMIT: u64 = 250_000;
-
-lazy_static! {
-    pub static ref GOLDEN_TOUCH_ACCOUNT: Address = {
-        Address::from_str("0x0000777735367b36bC9B61C50022d9D0700dB4Ec")
-            .expect("invalid golden touch account")
-    };
-    static ref GX1: U256 =
-        uint!(0x79BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798_U256);
-    static ref N: U256 =
-        uint!(0xfffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141_U256);
-    static ref GX1_MUL_PRIVATEKEY: U256 =
-        uint!(0x4341adf5a780b4a87939938fd7a032f6e6664c7da553c121d3b4947429639122_U256);
-    static ref GX2: U256 =
-        uint!(0xc6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5_U256);
-}
 
 pub fn decode_transactions(tx_list: &[u8]) -> Vec<TxEnvelope> {
     match Vec::<TxEnvelope>::decode(&mut &tx_list.to_owned()[..]) {
@@ -103,6 +81,50 @@ pub fn generate_transactions(
     transactions
 }
 
+/// Check that the tx list is the blob with the given versioned hash.
+///
+/// The KZG commitment always has to match the versioned hash. The tx list is checked against
+/// the commitment with the KZG proof, with c-kzg with the `kzg` feature, and without the
+/// trusted setup otherwise, which is a lot cheaper in the guests.
+pub fn verify_blob(taiko: &TaikoGuestInput, blob_hash: B256) -> Result<()> {
+    let commitment = taiko
+        .blob_commitment
+        .ok_or_else(|| anyhow!("Missing the KZG commitment of blob {blob_hash}"))?;
+    ensure!(
+        commitment_to_versioned_hash(commitment.as_slice()) == blob_hash,
+        "The KZG commitment doesn't match blob {blob_hash}"
+    );
+
+    let proof = taiko
+        .blob_proof
+        .ok_or_else(|| anyhow!("Missing the KZG proof of blob {blob_hash}"))?;
+
+    #[cfg(feature = "kzg")]
+    let valid = {
+        use raiko_primitives::{
+            eip4844::MAINNET_KZG_TRUSTED_SETUP,
+            kzg::{Blob, Bytes48, KzgProof},
+        };
+
+        let blob = Blob::from_bytes(&taiko.tx_list)
+            .map_err(|e| anyhow!("Invalid blob {blob_hash}: {e:?}"))?;
+        KzgProof::verify_blob_kzg_proof(
+            &blob,
+            &Bytes48::from(commitment.0),
+            &Bytes48::from(proof.0),
+            &MAINNET_KZG_TRUSTED_SETUP,
+        )
+        .map_err(|e| anyhow!("Could not verify blob {blob_hash}: {e:?}"))?
+    };
+    #[cfg(not(feature = "kzg"))]
+    let valid =
+        raiko_primitives::blob_kzg::verify_blob_kzg_proof(&taiko.tx_list, &commitment.0, &proof.0)
+            .with_context(|| format!("Could not verify blob {blob_hash}"))?;
+    ensure!(valid, "Invalid KZG proof for blob {blob_hash}");
+
+    Ok(())
+}
+
 const BLOB_FIELD_ELEMENT_NUM: usize = 4096;
 const BLOB_FIELD_ELEMENT_BYTES: usize = 32;
 const BLOB_DATA_CAPACITY: usize = BLOB_FIELD_ELEMENT_NUM * BLOB_FIELD_ELEMENT_BYTES;
@@ -214,109 +236,6 @@ fn zlib_decompress_blob(blob: &[u8]) -> Result<Vec<u8>> {
     Ok(decoded_buf)
 }
 
-/// check the anchor signature with fixed K value
-fn check_anchor_signature(anchor: &Signed<TxEip1559>) -> Result<()> {
-    let sign = anchor.signature();
-    if sign.r() == *GX1 {
-        return Ok(());
-    }
-    let msg_hash = anchor.signature_hash();
-    let msg_hash: U256 = msg_hash.into();
-    if sign.r() == *GX2 {
-        // when r == GX2 require s == 0 if k == 1
-        // alias: when r == GX2 require N == msg_hash + *GX1_MUL_PRIVATEKEY
-        if *N != msg_hash + *GX1_MUL_PRIVATEKEY {
-            bail!(
-                "r == GX2, but N != msg_hash + *GX1_MUL_PRIVATEKEY, N: {}, msg_hash: {msg_hash}, *GX1_MUL_PRIVATEKEY: {}",
-                *N, *GX1_MUL_PRIVATEKEY
-            );
-        }
-        return Ok(());
-    }
-    Err(anyhow!(
-        "r != *GX1 && r != GX2, r: {}, *GX1: {}, GX2: {}",
-        sign.r(),
-        *GX1,
-        *GX2
-    ))
-}
-
-pub fn check_anchor_tx(
-    input: &GuestInput,
-    anchor: &TxEnvelope,
-    from: &Address,
-    network: Network,
-) -> Result<()> {
-    match anchor {
-        TxEnvelope::Eip1559(tx) => {
-            // Check the signature
-            check_anchor_signature(tx).context(anyhow!("failed to check anchor signature"))?;
-
-            let tx = tx.tx();
-
-            // Extract the `to` address
-            let to = if let TxKind::Call(to_addr) = tx.to {
-                to_addr
-            } else {
-                panic!("anchor tx not a smart contract call")
-            };
-            // Check that it's from the golden touch address
-            ensure!(
-                *from == *GOLDEN_TOUCH_ACCOUNT,
-                "anchor transaction from mismatch"
-            );
-            // Check that the L2 contract is being called
-            ensure!(
-                to == get_network_spec(network).l2_contract.unwrap(),
-                "anchor transaction to mismatch"
-            );
-            // Tx can't have any ETH attached
-            ensure!(
-                tx.value == U256::from(0),
-                "anchor transaction value mismatch"
-            );
-            // Tx needs to have the expected gas limit
-            ensure!(
-                tx.gas_limit == ANCHOR_GAS_LIMIT,
-                "anchor transaction gas price mismatch"
-            );
-            // Check needs to have the base fee set to the block base fee
-            ensure!(
-                tx.max_fee_per_gas == input.base_fee_per_gas.into(),
-                "anchor transaction gas mismatch"
-            );
-
-            // Okay now let's decode the anchor tx to verify the inputs
-            let anchor_call = decode_anchor(&tx.input)?;
-            // The L1 blockhash needs to match the expected value
-            ensure!(
-                anchor_call.l1Hash == input.taiko.l1_header.hash(),
-                "L1 hash mismatch"
-            );
-            if network == Network::TaikoA7 {
-                ensure!(
-                    anchor_call.l1StateRoot == input.taiko.l1_header.state_root,
-                    "L1 state root mismatch"
-                );
-            }
-            ensure!(
-                anchor_call.l1BlockId == input.taiko.l1_header.number,
-                "L1 block number mismatch"
-            );
-            // The parent gas used input needs to match the gas used value of the parent block
-            ensure!(
-                anchor_call.parentGasUsed == input.parent_header.gas_used as u32,
-                "parentGasUsed mismatch"
-            );
-        }
-        _ => {
-            panic!("invalid anchor tx type");
-        }
-    }
-
-    Ok(())
-}
-
 pub trait HeaderHasher {
     fn hash(&self) -> B256;
 }
@@ -0,0 +1,212 @@
+//! Verification of blob KZG proofs without the KZG trusted setup.
+//!
+//! Checking a blob against its commitment with c-kzg needs the 4096 G1 points of the trusted
+//! setup, which is a large cycle cost to load in a zkVM guest. The blob proof of a sidecar is
+//! the KZG proof of the evaluation of the blob polynomial at a Fiat-Shamir challenge, so
+//! verifying it only needs the blob evaluated at the challenge, done in evaluation form
+//! without any setup, and a single pairing check with the `[τ]G2` point of the setup.
+//!
+//! Follows `verify_blob_kzg_proof` of the
+//! [Deneb polynomial commitments](https://github.com/ethereum/consensus-specs/blob/dev/specs/deneb/polynomial-commitments.md).
+
+use alloc::vec::Vec;
+
+use anyhow::{anyhow, ensure, Result};
+use bls12_381::{multi_miller_loop, G1Affine, G2Affine, G2Prepared, Gt, Scalar};
+use hex_literal::hex;
+use sha2::{Digest, Sha256};
+
+use crate::eip4844::{FIELD_ELEMENTS_PER_BLOB, FIELD_ELEMENT_BYTES};
+
+/// The number of bytes in a blob.
+pub const BYTES_PER_BLOB: usize = FIELD_ELEMENTS_PER_BLOB as usize * FIELD_ELEMENT_BYTES as usize;
+
+/// The domain of the Fiat-Shamir challenge of blob proofs.
+const FIAT_SHAMIR_PROTOCOL_DOMAIN: &[u8; 16] = b"FSBLOBVERIFY_V1_";
+
+/// The `[τ]G2` point of the mainnet trusted setup, the only point of the setup needed to verify
+/// a KZG proof.
+pub const TRUSTED_SETUP_TAU_G2: [u8; 96] = hex!(
+    "b5bfd7dd8cdeb128843bc287230af38926187075cbfbefa81009a2ce615ac53d2914e5870cb452d2afaaab24f3499f72"
+    "185cbfee53492714734429b7b38608e23926c911cceceac9a36851477ba4c60b087041de621000edc98edada20c1def2"
+);
+
+/// `(BLS_MODULUS - 1) / FIELD_ELEMENTS_PER_BLOB` as little-endian limbs. The primitive root of
+/// unity `7` raised to this is a root of unity of the order of the blob size.
+const ROOT_OF_UNITY_EXPONENT: [u64; 4] = [
+    0xbfeffffffff00000,
+    0x80553bda402fffe5,
+    0xd483339d80809a1d,
+    0x00073eda753299d7,
+];
+
+/// Verify the KZG proof of a blob against its commitment, like `verify_blob_kzg_proof` of
+/// c-kzg. Fails on malformed inputs.
+pub fn verify_blob_kzg_proof(blob: &[u8], commitment: &[u8; 48], proof: &[u8; 48]) -> Result<bool> {
+    ensure!(
+        blob.len() == BYTES_PER_BLOB,
+        "Invalid blob length {}, expected {BYTES_PER_BLOB}",
+        blob.len()
+    );
+    let commitment_point = g1_from_bytes(commitment)?;
+    let proof_point = g1_from_bytes(proof)?;
+    let polynomial = blob
+        .chunks_exact(FIELD_ELEMENT_BYTES as usize)
+        .map(scalar_from_bytes)
+        .collect::<Result<Vec<_>>>()?;
+
+    let z = compute_challenge(blob, commitment);
+    let y = evaluate_polynomial(&polynomial, z);
+    Ok(verify_kzg_proof(&commitment_point, z, y, &proof_point))
+}
+
+/// The Fiat-Shamir challenge the blob proof evaluates the blob polynomial at.
+fn compute_challenge(blob: &[u8], commitment: &[u8; 48]) -> Scalar {
+    let mut hasher = Sha256::new();
+    hasher.update(FIAT_SHAMIR_PROTOCOL_DOMAIN);
+    hasher.update((FIELD_ELEMENTS_PER_BLOB as u128).to_be_bytes());
+    hasher.update(blob);
+    hasher.update(commitment);
+    let hash = hasher.finalize();
+
+    // Reduce the big-endian hash modulo the field modulus.
+    let mut wide = [0u8; 64];
+    for (i, byte) in hash.iter().rev().enumerate() {
+        wide[i] = *byte;
+    }
+    Scalar::from_bytes_wide(&wide)
+}
+
+/// Evaluate the polynomial given by its evaluations at the bit-reversed roots of unity at `z`,
+/// with the barycentric formula.
+fn evaluate_polynomial(polynomial: &[Scalar], z: Scalar) -> Scalar {
+    let width = polynomial.len();
+    let roots = roots_of_unity_brp(width);
+    if let Some(i) = roots.iter().position(|root| *root == z) {
+        return polynomial[i];
+    }
+
+    let denominators = batch_invert(roots.iter().map(|root| z - root).collect());
+    let sum = polynomial
+        .iter()
+        .zip(&roots)
+        .zip(&denominators)
+        .fold(Scalar::zero(), |sum, ((value, root), denominator)| {
+            sum + value * root * denominator
+        });
+    let width_inverse = Scalar::from(width as u64).invert().unwrap();
+    sum * (z.pow_vartime(&[width as u64, 0, 0, 0]) - Scalar::one()) * width_inverse
+}
+
+/// Check `e(commitment - [y]G1, -G2) * e(proof, [τ - z]G2) == 1`.
+fn verify_kzg_proof(commitment: &G1Affine, z: Scalar, y: Scalar, proof: &G1Affine) -> bool {
+    let tau_g2 = G2Affine::from_compressed(&TRUSTED_SETUP_TAU_G2).unwrap();
+    let x_minus_z = G2Affine::from(tau_g2 - G2Affine::generator() * z);
+    let p_minus_y = G1Affine::from(commitment - G1Affine::generator() * y);
+    multi_miller_loop(&[
+        (&p_minus_y, &G2Prepared::from(-G2Affine::generator())),
+        (proof, &G2Prepared::from(x_minus_z)),
+    ])
+    .final_exponentiation()
+        == Gt::identity()
+}
+
+/// The roots of unity of the given order, in bit-reversed order.
+fn roots_of_unity_brp(width: usize) -> Vec<Scalar> {
+    let root = Scalar::from(7).pow_vartime(&ROOT_OF_UNITY_EXPONENT);
+    let mut roots = Vec::with_capacity(width);
+    let mut current = Scalar::one();
+    for _ in 0..width {
+        roots.push(current);
+        current *= root;
+    }
+    let bits = width.trailing_zeros();
+    (0..width)
+        .map(|i| roots[i.reverse_bits() >> (usize::BITS - bits)])
+        .collect()
+}
+
+/// Invert all values with a single inversion (Montgomery's trick). The values must be non-zero.
+fn batch_invert(values: Vec<Scalar>) -> Vec<Scalar> {
+    let mut products = Vec::with_capacity(values.len());
+    let mut product = Scalar::one();
+    for value in &values {
+        products.push(product);
+        product *= value;
+    }
+    let mut inverse = product.invert().unwrap();
+    let mut inverses = alloc::vec![Scalar::zero(); values.len()];
+    for i in (0..values.len()).rev() {
+        inverses[i] = inverse * products[i];
+        inverse *= values[i];
+    }
+    inverses
+}
+
+fn g1_from_bytes(bytes: &[u8; 48]) -> Result<G1Affine> {
+    Option::from(G1Affine::from_compressed(bytes)).ok_or_else(|| anyhow!("Invalid G1 point"))
+}
+
+/// Parse a big-endian field element, rejecting values not below the modulus.
+fn scalar_from_bytes(bytes: &[u8]) -> Result<Scalar> {
+    let mut le = [0u8; 32];
+    for (i, byte) in bytes.iter().rev().enumerate() {
+        le[i] = *byte;
+    }
+    Option::from(Scalar::from_bytes(&le)).ok_or_else(|| anyhow!("Invalid field element"))
+}
+
+#[cfg(all(test, feature = "c-kzg"))]
+mod tests {
+    use c_kzg::{Blob, Bytes48, KzgCommitment, KzgProof};
+
+    use super::*;
+    use crate::eip4844::MAINNET_KZG_TRUSTED_SETUP;
+
+    #[test]
+    fn test_tau_g2_matches_trusted_setup() {
+        assert_eq!(revm_primitives::kzg::G2_POINTS.0[1], TRUSTED_SETUP_TAU_G2);
+    }
+
+    #[test]
+    fn test_verify_blob_kzg_proof_matches_c_kzg() {
+        let mut blob = [0u8; BYTES_PER_BLOB];
+        for (i, element) in blob.chunks_exact_mut(32).enumerate() {
+            // Keep the top byte zero so every element is below the modulus.
+            element[1..9].copy_from_slice(&((i as u64 + 1) * 0x9e3779b97f4a7c15).to_be_bytes());
+        }
+        let settings = &*MAINNET_KZG_TRUSTED_SETUP;
+        let c_kzg_blob = Blob::from_bytes(&blob).unwrap();
+        let commitment = KzgCommitment::blob_to_kzg_commitment(&c_kzg_blob, settings)
+            .unwrap()
+            .to_bytes();
+        let proof = KzgProof::compute_blob_kzg_proof(&c_kzg_blob, &commitment, settings)
+            .unwrap()
+            .to_bytes();
+        let commitment: [u8; 48] = commitment.as_slice().try_into().unwrap();
+        let proof: [u8; 48] = proof.as_slice().try_into().unwrap();
+
+        let c_kzg_verify = |blob: &[u8], proof: &[u8; 48]| {
+            KzgProof::verify_blob_kzg_proof(
+                &Blob::from_bytes(blob).unwrap(),
+                &Bytes48::from(commitment),
+                &Bytes48::from(*proof),
+                settings,
+            )
+            .unwrap()
+        };
+        assert!(c_kzg_verify(&blob, &proof));
+        assert!(verify_blob_kzg_proof(&blob, &commitment, &proof).unwrap());
+
+        // Another blob with the same commitment and proof.
+        let mut tampered = blob;
+        tampered[31] ^= 1;
+        assert!(!c_kzg_verify(&tampered, &proof));
+        assert!(!verify_blob_kzg_proof(&tampered, &commitment, &proof).unwrap());
+
+        // A field element not below the modulus.
+        let mut invalid = blob;
+        invalid[0] = 0xff;
+        assert!(verify_blob_kzg_proof(&invalid, &commitment, &proof).is_err());
+    }
+}
@@ -2,10 +2,8 @@
 
 // re-exports from revm for calculating blob fee
 pub use revm_primitives::{calc_blob_gasprice, calc_excess_blob_gas as calculate_excess_blob_gas};
-#[cfg(feature = "c-kzg")]
 use sha2::{Digest, Sha256};
 
-#[cfg(feature = "c-kzg")]
 use crate::B256;
 
 /// Calculates the versioned hash for a KzgCommitment
@@ -13,7 +11,13 @@ use crate::B256;
 /// Specified in [EIP-4844](https://eips.ethereum.org/EIPS/eip-4844#header-extension)
 #[cfg(feature = "c-kzg")]
 pub fn kzg_to_versioned_hash(commitment: c_kzg::KzgCommitment) -> B256 {
-    let mut res = Sha256::digest(commitment.as_slice());
+    commitment_to_versioned_hash(commitment.as_slice())
+}
+
+/// Calculates the versioned hash for the bytes of a KZG commitment, without needing the KZG
+/// library.
+pub fn commitment_to_versioned_hash(commitment: &[u8]) -> B256 {
+    let mut res = Sha256::digest(commitment);
     res[0] = VERSIONED_HASH_VERSION_KZG;
     B256::new(res.into())
 }
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use alloy_primitives::{b256, B256};
+#[cfg(not(feature = "tiny-keccak"))]
 use sha3::{Digest, Keccak256};
 
 /// Represents the Keccak-256 hash of an empty byte slice.
@@ -27,6 +28,9 @@ pub const KECCAK_EMPTY: B256 =
 /// This function is a thin wrapper around the Keccak256 hashing algorithm
 /// and is optimized for performance.
 ///
+/// With the `tiny-keccak` feature the hash is computed with tiny-keccak, which zkVM guests
+/// patch with their keccak precompile.
+///
 /// # TODO
 /// - Consider switching the return type to `B256` for consistency with other parts of the
 ///   codebase.
@@ -34,5 +38,29 @@ pub const KECCAK_EMPTY: B256 =
 pub fn keccak(data: impl AsRef<[u8]>) -> [u8; 32] {
     // TODO: Remove this benchmarking code once performance testing is complete.
     // std::hint::black_box(sha2::Sha256::digest(&data));
+    #[cfg(feature = "tiny-keccak")]
+    {
+        use tiny_keccak::Hasher;
+        let mut hasher = tiny_keccak::Keccak::v256();
+        hasher.update(data.as_ref());
+        let mut hash = [0u8; 32];
+        hasher.finalize(&mut hash);
+        hash
+    }
+    #[cfg(not(feature = "tiny-keccak"))]
     Keccak256::digest(data).into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keccak() {
+        assert_eq!(B256::from(keccak([])), KECCAK_EMPTY);
+        assert_eq!(
+            B256::from(keccak(b"raiko")),
+            alloy_primitives::keccak256(b"raiko")
+        );
+    }
+}
@@ -18,6 +18,7 @@ extern crate core;
 
 pub use alloc::{vec, vec::Vec};
 
+pub mod blob_kzg;
 pub mod eip4844;
 pub mod keccak;
 pub mod mpt;
@@ -17,6 +17,7 @@ use core::{
     cmp,
     fmt::{Debug, Write},
     iter, mem,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
 use alloy_primitives::{b256, TxNumber, B256, U256};
@@ -29,8 +30,32 @@ use rlp::{Decodable, DecoderError, Prototype, Rlp};
 use serde::{Deserialize, Serialize};
 use thiserror::Error as ThisError;
 
+/// An arena of trie nodes, to serialize tries sharing subtries compactly.
+pub mod arena;
+
 pub type StorageEntry = (MptNode, Vec<U256>);
 
+/// The trie nodes visited by the reads and by the writes of all tries since the last
+/// [reset_node_accesses]. The counts are shared by all threads, they are only meaningful in
+/// the guest.
+static NODE_READS: AtomicUsize = AtomicUsize::new(0);
+static NODE_WRITES: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of trie nodes visited by reads and by writes (inserts and deletes)
+/// since the last [reset_node_accesses].
+pub fn node_accesses() -> (usize, usize) {
+    (
+        NODE_READS.load(Ordering::Relaxed),
+        NODE_WRITES.load(Ordering::Relaxed),
+    )
+}
+
+/// Resets the counts of [node_accesses].
+pub fn reset_node_accesses() {
+    NODE_READS.store(0, Ordering::Relaxed);
+    NODE_WRITES.store(0, Ordering::Relaxed);
+}
+
 /// Represents an Ethereum account within the state trie.
 ///
 /// The `StateAccount` struct encapsulates key details of an Ethereum account, including
@@ -479,6 +504,7 @@ impl MptNode {
     }
 
     fn get_internal(&self, key_nibs: &[u8]) -> Result<Option<&[u8]>, Error> {
+        NODE_READS.fetch_add(1, Ordering::Relaxed);
         match &self.data {
             MptNodeData::Null => Ok(None),
             MptNodeData::Branch(nodes) => {
@@ -519,6 +545,7 @@ impl MptNode {
     }
 
     fn delete_internal(&mut self, key_nibs: &[u8]) -> Result<bool, Error> {
+        NODE_WRITES.fetch_add(1, Ordering::Relaxed);
         match &mut self.data {
             MptNodeData::Null => return Ok(false),
             MptNodeData::Branch(children) => {
@@ -651,6 +678,7 @@ impl MptNode {
     }
 
     fn insert_internal(&mut self, key_nibs: &[u8], value: Vec<u8>) -> Result<bool, Error> {
+        NODE_WRITES.fetch_add(1, Ordering::Relaxed);
         match &mut self.data {
             MptNodeData::Null => {
                 self.data = MptNodeData::Leaf(to_encoded_path(key_nibs, true), value);
@@ -1255,6 +1283,20 @@ mod tests {
         assert_eq!(trie.hash(), decoded.hash());
     }
 
+    #[test]
+    pub fn test_node_accesses() {
+        // the counts are shared with the tests running in parallel
+        let (reads, writes) = node_accesses();
+        let mut trie = MptNode::default();
+        trie.insert_rlp(b"a", 0u8).unwrap();
+        trie.insert_rlp(b"b", 1u8).unwrap();
+        assert_eq!(trie.get_rlp::<u8>(b"b").unwrap(), Some(1));
+        let (new_reads, new_writes) = node_accesses();
+        // the extension, the branch and the leaf of the key
+        assert!(new_reads >= reads + 3);
+        assert!(new_writes >= writes + 2);
+    }
+
     #[test]
     pub fn test_partial() {
         let mut trie = MptNode::default();
@@ -0,0 +1,269 @@
+//! An arena of trie nodes, which stores every distinct node once.
+//!
+//! The storage tries of a block repeat whole subtries, e.g. the slots of contracts deployed
+//! from the same code, and serializing the tries as trees writes every copy. In the arena a
+//! node references its children by index and identical nodes share an index, so the tries are
+//! serialized as the indices of their roots into the arena.
+
+use alloy_primitives::B256;
+use anyhow::{anyhow, ensure, Result};
+use revm_primitives::HashMap;
+use serde::{Deserialize, Serialize};
+
+use super::{MptNode, MptNodeData};
+
+/// The tries in a zero-copy archive, see [archive::Tries].
+#[cfg(feature = "rkyv")]
+pub mod archive;
+
+/// Trie nodes stored once each, with the children referenced by index.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct NodeArena {
+    /// The nodes, every node after its children.
+    nodes: Vec<ArenaNode>,
+    /// The index of every node, to find identical nodes while inserting.
+    #[serde(skip)]
+    indices: HashMap<ArenaNode, u32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum ArenaNode {
+    Null,
+    Branch([Option<u32>; 16]),
+    Leaf(Vec<u8>, Vec<u8>),
+    Extension(Vec<u8>, u32),
+    Digest(B256),
+}
+
+impl NodeArena {
+    /// The number of distinct nodes in the arena.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Insert the trie, returning the index of its root.
+    ///
+    /// Nodes are only shared when their subtries are identical, including which of their
+    /// nodes are resolved, as a digest has the same reference as the node it stands for.
+    pub fn insert(&mut self, trie: &MptNode) -> u32 {
+        let node = match trie.as_data() {
+            MptNodeData::Null => ArenaNode::Null,
+            MptNodeData::Branch(children) => ArenaNode::Branch(core::array::from_fn(|i| {
+                children[i].as_ref().map(|child| self.insert(child))
+            })),
+            MptNodeData::Leaf(prefix, value) => ArenaNode::Leaf(prefix.clone(), value.clone()),
+            MptNodeData::Extension(prefix, child) => {
+                ArenaNode::Extension(prefix.clone(), self.insert(child))
+            }
+            MptNodeData::Digest(digest) => ArenaNode::Digest(*digest),
+        };
+        *self.indices.entry(node).or_insert_with_key(|node| {
+            self.nodes.push(node.clone());
+            (self.nodes.len() - 1) as u32
+        })
+    }
+
+    /// Rebuild the trie with its root at the index.
+    pub fn get(&self, index: u32) -> Result<MptNode> {
+        self.rebuilder().get(index)
+    }
+
+    /// Rebuild the tries with their roots at the indices, rebuilding the nodes they share once.
+    pub fn get_all(&self, indices: impl IntoIterator<Item = u32>) -> Result<Vec<MptNode>> {
+        let mut rebuilder = self.rebuilder();
+        indices
+            .into_iter()
+            .map(|index| rebuilder.get(index))
+            .collect()
+    }
+
+    fn rebuilder(&self) -> Rebuilder<impl Fn(u32) -> Result<ArenaNode> + '_> {
+        Rebuilder::new(move |index| {
+            self.nodes
+                .get(index as usize)
+                .cloned()
+                .ok_or_else(|| anyhow!("Trie node {index} is not in the arena"))
+        })
+    }
+}
+
+/// The most trie nodes the tries of an arena are rebuilt into. A node shared by several parents
+/// is copied into each of them, so a small arena can stand for tries of exponential size.
+pub const MAX_REBUILT_NODES: usize = 1 << 25;
+
+/// Rebuilds tries from the nodes of an arena, every node once however many parents share it.
+struct Rebuilder<F> {
+    /// The node at an index.
+    node: F,
+    /// The number of nodes in the subtries by the index of their root.
+    sizes: HashMap<u32, usize>,
+    /// The rebuilt subtries by the index of their root.
+    rebuilt: HashMap<u32, MptNode>,
+    /// The number of nodes in the rebuilt tries and in the subtries kept to copy from.
+    total: usize,
+}
+
+impl<F: Fn(u32) -> Result<ArenaNode>> Rebuilder<F> {
+    fn new(node: F) -> Self {
+        Self {
+            node,
+            sizes: HashMap::default(),
+            rebuilt: HashMap::default(),
+            total: 0,
+        }
+    }
+
+    /// Rebuild the trie with its root at the index.
+    fn get(&mut self, index: u32) -> Result<MptNode> {
+        // The nodes are counted first, so tries too large are rejected before they're rebuilt
+        let size = self.size(index)?;
+        self.count(size)?;
+        self.rebuild(index)
+    }
+
+    /// The number of nodes in the subtrie with its root at the index.
+    fn size(&mut self, index: u32) -> Result<usize> {
+        if let Some(size) = self.sizes.get(&index) {
+            return Ok(*size);
+        }
+        let mut size: usize = 1;
+        for child_index in (self.node)(index)?.children() {
+            // Children come before their parents, which rules out cycles.
+            ensure!(
+                child_index < index,
+                "Trie node {index} references the later node {child_index}"
+            );
+            size = size.saturating_add(self.size(child_index)?);
+        }
+        // Every subtrie is kept to copy from once rebuilt
+        self.count(size)?;
+        self.sizes.insert(index, size);
+        Ok(size)
+    }
+
+    fn count(&mut self, size: usize) -> Result<()> {
+        self.total = self.total.saturating_add(size);
+        ensure!(
+            self.total <= MAX_REBUILT_NODES,
+            "The tries have more than {MAX_REBUILT_NODES} nodes"
+        );
+        Ok(())
+    }
+
+    /// Rebuild the subtrie with its root at the index, after it was counted.
+    fn rebuild(&mut self, index: u32) -> Result<MptNode> {
+        if let Some(trie) = self.rebuilt.get(&index) {
+            return Ok(trie.clone());
+        }
+        let data = match (self.node)(index)? {
+            ArenaNode::Null => MptNodeData::Null,
+            ArenaNode::Branch(children) => {
+                let mut nodes: [Option<Box<MptNode>>; 16] = Default::default();
+                for (node, child_index) in nodes.iter_mut().zip(children) {
+                    if let Some(child_index) = child_index {
+                        *node = Some(Box::new(self.rebuild(child_index)?));
+                    }
+                }
+                MptNodeData::Branch(nodes)
+            }
+            ArenaNode::Leaf(prefix, value) => MptNodeData::Leaf(prefix, value),
+            ArenaNode::Extension(prefix, child_index) => {
+                MptNodeData::Extension(prefix, Box::new(self.rebuild(child_index)?))
+            }
+            ArenaNode::Digest(digest) => MptNodeData::Digest(digest),
+        };
+        let trie: MptNode = data.into();
+        self.rebuilt.insert(index, trie.clone());
+        Ok(trie)
+    }
+}
+
+impl ArenaNode {
+    /// The indices of the children of the node.
+    fn children(&self) -> Vec<u32> {
+        match self {
+            ArenaNode::Branch(children) => children.iter().flatten().copied().collect(),
+            ArenaNode::Extension(_, child) => vec![*child],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keccak::keccak;
+
+    fn trie(keys: &[u8]) -> MptNode {
+        let mut trie = MptNode::default();
+        for key in keys {
+            trie.insert(&keccak([*key]), vec![*key; 40]).unwrap();
+        }
+        trie
+    }
+
+    #[test]
+    fn test_shared_nodes() {
+        let mut arena = NodeArena::default();
+        let first = arena.insert(&trie(&[1, 2, 3]));
+        let size = arena.len();
+        // The same trie again only shares nodes.
+        assert_eq!(arena.insert(&trie(&[1, 2, 3])), first);
+        assert_eq!(arena.len(), size);
+        let second = arena.insert(&trie(&[1, 2, 4]));
+        assert!(arena.len() < 2 * size);
+
+        let arena: NodeArena = bincode::deserialize(&bincode::serialize(&arena).unwrap()).unwrap();
+        assert_eq!(arena.get(first).unwrap().hash(), trie(&[1, 2, 3]).hash());
+        assert_eq!(arena.get(second).unwrap().hash(), trie(&[1, 2, 4]).hash());
+        assert!(arena.get(arena.len() as u32).is_err());
+    }
+
+    #[test]
+    fn test_digest_not_shared() {
+        let resolved = trie(&[1, 2]);
+        let digest: MptNode = MptNodeData::Digest(resolved.hash()).into();
+        let mut arena = NodeArena::default();
+        let digest_index =
+            arena.insert(&MptNodeData::Extension(vec![0x10], Box::new(digest)).into());
+        let resolved_index =
+            arena.insert(&MptNodeData::Extension(vec![0x10], Box::new(resolved)).into());
+        assert_ne!(digest_index, resolved_index);
+        let MptNodeData::Extension(_, child) = arena.get(resolved_index).unwrap().as_data().clone()
+        else {
+            panic!("not an extension");
+        };
+        assert!(!child.is_digest());
+    }
+
+    #[test]
+    fn test_reject_exponential_tries() {
+        // Every branch references the previous one twice, doubling the size of the trie
+        let mut nodes = vec![ArenaNode::Leaf(vec![0x20], vec![1])];
+        for index in 0..64 {
+            let mut children = [None; 16];
+            children[0] = Some(index);
+            children[1] = Some(index);
+            nodes.push(ArenaNode::Branch(children));
+        }
+        let arena = NodeArena {
+            nodes,
+            ..Default::default()
+        };
+        assert!(arena.get(16).is_ok());
+        assert!(arena.get(64).is_err());
+    }
+
+    #[test]
+    fn test_reject_cycle() {
+        let arena = NodeArena {
+            nodes: vec![ArenaNode::Extension(vec![0x10], 0)],
+            ..Default::default()
+        };
+        assert!(arena.get(0).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+//! The tries of an input in an [rkyv] archive, which is read in place instead of being
+//! deserialized into a [NodeArena] first.
+//!
+//! The nodes are stored like in the arena, every distinct node once and after its children, so
+//! the archive is as compact as the serialized arena. The tries are rebuilt straight from the
+//! archived nodes.
+
+use alloy_primitives::{Address, U256};
+use anyhow::{anyhow, Result};
+use revm_primitives::HashMap;
+use rkyv::{Archive, Serialize};
+
+use super::{ArenaNode, NodeArena, Rebuilder};
+use crate::mpt::{MptNode, StorageEntry};
+
+/// The state trie and the storage tries of a block.
+#[derive(Archive, Serialize)]
+#[archive(check_bytes)]
+pub struct Tries {
+    /// The nodes of all tries, every node after its children.
+    nodes: Vec<Node>,
+    /// The index of the root of the state trie.
+    state_root: u32,
+    /// The storage tries by account.
+    storage: Vec<StorageRoot>,
+}
+
+#[derive(Archive, Serialize)]
+#[archive(check_bytes)]
+enum Node {
+    Null,
+    Branch([Option<u32>; 16]),
+    Leaf(Vec<u8>, Vec<u8>),
+    Extension(Vec<u8>, u32),
+    Digest([u8; 32]),
+}
+
+#[derive(Archive, Serialize)]
+#[archive(check_bytes)]
+struct StorageRoot {
+    address: [u8; 20],
+    /// The index of the root of the storage trie.
+    root: u32,
+    /// The used storage slots, as little endian bytes.
+    slots: Vec<[u8; 32]>,
+}
+
+impl From<ArenaNode> for Node {
+    fn from(node: ArenaNode) -> Self {
+        match node {
+            ArenaNode::Null => Node::Null,
+            ArenaNode::Branch(children) => Node::Branch(children),
+            ArenaNode::Leaf(prefix, value) => Node::Leaf(prefix, value),
+            ArenaNode::Extension(prefix, child) => Node::Extension(prefix, child),
+            ArenaNode::Digest(digest) => Node::Digest(digest.0),
+        }
+    }
+}
+
+impl Tries {
+    /// Collect the state trie and the storage tries into one arena of nodes.
+    pub fn new(state_trie: &MptNode, storage: &HashMap<Address, StorageEntry>) -> Self {
+        let mut arena = NodeArena::default();
+        let state_root = arena.insert(state_trie);
+        let storage = storage
+            .iter()
+            .map(|(address, (trie, slots))| StorageRoot {
+                address: address.0 .0,
+                root: arena.insert(trie),
+                slots: slots.iter().map(|slot| slot.to_le_bytes()).collect(),
+            })
+            .collect();
+        Self {
+            nodes: arena.nodes.into_iter().map(Node::from).collect(),
+            state_root,
+            storage,
+        }
+    }
+}
+
+impl ArchivedTries {
+    /// Rebuild the state trie.
+    pub fn state_trie(&self) -> Result<MptNode> {
+        self.rebuilder().get(self.state_root)
+    }
+
+    /// Rebuild the storage tries with the used slots of the accounts.
+    pub fn storage(&self) -> Result<HashMap<Address, StorageEntry>> {
+        let mut rebuilder = self.rebuilder();
+        self.storage
+            .iter()
+            .map(|account| {
+                let slots = account
+                    .slots
+                    .iter()
+                    .map(|slot| U256::from_le_bytes(*slot))
+                    .collect();
+                Ok((
+                    Address::from(account.address),
+                    (rebuilder.get(account.root)?, slots),
+                ))
+            })
+            .collect()
+    }
+
+    /// Rebuild the tries from the archived nodes, like [NodeArena::get_all].
+    fn rebuilder(&self) -> Rebuilder<impl Fn(u32) -> Result<ArenaNode> + '_> {
+        Rebuilder::new(move |index| {
+            let node = self
+                .nodes
+                .get(index as usize)
+                .ok_or_else(|| anyhow!("Trie node {index} is not in the archive"))?;
+            Ok(match node {
+                ArchivedNode::Null => ArenaNode::Null,
+                ArchivedNode::Branch(children) => {
+                    ArenaNode::Branch(core::array::from_fn(|i| children[i].as_ref().copied()))
+                }
+                ArchivedNode::Leaf(prefix, value) => {
+                    ArenaNode::Leaf(prefix.to_vec(), value.to_vec())
+                }
+                ArchivedNode::Extension(prefix, child_index) => {
+                    ArenaNode::Extension(prefix.to_vec(), *child_index)
+                }
+                ArchivedNode::Digest(digest) => ArenaNode::Digest((*digest).into()),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keccak::keccak;
+
+    fn trie(keys: &[u8]) -> MptNode {
+        let mut trie = MptNode::default();
+        for key in keys {
+            trie.insert(&keccak([*key]), vec![*key; 40]).unwrap();
+        }
+        trie
+    }
+
+    #[test]
+    fn test_archived_tries() {
+        let state_trie = trie(&[1, 2, 3]);
+        let mut storage = HashMap::default();
+        storage.insert(
+            Address::repeat_byte(1),
+            (trie(&[4, 5]), vec![U256::from(7), U256::MAX]),
+        );
+        let bytes = rkyv::to_bytes::<_, 256>(&Tries::new(&state_trie, &storage)).unwrap();
+        let archived = rkyv::check_archived_root::<Tries>(&bytes).unwrap();
+
+        assert_eq!(archived.state_trie().unwrap().hash(), state_trie.hash());
+        let rebuilt = archived.storage().unwrap();
+        let (trie, slots) = &rebuilt[&Address::repeat_byte(1)];
+        assert_eq!(trie.hash(), storage[&Address::repeat_byte(1)].0.hash());
+        assert_eq!(slots, &vec![U256::from(7), U256::MAX]);
+    }
+}
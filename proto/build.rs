@@ -0,0 +1,5 @@
+fn main() {
+    tonic_build::configure()
+        .compile(&["raiko/v1/proof.proto"], &["."])
+        .expect("Could not compile the protobuf definitions, is protoc installed?");
+}
@@ -0,0 +1,5 @@
+//! The protobuf definitions of the gRPC API of raiko.
+
+pub mod v1 {
+    tonic::include_proto!("raiko.v1");
+}
@@ -49,6 +49,18 @@ elif [ "$proof" == "sp1" ]; then
 	proofParam='
     "proof_type": "sp1"
   '
+elif [ "$proof" == "jolt" ]; then
+	proofParam='
+    "proof_type": "jolt"
+  '
+elif [ "$proof" == "plonky3" ]; then
+	proofParam='
+    "proof_type": "plonky3"
+  '
+elif [ "$proof" == "mock" ]; then
+	proofParam='
+    "proof_type": "mock"
+  '
 elif [ "$proof" == "sgx" ]; then
 	proofParam='
     "proof_type": "sgx",
@@ -81,7 +93,7 @@ elif [ "$proof" == "risc0-bonsai" ]; then
     }
   '
 else
-	echo "Invalid proof name. Please use 'native', 'risc0[-bonsai]', 'sp1', or 'sgx'."
+	echo "Invalid proof name. Please use 'native', 'mock', 'risc0[-bonsai]', 'sp1', 'jolt', 'plonky3', or 'sgx'."
 	exit 1
 fi
 
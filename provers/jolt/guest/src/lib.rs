@@ -0,0 +1,29 @@
+#![no_main]
+
+use raiko_lib::{
+    builder::{BlockBuilderStrategy, TaikoStrategy},
+    consts::verify_chain_spec,
+    input::{GuestInput, GuestOutput, WrappedHeader},
+    protocol_instance::{assemble_protocol_instance, EvidenceType},
+};
+
+// The block input is far bigger than the default limits of the Jolt guest.
+#[jolt::provable(
+    stack_size = 1048576,
+    memory_size = 268435456,
+    max_input_size = 16777216,
+    max_output_size = 4096
+)]
+fn prove_block(input: GuestInput) -> GuestOutput {
+    // The chain spec comes with the input, only the rules of the built-in chains are trusted.
+    verify_chain_spec(input.network, &input.chain_spec).expect("Unsupported chain spec");
+    match TaikoStrategy::build_from(&input) {
+        Ok((header, _mpt_node)) => {
+            let pi = assemble_protocol_instance(&input, &header)
+                .expect("Failed to assemble protocol instance")
+                .instance_hash(EvidenceType::Jolt);
+            GuestOutput::Success((WrappedHeader { header }, pi))
+        }
+        Err(_) => GuestOutput::Failure,
+    }
+}
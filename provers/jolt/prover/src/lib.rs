@@ -0,0 +1,67 @@
+#![cfg(feature = "enable")]
+
+use alloy_primitives::B256;
+use ark_serialize::CanonicalSerialize;
+use raiko_lib::{
+    input::{GuestInput, GuestOutput},
+    protocol_instance::{EvidenceType, ProtocolInstance},
+    prover::{
+        to_proof, ProgressReporter, Proof, Prover, ProverConfig, ProverError, ProverProgress,
+        ProverResult,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JoltResponse {
+    /// The hex encoded Jolt proof.
+    pub proof: String,
+    pub output: GuestOutput,
+}
+
+pub struct JoltProver;
+
+impl Prover for JoltProver {
+    async fn run(
+        input: GuestInput,
+        output: GuestOutput,
+        config: &ProverConfig,
+    ) -> ProverResult<Proof> {
+        Self::run_with_progress(input, output, config, &ProgressReporter::default()).await
+    }
+
+    async fn run_with_progress(
+        input: GuestInput,
+        _output: GuestOutput,
+        _config: &ProverConfig,
+        progress: &ProgressReporter,
+    ) -> ProverResult<Proof> {
+        // Build the guest program and generate the proof.
+        let (prove_block, verify_block) = jolt_guest::build_prove_block();
+        let (output, proof) = prove_block(input);
+
+        // Save the proof before the verification consumes it.
+        let mut proof_bytes = Vec::new();
+        proof
+            .serialize_compressed(&mut proof_bytes)
+            .map_err(|e| ProverError::GuestError(format!("Jolt: serializing proof failed: {e}")))?;
+
+        // Verify proof.
+        if !verify_block(proof) {
+            return Err(ProverError::GuestError(
+                "Jolt: verification failed".to_string(),
+            ));
+        }
+        progress.report(ProverProgress::Verified);
+
+        println!("succesfully generated and verified proof for the program!");
+        to_proof(Ok(JoltResponse {
+            proof: hex::encode(proof_bytes),
+            output,
+        }))
+    }
+
+    fn instance_hash(pi: ProtocolInstance) -> B256 {
+        pi.instance_hash(EvidenceType::Jolt)
+    }
+}
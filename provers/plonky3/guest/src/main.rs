@@ -0,0 +1,32 @@
+#![no_main]
+
+use powdr_riscv_runtime::{commit, io::read};
+use raiko_lib::{
+    builder::{BlockBuilderStrategy, TaikoStrategy},
+    consts::verify_chain_spec,
+    input::GuestInput,
+    protocol_instance::{assemble_protocol_instance, EvidenceType},
+};
+
+/// The channel the host writes the guest input to.
+const INPUT_CHANNEL: u32 = 1;
+
+#[no_mangle]
+fn main() {
+    let input: GuestInput = read(INPUT_CHANNEL);
+    // The chain spec comes with the input, only the rules of the built-in chains are trusted.
+    verify_chain_spec(input.network, &input.chain_spec).expect("Unsupported chain spec");
+
+    // The instance hash is the public output of the proof, a zero hash marks a block that
+    // failed to build.
+    let pi = match TaikoStrategy::build_from(&input) {
+        Ok((header, _mpt_node)) => assemble_protocol_instance(&input, &header)
+            .expect("Failed to assemble protocol instance")
+            .instance_hash(EvidenceType::Powdr),
+        Err(_) => Default::default(),
+    };
+
+    for (i, word) in pi.chunks_exact(4).enumerate() {
+        commit::commit(i as u32, u32::from_le_bytes(word.try_into().unwrap()));
+    }
+}
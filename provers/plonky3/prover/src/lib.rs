@@ -0,0 +1,132 @@
+#![cfg(feature = "enable")]
+
+use std::path::{Path, PathBuf};
+
+use alloy_primitives::B256;
+use powdr::{
+    backend::BackendType,
+    pipeline::Pipeline,
+    riscv::{compile_rust, CompilerOptions, Runtime},
+    GoldilocksField,
+};
+use raiko_lib::{
+    input::{GuestInput, GuestOutput},
+    protocol_instance::{EvidenceType, ProtocolInstance},
+    prover::{to_proof, Proof, Prover, ProverConfig, ProverError, ProverResult},
+};
+use serde::{Deserialize, Serialize};
+
+/// The path to the block guest program, which is compiled by powdr before proving.
+const GUEST_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../guest");
+/// The channel the guest reads its input from.
+const INPUT_CHANNEL: u32 = 1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Plonky3Param {
+    /// The directory the compiled guest and the proving artifacts are written to.
+    #[serde(default = "default_output_dir")]
+    pub output_dir: PathBuf,
+}
+
+impl Default for Plonky3Param {
+    fn default() -> Self {
+        Self {
+            output_dir: default_output_dir(),
+        }
+    }
+}
+
+fn default_output_dir() -> PathBuf {
+    PathBuf::from("/tmp/raiko-plonky3")
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Plonky3Response {
+    /// The hex encoded Plonky3 proof.
+    pub proof: String,
+    pub output: GuestOutput,
+}
+
+/// An experimental prover using the Plonky3 backend of powdr.
+pub struct Plonky3Prover;
+
+impl Prover for Plonky3Prover {
+    async fn run(
+        input: GuestInput,
+        output: GuestOutput,
+        config: &ProverConfig,
+    ) -> ProverResult<Proof> {
+        let param = config
+            .get("plonky3")
+            .map(Plonky3Param::deserialize)
+            .transpose()
+            .map_err(|e| ProverError::GuestError(format!("Plonky3: invalid params: {e}")))?
+            .unwrap_or_default();
+
+        let proof = prove(&param.output_dir, &input, &output).map_err(ProverError::GuestError)?;
+
+        println!("succesfully generated and verified proof for the program!");
+        to_proof(Ok(Plonky3Response {
+            proof: hex::encode(proof),
+            output,
+        }))
+    }
+
+    fn instance_hash(pi: ProtocolInstance) -> B256 {
+        pi.instance_hash(EvidenceType::Powdr)
+    }
+}
+
+fn prove(output_dir: &Path, input: &GuestInput, output: &GuestOutput) -> Result<Vec<u8>, String> {
+    // Compile the guest to powdr assembly, this is skipped when it is up to date.
+    let (asm_path, asm) = compile_rust::<GoldilocksField>(
+        GUEST_PATH,
+        CompilerOptions::new_gl(),
+        output_dir,
+        true,
+        &Runtime::base(),
+        true,
+        false,
+        None,
+    )
+    .ok_or_else(|| "Plonky3: compiling the guest failed".to_string())?;
+
+    let mut pipeline = Pipeline::<GoldilocksField>::default()
+        .from_asm_string(asm, Some(asm_path))
+        .with_output(output_dir.to_path_buf(), true)
+        .with_backend(BackendType::Plonky3, None);
+    pipeline.add_data(INPUT_CHANNEL, input);
+
+    let proof = pipeline
+        .compute_proof()
+        .cloned()
+        .map_err(|e| format!("Plonky3: proving failed: {e:?}"))?;
+
+    // Verify the proof against the public values of the run before reporting it.
+    let publics = pipeline
+        .publics()
+        .map_err(|e| format!("Plonky3: reading the public values failed: {e:?}"))?
+        .into_iter()
+        .map(|(name, value)| value.ok_or_else(|| format!("Plonky3: public value {name} unknown")))
+        .collect::<Result<Vec<_>, _>>()?;
+    if publics != instance_words(output) {
+        return Err("Plonky3: the proof does not match the expected output".to_string());
+    }
+    pipeline
+        .verify(&proof, &[publics])
+        .map_err(|e| format!("Plonky3: verification failed: {e:?}"))?;
+
+    Ok(proof)
+}
+
+/// The public values the guest commits for the output, the words of the instance hash, or of a
+/// zero hash for a block that failed to build.
+fn instance_words(output: &GuestOutput) -> Vec<GoldilocksField> {
+    let hash = match output {
+        GuestOutput::Success((_, hash)) => *hash,
+        GuestOutput::Failure => B256::ZERO,
+    };
+    hash.chunks_exact(4)
+        .map(|word| GoldilocksField::from(u32::from_le_bytes(word.try_into().unwrap())))
+        .collect()
+}
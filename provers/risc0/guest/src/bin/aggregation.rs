@@ -0,0 +1,25 @@
+#![no_main]
+use risc0_zkvm::{guest::env, serde, sha::Digest};
+risc0_zkvm::guest::entry!(main);
+
+use raiko_lib::aggregate::{AggregationGuestInput, AggregationGuestOutput};
+use raiko_primitives::B256;
+
+fn main() {
+    let input: AggregationGuestInput = env::read();
+
+    // Verify the block proofs, which are passed to the prover as assumptions.
+    for output in &input.block_outputs {
+        env::verify(
+            Digest::from(input.program_id),
+            &serde::to_vec(output).expect("Failed to serialize block output"),
+        )
+        .expect("Failed to verify block proof");
+    }
+
+    let program_id = B256::from_slice(Digest::from(input.program_id).as_bytes());
+    let output = AggregationGuestOutput::new(program_id, &input.block_outputs)
+        .expect("Can not aggregate failed blocks");
+
+    env::commit(&output);
+}
@@ -0,0 +1,74 @@
+#![no_main]
+use risc0_zkvm::guest::env;
+risc0_zkvm::guest::entry!(main);
+
+use std::hint::black_box;
+
+use k256::ecdsa::{SigningKey, VerifyingKey};
+use raiko_lib::prover::PrecompileCycles;
+use sha2::Digest;
+
+/// Run the accelerated and the software implementation of a precompile the given number of
+/// times each and count their cycles.
+fn bench(
+    precompile: &str,
+    iterations: u32,
+    mut accelerated: impl FnMut(),
+    mut software: impl FnMut(),
+) -> PrecompileCycles {
+    let cycles = |op: &mut dyn FnMut()| {
+        let start = env::cycle_count();
+        for _ in 0..iterations {
+            op();
+        }
+        (env::cycle_count() - start) as u64
+    };
+    PrecompileCycles {
+        precompile: precompile.to_string(),
+        accelerated: cycles(&mut accelerated),
+        software: cycles(&mut software),
+    }
+}
+
+fn main() {
+    let iterations: u32 = env::read();
+
+    let data = [0xa5u8; 1024];
+    let hash: [u8; 32] = sha2::Sha256::digest(b"raiko").into();
+    let signing_key = SigningKey::from_bytes(&[0x11u8; 32].into()).unwrap();
+    let (signature, recovery_id) = signing_key.sign_prehash_recoverable(&hash).unwrap();
+    let message = libsecp256k1::Message::parse(&hash);
+    let soft_signature =
+        libsecp256k1::Signature::parse_standard(&signature.to_bytes().into()).unwrap();
+    let soft_recovery_id = libsecp256k1::RecoveryId::parse(recovery_id.to_byte()).unwrap();
+
+    // This version of risc0 has no keccak or bn254 precompile to compare.
+    let cycles = vec![
+        bench(
+            "sha256",
+            iterations,
+            || {
+                black_box(sha2::Sha256::digest(black_box(&data)));
+            },
+            || {
+                black_box(hmac_sha256::Hash::hash(black_box(&data)));
+            },
+        ),
+        bench(
+            "ecrecover",
+            iterations,
+            || {
+                black_box(
+                    VerifyingKey::recover_from_prehash(&hash, &signature, recovery_id).unwrap(),
+                );
+            },
+            || {
+                black_box(
+                    libsecp256k1::recover(&message, &soft_signature, &soft_recovery_id).unwrap(),
+                );
+            },
+        ),
+    ];
+
+    env::commit(&cycles);
+}
@@ -1,18 +1,34 @@
 #![no_main]
-use risc0_zkvm::guest::env;
+use risc0_zkvm::guest::env::{self, FdWriter, Write as _};
 risc0_zkvm::guest::entry!(main);
 
+use raiko_lib::protocol_instance::assemble_protocol_instance;
+use raiko_lib::protocol_instance::EvidenceType;
 use raiko_lib::{
     builder::{BlockBuilderStrategy, TaikoStrategy},
+    consts::verify_chain_spec,
     input::{GuestInput, GuestOutput, WrappedHeader},
+    prover::{GuestProfile, PhaseCycles, GUEST_PROFILE_FD},
 };
-use raiko_lib::protocol_instance::assemble_protocol_instance;
-use raiko_lib::protocol_instance::EvidenceType;
+use raiko_primitives::mpt::node_accesses;
 
-fn main() {
+/// The address the next allocation lands at. The guest allocates with a bump allocator that
+/// never frees, so this is the high-water mark of the heap.
+fn heap_top() -> u64 {
+    Box::into_raw(Box::new(0u8)) as usize as u64
+}
 
+fn main() {
+    let heap_start = heap_top();
+    let start = env::cycle_count();
     let input: GuestInput = env::read();
-    let build_result = TaikoStrategy::build_from(&input);
+    let profile: bool = env::read();
+    let deserialized = env::cycle_count();
+    // The chain spec comes with the input, only the rules of the built-in chains are trusted.
+    verify_chain_spec(input.network, &input.chain_spec).expect("Unsupported chain spec");
+
+    let mut executed = deserialized;
+    let build_result = TaikoStrategy::build_from_with(&input, || executed = env::cycle_count());
 
     // TODO: cherry-pick risc0 latest output
     let output = match &build_result {
@@ -28,4 +44,20 @@ fn main() {
     };
 
     env::commit(&output);
-}
\ No newline at end of file
+
+    if profile {
+        let finalized = env::cycle_count();
+        let (trie_node_reads, trie_node_writes) = node_accesses();
+        let profile = GuestProfile {
+            cycles: PhaseCycles {
+                deserialize: (deserialized - start) as u64,
+                execute: (executed - deserialized) as u64,
+                finalize: (finalized - executed) as u64,
+            },
+            peak_memory: heap_top() - heap_start,
+            trie_node_reads: trie_node_reads as u64,
+            trie_node_writes: trie_node_writes as u64,
+        };
+        FdWriter::new(GUEST_PROFILE_FD, |_| {}).write(&profile);
+    }
+}
@@ -0,0 +1,95 @@
+//! Checkpoints of the proofs in the workspace given by the host.
+//!
+//! The receipts of the proven segments are kept per segment limit, as the segments of another
+//! limit don't match, and the Bonsai session of a proof is kept until it finished. A proof
+//! interrupted by a crash or a restart of the host resumes from them.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::{debug, warn};
+use risc0_zkvm::SegmentReceipt;
+
+/// The file the id of the Bonsai session of the proof is kept in.
+const BONSAI_SESSION_FILE: &str = "bonsai-session";
+
+/// The checkpoints of a proof.
+pub struct Checkpoint {
+    dir: PathBuf,
+}
+
+impl Checkpoint {
+    /// The checkpoints of the proof with the receipt label in the workspace.
+    pub fn new(workspace: &Path, receipt_label: &str) -> Self {
+        Self {
+            dir: workspace.join(format!("risc0-{receipt_label}")),
+        }
+    }
+
+    /// The id of the Bonsai session the proof was started in.
+    pub fn bonsai_session(&self) -> Option<String> {
+        fs::read_to_string(self.dir.join(BONSAI_SESSION_FILE))
+            .ok()
+            .map(|uuid| uuid.trim().to_string())
+            .filter(|uuid| !uuid.is_empty())
+    }
+
+    pub fn save_bonsai_session(&self, uuid: &str) {
+        self.write(&self.dir.join(BONSAI_SESSION_FILE), uuid.as_bytes());
+    }
+
+    /// Forget the Bonsai session, once it failed.
+    pub fn clear_bonsai_session(&self) {
+        let _ = fs::remove_file(self.dir.join(BONSAI_SESSION_FILE));
+    }
+
+    /// The receipt of the segment, if it was proven with the segment limit.
+    pub fn segment(&self, po2: u32, index: usize) -> Option<SegmentReceipt> {
+        let bytes = fs::read(self.segment_path(po2, index)).ok()?;
+        match bincode::deserialize(&bytes) {
+            Ok(receipt) => Some(receipt),
+            Err(e) => {
+                warn!("Ignoring the invalid receipt of segment {index}: {e}");
+                None
+            }
+        }
+    }
+
+    pub fn save_segment(&self, po2: u32, index: usize, receipt: &SegmentReceipt) {
+        match bincode::serialize(receipt) {
+            Ok(bytes) => self.write(&self.segment_path(po2, index), &bytes),
+            Err(e) => warn!("Could not serialize the receipt of segment {index}: {e}"),
+        }
+    }
+
+    /// Remove the receipts of the segments, once they turned out to be unusable.
+    pub fn clear_segments(&self, po2: u32) {
+        let _ = fs::remove_dir_all(self.segments_dir(po2));
+    }
+
+    fn segments_dir(&self, po2: u32) -> PathBuf {
+        self.dir.join(format!("segments-po2-{po2}"))
+    }
+
+    fn segment_path(&self, po2: u32, index: usize) -> PathBuf {
+        self.segments_dir(po2)
+            .join(format!("segment-{index}.receipt"))
+    }
+
+    /// Write the file through a temporary one, so an interrupted write leaves no partial
+    /// checkpoint. A checkpoint that can't be written only costs the resume.
+    fn write(&self, path: &Path, bytes: &[u8]) {
+        let tmp = path.with_extension("tmp");
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&tmp, bytes))
+            .and_then(|_| fs::rename(&tmp, path));
+        match result {
+            Ok(()) => debug!("Saved the checkpoint {}", path.display()),
+            Err(e) => warn!("Could not save the checkpoint {}: {e}", path.display()),
+        }
+    }
+}
@@ -5,6 +5,7 @@ use std::{
     fmt::Debug,
     fs,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use alloy_primitives::B256;
@@ -13,26 +14,38 @@ use bonsai_sdk::alpha::responses::SnarkReceipt;
 use hex::ToHex;
 use log::{debug, error, info, warn};
 use raiko_lib::{
+    aggregate::{AggregationGuestInput, AggregationGuestOutput, Aggregator},
     input::{GuestInput, GuestOutput},
     protocol_instance::ProtocolInstance,
-    prover::{to_proof, Proof, Prover, ProverConfig, ProverResult},
+    prover::{
+        cuda_device, to_proof, GuestProfile, GuestSelection, PrecompileCycles, ProgressReporter,
+        Proof, Prover, ProverConfig, ProverProgress, ProverResult, GUEST_PROFILE_FD,
+    },
 };
 use raiko_primitives::keccak::keccak;
 use risc0_zkvm::{
-    compute_image_id, is_dev_mode,
+    compute_image_id, get_prover_server, is_dev_mode,
     serde::to_vec,
     sha::{Digest, Digestible},
-    Assumption, ExecutorEnv, ExecutorImpl, Receipt,
+    Assumption, CompositeReceipt, ExecutorEnv, ExecutorImpl, InnerReceipt, ProverOpts, Receipt,
+    Session, VerifierContext,
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_with::serde_as;
 use tracing::info as traicing_info;
 
+pub mod checkpoint;
 pub mod snarks;
-use crate::snarks::verify_groth16_snark;
+use crate::{
+    checkpoint::Checkpoint,
+    snarks::{verifier_calldata, verify_groth16_snark},
+};
 
 include!(concat!(env!("OUT_DIR"), "/methods.rs"));
 
+/// The interval in which the status of a Bonsai session is polled.
+const BONSAI_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
 #[serde_as]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Risc0Param {
@@ -40,11 +53,35 @@ pub struct Risc0Param {
     pub snark: bool,
     pub profile: bool,
     pub execution_po2: u32,
+    /// Have the guest report its profile, set from `profile_guest` of the request.
+    #[serde(skip)]
+    pub profile_guest: bool,
+    /// The CUDA device to prove on locally, set from `cuda_device` of the host.
+    #[serde(skip)]
+    pub cuda_device: Option<u32>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Risc0Response {
+    /// The hex encoded journal of the receipt.
     pub proof: String,
+    /// The Bonsai session id of the receipt, empty for receipts proven locally.
+    #[serde(default)]
+    pub uuid: String,
+    /// The hex encoded receipt, used to aggregate proofs.
+    #[serde(default)]
+    pub receipt: String,
+    /// The hex encoded call to the on-chain verifier, set when the proof was wrapped into a
+    /// SNARK.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calldata: Option<String>,
+    /// The Bonsai session id of the SNARK, set when the proof was wrapped into a SNARK.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snark_uuid: Option<String>,
+    /// The image id of the guest, set when the host selected another guest than the built-in
+    /// one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_id: Option<B256>,
 }
 
 pub struct Risc0Prover;
@@ -55,53 +92,304 @@ impl Prover for Risc0Prover {
         output: GuestOutput,
         config: &ProverConfig,
     ) -> ProverResult<Proof> {
-        let config = Risc0Param::deserialize(config.get("risc0").unwrap()).unwrap();
+        Self::run_with_progress(input, output, config, &ProgressReporter::default()).await
+    }
+
+    async fn run_with_progress(
+        input: GuestInput,
+        output: GuestOutput,
+        config: &ProverConfig,
+        progress: &ProgressReporter,
+    ) -> ProverResult<Proof> {
+        let compress = compress_requested(config);
+        let workspace = workspace(config);
+        let profile_guest = flag_requested(config, "profile_guest");
+        let guest = guest_elf(config)?;
+        let device = cuda_device(config);
+        let mut config = risc0_param(config)?;
+        // Bonsai doesn't return what the guest writes besides the journal.
+        config.profile_guest = profile_guest && !config.bonsai;
+        config.cuda_device = device;
+        let (elf, image_id) = match &guest {
+            Some((elf, image_id)) => (elf.as_slice(), *image_id),
+            None => (RISC0_METHODS_ELF, Digest::from(RISC0_METHODS_ID)),
+        };
 
-        println!("elf code length: {}", RISC0_METHODS_ELF.len());
-        let encoded_input = to_vec(&input).expect("Could not serialize proving input!");
+        println!("elf code length: {}", elf.len());
+        let mut encoded_input = to_vec(&input).expect("Could not serialize proving input!");
+        encoded_input.extend(to_vec(&config.profile_guest).expect("Could not serialize flag!"));
 
         let result = maybe_prove::<GuestInput, GuestOutput>(
             &config,
             encoded_input,
-            RISC0_METHODS_ELF,
+            elf,
             &output,
             Default::default(),
+            workspace.as_deref(),
+            progress,
         )
         .await;
 
-        let journal: String = result.clone().unwrap().1.journal.encode_hex();
+        let guest_image_id = guest
+            .is_some()
+            .then(|| B256::from_slice(image_id.as_bytes()));
+        finish_proof(
+            config.snark || compress,
+            image_id,
+            result,
+            guest_image_id,
+            progress,
+        )
+        .await
+    }
 
-        // Create/verify Groth16 SNARK
-        if config.snark {
-            let Some((stark_uuid, stark_receipt)) = result else {
-                panic!("No STARK data to snarkify!");
-            };
-            let image_id = Digest::from(RISC0_METHODS_ID);
-            let (snark_uuid, snark_receipt) = stark2snark(image_id, stark_uuid, stark_receipt)
-                .await
-                .map_err(|err| format!("Failed to convert STARK to SNARK: {err:?}"))?;
+    fn instance_hash(pi: ProtocolInstance) -> B256 {
+        let data = (pi.transition.clone(), pi.prover, pi.meta_hash()).abi_encode();
 
-            traicing_info!("Validating SNARK uuid: {snark_uuid}");
+        keccak(data).into()
+    }
 
-            verify_groth16_snark(image_id, snark_receipt)
-                .await
-                .map_err(|err| format!("Failed to verify SNARK: {err:?}"))?;
+    fn program_id() -> Option<B256> {
+        Some(B256::from_slice(Digest::from(RISC0_METHODS_ID).as_bytes()))
+    }
+
+    fn verify(
+        proof: &Proof,
+        _input: &GuestInput,
+        output: &GuestOutput,
+    ) -> Option<ProverResult<()>> {
+        Some(proof_image_id(proof).and_then(|image_id| verify_receipt(proof, image_id, output)))
+    }
+}
+
+impl Aggregator for Risc0Prover {
+    async fn aggregate(proofs: Vec<Proof>, config: &ProverConfig) -> ProverResult<Proof> {
+        let compress = compress_requested(config);
+        let workspace = workspace(config);
+        let config = risc0_param(config)?;
+
+        // Collect the block outputs and pass the block receipts on as assumptions.
+        let mut block_outputs = Vec::with_capacity(proofs.len());
+        let mut assumptions = Vec::with_capacity(proofs.len());
+        let mut assumption_uuids = Vec::with_capacity(proofs.len());
+        for proof in proofs {
+            let (response, receipt) = decode_receipt(&proof)?;
+            receipt
+                .verify(RISC0_METHODS_ID)
+                .map_err(|e| format!("Failed to verify block receipt: {e}"))?;
+            block_outputs.push(
+                receipt
+                    .journal
+                    .decode::<GuestOutput>()
+                    .map_err(|e| format!("Invalid block receipt journal: {e}"))?,
+            );
+            let uuid = if config.bonsai && response.uuid.is_empty() {
+                upload_receipt(&receipt)
+                    .await
+                    .map_err(|e| format!("Failed to upload block receipt to Bonsai: {e}"))?
+            } else {
+                response.uuid
+            };
+            assumptions.push(receipt.into());
+            assumption_uuids.push(uuid);
         }
 
-        to_proof(Ok(Risc0Response { proof: journal }))
+        let program_id = Digest::from(RISC0_METHODS_ID);
+        let output =
+            AggregationGuestOutput::new(B256::from_slice(program_id.as_bytes()), &block_outputs)
+                .ok_or_else(|| "Can not aggregate failed blocks".to_string())?;
+        let input = AggregationGuestInput {
+            program_id: RISC0_METHODS_ID,
+            block_outputs,
+        };
+
+        println!("elf code length: {}", AGGREGATION_ELF.len());
+        let encoded_input = to_vec(&input).expect("Could not serialize aggregation input!");
+
+        let progress = ProgressReporter::default();
+        let result = maybe_prove::<AggregationGuestInput, AggregationGuestOutput>(
+            &config,
+            encoded_input,
+            AGGREGATION_ELF,
+            &output,
+            (assumptions, assumption_uuids),
+            workspace.as_deref(),
+            &progress,
+        )
+        .await;
+
+        finish_proof(
+            config.snark || compress,
+            Digest::from(AGGREGATION_ID),
+            result,
+            None,
+            &progress,
+        )
+        .await
     }
+}
 
-    fn instance_hash(pi: ProtocolInstance) -> B256 {
-        let data = (pi.transition.clone(), pi.prover, pi.meta_hash()).abi_encode();
+/// The image id to verify the proof against. Proofs of another guest than the built-in one carry
+/// its image id, which has to be the image id of a guest configured by the host.
+fn proof_image_id(proof: &Proof) -> ProverResult<Digest> {
+    let image_id = proof
+        .get("image_id")
+        .map(B256::deserialize)
+        .transpose()
+        .map_err(|e| format!("Invalid image id: {e}"))?;
+    let Some(image_id) = image_id else {
+        return Ok(Digest::from(RISC0_METHODS_ID));
+    };
+    if GuestSelection::configured(image_id).is_none() {
+        return Err(
+            format!("The proof is of the unknown guest with the image id {image_id}").into(),
+        );
+    }
+    Digest::try_from(image_id.as_slice())
+        .map_err(|e| format!("Invalid image id {image_id}: {e}").into())
+}
 
-        keccak(data).into()
+/// Get the receipt out of a proof response.
+fn decode_receipt(proof: &Proof) -> ProverResult<(Risc0Response, Receipt)> {
+    let response =
+        Risc0Response::deserialize(proof).map_err(|e| format!("Invalid Risc0 proof: {e}"))?;
+    if response.receipt.is_empty() {
+        return Err("The Risc0 proof has no receipt".to_string().into());
+    }
+    let receipt: Receipt = hex::decode(&response.receipt)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| bincode::deserialize(&bytes).map_err(|e| e.to_string()))
+        .map_err(|e| format!("Invalid Risc0 receipt: {e}"))?;
+    Ok((response, receipt))
+}
+
+/// Verify the receipt of the proof against the image id, and check that its journal is the
+/// expected output. A wrong image id or a miscompiled guest fails here instead of on L1.
+fn verify_receipt<O: Eq + Debug + DeserializeOwned>(
+    proof: &Proof,
+    image_id: impl Into<Digest>,
+    expected_output: &O,
+) -> ProverResult<()> {
+    let (_, receipt) = decode_receipt(proof)?;
+    receipt
+        .verify(image_id)
+        .map_err(|e| format!("Failed to verify receipt: {e}"))?;
+    let output: O = receipt
+        .journal
+        .decode()
+        .map_err(|e| format!("Invalid receipt journal: {e}"))?;
+    if output != *expected_output {
+        return Err(format!("The receipt proves {output:?} instead of {expected_output:?}").into());
     }
+    Ok(())
+}
+
+/// The ELF and the image id of the guest the host selected instead of the built-in one, after
+/// checking that the ELF has the image id the host expects.
+fn guest_elf(config: &ProverConfig) -> ProverResult<Option<(Vec<u8>, Digest)>> {
+    let Some(guest) = GuestSelection::from_config(config)? else {
+        return Ok(None);
+    };
+    let elf = fs::read(&guest.elf).map_err(|e| {
+        format!(
+            "Could not read the guest {} from {}: {e}",
+            guest.name, guest.elf
+        )
+    })?;
+    let image_id =
+        compute_image_id(&elf).map_err(|e| format!("Invalid guest {}: {e}", guest.name))?;
+    if image_id.as_bytes() != guest.program_id.as_slice() {
+        return Err(format!(
+            "The guest {} has the image id {image_id} instead of {}",
+            guest.name, guest.program_id
+        )
+        .into());
+    }
+    Ok(Some((elf, image_id)))
+}
+
+/// The RISC Zero params of the request.
+fn risc0_param(config: &ProverConfig) -> ProverResult<Risc0Param> {
+    let param = config
+        .get("risc0")
+        .ok_or_else(|| "Risc0: missing the risc0 params".to_string())?;
+    Risc0Param::deserialize(param).map_err(|e| format!("Risc0: invalid params: {e}").into())
+}
+
+/// Whether the request asks for the proof to be wrapped into a SNARK.
+fn compress_requested(config: &ProverConfig) -> bool {
+    flag_requested(config, "compress")
+}
+
+/// Whether the given flag is set in the request.
+fn flag_requested(config: &ProverConfig, flag: &str) -> bool {
+    config
+        .get(flag)
+        .and_then(|flag| flag.as_bool())
+        .unwrap_or_default()
+}
+
+/// The workspace the host keeps the checkpoints of the proof in, if any.
+fn workspace(config: &ProverConfig) -> Option<PathBuf> {
+    config
+        .get("workspace")
+        .and_then(|workspace| workspace.as_str())
+        .map(PathBuf::from)
+}
+
+/// Turn the receipt into the proof response, after wrapping it into a Groth16 SNARK if
+/// requested.
+async fn finish_proof(
+    snark: bool,
+    image_id: Digest,
+    result: Option<(String, Receipt)>,
+    guest_image_id: Option<B256>,
+    progress: &ProgressReporter,
+) -> ProverResult<Proof> {
+    let Some((uuid, receipt)) = result else {
+        return Err("No STARK receipt was generated".to_string().into());
+    };
+    let journal: String = receipt.journal.encode_hex();
+    let encoded_receipt = bincode::serialize(&receipt)
+        .map_err(|e| format!("Failed to serialize receipt: {e}"))?
+        .encode_hex();
+
+    // Create/verify Groth16 SNARK
+    let (calldata, snark_uuid) = if snark {
+        let (snark_uuid, snark_receipt) = stark2snark(image_id, uuid.clone(), receipt, progress)
+            .await
+            .map_err(|err| format!("Failed to convert STARK to SNARK: {err:?}"))?;
+
+        traicing_info!("Validating SNARK uuid: {snark_uuid}");
+
+        let calldata = verifier_calldata(image_id, &snark_receipt);
+        verify_groth16_snark(image_id, snark_receipt)
+            .await
+            .map_err(|err| format!("Failed to verify SNARK: {err:?}"))?;
+        (
+            Some(format!("0x{}", calldata.encode_hex::<String>())),
+            Some(snark_uuid),
+        )
+    } else {
+        (None, None)
+    };
+    progress.report(ProverProgress::Verified);
+
+    to_proof(Ok(Risc0Response {
+        proof: journal,
+        uuid,
+        receipt: encoded_receipt,
+        calldata,
+        snark_uuid,
+        image_id: guest_image_id,
+    }))
 }
 
 pub async fn stark2snark(
     image_id: Digest,
     stark_uuid: String,
     stark_receipt: Receipt,
+    progress: &ProgressReporter,
 ) -> anyhow::Result<(String, SnarkReceipt)> {
     info!("Submitting SNARK workload");
     // Label snark output as journal digest
@@ -124,20 +412,26 @@ pub async fn stark2snark(
 
     let client = bonsai_sdk::alpha_async::get_client_from_env(risc0_zkvm::VERSION).await?;
     let snark_uuid = client.create_snark(stark_uuid)?;
+    info!("Tracking SNARK uuid: {}", snark_uuid.uuid);
+    report_bonsai_session(progress, &snark_uuid.uuid);
 
     let snark_receipt = loop {
         let res = snark_uuid.status(&client)?;
 
         if res.status == "RUNNING" {
             info!("Current status: {} - continue polling...", res.status);
-            std::thread::sleep(std::time::Duration::from_secs(15));
+            tokio::time::sleep(BONSAI_POLL_INTERVAL).await;
         } else if res.status == "SUCCEEDED" {
-            break res
-                .output
-                .expect("Bonsai response is missing SnarkReceipt.");
+            break res.output.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Bonsai SNARK session {} is missing the receipt",
+                    snark_uuid.uuid
+                )
+            })?;
         } else {
-            panic!(
-                "Workflow exited: {} - | err: {}",
+            anyhow::bail!(
+                "Bonsai SNARK session {} exited: {} - | err: {}",
+                snark_uuid.uuid,
                 res.status,
                 res.error_msg.unwrap_or_default()
             );
@@ -171,6 +465,7 @@ pub async fn verify_bonsai_receipt<O: Eq + Debug + DeserializeOwned>(
     expected_output: &O,
     uuid: String,
     max_retries: usize,
+    progress: &ProgressReporter,
 ) -> anyhow::Result<(String, Receipt)> {
     info!("Tracking receipt uuid: {uuid}");
     let session = bonsai_sdk::alpha::SessionId { uuid };
@@ -190,13 +485,13 @@ pub async fn verify_bonsai_receipt<O: Eq + Debug + DeserializeOwned>(
                         anyhow::bail!(err);
                     }
                     warn!("Attempt {attempt}/{max_retries} for session status request: {err:?}");
-                    std::thread::sleep(std::time::Duration::from_secs(15));
+                    tokio::time::sleep(BONSAI_POLL_INTERVAL).await;
                     continue;
                 }
             }
         }
 
-        let res = res.unwrap();
+        let res = res.ok_or_else(|| anyhow::anyhow!("No session status request was made"))?;
 
         if res.status == "RUNNING" {
             info!(
@@ -204,20 +499,24 @@ pub async fn verify_bonsai_receipt<O: Eq + Debug + DeserializeOwned>(
                 res.status,
                 res.state.unwrap_or_default()
             );
-            std::thread::sleep(std::time::Duration::from_secs(15));
+            tokio::time::sleep(BONSAI_POLL_INTERVAL).await;
         } else if res.status == "SUCCEEDED" {
+            if let Some(stats) = &res.stats {
+                progress.report(ProverProgress::Executed {
+                    cycles: Some(stats.total_cycles),
+                    segments: Some(stats.segments as u64),
+                });
+            }
             // Download the receipt, containing the output
-            let receipt_url = res
-                .receipt_url
-                .expect("API error, missing receipt on completed session");
+            let receipt_url = res.receipt_url.ok_or_else(|| {
+                anyhow::anyhow!("Bonsai session {} is missing the receipt", session.uuid)
+            })?;
             let client = bonsai_sdk::alpha_async::get_client_from_env(risc0_zkvm::VERSION).await?;
             let receipt_buf = client.download(&receipt_url)?;
             let receipt: Receipt = bincode::deserialize(&receipt_buf)?;
-            receipt
-                .verify(image_id)
-                .expect("Receipt verification failed");
+            receipt.verify(image_id)?;
             // verify output
-            let receipt_output: O = receipt.journal.decode().unwrap();
+            let receipt_output: O = receipt.journal.decode()?;
             if expected_output == &receipt_output {
                 info!("Receipt validated!");
             } else {
@@ -227,8 +526,9 @@ pub async fn verify_bonsai_receipt<O: Eq + Debug + DeserializeOwned>(
             }
             return Ok((session.uuid, receipt));
         } else {
-            panic!(
-                "Workflow exited: {} - | err: {}",
+            anyhow::bail!(
+                "Bonsai session {} exited: {} - | err: {}",
+                session.uuid,
                 res.status,
                 res.error_msg.unwrap_or_default()
             );
@@ -242,6 +542,8 @@ pub async fn maybe_prove<I: Serialize, O: Eq + Debug + Serialize + DeserializeOw
     elf: &[u8],
     expected_output: &O,
     assumptions: (Vec<Assumption>, Vec<String>),
+    workspace: Option<&Path>,
+    progress: &ProgressReporter,
 ) -> Option<(String, Receipt)> {
     let (assumption_instances, assumption_uuids) = assumptions;
 
@@ -254,6 +556,7 @@ pub async fn maybe_prove<I: Serialize, O: Eq + Debug + Serialize + DeserializeOw
         hex::encode(computed_image_id),
         hex::encode(keccak(bytemuck::cast_slice(&encoded_output)))
     );
+    let checkpoint = workspace.map(|workspace| Checkpoint::new(workspace, &receipt_label));
 
     // get receipt
     let (mut receipt_uuid, receipt, cached) =
@@ -261,39 +564,66 @@ pub async fn maybe_prove<I: Serialize, O: Eq + Debug + Serialize + DeserializeOw
             info!("Loaded locally cached stark receipt {receipt_label:?}");
             (cached_data.0, cached_data.1, true)
         } else if param.bonsai {
-            // query bonsai service until it works
-            loop {
-                match prove_bonsai(
-                    encoded_input.clone(),
-                    elf,
-                    expected_output,
-                    assumption_uuids.clone(),
-                )
-                .await
-                {
-                    Ok((receipt_uuid, receipt)) => {
-                        break (receipt_uuid, receipt, false);
-                    }
-                    Err(err) => {
-                        warn!("Failed to prove on Bonsai: {err:?}");
-                        std::thread::sleep(std::time::Duration::from_secs(15));
-                    }
+            // Resume the session of an interrupted proof.
+            let resumed = match &checkpoint {
+                Some(checkpoint) => {
+                    resume_bonsai(computed_image_id, expected_output, checkpoint, progress).await
                 }
+                None => None,
+            };
+            match resumed {
+                Some((receipt_uuid, receipt)) => (receipt_uuid, receipt, false),
+                // query bonsai service until it works
+                None => loop {
+                    match prove_bonsai(
+                        encoded_input.clone(),
+                        elf,
+                        expected_output,
+                        assumption_uuids.clone(),
+                        checkpoint.as_ref(),
+                        progress,
+                    )
+                    .await
+                    {
+                        Ok((receipt_uuid, receipt)) => {
+                            break (receipt_uuid, receipt, false);
+                        }
+                        Err(err) => {
+                            warn!("Failed to prove on Bonsai: {err:?}");
+                            if let Some(checkpoint) = &checkpoint {
+                                checkpoint.clear_bonsai_session();
+                            }
+                            if progress.is_cancelled() {
+                                return None;
+                            }
+                            tokio::time::sleep(BONSAI_POLL_INTERVAL).await;
+                        }
+                    }
+                },
             }
         } else {
             // run prover
             info!("start running local prover");
-            (
-                Default::default(),
-                prove_locally(
-                    param.execution_po2,
-                    encoded_input,
-                    elf,
-                    assumption_instances,
-                    param.profile,
-                ),
-                false,
-            )
+            if let Some(device) = param.cuda_device {
+                info!("proving on CUDA device {device}");
+            }
+            let receipt = prove_locally(
+                param.execution_po2,
+                encoded_input,
+                elf,
+                assumption_instances,
+                param.profile,
+                param.profile_guest,
+                checkpoint.as_ref(),
+                progress,
+            );
+            match receipt {
+                Ok(receipt) => (Default::default(), receipt, false),
+                Err(err) => {
+                    error!("Failed to prove locally: {err:?}");
+                    return None;
+                }
+            }
         };
 
     info!("receipt: {receipt:?}");
@@ -326,16 +656,47 @@ pub async fn maybe_prove<I: Serialize, O: Eq + Debug + Serialize + DeserializeOw
     Some(result)
 }
 
+/// Report the Bonsai session a proof is generated in, so it can be tracked by the caller.
+fn report_bonsai_session(progress: &ProgressReporter, uuid: &str) {
+    progress.report(ProverProgress::RemoteSession {
+        service: "bonsai".to_string(),
+        session_id: uuid.to_string(),
+    });
+}
+
 pub async fn upload_receipt(receipt: &Receipt) -> anyhow::Result<String> {
     let client = bonsai_sdk::alpha_async::get_client_from_env(risc0_zkvm::VERSION).await?;
     Ok(client.upload_receipt(bincode::serialize(receipt)?)?)
 }
 
+/// Wait for the receipt of the Bonsai session of an interrupted proof, if it was started in
+/// one and the session didn't fail.
+async fn resume_bonsai<O: Eq + Debug + DeserializeOwned>(
+    image_id: Digest,
+    expected_output: &O,
+    checkpoint: &Checkpoint,
+    progress: &ProgressReporter,
+) -> Option<(String, Receipt)> {
+    let uuid = checkpoint.bonsai_session()?;
+    info!("Resuming Bonsai session {uuid}");
+    report_bonsai_session(progress, &uuid);
+    match verify_bonsai_receipt(image_id, expected_output, uuid, 8, progress).await {
+        Ok(result) => Some(result),
+        Err(err) => {
+            warn!("Failed to resume the Bonsai session, starting a new one: {err:?}");
+            checkpoint.clear_bonsai_session();
+            None
+        }
+    }
+}
+
 pub async fn prove_bonsai<O: Eq + Debug + DeserializeOwned>(
     encoded_input: Vec<u32>,
     elf: &[u8],
     expected_output: &O,
     assumption_uuids: Vec<String>,
+    checkpoint: Option<&Checkpoint>,
+    progress: &ProgressReporter,
 ) -> anyhow::Result<(String, Receipt)> {
     info!("Proving on Bonsai");
     // Compute the image_id, then upload the ELF with the image_id as its key.
@@ -354,19 +715,54 @@ pub async fn prove_bonsai<O: Eq + Debug + DeserializeOwned>(
         input_id.clone(),
         assumption_uuids.clone(),
     )?;
+    report_bonsai_session(progress, &session.uuid);
+    if let Some(checkpoint) = checkpoint {
+        checkpoint.save_bonsai_session(&session.uuid);
+    }
+
+    verify_bonsai_receipt(image_id, expected_output, session.uuid.clone(), 8, progress).await
+}
 
-    verify_bonsai_receipt(image_id, expected_output, session.uuid.clone(), 8).await
+/// The image id of a guest ELF, e.g. of a guest rebuilt to check the one built into the host.
+pub fn elf_image_id(elf: &[u8]) -> ProverResult<B256> {
+    let image_id = compute_image_id(elf).map_err(|e| format!("Invalid guest ELF: {e}"))?;
+    Ok(B256::from_slice(image_id.as_bytes()))
+}
+
+/// Execute the precompiles benchmark guest, which runs every accelerated precompile and a
+/// software implementation of it the given number of times, and return their cycles.
+pub fn bench_precompiles(iterations: u32) -> ProverResult<Vec<PrecompileCycles>> {
+    let env = ExecutorEnv::builder()
+        .write(&iterations)
+        .and_then(|builder| builder.build())
+        .map_err(|e| format!("Failed to write the benchmark input: {e}"))?;
+    let session = ExecutorImpl::from_elf(env, PRECOMPILES_ELF)
+        .and_then(|mut exec| exec.run())
+        .map_err(|e| format!("Failed to run the precompiles benchmark: {e}"))?;
+    let journal = session
+        .journal
+        .ok_or_else(|| "The precompiles benchmark has no journal".to_string())?;
+    Ok(journal
+        .decode()
+        .map_err(|e| format!("Invalid precompiles benchmark journal: {e}"))?)
 }
 
 /// Prove the given ELF locally with the given input and assumptions. The segments are
 /// stored in a temporary directory, to allow for proofs larger than the available memory.
+/// With a checkpoint, the segments are proven one by one and the ones proven before the proof
+/// was interrupted are skipped. With `profile_guest`, the profile the guest writes is reported.
+/// The segments are proven one by one, so a cancelled proof stops after the current segment.
+#[allow(clippy::too_many_arguments)]
 pub fn prove_locally(
     segment_limit_po2: u32,
     encoded_input: Vec<u32>,
     elf: &[u8],
     assumptions: Vec<Assumption>,
     profile: bool,
-) -> Receipt {
+    profile_guest: bool,
+    checkpoint: Option<&Checkpoint>,
+    progress: &ProgressReporter,
+) -> anyhow::Result<Receipt> {
     debug!("Proving with segment_limit_po2 = {segment_limit_po2:?}");
     debug!(
         "Input size: {} words ( {} MB )",
@@ -375,6 +771,7 @@ pub fn prove_locally(
     );
 
     info!("Running the prover...");
+    let mut guest_profile = Vec::new();
     let session = {
         let mut env_builder = ExecutorEnv::builder();
         env_builder
@@ -387,6 +784,10 @@ pub fn prove_locally(
             env_builder.enable_profiler(format!("profile_r0_local.pb"));
         }
 
+        if profile_guest {
+            env_builder.write_fd(GUEST_PROFILE_FD, &mut guest_profile);
+        }
+
         for assumption in assumptions {
             env_builder.add_assumption(assumption);
         }
@@ -401,7 +802,94 @@ pub fn prove_locally(
 
         exec.run().unwrap()
     };
-    session.prove().unwrap()
+    progress.report(ProverProgress::Execution(100));
+    progress.report(ProverProgress::Executed {
+        cycles: session.get_cycles().ok(),
+        segments: Some(session.segments.len() as u64),
+    });
+    if profile_guest {
+        match decode_guest_profile(&guest_profile) {
+            Ok(guest_profile) => progress.report(ProverProgress::Profiled(guest_profile)),
+            Err(err) => warn!("Invalid guest profile: {err}"),
+        }
+    }
+    match prove_segments(&session, elf, segment_limit_po2, checkpoint, progress) {
+        Ok(receipt) => return Ok(receipt),
+        Err(err) if progress.is_cancelled() => return Err(err),
+        Err(err) => {
+            warn!("Failed to prove the segments, proving from scratch: {err:?}");
+            if let Some(checkpoint) = checkpoint {
+                checkpoint.clear_segments(segment_limit_po2);
+            }
+        }
+    }
+    session.prove()
+}
+
+/// Decode the profile the guest wrote in words.
+fn decode_guest_profile(bytes: &[u8]) -> Result<GuestProfile, risc0_zkvm::serde::Error> {
+    let words: Vec<u32> = bytemuck::pod_collect_to_vec(bytes);
+    risc0_zkvm::serde::from_slice(&words)
+}
+
+/// Prove the segments of the session one by one, saving the receipt of every segment and
+/// reusing the ones saved before with a checkpoint, and compose them into the receipt of the
+/// session. Stops before the next segment once the proof was cancelled.
+fn prove_segments(
+    session: &Session,
+    elf: &[u8],
+    segment_limit_po2: u32,
+    checkpoint: Option<&Checkpoint>,
+    progress: &ProgressReporter,
+) -> anyhow::Result<Receipt> {
+    let prover = get_prover_server(&ProverOpts::default())?;
+    let ctx = VerifierContext::default();
+    let mut segments = Vec::with_capacity(session.segments.len());
+    for (index, segment) in session.segments.iter().enumerate() {
+        anyhow::ensure!(
+            !progress.is_cancelled(),
+            "The proof was cancelled at segment {index}"
+        );
+        let saved = checkpoint.and_then(|checkpoint| checkpoint.segment(segment_limit_po2, index));
+        let receipt = match saved {
+            Some(receipt) => {
+                debug!("Resumed segment {index} from its checkpoint");
+                receipt
+            }
+            None => {
+                let receipt = prover.prove_segment(&ctx, &segment.resolve()?)?;
+                if let Some(checkpoint) = checkpoint {
+                    checkpoint.save_segment(segment_limit_po2, index, &receipt);
+                }
+                receipt
+            }
+        };
+        segments.push(receipt);
+    }
+
+    let assumptions = session
+        .assumptions
+        .iter()
+        .map(|assumption| match assumption {
+            Assumption::Proven(receipt) => Ok(receipt.clone()),
+            Assumption::Unresolved(_) => Err(anyhow::anyhow!("Unresolved assumption")),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let journal = session
+        .journal
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("The session has no journal"))?;
+    let receipt = Receipt::new(
+        InnerReceipt::Composite(CompositeReceipt {
+            segments,
+            assumptions,
+            journal_digest: Some(journal.digest()),
+        }),
+        journal.bytes,
+    );
+    // Segments of another execution of the block don't compose into a valid receipt.
+    receipt.verify(compute_image_id(elf)?)?;
+    Ok(receipt)
 }
 
 pub fn load_receipt<T: serde::de::DeserializeOwned>(
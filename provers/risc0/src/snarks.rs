@@ -15,7 +15,7 @@
 use std::{str::FromStr, sync::Arc};
 
 use alloy_primitives::U256;
-use alloy_sol_types::{sol, SolValue};
+use alloy_sol_types::{sol, SolCall, SolValue};
 use bonsai_sdk::alpha::responses::SnarkReceipt;
 use ethers_contract::abigen;
 use ethers_core::types::H160;
@@ -82,6 +82,27 @@ impl From<Groth16Seal> for Seal {
     }
 }
 
+/// Encode the call to the on-chain RISC Zero verifier for the given SNARK receipt.
+pub fn verifier_calldata(image_id: Digest, snark_receipt: &SnarkReceipt) -> Vec<u8> {
+    let seal = <Groth16Seal as Into<Seal>>::into(snark_receipt.snark.clone()).abi_encode();
+    RiscZeroVerifier::verifyCall {
+        seal: seal.into(),
+        imageId: image_id.as_bytes().try_into().unwrap(),
+        postStateDigest: snark_receipt
+            .post_state_digest
+            .as_slice()
+            .try_into()
+            .unwrap(),
+        journalDigest: snark_receipt
+            .journal
+            .digest()
+            .as_bytes()
+            .try_into()
+            .unwrap(),
+    }
+    .abi_encode()
+}
+
 pub async fn verify_groth16_snark(
     image_id: Digest,
     snark_receipt: SnarkReceipt,
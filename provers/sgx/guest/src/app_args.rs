@@ -23,12 +23,32 @@ pub enum Command {
     Bootstrap,
     /// Check if bootstrap is readable
     Check,
+    /// Produce a fresh quote attesting the public key of the bootstrapped key pair.
+    Attest(AttestArgs),
+    /// Generate a new key pair with the next version to replace the bootstrapped one. The
+    /// bootstrapped key pair is used until the new one is activated.
+    Rotate,
+    /// Replace the bootstrapped key pair with the rotated one. The old private key is kept
+    /// encrypted under its version.
+    Activate,
 }
 
 #[derive(Debug, Args)]
 pub struct OneShotArgs {
     #[clap(long)]
     pub sgx_instance_id: u32,
+
+    #[clap(long)]
+    /// Map the input from this file in shared memory instead of reading it from stdin.
+    pub input_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct AttestArgs {
+    #[clap(long)]
+    /// A 32 byte hex value included in the report data of the quote, so the verifier knows the
+    /// quote is fresh.
+    pub nonce: Option<String>,
 }
 
 fn get_default_raiko_user_config_path(subdir: &str) -> PathBuf {
@@ -8,7 +8,7 @@ extern crate secp256k1;
 use anyhow::Result;
 use app_args::{App, Command};
 use clap::Parser;
-use one_shot::{bootstrap, load_bootstrap, one_shot};
+use one_shot::{activate, attest, bootstrap, load_bootstrap, one_shot, rotate};
 
 #[tokio::main]
 pub async fn main() -> Result<()> {
@@ -28,6 +28,18 @@ pub async fn main() -> Result<()> {
             load_bootstrap(&args.global_opts.secrets_dir)
                 .map_err(|_| anyhow::Error::msg("check booststrap failed"))?;
         }
+        Command::Attest(attest_args) => {
+            println!("Attesting the bootstrapped key");
+            attest(args.global_opts, attest_args)?
+        }
+        Command::Rotate => {
+            println!("Rotating the bootstrapped key");
+            rotate(args.global_opts)?
+        }
+        Command::Activate => {
+            println!("Activating the rotated key");
+            activate(args.global_opts)?
+        }
     }
 
     Ok(())
@@ -7,17 +7,20 @@ use std::{
 
 use anyhow::{anyhow, bail, Context, Error, Result};
 use base64_serde::base64_serde_type;
+use memmap2::Mmap;
 use raiko_lib::{
     builder::{BlockBuilderStrategy, TaikoStrategy},
-    protocol_instance::{assemble_protocol_instance, EvidenceType},
+    consts::verify_chain_spec,
+    input::GuestInput,
+    protocol_instance::{assemble_protocol_instance, EvidenceType, SGX_PROOF_LEN},
 };
-use raiko_primitives::Address;
-use secp256k1::{KeyPair, SecretKey};
+use raiko_primitives::{Address, B256};
+use secp256k1::{KeyPair, SecretKey, SECP256K1};
 use serde::Serialize;
 base64_serde_type!(Base64Standard, base64::engine::general_purpose::STANDARD);
 
 use crate::{
-    app_args::{GlobalOpts, OneShotArgs},
+    app_args::{AttestArgs, GlobalOpts, OneShotArgs},
     signature::*,
 };
 
@@ -26,11 +29,16 @@ pub const ATTESTATION_TYPE_DEVICE_FILE: &str = "/dev/attestation/attestation_typ
 pub const ATTESTATION_USER_REPORT_DATA_DEVICE_FILE: &str = "/dev/attestation/user_report_data";
 pub const BOOTSTRAP_INFO_FILENAME: &str = "bootstrap.json";
 pub const PRIV_KEY_FILENAME: &str = "priv.key";
+pub const KEY_VERSION_FILENAME: &str = "key_version";
+/// The subdirectory of the secrets and config directories a rotated key waits in until it's
+/// activated.
+pub const PENDING_KEY_DIR: &str = "pending";
 
 #[derive(Serialize)]
 struct BootstrapData {
     public_key: String,
     new_instance: Address,
+    key_version: u32,
     quote: String,
 }
 
@@ -59,12 +67,14 @@ fn get_sgx_quote() -> Result<Vec<u8>> {
 fn save_bootstrap_details(
     key_pair: &KeyPair,
     new_instance: Address,
+    key_version: u32,
     quote: Vec<u8>,
     bootstrap_details_file_path: &Path,
 ) -> Result<(), Error> {
     let bootstrap_details = BootstrapData {
         public_key: format!("0x{}", key_pair.public_key()),
         new_instance,
+        key_version,
         quote: hex::encode(quote),
     };
 
@@ -87,21 +97,99 @@ pub enum BootStrapError {
 }
 
 pub fn bootstrap(global_opts: GlobalOpts) -> Result<()> {
+    let key_version = load_key_version(&global_opts.secrets_dir)? + 1;
+    new_key(global_opts, key_version)
+}
+
+/// Generate the key to replace the bootstrapped key with. The new key is kept apart until it's
+/// activated, so proofs are signed with the bootstrapped key until the new one is registered.
+pub fn rotate(global_opts: GlobalOpts) -> Result<()> {
+    load_bootstrap(&global_opts.secrets_dir)
+        .or_else(|_| bail!("Application was not bootstrapped or has a deprecated bootstrap."))?;
+    let key_version = load_key_version(&global_opts.secrets_dir)?;
+    let pending_opts = pending_opts(&global_opts);
+    fs::create_dir_all(&pending_opts.secrets_dir)?;
+    fs::create_dir_all(&pending_opts.config_dir)?;
+    new_key(pending_opts, key_version + 1)
+}
+
+/// Replace the bootstrapped key with the rotated one. The old key is kept in the secrets
+/// directory under its version, so it can still be inspected after the rotation.
+pub fn activate(global_opts: GlobalOpts) -> Result<()> {
+    let pending_opts = pending_opts(&global_opts);
+    let pending_key = load_bootstrap(&pending_opts.secrets_dir)
+        .or_else(|_| bail!("No rotated key to activate."))?;
+    let pending_version = load_key_version(&pending_opts.secrets_dir)?;
+
+    let key_version = load_key_version(&global_opts.secrets_dir)?;
+    let privkey_path = global_opts.secrets_dir.join(PRIV_KEY_FILENAME);
+    let archived_privkey_path = global_opts
+        .secrets_dir
+        .join(format!("{PRIV_KEY_FILENAME}.{key_version}"));
+    fs::rename(&privkey_path, &archived_privkey_path).with_context(|| {
+        format!(
+            "Failed to archive private key file {}",
+            privkey_path.display()
+        )
+    })?;
+    println!(
+        "Private key version {key_version} archived in {}",
+        archived_privkey_path.display()
+    );
+
+    // Encrypted files are bound to their path, so the key is written again instead of moved
+    save_priv_key(
+        &KeyPair::from_secret_key(SECP256K1, &pending_key),
+        &privkey_path,
+    )?;
+    save_key_version(&global_opts.secrets_dir, pending_version)?;
+    let bootstrap_details_file_path = global_opts.config_dir.join(BOOTSTRAP_INFO_FILENAME);
+    fs::rename(
+        pending_opts.config_dir.join(BOOTSTRAP_INFO_FILENAME),
+        &bootstrap_details_file_path,
+    )
+    .with_context(|| {
+        format!(
+            "Failed to save bootstrap data file {}",
+            bootstrap_details_file_path.display()
+        )
+    })?;
+    fs::remove_dir_all(&pending_opts.secrets_dir)?;
+    println!("Activated key version {pending_version}");
+    Ok(())
+}
+
+/// The options with the directories of the rotated key.
+fn pending_opts(global_opts: &GlobalOpts) -> GlobalOpts {
+    GlobalOpts {
+        secrets_dir: global_opts.secrets_dir.join(PENDING_KEY_DIR),
+        config_dir: global_opts.config_dir.join(PENDING_KEY_DIR),
+    }
+}
+
+fn new_key(global_opts: GlobalOpts, key_version: u32) -> Result<()> {
     // Generate a new key pair
     let key_pair = generate_key();
     // Store it on disk encrypted inside SGX so we can reuse it between program runs
     let privkey_path = global_opts.secrets_dir.join(PRIV_KEY_FILENAME);
     save_priv_key(&key_pair, &privkey_path)?;
+    save_key_version(&global_opts.secrets_dir, key_version)?;
     // Get the public key from the pair
     println!("Public key: 0x{}", key_pair.public_key());
     let new_instance = public_key_to_address(&key_pair.public_key());
-    println!("Instance address: {new_instance}");
+    println!("Instance address: {new_instance}, key version: {key_version}");
     // Store the attestation with the new public key
-    save_attestation_user_report_data(new_instance)?;
+    save_attestation_user_report_data(new_instance, None)?;
     // Store all this data for future use on disk (no encryption necessary)
     let quote = get_sgx_quote()?;
     let bootstrap_details_file_path = global_opts.config_dir.join(BOOTSTRAP_INFO_FILENAME);
-    save_bootstrap_details(&key_pair, new_instance, quote, &bootstrap_details_file_path)?;
+    save_bootstrap_details(
+        &key_pair,
+        new_instance,
+        key_version,
+        quote,
+        &bootstrap_details_file_path,
+    )?;
     println!(
         "Bootstrap details saved in {}",
         bootstrap_details_file_path.display()
@@ -110,6 +198,42 @@ pub fn bootstrap(global_opts: GlobalOpts) -> Result<()> {
     Ok(())
 }
 
+/// The version of the bootstrapped key, sealed next to it. Keys bootstrapped before keys were
+/// versioned are version 0.
+fn load_key_version(secrets_dir: &Path) -> Result<u32> {
+    let path = secrets_dir.join(KEY_VERSION_FILENAME);
+    if !path.is_file() {
+        return Ok(0);
+    }
+    fs::read_to_string(&path)?
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid key version in {}", path.display()))
+}
+
+fn save_key_version(secrets_dir: &Path, key_version: u32) -> Result<()> {
+    let path = secrets_dir.join(KEY_VERSION_FILENAME);
+    fs::write(&path, key_version.to_string())
+        .with_context(|| format!("Failed to save key version file {}", path.display()))
+}
+
+/// Deserialize the input straight from the shared memory file the host wrote it to, without
+/// copying it through a pipe first. The file is outside of the enclave and the host can change
+/// it at any time, so it's copied into the enclave once before any of it is read.
+fn map_input(path: &Path) -> Result<GuestInput> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open the input file {}", path.display()))?;
+    // Safety: the map is only read by the copy, nothing references it afterwards.
+    let bytes = unsafe { Mmap::map(&file) }
+        .with_context(|| format!("Failed to map the input file {}", path.display()))?
+        .to_vec();
+    #[cfg(feature = "zero-copy")]
+    if raiko_lib::input::zero_copy::is_encoded(&bytes) {
+        return raiko_lib::input::zero_copy::decode(&bytes).context("unable to decode input");
+    }
+    bincode::deserialize(&bytes).context("unable to deserialize input")
+}
+
 pub async fn one_shot(global_opts: GlobalOpts, args: OneShotArgs) -> Result<()> {
     // Make sure this SGX instance was bootstrapped
     let prev_privkey = load_bootstrap(&global_opts.secrets_dir)
@@ -121,7 +245,13 @@ pub async fn one_shot(global_opts: GlobalOpts, args: OneShotArgs) -> Result<()>
     let new_pubkey = public_key(&prev_privkey);
     let new_instance = public_key_to_address(&new_pubkey);
 
-    let input = bincode::deserialize_from(std::io::stdin()).expect("unable to deserialize input");
+    let input = match &args.input_path {
+        Some(path) => map_input(path)?,
+        None => bincode::deserialize_from(std::io::stdin()).expect("unable to deserialize input"),
+    };
+
+    // The chain spec comes with the input, only the rules of the built-in chains are trusted.
+    verify_chain_spec(input.network, &input.chain_spec)?;
 
     // Process the block
     let (header, _mpt_node) =
@@ -142,7 +272,6 @@ pub async fn one_shot(global_opts: GlobalOpts, args: OneShotArgs) -> Result<()>
     let sig = sign_message(&prev_privkey, pi_hash)?;
 
     // Create the proof for the onchain SGX verifier
-    const SGX_PROOF_LEN: usize = 89;
     let mut proof = Vec::with_capacity(SGX_PROOF_LEN);
     proof.extend(args.sgx_instance_id.to_be_bytes());
     proof.extend(new_instance);
@@ -150,7 +279,7 @@ pub async fn one_shot(global_opts: GlobalOpts, args: OneShotArgs) -> Result<()>
     let proof = hex::encode(proof);
 
     // Store the public key address in the attestation data
-    save_attestation_user_report_data(new_instance)?;
+    save_attestation_user_report_data(new_instance, None)?;
 
     // Print out the proof and updated public info
     let quote = get_sgx_quote()?;
@@ -166,6 +295,28 @@ pub async fn one_shot(global_opts: GlobalOpts, args: OneShotArgs) -> Result<()>
     print_sgx_info()
 }
 
+pub fn attest(global_opts: GlobalOpts, args: AttestArgs) -> Result<()> {
+    let privkey = load_bootstrap(&global_opts.secrets_dir)
+        .or_else(|_| bail!("Application was not bootstrapped or has a deprecated bootstrap."))?;
+    let nonce = args
+        .nonce
+        .map(|nonce| nonce.parse::<B256>())
+        .transpose()
+        .context("Invalid nonce")?;
+
+    let pubkey = public_key(&privkey);
+    let instance = public_key_to_address(&pubkey);
+    save_attestation_user_report_data(instance, nonce)?;
+    let quote = get_sgx_quote()?;
+    let data = serde_json::json!({
+        "quote": hex::encode(quote),
+        "public_key": format!("0x{pubkey}"),
+        "instance_address": instance.to_string(),
+    });
+    println!("{data}");
+    Ok(())
+}
+
 pub fn load_bootstrap(secrets_dir: &Path) -> Result<SecretKey, BootStrapError> {
     let privkey_path = secrets_dir.join(PRIV_KEY_FILENAME);
     if privkey_path.is_file() && !privkey_path.metadata().unwrap().permissions().readonly() {
@@ -182,9 +333,12 @@ pub fn load_bootstrap(secrets_dir: &Path) -> Result<SecretKey, BootStrapError> {
     }
 }
 
-fn save_attestation_user_report_data(pubkey: Address) -> Result<()> {
+/// Store the address of the public key in the report data of the next quote, followed by the
+/// nonce in the second half of the report data if given.
+fn save_attestation_user_report_data(pubkey: Address, nonce: Option<B256>) -> Result<()> {
     let mut extended_pubkey = pubkey.to_vec();
-    extended_pubkey.resize(64, 0);
+    extended_pubkey.resize(32, 0);
+    extended_pubkey.extend(nonce.unwrap_or_default());
     let mut user_report_data_file = OpenOptions::new()
         .write(true)
         .open(ATTESTATION_USER_REPORT_DATA_DEVICE_FILE)?;
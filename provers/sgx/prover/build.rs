@@ -0,0 +1,19 @@
+fn main() {
+    // The enclave shim service, built from the message types in `src/shim.rs` so no protoc is
+    // needed.
+    let service = tonic_build::manual::Service::builder()
+        .name("Enclave")
+        .package("raiko.sgx")
+        .method(
+            tonic_build::manual::Method::builder()
+                .name("prove")
+                .route_name("Prove")
+                .input_type("crate::shim::ProveRequest")
+                .output_type("crate::shim::ProveResponse")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
+        .build();
+
+    tonic_build::manual::Builder::new().compile(&[service]);
+}
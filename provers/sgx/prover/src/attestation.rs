@@ -0,0 +1,650 @@
+use std::fmt;
+
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use raiko_lib::prover::{ProverError, ProverResult};
+use raiko_primitives::{hex, Address, B256};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::process::Command;
+use x509_parser::{
+    der_parser::{ber::BerObject, der::parse_der},
+    pem::Pem,
+    prelude::X509Certificate,
+};
+
+use crate::{
+    collateral::{get_collateral, now, Collateral},
+    gramine_command, handle_gramine_error, handle_output, is_direct_mode, working_dir,
+};
+
+/// The Intel Provisioning Certification Service, used when no PCCS is configured.
+pub const INTEL_PCS_URL: &str = "https://api.trustedservices.intel.com";
+
+/// The public key of the Intel SGX Root CA, which all PCK certificates and collateral chain up to.
+const INTEL_ROOT_CA_PUBLIC_KEY: [u8; 64] = hex!(
+    "0ba9c4c0c0c86193a3fe23d6b02cda10a8bbd4e88e48b4458561a36e705525f5"
+    "67918e2edc88e40d860bd0cc4ee26aacc988e505a953558c453f6b0904ae7394"
+);
+
+const HEADER_LEN: usize = 48;
+const REPORT_LEN: usize = 384;
+const SIGNATURE_LEN: usize = 64;
+const ATTESTATION_KEY_LEN: usize = 64;
+/// Certification data type of a PEM encoded PCK certificate chain.
+const PCK_CERT_CHAIN_TYPE: u16 = 5;
+
+const SGX_EXTENSIONS_OID: &str = "1.2.840.113741.1.13.1";
+const TCB_OID: &str = "1.2.840.113741.1.13.1.2";
+const PCE_SVN_OID: &str = "1.2.840.113741.1.13.1.2.17";
+const FMSPC_OID: &str = "1.2.840.113741.1.13.1.4";
+/// The TCB status of revoked platforms and quoting enclaves.
+const REVOKED: &str = "Revoked";
+
+/// A fresh quote of the enclave, attesting the public key it signs proofs with.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SgxAttestation {
+    /// The hex encoded DCAP quote.
+    pub quote: String,
+    /// The public key of the enclave.
+    pub public_key: String,
+    /// The instance address derived from the public key, included in the report data.
+    pub instance_address: Address,
+    /// The nonce included in the report data.
+    #[serde(default)]
+    pub nonce: Option<B256>,
+}
+
+/// The attested enclave of a verified quote.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuoteVerification {
+    pub mr_enclave: B256,
+    pub mr_signer: B256,
+    pub isv_prod_id: u16,
+    pub isv_svn: u16,
+    /// Whether the enclave runs in debug mode, in which case its memory can be read.
+    pub debug: bool,
+    /// The instance address in the first half of the report data.
+    pub instance_address: Address,
+    /// The nonce in the second half of the report data.
+    pub nonce: B256,
+    /// The FMSPC of the platform, from the PCK certificate.
+    pub fmspc: String,
+    /// The TCB status of the platform, e.g. `UpToDate` or `OutOfDate`.
+    pub tcb_status: String,
+    /// The TCB status of the quoting enclave.
+    pub qe_tcb_status: String,
+}
+
+#[derive(Debug)]
+pub enum QuoteError {
+    /// The quote is malformed or its signatures don't verify.
+    Invalid(String),
+    /// The collateral could not be fetched from the PCCS or doesn't verify.
+    Collateral(String),
+}
+
+impl fmt::Display for QuoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuoteError::Invalid(e) => write!(f, "Invalid quote: {e}"),
+            QuoteError::Collateral(e) => write!(f, "Invalid collateral: {e}"),
+        }
+    }
+}
+
+/// Produce a fresh quote for the bootstrapped key of the enclave, with the nonce in its report
+/// data.
+pub async fn attest(nonce: Option<B256>) -> ProverResult<SgxAttestation, ProverError> {
+    let mut cmd = Command::from(gramine_command(&working_dir(), is_direct_mode()));
+    cmd.arg("attest");
+    if let Some(nonce) = nonce {
+        cmd.arg("--nonce").arg(nonce.to_string());
+    }
+    let output = cmd
+        .output()
+        .await
+        .map_err(|e| handle_gramine_error("Could not run SGX guest attestation", e))?;
+    handle_output(&output, "SGX attest")?;
+
+    let output = String::from_utf8(output.stdout).map_err(|e| e.to_string())?;
+    let mut attestation = output
+        .lines()
+        .find_map(|line| serde_json::from_str::<SgxAttestation>(line.trim()).ok())
+        .ok_or_else(|| "SGX guest did not output an attestation".to_string())?;
+    attestation.nonce = nonce;
+    Ok(attestation)
+}
+
+/// Verify a DCAP quote against the collateral of the PCCS at `pccs_url`.
+///
+/// Checks the signatures of the quote and its PCK certificate chain up to the Intel SGX Root
+/// CA, and looks up the TCB status of the platform and the quoting enclave.
+pub async fn verify_quote(
+    quote: &[u8],
+    pccs_url: &str,
+) -> ProverResult<QuoteVerification, QuoteError> {
+    let quote = Quote::parse(quote).map_err(QuoteError::Invalid)?;
+    let pck = quote.verify().map_err(QuoteError::Invalid)?;
+
+    let collateral = async {
+        let client = reqwest::Client::new();
+        let tcb_info: TcbInfo =
+            get_collateral(&client, pccs_url, &Collateral::TcbInfo(pck.fmspc.clone()))
+                .await?
+                .verify_signed("tcbInfo")?;
+        let qe_identity: EnclaveIdentity =
+            get_collateral(&client, pccs_url, &Collateral::QeIdentity)
+                .await?
+                .verify_signed("enclaveIdentity")?;
+        let pck_crl = get_collateral(&client, pccs_url, &Collateral::PckCrl(pck.ca))
+            .await?
+            .verify_crl(&pck.root)?;
+        let root_ca_crl = get_collateral(&client, pccs_url, &Collateral::RootCaCrl)
+            .await?
+            .verify_crl(&pck.root)?;
+        Ok::<_, String>((tcb_info, qe_identity, pck_crl, root_ca_crl))
+    };
+    let (tcb_info, qe_identity, pck_crl, root_ca_crl) =
+        collateral.await.map_err(QuoteError::Collateral)?;
+    check_next_update("TCB info", &tcb_info.next_update)?;
+    check_next_update("QE identity", &qe_identity.next_update)?;
+
+    if pck_crl.contains(&pck.serial) {
+        return Err(QuoteError::Invalid(
+            "PCK certificate is revoked".to_string(),
+        ));
+    }
+    if root_ca_crl.contains(&pck.ca_serial) {
+        return Err(QuoteError::Invalid(
+            "PCK CA certificate is revoked".to_string(),
+        ));
+    }
+
+    if !tcb_info.fmspc.eq_ignore_ascii_case(&pck.fmspc) {
+        return Err(QuoteError::Collateral(format!(
+            "TCB info is for FMSPC {} instead of {}",
+            tcb_info.fmspc, pck.fmspc
+        )));
+    }
+    let tcb_status = tcb_info
+        .tcb_levels
+        .iter()
+        .find(|level| level.matches(&pck))
+        .map(|level| level.tcb_status.clone())
+        .ok_or_else(|| QuoteError::Invalid("TCB level of the platform is unknown".to_string()))?;
+
+    let qe_report = Report(quote.qe_report);
+    if hex::decode(&qe_identity.mrsigner).ok().as_deref() != Some(qe_report.mr_signer().as_slice())
+        || qe_identity.isvprodid != qe_report.isv_prod_id()
+    {
+        return Err(QuoteError::Invalid(
+            "Quote was not signed by the Intel quoting enclave".to_string(),
+        ));
+    }
+    let qe_tcb_status = qe_identity
+        .tcb_levels
+        .iter()
+        .find(|level| qe_report.isv_svn() >= level.tcb.isvsvn)
+        .map(|level| level.tcb_status.clone())
+        .unwrap_or_else(|| REVOKED.to_string());
+
+    let report = Report(quote.report);
+    let report_data = report.report_data();
+    let verification = QuoteVerification {
+        mr_enclave: report.mr_enclave(),
+        mr_signer: report.mr_signer(),
+        isv_prod_id: report.isv_prod_id(),
+        isv_svn: report.isv_svn(),
+        debug: report.is_debug(),
+        instance_address: Address::from_slice(&report_data[..20]),
+        nonce: B256::from_slice(&report_data[32..]),
+        fmspc: pck.fmspc,
+        tcb_status,
+        qe_tcb_status,
+    };
+    check_trusted(&verification)?;
+    Ok(verification)
+}
+
+/// Reject the quotes of enclaves whose signatures can't be trusted: debug enclaves, whose memory
+/// can be read, and enclaves on revoked platforms or attested by a revoked quoting enclave.
+fn check_trusted(verification: &QuoteVerification) -> Result<(), QuoteError> {
+    if verification.debug {
+        return Err(QuoteError::Invalid(
+            "Enclave runs in debug mode".to_string(),
+        ));
+    }
+    if verification.tcb_status == REVOKED || verification.qe_tcb_status == REVOKED {
+        return Err(QuoteError::Invalid(format!(
+            "TCB is revoked, platform {}, quoting enclave {}",
+            verification.tcb_status, verification.qe_tcb_status
+        )));
+    }
+    Ok(())
+}
+
+/// Reject collateral past its next update, the TCB statuses in it may have changed since.
+fn check_next_update(name: &str, next_update: &str) -> Result<(), QuoteError> {
+    let expiry = chrono::DateTime::parse_from_rfc3339(next_update)
+        .map_err(|e| QuoteError::Collateral(format!("Invalid next update of the {name}: {e}")))?;
+    if expiry.timestamp() < now() as i64 {
+        return Err(QuoteError::Collateral(format!(
+            "The {name} expired at {next_update}, refresh the collateral"
+        )));
+    }
+    Ok(())
+}
+
+/// The FMSPC of the platform that generated the quote.
+pub(crate) fn quote_fmspc(quote: &[u8]) -> Result<String, QuoteError> {
+    let quote = Quote::parse(quote).map_err(QuoteError::Invalid)?;
+    Ok(quote.verify().map_err(QuoteError::Invalid)?.fmspc)
+}
+
+/// The MRENCLAVE and the MRSIGNER of the enclave of a quote, without verifying the quote, e.g.
+/// of a quote of this host.
+pub fn quote_measurement(quote: &[u8]) -> Result<(B256, B256), QuoteError> {
+    let report = Report(Quote::parse(quote).map_err(QuoteError::Invalid)?.report);
+    Ok((report.mr_enclave(), report.mr_signer()))
+}
+
+/// A version 3 ECDSA quote, borrowing the raw quote.
+struct Quote<'a> {
+    /// The header and the enclave report, signed by the attestation key.
+    body: &'a [u8],
+    report: &'a [u8],
+    signature: &'a [u8],
+    attestation_key: &'a [u8],
+    qe_report: &'a [u8],
+    qe_report_signature: &'a [u8],
+    qe_auth_data: &'a [u8],
+    pck_cert_chain: &'a [u8],
+}
+
+impl<'a> Quote<'a> {
+    fn parse(quote: &'a [u8]) -> Result<Self, String> {
+        let mut reader = Reader { data: quote };
+        let header = reader.take(HEADER_LEN)?;
+        let version = u16::from_le_bytes([header[0], header[1]]);
+        if version != 3 {
+            return Err(format!("Unsupported quote version {version}"));
+        }
+        let report = reader.take(REPORT_LEN)?;
+        let body = &quote[..HEADER_LEN + REPORT_LEN];
+        // The length of the signature data
+        reader.take(4)?;
+        let signature = reader.take(SIGNATURE_LEN)?;
+        let attestation_key = reader.take(ATTESTATION_KEY_LEN)?;
+        let qe_report = reader.take(REPORT_LEN)?;
+        let qe_report_signature = reader.take(SIGNATURE_LEN)?;
+        let qe_auth_data_len = reader.u16()?;
+        let qe_auth_data = reader.take(qe_auth_data_len as usize)?;
+        let cert_type = reader.u16()?;
+        if cert_type != PCK_CERT_CHAIN_TYPE {
+            return Err(format!("Unsupported certification data type {cert_type}"));
+        }
+        let cert_len = reader.u32()?;
+        let pck_cert_chain = reader.take(cert_len as usize)?;
+
+        Ok(Self {
+            body,
+            report,
+            signature,
+            attestation_key,
+            qe_report,
+            qe_report_signature,
+            qe_auth_data,
+            pck_cert_chain,
+        })
+    }
+
+    /// Verify the signatures of the quote, returning the platform info of the PCK certificate.
+    fn verify(&self) -> Result<PckInfo, String> {
+        let chain = parse_pem_chain(self.pck_cert_chain)?;
+        let certs = parse_certs(&chain)?;
+        verify_cert_chain(&certs)?;
+        let pck = &certs[0];
+
+        verify_signature(
+            &pck.public_key().subject_public_key.data,
+            self.qe_report,
+            self.qe_report_signature,
+        )
+        .map_err(|e| format!("QE report: {e}"))?;
+
+        // The quoting enclave binds the attestation key to its report
+        let key_hash = Sha256::new()
+            .chain_update(self.attestation_key)
+            .chain_update(self.qe_auth_data)
+            .finalize();
+        if Report(self.qe_report).report_data()[..32] != key_hash[..] {
+            return Err("Attestation key does not match the QE report".to_string());
+        }
+
+        let mut attestation_key = vec![0x04];
+        attestation_key.extend(self.attestation_key);
+        verify_signature(&attestation_key, self.body, self.signature)
+            .map_err(|e| format!("Enclave report: {e}"))?;
+
+        PckInfo::from_chain(&certs, &chain[chain.len() - 1].contents)
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.data.len() < len {
+            return Err("Quote is too short".to_string());
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+/// An enclave report of a quote.
+struct Report<'a>(&'a [u8]);
+
+impl Report<'_> {
+    fn is_debug(&self) -> bool {
+        self.0[48] & 0x02 != 0
+    }
+
+    fn mr_enclave(&self) -> B256 {
+        B256::from_slice(&self.0[64..96])
+    }
+
+    fn mr_signer(&self) -> B256 {
+        B256::from_slice(&self.0[128..160])
+    }
+
+    fn isv_prod_id(&self) -> u16 {
+        u16::from_le_bytes([self.0[256], self.0[257]])
+    }
+
+    fn isv_svn(&self) -> u16 {
+        u16::from_le_bytes([self.0[258], self.0[259]])
+    }
+
+    fn report_data(&self) -> &[u8] {
+        &self.0[320..384]
+    }
+}
+
+/// The platform info in the SGX extensions of a PCK certificate, and the certificates to check
+/// for revocation.
+struct PckInfo {
+    fmspc: String,
+    cpu_svn: [u8; 16],
+    pce_svn: u16,
+    /// The serial number of the PCK certificate.
+    serial: Vec<u8>,
+    /// The PCK CA that issued the certificate, `processor` or `platform`.
+    ca: &'static str,
+    ca_serial: Vec<u8>,
+    /// The DER encoded Intel SGX Root CA certificate.
+    root: Vec<u8>,
+}
+
+impl PckInfo {
+    fn from_chain(certs: &[X509Certificate], root: &[u8]) -> Result<Self, String> {
+        let [pck, ca, ..] = certs else {
+            return Err("PCK certificate chain has no CA".to_string());
+        };
+        let extension = pck
+            .extensions()
+            .iter()
+            .find(|extension| extension.oid.to_id_string() == SGX_EXTENSIONS_OID)
+            .ok_or_else(|| "PCK certificate has no SGX extensions".to_string())?;
+        let (_, extensions) = parse_der(extension.value).map_err(|e| e.to_string())?;
+
+        let mut fmspc = None;
+        let mut cpu_svn = [0; 16];
+        let mut pce_svn = None;
+        for (oid, value) in oid_values(&extensions)? {
+            match oid.as_str() {
+                FMSPC_OID => {
+                    let value = value.as_slice().map_err(|e| e.to_string())?;
+                    fmspc = Some(hex::encode_upper(value));
+                }
+                TCB_OID => {
+                    for (oid, value) in oid_values(value)? {
+                        let svn = value.as_u32().map_err(|e| e.to_string())?;
+                        if oid == PCE_SVN_OID {
+                            pce_svn = Some(svn as u16);
+                        } else if let Some(component) = oid
+                            .strip_prefix(TCB_OID)
+                            .and_then(|index| index.strip_prefix('.')?.parse::<usize>().ok())
+                            .filter(|index| (1..=16).contains(index))
+                        {
+                            cpu_svn[component - 1] = svn as u8;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            fmspc: fmspc.ok_or_else(|| "PCK certificate has no FMSPC".to_string())?,
+            cpu_svn,
+            pce_svn: pce_svn.ok_or_else(|| "PCK certificate has no PCESVN".to_string())?,
+            serial: pck.raw_serial().to_vec(),
+            ca: if ca.subject().to_string().contains("Platform") {
+                "platform"
+            } else {
+                "processor"
+            },
+            ca_serial: ca.raw_serial().to_vec(),
+            root: root.to_vec(),
+        })
+    }
+}
+
+/// The `(OID, value)` pairs of a DER sequence.
+fn oid_values<'a, 'b>(
+    sequence: &'b BerObject<'a>,
+) -> Result<Vec<(String, &'b BerObject<'a>)>, String> {
+    sequence
+        .as_sequence()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(
+            |pair| match pair.as_sequence().map_err(|e| e.to_string())?.as_slice() {
+                [oid, value] => Ok((
+                    oid.as_oid().map_err(|e| e.to_string())?.to_id_string(),
+                    value,
+                )),
+                _ => Err("Malformed SGX extension".to_string()),
+            },
+        )
+        .collect()
+}
+
+pub(crate) fn parse_pem_chain(chain: &[u8]) -> Result<Vec<Pem>, String> {
+    // The certification data is zero terminated
+    let chain = chain
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(&chain[..0], |end| &chain[..=end]);
+    Pem::iter_from_buffer(chain)
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Invalid certificate chain: {e}"))
+}
+
+pub(crate) fn parse_certs(chain: &[Pem]) -> Result<Vec<X509Certificate<'_>>, String> {
+    chain
+        .iter()
+        .map(|pem| {
+            pem.parse_x509()
+                .map_err(|e| format!("Invalid certificate: {e}"))
+        })
+        .collect()
+}
+
+/// Verify that each certificate is valid and issued by the next one, up to the Intel SGX Root
+/// CA.
+pub(crate) fn verify_cert_chain(certs: &[X509Certificate]) -> Result<(), String> {
+    let root = certs
+        .last()
+        .ok_or_else(|| "Empty certificate chain".to_string())?;
+    if root.public_key().subject_public_key.data[1..] != INTEL_ROOT_CA_PUBLIC_KEY {
+        return Err("Certificate chain is not rooted in the Intel SGX Root CA".to_string());
+    }
+    for (i, cert) in certs.iter().enumerate() {
+        if !cert.validity().is_valid() {
+            return Err(format!("Certificate {} is expired", cert.subject()));
+        }
+        let issuer = certs.get(i + 1).unwrap_or(root);
+        cert.verify_signature(Some(issuer.public_key()))
+            .map_err(|e| {
+                format!(
+                    "Certificate {} is not signed by its issuer: {e}",
+                    cert.subject()
+                )
+            })?;
+    }
+    Ok(())
+}
+
+/// Verify a raw `r || s` ECDSA P-256 signature with a SEC1 encoded public key.
+pub(crate) fn verify_signature(
+    public_key: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), String> {
+    let public_key = VerifyingKey::from_sec1_bytes(public_key).map_err(|e| e.to_string())?;
+    let signature = Signature::from_slice(signature).map_err(|e| e.to_string())?;
+    public_key
+        .verify(message, &signature)
+        .map_err(|_| "signature verification failed".to_string())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TcbInfo {
+    fmspc: String,
+    next_update: String,
+    tcb_levels: Vec<TcbLevel>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TcbLevel {
+    tcb: Tcb,
+    tcb_status: String,
+}
+
+impl TcbLevel {
+    /// Whether the platform is at least at this TCB level.
+    fn matches(&self, pck: &PckInfo) -> bool {
+        pck.pce_svn >= self.tcb.pcesvn
+            && self.tcb.sgxtcbcomponents.len() == pck.cpu_svn.len()
+            && pck
+                .cpu_svn
+                .iter()
+                .zip(&self.tcb.sgxtcbcomponents)
+                .all(|(svn, component)| *svn >= component.svn)
+    }
+}
+
+#[derive(Deserialize)]
+struct Tcb {
+    sgxtcbcomponents: Vec<TcbComponent>,
+    pcesvn: u16,
+}
+
+#[derive(Deserialize)]
+struct TcbComponent {
+    svn: u8,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EnclaveIdentity {
+    mrsigner: String,
+    next_update: String,
+    isvprodid: u16,
+    tcb_levels: Vec<EnclaveTcbLevel>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EnclaveTcbLevel {
+    tcb: EnclaveTcb,
+    tcb_status: String,
+}
+
+#[derive(Deserialize)]
+struct EnclaveTcb {
+    isvsvn: u16,
+}
+
+#[cfg(test)]
+mod test {
+    use raiko_primitives::address;
+
+    use super::*;
+    use crate::sgx_register_utils::test::SAMPLE_QUOTE;
+
+    #[test]
+    fn test_verify_quote_signatures() {
+        let quote = hex::decode(SAMPLE_QUOTE[0]).unwrap();
+        let quote = Quote::parse(&quote).unwrap();
+        let report = Report(quote.report);
+        assert_eq!(
+            Address::from_slice(&report.report_data()[..20]),
+            address!("f335b69bdd6963daccd7c4b09f296245366ee344")
+        );
+        assert!(!report.is_debug());
+
+        let pck = quote.verify().unwrap();
+        assert_eq!(pck.fmspc, "00606A000000");
+    }
+
+    #[test]
+    fn test_check_trusted() {
+        let mut verification = QuoteVerification {
+            mr_enclave: B256::ZERO,
+            mr_signer: B256::ZERO,
+            isv_prod_id: 0,
+            isv_svn: 0,
+            debug: false,
+            instance_address: Address::ZERO,
+            nonce: B256::ZERO,
+            fmspc: "00606A000000".to_string(),
+            tcb_status: "UpToDate".to_string(),
+            qe_tcb_status: "UpToDate".to_string(),
+        };
+        assert!(check_trusted(&verification).is_ok());
+        verification.qe_tcb_status = REVOKED.to_string();
+        assert!(check_trusted(&verification).is_err());
+        verification.qe_tcb_status = "UpToDate".to_string();
+        verification.debug = true;
+        assert!(check_trusted(&verification).is_err());
+
+        assert!(check_next_update("TCB info", "2099-01-01T00:00:00Z").is_ok());
+        assert!(check_next_update("TCB info", "2020-01-01T00:00:00Z").is_err());
+    }
+
+    #[test]
+    fn test_reject_tampered_quote() {
+        let mut quote = hex::decode(SAMPLE_QUOTE[0]).unwrap();
+        // Change the instance address in the report data
+        quote[HEADER_LEN + 320] ^= 1;
+        assert!(Quote::parse(&quote).unwrap().verify().is_err());
+    }
+}
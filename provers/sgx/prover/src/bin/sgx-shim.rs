@@ -0,0 +1,29 @@
+use std::net::SocketAddr;
+
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+/// Serve the SGX enclave of this machine to raiko hosts on other machines.
+///
+/// Run it from the directory of the `sgx-guest` binary, with the same `SGX_DIRECT` setting a
+/// local host would use.
+struct Opts {
+    /// The address to serve the enclave on. Bind a public address only on a private network.
+    #[arg(long, require_equals = true, default_value = "127.0.0.1:50051")]
+    address: SocketAddr,
+
+    /// The token hosts authenticate with, the `token` of the enclave in their config.
+    #[arg(
+        long,
+        require_equals = true,
+        env = "SGX_SHIM_TOKEN",
+        hide_env_values = true
+    )]
+    token: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    let opts = Opts::parse();
+    sgx_prover::serve_enclave(opts.address, opts.token).await
+}
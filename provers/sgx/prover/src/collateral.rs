@@ -0,0 +1,367 @@
+//! A cache of the PCCS collateral needed to verify quotes.
+//!
+//! The TCB info, QE identity and CRLs are stored in `config/collateral` as downloaded, with the
+//! issuer chain of their signature, and are verified each time they are used. Hosts without
+//! outbound internet can verify quotes with a cache refreshed by [refresh_collateral], or
+//! copied over from another host.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use percent_encoding::percent_decode_str;
+use raiko_lib::prover::{ProverError, ProverResult};
+use raiko_primitives::hex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::value::RawValue;
+use x509_parser::{parse_x509_certificate, parse_x509_crl, pem::Pem};
+
+use crate::{
+    attestation::{
+        attest, parse_certs, parse_pem_chain, quote_fmspc, verify_cert_chain, verify_signature,
+        QuoteError, INTEL_PCS_URL,
+    },
+    working_dir,
+};
+
+/// The Intel SGX Root CA CRL, which the Intel PCS does not serve itself.
+const INTEL_ROOT_CA_CRL_URL: &str =
+    "https://certificates.trustedservices.intel.com/IntelSGXRootCA.der";
+/// Cached collateral younger than this is used without checking the PCCS for updates.
+const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A collateral object of the PCCS.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Collateral {
+    /// The TCB levels of the platforms with the FMSPC.
+    TcbInfo(String),
+    QeIdentity,
+    /// The CRL of the `processor` or `platform` PCK CA.
+    PckCrl(&'static str),
+    RootCaCrl,
+}
+
+impl Collateral {
+    fn url(&self, pccs_url: &str) -> String {
+        let pccs_url = pccs_url.trim_end_matches('/');
+        match self {
+            Collateral::TcbInfo(fmspc) => {
+                format!("{pccs_url}/sgx/certification/v4/tcb?fmspc={fmspc}")
+            }
+            Collateral::QeIdentity => format!("{pccs_url}/sgx/certification/v4/qe/identity"),
+            Collateral::PckCrl(ca) => {
+                format!("{pccs_url}/sgx/certification/v4/pckcrl?ca={ca}&encoding=pem")
+            }
+            Collateral::RootCaCrl if pccs_url == INTEL_PCS_URL => INTEL_ROOT_CA_CRL_URL.to_string(),
+            Collateral::RootCaCrl => format!("{pccs_url}/sgx/certification/v4/rootcacrl"),
+        }
+    }
+
+    /// The header with the issuer chain of the signature, the Root CA CRL is signed by the
+    /// pinned root itself.
+    fn issuer_chain_header(&self) -> Option<&'static str> {
+        match self {
+            Collateral::TcbInfo(_) => Some("TCB-Info-Issuer-Chain"),
+            Collateral::QeIdentity => Some("SGX-Enclave-Identity-Issuer-Chain"),
+            Collateral::PckCrl(_) => Some("SGX-PCK-CRL-Issuer-Chain"),
+            Collateral::RootCaCrl => None,
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Collateral::TcbInfo(fmspc) => format!("tcb-info-{fmspc}"),
+            Collateral::QeIdentity => "qe-identity".to_string(),
+            Collateral::PckCrl(ca) => format!("pck-crl-{ca}"),
+            Collateral::RootCaCrl => "root-ca-crl".to_string(),
+        }
+    }
+}
+
+/// A downloaded collateral object, as stored in the cache.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct CachedCollateral {
+    pub url: String,
+    /// The URL decoded PEM issuer chain of the signature.
+    pub issuer_chain: Option<String>,
+    /// The response body, hex encoded if it is binary.
+    pub body: String,
+    /// Unix timestamp of the download.
+    pub fetched_at: u64,
+}
+
+impl CachedCollateral {
+    /// Parse the signed object in `field` of a JSON collateral, verifying its signature.
+    pub(crate) fn verify_signed<T: DeserializeOwned>(&self, field: &str) -> Result<T, String> {
+        // The signature covers the exact bytes of the signed object
+        let fields: HashMap<&str, &RawValue> =
+            serde_json::from_str(&self.body).map_err(|e| e.to_string())?;
+        let signed = fields
+            .get(field)
+            .ok_or_else(|| format!("Missing {field} from {}", self.url))?;
+        let signature: String = fields
+            .get("signature")
+            .and_then(|signature| serde_json::from_str(signature.get()).ok())
+            .ok_or_else(|| format!("Missing signature from {}", self.url))?;
+
+        let chain = self.issuer_chain()?;
+        let certs = parse_certs(&chain)?;
+        verify_cert_chain(&certs)?;
+        verify_signature(
+            &certs[0].public_key().subject_public_key.data,
+            signed.get().as_bytes(),
+            &hex::decode(signature).map_err(|e| e.to_string())?,
+        )
+        .map_err(|e| format!("{field}: {e}"))?;
+
+        serde_json::from_str(signed.get()).map_err(|e| e.to_string())
+    }
+
+    /// The serial numbers revoked by a CRL, verifying its signature against its issuer chain or
+    /// the DER encoded `root` CA certificate.
+    pub(crate) fn verify_crl(&self, root: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+        let der = if self.body.trim_start().starts_with("-----BEGIN") {
+            parse_pem_chain(self.body.as_bytes())?
+                .into_iter()
+                .next()
+                .ok_or_else(|| "Empty CRL".to_string())?
+                .contents
+        } else {
+            hex::decode(self.body.trim()).map_err(|e| format!("Invalid CRL: {e}"))?
+        };
+        let (_, crl) = parse_x509_crl(&der).map_err(|e| format!("Invalid CRL: {e}"))?;
+
+        let chain = self.issuer_chain()?;
+        let mut certs = parse_certs(&chain)?;
+        if certs.is_empty() {
+            let (_, root) =
+                parse_x509_certificate(root).map_err(|e| format!("Invalid root CA: {e}"))?;
+            certs.push(root);
+        }
+        verify_cert_chain(&certs)?;
+        crl.verify_signature(certs[0].public_key())
+            .map_err(|e| format!("CRL of {} is not signed by its issuer: {e}", crl.issuer()))?;
+        if let Some(next_update) = crl.next_update() {
+            if next_update.timestamp() < now() as i64 {
+                return Err(format!(
+                    "CRL of {} expired at {next_update}, refresh the collateral",
+                    crl.issuer()
+                ));
+            }
+        }
+
+        Ok(crl
+            .iter_revoked_certificates()
+            .map(|revoked| revoked.raw_serial().to_vec())
+            .collect())
+    }
+
+    fn issuer_chain(&self) -> Result<Vec<Pem>, String> {
+        match &self.issuer_chain {
+            Some(chain) => parse_pem_chain(chain.as_bytes()),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+/// A collateral object in the cache.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollateralEntry {
+    /// The name of the object, e.g. `tcb-info-00606A000000` or `pck-crl-platform`.
+    pub name: String,
+    pub url: String,
+    /// Unix timestamp of the download.
+    pub fetched_at: u64,
+}
+
+fn cache_dir() -> PathBuf {
+    working_dir().join("config").join("collateral")
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+fn load(collateral: &Collateral) -> Option<CachedCollateral> {
+    let path = cache_dir().join(format!("{}.json", collateral.name()));
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+fn store(collateral: &Collateral, cached: &CachedCollateral) -> Result<(), String> {
+    let dir = cache_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("Could not create {}: {e}", dir.display()))?;
+    let path = dir.join(format!("{}.json", collateral.name()));
+    let json = serde_json::to_string_pretty(cached).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| format!("Could not save {}: {e}", path.display()))
+}
+
+async fn download(
+    client: &reqwest::Client,
+    pccs_url: &str,
+    collateral: &Collateral,
+) -> Result<CachedCollateral, String> {
+    let url = collateral.url(pccs_url);
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Could not fetch {url}: {e}"))?;
+    let issuer_chain = match collateral.issuer_chain_header() {
+        Some(header) => {
+            let chain = response
+                .headers()
+                .get(header)
+                .and_then(|chain| chain.to_str().ok())
+                .ok_or_else(|| format!("Missing {header} header from {url}"))?;
+            Some(percent_decode_str(chain).decode_utf8_lossy().into_owned())
+        }
+        None => None,
+    };
+    let body = response.bytes().await.map_err(|e| e.to_string())?.to_vec();
+    let body = String::from_utf8(body).unwrap_or_else(|e| hex::encode(e.into_bytes()));
+
+    Ok(CachedCollateral {
+        url,
+        issuer_chain,
+        body,
+        fetched_at: now(),
+    })
+}
+
+/// Get a collateral object, from the cache if it is fresh and from the PCCS otherwise. Falls
+/// back to a stale cached object when the PCCS can't be reached.
+pub(crate) async fn get_collateral(
+    client: &reqwest::Client,
+    pccs_url: &str,
+    collateral: &Collateral,
+) -> Result<CachedCollateral, String> {
+    let cached = load(collateral);
+    if let Some(cached) = &cached {
+        if now().saturating_sub(cached.fetched_at) < CACHE_TTL.as_secs() {
+            return Ok(cached.clone());
+        }
+    }
+    match download(client, pccs_url, collateral).await {
+        Ok(downloaded) => {
+            if let Err(e) = store(collateral, &downloaded) {
+                println!("WARNING: could not cache {}: {e}", collateral.name());
+            }
+            Ok(downloaded)
+        }
+        Err(e) => match cached {
+            Some(cached) => {
+                println!(
+                    "WARNING: using the cached {} from {}: {e}",
+                    collateral.name(),
+                    cached.fetched_at
+                );
+                Ok(cached)
+            }
+            None => Err(e),
+        },
+    }
+}
+
+/// Download the collateral for the FMSPCs, and for the ones already in the cache, into the
+/// cache. With an empty cache and no FMSPCs, the FMSPC of the local platform is used.
+pub async fn refresh_collateral(
+    pccs_url: &str,
+    fmspcs: &[String],
+) -> ProverResult<Vec<CollateralEntry>, QuoteError> {
+    let mut fmspcs: Vec<String> = fmspcs.iter().map(|fmspc| fmspc.to_uppercase()).collect();
+    for entry in list_collateral().map_err(|e| QuoteError::Collateral(e.to_string()))? {
+        if let Some(fmspc) = entry.name.strip_prefix("tcb-info-") {
+            fmspcs.push(fmspc.to_string());
+        }
+    }
+    if fmspcs.is_empty() {
+        let attestation = attest(None)
+            .await
+            .map_err(|e| QuoteError::Collateral(format!("No FMSPC to refresh: {e}")))?;
+        let quote =
+            hex::decode(&attestation.quote).map_err(|e| QuoteError::Invalid(e.to_string()))?;
+        fmspcs.push(quote_fmspc(&quote)?);
+    }
+    fmspcs.sort();
+    fmspcs.dedup();
+
+    let client = reqwest::Client::new();
+    let mut collaterals = vec![
+        Collateral::QeIdentity,
+        Collateral::PckCrl("processor"),
+        Collateral::PckCrl("platform"),
+        Collateral::RootCaCrl,
+    ];
+    collaterals.extend(fmspcs.into_iter().map(Collateral::TcbInfo));
+    for collateral in &collaterals {
+        let downloaded = download(&client, pccs_url, collateral)
+            .await
+            .map_err(QuoteError::Collateral)?;
+        store(collateral, &downloaded).map_err(QuoteError::Collateral)?;
+    }
+
+    list_collateral().map_err(|e| QuoteError::Collateral(e.to_string()))
+}
+
+/// List the collateral objects in the cache.
+pub fn list_collateral() -> ProverResult<Vec<CollateralEntry>, ProverError> {
+    let dir = cache_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let entries = fs::read_dir(&dir)
+        .map_err(|e| ProverError::GuestError(format!("Could not read {}: {e}", dir.display())))?;
+    let mut collateral = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(name) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".json"))
+        else {
+            continue;
+        };
+        let Some(cached) = fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice::<CachedCollateral>(&data).ok())
+        else {
+            continue;
+        };
+        collateral.push(CollateralEntry {
+            name: name.to_string(),
+            url: cached.url,
+            fetched_at: cached.fetched_at,
+        });
+    }
+    collateral.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(collateral)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_collateral_urls() {
+        assert_eq!(
+            Collateral::TcbInfo("00606A000000".to_string()).url("http://pccs:8081/"),
+            "http://pccs:8081/sgx/certification/v4/tcb?fmspc=00606A000000"
+        );
+        assert_eq!(
+            Collateral::RootCaCrl.url(INTEL_PCS_URL),
+            INTEL_ROOT_CA_CRL_URL
+        );
+        assert_eq!(
+            Collateral::RootCaCrl.url("http://pccs:8081"),
+            "http://pccs:8081/sgx/certification/v4/rootcacrl"
+        );
+        assert_eq!(Collateral::PckCrl("platform").name(), "pck-crl-platform");
+    }
+}
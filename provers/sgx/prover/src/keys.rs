@@ -0,0 +1,216 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use raiko_lib::prover::{ProverError, ProverResult};
+use raiko_primitives::Address;
+use raiko_signer::RaikoSigner;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::{
+    gramine_command, handle_gramine_error, handle_output, is_direct_mode, register_sgx_instance,
+    working_dir,
+};
+
+/// The file the guest stores the details of the current key in.
+const BOOTSTRAP_INFO_FILENAME: &str = "bootstrap.json";
+/// The file with the instances registered for the keys of the enclave.
+const INSTANCES_FILENAME: &str = "instances.json";
+
+/// The current signing key of the enclave.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SgxKey {
+    /// The public key of the enclave.
+    pub public_key: String,
+    /// The instance address derived from the public key.
+    #[serde(alias = "new_instance")]
+    pub instance_address: Address,
+    /// The version of the key, bumped on every bootstrap and rotation. Keys bootstrapped
+    /// before keys were versioned are version 0.
+    #[serde(default)]
+    pub key_version: u32,
+    /// The hex encoded quote attesting the key when it was created.
+    pub quote: String,
+}
+
+/// An instance registered with the on-chain SGX verifier.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SgxInstance {
+    /// The id of the instance, used as `instance_id` in the SGX proofs.
+    pub instance_id: u64,
+    pub instance_address: Address,
+    pub key_version: u32,
+    /// The chain id of the L1 the verifier is deployed on.
+    pub chain_id: u64,
+    /// The address of the SGX verifier the instance is registered with.
+    pub verifier_address: Address,
+    /// Unix timestamp of the registration.
+    pub registered_at: u64,
+}
+
+fn config_dir() -> PathBuf {
+    working_dir().join("config")
+}
+
+/// Get the current key of the enclave, as saved by the last bootstrap or rotation.
+pub fn load_key() -> ProverResult<SgxKey, ProverError> {
+    let path = config_dir().join(BOOTSTRAP_INFO_FILENAME);
+    let data = fs::read(&path).map_err(|e| {
+        ProverError::GuestError(format!(
+            "SGX was not bootstrapped, could not read {}: {e}",
+            path.display()
+        ))
+    })?;
+    serde_json::from_slice(&data)
+        .map_err(|e| ProverError::GuestError(format!("Invalid {}: {e}", path.display())))
+}
+
+/// Generate a new key to replace the key of the enclave with.
+///
+/// The enclave keeps signing with its current key until the new one is activated with
+/// [activate_key], which should only be done once it's registered with [register_key].
+pub async fn rotate_key() -> ProverResult<SgxKey, ProverError> {
+    let output = Command::from(gramine_command(&working_dir(), is_direct_mode()))
+        .arg("rotate")
+        .output()
+        .await
+        .map_err(|e| handle_gramine_error("Could not run SGX guest key rotation", e))?;
+    handle_output(&output, "SGX rotate")?;
+
+    let output = String::from_utf8(output.stdout).map_err(|e| e.to_string())?;
+    let key = output
+        .lines()
+        .find_map(|line| serde_json::from_str::<SgxKey>(line.trim()).ok())
+        .ok_or_else(|| "SGX guest did not output the new key".to_string())?;
+    Ok(key)
+}
+
+/// Replace the key of the enclave with the key generated by the last [rotate_key].
+pub async fn activate_key() -> ProverResult<SgxKey, ProverError> {
+    let output = Command::from(gramine_command(&working_dir(), is_direct_mode()))
+        .arg("activate")
+        .output()
+        .await
+        .map_err(|e| handle_gramine_error("Could not run SGX guest key activation", e))?;
+    handle_output(&output, "SGX activate")?;
+    load_key()
+}
+
+/// Register the key with the SGX verifier at `verifier_address` and record the new instance.
+/// The registration is sent from the account of `signer`.
+pub async fn register_key(
+    key: &SgxKey,
+    l1_rpc: &str,
+    chain_id: u64,
+    verifier_address: Address,
+    signer: &RaikoSigner,
+) -> ProverResult<SgxInstance, ProverError> {
+    let instance_id = register_sgx_instance(&key.quote, l1_rpc, chain_id, verifier_address, signer)
+        .await
+        .map_err(|e| ProverError::GuestError(format!("Could not register SGX instance: {e}")))?;
+    let instance = SgxInstance {
+        instance_id,
+        instance_address: key.instance_address,
+        key_version: key.key_version,
+        chain_id,
+        verifier_address,
+        registered_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default(),
+    };
+    record_instance(instance.clone())?;
+    Ok(instance)
+}
+
+/// Record an instance registered for a key of the enclave.
+pub fn record_instance(instance: SgxInstance) -> ProverResult<(), ProverError> {
+    let mut instances = list_instances()?;
+    instances.retain(|registered| {
+        registered.instance_id != instance.instance_id
+            || registered.verifier_address != instance.verifier_address
+    });
+    instances.push(instance);
+    let path = config_dir().join(INSTANCES_FILENAME);
+    let json = serde_json::to_string_pretty(&instances).map_err(|e| e.to_string())?;
+    fs::write(&path, json)
+        .map_err(|e| ProverError::GuestError(format!("Could not save {}: {e}", path.display())))
+}
+
+/// List the instances registered for the keys of the enclave, oldest first.
+pub fn list_instances() -> ProverResult<Vec<SgxInstance>, ProverError> {
+    read_instances(&config_dir())
+}
+
+fn read_instances(config_dir: &Path) -> ProverResult<Vec<SgxInstance>, ProverError> {
+    let path = config_dir.join(INSTANCES_FILENAME);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let data = fs::read(&path)
+        .map_err(|e| ProverError::GuestError(format!("Could not read {}: {e}", path.display())))?;
+    serde_json::from_slice(&data)
+        .map_err(|e| ProverError::GuestError(format!("Invalid {}: {e}", path.display())))
+}
+
+/// The id of the latest instance registered for the current key, if any. Proofs signed after
+/// a rotation have to use the instance of the new key.
+pub(crate) fn current_instance_id(cur_dir: &Path) -> Option<u64> {
+    let config_dir = cur_dir.join("config");
+    let key: SgxKey =
+        serde_json::from_slice(&fs::read(config_dir.join(BOOTSTRAP_INFO_FILENAME)).ok()?).ok()?;
+    read_instances(&config_dir)
+        .ok()?
+        .into_iter()
+        .rev()
+        .find(|instance| instance.instance_address == key.instance_address)
+        .map(|instance| instance.instance_id)
+}
+
+#[cfg(test)]
+mod test {
+    use raiko_primitives::address;
+
+    use super::*;
+
+    #[test]
+    fn test_current_instance_id() {
+        let cur_dir = tempfile::tempdir().unwrap();
+        let config_dir = cur_dir.path().join("config");
+        fs::create_dir(&config_dir).unwrap();
+        assert_eq!(current_instance_id(cur_dir.path()), None);
+
+        // Bootstrapped before keys were versioned
+        let bootstrap = serde_json::json!({
+            "public_key": "0x02",
+            "new_instance": "0x1de1f05a31ef99d8bc600a99ce290eafec42b1ec",
+            "quote": "",
+        });
+        fs::write(
+            config_dir.join(BOOTSTRAP_INFO_FILENAME),
+            bootstrap.to_string(),
+        )
+        .unwrap();
+        let instance = |instance_id, instance_address, key_version| SgxInstance {
+            instance_id,
+            instance_address,
+            key_version,
+            chain_id: 17000,
+            verifier_address: Address::ZERO,
+            registered_at: 0,
+        };
+        let instances = vec![
+            instance(1, address!("1de1f05a31ef99d8bc600a99ce290eafec42b1ec"), 0),
+            instance(2, address!("f335b69bdd6963daccd7c4b09f296245366ee344"), 1),
+        ];
+        fs::write(
+            config_dir.join(INSTANCES_FILENAME),
+            serde_json::to_string(&instances).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(current_instance_id(cur_dir.path()), Some(1));
+    }
+}
@@ -1,31 +1,63 @@
 #![cfg(feature = "enable")]
 use std::{
     env,
-    fs::{copy, create_dir_all, remove_file},
+    fs::{copy, create_dir_all, remove_file, OpenOptions},
+    io::{BufWriter, Write},
+    os::unix::fs::OpenOptionsExt,
     path::{Path, PathBuf},
     process::{Command as StdCommand, Output, Stdio},
-    str,
+    sync::atomic::{AtomicU64, Ordering},
 };
 
 use alloy_sol_types::SolValue;
 use once_cell::sync::Lazy;
 use raiko_lib::{
     input::{GuestInput, GuestOutput},
-    protocol_instance::ProtocolInstance,
+    protocol_instance::{
+        assemble_protocol_instance, EvidenceType, ProtocolInstance, SGX_PROOF_LEN,
+    },
     prover::{to_proof, Proof, Prover, ProverConfig, ProverError, ProverResult},
 };
-use raiko_primitives::{keccak::keccak, B256};
+use raiko_primitives::{hex, keccak::keccak, Address, Signature, B256};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use serde_with::serde_as;
-use tokio::{process::Command, sync::OnceCell};
-
-pub use crate::sgx_register_utils::register_sgx_instance;
+use tokio::{io::AsyncWriteExt, process::Command, sync::OnceCell};
+use tracing::{info, warn, Span};
+
+pub use crate::{
+    attestation::{
+        attest, quote_measurement, verify_quote, QuoteError, QuoteVerification, SgxAttestation,
+        INTEL_PCS_URL,
+    },
+    collateral::{list_collateral, refresh_collateral, CollateralEntry},
+    keys::{
+        activate_key, list_instances, load_key, record_instance, register_key, rotate_key,
+        SgxInstance, SgxKey,
+    },
+    pool::{configure_enclaves, EnclaveConfig},
+    sgx_register_utils::register_sgx_instance,
+    shim::serve_enclave,
+};
+pub use raiko_signer::{RaikoSigner, SignerConfig};
 
 pub const PRIV_KEY_FILENAME: &str = "priv.key";
 
+/// The device of the in-kernel SGX driver the enclave runs on.
+pub const SGX_DEVICE: &str = "/dev/sgx_enclave";
+
+// to attest the enclave and verify quotes
+mod attestation;
+// to verify quotes without outbound internet
+mod collateral;
+// to rotate the enclave key and keep track of its instances
+mod keys;
+// to dispatch proofs to the least loaded enclave
+mod pool;
 // to register the instance id
 mod sgx_register_utils;
+// to serve an enclave to hosts on other machines
+mod shim;
 
 #[serde_as]
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -42,9 +74,16 @@ pub struct SgxResponse {
     /// proof format: 4b(id)+20b(pubkey)+65b(signature)
     pub proof: String,
     pub quote: String,
+    /// The instance id the proof was signed as.
+    pub instance_id: u64,
+    /// The enclave that generated the proof.
+    pub enclave: String,
 }
 
 pub const ELF_NAME: &str = "sgx-guest";
+
+/// The shared memory the input of a proof is handed to the guest in, mounted into the enclave.
+const INPUT_SHM_DIR: &str = "/dev/shm";
 pub const CONFIG: &str = if cfg!(feature = "docker_build") {
     "../provers/sgx/config"
 } else {
@@ -65,13 +104,10 @@ impl Prover for SgxProver {
         let sgx_param = SgxParam::deserialize(config.get("sgx").unwrap()).unwrap();
 
         // Support both SGX and the direct backend for testing
-        let direct_mode = match env::var("SGX_DIRECT") {
-            Ok(value) => value == "1",
-            Err(_) => false,
-        };
+        let direct_mode = is_direct_mode();
 
-        println!(
-            "WARNING: running SGX in {} mode!",
+        warn!(
+            "Running SGX in {} mode!",
             if direct_mode {
                 "direct (a.k.a. simulation)"
             } else {
@@ -80,12 +116,8 @@ impl Prover for SgxProver {
         );
 
         // The working directory
-        let cur_dir = env::current_exe()
-            .expect("Fail to get current directory")
-            .parent()
-            .unwrap()
-            .to_path_buf();
-        println!("Current directory: {cur_dir:?}\n");
+        let cur_dir = working_dir();
+        info!("Current directory: {cur_dir:?}");
         // Working paths
         PRIVATE_KEY
             .get_or_init(|| async { cur_dir.join("secrets").join(PRIV_KEY_FILENAME) })
@@ -98,18 +130,7 @@ impl Prover for SgxProver {
             })
             .await;
 
-        // The gramine command (gramine or gramine-direct for testing in non-SGX environment)
-        let gramine_cmd = || -> StdCommand {
-            let mut cmd = if direct_mode {
-                StdCommand::new("gramine-direct")
-            } else {
-                let mut cmd = StdCommand::new("sudo");
-                cmd.arg("gramine-sgx");
-                cmd
-            };
-            cmd.current_dir(&cur_dir).arg(ELF_NAME);
-            cmd
-        };
+        let gramine_cmd = || gramine_command(&cur_dir, direct_mode);
 
         // Setup: run this once while setting up your SGX instance
         if sgx_param.setup {
@@ -124,8 +145,15 @@ impl Prover for SgxProver {
         };
 
         if sgx_param.prove {
+            // The enclaves of the host, the least loaded one gets the proof.
+            let enclaves = pool::enclaves();
+            let (enclave, _load) = pool::acquire(&enclaves).expect("at least one enclave");
+            println!("Proving with SGX enclave {}", enclave.name());
             // overwrite sgx_proof as the bootstrap quote stays the same in bootstrap & prove.
-            sgx_proof = prove(gramine_cmd(), input.clone(), sgx_param.instance_id).await
+            sgx_proof = enclave
+                .prove(input.clone(), sgx_param.instance_id, &cur_dir, direct_mode)
+                .await
+                .map_err(ProverError::GuestError);
         }
 
         to_proof(sgx_proof)
@@ -144,6 +172,98 @@ impl Prover for SgxProver {
 
         keccak(data).into()
     }
+
+    fn verify(proof: &Proof, input: &GuestInput, output: &GuestOutput) -> Option<ProverResult<()>> {
+        let response = match SgxResponse::deserialize(proof) {
+            Ok(response) => response,
+            Err(e) => return Some(Err(format!("Invalid SGX proof: {e}").into())),
+        };
+        // Setup and bootstrap only requests have no proof.
+        if response.proof.is_empty() {
+            return None;
+        }
+        Some(verify_proof(&response.proof, input, output))
+    }
+}
+
+/// Recover the signer of the proof from the public input hash of the block, and check that
+/// it's the instance the proof claims and, if this host registered it, the instance of the id.
+fn verify_proof(proof: &str, input: &GuestInput, output: &GuestOutput) -> ProverResult<()> {
+    let GuestOutput::Success((header, _)) = output else {
+        return Err("Can not verify the SGX proof of a failed block"
+            .to_string()
+            .into());
+    };
+    let bytes = hex::decode(proof).map_err(|e| format!("Invalid SGX proof: {e}"))?;
+    if bytes.len() != SGX_PROOF_LEN {
+        return Err(format!(
+            "Invalid SGX proof: {} bytes instead of {SGX_PROOF_LEN}",
+            bytes.len()
+        )
+        .into());
+    }
+    let instance_id = u32::from_be_bytes(bytes[..4].try_into().unwrap());
+    let instance_address = Address::from_slice(&bytes[4..24]);
+    let signature =
+        Signature::try_from(&bytes[24..]).map_err(|e| format!("Invalid SGX signature: {e}"))?;
+
+    let pi = assemble_protocol_instance(input, &header.header)
+        .map_err(|e| format!("Could not assemble the protocol instance: {e}"))?;
+    let pi_hash = pi.instance_hash(EvidenceType::Sgx {
+        new_pubkey: instance_address,
+    });
+    let signer = signature
+        .recover_address_from_prehash(&pi_hash)
+        .map_err(|e| format!("Could not recover the SGX signer: {e}"))?;
+    if signer != instance_address {
+        return Err(format!(
+            "The SGX proof is signed by {signer} instead of instance {instance_address}"
+        )
+        .into());
+    }
+    if let Some(instance) = list_instances()?
+        .into_iter()
+        .find(|instance| instance.instance_id == instance_id as u64)
+    {
+        if instance.instance_address != signer {
+            return Err(format!(
+                "The SGX proof is signed by {signer} instead of instance {instance_id} ({})",
+                instance.instance_address
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Whether to run the guest with gramine-direct instead of on SGX, for testing.
+fn is_direct_mode() -> bool {
+    match env::var("SGX_DIRECT") {
+        Ok(value) => value == "1",
+        Err(_) => false,
+    }
+}
+
+/// The directory of the host binary, which holds the guest and its secrets.
+fn working_dir() -> PathBuf {
+    env::current_exe()
+        .expect("Fail to get current directory")
+        .parent()
+        .unwrap()
+        .to_path_buf()
+}
+
+/// The gramine command (gramine or gramine-direct for testing in non-SGX environment)
+fn gramine_command(cur_dir: &Path, direct_mode: bool) -> StdCommand {
+    let mut cmd = if direct_mode {
+        StdCommand::new("gramine-direct")
+    } else {
+        let mut cmd = StdCommand::new("sudo");
+        cmd.arg("gramine-sgx");
+        cmd
+    };
+    cmd.current_dir(cur_dir).arg(ELF_NAME);
+    cmd
 }
 
 async fn setup(cur_dir: &Path, direct_mode: bool) -> ProverResult<(), String> {
@@ -213,7 +333,9 @@ pub async fn check_bootstrap(
     secret_dir: PathBuf,
     mut gramine_cmd: StdCommand,
 ) -> ProverResult<(), ProverError> {
+    let span = Span::current();
     tokio::task::spawn_blocking(move || {
+        let _span = span.enter();
         // Check if the private key exists
         let path = secret_dir.join(PRIV_KEY_FILENAME);
         if !path.exists() {
@@ -236,17 +358,30 @@ pub async fn check_bootstrap(
     .map_err(|e| ProverError::GuestError(e.to_string()))?
 }
 
+/// Check the local enclave is up without running it: the SGX device is visible, except in
+/// direct mode, and the enclave was bootstrapped. Returns the current key of the enclave.
+pub fn check_enclave() -> ProverResult<SgxKey, ProverError> {
+    if !is_direct_mode() && !Path::new(SGX_DEVICE).exists() {
+        return Err(ProverError::GuestError(format!(
+            "The SGX device {SGX_DEVICE} is missing"
+        )));
+    }
+    load_key()
+}
+
 pub async fn bootstrap(
     secret_dir: PathBuf,
     mut gramine_cmd: StdCommand,
 ) -> ProverResult<SgxResponse, ProverError> {
+    let span = Span::current();
     tokio::task::spawn_blocking(move || {
+        let _span = span.enter();
         // Bootstrap with new private key for signing proofs
         // First delete the private key if it already exists
         let path = secret_dir.join(PRIV_KEY_FILENAME);
         if path.exists() {
             if let Err(e) = remove_file(&path) {
-                println!("Error deleting file: {e}");
+                warn!("Error deleting file: {e}");
             }
         }
         let output = gramine_cmd
@@ -262,31 +397,88 @@ pub async fn bootstrap(
 }
 
 async fn prove(
-    mut gramine_cmd: StdCommand,
-    input: GuestInput,
+    gramine_cmd: StdCommand,
+    mut input: GuestInput,
     instance_id: u64,
 ) -> ProverResult<SgxResponse, ProverError> {
-    tokio::task::spawn_blocking(move || {
-        let mut child = gramine_cmd
-            .arg("one-shot")
-            .arg("--sgx-instance-id")
-            .arg(instance_id.to_string())
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| format!("Could not spawn gramine cmd: {e}"))?;
-        let stdin = child.stdin.as_mut().expect("Failed to open stdin");
-        bincode::serialize_into(stdin, &input).expect("Unable to serialize input");
-
-        let output = child
-            .wait_with_output()
-            .map_err(|e| handle_gramine_error("Could not run SGX guest prover", e))?;
-        handle_output(&output, "SGX prove")?;
-        Ok(parse_sgx_result(output.stdout)?)
-    })
-    .await
-    .map_err(|e| ProverError::GuestError(e.to_string()))?
+    let mut cmd = Command::from(gramine_cmd);
+    cmd.arg("one-shot")
+        .arg("--sgx-instance-id")
+        .arg(instance_id.to_string());
+    // Hand the input over in shared memory, the guest maps it instead of reading a pipe.
+    let input_file = InputFile::write(&mut input)
+        .map_err(|e| warn!("Handing the input to the SGX guest over stdin: {e}"))
+        .ok();
+    match &input_file {
+        Some(file) => cmd.arg("--input-path").arg(&file.path).stdin(Stdio::null()),
+        None => cmd.stdin(Stdio::piped()),
+    };
+    // Kill the guest when the proof generation is cancelled.
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Could not spawn gramine cmd: {e}"))?;
+    if input_file.is_none() {
+        let input = bincode::serialize(&input).expect("Unable to serialize input");
+        let mut stdin = child.stdin.take().expect("Failed to open stdin");
+        stdin
+            .write_all(&input)
+            .await
+            .map_err(|e| handle_gramine_error("Could not write SGX guest input", e))?;
+        drop(stdin);
+    }
+
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| handle_gramine_error("Could not run SGX guest prover", e))?;
+    handle_output(&output, "SGX prove")?;
+    Ok(parse_sgx_result(output.stdout)?)
+}
+
+/// The input of a proof in a file in shared memory, removed once the guest is done with it.
+struct InputFile {
+    path: PathBuf,
+}
+
+impl InputFile {
+    /// Serialize the input into a new file, readable by the owner only, without buffering all of
+    /// it in memory first. With `zero-copy`, the guest reads the tries from an archive, see
+    /// [raiko_lib::input::zero_copy].
+    fn write(input: &mut GuestInput) -> std::io::Result<Self> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let file = Self {
+            path: Path::new(INPUT_SHM_DIR).join(format!(
+                "raiko-sgx-input-{}-{}",
+                std::process::id(),
+                NEXT_ID.fetch_add(1, Ordering::Relaxed)
+            )),
+        };
+        // Only the host and the guest, which runs as the same user, may read the input.
+        let mut writer = BufWriter::new(
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&file.path)?,
+        );
+        #[cfg(feature = "zero-copy")]
+        raiko_lib::input::zero_copy::encode(input, &mut writer)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        #[cfg(not(feature = "zero-copy"))]
+        bincode::serialize_into(&mut writer, input)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        writer.flush()?;
+        Ok(file)
+    }
+}
+
+impl Drop for InputFile {
+    fn drop(&mut self) {
+        let _ = remove_file(&self.path);
+    }
 }
 
 fn parse_sgx_result(output: Vec<u8>) -> ProverResult<SgxResponse, String> {
@@ -310,6 +502,7 @@ fn parse_sgx_result(output: Vec<u8>) -> ProverResult<SgxResponse, String> {
     Ok(SgxResponse {
         proof: extract_field("proof"),
         quote: extract_field("quote"),
+        ..Default::default()
     })
 }
 
@@ -321,9 +514,14 @@ fn handle_gramine_error(context: &str, err: std::io::Error) -> String {
     }
 }
 
+/// Log the output of the guest line by line, in the span of the request it runs for.
 fn handle_output(output: &Output, name: &str) -> ProverResult<(), String> {
-    println!("{name} stderr: {}", str::from_utf8(&output.stderr).unwrap());
-    println!("{name} stdout: {}", str::from_utf8(&output.stdout).unwrap());
+    for line in String::from_utf8_lossy(&output.stderr).lines() {
+        info!(target: "sgx_guest", stream = "stderr", "{name}: {line}");
+    }
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        info!(target: "sgx_guest", stream = "stdout", "{name}: {line}");
+    }
     if !output.status.success() {
         return Err(format!(
             "{name} encountered an error ({}): {}",
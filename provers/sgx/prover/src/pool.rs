@@ -0,0 +1,197 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+};
+
+use once_cell::sync::Lazy;
+use raiko_lib::{input::GuestInput, prover::ProverResult};
+use serde::{Deserialize, Serialize};
+use tonic::Request;
+
+use crate::{
+    gramine_command,
+    keys::current_instance_id,
+    prove,
+    shim::{EnclaveClient, ProveRequest, AUTHORIZATION, MAX_MESSAGE_SIZE},
+    SgxResponse,
+};
+
+/// An enclave of the SGX prover pool.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EnclaveConfig {
+    /// The gRPC endpoint of an enclave shim on another machine, e.g. `http://10.0.0.2:50051`.
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// The token of the shim of the endpoint, see [crate::serve_enclave].
+    #[serde(default, skip_serializing)]
+    pub token: Option<String>,
+    /// The directory of a local enclave with its own `sgx-guest`, secrets and config, e.g. one
+    /// per socket. Defaults to the directory of the host.
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    /// The instance id the enclave signs proofs as. Defaults to the instance registered for the
+    /// current key of the enclave, then to the `instance_id` param.
+    #[serde(default)]
+    pub instance_id: Option<u64>,
+}
+
+impl EnclaveConfig {
+    /// The name of the enclave, its endpoint or directory.
+    pub fn name(&self) -> String {
+        match (&self.endpoint, &self.dir) {
+            (Some(endpoint), _) => endpoint.clone(),
+            (None, Some(dir)) => dir.display().to_string(),
+            (None, None) => "local".to_string(),
+        }
+    }
+
+    /// Prove the block with the enclave.
+    pub(crate) async fn prove(
+        &self,
+        input: GuestInput,
+        default_instance_id: u64,
+        cur_dir: &Path,
+        direct_mode: bool,
+    ) -> ProverResult<SgxResponse, String> {
+        let response = match &self.endpoint {
+            Some(endpoint) => {
+                let token = self
+                    .token
+                    .as_deref()
+                    .ok_or_else(|| format!("SGX enclave {endpoint} has no token"))?;
+                let mut client = EnclaveClient::connect(endpoint.clone())
+                    .await
+                    .map_err(|e| format!("Could not connect to SGX enclave {endpoint}: {e}"))?
+                    .max_decoding_message_size(MAX_MESSAGE_SIZE)
+                    .max_encoding_message_size(MAX_MESSAGE_SIZE);
+                let input = bincode::serialize(&input).map_err(|e| e.to_string())?;
+                let mut request = Request::new(ProveRequest {
+                    input,
+                    instance_id: self.instance_id,
+                });
+                request.metadata_mut().insert(
+                    AUTHORIZATION,
+                    format!("Bearer {token}")
+                        .parse()
+                        .map_err(|_| format!("Invalid token of SGX enclave {endpoint}"))?,
+                );
+                let response = client
+                    .prove(request)
+                    .await
+                    .map_err(|e| format!("SGX enclave {endpoint} failed: {}", e.message()))?
+                    .into_inner();
+                SgxResponse {
+                    proof: response.proof,
+                    quote: response.quote,
+                    instance_id: response.instance_id,
+                    enclave: self.name(),
+                }
+            }
+            None => {
+                let dir = self.dir.as_deref().unwrap_or(cur_dir);
+                let instance_id = self
+                    .instance_id
+                    .or_else(|| current_instance_id(dir))
+                    .unwrap_or(default_instance_id);
+                let response = prove(gramine_command(dir, direct_mode), input, instance_id)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                SgxResponse {
+                    instance_id,
+                    enclave: self.name(),
+                    ..response
+                }
+            }
+        };
+        Ok(response)
+    }
+}
+
+/// The enclaves of the pool, see [configure_enclaves].
+static ENCLAVES: Lazy<RwLock<Vec<EnclaveConfig>>> = Lazy::new(Default::default);
+
+/// Set the enclaves to prove with from the config of the host. They run the `sgx-guest` of their
+/// directory and get the inputs of the proofs, so they are never taken from proof requests.
+/// Without enclaves, the proofs go to the local enclave.
+pub fn configure_enclaves(enclaves: Vec<EnclaveConfig>) {
+    *ENCLAVES.write().unwrap() = enclaves;
+}
+
+/// The enclaves to prove with, the local enclave if none are configured.
+pub(crate) fn enclaves() -> Vec<EnclaveConfig> {
+    let enclaves = ENCLAVES.read().unwrap().clone();
+    if enclaves.is_empty() {
+        vec![EnclaveConfig::default()]
+    } else {
+        enclaves
+    }
+}
+
+/// The number of proofs in flight per enclave, shared by all requests.
+static LOADS: Lazy<Mutex<HashMap<String, usize>>> = Lazy::new(Default::default);
+
+/// A proof in flight on an enclave, released when dropped.
+pub(crate) struct EnclaveLoad(String);
+
+impl Drop for EnclaveLoad {
+    fn drop(&mut self) {
+        let mut loads = LOADS.lock().unwrap();
+        if let Some(load) = loads.get_mut(&self.0) {
+            *load = load.saturating_sub(1);
+        }
+    }
+}
+
+/// Pick the least loaded of the enclaves, preferring the first one on a tie.
+pub(crate) fn acquire(enclaves: &[EnclaveConfig]) -> Option<(&EnclaveConfig, EnclaveLoad)> {
+    let mut loads = LOADS.lock().unwrap();
+    let enclave = enclaves
+        .iter()
+        .min_by_key(|enclave| loads.get(&enclave.name()).copied().unwrap_or_default())?;
+    *loads.entry(enclave.name()).or_default() += 1;
+    Some((enclave, EnclaveLoad(enclave.name())))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_acquire_least_loaded() {
+        let enclaves = [
+            EnclaveConfig {
+                endpoint: Some("http://enclave-a:50051".to_string()),
+                ..Default::default()
+            },
+            EnclaveConfig {
+                endpoint: Some("http://enclave-b:50051".to_string()),
+                ..Default::default()
+            },
+        ];
+        let (first, first_load) = acquire(&enclaves).unwrap();
+        assert_eq!(first, &enclaves[0]);
+        let (second, _second_load) = acquire(&enclaves).unwrap();
+        assert_eq!(second, &enclaves[1]);
+
+        drop(first_load);
+        let (third, _third_load) = acquire(&enclaves).unwrap();
+        assert_eq!(third, &enclaves[0]);
+        assert_eq!(LOADS.lock().unwrap()["http://enclave-a:50051"], 1);
+        assert!(acquire(&[]).is_none());
+    }
+
+    #[test]
+    fn test_configure_enclaves() {
+        assert_eq!(enclaves(), vec![EnclaveConfig::default()]);
+        let enclave = EnclaveConfig {
+            endpoint: Some("http://enclave-a:50051".to_string()),
+            token: Some("secret".to_string()),
+            ..Default::default()
+        };
+        configure_enclaves(vec![enclave.clone()]);
+        assert_eq!(enclaves(), vec![enclave.clone()]);
+        // The token of the shim is never serialized
+        assert!(!serde_json::to_string(&enclave).unwrap().contains("secret"));
+    }
+}
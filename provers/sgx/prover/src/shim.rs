@@ -0,0 +1,106 @@
+//! A gRPC shim to run the SGX guest of a host on another machine.
+//!
+//! The shim runs next to the `sgx-guest` binary on the SGX machine and proves the blocks sent
+//! by the host with its local enclave, see [serve_enclave]. Hosts authenticate with the token
+//! of the shim.
+
+use std::net::SocketAddr;
+
+use raiko_lib::{input::GuestInput, prover::ProverResult};
+use tonic::{
+    service::interceptor::InterceptedService, transport::Server, Request, Response, Status,
+};
+
+use crate::{gramine_command, is_direct_mode, keys::current_instance_id, prove, working_dir};
+
+include!(concat!(env!("OUT_DIR"), "/raiko.sgx.Enclave.rs"));
+
+pub(crate) use enclave_client::EnclaveClient;
+use enclave_server::{Enclave, EnclaveServer};
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ProveRequest {
+    /// The bincode encoded guest input.
+    #[prost(bytes = "vec", tag = "1")]
+    pub input: Vec<u8>,
+    /// The instance id to sign the proof as, defaults to the instance of the current key of
+    /// the enclave.
+    #[prost(uint64, optional, tag = "2")]
+    pub instance_id: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ProveResponse {
+    #[prost(string, tag = "1")]
+    pub proof: String,
+    #[prost(string, tag = "2")]
+    pub quote: String,
+    /// The instance id the proof was signed as.
+    #[prost(uint64, tag = "3")]
+    pub instance_id: u64,
+}
+
+/// Guest inputs carry the full block state, which is well above the default gRPC limit.
+pub(crate) const MAX_MESSAGE_SIZE: usize = 256 << 20;
+
+/// The metadata hosts send the token of the shim in, as a bearer token.
+pub(crate) const AUTHORIZATION: &str = "authorization";
+
+struct LocalEnclave;
+
+#[tonic::async_trait]
+impl Enclave for LocalEnclave {
+    async fn prove(
+        &self,
+        request: Request<ProveRequest>,
+    ) -> Result<Response<ProveResponse>, Status> {
+        let ProveRequest { input, instance_id } = request.into_inner();
+        let input: GuestInput = bincode::deserialize(&input)
+            .map_err(|e| Status::invalid_argument(format!("Invalid guest input: {e}")))?;
+        let cur_dir = working_dir();
+        let instance_id = instance_id
+            .or_else(|| current_instance_id(&cur_dir))
+            .ok_or_else(|| Status::failed_precondition("The enclave key is not registered"))?;
+
+        let response = prove(
+            gramine_command(&cur_dir, is_direct_mode()),
+            input,
+            instance_id,
+        )
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(ProveResponse {
+            proof: response.proof,
+            quote: response.quote,
+            instance_id,
+        }))
+    }
+}
+
+/// Serve the local enclave to hosts at `address` until the shim is stopped. Hosts have to send
+/// the `token` with every proof.
+pub async fn serve_enclave(address: SocketAddr, token: String) -> ProverResult<(), String> {
+    if token.is_empty() {
+        return Err("The SGX enclave shim needs a token".to_string());
+    }
+    let bearer = format!("Bearer {token}");
+    let check_token = move |request: Request<()>| {
+        let metadata = request.metadata().get(AUTHORIZATION);
+        if metadata.and_then(|value| value.to_str().ok()) == Some(bearer.as_str()) {
+            Ok(request)
+        } else {
+            Err(Status::unauthenticated("Invalid SGX enclave shim token"))
+        }
+    };
+    println!("Serving the SGX enclave on {address}");
+    Server::builder()
+        .add_service(InterceptedService::new(
+            EnclaveServer::new(LocalEnclave)
+                .max_decoding_message_size(MAX_MESSAGE_SIZE)
+                .max_encoding_message_size(MAX_MESSAGE_SIZE),
+            check_token,
+        ))
+        .serve(address)
+        .await
+        .map_err(|e| format!("SGX enclave shim failed: {e}"))
+}
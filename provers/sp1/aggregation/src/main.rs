@@ -0,0 +1,24 @@
+// cargo prove build
+#![no_main]
+sp1_zkvm::entrypoint!(main);
+
+use raiko_lib::aggregate::{program_id_from_words, AggregationGuestInput, AggregationGuestOutput};
+use sha2::{Digest, Sha256};
+
+pub fn main() {
+    let input = sp1_zkvm::io::read::<AggregationGuestInput>();
+
+    // Verify the compressed block proofs, which are passed to the prover with the input. A
+    // proof commits to its public values, the serialized output of the block, by their digest.
+    for output in &input.block_outputs {
+        let public_values = bincode::serialize(output).expect("Failed to serialize block output");
+        let public_values_digest: [u8; 32] = Sha256::digest(public_values).into();
+        sp1_zkvm::precompiles::verify::verify_sp1_proof(&input.program_id, &public_values_digest);
+    }
+
+    let program_id = program_id_from_words(input.program_id);
+    let output = AggregationGuestOutput::new(program_id, &input.block_outputs)
+        .expect("Can not aggregate failed blocks");
+
+    sp1_zkvm::io::commit(&output);
+}
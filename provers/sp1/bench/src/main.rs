@@ -0,0 +1,109 @@
+#![no_main]
+sp1_zkvm::entrypoint!(main);
+
+use std::hint::black_box;
+
+use k256::ecdsa::{SigningKey, VerifyingKey};
+use sha2::Digest;
+
+/// Run the accelerated and the software implementation of a precompile the given number of
+/// times each, tracking their cycles. The executor logs the cycles of every tracked section.
+fn bench(
+    precompile: &str,
+    iterations: u32,
+    mut accelerated: impl FnMut(),
+    mut software: impl FnMut(),
+) {
+    let track = |implementation: &str, op: &mut dyn FnMut()| {
+        println!("cycle-tracker-start: {precompile}-{implementation}");
+        for _ in 0..iterations {
+            op();
+        }
+        println!("cycle-tracker-end: {precompile}-{implementation}");
+    };
+    track("accelerated", &mut accelerated);
+    track("software", &mut software);
+}
+
+pub fn main() {
+    let iterations = sp1_zkvm::io::read::<u32>();
+
+    let data = [0xa5u8; 1024];
+    let hash: [u8; 32] = sha2::Sha256::digest(b"raiko").into();
+    let signing_key = SigningKey::from_bytes(&[0x11u8; 32].into()).unwrap();
+    let (signature, recovery_id) = signing_key.sign_prehash_recoverable(&hash).unwrap();
+    let message = libsecp256k1::Message::parse(&hash);
+    let soft_signature =
+        libsecp256k1::Signature::parse_standard(&signature.to_bytes().into()).unwrap();
+    let soft_recovery_id = libsecp256k1::RecoveryId::parse(recovery_id.to_byte()).unwrap();
+    let scalar = "1234567890123456789";
+
+    bench(
+        "sha256",
+        iterations,
+        || {
+            black_box(sha2::Sha256::digest(black_box(&data)));
+        },
+        || {
+            black_box(hmac_sha256::Hash::hash(black_box(&data)));
+        },
+    );
+    bench(
+        "keccak",
+        iterations,
+        || {
+            black_box(raiko_primitives::keccak::keccak(black_box(&data)));
+        },
+        || {
+            black_box(sha3::Keccak256::digest(black_box(&data)));
+        },
+    );
+    bench(
+        "ecrecover",
+        iterations,
+        || {
+            black_box(VerifyingKey::recover_from_prehash(&hash, &signature, recovery_id).unwrap());
+        },
+        || {
+            black_box(libsecp256k1::recover(&message, &soft_signature, &soft_recovery_id).unwrap());
+        },
+    );
+    bench(
+        "bn254_add",
+        iterations,
+        || {
+            use substrate_bn::{Group, G1};
+            black_box(black_box(G1::one()) + black_box(G1::one()));
+        },
+        || {
+            use substrate_bn_soft::{Group, G1};
+            black_box(black_box(G1::one()) + black_box(G1::one()));
+        },
+    );
+    bench(
+        "bn254_mul",
+        iterations,
+        || {
+            use substrate_bn::{Fr, Group, G1};
+            black_box(black_box(G1::one()) * Fr::from_str(scalar).unwrap());
+        },
+        || {
+            use substrate_bn_soft::{Fr, Group, G1};
+            black_box(black_box(G1::one()) * Fr::from_str(scalar).unwrap());
+        },
+    );
+    bench(
+        "bn254_pairing",
+        iterations,
+        || {
+            use substrate_bn::{pairing, Group, G1, G2};
+            black_box(pairing(black_box(G1::one()), black_box(G2::one())));
+        },
+        || {
+            use substrate_bn_soft::{pairing, Group, G1, G2};
+            black_box(pairing(black_box(G1::one()), black_box(G2::one())));
+        },
+    );
+
+    sp1_zkvm::io::commit(&iterations);
+}
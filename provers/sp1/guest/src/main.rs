@@ -5,12 +5,23 @@ use raiko_lib::protocol_instance::assemble_protocol_instance;
 use raiko_lib::protocol_instance::EvidenceType;
 use raiko_lib::{
     builder::{BlockBuilderStrategy, TaikoStrategy},
+    consts::verify_chain_spec,
     input::{GuestInput, GuestOutput, WrappedHeader},
 };
 
 pub fn main() {
+    // The cycles of the phases are reported in the cycle tracker logs of the SP1 executor, the
+    // host can't read a profile written by the guest.
+    println!("cycle-tracker-start: deserialize");
     let input = sp1_zkvm::io::read::<GuestInput>();
-    let build_result = TaikoStrategy::build_from(&input);
+    println!("cycle-tracker-end: deserialize");
+    // The chain spec comes with the input, only the rules of the built-in chains are trusted.
+    verify_chain_spec(input.network, &input.chain_spec).expect("Unsupported chain spec");
+    println!("cycle-tracker-start: execute");
+    let build_result = TaikoStrategy::build_from_with(&input, || {
+        println!("cycle-tracker-end: execute");
+        println!("cycle-tracker-start: finalize");
+    });
 
     let output = match &build_result {
         Ok((header, mpt_node)) => {
@@ -26,6 +37,7 @@ pub fn main() {
         }
         Err(_) => GuestOutput::Failure,
     };
+    println!("cycle-tracker-end: finalize");
 
     sp1_zkvm::io::commit(&output);
 }
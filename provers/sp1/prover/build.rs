@@ -3,5 +3,9 @@ fn main() {
     println!("Sp1 not enabled");
 
     #[cfg(feature = "enable")]
-    sp1_helper::build_program("../guest");
+    {
+        sp1_helper::build_program("../guest");
+        sp1_helper::build_program("../bench");
+        sp1_helper::build_program("../aggregation");
+    }
 }
@@ -1,24 +1,235 @@
 #![cfg(feature = "enable")]
 
-use std::env;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
 
-use alloy_primitives::B256;
-use alloy_sol_types::SolValue;
+use alloy_primitives::{hex, B256};
+use alloy_sol_types::{sol, SolCall, SolValue};
 use raiko_lib::{
+    aggregate::{program_id_from_words, AggregationGuestInput, AggregationGuestOutput, Aggregator},
     input::{GuestInput, GuestOutput},
     protocol_instance::ProtocolInstance,
-    prover::{to_proof, Proof, Prover, ProverConfig, ProverResult},
+    prover::{
+        cuda_device, to_proof, GuestSelection, ProgressReporter, Proof, Prover, ProverConfig,
+        ProverError, ProverProgress, ProverResult,
+    },
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use sha3::{self, Digest};
-use sp1_sdk::{ProverClient, SP1Stdin};
+use sp1_sdk::{
+    client::NetworkClient,
+    proto::network::{ProofMode, ProofStatus},
+    ProverClient, SP1CompressedProof, SP1DefaultProof, SP1PlonkBn254Proof, SP1Stdin,
+};
 
 const ELF: &[u8] = include_bytes!("../../guest/elf/riscv32im-succinct-zkvm-elf");
 
+/// The precompiles benchmark program.
+const BENCH_ELF: &[u8] = include_bytes!("../../bench/elf/riscv32im-succinct-zkvm-elf");
+
+/// The program aggregating compressed block proofs.
+const AGGREGATION_ELF: &[u8] = include_bytes!("../../aggregation/elf/riscv32im-succinct-zkvm-elf");
+
+/// The prefix of the file in the workspace the id of the proof on the prover network is kept
+/// in, followed by the hash of the expected output.
+const NETWORK_PROOF_FILE: &str = "sp1-network-proof";
+
+/// The file the generated proof is saved in, in the workspace or the working directory.
+const PROOF_FILE: &str = "proof-with-io.json";
+
+sol! {
+    /// The SP1 verifier contract, verifying the PLONK proofs of a program.
+    interface ISP1Verifier {
+        function verifyProof(
+            bytes32 programVKey,
+            bytes calldata publicValues,
+            bytes calldata proofBytes
+        ) external view;
+    }
+}
+
+/// The client of the SDK, which sets up the prover once per process.
+static CLIENT: OnceLock<ProverClient> = OnceLock::new();
+
+fn client() -> &'static ProverClient {
+    CLIENT.get_or_init(ProverClient::new)
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Sp1Param {
+    /// Offload the proving to the Succinct prover network when set.
+    #[serde(default)]
+    pub network: Option<Sp1NetworkParam>,
+    /// Generate a compressed proof, of constant size, which can be aggregated with the
+    /// proofs of other blocks.
+    #[serde(default)]
+    pub compressed: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sp1NetworkParam {
+    /// The key used to authenticate with the prover network.
+    pub api_key: String,
+    /// The prover network RPC endpoint, uses the SDK default when not set.
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// The number of seconds between two proof status requests.
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+    /// The number of seconds to wait for the network before proving locally.
+    #[serde(default = "default_network_timeout")]
+    pub timeout: u64,
+}
+
+fn default_poll_interval() -> u64 {
+    15
+}
+
+fn default_network_timeout() -> u64 {
+    60 * 60
+}
+
+/// The kind of an SP1 proof.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sp1ProofMode {
+    /// A proof of every shard of the execution.
+    #[default]
+    Core,
+    /// The shard proofs recursively compressed into one proof.
+    Compressed,
+    /// The compressed proof wrapped into a PLONK proof, which can be verified on-chain.
+    Plonk,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Sp1Response {
     pub proof: String,
     pub output: GuestOutput,
+    /// The kind of the proof, proofs generated before it was added are core proofs.
+    #[serde(default)]
+    pub mode: Sp1ProofMode,
+    /// The hex encoded call to the SP1 verifier contract, set for PLONK proofs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calldata: Option<String>,
+    /// The id of the proof on the prover network, not set for proofs generated locally.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_proof_id: Option<String>,
+    /// The guest the host selected instead of the built-in one, to verify the proof against.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guest: Option<GuestSelection>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Sp1AggregationResponse {
+    /// The compressed proof of the aggregation program.
+    pub proof: String,
+    pub output: AggregationGuestOutput,
+    /// The digest of the verifying key of the block program the aggregated proofs are of.
+    pub program_id: B256,
+}
+
+/// A kind of SP1 proof, generated and verified with its own methods of the SDK.
+trait Sp1Proof: Serialize + DeserializeOwned + Sized {
+    /// The kind of the proof.
+    const MODE: Sp1ProofMode;
+    /// The proof mode of the prover network.
+    const NETWORK_MODE: ProofMode;
+
+    fn prove(client: &ProverClient, elf: &[u8], stdin: SP1Stdin) -> anyhow::Result<Self>;
+
+    fn verify(client: &ProverClient, elf: &[u8], proof: &Self) -> anyhow::Result<()>;
+
+    fn read_output<T: DeserializeOwned>(&self) -> T;
+
+    /// The number of shards the execution was proven in, if the proof has them.
+    fn shards(&self) -> Option<u64> {
+        None
+    }
+
+    /// The call verifying the proof with the SP1 verifier contract, if it can be verified
+    /// on-chain.
+    fn calldata(&self, _program_vkey: B256) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+impl Sp1Proof for SP1DefaultProof {
+    const MODE: Sp1ProofMode = Sp1ProofMode::Core;
+    const NETWORK_MODE: ProofMode = ProofMode::Core;
+
+    fn prove(client: &ProverClient, elf: &[u8], stdin: SP1Stdin) -> anyhow::Result<Self> {
+        client.prove(elf, stdin)
+    }
+
+    fn verify(client: &ProverClient, elf: &[u8], proof: &Self) -> anyhow::Result<()> {
+        client
+            .verify(elf, proof)
+            .map_err(|e| anyhow::anyhow!("{e}"))
+    }
+
+    fn read_output<T: DeserializeOwned>(&self) -> T {
+        self.public_values.clone().read::<T>()
+    }
+
+    fn shards(&self) -> Option<u64> {
+        Some(self.proof.len() as u64)
+    }
+}
+
+impl Sp1Proof for SP1CompressedProof {
+    const MODE: Sp1ProofMode = Sp1ProofMode::Compressed;
+    const NETWORK_MODE: ProofMode = ProofMode::Compressed;
+
+    fn prove(client: &ProverClient, elf: &[u8], stdin: SP1Stdin) -> anyhow::Result<Self> {
+        client.prove_compressed(elf, stdin)
+    }
+
+    fn verify(client: &ProverClient, elf: &[u8], proof: &Self) -> anyhow::Result<()> {
+        client
+            .verify_compressed(elf, proof)
+            .map_err(|e| anyhow::anyhow!("{e}"))
+    }
+
+    fn read_output<T: DeserializeOwned>(&self) -> T {
+        self.public_values.clone().read::<T>()
+    }
+}
+
+impl Sp1Proof for SP1PlonkBn254Proof {
+    const MODE: Sp1ProofMode = Sp1ProofMode::Plonk;
+    const NETWORK_MODE: ProofMode = ProofMode::Plonk;
+
+    fn prove(client: &ProverClient, elf: &[u8], stdin: SP1Stdin) -> anyhow::Result<Self> {
+        client.prove_plonk(elf, stdin)
+    }
+
+    fn verify(client: &ProverClient, elf: &[u8], proof: &Self) -> anyhow::Result<()> {
+        client
+            .verify_plonk(elf, proof)
+            .map_err(|e| anyhow::anyhow!("{e}"))
+    }
+
+    fn read_output<T: DeserializeOwned>(&self) -> T {
+        self.public_values.clone().read::<T>()
+    }
+
+    fn calldata(&self, program_vkey: B256) -> Option<Vec<u8>> {
+        let proof_bytes = hex::decode(&self.proof.encoded_proof).ok()?;
+        Some(
+            ISP1Verifier::verifyProofCall {
+                programVKey: program_vkey,
+                publicValues: self.public_values.as_slice().to_vec().into(),
+                proofBytes: proof_bytes.into(),
+            }
+            .abi_encode(),
+        )
+    }
 }
 
 pub struct Sp1Prover;
@@ -26,48 +237,424 @@ pub struct Sp1Prover;
 impl Prover for Sp1Prover {
     async fn run(
         input: GuestInput,
-        _output: GuestOutput,
-        _config: &ProverConfig,
+        output: GuestOutput,
+        config: &ProverConfig,
+    ) -> ProverResult<Proof> {
+        Self::run_with_progress(input, output, config, &ProgressReporter::default()).await
+    }
+
+    async fn run_with_progress(
+        input: GuestInput,
+        expected_output: GuestOutput,
+        config: &ProverConfig,
+        progress: &ProgressReporter,
     ) -> ProverResult<Proof> {
+        let param = config
+            .get("sp1")
+            .map(Sp1Param::deserialize)
+            .transpose()
+            .map_err(|e| ProverError::GuestError(format!("Sp1: invalid params: {e}")))?
+            .unwrap_or_default();
+        let guest = GuestSelection::from_config(config)?;
+        let elf = guest_elf(guest.as_ref())?;
+
+        // The SDK can't resume a proof from its shards, the workspace of the host only keeps
+        // the proof on the prover network and the finished proof.
+        let workspace = config
+            .get("workspace")
+            .and_then(|workspace| workspace.as_str())
+            .map(PathBuf::from);
+
         // Write the input.
         let mut stdin = SP1Stdin::new();
         stdin.write(&input);
 
-        // Generate the proof for the given program.
-        let client = ProverClient::new();
-        let mut proof = client.prove(ELF, stdin).expect("Sp1: proving failed");
+        let block = Block {
+            param: &param,
+            elf: &elf,
+            expected_output: &expected_output,
+            workspace: workspace.as_deref(),
+            config,
+            progress,
+        };
+        // Like risc0, `compress` wraps the proof into a SNARK which can be verified on-chain.
+        let snark = config
+            .get("compress")
+            .and_then(|compress| compress.as_bool())
+            .unwrap_or_default();
+        let response = if snark {
+            block.prove::<SP1PlonkBn254Proof>(stdin, guest).await?
+        } else if param.compressed {
+            block.prove::<SP1CompressedProof>(stdin, guest).await?
+        } else {
+            block.prove::<SP1DefaultProof>(stdin, guest).await?
+        };
+
+        println!("succesfully generated and verified proof for the program!");
+        to_proof(Ok(response))
+    }
+
+    fn instance_hash(pi: ProtocolInstance) -> B256 {
+        let data = (pi.transition.clone(), pi.prover, pi.meta_hash()).abi_encode();
+
+        let hash: [u8; 32] = sha3::Keccak256::digest(data).into();
+        hash.into()
+    }
+
+    fn program_id() -> Option<B256> {
+        let hash: [u8; 32] = sha3::Keccak256::digest(ELF).into();
+        Some(hash.into())
+    }
+
+    fn warm_up() -> ProverResult<()> {
+        client();
+        Ok(())
+    }
+
+    fn verify(
+        proof: &Proof,
+        _input: &GuestInput,
+        output: &GuestOutput,
+    ) -> Option<ProverResult<()>> {
+        Some(verify_proof(proof, output))
+    }
+}
+
+/// Verify the proof of the response against the program, and check that its public values are
+/// the expected output. Proofs of another guest than the built-in one are verified against the
+/// ELF of the guest the host configured with the program id of the proof, not the ELF the proof
+/// names.
+fn verify_proof(proof: &Proof, expected_output: &GuestOutput) -> ProverResult<()> {
+    let response =
+        Sp1Response::deserialize(proof).map_err(|e| format!("Sp1: invalid proof: {e}"))?;
+    let guest = response
+        .guest
+        .map(|guest| {
+            GuestSelection::configured(guest.program_id).ok_or_else(|| {
+                format!(
+                    "Sp1: the proof is of the unknown guest with the program id {}",
+                    guest.program_id
+                )
+            })
+        })
+        .transpose()?;
+    let elf = guest_elf(guest.as_ref())?;
+    let output = match response.mode {
+        Sp1ProofMode::Core => verify_with::<SP1DefaultProof>(&response.proof, &elf)?,
+        Sp1ProofMode::Compressed => verify_with::<SP1CompressedProof>(&response.proof, &elf)?,
+        Sp1ProofMode::Plonk => verify_with::<SP1PlonkBn254Proof>(&response.proof, &elf)?,
+    };
+    if output != *expected_output {
+        return Err(
+            format!("Sp1: the proof proves {output:?} instead of {expected_output:?}").into(),
+        );
+    }
+    Ok(())
+}
+
+/// Verify the serialized proof against the program and return its output.
+fn verify_with<P: Sp1Proof>(proof: &str, elf: &[u8]) -> ProverResult<GuestOutput> {
+    let proof: P = serde_json::from_str(proof).map_err(|e| format!("Sp1: invalid proof: {e}"))?;
+    P::verify(client(), elf, &proof).map_err(|e| format!("Sp1: verification failed: {e}"))?;
+    Ok(proof.read_output())
+}
+
+/// The ELF of the selected guest, after checking that it has the program id the host expects,
+/// or the built-in one.
+fn guest_elf(guest: Option<&GuestSelection>) -> ProverResult<Vec<u8>> {
+    let Some(guest) = guest else {
+        return Ok(ELF.to_vec());
+    };
+    let elf = fs::read(&guest.elf).map_err(|e| {
+        format!(
+            "Sp1: could not read the guest {} from {}: {e}",
+            guest.name, guest.elf
+        )
+    })?;
+    let hash: [u8; 32] = sha3::Keccak256::digest(&elf).into();
+    if B256::from(hash) != guest.program_id {
+        return Err(format!(
+            "Sp1: the guest {} has the program id {} instead of {}",
+            guest.name,
+            B256::from(hash),
+            guest.program_id
+        )
+        .into());
+    }
+    Ok(elf)
+}
+
+/// The block proven by [Sp1Prover::run_with_progress].
+struct Block<'a> {
+    param: &'a Sp1Param,
+    elf: &'a [u8],
+    expected_output: &'a GuestOutput,
+    workspace: Option<&'a Path>,
+    config: &'a ProverConfig,
+    progress: &'a ProgressReporter,
+}
+
+impl Block<'_> {
+    /// Generate a proof of the given kind for the program, on the prover network if
+    /// configured, unless it was generated before the proof was interrupted.
+    async fn prove<P: Sp1Proof>(
+        &self,
+        stdin: SP1Stdin,
+        guest: Option<GuestSelection>,
+    ) -> ProverResult<Sp1Response> {
+        let proof_path = self
+            .workspace
+            .map_or_else(
+                || env::current_dir().expect("Sp1: dir error"),
+                Path::to_path_buf,
+            )
+            .join(PROOF_FILE);
+
+        let client = client();
+        // The workspace is kept per block, a proof of a reorged block has another output.
+        let saved = self
+            .workspace
+            .and_then(|_| fs::read(&proof_path).ok())
+            .and_then(|bytes| serde_json::from_slice::<P>(&bytes).ok())
+            .filter(|proof| proof.read_output::<GuestOutput>() == *self.expected_output);
+        let remote = match (&saved, &self.param.network) {
+            (None, Some(network)) => {
+                let checkpoint = self.workspace.map(|workspace| {
+                    let output = serde_json::to_vec(self.expected_output).unwrap();
+                    let hash: [u8; 32] = sha3::Keccak256::digest(output).into();
+                    workspace.join(format!("{NETWORK_PROOF_FILE}-{}", B256::from(hash)))
+                });
+                match prove_remote::<P>(
+                    network,
+                    self.elf,
+                    &stdin,
+                    checkpoint.as_deref(),
+                    self.progress,
+                )
+                .await
+                {
+                    Ok(remote) => Some(remote),
+                    Err(e) => {
+                        println!("Sp1: prover network unavailable, proving locally: {e}");
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+        let (proof, network_proof_id) = match (saved, remote) {
+            (Some(proof), _) => {
+                println!("Sp1: resumed the proof saved in {}", proof_path.display());
+                (proof, None)
+            }
+            (None, Some((proof_id, proof))) => (proof, Some(proof_id)),
+            (None, None) => {
+                if let Some(device) = cuda_device(self.config) {
+                    println!("Sp1: proving on CUDA device {device}");
+                }
+                // Proving locally blocks the thread until the proof is done.
+                if self.progress.is_cancelled() {
+                    return Err("Sp1: the proof was cancelled".to_string().into());
+                }
+                let proof = P::prove(client, self.elf, stdin)
+                    .map_err(|e| format!("Sp1: proving failed: {e}"))?;
+                (proof, None)
+            }
+        };
+
+        // SP1 doesn't report the cycles of the execution.
+        self.progress.report(ProverProgress::Executed {
+            cycles: None,
+            segments: proof.shards(),
+        });
 
         // Read the output.
-        let output = proof.public_values.read::<GuestOutput>();
+        let output = proof.read_output::<GuestOutput>();
 
-        // Verify proof.
-        client
-            .verify(ELF, &proof)
-            .expect("Sp1: verification failed");
+        // Verify proof, also the ones generated by the prover network.
+        P::verify(client, self.elf, &proof)
+            .map_err(|e| format!("Sp1: verification failed: {e}"))?;
+        self.progress.report(ProverProgress::Verified);
+
+        // The verifier contract checks the proof against the digest of the verifying key.
+        let calldata = match P::MODE {
+            Sp1ProofMode::Plonk => {
+                let (_, vk) = client.setup(self.elf);
+                let program_vkey = B256::from_str(&vk.bytes32())
+                    .map_err(|e| format!("Sp1: invalid verifying key digest: {e}"))?;
+                let calldata = proof
+                    .calldata(program_vkey)
+                    .ok_or_else(|| "Sp1: invalid PLONK proof encoding".to_string())?;
+                Some(format!("0x{}", hex::encode(calldata)))
+            }
+            _ => None,
+        };
 
         // Save the proof.
-        let proof_dir = env::current_dir().expect("Sp1: dir error");
-        proof
-            .save(
-                proof_dir
-                    .as_path()
-                    .join("proof-with-io.json")
-                    .to_str()
-                    .unwrap(),
+        let proof = serde_json::to_string(&proof).unwrap();
+        fs::write(&proof_path, &proof).map_err(|e| format!("Sp1: saving proof failed: {e}"))?;
+
+        Ok(Sp1Response {
+            proof,
+            output,
+            mode: P::MODE,
+            calldata,
+            network_proof_id,
+            guest,
+        })
+    }
+}
+
+impl Aggregator for Sp1Prover {
+    async fn aggregate(proofs: Vec<Proof>, config: &ProverConfig) -> ProverResult<Proof> {
+        let client = client();
+        // The aggregation program verifies the block proofs against the verifying key of the
+        // block program, only proofs of the built-in guest can be aggregated.
+        let (_, vk) = client.setup(ELF);
+        let program_id = vk.hash_u32();
+
+        let mut block_outputs = Vec::with_capacity(proofs.len());
+        let mut block_proofs = Vec::with_capacity(proofs.len());
+        for proof in proofs {
+            let response =
+                Sp1Response::deserialize(&proof).map_err(|e| format!("Sp1: invalid proof: {e}"))?;
+            if response.guest.is_some() {
+                return Err("Sp1: only proofs of the built-in guest can be aggregated"
+                    .to_string()
+                    .into());
+            }
+            if response.mode != Sp1ProofMode::Compressed {
+                return Err(
+                    "Sp1: only compressed proofs can be aggregated, prove the blocks \
+                    with the `compressed` param"
+                        .to_string()
+                        .into(),
+                );
+            }
+            let proof: SP1CompressedProof = serde_json::from_str(&response.proof)
+                .map_err(|e| format!("Sp1: invalid proof: {e}"))?;
+            SP1CompressedProof::verify(client, ELF, &proof)
+                .map_err(|e| format!("Sp1: failed to verify block proof: {e}"))?;
+            block_outputs.push(proof.read_output::<GuestOutput>());
+            block_proofs.push(proof.proof);
+        }
+
+        let output = AggregationGuestOutput::new(program_id_from_words(program_id), &block_outputs)
+            .ok_or_else(|| "Sp1: can not aggregate failed blocks".to_string())?;
+        let mut stdin = SP1Stdin::new();
+        stdin.write(&AggregationGuestInput {
+            program_id,
+            block_outputs,
+        });
+        // The block proofs are verified in the program against their public values.
+        for proof in block_proofs {
+            stdin.write_proof(proof, vk.vk.clone());
+        }
+
+        let proof = SP1CompressedProof::prove(client, AGGREGATION_ELF, stdin)
+            .map_err(|e| format!("Sp1: aggregation failed: {e}"))?;
+        SP1CompressedProof::verify(client, AGGREGATION_ELF, &proof)
+            .map_err(|e| format!("Sp1: verification failed: {e}"))?;
+        let aggregated = proof.read_output::<AggregationGuestOutput>();
+        if aggregated != output {
+            return Err(format!(
+                "Sp1: the aggregation proves {aggregated:?} instead of {output:?}"
             )
-            .expect("Sp1: saving proof failed");
+            .into());
+        }
 
-        println!("succesfully generated and verified proof for the program!");
-        to_proof(Ok(Sp1Response {
+        to_proof(Ok(Sp1AggregationResponse {
             proof: serde_json::to_string(&proof).unwrap(),
             output,
+            program_id: program_id_from_words(program_id),
         }))
     }
+}
 
-    fn instance_hash(pi: ProtocolInstance) -> B256 {
-        let data = (pi.transition.clone(), pi.prover, pi.meta_hash()).abi_encode();
+/// Execute the precompiles benchmark program, which runs every accelerated precompile and a
+/// software implementation of it the given number of times. The SDK only reports the cycles
+/// of the tracked sections in its logs, as `<precompile>-accelerated` and
+/// `<precompile>-software`.
+pub fn bench_precompiles(iterations: u32) -> ProverResult<()> {
+    let mut stdin = SP1Stdin::new();
+    stdin.write(&iterations);
+    ProverClient::new()
+        .execute(BENCH_ELF, stdin)
+        .map_err(|e| format!("Sp1: failed to run the precompiles benchmark: {e}"))?;
+    Ok(())
+}
 
-        let hash: [u8; 32] = sha3::Keccak256::digest(data).into();
-        hash.into()
+/// Submit the program and its input to the prover network and wait for the proof. The id of
+/// the proof is kept in the checkpoint file, so an interrupted proof is awaited again instead
+/// of submitted anew.
+async fn prove_remote<P: Sp1Proof>(
+    network: &Sp1NetworkParam,
+    elf: &[u8],
+    stdin: &SP1Stdin,
+    checkpoint: Option<&Path>,
+    progress: &ProgressReporter,
+) -> anyhow::Result<(String, P)> {
+    // Jobs proving concurrently may use other endpoints, so it's not set in the environment.
+    let client = NetworkClient::new(&network.api_key, network.endpoint.clone());
+    let resumed = checkpoint
+        .and_then(|checkpoint| fs::read_to_string(checkpoint).ok())
+        .map(|proof_id| proof_id.trim().to_string())
+        .filter(|proof_id| !proof_id.is_empty());
+    let proof_id = match resumed {
+        Some(proof_id) => {
+            println!("Sp1: resuming proof {proof_id} on the prover network");
+            proof_id
+        }
+        None => {
+            let proof_id = client.create_proof(elf, stdin, P::NETWORK_MODE).await?;
+            println!("Sp1: submitted proof {proof_id} to the prover network");
+            if let Some(checkpoint) = checkpoint {
+                if let Err(e) = fs::write(checkpoint, &proof_id) {
+                    println!("Sp1: could not save the proof id: {e}");
+                }
+            }
+            proof_id
+        }
+    };
+    let result = wait_for_proof::<P>(&client, network, proof_id, progress).await;
+    if result.is_err() {
+        // Submit a new proof the next time.
+        if let Some(checkpoint) = checkpoint {
+            let _ = fs::remove_file(checkpoint);
+        }
+    }
+    result
+}
+
+/// Poll the status of the proof on the prover network until it was generated.
+async fn wait_for_proof<P: Sp1Proof>(
+    client: &NetworkClient,
+    network: &Sp1NetworkParam,
+    proof_id: String,
+    progress: &ProgressReporter,
+) -> anyhow::Result<(String, P)> {
+    progress.report(ProverProgress::RemoteSession {
+        service: "sp1_network".to_string(),
+        session_id: proof_id.clone(),
+    });
+
+    let deadline = Instant::now() + Duration::from_secs(network.timeout);
+    loop {
+        let (status, proof) = client.get_proof_status::<P>(&proof_id).await?;
+        match status.status() {
+            ProofStatus::ProofFulfilled => {
+                let proof = proof.ok_or_else(|| anyhow::anyhow!("proof {proof_id} is missing"))?;
+                return Ok((proof_id, proof));
+            }
+            ProofStatus::ProofUnclaimed => {
+                anyhow::bail!("proof {proof_id} was not claimed by any prover")
+            }
+            _ => {}
+        }
+        if Instant::now() > deadline {
+            anyhow::bail!("timed out waiting for proof {proof_id}");
+        }
+        // Don't block the worker thread of the runtime while waiting.
+        tokio::time::sleep(Duration::from_secs(network.poll_interval)).await;
     }
 }
@@ -1,6 +1,7 @@
 use std::path::PathBuf;
 
 use clap::{Args, Parser, Subcommand};
+use raiko_signer::SignerConfig;
 
 const DEFAULT_RAIKO_USER_CONFIG_SUBDIR_PATH: &str = ".config/raiko";
 
@@ -19,6 +20,12 @@ pub enum Command {
     /// initial public-private key pair and stores it on the disk in an encrypted
     /// format using SGX encryption primitives.
     Bootstrap(BootstrapArgs),
+    /// Replace the bootstrapped key pair with a new one and register it as a new instance.
+    Rotate(BootstrapArgs),
+    /// Print the public key the enclave currently signs proofs with.
+    PublicKey,
+    /// List the instances registered for the keys of the enclave.
+    Instances,
 }
 
 #[derive(Debug, Args)]
@@ -29,6 +36,15 @@ pub struct BootstrapArgs {
     pub l1_chain_id: u64,
     #[clap(long, default_value = "0x4826533B4897376654Bb4d4AD88B7faFD0C98528")]
     pub sgx_verifier_address: String,
+    #[clap(
+        long,
+        default_value = r#"{"type": "private_key", "env": "SENDER_PRIV_KEY"}"#
+    )]
+    /// The signer of the registration in JSON, e.g. `{"type": "keystore", "path":
+    /// "/etc/raiko/sender.json", "password_env": "KEYSTORE_PASSWORD"}`, `{"type": "web3signer",
+    /// "url": "http://localhost:9000", "address": "0x..."}` or `{"type": "aws_kms", "key_id":
+    /// "..."}`
+    pub signer: SignerConfig,
     #[clap(long, default_value = "/etc/raiko/config.sgx.json")]
     /// Path to a config file that includes sufficient json args to request
     /// a proof of specified type. Curl json-rpc overrides its contents
@@ -4,7 +4,8 @@ mod setup_bootstrap;
 use anyhow::Result;
 use app_args::{App, Command};
 use clap::Parser;
-use setup_bootstrap::setup_bootstrap;
+use setup_bootstrap::{setup_bootstrap, setup_rotate};
+use sgx_prover::{list_instances, load_key};
 
 #[tokio::main]
 pub async fn main() -> Result<()> {
@@ -15,6 +16,16 @@ pub async fn main() -> Result<()> {
             println!("Setup bootstrapping: {:?}", sgx_bootstrap_args);
             setup_bootstrap(args.global_opts.secrets_dir, &sgx_bootstrap_args).await?;
         }
+        Command::Rotate(sgx_bootstrap_args) => {
+            println!("Rotating the SGX key: {:?}", sgx_bootstrap_args);
+            setup_rotate(&sgx_bootstrap_args).await?;
+        }
+        Command::PublicKey => {
+            println!("{}", serde_json::to_string_pretty(&load_key()?)?);
+        }
+        Command::Instances => {
+            println!("{}", serde_json::to_string_pretty(&list_instances()?)?);
+        }
     }
 
     Ok(())
@@ -2,7 +2,7 @@ use std::{
     env,
     fs::{self, File},
     io::BufReader,
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
@@ -10,7 +10,10 @@ use crate::app_args::BootstrapArgs;
 use alloy_primitives::Address;
 use anyhow::{Context, Result};
 use serde_json::{Number, Value};
-use sgx_prover::{bootstrap, check_bootstrap, register_sgx_instance, ELF_NAME};
+use sgx_prover::{
+    activate_key, bootstrap, check_bootstrap, load_key, register_key, rotate_key, RaikoSigner,
+    ELF_NAME,
+};
 use std::process::Command;
 use tracing::info;
 
@@ -42,7 +45,7 @@ pub(crate) async fn setup_bootstrap(
         || fs::metadata(&registered_check_file).is_err();
 
     if need_init {
-        let bootstrap_proof = bootstrap(secret_dir, gramine_cmd()).await?;
+        bootstrap(secret_dir, gramine_cmd()).await?;
         match fs::remove_file(&registered_check_file) {
             Ok(_) => Ok(()),
             Err(e) => {
@@ -53,33 +56,59 @@ pub(crate) async fn setup_bootstrap(
                 }
             }
         }?;
-        let _register_res = register_sgx_instance(
-            &bootstrap_proof.quote,
+        let key = load_key()?;
+        let signer = RaikoSigner::new(&bootstrap_args.signer).await?;
+        let instance = register_key(
+            &key,
             &bootstrap_args.l1_rpc,
             bootstrap_args.l1_chain_id,
             Address::from_str(&bootstrap_args.sgx_verifier_address).unwrap(),
+            &signer,
         )
-        .await
-        .map_err(|e| anyhow::Error::msg(e.to_string()))?;
-        //todo: update the config
-        // Config file has the lowest preference
-        let file = File::open(&bootstrap_args.config_path)?;
-        let reader = BufReader::new(file);
-        let mut file_config: Value = serde_json::from_reader(reader)?;
-        file_config["sgx"]["instance_id"] = Value::Number(Number::from(_register_res));
-
-        //save to the same file
-        info!(
-            "Saving bootstrap data file {}",
-            bootstrap_args.config_path.display()
-        );
-        let json = serde_json::to_string_pretty(&file_config)?;
-        fs::write(&bootstrap_args.config_path, json).context(format!(
-            "Saving bootstrap data file {} failed",
-            bootstrap_args.config_path.display()
-        ))?;
+        .await?;
+        save_instance_id(&bootstrap_args.config_path, instance.instance_id)?;
         File::create(&registered_check_file)?;
     }
 
     Ok(())
 }
+
+/// Replace the enclave key with a new one and register it as a new instance.
+pub(crate) async fn setup_rotate(bootstrap_args: &BootstrapArgs) -> Result<()> {
+    // Check the signer before replacing the key
+    let signer = RaikoSigner::new(&bootstrap_args.signer).await?;
+    let key = rotate_key().await?;
+    info!(
+        "Generated key version {} with instance address {}",
+        key.key_version, key.instance_address
+    );
+    let instance = register_key(
+        &key,
+        &bootstrap_args.l1_rpc,
+        bootstrap_args.l1_chain_id,
+        Address::from_str(&bootstrap_args.sgx_verifier_address).unwrap(),
+        &signer,
+    )
+    .await?;
+    // Only switch to the new key once it's registered
+    activate_key().await?;
+    info!("Rotated to key version {}", key.key_version);
+    save_instance_id(&bootstrap_args.config_path, instance.instance_id)
+}
+
+/// Update the instance id in the SGX params of the config file.
+fn save_instance_id(config_path: &Path, instance_id: u64) -> Result<()> {
+    // Config file has the lowest preference
+    let file = File::open(config_path)?;
+    let reader = BufReader::new(file);
+    let mut file_config: Value = serde_json::from_reader(reader)?;
+    file_config["sgx"]["instance_id"] = Value::Number(Number::from(instance_id));
+
+    //save to the same file
+    info!("Saving bootstrap data file {}", config_path.display());
+    let json = serde_json::to_string_pretty(&file_config)?;
+    fs::write(config_path, json).context(format!(
+        "Saving bootstrap data file {} failed",
+        config_path.display()
+    ))
+}
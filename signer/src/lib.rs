@@ -0,0 +1,189 @@
+//! Signers for the L1 transactions of raiko, the proofs submitted by the relayer and the
+//! registrations of SGX instances.
+//!
+//! Besides a private key in an environment variable, transactions can be signed with an
+//! encrypted keystore, a remote [web3signer](https://docs.web3signer.consensys.io) or an AWS
+//! KMS key, so no hot key has to be kept in the config of the host.
+
+use std::{env, fmt, path::PathBuf, str::FromStr};
+
+use alloy_network::EthereumSigner;
+use alloy_primitives::{Address, ChainId};
+use alloy_signer::Signer;
+use alloy_signer_wallet::LocalWallet;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+pub use crate::web3signer::Web3Signer;
+
+mod web3signer;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+/// Where the key signing the transactions is kept.
+pub enum SignerConfig {
+    /// A hex encoded private key in an environment variable.
+    PrivateKey { env: String },
+    /// An encrypted JSON keystore, unlocked with the password in an environment variable.
+    Keystore { path: PathBuf, password_env: String },
+    /// A web3signer holding the key of the address.
+    Web3Signer { url: String, address: Address },
+    /// An AWS KMS key, with the credentials and region of the environment.
+    AwsKms { key_id: String },
+}
+
+impl SignerConfig {
+    /// A private key in the given environment variable, used when no signer is configured.
+    pub fn private_key(env: &str) -> Self {
+        SignerConfig::PrivateKey {
+            env: env.to_string(),
+        }
+    }
+}
+
+impl FromStr for SignerConfig {
+    type Err = serde_json::Error;
+
+    /// Parse a signer config in JSON, e.g. `{"type": "keystore", "path": "...", "password_env":
+    /// "KEYSTORE_PASSWORD"}`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+/// A signer for L1 transactions.
+#[derive(Clone, Debug)]
+pub enum RaikoSigner {
+    /// A private key, from the environment or a keystore.
+    Local(LocalWallet),
+    Web3Signer(Web3Signer),
+    #[cfg(feature = "aws")]
+    AwsKms(alloy_signer_aws::AwsSigner),
+}
+
+impl RaikoSigner {
+    /// Create the signer of the config. Keystores are decrypted and remote signers are asked
+    /// for their public key, so a misconfigured signer is noticed before the first
+    /// transaction.
+    pub async fn new(config: &SignerConfig) -> Result<Self> {
+        let signer = match config {
+            SignerConfig::PrivateKey { env } => {
+                let private_key = env::var(env).with_context(|| format!("{env} is not set"))?;
+                RaikoSigner::Local(
+                    private_key
+                        .parse()
+                        .map_err(|e| anyhow!("Invalid private key in {env}: {e}"))?,
+                )
+            }
+            SignerConfig::Keystore { path, password_env } => {
+                let password =
+                    env::var(password_env).with_context(|| format!("{password_env} is not set"))?;
+                RaikoSigner::Local(
+                    LocalWallet::decrypt_keystore(path, password)
+                        .with_context(|| format!("Could not decrypt {}", path.display()))?,
+                )
+            }
+            SignerConfig::Web3Signer { url, address } => {
+                RaikoSigner::Web3Signer(Web3Signer::connect(url, *address).await?)
+            }
+            #[cfg(feature = "aws")]
+            SignerConfig::AwsKms { key_id } => {
+                let config = aws_config::load_from_env().await;
+                let client = aws_sdk_kms::Client::new(&config);
+                RaikoSigner::AwsKms(
+                    alloy_signer_aws::AwsSigner::new(client, key_id.clone(), None)
+                        .await
+                        .with_context(|| format!("Could not load the KMS key {key_id}"))?,
+                )
+            }
+            #[cfg(not(feature = "aws"))]
+            SignerConfig::AwsKms { .. } => {
+                anyhow::bail!("raiko was built without AWS KMS support, enable the aws feature")
+            }
+        };
+        info!("Signing transactions with {signer}");
+        Ok(signer)
+    }
+
+    /// The address of the account signing the transactions.
+    pub fn address(&self) -> Address {
+        match self {
+            RaikoSigner::Local(wallet) => wallet.address(),
+            RaikoSigner::Web3Signer(signer) => signer.address(),
+            #[cfg(feature = "aws")]
+            RaikoSigner::AwsKms(signer) => signer.address(),
+        }
+    }
+
+    /// A signer for the transactions of a provider on the chain.
+    pub fn ethereum_signer(&self, chain_id: ChainId) -> EthereumSigner {
+        match self.clone() {
+            RaikoSigner::Local(mut wallet) => {
+                wallet.set_chain_id(Some(chain_id));
+                EthereumSigner::from(wallet)
+            }
+            RaikoSigner::Web3Signer(signer) => EthereumSigner::from(signer.with_chain_id(chain_id)),
+            #[cfg(feature = "aws")]
+            RaikoSigner::AwsKms(mut signer) => {
+                signer.set_chain_id(Some(chain_id));
+                EthereumSigner::from(signer)
+            }
+        }
+    }
+}
+
+impl fmt::Display for RaikoSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            RaikoSigner::Local(_) => "local key",
+            RaikoSigner::Web3Signer(_) => "web3signer",
+            #[cfg(feature = "aws")]
+            RaikoSigner::AwsKms(_) => "AWS KMS key",
+        };
+        write!(f, "{kind} {}", self.address())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_signer_config() {
+        let config: SignerConfig =
+            r#"{"type": "keystore", "path": "/etc/raiko/key.json", "password_env": "PASSWORD"}"#
+                .parse()
+                .unwrap();
+        assert_eq!(
+            config,
+            SignerConfig::Keystore {
+                path: PathBuf::from("/etc/raiko/key.json"),
+                password_env: "PASSWORD".to_string(),
+            }
+        );
+        assert!(r#"{"type": "hsm"}"#.parse::<SignerConfig>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_private_key_signer() {
+        env::set_var(
+            "RAIKO_SIGNER_TEST_KEY",
+            "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80",
+        );
+        let signer = RaikoSigner::new(&SignerConfig::private_key("RAIKO_SIGNER_TEST_KEY"))
+            .await
+            .unwrap();
+        assert_eq!(
+            signer.address(),
+            "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"
+                .parse::<Address>()
+                .unwrap()
+        );
+        assert!(
+            RaikoSigner::new(&SignerConfig::private_key("RAIKO_SIGNER_UNSET"))
+                .await
+                .is_err()
+        );
+    }
+}
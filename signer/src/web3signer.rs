@@ -0,0 +1,123 @@
+use alloy_consensus::SignableTransaction;
+use alloy_network::TxSigner;
+use alloy_primitives::{hex, Address, ChainId, Signature};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde_json::json;
+
+/// Signs transactions with a key held by a web3signer, using its eth1 signing API.
+#[derive(Clone, Debug)]
+pub struct Web3Signer {
+    client: reqwest::Client,
+    url: String,
+    address: Address,
+    chain_id: Option<ChainId>,
+}
+
+impl Web3Signer {
+    /// Connect to the web3signer at `url` and check that it holds the key of `address`.
+    pub async fn connect(url: &str, address: Address) -> Result<Self> {
+        let url = url.trim_end_matches('/').to_string();
+        let client = reqwest::Client::new();
+        let keys: Vec<String> = client
+            .get(format!("{url}/api/v1/eth1/publicKeys"))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Could not reach the web3signer at {url}"))?
+            .json()
+            .await?;
+        let holds_key = keys.iter().any(|key| {
+            hex::decode(key)
+                .ok()
+                .and_then(|key| public_key_address(&key))
+                == Some(address)
+        });
+        if !holds_key {
+            bail!("The web3signer at {url} has no key for {address}");
+        }
+        Ok(Self {
+            client,
+            url,
+            address,
+            chain_id: None,
+        })
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Set the chain id of the transactions.
+    pub fn with_chain_id(mut self, chain_id: ChainId) -> Self {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    /// Sign the keccak hash of `data`.
+    async fn sign(&self, data: &[u8]) -> Result<Signature> {
+        let signature = self
+            .client
+            .post(format!("{}/api/v1/eth1/sign/{}", self.url, self.address))
+            .json(&json!({ "data": hex::encode_prefixed(data) }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .context("web3signer did not sign")?
+            .text()
+            .await?;
+        let signature = hex::decode(signature.trim())?;
+        Ok(Signature::try_from(signature.as_slice())?)
+    }
+}
+
+/// The address of an uncompressed secp256k1 public key, with or without the `0x04` prefix.
+fn public_key_address(key: &[u8]) -> Option<Address> {
+    let key = match key.len() {
+        65 if key[0] == 4 => &key[1..],
+        64 => key,
+        _ => return None,
+    };
+    Some(Address::from_raw_public_key(key))
+}
+
+#[async_trait]
+impl TxSigner<Signature> for Web3Signer {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> alloy_signer::Result<Signature> {
+        if let Some(chain_id) = self.chain_id {
+            tx.set_chain_id(chain_id);
+        }
+        let mut payload = Vec::new();
+        tx.encode_for_signing(&mut payload);
+        self.sign(&payload)
+            .await
+            .map_err(|e| alloy_signer::Error::other(e.into_boxed_dyn_error()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_key_address() {
+        // The public key of the first anvil account
+        let key = hex::decode(
+            "048318535b54105d4a7aae60c08fc45f9687181b4fdfc625bd1a753fa7397fed753547f11ca8696646f2f3acb08e31016afac23e630c5d11f59f61fef57b0d2aa5",
+        )
+        .unwrap();
+        let address: Address = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"
+            .parse()
+            .unwrap();
+        assert_eq!(public_key_address(&key), Some(address));
+        assert_eq!(public_key_address(&key[1..]), Some(address));
+        assert_eq!(public_key_address(&key[..33]), None);
+    }
+}